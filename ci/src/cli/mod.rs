@@ -3,18 +3,42 @@ use clap_complete::{
     generate,
     shells::{Bash, Fish, Zsh},
 };
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 use codeinput::core::{
     commands::{
         self,
-        infer_owners::{InferAlgorithm, InferScope},
+        infer_owners::{
+            InferAggregation, InferAlgorithm, InferExclusions, InferOptions, InferScope,
+            InferWeights,
+        },
+        list_files::GroupBy,
+        verify_owners::Provider,
     },
-    types::{CacheEncoding, OutputFormat},
+    completion, discovery,
+    expiry::DEFAULT_WARNING_DAYS,
+    export::ExportFormat,
+    gate::GateCheck,
+    github::GitHubRepo,
+    gitlab::GitLabProject,
+    heatmap::HeatmapFormat,
+    matcher::MatcherStrategy,
+    org::ReportFormat,
+    registry,
+    reviews::ReviewSource,
+    rule_filter::{self, RuleFilter},
+    types::{CacheEncoding, Dialect, OutputFormat},
+    watch::WatchBackendKind,
 };
 use codeinput::utils::app_config::AppConfig;
-use codeinput::utils::error::Result;
+use codeinput::utils::error::{Error, Result};
+use codeinput::utils::interrupt;
+use codeinput::utils::parallelism;
+use codeinput::utils::telemetry;
 use codeinput::utils::types::LogLevel;
+use codeinput::utils::{output, summary, warnings};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -45,6 +69,80 @@ pub struct Cli {
     )]
     pub log_level: Option<LogLevel>,
 
+    /// Read CODEOWNERS as this hosting platform does: github or bitbucket
+    #[arg(
+        name = "dialect",
+        long = "dialect",
+        value_name = "DIALECT",
+        value_parser = parse_dialect,
+        global = true
+    )]
+    pub dialect: Option<Dialect>,
+
+    /// Layer the files changed since this cache was built, such as one built on the default
+    /// branch, over it instead of using the local cache; relative to the repository
+    #[arg(
+        name = "base_cache",
+        long = "base-cache",
+        value_name = "FILE",
+        global = true
+    )]
+    pub base_cache: Option<PathBuf>,
+
+    /// Number of worker threads (default: one per CPU)
+    #[arg(
+        name = "jobs",
+        short = 'j',
+        long = "jobs",
+        value_name = "N",
+        global = true
+    )]
+    pub jobs: Option<usize>,
+
+    /// Run at the lowest CPU and IO priority
+    #[arg(name = "low_priority", long = "low-priority", global = true)]
+    pub low_priority: bool,
+
+    /// Do not leave out files ignored by the global gitignore (git's core.excludesFile)
+    #[arg(
+        name = "no_global_gitignore",
+        long = "no-global-gitignore",
+        global = true
+    )]
+    pub no_global_gitignore: bool,
+
+    /// Do not leave out files ignored by .git/info/exclude
+    #[arg(name = "no_git_exclude", long = "no-git-exclude", global = true)]
+    pub no_git_exclude: bool,
+
+    /// Print a key=value summary line (file counts, duration) to stderr after the command
+    #[arg(long, global = true)]
+    pub summary: bool,
+
+    /// Fail when the command logs more than this many warnings, such as skipped patterns,
+    /// unreadable files or inline markers without owners
+    #[arg(long, value_name = "N", global = true)]
+    pub max_warnings: Option<usize>,
+
+    /// Stop cache builds, inference and history walks after this many seconds; inference
+    /// reports the files analyzed so far, then the command fails
+    #[arg(long, value_name = "SECONDS", global = true)]
+    pub timeout: Option<u64>,
+
+    /// Resolve ownership as if rules with this tag did not exist, e.g. #deprecated
+    /// (repeatable or comma-separated)
+    #[arg(long, value_name = "TAG", value_delimiter = ',', global = true)]
+    pub ignore_rules_tagged: Vec<String>,
+
+    /// Resolve ownership with only the rules with this tag, e.g. #prod (repeatable or
+    /// comma-separated)
+    #[arg(long, value_name = "TAG", value_delimiter = ',', global = true)]
+    pub only_rules_tagged: Vec<String>,
+
+    /// Reproducible output for golden tests: fixed timestamps, sorted order, no progress
+    #[arg(long = "stable-output", global = true, hide = true)]
+    pub stable_output: bool,
+
     /// Subcommands
     #[clap(subcommand)]
     command: Commands,
@@ -58,8 +156,14 @@ enum Commands {
         long_about = "Tools for parsing, validating and querying CODEOWNERS files"
     )]
     Codeowners {
+        /// Repository to work on (default: the root of the git repository holding the
+        /// current directory, or else the current directory)
+        #[arg(short, long, global = true, value_name = "PATH")]
+        repo: Option<PathBuf>,
+
+        // Boxed, as the codeowners subcommands carry far more arguments than the others
         #[clap(subcommand)]
-        subcommand: CodeownersSubcommand,
+        subcommand: Box<CodeownersSubcommand>,
     },
     #[clap(
         name = "completion",
@@ -70,12 +174,127 @@ enum Commands {
         #[clap(subcommand)]
         subcommand: CompletionSubcommand,
     },
+    #[clap(
+        name = "complete",
+        about = "Complete an option value, for shell completion scripts",
+        long_about = "Print the owners, tags or paths from the cache that complete the word at POSITION of the command line, one per line. Called by the scripts of `completion`",
+        hide = true
+    )]
+    Complete {
+        /// Index in ARGS of the word being completed; the program name is 0
+        #[arg(long, value_name = "N")]
+        position: usize,
+
+        /// The command line as split by the shell
+        #[arg(last = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    #[clap(
+        name = "doctor",
+        about = "Diagnose common setup problems",
+        long_about = "Check git, repository state, configuration, CODEOWNERS patterns and the cache, and suggest fixes"
+    )]
+    Doctor {
+        /// Directory path to check (default: current directory)
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+    },
     #[clap(
         name = "config",
-        about = "Show Configuration",
+        about = "Show or change configuration",
+        long_about = None,
+    )]
+    Config {
+        #[clap(subcommand)]
+        subcommand: Option<ConfigSubcommand>,
+    },
+    #[clap(
+        name = "org",
+        about = "Aggregate ownership across repositories",
+        long_about = None,
+    )]
+    Org {
+        #[clap(subcommand)]
+        subcommand: OrgSubcommand,
+    },
+    #[clap(
+        name = "repos",
+        about = "List or refresh the repositories whose cache was built on this machine",
         long_about = None,
     )]
-    Config,
+    Repos {
+        #[clap(subcommand)]
+        subcommand: Option<ReposSubcommand>,
+    },
+    #[clap(
+        name = "demo",
+        about = "Generate a demo repository to try every feature on",
+        long_about = "Create a synthetic repository with nested CODEOWNERS files, inline markers, unowned areas and git history, for demos and reproducible bug reports"
+    )]
+    Demo {
+        /// Directory to create the demo repository in; must not exist or be empty
+        #[arg(long, value_name = "PATH")]
+        generate: PathBuf,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum ConfigSubcommand {
+    #[clap(about = "Show the current configuration (default)")]
+    Show,
+    #[clap(about = "Save a setting to the user config file")]
+    Set {
+        /// Setting name, e.g. telemetry, matcher or jobs
+        key: String,
+
+        /// New value
+        value: String,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum OrgSubcommand {
+    #[clap(
+        about = "Roll up the caches of the repositories in a workspace",
+        long_about = "Load the ownership caches of the repositories listed in a workspace file and report totals, owners spanning repositories and the repositories of each team. Caches are not rebuilt; repositories without a readable cache are listed as skipped."
+    )]
+    Report {
+        /// Workspace file listing the repositories ([[repo]] entries with a path)
+        /// (default: the repositories in the registry, see `ci repos`)
+        #[arg(long, value_name = "FILE")]
+        workspace: Option<PathBuf>,
+
+        /// Output format: text|json|html
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_report_format)]
+        format: ReportFormat,
+
+        /// Write the report to a file instead of standard output
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+enum ReposSubcommand {
+    #[clap(about = "List the repositories in the registry (default)")]
+    List {
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        about = "Bring the cache of every repository in the registry up to date",
+        long_about = "Build, update or rebuild the cache of every repository in the registry, as any command reading it would. Repositories whose directory is gone are skipped."
+    )]
+    Refresh {
+        /// Forget the repositories whose directory is gone
+        #[arg(long)]
+        prune: bool,
+    },
 }
 
 #[derive(Subcommand, PartialEq, Debug)]
@@ -95,17 +314,71 @@ pub(crate) enum CodeownersSubcommand {
         about = "Preprocess CODEOWNERS files and build ownership map"
     )]
     Parse {
-        /// Directory path to analyze (default: current directory)
-        #[arg(default_value = ".")]
-        path: PathBuf,
+        /// Directory path to analyze (default: --repo or the current git root)
+        path: Option<PathBuf>,
 
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
 
-        /// Output format: json|bincode
+        /// Output format: json|bincode, or sqlite and archive in builds with those features
         #[arg(long, value_name = "FORMAT", default_value = "bincode", value_parser = parse_cache_encoding)]
         format: CacheEncoding,
+
+        /// Matcher strategy: auto|override|gitignore|regex-set|globset (default: from config)
+        #[arg(long, value_name = "MATCHER", value_parser = parse_matcher_strategy)]
+        matcher: Option<MatcherStrategy>,
+
+        /// Print a summary of the CODEOWNERS files, skipped patterns and inline markers found: text|json|bincode
+        #[arg(long, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "text", value_parser = parse_output_format)]
+        report: Option<OutputFormat>,
+
+        /// Upload the cache built to a remote cache, an http(s):// URL or s3://bucket/prefix,
+        /// named after the repository hash
+        #[arg(long, value_name = "URL")]
+        push_cache: Option<String>,
+    },
+
+    #[clap(
+        name = "calibrate",
+        about = "Measure matcher performance and store the profile used by --matcher auto"
+    )]
+    Calibrate {
+        /// Directory path to calibrate against (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Maximum number of files to time matching against
+        #[arg(long, value_name = "COUNT", default_value = "2000")]
+        sample: usize,
+    },
+
+    #[clap(
+        name = "watch",
+        about = "Keep the ownership cache up to date as files change",
+        long_about = "Build the cache, then rebuild it whenever files or CODEOWNERS files change until Ctrl-C. Changes are gathered into one rebuild once the repository has been quiet for `watch_debounce_ms`; large bursts such as branch checkouts trigger a single full rescan."
+    )]
+    Watch {
+        /// Directory path to watch (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Watch backend: auto|native|poll (default: from config)
+        #[arg(long, value_name = "BACKEND", value_parser = parse_watch_backend)]
+        backend: Option<WatchBackendKind>,
+    },
+
+    #[cfg(feature = "serve")]
+    #[clap(
+        about = "Answer ownership queries over HTTP",
+        long_about = "Serve the ownership cache as a JSON API until Ctrl-C: `GET /files/{path}/owners`, `GET /owners`, `GET /tags` and `POST /changed-files` with `{\"files\": [...]}`. The cache is built or updated at start, then read again whenever its file changes; keep it fresh with `ci codeowners watch`."
+    )]
+    Serve {
+        /// Address to listen on
+        #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:3000")]
+        bind: String,
+
+        /// Custom cache file location (default: from config)
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
     },
 
     #[clap(
@@ -113,10 +386,13 @@ pub(crate) enum CodeownersSubcommand {
         about = "Find and list files with their owners based on filter criteria"
     )]
     ListFiles {
-        /// Directory path to analyze (default: current directory)
-        #[arg(default_value = ".")]
+        /// Directory path to analyze (default: --repo or the current git root)
         path: Option<PathBuf>,
 
+        /// Only show files under this directory, relative to the current directory
+        #[arg(long = "path", visible_alias = "scope", value_name = "DIR")]
+        path_prefix: Option<PathBuf>,
+
         /// Only show files with specified tags
         #[arg(long, value_name = "LIST")]
         tags: Option<String>,
@@ -140,6 +416,26 @@ pub(crate) enum CodeownersSubcommand {
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
+
+        /// List the members of team owners, from the team mapping (see the team_map setting)
+        #[arg(long)]
+        expand: bool,
+
+        /// Only show the paths listed one per line in FILE, or stdin with `-`
+        #[arg(long, value_name = "FILE")]
+        paths_from: Option<PathBuf>,
+
+        /// Resolve only the files under --path (or from --paths-from) on the fly, without the cache
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Leave out files with a generated-code marker (see the generated_markers setting)
+        #[arg(long)]
+        exclude_generated: bool,
+
+        /// List the files under each owner, tag or directory: owner|tag|dir
+        #[arg(long, value_name = "GROUP", value_parser = parse_group_by)]
+        group_by: Option<GroupBy>,
     },
 
     #[clap(
@@ -147,8 +443,7 @@ pub(crate) enum CodeownersSubcommand {
         about = "Display aggregated owner statistics and associations"
     )]
     ListOwners {
-        /// Directory path to analyze (default: current directory)
-        #[arg(default_value = ".")]
+        /// Directory path to analyze (default: --repo or the current git root)
         path: Option<PathBuf>,
 
         /// Output format: text|json|bincode
@@ -158,14 +453,27 @@ pub(crate) enum CodeownersSubcommand {
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
+
+        /// Aggregate individual owners into their teams, from the team mapping
+        #[arg(long, conflicts_with = "by_type")]
+        rollup: bool,
+
+        /// Count owners and files per owner type, and flag email addresses that probably
+        /// belong to a listed handle
+        #[arg(long)]
+        by_type: bool,
+
+        /// Only analyze this subtree, relative to the current directory; rules
+        /// inherited from CODEOWNERS files above it still apply
+        #[arg(long, value_name = "DIR")]
+        scope: Option<PathBuf>,
     },
     #[clap(
         name = "list-tags",
         about = "Audit and analyze tag usage across CODEOWNERS files"
     )]
     ListTags {
-        /// Directory path to analyze (default: current directory)
-        #[arg(default_value = ".")]
+        /// Directory path to analyze (default: --repo or the current git root)
         path: Option<PathBuf>,
 
         /// Output format: text|json|bincode
@@ -175,6 +483,15 @@ pub(crate) enum CodeownersSubcommand {
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
+
+        /// Report tags that appear together and the owner most associated with each tag
+        #[arg(long)]
+        co_occurrence: bool,
+
+        /// Only analyze this subtree, relative to the current directory; rules
+        /// inherited from CODEOWNERS files above it still apply
+        #[arg(long, value_name = "DIR")]
+        scope: Option<PathBuf>,
     },
     #[clap(
         name = "list-rules",
@@ -185,9 +502,23 @@ pub(crate) enum CodeownersSubcommand {
         #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
         format: OutputFormat,
 
+        /// Include the files each rule decides ownership for
+        #[arg(long)]
+        with_matches: bool,
+
+        /// Summarize rules per CODEOWNERS file: rule count, matched files, dead rules and
+        /// last commit
+        #[arg(long, conflicts_with = "with_matches")]
+        by_source: bool,
+
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
+
+        /// Only analyze this subtree, relative to the current directory; rules
+        /// inherited from CODEOWNERS files above it still apply
+        #[arg(long, value_name = "DIR")]
+        scope: Option<PathBuf>,
     },
     #[clap(
         name = "inspect",
@@ -198,9 +529,56 @@ pub(crate) enum CodeownersSubcommand {
         #[arg(value_name = "FILE")]
         file_path: PathBuf,
 
-        /// Directory path to analyze (default: current directory)
-        #[arg(short, long, default_value = ".")]
-        repo: Option<PathBuf>,
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
+        cache_file: Option<PathBuf>,
+
+        /// Show every matching rule with its owners and tags as written, not only the one that applies
+        #[arg(long)]
+        raw: bool,
+
+        /// Explain the match: case sensitivity, normalized patterns and rule precedence
+        #[arg(long)]
+        explain: bool,
+
+        /// List the members of team owners, from the team mapping (see the team_map setting)
+        #[arg(long)]
+        expand: bool,
+
+        /// Resolve the file on the fly without reading or building the cache
+        #[arg(long)]
+        no_cache: bool,
+    },
+    #[clap(
+        name = "statusline",
+        about = "Print a file's owners, tags and rule on one line for editor status lines",
+        long_about = "Print `@owner1,@owner2 · #tag · rule CODEOWNERS:42` for FILE, read from the existing cache without checking it is up to date. Keep the cache fresh with `ci codeowners watch`. Fails if the cache cannot be loaded within the latency budget."
+    )]
+    Statusline {
+        /// File to describe
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+
+        /// Custom cache file location (default: from config)
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Give up if the cache is not loaded within this many milliseconds
+        #[arg(long, value_name = "MS", default_value = "100")]
+        budget_ms: u64,
+    },
+    #[clap(
+        name = "ls",
+        about = "List a directory's children with their owners, tags and coverage"
+    )]
+    Ls {
+        /// Directory to list, relative to the current directory
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
 
         /// Output format: text|json|bincode
         #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
@@ -212,11 +590,11 @@ pub(crate) enum CodeownersSubcommand {
     },
     #[clap(
         name = "infer-owners",
-        about = "Infer file ownership from git history and blame information"
+        about = "Infer file ownership from git history and blame information",
+        long_about = "Suggest owners for files from their git history and blame information. Files are analyzed in parallel, on as many worker threads as --jobs allows, with progress shown on stderr when it is a terminal. With --reviews, the reviewers of the merged pull requests touching each file are read with the GitHub API, authenticating with GITHUB_TOKEN, GH_TOKEN or the github_token setting, and blended in by --review-weight. The weighted algorithm scores each author by their shares of the blamed lines, of the commits touching the file and of recent activity, combined by --weight-lines, --weight-commits and --weight-recency."
     )]
     InferOwners {
-        /// Directory path to analyze (default: current directory)
-        #[arg(default_value = ".")]
+        /// Directory path to analyze (default: --repo or the current git root)
         path: Option<PathBuf>,
 
         /// Scope of analysis: all files or only unowned files
@@ -227,11 +605,27 @@ pub(crate) enum CodeownersSubcommand {
         #[arg(long, value_name = "ALGORITHM", default_value = "lines", value_parser = parse_infer_algorithm)]
         algorithm: InferAlgorithm,
 
+        /// Weight of the share of blamed lines with --algorithm weighted (default: the
+        /// infer_weight_lines setting)
+        #[arg(long, value_name = "WEIGHT")]
+        weight_lines: Option<f64>,
+
+        /// Weight of the share of commits with --algorithm weighted (default: the
+        /// infer_weight_commits setting)
+        #[arg(long, value_name = "WEIGHT")]
+        weight_commits: Option<f64>,
+
+        /// Weight of the recency of the last change with --algorithm weighted (default:
+        /// the infer_weight_recency setting)
+        #[arg(long, value_name = "WEIGHT")]
+        weight_recency: Option<f64>,
+
         /// Only consider commits from last N days
         #[arg(long, value_name = "DAYS", default_value = "365")]
         lookback_days: u32,
 
-        /// Minimum commits required to be considered owner
+        /// Minimum commits, and pull requests reviewed with --reviews, required to be
+        /// considered owner
         #[arg(long, value_name = "COUNT", default_value = "3")]
         min_commits: u32,
 
@@ -239,119 +633,1392 @@ pub(crate) enum CodeownersSubcommand {
         #[arg(long, value_name = "PERCENT", default_value = "20")]
         min_percentage: u32,
 
+        /// Leave out commits by authors whose email matches this glob, e.g. a contractor
+        /// or *@vendor.com (repeatable)
+        #[arg(long, visible_alias = "exclude-authors", value_name = "GLOB")]
+        exclude_author: Vec<String>,
+
+        /// Keep commits by the bots of the bot_authors setting, such as dependabot[bot]
+        #[arg(long)]
+        include_bots: bool,
+
+        /// Skip files matching this glob, relative to the repository, e.g. vendor/**
+        /// (repeatable)
+        #[arg(long, value_name = "GLOB")]
+        exclude_path: Vec<String>,
+
+        /// TOML file aliasing author emails to @handles or @org/teams (default: the
+        /// owner_aliases setting)
+        #[arg(long, value_name = "FILE")]
+        alias_file: Option<PathBuf>,
+
+        /// Blend in who reviewed the merged pull requests touching each file, read with the
+        /// GitHub API
+        #[arg(long)]
+        reviews: bool,
+
+        /// Repository on GitHub as OWNER/NAME (default: from the origin remote)
+        #[arg(long, value_name = "OWNER/NAME", value_parser = parse_github_repo, requires = "reviews")]
+        github_repo: Option<GitHubRepo>,
+
+        /// Weight of the review signal, from 0 to 1; blame or commits get the rest
+        #[arg(
+            long,
+            value_name = "WEIGHT",
+            default_value = "0.5",
+            requires = "reviews"
+        )]
+        review_weight: f64,
+
+        /// Most merged pull requests to read reviews of, the most recently updated first
+        #[arg(
+            long,
+            value_name = "COUNT",
+            default_value = "200",
+            requires = "reviews"
+        )]
+        max_pulls: usize,
+
+        /// Answer from GitHub responses cached by earlier runs, without network access
+        #[arg(long, requires = "reviews")]
+        offline: bool,
+
+        /// Suggest a team instead of individuals where enough of a file's inferred owners
+        /// are its members
+        #[arg(long)]
+        suggest_teams: bool,
+
+        /// TOML file listing the members of each team (default: the team_map setting)
+        #[arg(long, value_name = "FILE", requires = "suggest_teams")]
+        team_map: Option<PathBuf>,
+
+        /// Percentage of a file's inferred owners that must be members of one team for it
+        /// to be suggested
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            default_value = "50",
+            requires = "suggest_teams"
+        )]
+        team_threshold: u32,
+
         /// Custom cache file location
         #[arg(long, value_name = "FILE", default_value = ".codeowners.cache")]
         cache_file: Option<PathBuf>,
 
-        /// Output file to write CODEOWNERS entries
+        /// CODEOWNERS file to merge the inferred rules into; files it already covers are
+        /// skipped
         #[arg(long, short = 'o', value_name = "FILE")]
         output: Option<PathBuf>,
+
+        /// Print the changes to the --output file as a diff instead of writing them
+        #[arg(long, requires = "output")]
+        dry_run: bool,
+
+        /// Rules written to the --output file: one per file, or one per directory where
+        /// enough of the files share an owner (file, directory)
+        #[arg(long, value_name = "LEVEL", default_value = "file", value_parser = parse_infer_aggregation, requires = "output")]
+        aggregate: InferAggregation,
+
+        /// Percentage of the files of a directory one owner needs for --aggregate directory
+        #[arg(long, value_name = "PERCENT", default_value = "80", value_parser = clap::value_parser!(u32).range(1..=100))]
+        aggregate_threshold: u32,
     },
-}
+    #[clap(
+        name = "equivalent",
+        about = "Check that two CODEOWNERS configurations resolve every file the same way",
+        long_about = "Resolve every file in the repository against two CODEOWNERS configurations and list the files whose owners or tags differ. Each configuration is a CODEOWNERS file or a directory of CODEOWNERS files laid out like the repository. Fails when any file differs."
+    )]
+    Equivalent {
+        /// First configuration: a CODEOWNERS file or a directory of them
+        #[arg(long = "a", value_name = "PATH")]
+        a: PathBuf,
 
-pub fn cli_match() -> Result<()> {
-    // Parse the command line arguments
-    let cli = Cli::parse();
+        /// Second configuration: a CODEOWNERS file or a directory of them
+        #[arg(long = "b", value_name = "PATH")]
+        b: PathBuf,
 
-    // Merge clap config file if the value is set
-    AppConfig::merge_config(cli.config.as_deref())?;
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "compare-github",
+        about = "Compare local owner resolution with GitHub's for a sample of files",
+        long_about = "Fetch the CODEOWNERS file GitHub uses at a ref and the lines GitHub rejects in it, resolve a sample of files the way GitHub does (that file alone, from the repository root, without the rejected lines) and list the files whose owners differ from the local cache. Authenticates with GITHUB_TOKEN or GH_TOKEN; the API URL is the github_api_url setting. Fails when any sampled file differs."
+    )]
+    CompareGithub {
+        /// Directory path of a checkout of the ref (default: --repo or the current git root)
+        path: Option<PathBuf>,
 
-    let app = Cli::command();
-    let matches = app.get_matches();
+        /// Branch, tag or commit to compare against
+        #[arg(long = "ref", value_name = "REF")]
+        git_ref: String,
 
-    AppConfig::merge_args(matches)?;
+        /// Repository on GitHub as OWNER/NAME (default: from the origin remote)
+        #[arg(long, value_name = "OWNER/NAME", value_parser = parse_github_repo)]
+        github_repo: Option<GitHubRepo>,
 
-    // Execute the subcommand
-    match &cli.command {
-        Commands::Codeowners { subcommand } => codeowners(subcommand)?,
-        Commands::Completion { subcommand } => {
-            let mut app = Cli::command();
-            match subcommand {
-                CompletionSubcommand::Bash => {
-                    generate(Bash, &mut app, "codeinput", &mut std::io::stdout());
-                }
-                CompletionSubcommand::Zsh => {
-                    generate(Zsh, &mut app, "codeinput", &mut std::io::stdout());
-                }
-                CompletionSubcommand::Fish => {
-                    generate(Fish, &mut app, "codeinput", &mut std::io::stdout());
-                }
-            }
-        }
-        Commands::Config => commands::config::run()?,
-    }
+        /// Files to compare, picked evenly across the repository; 0 compares every file
+        #[arg(long, value_name = "COUNT", default_value = "200")]
+        sample: usize,
 
-    Ok(())
-}
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
 
-/// Handle codeowners subcommands
-pub(crate) fn codeowners(subcommand: &CodeownersSubcommand) -> Result<()> {
-    match subcommand {
-        CodeownersSubcommand::Parse {
-            path,
-            cache_file,
-            format,
-        } => commands::parse::run(path, cache_file.as_deref(), *format),
-        CodeownersSubcommand::ListFiles {
-            path,
-            tags,
-            owners,
-            unowned,
-            show_all,
-            format,
-            cache_file,
-        } => commands::list_files::run(
-            path.as_deref(),
-            tags.as_deref(),
-            owners.as_deref(),
-            *unowned,
-            *show_all,
-            format,
-            cache_file.as_deref(),
-        ),
-        CodeownersSubcommand::ListOwners {
-            path,
-            format,
-            cache_file,
-        } => commands::list_owners::run(path.as_deref(), format, cache_file.as_deref()),
-        CodeownersSubcommand::ListTags {
-            path,
-            format,
-            cache_file,
-        } => commands::list_tags::run(path.as_deref(), format, cache_file.as_deref()),
-        CodeownersSubcommand::ListRules { format, cache_file } => {
-            commands::list_rules::run(format, cache_file.as_deref())
-        }
-        CodeownersSubcommand::Inspect {
-            file_path,
-            repo,
-            format,
-            cache_file,
-        } => commands::inspect::run(file_path, repo.as_deref(), format, cache_file.as_deref()),
-        CodeownersSubcommand::InferOwners {
-            path,
-            scope,
-            algorithm,
-            lookback_days,
-            min_commits,
-            min_percentage,
-            cache_file,
-            output,
-        } => commands::infer_owners::run(
-            path.as_deref(),
-            scope,
-            algorithm,
-            *lookback_days,
-            *min_commits,
-            *min_percentage,
-            cache_file.as_deref(),
-            output.as_deref(),
-        ),
-    }
-}
+        /// Answer from GitHub responses cached by earlier runs, without network access
+        #[arg(long)]
+        offline: bool,
 
-fn parse_output_format(s: &str) -> std::result::Result<OutputFormat, String> {
-    match s.to_lowercase().as_str() {
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "verify-owners",
+        about = "Check that every owner exists on GitHub or GitLab",
+        long_about = "Look up every user and team owning files in the cache with the GitHub API and list those that do not exist, are suspended, are organizations rather than users, or are teams without access to the repository; GitHub requests no review from them. Email owners are skipped. Authenticates with GITHUB_TOKEN, GH_TOKEN or the github_token setting; teams are only visible to a token with the read:org scope. With --provider gitlab, users and groups are looked up with the GitLab API at the gitlab_api_url setting instead, authenticating with GITLAB_TOKEN or the gitlab_token setting. Fails when any owner is not valid."
+    )]
+    VerifyOwners {
+        /// Directory path to analyze (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Code host to verify owners against: github|gitlab
+        #[arg(long, value_name = "PROVIDER", default_value = "github", value_parser = parse_provider)]
+        provider: Provider,
+
+        /// Repository on GitHub as OWNER/NAME (default: from the origin remote)
+        #[arg(long, value_name = "OWNER/NAME", value_parser = parse_github_repo)]
+        github_repo: Option<GitHubRepo>,
+
+        /// Project on GitLab as GROUP/PROJECT (default: from the origin remote)
+        #[arg(long, value_name = "GROUP/PROJECT", value_parser = parse_gitlab_project)]
+        gitlab_project: Option<GitLabProject>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// List the members of every valid team or group
+        #[arg(long)]
+        expand_groups: bool,
+
+        /// Answer from API responses cached by earlier runs, without network access
+        #[arg(long)]
+        offline: bool,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "validate",
+        about = "Lint CODEOWNERS files",
+        long_about = "Read every CODEOWNERS file and report unreadable files, lines that do not parse as intended, invalid patterns, rules without owners, malformed owners, broken DELEGATE lines and rules past their expires:YYYY-MM-DD date, each as file:line: message. Rules about to expire are reported as warnings. Fails when any problem is found."
+    )]
+    Validate {
+        /// Directory path to validate (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Warn about rules expiring within this many days
+        #[arg(long, value_name = "DAYS", default_value_t = DEFAULT_WARNING_DAYS)]
+        warn_days: u32,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "coverage",
+        about = "Show the share of owned files per top-level directory",
+        long_about = "Report how many files have an owner, overall and for each top-level directory, leaving generated files out. With --min-coverage, fails when overall coverage is below the threshold, for tracking ownership debt in CI."
+    )]
+    Coverage {
+        /// Directory path to analyze (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Fail when less than this percentage of files is owned
+        #[arg(long, value_name = "PERCENT", value_parser = parse_min_coverage)]
+        min_coverage: Option<f64>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Only report this subtree, relative to the current directory; rules
+        /// inherited from CODEOWNERS files above it still apply
+        #[arg(long, value_name = "DIR")]
+        scope: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "gate",
+        about = "Run several ownership checks for CI with one exit code",
+        long_about = "Run the given checks over a single load of the cache and print a pass/fail table. Checks: validate (rules have valid patterns and well-formed owners and have not expired), coverage:N (at least N% of files owned), unowned:N (at most N unowned files) and policy (no file breaks the policy file). Fails when any check fails."
+    )]
+    Gate {
+        /// Directory path to check (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Comma-separated checks, e.g. validate,coverage:90,unowned:0,policy
+        #[arg(long, value_name = "LIST", value_delimiter = ',', required = true, value_parser = parse_gate_check)]
+        checks: Vec<GateCheck>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Only check this subtree, relative to the current directory; rules
+        /// inherited from CODEOWNERS files above it still apply
+        #[arg(long, value_name = "DIR")]
+        scope: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "simulate-membership",
+        about = "Show who gains or loses review responsibility in a team reorganization",
+        long_about = "Apply the membership changes in a TOML file, with [add] and [remove] tables of team handle to members, to the team mapping (see the team_map setting) and list the files each member named would gain or lose review responsibility over."
+    )]
+    SimulateMembership {
+        /// TOML file of membership changes
+        changes: PathBuf,
+
+        /// Directory path to analyze (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "impact",
+        about = "Report what would become unowned if an owner were removed",
+        long_about = "List the rules naming the owner and the files that would lose their only owner if the owner were removed, e.g. before offboarding someone. Replacement owners are suggested for each such file from its blame history."
+    )]
+    Impact {
+        /// Owner to remove, e.g. @alice, @org/team or alice@example.com
+        owner: String,
+
+        /// Directory path to analyze (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Skip suggesting replacement owners from git history
+        #[arg(long)]
+        no_suggest: bool,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "diff",
+        about = "List the rules and files whose ownership differs between two git refs",
+        long_about = "Resolve every file against the CODEOWNERS files as committed at two git refs, such as main and a feature branch, and list the rules added, removed or given other owners, and the files whose owners or tags changed, were added or were removed. Both refs are read from git, so neither has to be checked out. Generated files are not flagged."
+    )]
+    Diff {
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Ref to compare from, e.g. main
+        #[arg(long, value_name = "REF")]
+        from: String,
+
+        /// Ref to compare to
+        #[arg(long, value_name = "REF", default_value = "HEAD")]
+        to: String,
+
+        /// Fail when any rule or file differs
+        #[arg(long)]
+        exit_code: bool,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "churn",
+        about = "Report how often ownership changed and flag areas with unstable ownership",
+        long_about = "Read the history of the CODEOWNERS files and count, for each rule pattern, the commits that added it, removed it or gave it different owners within the time window. Areas changed repeatedly are flagged unstable."
+    )]
+    Churn {
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Length of the time window, in days
+        #[arg(long, value_name = "N", default_value = "90")]
+        days: u32,
+
+        /// Changes within the window from which an area is flagged unstable
+        #[arg(long, value_name = "N", default_value = "3")]
+        min_changes: usize,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "heatmap",
+        about = "Export the size, owner and churn of every file for treemaps",
+        long_about = "Write every file with its size in bytes, its primary owner and the commits that changed it within the time window, to visualize which owners dominate which parts of the codebase. JSON nests the files in their directories, with each directory's totals and the owner of most of its bytes, for d3.hierarchy or Observable; CSV has one row per file, for d3.stratify or a spreadsheet. Generated files are left out."
+    )]
+    Heatmap {
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Length of the time window churn is counted over, in days
+        #[arg(long, value_name = "N", default_value = "90")]
+        days: u32,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: json|csv
+        #[arg(long, value_name = "FORMAT", default_value = "json", value_parser = parse_heatmap_format)]
+        format: HeatmapFormat,
+
+        /// Write the heatmap to a file instead of standard output
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    #[clap(
+        name = "export-paths",
+        about = "Export the files of given owners as a list or a tarball",
+        long_about = "List the files any of the given owners is responsible for, or pack them into a tarball, to scope an audit, a handover package or a security review. Owners are compared without case and paths are relative to the repository root. The format follows the name of the --to file, a tarball for .tar and a list otherwise, unless --format is given."
+    )]
+    ExportPaths {
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Owners whose files are exported, comma separated
+        #[arg(long, value_name = "LIST", value_delimiter = ',', required = true)]
+        owners: Vec<String>,
+
+        /// Write to a file instead of standard output
+        #[arg(long, value_name = "FILE")]
+        to: Option<PathBuf>,
+
+        /// Export format: list|tar (default: from the --to file name)
+        #[arg(long, value_name = "FORMAT", value_parser = parse_export_format)]
+        format: Option<ExportFormat>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+    },
+    #[clap(
+        name = "checklist",
+        about = "Generate a Markdown review checklist for the changes since a base revision",
+        long_about = "Group the files changed since the current branch left the base revision by owner, with a checkbox per file and the review notes of their tags, ready to paste into a pull request. The layout and notes come from the checklist template (the checklist_template setting)."
+    )]
+    Checklist {
+        /// Revision the changes are compared against, e.g. main or origin/main
+        #[arg(long, value_name = "REF")]
+        base: String,
+
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "changed-files",
+        about = "List the owners who must review the files changed since a base revision",
+        long_about = "Resolve the files changed since the current branch left the base revision, or the paths read from stdin, and print the union of their owners and tags, each listed once. Files the cache does not hold, such as deleted ones, are resolved against its rules."
+    )]
+    ChangedFiles {
+        /// Revision the changes are compared against, e.g. main or origin/main
+        #[arg(long, value_name = "REF", required_unless_present = "stdin")]
+        base: Option<String>,
+
+        /// Read the changed paths from stdin, one per line, instead of from git
+        #[arg(long, conflicts_with = "base")]
+        stdin: bool,
+
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "branches",
+        about = "List the remote branches changing an owner's files, oldest first",
+        long_about = "Compare every remote branch with the point where it left the default branch and list those changing files the owner, or a team the team mapping lists them in, is responsible for under the current rules. Branches nobody committed to recently come first, to help teams find and clean up their stale branches. The default branch is the one origin/HEAD points at, or else origin/main or origin/master."
+    )]
+    Branches {
+        /// Owner whose files the branches change, e.g. @alice or @org/team
+        owner: String,
+
+        /// Directory path of the repository (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Branch the others are compared against (default: the default branch of origin)
+        #[arg(long, value_name = "REF")]
+        base: Option<String>,
+
+        /// Only list branches without commits in the last N days
+        #[arg(long, value_name = "DAYS", default_value = "0")]
+        older_than: u32,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "optimize",
+        about = "Shrink CODEOWNERS files to fewer rules with the same ownership",
+        long_about = "Remove rules that decide no file and merge rules with the same owners and tags into directory rules. The result is checked by resolving every file in the repository against both rule sets before it is printed or written."
+    )]
+    Optimize {
+        /// Directory path to optimize (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Rewrite the CODEOWNERS files in place instead of printing them
+        #[arg(long)]
+        write: bool,
+    },
+    #[clap(
+        name = "import",
+        about = "Merge another repository's CODEOWNERS rules under a directory prefix",
+        long_about = "Rewrite the CODEOWNERS rules of another repository to apply below a directory of this one, as when consolidating repositories into a monorepo, and append them to the root CODEOWNERS file. The other repository's files are resolved at their new location before and after the merge to report owner conflicts with the rules in place."
+    )]
+    Import {
+        /// Directory path to import into (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Checkout of the repository whose rules are imported
+        #[arg(long, value_name = "PATH")]
+        from: PathBuf,
+
+        /// Directory the other repository's files live in, relative to the repository root
+        #[arg(long, value_name = "DIR")]
+        prefix: String,
+
+        /// Write the merged CODEOWNERS file instead of printing it
+        #[arg(long)]
+        write: bool,
+    },
+    #[clap(
+        name = "tag",
+        about = "Rewrite tags across CODEOWNERS files and inline markers"
+    )]
+    Tag {
+        #[clap(subcommand)]
+        subcommand: TagSubcommand,
+    },
+    #[clap(
+        name = "snapshot",
+        about = "Record ownership over time and prune old snapshots"
+    )]
+    Snapshot {
+        #[clap(subcommand)]
+        subcommand: SnapshotSubcommand,
+    },
+    #[clap(name = "cache", about = "Work with ownership cache files")]
+    Cache {
+        #[clap(subcommand)]
+        subcommand: CacheSubcommand,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+pub(crate) enum TagSubcommand {
+    #[clap(
+        name = "rename",
+        about = "Rename a tag in every CODEOWNERS file and inline marker"
+    )]
+    Rename {
+        /// Tag to rename, with or without the leading #
+        old: String,
+
+        /// New tag name
+        new: String,
+
+        /// Print the changes as a diff without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[clap(
+        name = "apply",
+        about = "Tag the rules for a pattern, adding a rule if there is none"
+    )]
+    Apply {
+        /// Tag to add, with or without the leading #
+        tag: String,
+
+        /// Pattern to tag, relative to the repository root, e.g. 'terraform/**'
+        #[arg(long, value_name = "PATTERN")]
+        pattern: String,
+
+        /// Owners of a new rule (default: the owners the matching files already have)
+        #[arg(long, value_name = "LIST")]
+        owners: Option<String>,
+
+        /// Print the changes as a diff without writing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+pub(crate) enum SnapshotSubcommand {
+    #[clap(
+        name = "save",
+        about = "Record the current file counts of every owner and tag"
+    )]
+    Save {
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+    },
+    #[clap(name = "list", about = "Show the stored snapshots, oldest first")]
+    List {
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "gc",
+        about = "Delete snapshots the retention policy no longer keeps",
+        long_about = "Keep the latest snapshot of each day for snapshot_keep_daily_days days, then the latest of each week for snapshot_keep_weekly_weeks weeks (0: forever), and delete the rest. The newest snapshot is always kept."
+    )]
+    Gc {
+        /// List the snapshots that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, PartialEq, Debug)]
+pub(crate) enum CacheSubcommand {
+    #[clap(
+        name = "diff",
+        about = "List the files whose owners or tags differ between two cache files",
+        long_about = "Compare two cache files, such as build artifacts of the main branch and a pull request, and list the files whose owners or tags changed, were added or were removed. Nothing is resolved locally. Files are matched by path, so build both caches from the same directory spelling."
+    )]
+    Diff {
+        /// Cache file to compare from
+        a: PathBuf,
+
+        /// Cache file to compare to
+        b: PathBuf,
+
+        /// Fail when any file differs
+        #[arg(long)]
+        exit_code: bool,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "migrate",
+        about = "Bring a cache written by an older version up to date without rebuilding it",
+        long_about = "Convert a cache file in an older layout to the current one, filling in only what later layouts added instead of resolving every file again. Commands that build the cache do this on their own; run it to migrate ahead of time. Run it where the cache is built, as files are read from the paths it records."
+    )]
+    Migrate {
+        /// Directory path whose cache to migrate (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+    },
+    #[clap(
+        name = "info",
+        about = "Show what a cache holds and how it was built",
+        long_about = "Print the cache file's encoding, layout, size and repository hash, the version, settings and commit it was built with, and how many rules, files, owners and tags it holds. The cache is read as it is, without checking it against the repository."
+    )]
+    Info {
+        /// Directory path whose cache to show (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "verify",
+        about = "Check that a cache is current without rebuilding it",
+        long_about = "Check the cache against the repository as commands do before using it: its layout, the version and settings it was built with, and the repository hash. Nothing is migrated, updated or rebuilt. Fails unless commands would use the cache as it is, naming what the next one will do to it."
+    )]
+    Verify {
+        /// Directory path whose cache to check (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Output format: text|json|bincode
+        #[arg(long, value_name = "FORMAT", default_value = "text", value_parser = parse_output_format)]
+        format: OutputFormat,
+    },
+    #[clap(
+        name = "clear",
+        about = "Delete a cache that is not current",
+        long_about = "Delete the cache file if it is stale, unreadable, in an older layout or built with other settings, as `cache verify` reports; a current cache is kept unless --force is given. The next command that needs the cache builds it again."
+    )]
+    Clear {
+        /// Directory path whose cache to delete (default: --repo or the current git root)
+        path: Option<PathBuf>,
+
+        /// Custom cache file location
+        #[arg(long, value_name = "FILE")]
+        cache_file: Option<PathBuf>,
+
+        /// Delete the cache even if it is current
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+pub fn cli_match() -> Result<()> {
+    // Parse the command line arguments
+    let cli = Cli::parse();
+
+    // Merge clap config file if the value is set
+    AppConfig::merge_config(cli.config.as_deref())?;
+
+    let app = Cli::command();
+    let matches = app.get_matches();
+    let command = command_name(&matches);
+
+    AppConfig::merge_args(matches)?;
+
+    let config = match AppConfig::fetch() {
+        Ok(config) => config,
+        // doctor reports configuration errors itself
+        Err(_) if matches!(cli.command, Commands::Doctor { .. }) => AppConfig::default(),
+        Err(e) => return Err(e),
+    };
+    parallelism::configure(config.jobs, config.low_priority)?;
+    discovery::set(config.global_gitignore, config.git_exclude);
+    output::set_stable(cli.stable_output);
+    registry::set_enabled(config.registry);
+    rule_filter::set(RuleFilter::new(
+        &cli.ignore_rules_tagged,
+        &cli.only_rules_tagged,
+    ));
+    if let Some(timeout) = cli.timeout {
+        interrupt::set_deadline(Duration::from_secs(timeout));
+    }
+
+    let started = Instant::now();
+    let mut result = run_command(&cli.command);
+    let elapsed = started.elapsed();
+
+    let warning_counts = warnings::counts();
+    if let Some(line) = warnings::format_summary(&warning_counts) {
+        eprintln!("{}", line);
+    }
+    let warning_count = warnings::count();
+    if let (Ok(()), Some(max)) = (&result, cli.max_warnings) {
+        if warning_count > max {
+            result = Err(Error::new(&format!(
+                "{} warnings, more than --max-warnings {}",
+                warning_count, max
+            )));
+        }
+    }
+    if warning_count > 0 {
+        summary::note("warnings", warning_count as u64);
+    }
+
+    telemetry::record(config.telemetry, &command, elapsed, result.is_ok());
+    if cli.summary {
+        eprintln!(
+            "{}",
+            summary::format_line(&command, result.is_ok(), elapsed, &summary::stats())
+        );
+    }
+
+    result
+}
+
+/// Execute the subcommand
+fn run_command(command: &Commands) -> Result<()> {
+    match command {
+        Commands::Codeowners { repo, subcommand } => codeowners(subcommand, repo.as_deref())?,
+        Commands::Completion { subcommand } => {
+            let mut app = Cli::command();
+            match subcommand {
+                CompletionSubcommand::Bash => {
+                    generate(Bash, &mut app, "codeinput", &mut std::io::stdout());
+                    print!("{}", completion::BASH_HOOK);
+                }
+                CompletionSubcommand::Zsh => {
+                    generate(Zsh, &mut app, "codeinput", &mut std::io::stdout());
+                    print!("{}", completion::ZSH_HOOK);
+                }
+                CompletionSubcommand::Fish => {
+                    generate(Fish, &mut app, "codeinput", &mut std::io::stdout());
+                    print!("{}", completion::FISH_HOOK);
+                }
+            }
+        }
+        Commands::Complete { position, args } => {
+            commands::complete::run(args, *position, takes_positional(args, *position))?
+        }
+        Commands::Doctor { path, cache_file } => {
+            commands::doctor::run(path, cache_file.as_deref())?
+        }
+        Commands::Config { subcommand } => match subcommand {
+            None | Some(ConfigSubcommand::Show) => commands::config::run()?,
+            Some(ConfigSubcommand::Set { key, value }) => commands::config::set(key, value)?,
+        },
+        Commands::Org { subcommand } => match subcommand {
+            OrgSubcommand::Report {
+                workspace,
+                format,
+                output,
+            } => commands::org::report(workspace.as_deref(), *format, output.as_deref())?,
+        },
+        Commands::Repos { subcommand } => match subcommand {
+            None => commands::repos::list(&OutputFormat::Text)?,
+            Some(ReposSubcommand::List { format }) => commands::repos::list(format)?,
+            Some(ReposSubcommand::Refresh { prune }) => commands::repos::refresh(*prune)?,
+        },
+        Commands::Demo { generate } => commands::demo::run(generate)?,
+    }
+
+    Ok(())
+}
+
+/// Whether the word at `position` of `args` is an argument of a command rather than the
+/// name of a subcommand
+fn takes_positional(args: &[String], position: usize) -> bool {
+    let mut command = Cli::command();
+    for word in args.iter().take(position).skip(1) {
+        if let Some(subcommand) = command.find_subcommand(word) {
+            command = subcommand.clone();
+        }
+    }
+    !command.has_subcommands()
+}
+
+/// Subcommand path reported to telemetry, e.g. `codeowners list-files`
+///
+/// Only subcommand names are used, never argument values.
+fn command_name(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        names.push(name);
+        current = sub;
+    }
+    names.join(" ")
+}
+
+/// Directory the command was started in, relative to the repository root it moved to
+static START_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Repository a codeowners subcommand works on, given as its PATH or with `--repo`
+///
+/// With neither, the command moves to the root of the git repository holding the current
+/// directory, so paths are read and printed as when it is run from the root. Outside a
+/// git repository, the current directory is used.
+fn repo_path(path: Option<&Path>, repo: Option<&Path>) -> Result<PathBuf> {
+    match (path, repo) {
+        (Some(_), Some(_)) => Err(Error::new(
+            "Give the repository either as PATH or with --repo, not both",
+        )),
+        (Some(path), None) | (None, Some(path)) => Ok(path.to_path_buf()),
+        (None, None) => {
+            if START_DIR.get().is_some() {
+                return Ok(PathBuf::from("."));
+            }
+            let current = std::env::current_dir().map_err(|e| {
+                Error::with_source("Failed to read the current directory", Box::new(e))
+            })?;
+            let Some(root) = discovery::repo_root(&current) else {
+                return Ok(PathBuf::from("."));
+            };
+            let start = match current.strip_prefix(&root) {
+                Ok(start) => start.to_path_buf(),
+                Err(_) => current.clone(),
+            };
+            if !start.as_os_str().is_empty() {
+                std::env::set_current_dir(&root).map_err(|e| {
+                    Error::with_source(
+                        &format!("Failed to change to {}", root.display()),
+                        Box::new(e),
+                    )
+                })?;
+            }
+            let _ = START_DIR.set(start);
+            Ok(PathBuf::from("."))
+        }
+    }
+}
+
+/// `path`, a file argument relative to the directory the command was started in, as seen
+/// from the current directory once [`repo_path`] has moved to the repository root
+///
+/// `..` is resolved without following links, as git resolves pathspecs, so `../README.md`
+/// from `src` is spelled as the cache spells it.
+fn from_start(path: &Path) -> PathBuf {
+    let Some(start) = START_DIR.get().filter(|_| path.is_relative()) else {
+        return path.to_path_buf();
+    };
+    let mut resolved = PathBuf::new();
+    for component in start.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if resolved.file_name().is_some() => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    if resolved.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        resolved
+    }
+}
+
+/// Handle codeowners subcommands, in the repository given with `--repo`
+pub(crate) fn codeowners(subcommand: &CodeownersSubcommand, repo: Option<&Path>) -> Result<()> {
+    match subcommand {
+        CodeownersSubcommand::Parse {
+            path,
+            cache_file,
+            format,
+            matcher,
+            report,
+            push_cache,
+        } => commands::parse::run(
+            &repo_path(path.as_deref(), repo)?,
+            cache_file.as_deref(),
+            *format,
+            *matcher,
+            report.as_ref(),
+            push_cache.as_deref(),
+        ),
+        CodeownersSubcommand::Calibrate { path, sample } => {
+            commands::calibrate::run(&repo_path(path.as_deref(), repo)?, *sample)
+        }
+        CodeownersSubcommand::Watch { path, backend } => {
+            commands::watch::run(&repo_path(path.as_deref(), repo)?, *backend)
+        }
+        #[cfg(feature = "serve")]
+        CodeownersSubcommand::Serve { bind, cache_file } => {
+            commands::serve::run(&repo_path(None, repo)?, bind, cache_file.as_deref())
+        }
+        CodeownersSubcommand::ListFiles {
+            path,
+            path_prefix,
+            tags,
+            owners,
+            unowned,
+            show_all,
+            format,
+            cache_file,
+            expand,
+            paths_from,
+            no_cache,
+            exclude_generated,
+            group_by,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::list_files::run(
+                Some(&repo),
+                path_prefix.as_deref().map(from_start).as_deref(),
+                tags.as_deref(),
+                owners.as_deref(),
+                *unowned,
+                *show_all,
+                format,
+                cache_file.as_deref(),
+                *expand,
+                paths_from
+                    .as_deref()
+                    .map(|file| {
+                        if file == Path::new("-") {
+                            file.to_path_buf()
+                        } else {
+                            from_start(file)
+                        }
+                    })
+                    .as_deref(),
+                *no_cache,
+                *exclude_generated,
+                *group_by,
+            )
+        }
+        CodeownersSubcommand::ListOwners {
+            path,
+            format,
+            cache_file,
+            rollup,
+            by_type,
+            scope,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::list_owners::run(
+                Some(&repo),
+                format,
+                cache_file.as_deref(),
+                *rollup,
+                *by_type,
+                scope.as_deref().map(from_start).as_deref(),
+            )
+        }
+        CodeownersSubcommand::ListTags {
+            path,
+            format,
+            cache_file,
+            co_occurrence,
+            scope,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::list_tags::run(
+                Some(&repo),
+                format,
+                cache_file.as_deref(),
+                *co_occurrence,
+                scope.as_deref().map(from_start).as_deref(),
+            )
+        }
+        CodeownersSubcommand::ListRules {
+            format,
+            with_matches,
+            by_source,
+            cache_file,
+            scope,
+        } => {
+            let repo = repo_path(None, repo)?;
+            commands::list_rules::run(
+                &repo,
+                format,
+                cache_file.as_deref(),
+                *with_matches,
+                *by_source,
+                scope.as_deref().map(from_start).as_deref(),
+            )
+        }
+        CodeownersSubcommand::Inspect {
+            file_path,
+            format,
+            cache_file,
+            raw,
+            explain,
+            expand,
+            no_cache,
+        } => {
+            let repo = repo_path(None, repo)?;
+            commands::inspect::run(
+                &from_start(file_path),
+                Some(&repo),
+                format,
+                cache_file.as_deref(),
+                *raw,
+                *explain,
+                *expand,
+                *no_cache,
+            )
+        }
+        CodeownersSubcommand::Ls {
+            dir,
+            format,
+            cache_file,
+        } => {
+            let repo = repo_path(None, repo)?;
+            commands::ls::run(&from_start(dir), Some(&repo), format, cache_file.as_deref())
+        }
+        CodeownersSubcommand::Statusline {
+            file,
+            cache_file,
+            budget_ms,
+        } => commands::statusline::run(file, repo, cache_file.as_deref(), *budget_ms),
+        CodeownersSubcommand::InferOwners {
+            path,
+            scope,
+            algorithm,
+            weight_lines,
+            weight_commits,
+            weight_recency,
+            lookback_days,
+            min_commits,
+            min_percentage,
+            exclude_author,
+            include_bots,
+            exclude_path,
+            alias_file,
+            reviews,
+            github_repo,
+            review_weight,
+            max_pulls,
+            offline,
+            suggest_teams,
+            team_map,
+            team_threshold,
+            cache_file,
+            output,
+            dry_run,
+            aggregate,
+            aggregate_threshold,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            let config = AppConfig::fetch()?;
+            let mut exclusions = InferExclusions::new(exclude_author, exclude_path)?;
+            if !include_bots {
+                exclusions = exclusions.with_bots(&config.bot_authors)?;
+            }
+            // Flags override the configured weights one by one
+            let algorithm = match algorithm {
+                InferAlgorithm::Weighted(_) => InferAlgorithm::Weighted(InferWeights::new(
+                    weight_lines.unwrap_or(config.infer_weight_lines),
+                    weight_commits.unwrap_or(config.infer_weight_commits),
+                    weight_recency.unwrap_or(config.infer_weight_recency),
+                )?),
+                algorithm => algorithm.clone(),
+            };
+            let reviews = reviews
+                .then(|| {
+                    ReviewSource::new(github_repo.clone(), *review_weight, *max_pulls, *offline)
+                })
+                .transpose()?;
+            let options = InferOptions {
+                scope: scope.clone(),
+                algorithm,
+                lookback_days: *lookback_days,
+                min_commits: *min_commits,
+                min_percentage: *min_percentage,
+                exclusions,
+                reviews,
+                suggest_teams: suggest_teams.then_some(*team_threshold),
+                aggregate: (*aggregate == InferAggregation::Directory)
+                    .then_some(*aggregate_threshold),
+            };
+            commands::infer_owners::run(
+                Some(&repo),
+                &options,
+                alias_file.as_deref().map(from_start).as_deref(),
+                team_map.as_deref().map(from_start).as_deref(),
+                cache_file.as_deref(),
+                output.as_deref().map(from_start).as_deref(),
+                *dry_run,
+            )
+        }
+        CodeownersSubcommand::Equivalent { a, b, format } => {
+            let repo = repo_path(None, repo)?;
+            commands::equivalent::run(&repo, &from_start(a), &from_start(b), format)
+        }
+        CodeownersSubcommand::CompareGithub {
+            path,
+            git_ref,
+            github_repo,
+            sample,
+            cache_file,
+            offline,
+            format,
+        } => commands::compare_github::run(
+            &repo_path(path.as_deref(), repo)?,
+            git_ref,
+            github_repo.as_ref(),
+            *sample,
+            cache_file.as_deref(),
+            *offline,
+            format,
+        ),
+        CodeownersSubcommand::VerifyOwners {
+            path,
+            provider,
+            github_repo,
+            gitlab_project,
+            cache_file,
+            expand_groups,
+            offline,
+            format,
+        } => commands::verify_owners::run(
+            &repo_path(path.as_deref(), repo)?,
+            *provider,
+            github_repo.as_ref(),
+            gitlab_project.as_ref(),
+            cache_file.as_deref(),
+            *expand_groups,
+            *offline,
+            format,
+        ),
+        CodeownersSubcommand::Validate {
+            path,
+            warn_days,
+            format,
+        } => commands::validate::run(&repo_path(path.as_deref(), repo)?, *warn_days, format),
+        CodeownersSubcommand::Coverage {
+            path,
+            min_coverage,
+            cache_file,
+            scope,
+            format,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::coverage::run(
+                &repo,
+                cache_file.as_deref(),
+                scope.as_deref().map(from_start).as_deref(),
+                *min_coverage,
+                format,
+            )
+        }
+        CodeownersSubcommand::Gate {
+            path,
+            checks,
+            cache_file,
+            scope,
+            format,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::gate::run(
+                &repo,
+                checks,
+                cache_file.as_deref(),
+                scope.as_deref().map(from_start).as_deref(),
+                format,
+            )
+        }
+        CodeownersSubcommand::SimulateMembership {
+            changes,
+            path,
+            cache_file,
+            format,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::simulate_membership::run(
+                &repo,
+                &from_start(changes),
+                cache_file.as_deref(),
+                format,
+            )
+        }
+        CodeownersSubcommand::Impact {
+            owner,
+            path,
+            cache_file,
+            no_suggest,
+            format,
+        } => commands::impact::run(
+            &repo_path(path.as_deref(), repo)?,
+            owner,
+            cache_file.as_deref(),
+            *no_suggest,
+            format,
+        ),
+        CodeownersSubcommand::Diff {
+            path,
+            from,
+            to,
+            exit_code,
+            format,
+        } => commands::diff::run(
+            &repo_path(path.as_deref(), repo)?,
+            from,
+            to,
+            *exit_code,
+            format,
+        ),
+        CodeownersSubcommand::Churn {
+            path,
+            days,
+            min_changes,
+            format,
+        } => commands::churn::run(
+            &repo_path(path.as_deref(), repo)?,
+            *days,
+            *min_changes,
+            format,
+        ),
+        CodeownersSubcommand::Heatmap {
+            path,
+            days,
+            cache_file,
+            format,
+            output,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::heatmap::run(
+                &repo,
+                *days,
+                cache_file.as_deref(),
+                format,
+                output.as_deref().map(from_start).as_deref(),
+            )
+        }
+        CodeownersSubcommand::ExportPaths {
+            path,
+            owners,
+            to,
+            format,
+            cache_file,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            let to = to.as_deref().map(from_start);
+            commands::export_paths::run(
+                &repo,
+                owners,
+                to.as_deref(),
+                *format,
+                cache_file.as_deref(),
+            )
+        }
+        CodeownersSubcommand::Checklist {
+            base,
+            path,
+            cache_file,
+            format,
+        } => commands::checklist::run(
+            &repo_path(path.as_deref(), repo)?,
+            base,
+            cache_file.as_deref(),
+            format,
+        ),
+        CodeownersSubcommand::ChangedFiles {
+            base,
+            stdin: _,
+            path,
+            cache_file,
+            format,
+        } => commands::changed_files::run(
+            &repo_path(path.as_deref(), repo)?,
+            base.as_deref(),
+            cache_file.as_deref(),
+            format,
+        ),
+        CodeownersSubcommand::Branches {
+            owner,
+            path,
+            base,
+            older_than,
+            cache_file,
+            format,
+        } => commands::branches::run(
+            &repo_path(path.as_deref(), repo)?,
+            owner,
+            base.as_deref(),
+            *older_than,
+            cache_file.as_deref(),
+            format,
+        ),
+        CodeownersSubcommand::Optimize { path, write } => {
+            commands::optimize::run(&repo_path(path.as_deref(), repo)?, *write)
+        }
+        CodeownersSubcommand::Import {
+            path,
+            from,
+            prefix,
+            write,
+        } => {
+            let repo = repo_path(path.as_deref(), repo)?;
+            commands::import::run(&repo, &from_start(from), prefix, *write)
+        }
+        CodeownersSubcommand::Tag { subcommand } => match subcommand {
+            TagSubcommand::Rename { old, new, dry_run } => {
+                commands::tag::rename(&repo_path(None, repo)?, old, new, *dry_run)
+            }
+            TagSubcommand::Apply {
+                tag,
+                pattern,
+                owners,
+                dry_run,
+            } => commands::tag::apply(
+                &repo_path(None, repo)?,
+                tag,
+                pattern,
+                owners.as_deref(),
+                *dry_run,
+            ),
+        },
+        CodeownersSubcommand::Snapshot { subcommand } => match subcommand {
+            SnapshotSubcommand::Save { cache_file } => {
+                commands::snapshot::save(&repo_path(None, repo)?, cache_file.as_deref())
+            }
+            SnapshotSubcommand::List { format } => {
+                commands::snapshot::list(&repo_path(None, repo)?, format)
+            }
+            SnapshotSubcommand::Gc { dry_run } => {
+                commands::snapshot::gc(&repo_path(None, repo)?, *dry_run)
+            }
+        },
+        CodeownersSubcommand::Cache { subcommand } => match subcommand {
+            CacheSubcommand::Diff {
+                a,
+                b,
+                exit_code,
+                format,
+            } => commands::cache::diff(a, b, *exit_code, format),
+            CacheSubcommand::Migrate { path, cache_file } => {
+                commands::cache::migrate(&repo_path(path.as_deref(), repo)?, cache_file.as_deref())
+            }
+            CacheSubcommand::Info {
+                path,
+                cache_file,
+                format,
+            } => commands::cache::info(
+                &repo_path(path.as_deref(), repo)?,
+                cache_file.as_deref(),
+                format,
+            ),
+            CacheSubcommand::Verify {
+                path,
+                cache_file,
+                format,
+            } => commands::cache::verify(
+                &repo_path(path.as_deref(), repo)?,
+                cache_file.as_deref(),
+                format,
+            ),
+            CacheSubcommand::Clear {
+                path,
+                cache_file,
+                force,
+            } => commands::cache::clear(
+                &repo_path(path.as_deref(), repo)?,
+                cache_file.as_deref(),
+                *force,
+            ),
+        },
+    }
+}
+
+fn parse_output_format(s: &str) -> std::result::Result<OutputFormat, String> {
+    match s.to_lowercase().as_str() {
         "text" => Ok(OutputFormat::Text),
         "json" => Ok(OutputFormat::Json),
         "bincode" => Ok(OutputFormat::Bincode),
@@ -359,14 +2026,100 @@ fn parse_output_format(s: &str) -> std::result::Result<OutputFormat, String> {
     }
 }
 
+fn parse_report_format(s: &str) -> std::result::Result<ReportFormat, String> {
+    match s.to_lowercase().as_str() {
+        "text" => Ok(ReportFormat::Text),
+        "json" => Ok(ReportFormat::Json),
+        "html" => Ok(ReportFormat::Html),
+        _ => Err(format!("Invalid report format: {}", s)),
+    }
+}
+
+fn parse_heatmap_format(s: &str) -> std::result::Result<HeatmapFormat, String> {
+    match s.to_lowercase().as_str() {
+        "json" => Ok(HeatmapFormat::Json),
+        "csv" => Ok(HeatmapFormat::Csv),
+        _ => Err(format!("Invalid heatmap format: {}", s)),
+    }
+}
+
+fn parse_export_format(s: &str) -> std::result::Result<ExportFormat, String> {
+    match s.to_lowercase().as_str() {
+        "list" => Ok(ExportFormat::List),
+        "tar" => Ok(ExportFormat::Tar),
+        _ => Err(format!("Invalid export format: {}", s)),
+    }
+}
+
+fn parse_gate_check(s: &str) -> std::result::Result<GateCheck, String> {
+    s.trim().parse::<GateCheck>()
+}
+
+fn parse_min_coverage(s: &str) -> std::result::Result<f64, String> {
+    match s.trim().trim_end_matches('%').parse::<f64>() {
+        Ok(min) if (0.0..=100.0).contains(&min) => Ok(min),
+        _ => Err(format!(
+            "Invalid coverage threshold: {} (expected a percentage from 0 to 100)",
+            s
+        )),
+    }
+}
+
+fn parse_github_repo(s: &str) -> std::result::Result<GitHubRepo, String> {
+    s.trim().parse::<GitHubRepo>()
+}
+
+fn parse_gitlab_project(s: &str) -> std::result::Result<GitLabProject, String> {
+    s.trim().parse::<GitLabProject>()
+}
+
+fn parse_provider(s: &str) -> std::result::Result<Provider, String> {
+    match s.to_lowercase().as_str() {
+        "github" => Ok(Provider::GitHub),
+        "gitlab" => Ok(Provider::GitLab),
+        _ => Err(format!(
+            "Invalid provider: {}. Valid options: github, gitlab",
+            s
+        )),
+    }
+}
+
 fn parse_cache_encoding(s: &str) -> std::result::Result<CacheEncoding, String> {
     match s.to_lowercase().as_str() {
         "bincode" => Ok(CacheEncoding::Bincode),
         "json" => Ok(CacheEncoding::Json),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => Ok(CacheEncoding::Sqlite),
+        #[cfg(feature = "archive")]
+        "archive" => Ok(CacheEncoding::Archive),
         _ => Err(format!("Invalid cache encoding: {}", s)),
     }
 }
 
+fn parse_matcher_strategy(s: &str) -> std::result::Result<MatcherStrategy, String> {
+    s.parse::<MatcherStrategy>().map_err(|e| e.to_string())
+}
+
+fn parse_dialect(s: &str) -> std::result::Result<Dialect, String> {
+    s.parse::<Dialect>()
+}
+
+fn parse_watch_backend(s: &str) -> std::result::Result<WatchBackendKind, String> {
+    s.parse::<WatchBackendKind>().map_err(|e| e.to_string())
+}
+
+fn parse_group_by(s: &str) -> std::result::Result<GroupBy, String> {
+    match s.to_lowercase().as_str() {
+        "owner" => Ok(GroupBy::Owner),
+        "tag" => Ok(GroupBy::Tag),
+        "dir" => Ok(GroupBy::Dir),
+        _ => Err(format!(
+            "Invalid group: {}. Valid options: owner, tag, dir",
+            s
+        )),
+    }
+}
+
 fn parse_infer_scope(s: &str) -> std::result::Result<InferScope, String> {
     match s.to_lowercase().as_str() {
         "all" => Ok(InferScope::All),
@@ -375,13 +2128,25 @@ fn parse_infer_scope(s: &str) -> std::result::Result<InferScope, String> {
     }
 }
 
+fn parse_infer_aggregation(s: &str) -> std::result::Result<InferAggregation, String> {
+    match s.to_lowercase().as_str() {
+        "file" => Ok(InferAggregation::File),
+        "directory" => Ok(InferAggregation::Directory),
+        _ => Err(format!(
+            "Invalid aggregation: {}. Valid options: file, directory",
+            s
+        )),
+    }
+}
+
 fn parse_infer_algorithm(s: &str) -> std::result::Result<InferAlgorithm, String> {
     match s.to_lowercase().as_str() {
         "commits" => Ok(InferAlgorithm::Commits),
         "lines" => Ok(InferAlgorithm::Lines),
         "recent" => Ok(InferAlgorithm::Recent),
+        "weighted" => Ok(InferAlgorithm::Weighted(InferWeights::default())),
         _ => Err(format!(
-            "Invalid algorithm: {}. Valid options: commits, lines, recent",
+            "Invalid algorithm: {}. Valid options: commits, lines, recent, weighted",
             s
         )),
     }
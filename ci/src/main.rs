@@ -8,6 +8,7 @@ pub(crate) mod cli;
 
 use codeinput::utils::app_config::AppConfig;
 use codeinput::utils::error::Result;
+use codeinput::utils::interrupt;
 
 /// The main entry point of the application.
 fn main() -> Result<()> {
@@ -27,14 +28,25 @@ fn main() -> Result<()> {
             .install();
     }
 
-    let _guard = codeinput::utils::logger::setup_logging()?;
+    let guard = codeinput::utils::logger::setup_logging()?;
+
+    // Let long-running commands stop cleanly on Ctrl-C
+    interrupt::install_handler()?;
 
     // Initialize Configuration
     let config_contents = include_str!("resources/default_config.toml");
     AppConfig::init(Some(config_contents))?;
 
     // Match Commands
-    crate::cli::cli_match()?;
+    if let Err(e) = crate::cli::cli_match() {
+        if let Some(code) = interrupt::exit_code(&e) {
+            eprintln!("{}", e);
+            drop(guard);
+            std::process::exit(code);
+        }
+        return Err(e);
+    }
 
+    drop(guard);
     Ok(())
 }
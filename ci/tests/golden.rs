@@ -0,0 +1,2591 @@
+//! Golden tests for command output
+//!
+//! Every renderer runs against a small fixture repository with `--stable-output` and is
+//! compared against the snapshots in `tests/snapshots`. A format change fails these
+//! tests until the snapshots are reviewed and accepted with `cargo insta review`.
+
+use assert_cmd::Command;
+use insta::assert_snapshot;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const ROOT_CODEOWNERS: &str = "\
+# Root rules
+*.md @org/docs #docs
+*.rs @rust-team #rust
+src/api/** @api-owner api@example.com #api
+";
+
+const FILES: &[&str] = &[
+    "README.md",
+    "docs/guide.md",
+    "scripts/build.sh",
+    "src/api/handler.rs",
+    "src/api/routes.rs",
+    "src/lib.rs",
+    "src/main.rs",
+    "src/web/app.js",
+];
+
+/// Create the fixture repository and build its cache
+fn fixture() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let repo = dir.path();
+
+    let status = std::process::Command::new("git")
+        .args(["init", "-q", "."])
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git init failed");
+
+    // Keep the cache and the user's files out of the repository hash so the cache is not
+    // rebuilt on every run
+    fs::write(repo.join(".gitignore"), ".codeowners.cache\n.config/\n").unwrap();
+    fs::write(repo.join("CODEOWNERS"), ROOT_CODEOWNERS).unwrap();
+    fs::create_dir_all(repo.join("src/web")).unwrap();
+    fs::write(
+        repo.join("src/web/CODEOWNERS"),
+        "*.js @web-team #frontend\n",
+    )
+    .unwrap();
+    for file in FILES {
+        let path = repo.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "content\n").unwrap();
+    }
+
+    run(repo, &["codeowners", "parse"]);
+    dir
+}
+
+/// Run `git` in `repo` as a fixed author
+fn git(repo: &Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Commit every change in `repo` at `date`, e.g. `2023-12-01T00:00:00Z`
+fn commit_all(repo: &Path, message: &str, date: &str) {
+    git(repo, &["add", "-A"]);
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(["commit", "-q", "-m", message])
+        .env("GIT_AUTHOR_DATE", date)
+        .env("GIT_COMMITTER_DATE", date)
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git commit failed");
+}
+
+/// Run `ci` in `repo` with stable output and an isolated configuration
+fn run(repo: &Path, args: &[&str]) -> String {
+    run_in(repo, repo, args)
+}
+
+/// Run `ci` like [`run`], from `dir` instead of the root of `repo`
+fn run_in(repo: &Path, dir: &Path, args: &[&str]) -> String {
+    let config_home = repo.join(".config");
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(dir)
+        .env("HOME", &config_home)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &config_home)
+        .arg("--stable-output")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "ci {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run `ci` like [`run`], for commands expected to fail; returns their stdout
+fn run_failing(repo: &Path, args: &[&str]) -> String {
+    let config_home = repo.join(".config");
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(repo)
+        .env("HOME", &config_home)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &config_home)
+        .arg("--stable-output")
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "ci {} succeeded", args.join(" "));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_parse() {
+    let dir = TempDir::new().unwrap();
+    let repo = dir.path();
+    std::process::Command::new("git")
+        .args(["init", "-q", "."])
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    fs::write(repo.join("CODEOWNERS"), ROOT_CODEOWNERS).unwrap();
+    fs::write(repo.join("main.rs"), "content\n").unwrap();
+
+    assert_snapshot!("parse_text", run(repo, &["codeowners", "parse"]));
+}
+
+#[test]
+fn test_parse_report() {
+    let dir = TempDir::new().unwrap();
+    let repo = dir.path();
+    std::process::Command::new("git")
+        .args(["init", "-q", "."])
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    fs::write(
+        repo.join("CODEOWNERS"),
+        format!("{}src/[z-a].rs @nobody\n", ROOT_CODEOWNERS),
+    )
+    .unwrap();
+    fs::write(repo.join("main.rs"), "content\n").unwrap();
+    fs::write(
+        repo.join("build.sh"),
+        "# !!!CODEOWNERS @release-team #release\n",
+    )
+    .unwrap();
+    fs::write(
+        repo.join("guide.md"),
+        "---\ntitle: Guide\nowners: [@docs-team]\n---\n",
+    )
+    .unwrap();
+
+    // The skipped pattern is also logged, with a timestamp
+    let report = |args: &[&str]| {
+        run(repo, args)
+            .lines()
+            .filter(|line| !line.contains(" WARN "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_snapshot!(
+        "parse_report_text",
+        report(&["codeowners", "parse", "--report"])
+    );
+    assert_snapshot!(
+        "parse_report_json",
+        report(&["codeowners", "parse", "--report=json"])
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn test_sqlite_cache() {
+    let dir = fixture();
+    let repo = dir.path();
+    let queries: &[&[&str]] = &[
+        &["codeowners", "list-files", "--owners", "@org/docs,api"],
+        &[
+            "codeowners",
+            "list-files",
+            "--owners",
+            "rust",
+            "--format",
+            "json",
+        ],
+        &[
+            "codeowners",
+            "inspect",
+            "src/api/routes.rs",
+            "--format",
+            "json",
+        ],
+        &["codeowners", "inspect", "./README.md"],
+    ];
+
+    run(repo, &["codeowners", "parse"]);
+    let from_bincode: Vec<String> = queries.iter().map(|args| run(repo, args)).collect();
+
+    run(repo, &["codeowners", "parse", "--format", "sqlite"]);
+    let cache = fs::read(repo.join(".codeowners.cache")).unwrap();
+    assert!(cache.starts_with(b"SQLite format 3\0"));
+    for (args, expected) in queries.iter().zip(&from_bincode) {
+        assert_eq!(&run(repo, args), expected, "ci {}", args.join(" "));
+    }
+    // Commands that read every file load the whole cache
+    assert!(run(repo, &["codeowners", "list-files"]).contains("src/web/app.js"));
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn test_archive_cache() {
+    let dir = fixture();
+    let repo = dir.path();
+    let queries: &[&[&str]] = &[
+        &["codeowners", "list-files", "--owners", "@org/docs,api"],
+        &[
+            "codeowners",
+            "inspect",
+            "src/api/routes.rs",
+            "--format",
+            "json",
+        ],
+        &["codeowners", "inspect", "./README.md"],
+        &["codeowners", "statusline", "src/api/routes.rs"],
+        &["codeowners", "list-owners"],
+    ];
+
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    run(repo, &["codeowners", "parse"]);
+    let from_bincode: Vec<String> = queries.iter().map(|args| run(repo, args)).collect();
+
+    run(repo, &["codeowners", "parse", "--format", "archive"]);
+    let cache = fs::read(repo.join(".codeowners.cache")).unwrap();
+    assert!(cache.starts_with(b"codeowners-rkyv\0"));
+    for (args, expected) in queries.iter().zip(&from_bincode) {
+        assert_eq!(&run(repo, args), expected, "ci {}", args.join(" "));
+    }
+    assert!(run(repo, &["codeowners", "cache", "info"]).contains("archive"));
+
+    // A stale archive is brought up to date as an archive
+    fs::write(repo.join("src/new.rs"), "content\n").unwrap();
+    commit_all(repo, "Add a module", "2023-12-02T00:00:00Z");
+    assert!(run(repo, &["codeowners", "inspect", "src/new.rs"]).contains("@rust-team"));
+    let cache = fs::read(repo.join(".codeowners.cache")).unwrap();
+    assert!(cache.starts_with(b"codeowners-rkyv\0"));
+}
+
+/// Send `body`, if any, to `path` of the server at `address`, returning the status and
+/// JSON of the answer
+#[cfg(feature = "serve")]
+fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, serde_json::Value) {
+    use std::io::{Read, Write};
+
+    let mut stream = std::net::TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        method,
+        path,
+        address,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[cfg(feature = "serve")]
+#[test]
+fn test_serve() {
+    use std::io::{BufRead, BufReader};
+
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    let config_home = repo.join(".config");
+    let mut server = std::process::Command::new(assert_cmd::cargo::cargo_bin("ci"))
+        .current_dir(repo)
+        .env("HOME", &config_home)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &config_home)
+        .args(["codeowners", "serve", "--bind", "127.0.0.1:0"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The cache is brought up to date before the address is printed
+    let mut output = BufReader::new(server.stdout.take().unwrap()).lines();
+    let address = output
+        .by_ref()
+        .map(Result::unwrap)
+        .find_map(|line| {
+            let rest = line.split_once("http://")?.1;
+            Some(rest.split(',').next()?.to_string())
+        })
+        .expect("The server printed no address");
+    let json = |output: String| serde_json::from_str::<serde_json::Value>(&output).unwrap();
+
+    let (status, file) = request(&address, "GET", "/files/src/api/routes.rs/owners", "");
+    assert_eq!(status, 200);
+    assert_eq!(file["owners"][0]["identifier"], "@api-owner");
+    assert_eq!(file["tags"], serde_json::json!(["api"]));
+    let (status, error) = request(&address, "GET", "/files/src/gone.rs/owners", "");
+    assert_eq!(status, 404);
+    assert!(error["error"].as_str().unwrap().contains("src/gone.rs"));
+
+    let (_, owners) = request(&address, "GET", "/owners", "");
+    let listed = run(repo, &["codeowners", "list-owners", "--format", "json"]);
+    assert_eq!(owners, json(listed));
+    let (_, tags) = request(&address, "GET", "/tags", "");
+    let listed = run(repo, &["codeowners", "list-tags", "--format", "json"]);
+    assert_eq!(tags, json(listed));
+
+    let body = r#"{"files": ["src/lib.rs", "src/web/app.js"]}"#;
+    let (status, changed) = request(&address, "POST", "/changed-files", body);
+    assert_eq!(status, 200);
+    let owners: Vec<_> = changed["owners"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|owner| owner["identifier"].as_str().unwrap())
+        .collect();
+    assert_eq!(owners, ["@rust-team", "@web-team"]);
+
+    // A rebuilt cache is read again on the next request
+    fs::write(repo.join("src/new.rs"), "content\n").unwrap();
+    commit_all(repo, "Add a module", "2023-12-02T00:00:00Z");
+    run(repo, &["codeowners", "parse"]);
+    let (status, file) = request(&address, "GET", "/files/src/new.rs/owners", "");
+    assert_eq!(status, 200);
+    assert_eq!(file["owners"][0]["identifier"], "@rust-team");
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+
+    // Stopped by the deadline, the server exits as timed out rather than as a clean stop
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(repo)
+        .env("HOME", &config_home)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &config_home)
+        .args([
+            "--timeout",
+            "1",
+            "codeowners",
+            "serve",
+            "--bind",
+            "127.0.0.1:0",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Timed out after 1 s"));
+}
+
+#[test]
+fn test_list_files() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("list_files_text", run(repo, &["codeowners", "list-files"]));
+    assert_snapshot!(
+        "list_files_unowned_text",
+        run(repo, &["codeowners", "list-files", "--unowned"])
+    );
+    assert_snapshot!(
+        "list_files_json",
+        run(repo, &["codeowners", "list-files", "--format", "json"])
+    );
+    assert_snapshot!(
+        "list_files_group_by_owner_text",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "list-files",
+                "--group-by",
+                "owner",
+                "--show-all"
+            ]
+        )
+    );
+    assert_snapshot!(
+        "list_files_group_by_dir_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "list-files",
+                "--group-by",
+                "dir",
+                "--path",
+                "src",
+                "--format",
+                "json"
+            ]
+        )
+    );
+
+    // Listed paths are shown in their order, once; paths not in the cache are skipped
+    fs::write(
+        repo.join(".config/changed.txt"),
+        "src/web/app.js\nREADME.md\n\nsrc/gone.rs\nREADME.md\n",
+    )
+    .unwrap();
+    // The skipped paths are logged, with a timestamp
+    let without_logs = |output: String| {
+        assert!(output.contains("1 of 4 listed paths are not in the cache"));
+        output
+            .lines()
+            .filter(|line| !line.contains(" WARN "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let listed = without_logs(run(
+        repo,
+        &[
+            "codeowners",
+            "list-files",
+            "--paths-from",
+            ".config/changed.txt",
+        ],
+    ));
+    assert_snapshot!("list_files_paths_from_text", listed);
+    let config_home = repo.join(".config");
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(repo)
+        .env("HOME", &config_home)
+        .env("XDG_CONFIG_HOME", &config_home)
+        .env("XDG_DATA_HOME", &config_home)
+        .args([
+            "--stable-output",
+            "codeowners",
+            "list-files",
+            "--paths-from",
+            "-",
+        ])
+        .write_stdin(fs::read(config_home.join("changed.txt")).unwrap())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        without_logs(String::from_utf8(output.stdout).unwrap()),
+        listed
+    );
+}
+
+#[test]
+fn test_no_cache() {
+    let dir = fixture();
+    let repo = dir.path();
+    let cached_files = run(
+        repo,
+        &[
+            "codeowners",
+            "list-files",
+            "--path",
+            "src/web",
+            "--show-all",
+        ],
+    );
+    let cached_inspect = run(repo, &["codeowners", "inspect", "src/web/app.js"]);
+    fs::remove_file(repo.join(".codeowners.cache")).unwrap();
+
+    // The same answers for the requested files, without building the cache
+    assert_eq!(
+        run(
+            repo,
+            &[
+                "codeowners",
+                "list-files",
+                "--path",
+                "src/web",
+                "--show-all",
+                "--no-cache"
+            ]
+        ),
+        cached_files
+    );
+    assert_eq!(
+        run(
+            repo,
+            &["codeowners", "inspect", "src/web/app.js", "--no-cache"]
+        ),
+        cached_inspect
+    );
+    assert!(!repo.join(".codeowners.cache").exists());
+}
+
+#[test]
+fn test_generated_files() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(
+        repo.join("src/api/schema.rs"),
+        "// @generated by schema-gen\n",
+    )
+    .unwrap();
+
+    assert_snapshot!(
+        "list_files_generated_text",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "list-files",
+                "--path",
+                "src/api",
+                "--exclude-generated"
+            ]
+        )
+    );
+    // The generated file is owned, so it must not lift coverage either
+    assert_snapshot!(
+        "gate_generated_text",
+        run(repo, &["codeowners", "gate", "--checks", "coverage:87.5"])
+    );
+}
+
+#[test]
+fn test_list_owners() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "list_owners_text",
+        run(repo, &["codeowners", "list-owners"])
+    );
+    assert_snapshot!(
+        "list_owners_json",
+        run(repo, &["codeowners", "list-owners", "--format", "json"])
+    );
+}
+
+#[test]
+fn test_list_tags() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("list_tags_text", run(repo, &["codeowners", "list-tags"]));
+    assert_snapshot!(
+        "list_tags_json",
+        run(repo, &["codeowners", "list-tags", "--format", "json"])
+    );
+}
+
+#[test]
+fn test_list_rules() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("list_rules_text", run(repo, &["codeowners", "list-rules"]));
+    assert_snapshot!(
+        "list_rules_json",
+        run(repo, &["codeowners", "list-rules", "--format", "json"])
+    );
+
+    fs::write(
+        repo.join("CODEOWNERS"),
+        ROOT_CODEOWNERS.replace("src/api/**", "# slack:#api-team escalation:P1\nsrc/api/**"),
+    )
+    .unwrap();
+    run(repo, &["codeowners", "parse"]);
+    assert_snapshot!(
+        "list_rules_annotations_text",
+        run(repo, &["codeowners", "list-rules"])
+    );
+    assert_snapshot!(
+        "inspect_annotations_text",
+        run(repo, &["codeowners", "inspect", "src/api/routes.rs"])
+    );
+
+    fs::write(
+        repo.join("CODEOWNERS"),
+        format!(
+            "{}\n^[Scripts][2] @org/ops\n*.sh\n",
+            ROOT_CODEOWNERS.replace("*.md", "[Documentation] @org/docs\n*.md")
+        ),
+    )
+    .unwrap();
+    run(repo, &["codeowners", "parse"]);
+    assert_snapshot!(
+        "list_rules_sections_text",
+        run(repo, &["codeowners", "list-rules"])
+    );
+    assert_snapshot!(
+        "inspect_sections_text",
+        run(repo, &["codeowners", "inspect", "scripts/build.sh"])
+    );
+}
+
+#[test]
+fn test_inspect() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "inspect_text",
+        run(repo, &["codeowners", "inspect", "src/api/handler.rs"])
+    );
+    assert_snapshot!(
+        "inspect_explain_text",
+        run(
+            repo,
+            &["codeowners", "inspect", "src/web/app.js", "--explain"]
+        )
+    );
+    assert_snapshot!(
+        "inspect_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "inspect",
+                "src/api/handler.rs",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_repo_selection() {
+    let dir = fixture();
+    let repo = dir.path();
+    let web = repo.join("src/web");
+
+    // From a subdirectory the repository root is found, and FILE is relative to where
+    // the command runs
+    assert_eq!(
+        run_in(repo, &web, &["codeowners", "inspect", "app.js"]),
+        run(repo, &["codeowners", "inspect", "src/web/app.js"])
+    );
+    let owners = run(repo, &["codeowners", "list-owners"]);
+    assert_eq!(run_in(repo, &web, &["codeowners", "list-owners"]), owners);
+
+    // So are directories, `..` included, and completion offers paths from there
+    let src = repo.join("src");
+    assert_eq!(
+        run_in(repo, &web, &["codeowners", "ls"]),
+        run(repo, &["codeowners", "ls", "src/web"])
+    );
+    assert_eq!(
+        run_in(repo, &src, &["codeowners", "inspect", "../README.md"]),
+        run(repo, &["codeowners", "inspect", "README.md"])
+    );
+    assert_eq!(
+        run_in(repo, &src, &["codeowners", "list-files", "--path", "api"]),
+        run(repo, &["codeowners", "list-files", "--path", "src/api"])
+    );
+    assert_eq!(
+        run_in(repo, &src, &["codeowners", "list-tags", "--scope", "."]),
+        run(repo, &["codeowners", "list-tags", "--scope", "src"])
+    );
+    let line = ["codeinput", "codeowners", "inspect", "ap"];
+    let args: Vec<&str> = ["complete", "--position", "3", "--"]
+        .into_iter()
+        .chain(line)
+        .collect();
+    assert_eq!(run_in(repo, &src, &args), "api/\n");
+
+    // --repo selects the repository from anywhere, on any subcommand
+    let outside = TempDir::new().unwrap();
+    let repo_arg = repo.to_str().unwrap();
+    assert_eq!(
+        run_in(
+            repo,
+            outside.path(),
+            &["codeowners", "--repo", repo_arg, "list-owners"]
+        ),
+        owners
+    );
+    run_in(
+        repo,
+        outside.path(),
+        &["codeowners", "snapshot", "save", "--repo", repo_arg],
+    );
+    assert!(!run(repo, &["codeowners", "snapshot", "list"]).starts_with("No snapshots"));
+
+    run_failing(repo, &["codeowners", "list-owners", ".", "--repo", "."]);
+}
+
+#[test]
+fn test_front_matter_owners() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(
+        repo.join("docs/guide.md"),
+        "---\ntitle: Guide\nowners:\n  - \"@handbook-team\"\ntags: [handbook]\n---\n# Guide\n",
+    )
+    .unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "inspect_front_matter_text",
+        run(repo, &["codeowners", "inspect", "docs/guide.md"])
+    );
+}
+
+#[test]
+fn test_doctor() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    run(repo, &["codeowners", "parse"]);
+    // Versions and temporary paths vary, so only the verdicts are compared
+    let check = |output: &str, name: &str| -> String {
+        let row = output
+            .lines()
+            .find(|line| {
+                line.split('│')
+                    .nth(1)
+                    .is_some_and(|cell| cell.trim() == name)
+            })
+            .unwrap_or_else(|| panic!("No {} check in:\n{}", name, output));
+        row.split('│').nth(2).unwrap().trim().to_string()
+    };
+
+    let healthy = run(repo, &["doctor"]);
+    for name in [
+        "git",
+        "Repository",
+        "History",
+        "Config",
+        "CODEOWNERS",
+        "Cache",
+    ] {
+        assert_eq!(check(&healthy, name), "ok", "{}", healthy);
+    }
+    assert!(
+        healthy.contains("Up to date: 8 files, 4 rules"),
+        "{}",
+        healthy
+    );
+    assert!(healthy.ends_with("0 errors, 0 warnings\n"), "{}", healthy);
+
+    // A rule added since the cache was built leaves it stale
+    fs::write(
+        repo.join("CODEOWNERS"),
+        format!("{}*.sh @ops\n", ROOT_CODEOWNERS),
+    )
+    .unwrap();
+    let stale = run(repo, &["doctor"]);
+    assert_eq!(check(&stale, "Cache"), "warning");
+    assert!(stale.contains("Stale;"), "{}", stale);
+
+    fs::remove_file(repo.join(".codeowners.cache")).unwrap();
+    let missing = run(repo, &["doctor"]);
+    assert_eq!(check(&missing, "Cache"), "warning");
+    assert!(missing.contains("No cache at"), "{}", missing);
+
+    // An invalid pattern is an error, which fails the command
+    fs::write(repo.join("docs/CODEOWNERS"), "{unclosed @docs\n").unwrap();
+    let broken = run_failing(repo, &["doctor"]);
+    assert_eq!(check(&broken, "Pattern"), "error");
+    assert!(broken.contains("docs/CODEOWNERS:1"), "{}", broken);
+    assert!(broken.ends_with("1 errors, 1 warnings\n"), "{}", broken);
+}
+
+#[test]
+fn test_statusline() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    let lines: String = ["src/api/handler.rs", "src/web/app.js", "scripts/build.sh"]
+        .iter()
+        .map(|file| run(repo, &["codeowners", "statusline", file]))
+        .collect();
+    assert_snapshot!("statusline_text", lines);
+}
+
+#[test]
+fn test_ls() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("ls_text", run(repo, &["codeowners", "ls"]));
+    assert_snapshot!("ls_src_text", run(repo, &["codeowners", "ls", "src"]));
+    assert_snapshot!(
+        "ls_json",
+        run(repo, &["codeowners", "ls", "--format", "json"])
+    );
+}
+
+#[test]
+fn test_optimize() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners
+        .push_str("/docs/ @org/docs #docs\n/src/api/handler.rs @api-owner #api\n/gone/ @nobody\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+
+    assert_snapshot!("optimize_text", run(repo, &["codeowners", "optimize"]));
+}
+
+#[test]
+fn test_equivalent() {
+    let dir = fixture();
+    let repo = dir.path();
+    let alt = TempDir::new().unwrap();
+
+    // The web rules moved into the root file
+    let merged = format!("{}/src/web/*.js @web-team #frontend\n", ROOT_CODEOWNERS);
+    fs::write(alt.path().join("CODEOWNERS"), merged).unwrap();
+    let alt_file = alt.path().join("CODEOWNERS");
+    let alt_file = alt_file.to_str().unwrap();
+    assert_snapshot!(
+        "equivalent_text",
+        run(
+            repo,
+            &["codeowners", "equivalent", "--a", ".", "--b", alt_file]
+        )
+    );
+
+    fs::write(alt.path().join("CODEOWNERS"), ROOT_CODEOWNERS).unwrap();
+    assert_snapshot!(
+        "equivalent_differences_text",
+        run_failing(
+            repo,
+            &["codeowners", "equivalent", "--a", ".", "--b", alt_file]
+        )
+    );
+}
+
+#[test]
+fn test_cache_diff() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    // The PR moves the API to another owner, drops a file and adds one
+    fs::write(repo.join(".gitignore"), "*.cache\n.config/\n").unwrap();
+    run(repo, &["codeowners", "parse", "--cache-file", "main.cache"]);
+    let changed = ROOT_CODEOWNERS.replace("@api-owner api@example.com", "@platform");
+    fs::write(repo.join("CODEOWNERS"), changed).unwrap();
+    fs::remove_file(repo.join("scripts/build.sh")).unwrap();
+    fs::write(repo.join("src/api/auth.rs"), "content\n").unwrap();
+    run(
+        repo,
+        &[
+            "codeowners",
+            "parse",
+            "--cache-file",
+            "pr.cache",
+            "--format",
+            "json",
+        ],
+    );
+
+    assert_snapshot!(
+        "cache_diff_text",
+        run(
+            repo,
+            &["codeowners", "cache", "diff", "main.cache", "pr.cache"]
+        )
+    );
+    assert_snapshot!(
+        "cache_diff_exit_code",
+        run_failing(
+            repo,
+            &[
+                "codeowners",
+                "cache",
+                "diff",
+                "main.cache",
+                "pr.cache",
+                "--exit-code",
+            ]
+        )
+    );
+    assert_snapshot!(
+        "cache_diff_same_text",
+        run(
+            repo,
+            &["codeowners", "cache", "diff", "pr.cache", "pr.cache"]
+        )
+    );
+}
+
+#[test]
+fn test_cache_maintenance() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    run(repo, &["codeowners", "parse"]);
+
+    let info: serde_json::Value = serde_json::from_str(&run(
+        repo,
+        &["codeowners", "cache", "info", "--format", "json"],
+    ))
+    .unwrap();
+    assert_eq!(info["encoding"], "bincode");
+    assert_eq!(info["files"], FILES.len());
+    assert_eq!(info["rules"], 4);
+    assert_eq!(info["hash"].as_str().unwrap().len(), 64);
+    assert!(run(repo, &["codeowners", "cache", "info"]).contains("Owners: 5\n"));
+
+    assert!(run(repo, &["codeowners", "cache", "verify"]).ends_with(": current\n"));
+    assert!(run(repo, &["codeowners", "cache", "clear"]).contains("is current; kept it"));
+
+    fs::write(repo.join("docs/new.md"), "content\n").unwrap();
+    commit_all(repo, "Add a page", "2023-12-02T00:00:00Z");
+    let verify: serde_json::Value = serde_json::from_str(&run_failing(
+        repo,
+        &["codeowners", "cache", "verify", "--format", "json"],
+    ))
+    .unwrap();
+    assert_eq!(verify["status"], "stale");
+    assert!(run(repo, &["codeowners", "cache", "clear"]).contains("Deleted"));
+    assert!(!repo.join(".codeowners.cache").exists());
+    assert!(run_failing(repo, &["codeowners", "cache", "verify"]).ends_with(": missing\n"));
+}
+
+#[test]
+fn test_base_cache_overlay() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(
+        repo.join(".gitignore"),
+        ".codeowners.cache*\nbase.cache\n.config/\n",
+    )
+    .unwrap();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    run(repo, &["codeowners", "parse", "--cache-file", "base.cache"]);
+    fs::remove_file(repo.join(".codeowners.cache")).unwrap();
+
+    fs::write(repo.join("docs/new.md"), "content\n").unwrap();
+    commit_all(repo, "Add a page", "2023-12-02T00:00:00Z");
+    let layered = ["codeowners", "list-files", "--base-cache", "base.cache"];
+    let listed = run(repo, &layered);
+    assert!(listed.contains("docs/new.md"));
+    assert!(listed.contains("src/web/app.js"));
+    assert!(!repo.join(".codeowners.cache").exists());
+    assert!(repo.join(".codeowners.cache.overlay").exists());
+    assert_eq!(run(repo, &layered), listed);
+
+    // A change to the rules falls back to the local cache
+    fs::write(repo.join("src/web/CODEOWNERS"), "*.js @js-team\n").unwrap();
+    commit_all(repo, "Hand over the frontend", "2023-12-03T00:00:00Z");
+    assert!(run(repo, &layered).contains("@js-team"));
+    assert!(repo.join(".codeowners.cache").exists());
+
+    assert!(run(repo, &["codeowners", "cache", "clear", "--force"])
+        .ends_with(".codeowners.cache.overlay\n"));
+    assert!(!repo.join(".codeowners.cache.overlay").exists());
+}
+
+#[test]
+fn test_import() {
+    let dir = fixture();
+    let repo = dir.path();
+    let other_dir = TempDir::new().unwrap();
+    let other = other_dir.path().join("billing");
+    fs::create_dir_all(other.join("ui")).unwrap();
+    fs::write(other.join("CODEOWNERS"), "* @billing\n*.rs @billing-rust\n").unwrap();
+    fs::write(other.join("ui/CODEOWNERS"), "* @billing-ui\n").unwrap();
+    for file in ["main.rs", "README.md", "ui/app.js"] {
+        fs::write(other.join(file), "content\n").unwrap();
+    }
+
+    let other = other.to_str().unwrap();
+    let args = [
+        "codeowners",
+        "import",
+        "--from",
+        other,
+        "--prefix",
+        "src/web/billing/",
+    ];
+    assert_snapshot!("import_merged", run(repo, &args));
+    assert_eq!(
+        fs::read_to_string(repo.join("CODEOWNERS")).unwrap(),
+        ROOT_CODEOWNERS
+    );
+
+    let written = run(repo, &[&args[..], &["--write"]].concat());
+    assert_snapshot!("import_report", written);
+    let merged = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    assert!(merged.ends_with("/src/web/billing/ui/ @billing-ui\n"));
+}
+
+/// Objects of [`mock_store`] by path
+type Objects = Arc<std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>>;
+
+/// Serve a store of objects written with PUT and read with GET, sharing its contents
+fn mock_store() -> (String, Objects) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let objects = Arc::new(std::sync::Mutex::new(std::collections::HashMap::new()));
+    let stored = objects.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut parts = request_line.split_whitespace();
+            let method = parts.next().unwrap_or("").to_string();
+            let path = parts.next().unwrap_or("").to_string();
+            let mut objects = stored.lock().unwrap();
+            let (status, body) = match method.as_str() {
+                "PUT" => {
+                    objects.insert(path, body);
+                    ("200 OK", Vec::new())
+                }
+                _ => match objects.get(&path) {
+                    Some(object) => ("200 OK", object.clone()),
+                    None => ("404 Not Found", Vec::new()),
+                },
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    });
+    (url, objects)
+}
+
+#[test]
+fn test_remote_cache() {
+    let dir = fixture();
+    let repo = dir.path();
+    let (url, objects) = mock_store();
+    let remote = format!("{}/ci", url);
+
+    let pushed = run(repo, &["codeowners", "parse", "--push-cache", &remote]);
+    let object = {
+        let objects = objects.lock().unwrap();
+        assert_eq!(objects.len(), 1);
+        let (path, object) = objects.iter().next().unwrap();
+        assert!(path.starts_with("/ci/") && path.ends_with(".cache"));
+        assert!(pushed.ends_with(&format!("Pushed the cache to {}{}\n", url, path)));
+        object.clone()
+    };
+    assert_eq!(fs::read(repo.join(".codeowners.cache")).unwrap(), object);
+
+    // A missing cache is downloaded rather than built
+    run(repo, &["config", "set", "remote_cache", &remote]);
+    fs::remove_file(repo.join(".codeowners.cache")).unwrap();
+    assert!(run(repo, &["codeowners", "list-files"]).contains("src/web/app.js"));
+    assert_eq!(fs::read(repo.join(".codeowners.cache")).unwrap(), object);
+
+    // Without a cache for the current state, it is built locally
+    fs::write(repo.join("docs/new.md"), "content\n").unwrap();
+    commit_all(repo, "Add a page", "2024-02-01T00:00:00Z");
+    fs::remove_file(repo.join(".codeowners.cache")).unwrap();
+    assert!(run(repo, &["codeowners", "list-files"]).contains("docs/new.md"));
+    assert_eq!(objects.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn test_repos_registry() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    // The fixture's `parse` recorded the repository
+    let repos: serde_json::Value =
+        serde_json::from_str(&run(repo, &["repos", "list", "--format", "json"])).unwrap();
+    let repos = repos.as_array().unwrap();
+    assert_eq!(repos.len(), 1);
+    let path = repo.canonicalize().unwrap();
+    assert_eq!(repos[0]["path"], path.display().to_string());
+    assert_eq!(repos[0]["files"], FILES.len());
+    assert_eq!(repos[0]["cached_at"], "2024-01-01T00:00:00Z");
+    assert!(run(repo, &["repos"]).contains("2024-01-01 00:00"));
+
+    let report = run(repo, &["org", "report", "--format", "json"]);
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    assert_eq!(report["total_files"], FILES.len());
+
+    let refreshed = run(repo, &["repos", "refresh"]);
+    assert!(refreshed.starts_with(&format!("Refreshed {}: ", path.display())));
+
+    // With the registry off, caches are no longer recorded
+    run(repo, &["config", "set", "registry", "false"]);
+    fs::remove_file(repo.join(".config/codeinput/repos.json")).unwrap();
+    run(repo, &["codeowners", "parse"]);
+    assert!(run(repo, &["repos"]).starts_with("No repositories recorded"));
+}
+
+#[test]
+fn test_coverage() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("coverage_text", run(repo, &["codeowners", "coverage"]));
+    assert_snapshot!(
+        "coverage_json",
+        run(repo, &["codeowners", "coverage", "--format", "json"])
+    );
+    assert_snapshot!(
+        "coverage_min_fail_text",
+        run_failing(repo, &["codeowners", "coverage", "--min-coverage", "90"])
+    );
+}
+
+#[test]
+fn test_infer_owners_bots() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    fs::write(repo.join("scripts/build.sh"), "content\nbump\nbump\nbump\n").unwrap();
+    let status = std::process::Command::new("git")
+        .args([
+            "-c",
+            "user.name=dependabot[bot]",
+            "-c",
+            "user.email=49699333+dependabot[bot]@users.noreply.github.com",
+        ])
+        .args(["commit", "-q", "-am", "Bump dependencies"])
+        .env("GIT_AUTHOR_DATE", "2023-12-10T00:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2023-12-10T00:00:00Z")
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git commit failed");
+
+    let infer = |extra: &[&str]| {
+        let mut args = vec![
+            "codeowners",
+            "infer-owners",
+            "--min-commits",
+            "1",
+            "--lookback-days",
+            "0",
+        ];
+        args.extend(extra);
+        run(repo, &args)
+    };
+    let bot = "dependabot[bot]@users.noreply.github.com";
+
+    // The bot changed most of the file, but the other author is suggested
+    let output = infer(&[]);
+    assert!(!output.contains(bot), "{}", output);
+    assert!(output.contains("test@example.com"), "{}", output);
+
+    let output = infer(&["--include-bots"]);
+    assert!(output.contains(bot), "{}", output);
+
+    // Author globs ignore case
+    let output = infer(&["--include-bots", "--exclude-authors", "*@EXAMPLE.com"]);
+    assert!(output.contains(bot), "{}", output);
+    assert!(!output.contains("test@example.com"), "{}", output);
+
+    // An empty bot_authors setting keeps every author
+    run(repo, &["config", "set", "bot_authors", ""]);
+    let output = infer(&[]);
+    assert!(output.contains(bot), "{}", output);
+}
+
+#[test]
+fn test_infer_owners_exclusions() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    fs::write(
+        repo.join("scripts/build.sh"),
+        "content\nset -e\nmake\nmake test\n",
+    )
+    .unwrap();
+    let status = std::process::Command::new("git")
+        .args([
+            "-c",
+            "user.name=Contractor",
+            "-c",
+            "user.email=contractor@example.com",
+        ])
+        .args(["commit", "-q", "-am", "Build steps"])
+        .env("GIT_AUTHOR_DATE", "2023-12-10T00:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2023-12-10T00:00:00Z")
+        .current_dir(repo)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git commit failed");
+
+    let infer = |extra: &[&str]| {
+        // The whole history counts
+        let mut args = vec![
+            "codeowners",
+            "infer-owners",
+            "--min-commits",
+            "1",
+            "--lookback-days",
+            "0",
+        ];
+        args.extend(extra);
+        // Progress logs carry the time, so they stay out of the snapshots
+        run(repo, &args)
+            .lines()
+            .filter(|line| !line.contains(" INFO "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    assert_snapshot!("infer_owners_text", infer(&[]));
+    assert_snapshot!(
+        "infer_owners_commits_text",
+        infer(&["--algorithm", "commits"])
+    );
+    // The contractor's lines no longer count, so the other author owns the file
+    assert_snapshot!(
+        "infer_owners_exclude_author_text",
+        infer(&["--exclude-author", "Contractor@example.com"])
+    );
+    // The contractor made one commit, as did the other author, but more of the lines
+    assert_snapshot!(
+        "infer_owners_weighted_text",
+        infer(&["--algorithm", "weighted"])
+    );
+    // By commits alone, each has half of the file
+    assert_snapshot!(
+        "infer_owners_weighted_commits_text",
+        infer(&[
+            "--algorithm",
+            "weighted",
+            "--weight-lines",
+            "0",
+            "--weight-recency",
+            "0",
+        ])
+    );
+    run_failing(
+        repo,
+        &[
+            "codeowners",
+            "infer-owners",
+            "--algorithm",
+            "weighted",
+            "--weight-lines=-1",
+        ],
+    );
+    assert_snapshot!(
+        "infer_owners_exclude_path_text",
+        infer(&["--exclude-path", "scripts/**"])
+    );
+
+    // Both authors are aliased to one team, whose lines count together
+    let aliases = TempDir::new().unwrap();
+    let alias_file = aliases.path().join("aliases.toml");
+    fs::write(
+        &alias_file,
+        "\"test@example.com\" = \"@org/build\"\n\"Contractor@example.com\" = \"@org/build\"\n",
+    )
+    .unwrap();
+    let alias_file = alias_file.to_str().unwrap();
+    assert_snapshot!(
+        "infer_owners_aliases_text",
+        infer(&["--alias-file", alias_file])
+    );
+    let output = aliases.path().join("CODEOWNERS");
+    infer(&["--alias-file", alias_file, "-o", output.to_str().unwrap()]);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "./scripts/build.sh @org/build\n"
+    );
+    // Writing again merges into the rules already there instead of appending duplicates
+    infer(&["--alias-file", alias_file, "-o", output.to_str().unwrap()]);
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "./scripts/build.sh @org/build\n"
+    );
+    fs::write(&output, "# Build\nscripts/*.py @org/python\n").unwrap();
+    let dry_run = infer(&[
+        "--alias-file",
+        alias_file,
+        "-o",
+        output.to_str().unwrap(),
+        "--dry-run",
+    ]);
+    assert_snapshot!(
+        "infer_owners_dry_run_text",
+        dry_run.replace(aliases.path().to_str().unwrap(), "[TMP]")
+    );
+    assert_eq!(
+        fs::read_to_string(&output).unwrap(),
+        "# Build\nscripts/*.py @org/python\n"
+    );
+
+    // Every file of src shares the team, which gets one rule for the directory
+    let aggregated = aliases.path().join("CODEOWNERS.aggregated");
+    let aggregate = |extra: &[&str]| {
+        let mut args = vec![
+            "--scope",
+            "all",
+            "--alias-file",
+            alias_file,
+            "-o",
+            aggregated.to_str().unwrap(),
+            "--aggregate",
+            "directory",
+        ];
+        args.extend(extra);
+        infer(&args);
+        fs::read_to_string(&aggregated).unwrap()
+    };
+    assert_snapshot!("infer_owners_aggregate_directory", aggregate(&[]));
+    // With a file of src left out of the analysis, only src/api is rolled up
+    fs::remove_file(&aggregated).unwrap();
+    assert_snapshot!(
+        "infer_owners_aggregate_directory_excluded",
+        aggregate(&["--exclude-path", "src/web/**"])
+    );
+
+    // One of the two authors is a member, which meets the default threshold of half
+    let team_map = aliases.path().join("teams.toml");
+    fs::write(
+        &team_map,
+        "\"@org/build\" = [\"contractor@example.com\"]\n\"@org/qa\" = [\"@tester\"]\n",
+    )
+    .unwrap();
+    let team_map = team_map.to_str().unwrap();
+    assert_snapshot!(
+        "infer_owners_suggest_teams_text",
+        infer(&["--suggest-teams", "--team-map", team_map])
+    );
+    let output = infer(&[
+        "--suggest-teams",
+        "--team-map",
+        team_map,
+        "--team-threshold",
+        "60",
+    ]);
+    assert!(!output.contains("@org/build"), "{}", output);
+    // Without a team mapping there are no teams to suggest
+    run_failing(repo, &["codeowners", "infer-owners", "--suggest-teams"]);
+
+    // The contractor's address is mapped to the other author, whose lines it adds to
+    fs::write(
+        repo.join(".mailmap"),
+        "Test <test@example.com> <contractor@example.com>\n",
+    )
+    .unwrap();
+    assert_snapshot!("infer_owners_mailmap_text", infer(&[]));
+    assert_snapshot!(
+        "infer_owners_mailmap_commits_text",
+        infer(&["--algorithm", "commits"])
+    );
+
+    // A deadline that has already passed stops before any file, which is reported
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(repo)
+        .env("HOME", repo.join(".config"))
+        .env("XDG_CONFIG_HOME", repo.join(".config"))
+        .args([
+            "--stable-output",
+            "--timeout",
+            "0",
+            "codeowners",
+            "infer-owners",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(124));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Partial results: Timed out; 1 of 1 files were not analyzed"),
+        "{}",
+        stdout
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Timed out after 0 s"));
+}
+
+#[test]
+fn test_incremental_cache() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    let list = || run(repo, &["codeowners", "list-files", "--format", "json"]);
+    // Every file changed since the cache was built, so it is rebuilt
+    assert!(list().starts_with("Parsing CODEOWNERS files"));
+
+    // A new file is resolved on its own
+    fs::write(repo.join("src/extra.rs"), "content\n").unwrap();
+    git(repo, &["add", "src/extra.rs"]);
+    let listed = list();
+    assert!(!listed.contains("Parsing CODEOWNERS files"), "{}", listed);
+    assert!(listed.contains("./src/extra.rs"), "{}", listed);
+
+    // As is a file removed in a commit, with the new file committed
+    fs::remove_file(repo.join("src/lib.rs")).unwrap();
+    commit_all(repo, "Remove lib", "2023-12-02T00:00:00Z");
+    let listed = list();
+    assert!(!listed.contains("Parsing CODEOWNERS files"), "{}", listed);
+    assert!(!listed.contains("./src/lib.rs"), "{}", listed);
+    assert!(listed.contains("./src/extra.rs"), "{}", listed);
+    assert_eq!(list(), listed);
+
+    // A changed rule can affect any file, so the cache is rebuilt
+    fs::write(
+        repo.join("src/web/CODEOWNERS"),
+        "*.js @web-team @ux #frontend\n",
+    )
+    .unwrap();
+    let listed = list();
+    assert!(listed.starts_with("Parsing CODEOWNERS files"), "{}", listed);
+    assert!(listed.contains("@ux"), "{}", listed);
+}
+
+#[test]
+fn test_local_ignore_files() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(repo.join("notes.txt"), "todo\n").unwrap();
+    fs::write(repo.join("src/main.rs.orig"), "content\n").unwrap();
+    fs::write(repo.join(".git/info/exclude"), "notes.txt\n").unwrap();
+    // The global gitignore git reads without a core.excludesFile setting
+    fs::create_dir_all(repo.join(".config/git")).unwrap();
+    fs::write(repo.join(".config/git/ignore"), "*.orig\n").unwrap();
+
+    let unowned = |extra: &[&str]| {
+        let mut args = extra.to_vec();
+        args.extend(["codeowners", "list-files", "--unowned", "--format", "json"]);
+        run(repo, &args)
+    };
+    let listed = unowned(&[]);
+    assert!(!listed.contains("notes.txt"), "{}", listed);
+    assert!(!listed.contains("main.rs.orig"), "{}", listed);
+
+    // The cache built without them lists what a clean checkout would
+    let listed = unowned(&["--no-git-exclude"]);
+    assert!(listed.contains("notes.txt"), "{}", listed);
+    assert!(!listed.contains("main.rs.orig"), "{}", listed);
+    let listed = unowned(&["--no-global-gitignore", "--no-git-exclude"]);
+    assert!(listed.contains("notes.txt"), "{}", listed);
+    assert!(listed.contains("main.rs.orig"), "{}", listed);
+    let listed = unowned(&[]);
+    assert!(!listed.contains("notes.txt"), "{}", listed);
+}
+
+#[test]
+fn test_max_warnings() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners.push_str("src/{unclosed @nobody\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    fs::write(repo.join("scripts/deploy.sh"), "# !!!CODEOWNERS #wip\n").unwrap();
+
+    let ci = |args: &[&str]| {
+        Command::cargo_bin("ci")
+            .unwrap()
+            .current_dir(repo)
+            .env("HOME", repo.join(".config"))
+            .env("XDG_CONFIG_HOME", repo.join(".config"))
+            .arg("--stable-output")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let summary = "2 warnings: 1 skipped pattern, 1 unresolved inline marker";
+
+    let output = ci(&["--max-warnings", "2", "codeowners", "parse"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(summary), "{}", stderr);
+
+    let output = ci(&["--max-warnings", "1", "codeowners", "parse"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(summary), "{}", stderr);
+    assert!(
+        stderr.contains("2 warnings, more than --max-warnings 1"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_diff_refs() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    git(repo, &["branch", "base"]);
+    fs::write(
+        repo.join("CODEOWNERS"),
+        "*.md @org/docs #docs\n*.rs @rust-team @reviewers #rust\n/scripts/ @ops\n",
+    )
+    .unwrap();
+    fs::write(repo.join("src/api/auth.rs"), "content\n").unwrap();
+    commit_all(repo, "Reorganize ownership", "2023-12-10T00:00:00Z");
+
+    let diff = |format: &str| {
+        run(
+            repo,
+            &["codeowners", "diff", "--from", "base", "--format", format],
+        )
+    };
+    assert_snapshot!("diff_refs_text", diff("text"));
+    assert_snapshot!("diff_refs_json", diff("json"));
+    // The working directory does not count, only the committed refs
+    fs::write(repo.join("CODEOWNERS"), "* @everyone\n").unwrap();
+    run_failing(
+        repo,
+        &[
+            "codeowners",
+            "diff",
+            "--from",
+            "base",
+            "--to",
+            "HEAD",
+            "--exit-code",
+        ],
+    );
+    assert_snapshot!(
+        "diff_refs_unchanged_text",
+        run(repo, &["codeowners", "diff", "--from", "HEAD"])
+    );
+}
+
+#[test]
+fn test_gate() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "gate_pass_text",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "gate",
+                "--checks",
+                "validate,coverage:80,unowned:1"
+            ]
+        )
+    );
+    fs::write(
+        repo.join(".codeowners-policy.toml"),
+        "require_team = true\n",
+    )
+    .unwrap();
+    assert_snapshot!(
+        "gate_fail_text",
+        run_failing(
+            repo,
+            &["codeowners", "gate", "--checks", "unowned:0,policy"]
+        )
+    );
+}
+
+#[test]
+fn test_scope() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "scope_list_owners_text",
+        run(repo, &["codeowners", "list-owners", "--scope", "src"])
+    );
+    assert_snapshot!(
+        "scope_list_rules_text",
+        run(repo, &["codeowners", "list-rules", "--scope", "src/web"])
+    );
+    assert_snapshot!(
+        "scope_gate_text",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "gate",
+                "--checks",
+                "coverage:100",
+                "--scope",
+                "src"
+            ]
+        )
+    );
+    run_failing(repo, &["codeowners", "list-tags", "--scope", "missing"]);
+}
+
+#[test]
+fn test_validate() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!("validate_pass_text", run(repo, &["codeowners", "validate"]));
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners
+        .push_str("/build/\n*.lock lockfile-team\n*.sh #scripts @ops\nDELEGATE gone/ @org/gone\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    assert_snapshot!(
+        "validate_fail_text",
+        run_failing(repo, &["codeowners", "validate"])
+    );
+    assert_snapshot!(
+        "validate_fail_json",
+        run_failing(repo, &["codeowners", "validate", "--format", "json"])
+    );
+}
+
+#[test]
+fn test_expiry() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners.push_str("*.sh @ops # expires:2024-01-10\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners.clone()).unwrap();
+
+    // Stable output pins today to 2024-01-01
+    assert_snapshot!(
+        "expiry_validate_warning_text",
+        run(repo, &["codeowners", "validate"])
+    );
+    codeowners.push_str("docs/ @org/writers # expires:2023-12-15\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    run(repo, &["codeowners", "parse"]);
+    assert_snapshot!(
+        "expiry_validate_expired_text",
+        run_failing(repo, &["codeowners", "validate", "--warn-days", "5"])
+    );
+    assert_snapshot!(
+        "expiry_gate_text",
+        run_failing(repo, &["codeowners", "gate", "--checks", "validate"])
+    );
+}
+
+#[test]
+fn test_simulate_membership() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(
+        repo.join(".codeowners-teams.toml"),
+        "\"@org/docs\" = [\"@alice\", \"@bob\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        repo.join("reorg.toml"),
+        "[add]\n\"@org/docs\" = [\"@carol\"]\n\n[remove]\n\"@org/docs\" = [\"@bob\"]\n",
+    )
+    .unwrap();
+
+    assert_snapshot!(
+        "simulate_membership_text",
+        run(repo, &["codeowners", "simulate-membership", "reorg.toml"])
+    );
+    assert_snapshot!(
+        "simulate_membership_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "simulate-membership",
+                "reorg.toml",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_delegation() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners.push_str("DELEGATE src/web/ @web-team\nDELEGATE vendor/ @org/vendored\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "delegation_ls_text",
+        run(repo, &["codeowners", "ls", "src"])
+    );
+    assert_snapshot!(
+        "delegation_gate_text",
+        run_failing(repo, &["codeowners", "gate", "--checks", "validate"])
+    );
+}
+
+#[test]
+fn test_checklist() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    git(repo, &["branch", "base"]);
+
+    fs::write(repo.join("README.md"), "changed\n").unwrap();
+    fs::write(repo.join("src/api/routes.rs"), "changed\n").unwrap();
+    git(repo, &["rm", "-q", "src/lib.rs"]);
+    fs::write(
+        repo.join(".codeowners-checklist.toml"),
+        "item = \"- [ ] {path}\"\n[notes]\n\"#api\" = \"Check the API changelog\"\n",
+    )
+    .unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "checklist_text",
+        run(repo, &["codeowners", "checklist", "--base", "base"])
+    );
+    assert_snapshot!(
+        "checklist_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "checklist",
+                "--base",
+                "base",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_changed_files() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    git(repo, &["branch", "base"]);
+
+    fs::write(repo.join("README.md"), "changed\n").unwrap();
+    fs::write(repo.join("src/api/routes.rs"), "changed\n").unwrap();
+    fs::write(repo.join("scripts/build.sh"), "changed\n").unwrap();
+    git(repo, &["rm", "-q", "src/lib.rs"]);
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "changed_files_text",
+        run(repo, &["codeowners", "changed-files", "--base", "base"])
+    );
+    assert_snapshot!(
+        "changed_files_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "changed-files",
+                "--base",
+                "base",
+                "--format",
+                "json"
+            ]
+        )
+    );
+
+    // Paths piped in, as from `git diff --name-only`
+    let output = Command::cargo_bin("ci")
+        .unwrap()
+        .current_dir(repo)
+        .env("HOME", repo.join(".config"))
+        .env("XDG_CONFIG_HOME", repo.join(".config"))
+        .args(["--stable-output", "codeowners", "changed-files", "--stdin"])
+        .write_stdin("src/web/app.js\nsrc/main.rs\n\n")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_snapshot!(
+        "changed_files_stdin_text",
+        String::from_utf8(output.stdout).unwrap()
+    );
+}
+
+#[test]
+fn test_changed_files_deleted() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    git(repo, &["branch", "base"]);
+
+    // The change drops the rule of the file it deletes, and renames another away from it
+    git(repo, &["rm", "-q", "src/api/handler.rs"]);
+    git(repo, &["mv", "src/api/routes.rs", "src/routes.rs"]);
+    fs::write(
+        repo.join("CODEOWNERS"),
+        "*.md @org/docs #docs\n*.rs @rust-team #rust\n",
+    )
+    .unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "changed_files_deleted_text",
+        run(repo, &["codeowners", "changed-files", "--base", "base"])
+    );
+}
+
+#[test]
+fn test_branches() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-06-01T00:00:00Z");
+    git(repo, &["update-ref", "refs/remotes/origin/main", "HEAD"]);
+    git(
+        repo,
+        &[
+            "symbolic-ref",
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/main",
+        ],
+    );
+
+    for (branch, files, date) in [
+        (
+            "docs-typo",
+            &["docs/guide.md", "src/main.rs"][..],
+            "2023-08-01T00:00:00Z",
+        ),
+        (
+            "api-routes",
+            &["README.md", "src/api/routes.rs"][..],
+            "2023-12-20T00:00:00Z",
+        ),
+        ("web", &["src/web/app.js"][..], "2023-09-01T00:00:00Z"),
+    ] {
+        git(repo, &["checkout", "-q", "-b", branch]);
+        for file in files {
+            fs::write(repo.join(file), "changed\n").unwrap();
+        }
+        commit_all(repo, "Change", date);
+        git(
+            repo,
+            &[
+                "update-ref",
+                &format!("refs/remotes/origin/{}", branch),
+                "HEAD",
+            ],
+        );
+        git(repo, &["checkout", "-q", "-"]);
+    }
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "branches_text",
+        run(repo, &["codeowners", "branches", "@org/docs"])
+    );
+    assert_snapshot!(
+        "branches_json",
+        run(
+            repo,
+            &["codeowners", "branches", "@api-owner", "--format", "json"]
+        )
+    );
+
+    // Members of a team are responsible for its files
+    fs::write(
+        repo.join(".codeowners-teams.toml"),
+        "\"@org/docs\" = [\"@alice\"]\n",
+    )
+    .unwrap();
+    let output = run(
+        repo,
+        &["codeowners", "branches", "@alice", "--older-than", "30"],
+    );
+    assert!(output.contains("origin/docs-typo"), "{}", output);
+    assert!(!output.contains("origin/api-routes"), "{}", output);
+    assert!(run(repo, &["codeowners", "branches", "@nobody"])
+        .contains("No branches change files of @nobody since leaving origin/main"));
+}
+
+#[test]
+fn test_rules_filtered_by_tag() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    // Without the #api rule, the API sources fall back to the Rust rule
+    assert_snapshot!(
+        "ignore_rules_tagged_text",
+        run(
+            repo,
+            &["codeowners", "list-files", "--ignore-rules-tagged", "#api"]
+        )
+    );
+    let output = run(
+        repo,
+        &[
+            "codeowners",
+            "list-owners",
+            "--only-rules-tagged",
+            "docs,frontend",
+        ],
+    );
+    assert!(output.contains("@org/docs"), "{}", output);
+    assert!(output.contains("@web-team"), "{}", output);
+    assert!(!output.contains("@rust-team"), "{}", output);
+
+    // The cache keeps every rule
+    let output = run(repo, &["codeowners", "inspect", "src/api/routes.rs"]);
+    assert!(output.contains("@api-owner"), "{}", output);
+}
+
+#[test]
+fn test_central_ownership() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+
+    let central = TempDir::new().unwrap();
+    git(central.path(), &["init", "-q", "."]);
+    fs::write(
+        central.path().join("CODEOWNERS"),
+        "*.md @central-docs\nscripts/ @platform #build\n",
+    )
+    .unwrap();
+    commit_all(central.path(), "Baseline ownership", "2023-12-01T00:00:00Z");
+    let central_path = central.path().to_str().unwrap();
+
+    // Local rules win; the unowned script gets its owner from the central repository
+    run(repo, &["config", "set", "central_repo", central_path]);
+    assert_snapshot!(
+        "central_ownership_text",
+        run(repo, &["codeowners", "list-files"])
+    );
+    let report = run(repo, &["codeowners", "parse", "--report"]);
+    assert!(
+        report.contains(".codeowners-central/CODEOWNERS"),
+        "{}",
+        report
+    );
+
+    // Central rules win where they match
+    run(repo, &["config", "set", "central_precedence", "override"]);
+    let output = run(
+        repo,
+        &["codeowners", "list-files", "--owners", "@central-docs"],
+    );
+    assert!(output.contains("README.md"), "{}", output);
+    assert!(!output.contains("@org/docs"), "{}", output);
+
+    // A URL is fetched into a mirror, which is read while the remote is gone
+    let url = format!("file://{}", central_path);
+    run(repo, &["config", "set", "central_repo", &url]);
+    run(repo, &["config", "set", "central_precedence", "baseline"]);
+    run(repo, &["codeowners", "parse"]);
+    drop(central);
+    let output = run(repo, &["codeowners", "list-files", "--owners", "@platform"]);
+    assert!(output.contains("scripts/build.sh"), "{}", output);
+}
+
+#[test]
+fn test_churn() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-06-01T00:00:00Z");
+
+    let codeowners = repo.join("CODEOWNERS");
+    for (owner, date) in [
+        ("@api-team", "2023-11-01T00:00:00Z"),
+        ("@platform", "2023-11-15T00:00:00Z"),
+        ("@api-team", "2023-12-20T00:00:00Z"),
+    ] {
+        let contents = fs::read_to_string(&codeowners).unwrap();
+        let (head, _) = contents.split_once("src/api/**").unwrap();
+        fs::write(
+            &codeowners,
+            format!("{}src/api/** {} #api\nscripts/ @ops\n", head, owner),
+        )
+        .unwrap();
+        commit_all(repo, "Move the API", date);
+    }
+
+    assert_snapshot!("churn_text", run(repo, &["codeowners", "churn"]));
+    assert_snapshot!(
+        "churn_json",
+        run(
+            repo,
+            &["codeowners", "churn", "--days", "30", "--format", "json"]
+        )
+    );
+}
+
+#[test]
+fn test_heatmap() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-06-01T00:00:00Z");
+    for (contents, date) in [
+        ("fn main() {}\n", "2023-12-01T00:00:00Z"),
+        ("fn main() {\n    run();\n}\n", "2023-12-20T00:00:00Z"),
+    ] {
+        fs::write(repo.join("src/main.rs"), contents).unwrap();
+        commit_all(repo, "Change main", date);
+    }
+
+    assert_snapshot!(
+        "heatmap_csv",
+        run(repo, &["codeowners", "heatmap", "--format", "csv"])
+    );
+    assert_snapshot!(
+        "heatmap_json",
+        run(repo, &["codeowners", "heatmap", "--days", "30"])
+    );
+}
+
+#[test]
+fn test_export_paths() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "export_paths_list",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "export-paths",
+                "--owners",
+                "@API-owner,@web-team"
+            ]
+        )
+    );
+    // A tarball only goes to a file
+    run_failing(
+        repo,
+        &[
+            "codeowners",
+            "export-paths",
+            "--owners",
+            "@web-team",
+            "--format",
+            "tar",
+        ],
+    );
+
+    let out = TempDir::new().unwrap();
+    let tarball = out.path().join("api.tar");
+    let exported = run(
+        repo,
+        &[
+            "codeowners",
+            "export-paths",
+            "--owners",
+            "@api-owner",
+            "--to",
+            tarball.to_str().unwrap(),
+        ],
+    );
+    assert!(exported.starts_with("Exported 2 files owned by @api-owner to "));
+    let listing = std::process::Command::new("tar")
+        .arg("-tf")
+        .arg(&tarball)
+        .output()
+        .unwrap();
+    assert!(listing.status.success(), "tar -tf failed");
+    assert_eq!(
+        String::from_utf8(listing.stdout).unwrap(),
+        "src/api/handler.rs\nsrc/api/routes.rs\n"
+    );
+}
+
+#[test]
+fn test_impact() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "impact_text",
+        run(repo, &["codeowners", "impact", "@rust-team"])
+    );
+    assert_snapshot!(
+        "impact_json",
+        run(
+            repo,
+            &[
+                "codeowners",
+                "impact",
+                "@API-owner",
+                "--no-suggest",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+/// Serve the GitHub API endpoints `compare-github` reads, returning the base URL
+///
+/// The CODEOWNERS file is served from `.github/`; every other path answers 404.
+fn mock_github(codeowners: &'static str, errors: &'static str) -> (String, Arc<AtomicUsize>) {
+    mock_api(vec![
+        ("/contents/.github/CODEOWNERS", "200 OK", codeowners),
+        ("/codeowners/errors", "200 OK", errors),
+    ])
+}
+
+/// Serve each `(suffix, status, body)` route for the paths ending in its suffix, with or
+/// without their query, returning the base URL
+///
+/// Unrouted paths answer 404. Responses carry an ETag, and requests presenting it get 304
+/// Not Modified when they would succeed; those are counted in the returned counter.
+fn mock_api(routes: Vec<(&'static str, &'static str, &'static str)>) -> (String, Arc<AtomicUsize>) {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let revalidated = Arc::new(AtomicUsize::new(0));
+    let counter = revalidated.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut if_none_match = false;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if_none_match |= header.to_lowercase().starts_with("if-none-match: \"v1\"");
+            }
+
+            let target = request_line.split_whitespace().nth(1).unwrap_or("");
+            let path = target.split('?').next().unwrap();
+            let (status, body) = routes
+                .iter()
+                .find(|(suffix, _, _)| target.ends_with(suffix) || path.ends_with(suffix))
+                .map(|&(_, status, body)| (status, body))
+                .unwrap_or(("404 Not Found", r#"{"message":"Not Found"}"#));
+            let (status, body) = if if_none_match && status.starts_with('2') {
+                counter.fetch_add(1, Ordering::SeqCst);
+                ("304 Not Modified", "")
+            } else {
+                (status, body)
+            };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    (url, revalidated)
+}
+
+#[test]
+fn test_compare_github() {
+    let dir = fixture();
+    let repo = dir.path();
+    // GitHub rejects the api@example.com owner, which is not verified for the repository
+    let (url, revalidated) = mock_github(
+        ROOT_CODEOWNERS,
+        r#"{"errors":[{"line":4,"column":24,"kind":"Unknown owner","message":"Unknown owner on line 4: make sure api@example.com exists\n","path":".github/CODEOWNERS"}]}"#,
+    );
+    run(repo, &["config", "set", "github_api_url", &url]);
+    let args = [
+        "codeowners",
+        "compare-github",
+        "--ref",
+        "main",
+        "--github-repo",
+        "acme/fixture",
+        "--sample",
+        "0",
+    ];
+
+    let report = run_failing(repo, &args);
+    assert_snapshot!("compare_github_text", report);
+
+    // A second run revalidates the cached responses, and an offline run only reads them
+    assert_eq!(run_failing(repo, &args), report);
+    assert_eq!(revalidated.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        run_failing(repo, &[&args[..], &["--offline"]].concat()),
+        report
+    );
+}
+
+#[test]
+fn test_infer_owners_reviews() {
+    let dir = fixture();
+    let repo = dir.path();
+    commit_all(repo, "Initial", "2023-12-01T00:00:00Z");
+    // The bot and the author commenting on their own pull request get no credit
+    let (url, _) = mock_api(vec![
+        (
+            "/repos/acme/fixture/pulls",
+            "200 OK",
+            r#"[{"number":7,"user":{"login":"test"},"merged_at":"2023-12-15T00:00:00Z","updated_at":"2023-12-15T00:00:00Z"},{"number":8,"user":{"login":"test"},"merged_at":null,"updated_at":"2023-12-14T00:00:00Z"}]"#,
+        ),
+        (
+            "/pulls/7/files",
+            "200 OK",
+            r#"[{"filename":"scripts/build.sh"}]"#,
+        ),
+        (
+            "/pulls/7/reviews",
+            "200 OK",
+            r#"[{"user":{"login":"reviewer"},"state":"APPROVED"},{"user":{"login":"dependabot[bot]"},"state":"COMMENTED"},{"user":{"login":"test"},"state":"COMMENTED"}]"#,
+        ),
+    ]);
+    run(repo, &["config", "set", "github_api_url", &url]);
+    let args = [
+        "codeowners",
+        "infer-owners",
+        "--min-commits",
+        "1",
+        "--lookback-days",
+        "0",
+        "--reviews",
+        "--github-repo",
+        "acme/fixture",
+        "--review-weight",
+        "0.6",
+    ];
+    let infer = |extra: &[&str]| {
+        run(repo, &[&args[..], extra].concat())
+            .lines()
+            .filter(|line| !line.contains(" INFO "))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let report = infer(&[]);
+    assert_snapshot!("infer_owners_reviews_text", report);
+    assert_eq!(infer(&["--offline"]), report);
+}
+
+#[test]
+fn test_verify_owners() {
+    let dir = fixture();
+    let repo = dir.path();
+    // @api-owner does not exist, @web-team is an organization and @org/docs cannot access
+    // the repository
+    let (url, _) = mock_api(vec![
+        (
+            "/users/rust-team",
+            "200 OK",
+            r#"{"login":"rust-team","type":"User"}"#,
+        ),
+        (
+            "/users/web-team",
+            "200 OK",
+            r#"{"login":"web-team","type":"Organization"}"#,
+        ),
+        ("/orgs/org/teams/docs", "200 OK", r#"{"slug":"docs"}"#),
+    ]);
+    run(repo, &["config", "set", "github_api_url", &url]);
+    let args = [
+        "codeowners",
+        "verify-owners",
+        "--github-repo",
+        "acme/fixture",
+    ];
+
+    assert_snapshot!("verify_owners_text", run_failing(repo, &args));
+    assert_snapshot!(
+        "verify_owners_json",
+        run_failing(
+            repo,
+            &[&args[..], &["--offline", "--format", "json"]].concat()
+        )
+    );
+}
+
+#[test]
+fn test_verify_owners_gitlab() {
+    let dir = fixture();
+    let repo = dir.path();
+    // @api-owner does not exist, @web-team is a group without access to the project, which
+    // is shared with @org/docs
+    let (url, _) = mock_api(vec![
+        (
+            "/projects/acme%2Ffixture",
+            "200 OK",
+            r#"{"namespace":{"full_path":"acme"},"shared_with_groups":[{"group_full_path":"org/docs"}]}"#,
+        ),
+        (
+            "/users?username=rust-team",
+            "200 OK",
+            r#"[{"username":"rust-team","state":"active"}]"#,
+        ),
+        ("/users", "200 OK", "[]"),
+        ("/groups/web-team", "200 OK", r#"{"full_path":"web-team"}"#),
+        (
+            "/groups/org%2Fdocs",
+            "200 OK",
+            r#"{"full_path":"org/docs"}"#,
+        ),
+        (
+            "/groups/org%2Fdocs/members/all",
+            "200 OK",
+            r#"[{"username":"dana","state":"active"},{"username":"eve","state":"blocked"}]"#,
+        ),
+    ]);
+    run(repo, &["config", "set", "gitlab_api_url", &url]);
+    let args = [
+        "codeowners",
+        "verify-owners",
+        "--provider",
+        "gitlab",
+        "--gitlab-project",
+        "acme/fixture",
+        "--expand-groups",
+    ];
+
+    assert_snapshot!("verify_owners_gitlab_text", run_failing(repo, &args));
+    assert_snapshot!(
+        "verify_owners_gitlab_json",
+        run_failing(
+            repo,
+            &[&args[..], &["--offline", "--format", "json"]].concat()
+        )
+    );
+}
+
+#[test]
+fn test_org_report() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::write(
+        repo.join("ws.toml"),
+        "[[repo]]\nname = \"fixture\"\npath = \".\"\n\n[[repo]]\npath = \"missing\"\n",
+    )
+    .unwrap();
+
+    assert_snapshot!(
+        "org_report_text",
+        run(repo, &["org", "report", "--workspace", "ws.toml"])
+    );
+    assert_snapshot!(
+        "org_report_json",
+        run(
+            repo,
+            &[
+                "org",
+                "report",
+                "--workspace",
+                "ws.toml",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_snapshot() {
+    let dir = fixture();
+    let repo = dir.path();
+
+    assert_snapshot!(
+        "snapshot_save_text",
+        run(repo, &["codeowners", "snapshot", "save"])
+    );
+    // Two older snapshots taken on the same day: only the later one is kept
+    let snapshots = repo.join(".codeowners-snapshots");
+    let saved = fs::read_to_string(snapshots.join("20240101T000000Z.json")).unwrap();
+    for time in ["20231231T100000Z", "20231231T200000Z"] {
+        let taken_at = format!("2023-12-31T{}:00:00Z", &time[9..11]);
+        fs::write(
+            snapshots.join(format!("{}.json", time)),
+            saved.replace("2024-01-01T00:00:00Z", &taken_at),
+        )
+        .unwrap();
+    }
+
+    assert_snapshot!(
+        "snapshot_gc_text",
+        run(repo, &["codeowners", "snapshot", "gc"])
+    );
+    assert_snapshot!(
+        "snapshot_list_text",
+        run(repo, &["codeowners", "snapshot", "list"])
+    );
+}
+
+#[test]
+fn test_demo() {
+    let dir = TempDir::new().unwrap();
+    let demo = dir.path().join("demo");
+    let generated = run(dir.path(), &["demo", "--generate", "demo"]);
+    assert_snapshot!("demo_text", generated);
+
+    assert_snapshot!(
+        "demo_list_files_text",
+        run(&demo, &["codeowners", "list-files", "--show-all"])
+    );
+    assert_snapshot!(
+        "demo_ls_services_text",
+        run(&demo, &["codeowners", "ls", "services"])
+    );
+    assert_snapshot!(
+        "demo_list_tags_co_occurrence_text",
+        run(&demo, &["codeowners", "list-tags", "--co-occurrence"])
+    );
+    assert_snapshot!(
+        "demo_list_rules_by_source_text",
+        run(&demo, &["codeowners", "list-rules", "--by-source"])
+    );
+    assert_snapshot!(
+        "demo_tag_rename_dry_run_text",
+        run(
+            &demo,
+            &[
+                "codeowners",
+                "tag",
+                "rename",
+                "critical",
+                "#sev1",
+                "--dry-run"
+            ]
+        )
+    );
+    assert_snapshot!(
+        "demo_tag_apply_existing_rule_dry_run_text",
+        run(
+            &demo,
+            &[
+                "codeowners",
+                "tag",
+                "apply",
+                "#infra",
+                "--pattern",
+                "/services/api/",
+                "--dry-run"
+            ]
+        )
+    );
+    assert_snapshot!(
+        "demo_tag_apply_new_rule_dry_run_text",
+        run(
+            &demo,
+            &[
+                "codeowners",
+                "tag",
+                "apply",
+                "infra",
+                "--pattern",
+                "services/web/src/**",
+                "--dry-run"
+            ]
+        )
+    );
+    assert_snapshot!(
+        "demo_list_rules_by_source_json",
+        run(
+            &demo,
+            &[
+                "codeowners",
+                "list-rules",
+                "--by-source",
+                "--format",
+                "json"
+            ]
+        )
+    );
+}
+
+#[test]
+fn test_bitbucket_dialect() {
+    let dir = fixture();
+    let repo = dir.path();
+    fs::create_dir_all(repo.join(".bitbucket")).unwrap();
+    fs::write(
+        repo.join(".bitbucket/CODEOWNERS"),
+        "* @@platform\n/src/ @@@backend-reviewers @alice\n",
+    )
+    .unwrap();
+
+    // Only .bitbucket/CODEOWNERS is read, scoped to the repository root
+    assert_snapshot!(
+        "bitbucket_list_rules_text",
+        run(
+            repo,
+            &["--dialect", "bitbucket", "codeowners", "list-rules"]
+        )
+    );
+    assert_snapshot!(
+        "bitbucket_list_owners_text",
+        run(
+            repo,
+            &["--dialect", "bitbucket", "codeowners", "list-owners"]
+        )
+    );
+}
+
+#[test]
+fn test_negation() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners.push_str("!src/api/routes.rs\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    // The negation takes routes.rs out of every rule above it
+    assert_snapshot!(
+        "negation_list_files_text",
+        run(repo, &["codeowners", "list-files"])
+    );
+}
+
+#[test]
+fn test_list_owners_by_type() {
+    let dir = fixture();
+    let repo = dir.path();
+    let mut codeowners = fs::read_to_string(repo.join("CODEOWNERS")).unwrap();
+    codeowners.push_str("*.sh @ops Ops@example.com\n");
+    fs::write(repo.join("CODEOWNERS"), codeowners).unwrap();
+    run(repo, &["codeowners", "parse"]);
+
+    assert_snapshot!(
+        "list_owners_by_type_text",
+        run(repo, &["codeowners", "list-owners", "--by-type"])
+    );
+    assert_snapshot!(
+        "list_owners_by_type_json",
+        run(
+            repo,
+            &["codeowners", "list-owners", "--by-type", "--format", "json"]
+        )
+    );
+}
+
+#[test]
+fn test_complete() {
+    let dir = fixture();
+    let repo = dir.path();
+    run(repo, &["codeowners", "parse"]);
+
+    let complete = |position: &str, line: &[&str]| {
+        let mut args = vec!["complete", "--position", position, "--", "codeinput"];
+        args.extend(line);
+        run(repo, &args)
+    };
+    assert_snapshot!(
+        "complete_owners",
+        complete("4", &["codeowners", "list-files", "--owners", "@"])
+    );
+    assert_snapshot!(
+        "complete_tags",
+        complete("3", &["codeowners", "list-files", "--tags=docs,r"])
+    );
+    assert_snapshot!(
+        "complete_paths",
+        complete("3", &["codeowners", "inspect", "src/"])
+    );
+    // Subcommand names are left to the static completion
+    assert_eq!(complete("2", &["codeowners", "li"]), "");
+    assert!(run(repo, &["completion", "bash"]).contains("codeinput complete --position"));
+}
@@ -1,5 +1,5 @@
 use codeinput::core::owner_resolver::{find_files_for_owner, find_owners_for_file};
-use codeinput::core::types::{CodeownersEntry, FileEntry, Owner, OwnerType, Tag};
+use codeinput::core::types::{CodeownersEntry, EntrySpans, FileEntry, Owner, OwnerType};
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::hint::black_box;
 use std::path::{Path, PathBuf};
@@ -16,6 +16,8 @@ fn create_test_file_entry(path: &str, owners: Vec<Owner>) -> FileEntry {
         path: PathBuf::from(path),
         owners,
         tags: vec![],
+        generated: false,
+        inline: None,
     }
 }
 
@@ -28,6 +30,9 @@ fn create_test_codeowners_entry(
         pattern: pattern.to_string(),
         owners,
         tags: vec![],
+        spans: EntrySpans::default(),
+        metadata: Default::default(),
+        section: None,
     }
 }
 
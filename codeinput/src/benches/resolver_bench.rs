@@ -1,6 +1,7 @@
 use codeinput::core::resolver::find_owners_and_tags_for_file;
 use codeinput::core::types::{
-    codeowners_entry_to_matcher, CodeownersEntry, CodeownersEntryMatcher, Owner, OwnerType, Tag,
+    codeowners_entry_to_matcher, CodeownersEntry, CodeownersEntryMatcher, EntrySpans, Owner,
+    OwnerType, Tag,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::hint::black_box;
@@ -26,6 +27,9 @@ fn create_test_codeowners_entry_matcher(
         pattern: pattern.to_string(),
         owners,
         tags,
+        spans: EntrySpans::default(),
+        metadata: Default::default(),
+        section: None,
     };
     codeowners_entry_to_matcher(&entry)
 }
@@ -1,5 +1,5 @@
 use codeinput::core::tag_resolver::{find_files_for_tag, find_tags_for_file};
-use codeinput::core::types::{CodeownersEntry, FileEntry, Owner, OwnerType, Tag};
+use codeinput::core::types::{CodeownersEntry, EntrySpans, FileEntry, Tag};
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::hint::black_box;
 use std::path::{Path, PathBuf};
@@ -8,18 +8,13 @@ fn create_test_tag(name: &str) -> Tag {
     Tag(name.to_string())
 }
 
-fn create_test_owner(identifier: &str, owner_type: OwnerType) -> Owner {
-    Owner {
-        identifier: identifier.to_string(),
-        owner_type,
-    }
-}
-
 fn create_test_file_entry(path: &str, tags: Vec<Tag>) -> FileEntry {
     FileEntry {
         path: PathBuf::from(path),
         owners: vec![],
         tags,
+        generated: false,
+        inline: None,
     }
 }
 
@@ -32,6 +27,9 @@ fn create_test_codeowners_entry(
         pattern: pattern.to_string(),
         owners: vec![],
         tags,
+        spans: EntrySpans::default(),
+        metadata: Default::default(),
+        section: None,
     }
 }
 
@@ -411,7 +409,7 @@ fn bench_find_tags_for_file_deep_hierarchy(c: &mut Criterion) {
     let mut entries = Vec::new();
 
     // Create entries for different levels of hierarchy
-    let hierarchy_levels = vec![
+    let hierarchy_levels = [
         ("*", "root"),
         ("src/**/*", "source"),
         ("src/backend/**/*", "backend"),
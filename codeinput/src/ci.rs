@@ -0,0 +1,272 @@
+//! Ownership checks for Rust build tooling
+//!
+//! [`enforce`] runs the checks of `codeowners gate` from an xtask or a custom CI binary
+//! and hands back what it found instead of printing it. Everything it needs comes in
+//! [`PolicyOptions`]: it reads no global configuration and neither reads nor writes the
+//! cache file.
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use codeinput::ci::{enforce, PolicyOptions};
+//!
+//! # fn main() -> codeinput::utils::error::Result<()> {
+//! let options = PolicyOptions {
+//!     require_owned: true,
+//!     ..PolicyOptions::default()
+//! };
+//! let violations = enforce(Path::new("."), options)?;
+//! for violation in &violations {
+//!     eprintln!("{}", violation);
+//! }
+//! if !violations.is_empty() {
+//!     std::process::exit(1);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::core::context::Context;
+use crate::core::expiry::ExpiryWindow;
+use crate::core::parse::resolve_paths;
+use crate::core::policy::Policy;
+use crate::core::types::OwnerType;
+use crate::core::validate::validate_repo;
+use crate::utils::app_config::AppConfig;
+use crate::utils::error::Result;
+use crate::utils::output;
+
+/// What [`enforce`] checks, and the settings it resolves owners with
+#[derive(Debug, Clone)]
+pub struct PolicyOptions {
+    /// Settings owners are resolved with, as the CLI reads them from its configuration
+    pub config: AppConfig,
+    /// Requirements for the owners of each file; `None` reads the repository's
+    /// `policy_file`, if it has one
+    pub policy: Option<Policy>,
+    /// Report problems of the CODEOWNERS files, as `codeowners validate`
+    pub validate: bool,
+    /// Report every file without an owner, except generated ones
+    pub require_owned: bool,
+}
+
+impl Default for PolicyOptions {
+    fn default() -> Self {
+        PolicyOptions {
+            config: AppConfig::default(),
+            policy: None,
+            validate: true,
+            require_owned: false,
+        }
+    }
+}
+
+/// Which check a [`Violation`] failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// A CODEOWNERS line or delegation with a problem
+    Rule,
+    /// A file without an owner
+    Unowned,
+    /// A file or rule breaking the [`Policy`]
+    Policy,
+}
+
+/// One thing wrong with the ownership of a repository
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    /// File or CODEOWNERS file, relative to the repository
+    pub path: PathBuf,
+    /// 1-based line of a CODEOWNERS file, if the violation is at one
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    /// `path:line: message`, as [`Problem`](crate::core::validate::Problem) reads
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+/// Everything [`enforce`] found, rule problems first, then unowned files, then policy
+/// violations
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Violations {
+    /// Number of files checked
+    pub files: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl Violations {
+    /// Whether every check passed
+    pub fn is_empty(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.violations.len()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Violation> {
+        self.violations.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Violations {
+    type Item = &'a Violation;
+    type IntoIter = std::slice::Iter<'a, Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.violations.iter()
+    }
+}
+
+impl IntoIterator for Violations {
+    type Item = Violation;
+    type IntoIter = std::vec::IntoIter<Violation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.violations.into_iter()
+    }
+}
+
+/// Check the ownership of the repository at `path` as `options` asks
+///
+/// Only failing to read the repository or its policy is an error; whatever the checks
+/// find comes back as [`Violations`]. Rule problems are only errors, not expiry warnings.
+pub fn enforce(path: &Path, options: PolicyOptions) -> Result<Violations> {
+    let ctx = Context::new(options.config);
+    let relative = |file: &Path| -> PathBuf {
+        let file = file.strip_prefix(path).unwrap_or(file);
+        file.strip_prefix(".").unwrap_or(file).to_path_buf()
+    };
+
+    let mut violations = Vec::new();
+    if options.validate {
+        let expiry = ExpiryWindow {
+            today: output::now().date_naive(),
+            warning_days: 0,
+        };
+        let (_, problems) = validate_repo(path, ctx.config.match_options(), expiry)?;
+        violations.extend(
+            problems
+                .into_iter()
+                .filter(|problem| !problem.is_warning())
+                .map(|problem| Violation {
+                    kind: ViolationKind::Rule,
+                    path: relative(&problem.source_file),
+                    line: problem.line,
+                    message: problem.message,
+                }),
+        );
+    }
+
+    let cache = resolve_paths(&ctx, path, &[PathBuf::new()])?;
+    if options.require_owned {
+        violations.extend(
+            cache
+                .files
+                .iter()
+                .filter(|file| {
+                    !file.generated
+                        && file
+                            .owners
+                            .iter()
+                            .all(|owner| owner.owner_type == OwnerType::Unowned)
+                })
+                .map(|file| Violation {
+                    kind: ViolationKind::Unowned,
+                    path: relative(&file.path),
+                    line: None,
+                    message: "no owner".to_string(),
+                }),
+        );
+    }
+
+    let policy = match options.policy {
+        Some(policy) => Some(policy),
+        None => Policy::for_repo(&ctx, path)?,
+    };
+    if let Some(policy) = policy {
+        violations.extend(
+            policy
+                .violations(&cache)
+                .into_iter()
+                .map(|violation| Violation {
+                    kind: ViolationKind::Policy,
+                    path: relative(&violation.path),
+                    line: None,
+                    message: violation.message,
+                }),
+        );
+    }
+
+    Ok(Violations {
+        files: cache.files.len(),
+        violations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_enforce() {
+        let repo = TempDir::new().unwrap();
+        std::fs::write(
+            repo.path().join("CODEOWNERS"),
+            "*.rs @alice\nDELEGATE missing/ @org/core\n",
+        )
+        .unwrap();
+        std::fs::write(repo.path().join("lib.rs"), "").unwrap();
+        std::fs::write(repo.path().join("README.md"), "").unwrap();
+
+        let violations = enforce(
+            repo.path(),
+            PolicyOptions {
+                policy: Some(Policy {
+                    require_team: true,
+                    ..Policy::default()
+                }),
+                require_owned: true,
+                ..PolicyOptions::default()
+            },
+        )
+        .unwrap();
+        let found: Vec<(ViolationKind, String)> = violations
+            .iter()
+            .map(|violation| (violation.kind, violation.to_string()))
+            .collect();
+        assert_eq!(violations.files, 2);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].0, ViolationKind::Rule);
+        assert!(found[0].1.starts_with("CODEOWNERS:2: "));
+        assert_eq!(
+            found[1..],
+            [
+                (ViolationKind::Unowned, "README.md: no owner".to_string()),
+                (ViolationKind::Policy, "lib.rs: no team owner".to_string()),
+            ]
+        );
+
+        let lenient = PolicyOptions {
+            validate: false,
+            ..PolicyOptions::default()
+        };
+        assert!(enforce(repo.path(), lenient).unwrap().is_empty());
+    }
+}
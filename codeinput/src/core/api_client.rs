@@ -0,0 +1,405 @@
+//! HTTP client shared by the integrations with code hosts such as GitHub
+//!
+//! Requests carry the host's token, wait out rate limits and server errors, and keep the
+//! last response of every GET on disk with its ETag. Cached responses are revalidated with
+//! `If-None-Match`, which GitHub does not count against the rate limit, and answer every
+//! request in offline mode.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::utils::error::{Error, Result};
+use crate::utils::warnings::{self, WarningKind};
+
+/// Attempts per request, including the first
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Longest wait for a rate limit to reset or a server to recover before giving up
+const MAX_WAIT_SECS: u64 = 60;
+
+/// How requests authenticate with the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth {
+    /// `Authorization: Bearer <token>`, as GitHub expects
+    Bearer(String),
+    /// The token in a custom header, such as GitLab's `PRIVATE-TOKEN`
+    Header { name: String, value: String },
+}
+
+impl Auth {
+    fn secret(&self) -> &str {
+        match self {
+            Auth::Bearer(token) => token,
+            Auth::Header { value, .. } => value,
+        }
+    }
+}
+
+/// A successful response, fresh or from the cache
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: String,
+    /// Whether the body came from the cache, revalidated or offline
+    pub cached: bool,
+}
+
+impl ApiResponse {
+    /// Deserialize the body as JSON
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.body)
+            .map_err(|e| Error::new(&format!("Invalid JSON in API response: {}", e)))
+    }
+}
+
+/// A response kept on disk; 404s are kept too so offline mode can answer them
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    url: String,
+    etag: Option<String>,
+    status: u16,
+    body: String,
+}
+
+impl CachedResponse {
+    fn into_response(self) -> Option<ApiResponse> {
+        (self.status != 404).then_some(ApiResponse {
+            status: self.status,
+            body: self.body,
+            cached: true,
+        })
+    }
+}
+
+/// Client for the REST API of one host
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    base_url: String,
+    headers: Vec<(String, String)>,
+    auth: Option<Auth>,
+    cache_dir: Option<PathBuf>,
+    offline: bool,
+}
+
+/// Directory responses are cached in unless the client is given another
+pub fn default_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("codeinput").join("api"))
+}
+
+impl ApiClient {
+    /// Client for the API at `base_url`, caching in [`default_cache_dir`]
+    pub fn new(base_url: &str) -> ApiClient {
+        ApiClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: Vec::new(),
+            auth: None,
+            cache_dir: default_cache_dir(),
+            offline: false,
+        }
+    }
+
+    /// Send `name: value` with every request
+    pub fn header(mut self, name: &str, value: &str) -> ApiClient {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Authenticate requests; `None` sends them anonymously
+    pub fn auth(mut self, auth: Option<Auth>) -> ApiClient {
+        self.auth = auth;
+        self
+    }
+
+    /// Cache responses in `dir`, or not at all with `None`
+    pub fn cache_dir(mut self, dir: Option<PathBuf>) -> ApiClient {
+        self.cache_dir = dir;
+        self
+    }
+
+    /// Answer every request from the cache without touching the network
+    pub fn offline(mut self, offline: bool) -> ApiClient {
+        self.offline = offline;
+        self
+    }
+
+    /// GET `path` below the base URL; `None` when the host answers 404
+    pub fn get(
+        &self, path: &str, query: &[(&str, &str)], accept: &str,
+    ) -> Result<Option<ApiResponse>> {
+        let mut request = ureq::get(&format!("{}{}", self.base_url, path)).set("Accept", accept);
+        for (name, value) in query {
+            request = request.query(name, value);
+        }
+        let url = request
+            .request_url()
+            .map(|url| url.as_url().to_string())
+            .map_err(|e| Error::new(&format!("Invalid API URL: {}", e)))?;
+        let key = self.cache_key(&url, accept);
+        let cached = self.load(&key);
+
+        if self.offline {
+            return match cached {
+                Some(cached) => Ok(cached.into_response()),
+                None => Err(Error::new(&format!(
+                    "No cached response for {} (offline); run once online first",
+                    url
+                ))),
+            };
+        }
+
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        match &self.auth {
+            Some(Auth::Bearer(token)) => {
+                request = request.set("Authorization", &format!("Bearer {}", token));
+            }
+            Some(Auth::Header { name, value }) => request = request.set(name, value),
+            None => {}
+        }
+        if let Some(etag) = cached.as_ref().and_then(|cached| cached.etag.as_deref()) {
+            request = request.set("If-None-Match", etag);
+        }
+
+        let mut attempt = 0;
+        loop {
+            let delay = match request.clone().call() {
+                Ok(response) if response.status() == 304 => {
+                    if let Some(cached) = cached {
+                        return Ok(cached.into_response());
+                    }
+                    return Err(Error::new(&format!(
+                        "API answered {} with 304 but nothing is cached",
+                        url
+                    )));
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let etag = response.header("etag").map(str::to_string);
+                    let body = response.into_string().map_err(|e| {
+                        Error::new(&format!("Failed to read API response from {}: {}", url, e))
+                    })?;
+                    self.store(
+                        &key,
+                        &CachedResponse {
+                            url: url.clone(),
+                            etag,
+                            status,
+                            body: body.clone(),
+                        },
+                    );
+                    return Ok(Some(ApiResponse {
+                        status,
+                        body,
+                        cached: false,
+                    }));
+                }
+                Err(ureq::Error::Status(404, _)) => {
+                    self.store(
+                        &key,
+                        &CachedResponse {
+                            url: url.clone(),
+                            etag: None,
+                            status: 404,
+                            body: String::new(),
+                        },
+                    );
+                    return Ok(None);
+                }
+                Err(ureq::Error::Status(status, response)) => {
+                    let delay = retry_delay(
+                        status,
+                        response.header("retry-after"),
+                        response.header("x-ratelimit-remaining"),
+                        response.header("x-ratelimit-reset"),
+                        unix_now(),
+                        attempt,
+                    );
+                    match delay {
+                        Some(delay) if attempt + 1 < MAX_ATTEMPTS && delay <= MAX_WAIT_SECS => {
+                            delay
+                        }
+                        Some(delay) if delay > MAX_WAIT_SECS => {
+                            return Err(Error::new(&format!(
+                                "API rate limit reached for {}; it resets in {} s, try again later or use --offline",
+                                url, delay
+                            )));
+                        }
+                        _ => {
+                            let message = response
+                                .into_json::<serde_json::Value>()
+                                .ok()
+                                .and_then(|body| body["message"].as_str().map(str::to_string))
+                                .unwrap_or_default();
+                            return Err(Error::new(&format!(
+                                "API request {} failed with {}: {}",
+                                url, status, message
+                            )));
+                        }
+                    }
+                }
+                Err(e) => {
+                    if attempt + 1 >= MAX_ATTEMPTS {
+                        return Err(Error::new(&format!("API request failed: {}", e)));
+                    }
+                    1 << attempt
+                }
+            };
+
+            warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "API request {} failed, retrying in {} s ({}/{})",
+                    url,
+                    delay,
+                    attempt + 1,
+                    MAX_ATTEMPTS - 1
+                ),
+            );
+            std::thread::sleep(Duration::from_secs(delay));
+            attempt += 1;
+        }
+    }
+
+    /// Responses differ per token, so the token is part of the key
+    fn cache_key(&self, url: &str, accept: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(url.as_bytes());
+        hasher.update(b"\n");
+        hasher.update(accept.as_bytes());
+        hasher.update(b"\n");
+        if let Some(auth) = &self.auth {
+            hasher.update(auth.secret().as_bytes());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn load(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.cache_dir.as_ref()?.join(format!("{}.json", key));
+        let contents = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    /// Keep `response`; the cache only saves requests, so failures are just logged
+    fn store(&self, key: &str, response: &CachedResponse) {
+        let Some(dir) = &self.cache_dir else {
+            return;
+        };
+        let result = std::fs::create_dir_all(dir).and_then(|_| {
+            let contents = serde_json::to_string(response).map_err(std::io::Error::other)?;
+            std::fs::write(dir.join(format!("{}.json", key)), contents)
+        });
+        if let Err(e) = result {
+            log::debug!("Failed to cache API response for {}: {}", response.url, e);
+        }
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Seconds to wait before retrying a failed request, or `None` when retrying cannot help
+///
+/// `Retry-After` is honored first. An exhausted rate limit waits until it resets; other
+/// throttling and server errors back off exponentially.
+fn retry_delay(
+    status: u16, retry_after: Option<&str>, remaining: Option<&str>, reset: Option<&str>, now: i64,
+    attempt: u32,
+) -> Option<u64> {
+    let throttled = status == 403 || status == 429;
+    if throttled || status >= 500 {
+        if let Some(secs) = retry_after.and_then(|value| value.trim().parse::<u64>().ok()) {
+            return Some(secs);
+        }
+    }
+    if throttled && remaining.map(str::trim) == Some("0") {
+        if let Some(reset) = reset.and_then(|value| value.trim().parse::<i64>().ok()) {
+            return Some((reset - now).max(1) as u64);
+        }
+    }
+    (status == 429 || status >= 500).then(|| 1 << attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(429, Some("7"), None, None, 0, 0), Some(7));
+        assert_eq!(
+            retry_delay(403, None, Some("0"), Some("1030"), 1000, 0),
+            Some(30)
+        );
+        assert_eq!(
+            retry_delay(403, None, Some("0"), Some("900"), 1000, 0),
+            Some(1)
+        );
+        assert_eq!(retry_delay(503, None, None, None, 0, 2), Some(4));
+        // A forbidden request with rate limit left is a permission problem
+        assert_eq!(
+            retry_delay(403, None, Some("42"), Some("1030"), 1000, 0),
+            None
+        );
+        assert_eq!(retry_delay(401, Some("7"), None, None, 0, 0), None);
+    }
+
+    #[test]
+    fn test_offline_answers_from_cache() {
+        let dir = TempDir::new().unwrap();
+        let client = ApiClient::new("http://127.0.0.1:9/api/")
+            .auth(Some(Auth::Bearer("secret".to_string())))
+            .cache_dir(Some(dir.path().to_path_buf()))
+            .offline(true);
+        let accept = "application/json";
+
+        assert!(client.get("/repos/a/b", &[], accept).is_err());
+
+        let url = "http://127.0.0.1:9/api/repos/a/b?ref=main";
+        client.store(
+            &client.cache_key(url, accept),
+            &CachedResponse {
+                url: url.to_string(),
+                etag: Some("\"v1\"".to_string()),
+                status: 200,
+                body: "{\"name\":\"b\"}".to_string(),
+            },
+        );
+        let response = client
+            .get("/repos/a/b", &[("ref", "main")], accept)
+            .unwrap()
+            .unwrap();
+        assert!(response.cached);
+        assert_eq!(response.json::<serde_json::Value>().unwrap()["name"], "b");
+
+        // Another token does not see the cached response
+        let anonymous = client.clone().auth(None);
+        assert!(anonymous
+            .get("/repos/a/b", &[("ref", "main")], accept)
+            .is_err());
+
+        let missing = "http://127.0.0.1:9/api/repos/a/c";
+        client.store(
+            &client.cache_key(missing, accept),
+            &CachedResponse {
+                url: missing.to_string(),
+                etag: None,
+                status: 404,
+                body: String::new(),
+            },
+        );
+        assert_eq!(client.get("/repos/a/c", &[], accept).unwrap(), None);
+    }
+}
@@ -0,0 +1,516 @@
+//! Cache archived with rkyv in a memory-mapped file, read in place without deserializing it
+//!
+//! A cache written with [`CacheEncoding::Archive`](super::types::CacheEncoding) is
+//! [`ARCHIVE_HEADER`] followed by an rkyv archive: the build info, rules and precedence
+//! policy, which every query reads and which are small, then the tables of owners and
+//! tags and the file records referring to them, in order. An index of the files sorted by
+//! path and one of the files of each owner stand in for the indexes of a SQLite cache.
+//!
+//! [`ArchiveCache::open`] maps the file and validates the archive without allocating any
+//! of its files, so commands that need only the files of some owners, or a single file,
+//! read just those with [`ArchiveCache::query`] instead of deserializing every file of a
+//! large repository.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use rkyv::rancor;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::cache::{CacheQuery, ARCHIVE_HEADER};
+use super::path_index::PathIndex;
+use super::types::{
+    BuildInfo, CodeownersCache, CodeownersEntry, FileEntry, InlineDeclaration, Owner, OwnerType,
+    PrecedencePolicy, Tag, CACHE_FORMAT, CACHE_VERSION,
+};
+use crate::utils::error::{Error, Result};
+
+/// The archived cache
+#[derive(Default, rkyv::Archive, rkyv::Serialize)]
+struct Archive {
+    format: String,
+    version: u32,
+    hash: [u8; 32],
+    /// Build info, rules and precedence policy, as bincode
+    header: Vec<u8>,
+    /// Owners as bincode, referred to by position
+    owners: Vec<Vec<u8>>,
+    /// Identifier of each owner, for owner queries to match without decoding the owners
+    identifiers: Vec<String>,
+    tags: Vec<String>,
+    files: Vec<FileRecord>,
+    /// Positions of the files, sorted by path
+    by_path: Vec<u32>,
+    /// Positions of the files of each owner
+    owner_files: Vec<Vec<u32>>,
+    /// Number of files neither generated nor owned
+    unowned: u32,
+}
+
+#[derive(rkyv::Archive, rkyv::Serialize)]
+struct FileRecord {
+    path: String,
+    /// Positions of the owners, in the order the file has them
+    owners: Vec<u32>,
+    /// Positions of the tags, in the order the file has them
+    tags: Vec<u32>,
+    generated: bool,
+    /// Inline declaration as bincode, empty without one
+    inline: Vec<u8>,
+    /// Rules that decided the file's ownership
+    rules: Vec<u32>,
+}
+
+fn to_bincode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    bincode::serde::encode_to_vec(value, bincode::config::standard())
+        .map_err(|e| Error::new(&format!("Failed to serialize cache: {}", e)))
+}
+
+fn from_bincode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    bincode::serde::decode_from_slice(bytes, bincode::config::standard())
+        .map(|(value, _)| value)
+        .map_err(|e| Error::new(&format!("Failed to deserialize archived cache: {}", e)))
+}
+
+/// Writer of an archived cache, which holds the file records until [`finish`] archives
+/// them in one go
+///
+/// Records keep owners and tags as positions in shared tables, so they take far less
+/// memory than the file entries they are made from.
+///
+/// [`finish`]: ArchiveWriter::finish
+pub struct ArchiveWriter {
+    path: PathBuf,
+    archive: Archive,
+    rules: usize,
+    owners: HashMap<Owner, u32>,
+    tags: HashMap<Tag, u32>,
+}
+
+impl ArchiveWriter {
+    /// Start the cache to be written at `path`, with its header
+    pub fn create(
+        path: &Path, build: &BuildInfo, hash: &[u8; 32], entries: &[CodeownersEntry],
+        precedence: PrecedencePolicy,
+    ) -> Result<Self> {
+        Ok(ArchiveWriter {
+            path: path.to_path_buf(),
+            archive: Archive {
+                format: CACHE_FORMAT.to_string(),
+                version: CACHE_VERSION,
+                hash: *hash,
+                header: to_bincode(&(build, entries, precedence))?,
+                ..Archive::default()
+            },
+            rules: entries.len(),
+            owners: HashMap::new(),
+            tags: HashMap::new(),
+        })
+    }
+
+    /// Append a file entry, along with the indices of the rules that decided its ownership
+    pub fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        let archive = &mut self.archive;
+        let position = archive.files.len() as u32;
+        let path = file.path.to_str().ok_or_else(|| {
+            Error::new(&format!(
+                "{} is not valid UTF-8 and cannot be stored in an archived cache",
+                file.path.display()
+            ))
+        })?;
+        if let Some(&rule) = rules.iter().find(|&&rule| rule >= self.rules) {
+            return Err(Error::new(&format!(
+                "Rule {} is not in the cache header",
+                rule
+            )));
+        }
+
+        let mut owners = Vec::with_capacity(file.owners.len());
+        for owner in &file.owners {
+            let id = match self.owners.get(owner) {
+                Some(&id) => id,
+                None => {
+                    let id = archive.owners.len() as u32;
+                    archive.owners.push(to_bincode(owner)?);
+                    archive.identifiers.push(owner.identifier.clone());
+                    archive.owner_files.push(Vec::new());
+                    self.owners.insert(owner.clone(), id);
+                    id
+                }
+            };
+            let files = &mut archive.owner_files[id as usize];
+            if files.last() != Some(&position) {
+                files.push(position);
+            }
+            owners.push(id);
+        }
+
+        let mut tags = Vec::with_capacity(file.tags.len());
+        for tag in &file.tags {
+            let id = *self.tags.entry(tag.clone()).or_insert_with(|| {
+                archive.tags.push(tag.0.clone());
+                archive.tags.len() as u32 - 1
+            });
+            tags.push(id);
+        }
+
+        if !file.generated
+            && file
+                .owners
+                .iter()
+                .all(|owner| owner.owner_type == OwnerType::Unowned)
+        {
+            archive.unowned += 1;
+        }
+
+        archive.files.push(FileRecord {
+            path: path.to_string(),
+            owners,
+            tags,
+            generated: file.generated,
+            inline: match &file.inline {
+                Some(inline) => to_bincode(inline)?,
+                None => Vec::new(),
+            },
+            rules: rules.iter().map(|&rule| rule as u32).collect(),
+        });
+
+        Ok(())
+    }
+
+    /// Archive the cache, write it and flush it to disk
+    pub fn finish(self) -> Result<()> {
+        let mut archive = self.archive;
+        let mut by_path: Vec<u32> = (0..archive.files.len() as u32).collect();
+        by_path.sort_by(|&a, &b| {
+            archive.files[a as usize]
+                .path
+                .cmp(&archive.files[b as usize].path)
+        });
+        archive.by_path = by_path;
+
+        let bytes = rkyv::to_bytes::<rancor::Error>(&archive)
+            .map_err(|e| Error::new(&format!("Failed to archive cache: {}", e)))?;
+        let mut file = File::create(&self.path)?;
+        file.write_all(ARCHIVE_HEADER)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+impl super::cache::FileSink for ArchiveWriter {
+    fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        ArchiveWriter::write_file(self, file, rules)
+    }
+}
+
+/// An archived cache mapped for reading, with its header and rules read
+pub struct ArchiveCache {
+    map: Mmap,
+    /// Layout of the cache, see [`CACHE_VERSION`]
+    pub version: u32,
+    pub build: BuildInfo,
+    pub hash: [u8; 32],
+    pub precedence: PrecedencePolicy,
+    pub entries: Vec<CodeownersEntry>,
+}
+
+impl ArchiveCache {
+    /// Map the cache at `path` and validate it, reading only its header and rules
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))?;
+        // SAFETY: caches are replaced by renaming a complete file over them, never written
+        // in place, so the mapped file does not change while it is read
+        let map = unsafe { Mmap::map(&file) }
+            .map_err(|e| Error::with_source("Failed to map cache file", Box::new(e)))?;
+        if !map.starts_with(ARCHIVE_HEADER) {
+            return Err(Error::new("Not an archived codeowners cache"));
+        }
+        let archive = rkyv::access::<ArchivedArchive, rancor::Error>(&map[ARCHIVE_HEADER.len()..])
+            .map_err(|e| Error::new(&format!("Invalid archived cache: {}", e)))?;
+        if archive.format.as_str() != CACHE_FORMAT {
+            return Err(Error::new("Not a codeowners cache"));
+        }
+        let version = archive.version.to_native();
+        let hash = archive.hash;
+        let (build, entries, precedence) = from_bincode(&archive.header)?;
+
+        Ok(ArchiveCache {
+            map,
+            version,
+            build,
+            hash,
+            precedence,
+            entries,
+        })
+    }
+
+    fn archive(&self) -> &ArchivedArchive {
+        // SAFETY: the archive was validated by `open`, and the map is read-only
+        unsafe { rkyv::access_unchecked::<ArchivedArchive>(&self.map[ARCHIVE_HEADER.len()..]) }
+    }
+
+    /// Number of files, and of those neither generated nor owned, as the run summary
+    /// counts them
+    pub fn counts(&self) -> (usize, usize) {
+        let archive = self.archive();
+        (archive.files.len(), archive.unowned.to_native() as usize)
+    }
+
+    /// The whole cache
+    pub fn load(self) -> Result<CodeownersCache> {
+        let positions: Vec<u32> = (0..self.archive().files.len() as u32).collect();
+        self.select(&positions)
+    }
+
+    /// The files `query` selects, in cache order, with the rules and the rule matches
+    /// between them
+    pub fn query(self, query: CacheQuery<'_>) -> Result<CodeownersCache> {
+        let archive = self.archive();
+        let mut positions = BTreeSet::new();
+        match query {
+            CacheQuery::Owners(patterns) => {
+                for (identifier, files) in
+                    archive.identifiers.iter().zip(archive.owner_files.iter())
+                {
+                    if patterns
+                        .iter()
+                        .any(|pattern| identifier.as_str().contains(pattern))
+                    {
+                        positions.extend(files.iter().map(|position| position.to_native()));
+                    }
+                }
+            }
+            CacheQuery::Paths(paths) => {
+                for path in paths {
+                    let Some(path) = path.to_str() else {
+                        continue;
+                    };
+                    if let Ok(found) = archive.by_path.binary_search_by(|position| {
+                        archive.files[position.to_native() as usize]
+                            .path
+                            .as_str()
+                            .cmp(path)
+                    }) {
+                        positions.insert(archive.by_path[found].to_native());
+                    }
+                }
+            }
+        }
+
+        let positions: Vec<u32> = positions.into_iter().collect();
+        self.select(&positions)
+    }
+
+    /// The cache of the files at `positions`, in ascending order
+    fn select(self, positions: &[u32]) -> Result<CodeownersCache> {
+        let archive = self.archive();
+        let mut owners: HashMap<u32, Owner> = HashMap::new();
+        let mut files = Vec::with_capacity(positions.len());
+        let mut rule_matches = vec![Vec::new(); self.entries.len()];
+        for &position in positions {
+            let record = archive
+                .files
+                .get(position as usize)
+                .ok_or_else(|| Error::new("Archived cache refers to a file it does not have"))?;
+            for rule in record.rules.iter() {
+                rule_matches
+                    .get_mut(rule.to_native() as usize)
+                    .ok_or_else(|| Error::new("Archived cache matches a rule it does not have"))?
+                    .push(files.len());
+            }
+            files.push(file_entry(archive, record, &mut owners)?);
+        }
+
+        let cache = CodeownersCache::from_stored(
+            self.hash,
+            self.entries,
+            files,
+            rule_matches,
+            PathIndex::default(),
+            self.precedence,
+        )
+        .map_err(Error::new)?;
+        Ok(CodeownersCache {
+            build: self.build,
+            ..cache
+        })
+    }
+}
+
+/// The file entry of `record`, decoding each owner once into `owners`
+fn file_entry(
+    archive: &ArchivedArchive, record: &ArchivedFileRecord, owners: &mut HashMap<u32, Owner>,
+) -> Result<FileEntry> {
+    let mut file_owners = Vec::with_capacity(record.owners.len());
+    for id in record.owners.iter() {
+        let id = id.to_native();
+        let owner = match owners.get(&id) {
+            Some(owner) => owner.clone(),
+            None => {
+                let encoded = archive.owners.get(id as usize).ok_or_else(|| {
+                    Error::new("Archived cache refers to an owner it does not have")
+                })?;
+                let owner: Owner = from_bincode(encoded)?;
+                owners.insert(id, owner.clone());
+                owner
+            }
+        };
+        file_owners.push(owner);
+    }
+    let tags = record
+        .tags
+        .iter()
+        .map(|id| {
+            archive
+                .tags
+                .get(id.to_native() as usize)
+                .map(|tag| Tag(tag.to_string()))
+                .ok_or_else(|| Error::new("Archived cache refers to a tag it does not have"))
+        })
+        .collect::<Result<Vec<Tag>>>()?;
+    let inline: Option<InlineDeclaration> = if record.inline.is_empty() {
+        None
+    } else {
+        Some(from_bincode(&record.inline)?)
+    };
+
+    Ok(FileEntry {
+        path: PathBuf::from(record.path.as_str()),
+        owners: file_owners,
+        tags,
+        generated: record.generated,
+        inline,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{EntrySpans, InlineSource};
+    use tempfile::TempDir;
+
+    fn owner(identifier: &str) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::Team,
+        }
+    }
+
+    fn file(path: &str, owners: &[&str]) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners.iter().map(|identifier| owner(identifier)).collect(),
+            tags: vec![Tag("core".to_string())],
+            generated: false,
+            inline: None,
+        }
+    }
+
+    fn json<T: Serialize + ?Sized>(value: &T) -> String {
+        serde_json::to_string(value).unwrap()
+    }
+
+    #[test]
+    fn test_query() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cache.rkyv");
+        let entries = vec![CodeownersEntry {
+            source_file: PathBuf::from("./CODEOWNERS"),
+            line_number: 1,
+            pattern: "*.rs".to_string(),
+            owners: vec![owner("@org/core")],
+            tags: vec![Tag("core".to_string())],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        }];
+        let mut declared = file("./README.md", &["@org/docs"]);
+        declared.inline = Some(InlineDeclaration {
+            source: InlineSource::Marker,
+            line: 3,
+        });
+        let files = [
+            (file("./src/lib.rs", &["@org/core"]), vec![0]),
+            (declared, vec![]),
+            (file("./src/main.rs", &["@org/core", "@alice"]), vec![0]),
+            (file("./LICENSE", &[]), vec![]),
+        ];
+
+        let mut writer = ArchiveWriter::create(
+            &path,
+            &BuildInfo::default(),
+            &[7; 32],
+            &entries,
+            PrecedencePolicy::default(),
+        )
+        .unwrap();
+        for (file, rules) in &files {
+            writer.write_file(file, rules).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let full = ArchiveCache::open(&path).unwrap().load().unwrap();
+        assert_eq!(full.hash, [7; 32]);
+        assert_eq!(full.entries[0].pattern, "*.rs");
+        assert_eq!(
+            json(&full.files),
+            json(&files.iter().map(|(f, _)| f).collect::<Vec<_>>())
+        );
+        assert_eq!(full.rule_matches, vec![vec![0, 2]]);
+
+        let archive = ArchiveCache::open(&path).unwrap();
+        assert_eq!(archive.version, CACHE_VERSION);
+        assert_eq!(archive.counts(), (4, 1));
+        let core = archive
+            .query(CacheQuery::Owners(&["core", "alice"]))
+            .unwrap();
+        let paths: Vec<_> = core.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("./src/lib.rs"),
+                PathBuf::from("./src/main.rs")
+            ]
+        );
+        assert_eq!(core.rule_matches, vec![vec![0, 1]]);
+
+        let one = ArchiveCache::open(&path)
+            .unwrap()
+            .query(CacheQuery::Paths(&[
+                PathBuf::from("README.md"),
+                PathBuf::from("./README.md"),
+            ]))
+            .unwrap();
+        assert_eq!(json(&one.files), json(&[&files[1].0]));
+        assert_eq!(one.rule_matches, vec![Vec::<usize>::new()]);
+    }
+
+    #[test]
+    fn test_rejects_damaged_archive() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cache.rkyv");
+        let mut writer = ArchiveWriter::create(
+            &path,
+            &BuildInfo::default(),
+            &[0; 32],
+            &[],
+            PrecedencePolicy::default(),
+        )
+        .unwrap();
+        writer.write_file(&file("./a.rs", &["@a"]), &[]).unwrap();
+        writer.finish().unwrap();
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        let len = bytes.len();
+        bytes.truncate(len - 8);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(ArchiveCache::open(&path).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! Bitbucket's reading of CODEOWNERS, followed with the `bitbucket` [`Dialect`]
+//!
+//! Bitbucket reads a single CODEOWNERS file, the first of [`CODEOWNERS_LOCATIONS`], and
+//! scopes it to the repository root wherever it lives; nested CODEOWNERS files are not
+//! read. Besides users and email addresses, owners can be Bitbucket groups, written
+//! `@@group`, and reviewer groups, written `@@@group`. Within the file the last matching
+//! rule wins, as on GitHub.
+//!
+//! [`Dialect`]: super::types::Dialect
+
+use std::path::{Path, PathBuf};
+
+use super::types::{CodeownersEntry, OwnerType};
+
+/// Directory whose CODEOWNERS file Bitbucket scopes to the repository root
+pub const CONFIG_DIR: &str = super::types::BITBUCKET_CONFIG_DIR;
+
+/// Where Bitbucket looks for CODEOWNERS, in order; the first file found is the only one used
+pub const CODEOWNERS_LOCATIONS: [&str; 2] = [".bitbucket/CODEOWNERS", "CODEOWNERS"];
+
+/// The CODEOWNERS file Bitbucket reads in `repo`, if any
+pub fn find_codeowners_file(repo: &Path) -> Option<PathBuf> {
+    CODEOWNERS_LOCATIONS
+        .iter()
+        .map(|location| repo.join(location))
+        .find(|path| path.is_file())
+}
+
+/// Whether `identifier` names a group or reviewer group, `@@group` or `@@@group`
+pub fn is_group(identifier: &str) -> bool {
+    identifier
+        .strip_prefix("@@")
+        .map(|name| name.strip_prefix('@').unwrap_or(name))
+        .is_some_and(|name| !name.is_empty() && !name.contains('@'))
+}
+
+/// Read the owners of `entries` as Bitbucket does: groups are teams
+pub fn apply_owner_syntax(entries: &mut [CodeownersEntry]) {
+    let owners = entries.iter_mut().flat_map(|entry| {
+        let defaults = entry
+            .section
+            .iter_mut()
+            .flat_map(|section| section.default_owners.iter_mut());
+        entry.owners.iter_mut().chain(defaults)
+    });
+    for owner in owners {
+        if is_group(&owner.identifier) {
+            owner.owner_type = OwnerType::Team;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use crate::utils::error::Result;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_bitbucket_owners() -> Result<()> {
+        let mut entries = parse_content(
+            "* @@platform\n*.rs @@@rust-reviewers @alice dev@example.com\n*.md @@\n",
+            Path::new(".bitbucket/CODEOWNERS"),
+        )?;
+        apply_owner_syntax(&mut entries);
+
+        let types: Vec<Vec<OwnerType>> = entries
+            .iter()
+            .map(|entry| entry.owners.iter().map(|o| o.owner_type.clone()).collect())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                vec![OwnerType::Team],
+                vec![OwnerType::Team, OwnerType::User, OwnerType::Email],
+                vec![OwnerType::User],
+            ]
+        );
+        // Rules in .bitbucket/ apply from the repository root
+        assert_eq!(entries[0].base_dir(), Some(Path::new("")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_codeowners_file() -> Result<()> {
+        let dir = TempDir::new()?;
+        assert_eq!(find_codeowners_file(dir.path()), None);
+        std::fs::write(dir.path().join("CODEOWNERS"), "* @a\n")?;
+        assert_eq!(
+            find_codeowners_file(dir.path()),
+            Some(dir.path().join("CODEOWNERS"))
+        );
+        std::fs::create_dir(dir.path().join(CONFIG_DIR))?;
+        std::fs::write(dir.path().join(".bitbucket/CODEOWNERS"), "* @b\n")?;
+        assert_eq!(
+            find_codeowners_file(dir.path()),
+            Some(dir.path().join(".bitbucket/CODEOWNERS"))
+        );
+        Ok(())
+    }
+}
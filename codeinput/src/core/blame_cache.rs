@@ -0,0 +1,222 @@
+//! Blame summaries kept between `infer-owners` runs
+//!
+//! Blaming every file is what makes inference slow on large repositories, yet a file's
+//! blame only changes when its content does. Each file's blame is summarized per author
+//! and stored under the id of the blob blamed, in a store inside the git directory, so a
+//! later run blames only the files that changed since. Summaries not used for
+//! [`RETENTION_DAYS`] are dropped when the store is saved.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use git2::{Blame, Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+use super::common::write_file_atomically;
+use crate::utils::error::{Error, Result};
+use crate::utils::output;
+
+/// Name of the store in the git directory
+pub const BLAME_CACHE_FILE: &str = "codeowners-blame.cache";
+
+/// Days a summary is kept without being used
+pub const RETENTION_DAYS: i64 = 30;
+
+/// Layout of the store; a store of another layout is discarded
+///
+/// Layout 2 records author names, which `.mailmap` entries may match on.
+const BLAME_CACHE_VERSION: u32 = 2;
+
+/// The lines of a file blamed on one author
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlameAuthor {
+    pub email: String,
+    /// Name on the author's latest blamed commit
+    pub name: String,
+    pub lines: u32,
+    pub hunks: u32,
+    /// Time of the author's latest blamed commit, in seconds since the epoch
+    pub last_commit_time: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredSummary {
+    authors: Vec<BlameAuthor>,
+    /// When the summary was last used, in seconds since the epoch
+    last_used: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Store {
+    version: u32,
+    summaries: HashMap<String, StoredSummary>,
+}
+
+/// Summarize `blame` per author, in order of first appearance
+pub fn summarize(blame: &Blame) -> Vec<BlameAuthor> {
+    let mut authors: Vec<BlameAuthor> = Vec::new();
+    for hunk in blame.iter() {
+        let signature = hunk.final_signature();
+        let email = signature.email().unwrap_or("unknown");
+        let name = signature.name().unwrap_or_default();
+        let time = signature.when().seconds();
+        let lines = hunk.lines_in_hunk() as u32;
+        match authors.iter_mut().find(|author| author.email == email) {
+            Some(author) => {
+                author.lines += lines;
+                author.hunks += 1;
+                if time > author.last_commit_time {
+                    author.name = name.to_string();
+                    author.last_commit_time = time;
+                }
+            }
+            None => authors.push(BlameAuthor {
+                email: email.to_string(),
+                name: name.to_string(),
+                lines,
+                hunks: 1,
+                last_commit_time: time,
+            }),
+        }
+    }
+    authors
+}
+
+/// Key of the blame of `blob`, stopping at `oldest_commit` if set
+pub fn blame_key(blob: Oid, oldest_commit: Option<Oid>) -> String {
+    match oldest_commit {
+        Some(oldest) => format!("{}..{}", oldest, blob),
+        None => blob.to_string(),
+    }
+}
+
+/// Blame summaries of a repository, shared by the workers of a run
+pub struct BlameCache {
+    path: PathBuf,
+    summaries: Mutex<HashMap<String, StoredSummary>>,
+    blamed: AtomicUsize,
+    reused: AtomicUsize,
+}
+
+impl BlameCache {
+    /// Open the store of `repo`; a missing or unreadable store starts empty
+    pub fn open(repo: &Repository) -> BlameCache {
+        let path = repo.path().join(BLAME_CACHE_FILE);
+        let store = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| {
+                bincode::serde::decode_from_slice::<Store, _>(&bytes, bincode::config::standard())
+                    .map_err(|e| log::debug!("Discarding blame cache {}: {}", path.display(), e))
+                    .ok()
+            })
+            .map(|(store, _)| store)
+            .filter(|store| store.version == BLAME_CACHE_VERSION)
+            .unwrap_or_default();
+        BlameCache {
+            path,
+            summaries: Mutex::new(store.summaries),
+            blamed: AtomicUsize::new(0),
+            reused: AtomicUsize::new(0),
+        }
+    }
+
+    /// The summary stored under `key`, or the one `blame` produces, which is stored
+    pub fn summary(
+        &self, key: &str, blame: impl FnOnce() -> Result<Vec<BlameAuthor>>,
+    ) -> Result<Vec<BlameAuthor>> {
+        let now = output::now().timestamp();
+        if let Some(stored) = self.summaries.lock().unwrap().get_mut(key) {
+            stored.last_used = now;
+            self.reused.fetch_add(1, Ordering::Relaxed);
+            return Ok(stored.authors.clone());
+        }
+
+        // Blame without holding the lock, so workers blame in parallel
+        let authors = blame()?;
+        self.blamed.fetch_add(1, Ordering::Relaxed);
+        self.summaries.lock().unwrap().insert(
+            key.to_string(),
+            StoredSummary {
+                authors: authors.clone(),
+                last_used: now,
+            },
+        );
+        Ok(authors)
+    }
+
+    /// Files blamed and summaries reused so far
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.blamed.load(Ordering::Relaxed),
+            self.reused.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Write the store, dropping summaries unused for [`RETENTION_DAYS`]
+    pub fn save(&self) -> Result<()> {
+        let cutoff = output::now().timestamp() - RETENTION_DAYS * 86400;
+        let mut summaries = self.summaries.lock().unwrap();
+        summaries.retain(|_, stored| stored.last_used >= cutoff);
+        let store = Store {
+            version: BLAME_CACHE_VERSION,
+            summaries: std::mem::take(&mut *summaries),
+        };
+
+        let result = write_file_atomically(&self.path, |writer| {
+            bincode::serde::encode_into_std_write(&store, writer, bincode::config::standard())
+                .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+            Ok(())
+        });
+        *summaries = store.summaries;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{BlameOptions, Signature};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_blame_cache() -> Result<()> {
+        let dir = TempDir::new()?;
+        let repo = Repository::init(dir.path())?;
+        std::fs::write(dir.path().join("lib.rs"), "fn a() {}\nfn b() {}\n")?;
+        let mut index = repo.index()?;
+        index.add_path(Path::new("lib.rs"))?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let signature = Signature::new("Dev", "dev@example.com", &git2::Time::new(1000, 0))?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Add", &tree, &[])?;
+        let blob = tree.get_path(Path::new("lib.rs"))?.id();
+
+        let blame = || {
+            let blame = repo.blame_file(Path::new("lib.rs"), Some(&mut BlameOptions::new()))?;
+            Ok(summarize(&blame))
+        };
+        let expected = vec![BlameAuthor {
+            email: "dev@example.com".to_string(),
+            name: "Dev".to_string(),
+            lines: 2,
+            hunks: 1,
+            last_commit_time: 1000,
+        }];
+
+        let cache = BlameCache::open(&repo);
+        assert_eq!(cache.summary(&blame_key(blob, None), blame)?, expected);
+        cache.save()?;
+
+        // A later run reuses the summary of the unchanged blob
+        let cache = BlameCache::open(&repo);
+        let summary = cache.summary(&blame_key(blob, None), || Err(Error::new("blamed again")))?;
+        assert_eq!(summary, expected);
+        assert_eq!(cache.counts(), (0, 1));
+        assert!(cache
+            .summary(&blame_key(blob, Some(blob)), || Err(Error::new("new key")))
+            .is_err());
+        Ok(())
+    }
+}
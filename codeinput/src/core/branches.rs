@@ -0,0 +1,195 @@
+//! Remote branches changing an owner's files, for `codeowners branches`
+//!
+//! Each remote-tracking branch is compared with the point where it left the default
+//! branch, like `git diff origin/main...origin/feature`, and its changed files are resolved
+//! against the current rules. A branch concerns an owner when the owner, or a team the
+//! team map lists them in, owns one of those files. Branches nobody has committed to for a
+//! long time are usually abandoned, so the oldest are listed first.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use git2::{BranchType, Oid, Repository};
+use serde::Serialize;
+
+use super::changed::resolve_changed_files;
+use super::context::Context;
+use super::team_map::TeamMap;
+use super::types::{CodeownersCache, Owner, OwnerType};
+use crate::utils::error::{Error, Result};
+use crate::utils::interrupt;
+
+/// Branches tried, in order, when `origin/HEAD` does not name the default branch
+const DEFAULT_BRANCHES: [&str; 2] = ["origin/main", "origin/master"];
+
+/// A remote branch changing files of the owner asked about
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BranchOwnership {
+    /// Remote-tracking name, e.g. `origin/feature`
+    pub name: String,
+    pub last_commit: DateTime<Utc>,
+    /// Email of the author of the last commit
+    pub author: String,
+    /// Commits on the branch that the default branch does not have
+    pub ahead: usize,
+    /// Files changed since the branch left the default branch
+    pub changed: usize,
+    /// The changed files of the owner, relative to the repository root
+    pub owned_files: Vec<PathBuf>,
+}
+
+/// The default branch of the repository at `repo`: the branch `origin/HEAD` points at,
+/// or else `origin/main` or `origin/master`
+pub fn default_branch(repo: &Path) -> Result<String> {
+    let repository = Repository::open(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    if let Some(target) = repository
+        .find_reference("refs/remotes/origin/HEAD")
+        .ok()
+        .and_then(|head| head.symbolic_target().map(str::to_string))
+    {
+        return Ok(target.trim_start_matches("refs/remotes/").to_string());
+    }
+    DEFAULT_BRANCHES
+        .iter()
+        .find(|name| repository.find_branch(name, BranchType::Remote).is_ok())
+        .map(|name| name.to_string())
+        .ok_or_else(|| {
+            Error::new("Cannot tell the default branch from origin; pass it with --base REF")
+        })
+}
+
+/// Remote branches of `repo` changing files `owner` is responsible for since they left
+/// `base`, the least recently committed to first
+///
+/// Files are resolved from `cache`, and `owner` is responsible for those owned by them or
+/// by a team `teams` lists them in. Only branches whose last commit is at least
+/// `older_than_days` old are listed. Branches sharing no history with `base` are skipped.
+pub fn owned_branches(
+    ctx: &Context, repo: &Path, base: &str, owner: &str, older_than_days: u32,
+    cache: &CodeownersCache, teams: &TeamMap,
+) -> Result<Vec<BranchOwnership>> {
+    let repository = Repository::open(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let base_oid = repository
+        .revparse_single(base)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| Error::new(&format!("Unknown base revision {}: {}", base, e.message())))?
+        .id();
+    let cutoff = crate::utils::output::now() - chrono::Duration::days(older_than_days.into());
+
+    let mut branches = Vec::new();
+    for branch in repository.branches(Some(BranchType::Remote))? {
+        interrupt::check()?;
+        let (branch, _) = branch?;
+        // origin/HEAD is an alias of the default branch
+        if branch.get().symbolic_target().is_some() {
+            continue;
+        }
+        let Some(name) = branch.name()?.map(str::to_string) else {
+            continue;
+        };
+        let tip = branch.get().peel_to_commit()?;
+        if name == base || tip.id() == base_oid {
+            continue;
+        }
+
+        let last_commit = DateTime::from_timestamp(tip.time().seconds(), 0).unwrap_or_default();
+        if last_commit > cutoff {
+            continue;
+        }
+        let Ok(merge_base) = repository.merge_base(base_oid, tip.id()) else {
+            log::debug!("Skipping {}, which shares no history with {}", name, base);
+            continue;
+        };
+
+        let changed = changed_between(&repository, merge_base, tip.id())?;
+        let owned_files: Vec<PathBuf> = resolve_changed_files(ctx, repo, &changed, None, cache)?
+            .into_iter()
+            .filter(|file| is_responsible(&file.owners, teams, owner))
+            .map(|file| file.path)
+            .collect();
+        if owned_files.is_empty() {
+            continue;
+        }
+
+        let mut walk = repository.revwalk()?;
+        walk.push(tip.id())?;
+        walk.hide(base_oid)?;
+        branches.push(BranchOwnership {
+            name,
+            last_commit,
+            author: tip.author().email().unwrap_or_default().to_string(),
+            ahead: walk.count(),
+            changed: changed.len(),
+            owned_files,
+        });
+    }
+
+    branches.sort_by(|a, b| {
+        a.last_commit
+            .cmp(&b.last_commit)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(branches)
+}
+
+/// Files changed from commit `from` to commit `to`, sorted; deleted files are listed under
+/// their old path
+fn changed_between(repository: &Repository, from: Oid, to: Oid) -> Result<Vec<PathBuf>> {
+    let old_tree = repository.find_commit(from)?.tree()?;
+    let new_tree = repository.find_commit(to)?.tree()?;
+    let diff = repository.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+    let mut paths: Vec<PathBuf> = diff
+        .deltas()
+        .filter_map(|delta| match delta.status() {
+            git2::Delta::Deleted => delta.old_file().path(),
+            _ => delta.new_file().path(),
+        })
+        .map(Path::to_path_buf)
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Whether `owner` is one of `owners`, or a member of a team among them
+fn is_responsible(owners: &[Owner], teams: &TeamMap, owner: &str) -> bool {
+    owners.iter().any(|candidate| {
+        candidate.identifier.eq_ignore_ascii_case(owner)
+            || (candidate.owner_type == OwnerType::Team
+                && teams
+                    .members(&candidate.identifier)
+                    .is_some_and(|members| members.iter().any(|m| m.eq_ignore_ascii_case(owner))))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_responsible() -> Result<()> {
+        let teams = TeamMap::parse(
+            r#""@org/backend" = ["@alice", "@bob"]"#,
+            Path::new("teams.toml"),
+        )?;
+        let owners = vec![
+            Owner {
+                identifier: "@org/backend".to_string(),
+                owner_type: OwnerType::Team,
+            },
+            Owner {
+                identifier: "@Carol".to_string(),
+                owner_type: OwnerType::User,
+            },
+        ];
+
+        assert!(is_responsible(&owners, &teams, "@org/Backend"));
+        assert!(is_responsible(&owners, &teams, "@alice"));
+        assert!(is_responsible(&owners, &teams, "@carol"));
+        assert!(!is_responsible(&owners, &teams, "@dave"));
+        assert!(!is_responsible(&owners, &teams, "@org/docs"));
+        Ok(())
+    }
+}
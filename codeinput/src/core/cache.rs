@@ -1,214 +1,1860 @@
+#[cfg(feature = "archive")]
+use crate::core::archive_cache::{ArchiveCache, ArchiveWriter};
+#[cfg(any(feature = "sqlite", feature = "archive"))]
+use crate::core::common::replace_file_atomically;
+#[cfg(feature = "sqlite")]
+use crate::core::sqlite_cache::{SqliteCache, SqliteWriter};
 use crate::{
     core::{
-        common::{collect_owners, collect_tags, get_repo_hash},
-        parse::parse_repo,
-        resolver::find_owners_and_tags_for_file,
+        central,
+        common::{changed_since, get_cache_hash, write_file_atomically},
+        context::Context,
+        generated::is_generated,
+        inline_parser::{detect_inline_codeowners, reads_front_matter},
+        matcher::{Matcher, MatcherStrategy},
+        overlay,
+        parse::{changes_rules, parse_repo, refresh_paths},
+        parser::parse_codeowners,
+        path_index::PathIndex,
+        registry, remote_cache,
+        resolver::resolve_file_with_source,
+        rule_filter,
+        tag_remap::TagRemap,
         types::{
-            codeowners_entry_to_matcher, CacheEncoding, CodeownersCache, CodeownersEntry,
-            CodeownersEntryMatcher, FileEntry,
+            BuildInfo, CacheEncoding, CodeownersCache, CodeownersEntry, Dialect, EntrySpans,
+            FileEntry, InlineDeclaration, InlineSource, Owner, OwnerType, PrecedencePolicy,
+            RepoState, Tag, CACHE_FORMAT, CACHE_VERSION,
         },
     },
-    utils::error::{Error, Result},
+    utils::{
+        error::{Error, Result},
+        interrupt, output, summary, telemetry,
+        warnings::{self, WarningKind},
+    },
 };
-use rayon::{iter::ParallelIterator, slice::ParallelSlice};
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 use std::{
-    io::{Read, Write},
+    collections::{BTreeMap, HashMap},
+    io::{BufRead, Read, Write},
     path::{Path, PathBuf},
 };
 
-/// Create a cache from parsed CODEOWNERS entries and files
-pub fn build_cache(
-    entries: Vec<CodeownersEntry>, files: Vec<PathBuf>, hash: [u8; 32],
-) -> Result<CodeownersCache> {
-    let mut owners_map = std::collections::HashMap::new();
-    let mut tags_map = std::collections::HashMap::new();
+/// Number of files resolved in the first chunk, before entry sizes are known
+const INITIAL_CHUNK_FILES: usize = 1024;
 
-    let matched_entries: Vec<CodeownersEntryMatcher> = entries
-        .iter()
-        .map(|entry| codeowners_entry_to_matcher(entry))
-        .collect();
+/// Largest share of the files a stale cache is updated for instead of rebuilt
+const MAX_UPDATED_SHARE: f64 = 0.25;
+
+/// First bytes of every SQLite database
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// First bytes of every archived cache, which keep the archive after them aligned
+pub(crate) const ARCHIVE_HEADER: &[u8; 16] = b"codeowners-rkyv\0";
+
+/// Incremental writer for the cache format
+///
+/// Writes the header (format, version, build info, hash and rules) up front, then file
+/// entries as they
+/// are produced, then the rule matches and path index built along the way, and finally the
+/// precedence policy the files were resolved with. The output has the same layout as a
+/// serialized [`CodeownersCache`], so it loads with [`load_cache`].
+pub struct CacheWriter<W: Write> {
+    writer: W,
+    encoding: CacheEncoding,
+    remaining: usize,
+    written: usize,
+    rule_matches: Vec<Vec<usize>>,
+    path_index: PathIndex,
+    precedence: PrecedencePolicy,
+}
+
+impl<W: Write> CacheWriter<W> {
+    /// Write the cache header for `file_count` file entries
+    pub fn new(
+        mut writer: W, encoding: CacheEncoding, build: &BuildInfo, hash: &[u8; 32],
+        entries: &[CodeownersEntry], file_count: usize, precedence: PrecedencePolicy,
+    ) -> Result<Self> {
+        match encoding {
+            CacheEncoding::Bincode => {
+                encode_bincode(CACHE_FORMAT, &mut writer)?;
+                encode_bincode(&CACHE_VERSION, &mut writer)?;
+                encode_bincode(build, &mut writer)?;
+                encode_bincode(hash, &mut writer)?;
+                encode_bincode(entries, &mut writer)?;
+                // Sequence length prefix, as bincode writes it for `Vec<FileEntry>`
+                encode_bincode(&(file_count as u64), &mut writer)?;
+            }
+            CacheEncoding::Json => {
+                writer.write_all(b"{\"format\":")?;
+                encode_json(CACHE_FORMAT, &mut writer)?;
+                writer.write_all(b",\"version\":")?;
+                encode_json(&CACHE_VERSION, &mut writer)?;
+                writer.write_all(b",\"build\":")?;
+                encode_json(build, &mut writer)?;
+                writer.write_all(b",\"hash\":")?;
+                encode_json(hash, &mut writer)?;
+                writer.write_all(b",\"entries\":")?;
+                encode_json(&entries, &mut writer)?;
+                writer.write_all(b",\"files\":[")?;
+            }
+            #[cfg(feature = "sqlite")]
+            CacheEncoding::Sqlite => {
+                return Err(Error::new(
+                    "A SQLite cache is written to a file with write_cache, not to a stream",
+                ))
+            }
+            #[cfg(feature = "archive")]
+            CacheEncoding::Archive => {
+                return Err(Error::new(
+                    "An archived cache is written to a file with write_cache, not to a stream",
+                ))
+            }
+        }
+
+        Ok(CacheWriter {
+            writer,
+            encoding,
+            remaining: file_count,
+            written: 0,
+            rule_matches: vec![Vec::new(); entries.len()],
+            path_index: PathIndex::new(),
+            precedence,
+        })
+    }
+
+    /// Append a file entry, along with the indices of the rules that decided its ownership
+    pub fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        if self.remaining == 0 {
+            return Err(Error::new(
+                "More file entries written than declared in the cache header",
+            ));
+        }
+
+        match self.encoding {
+            CacheEncoding::Bincode => encode_bincode(file, &mut self.writer)?,
+            CacheEncoding::Json => {
+                if self.written > 0 {
+                    self.writer.write_all(b",")?;
+                }
+                self.writer.write_all(b"\n")?;
+                encode_json(file, &mut self.writer)?;
+            }
+            #[cfg(feature = "sqlite")]
+            CacheEncoding::Sqlite => unreachable!("CacheWriter::new rejects SQLite"),
+            #[cfg(feature = "archive")]
+            CacheEncoding::Archive => unreachable!("CacheWriter::new rejects archives"),
+        }
+
+        for &rule in rules {
+            let matches = self
+                .rule_matches
+                .get_mut(rule)
+                .ok_or_else(|| Error::new(&format!("Rule {} is not in the cache header", rule)))?;
+            matches.push(self.written);
+        }
+        self.path_index.insert(&file.path, self.written);
+        self.written += 1;
+        self.remaining -= 1;
+
+        Ok(())
+    }
+
+    /// Close the cache and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        if self.remaining != 0 {
+            return Err(Error::new(&format!(
+                "Cache is missing {} of {} declared file entries",
+                self.remaining,
+                self.remaining + self.written
+            )));
+        }
+
+        match self.encoding {
+            CacheEncoding::Bincode => {
+                encode_bincode(&self.rule_matches, &mut self.writer)?;
+                encode_bincode(&self.path_index, &mut self.writer)?;
+                encode_bincode(&self.precedence, &mut self.writer)?;
+            }
+            CacheEncoding::Json => {
+                self.writer.write_all(b"\n],\"rule_matches\":")?;
+                encode_json(&self.rule_matches, &mut self.writer)?;
+                self.writer.write_all(b",\"path_index\":")?;
+                encode_json(&self.path_index, &mut self.writer)?;
+                self.writer.write_all(b",\"precedence\":")?;
+                encode_json(&self.precedence, &mut self.writer)?;
+                self.writer.write_all(b"}\n")?;
+            }
+            #[cfg(feature = "sqlite")]
+            CacheEncoding::Sqlite => unreachable!("CacheWriter::new rejects SQLite"),
+            #[cfg(feature = "archive")]
+            CacheEncoding::Archive => unreachable!("CacheWriter::new rejects archives"),
+        }
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+/// Where resolved file entries are written, in order, with the rules that decided them
+pub(crate) trait FileSink {
+    fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()>;
+}
+
+impl<W: Write> FileSink for CacheWriter<W> {
+    fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        CacheWriter::write_file(self, file, rules)
+    }
+}
+
+fn encode_bincode<T: serde::Serialize + ?Sized, W: Write>(value: &T, writer: &mut W) -> Result<()> {
+    bincode::serde::encode_into_std_write(value, writer, bincode::config::standard())
+        .map(|_| ())
+        .map_err(|e| Error::new(&format!("Failed to serialize cache: {}", e)))
+}
+
+fn encode_json<T: serde::Serialize + ?Sized, W: Write>(value: &T, writer: &mut W) -> Result<()> {
+    serde_json::to_writer(writer, value)
+        .map_err(|e| Error::new(&format!("Failed to serialize cache to JSON: {}", e)))
+}
+
+/// Resolve owners and tags for `files` and stream the resulting cache into `writer`
+///
+/// Files are resolved in chunks sized so the pending entries stay within `memory_budget`
+/// bytes; only one chunk is held in memory at a time. `MatcherStrategy::Auto` is resolved
+/// against the matcher profile of `ctx`, and files are resolved with its precedence policy
+/// and match options. Tags of rules and inline markers are renamed with `remap`. `source`
+/// is recorded in the build info, for updating the cache later. Stops with an interrupted
+/// error on Ctrl-C.
+#[allow(clippy::too_many_arguments)]
+pub fn build_cache<W: Write>(
+    ctx: &Context, entries: &[CodeownersEntry], files: &[PathBuf], hash: [u8; 32],
+    source: &RepoState, strategy: MatcherStrategy, remap: &TagRemap, writer: W,
+    encoding: CacheEncoding, memory_budget: usize,
+) -> Result<W> {
+    resolve_files(
+        ctx,
+        entries,
+        files,
+        source,
+        strategy,
+        remap,
+        memory_budget,
+        |build, entries, precedence| {
+            CacheWriter::new(
+                writer,
+                encoding,
+                build,
+                &hash,
+                entries,
+                files.len(),
+                precedence,
+            )
+        },
+    )?
+    .finish()
+}
+
+/// Resolve `files` as [`build_cache`] does, into the sink `open` creates for the build
+/// info, the remapped rules and the precedence policy
+#[allow(clippy::too_many_arguments)]
+fn resolve_files<S: FileSink>(
+    ctx: &Context, entries: &[CodeownersEntry], files: &[PathBuf], source: &RepoState,
+    strategy: MatcherStrategy, remap: &TagRemap, memory_budget: usize,
+    open: impl FnOnce(&BuildInfo, &[CodeownersEntry], PrecedencePolicy) -> Result<S>,
+) -> Result<S> {
+    let config = &ctx.config;
+    let remapped;
+    let entries = if remap.is_empty() {
+        entries
+    } else {
+        remapped = remap.apply_to_entries(entries);
+        &remapped
+    };
+    let strategy = strategy.select(entries.len(), files.len(), &config.matcher_profile);
+    log::debug!(
+        "Matching {} files against {} rules with the {} matcher",
+        files.len(),
+        entries.len(),
+        strategy
+    );
+    let matcher = Matcher::build_with_options(entries, strategy, config.match_options());
+    telemetry::note_repo_size(files.len());
+
+    let precedence = config.precedence;
+    let build = BuildInfo {
+        source: Some(source.clone()),
+        ..BuildInfo::new(
+            &strategy.to_string(),
+            config.match_options(),
+            &config.generated_markers,
+        )
+    };
+    let mut sink = open(&build, entries, precedence)?;
 
     // Process each file to find owners and tags
     let total_files = files.len();
     let processed_count = std::sync::atomic::AtomicUsize::new(0);
 
-    let file_entries: Vec<FileEntry> = files
-        .par_chunks(100)
-        .flat_map(|chunk| {
-            chunk
-                .iter()
-                .map(|file_path| {
-                    let current =
-                        processed_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
-
-                    // Limit filename display length and clear the line properly
-                    let file_display = file_path.display().to_string();
-                    let truncated_file = if file_display.len() > 60 {
-                        format!("...{}", &file_display[file_display.len() - 57..])
-                    } else {
-                        file_display
-                    };
+    let mut chunk_len = INITIAL_CHUNK_FILES;
+    let mut start = 0;
+    while start < total_files {
+        let end = (start + chunk_len).min(total_files);
+
+        let chunk: Vec<(FileEntry, Vec<usize>)> = files[start..end]
+            .par_iter()
+            .map(|file_path| {
+                interrupt::check()?;
+
+                let current =
+                    processed_count.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+
+                // Limit filename display length and clear the line properly
+                let file_display = file_path.display().to_string();
+                let truncated_file = if file_display.len() > 60 {
+                    format!("...{}", &file_display[file_display.len() - 57..])
+                } else {
+                    file_display
+                };
 
+                if !output::is_stable() {
                     print!(
                         "\r\x1b[K📁 Processing [{}/{}] {}",
                         current, total_files, truncated_file
                     );
                     std::io::stdout().flush().unwrap();
+                }
 
-                    let (owners, tags) =
-                        find_owners_and_tags_for_file(file_path, &matched_entries).unwrap();
+                let (owners, tags, rules, inline) =
+                    resolve_file_with_source(file_path, entries, &matcher, precedence)?;
+                // Rule tags are already remapped; only inline tags are left
+                let tags = if rules.is_empty() {
+                    remap.apply(tags)
+                } else {
+                    tags
+                };
 
-                    // Build file entry
+                // Build file entry
+                Ok((
                     FileEntry {
                         path: file_path.clone(),
-                        owners: owners.clone(),
-                        tags: tags.clone(),
-                    }
-                })
-                .collect::<Vec<FileEntry>>()
-        })
-        .collect();
+                        owners,
+                        tags,
+                        generated: is_generated(file_path, &config.generated_markers),
+                        inline,
+                    },
+                    rules,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (file, rules) in &chunk {
+            sink.write_file(file, rules)?;
+        }
+
+        // Size the next chunk from this chunk's average entry size
+        let chunk_bytes: usize = chunk.iter().map(|(file, _)| estimated_size(file)).sum();
+        let average = (chunk_bytes / chunk.len()).max(1);
+        chunk_len = (memory_budget / average).max(1);
+        start = end;
+    }
 
     // Print newline after processing is complete
-    println!("\r\x1b[K✅ Processed {} files successfully", total_files);
-
-    // Process each owner
-    let owners = collect_owners(&entries);
-    owners.iter().for_each(|owner| {
-        let paths = owners_map.entry(owner.clone()).or_insert_with(Vec::new);
-        for file_entry in &file_entries {
-            if file_entry.owners.contains(owner) {
-                paths.push(file_entry.path.clone());
-            }
+    if output::is_stable() {
+        println!("Processed {} files successfully", total_files);
+    } else {
+        println!("\r\x1b[K✅ Processed {} files successfully", total_files);
+    }
+
+    Ok(sink)
+}
+
+/// Rough in-memory size of a file entry, used to size chunks
+fn estimated_size(entry: &FileEntry) -> usize {
+    std::mem::size_of::<FileEntry>()
+        + entry.path.as_os_str().len()
+        + entry
+            .owners
+            .iter()
+            .map(|owner| std::mem::size_of::<Owner>() + owner.identifier.len())
+            .sum::<usize>()
+        + entry
+            .tags
+            .iter()
+            .map(|tag| std::mem::size_of::<Tag>() + tag.0.len())
+            .sum::<usize>()
+}
+
+/// Build the cache for `files` and write it to `path`
+///
+/// `memory_budget` is in bytes; see [`build_cache`]. The file is replaced atomically, so
+/// an interrupted or failed build leaves any previous cache in place.
+#[allow(clippy::too_many_arguments)]
+pub fn write_cache(
+    ctx: &Context, entries: &[CodeownersEntry], files: &[PathBuf], hash: [u8; 32],
+    source: &RepoState, strategy: MatcherStrategy, remap: &TagRemap, path: &Path,
+    encoding: CacheEncoding, memory_budget: usize,
+) -> Result<()> {
+    #[cfg(feature = "sqlite")]
+    if encoding == CacheEncoding::Sqlite {
+        return replace_file_atomically(path, |temp_path| {
+            resolve_files(
+                ctx,
+                entries,
+                files,
+                source,
+                strategy,
+                remap,
+                memory_budget,
+                |build, entries, precedence| {
+                    SqliteWriter::create(temp_path, build, &hash, entries, precedence)
+                },
+            )?
+            .finish()
+        });
+    }
+
+    #[cfg(feature = "archive")]
+    if encoding == CacheEncoding::Archive {
+        return replace_file_atomically(path, |temp_path| {
+            resolve_files(
+                ctx,
+                entries,
+                files,
+                source,
+                strategy,
+                remap,
+                memory_budget,
+                |build, entries, precedence| {
+                    ArchiveWriter::create(temp_path, build, &hash, entries, precedence)
+                },
+            )?
+            .finish()
+        });
+    }
+
+    write_file_atomically(path, |writer| {
+        build_cache(
+            ctx,
+            entries,
+            files,
+            hash,
+            source,
+            strategy,
+            remap,
+            writer,
+            encoding,
+            memory_budget,
+        )?;
+        Ok(())
+    })
+}
+
+/// A cache file as read from disk, in the layout it was written in
+#[derive(Debug)]
+pub struct StoredCache {
+    /// Layout of the file, see [`CACHE_VERSION`]
+    pub version: u32,
+    pub encoding: CacheEncoding,
+    /// The cache as stored; older layouts still need [`migrate_cache`]
+    pub cache: CodeownersCache,
+}
+
+/// Bincode body of layouts 2 and up, after the header of layouts 3 and up
+#[derive(Deserialize)]
+struct BincodeBody<E, F> {
+    hash: [u8; 32],
+    entries: Vec<E>,
+    files: Vec<F>,
+    rule_matches: Vec<Vec<usize>>,
+    path_index: PathIndex,
+    precedence: PrecedencePolicy,
+}
+
+/// File entry of layout 1, before files were flagged generated
+#[derive(Deserialize)]
+struct FileEntryV1 {
+    path: PathBuf,
+    owners: Vec<Owner>,
+    tags: Vec<Tag>,
+}
+
+/// Rule of layouts 1 to 3, before rules carried annotations
+#[derive(Deserialize)]
+struct CodeownersEntryV3 {
+    source_file: PathBuf,
+    line_number: usize,
+    pattern: String,
+    owners: Vec<Owner>,
+    tags: Vec<Tag>,
+    spans: EntrySpans,
+}
+
+impl From<CodeownersEntryV3> for CodeownersEntry {
+    fn from(entry: CodeownersEntryV3) -> CodeownersEntry {
+        CodeownersEntry {
+            source_file: entry.source_file,
+            line_number: entry.line_number,
+            pattern: entry.pattern,
+            owners: entry.owners,
+            tags: entry.tags,
+            spans: entry.spans,
+            metadata: Default::default(),
+            section: None,
         }
-    });
+    }
+}
 
-    // Process each tag
-    let tags = collect_tags(&entries);
-    tags.iter().for_each(|tag| {
-        let paths = tags_map.entry(tag.clone()).or_insert_with(Vec::new);
-        for file_entry in &file_entries {
-            if file_entry.tags.contains(tag) {
-                paths.push(file_entry.path.clone());
-            }
+/// Rule of layout 4, before rules carried their section
+#[derive(Deserialize)]
+struct CodeownersEntryV4 {
+    source_file: PathBuf,
+    line_number: usize,
+    pattern: String,
+    owners: Vec<Owner>,
+    tags: Vec<Tag>,
+    spans: EntrySpans,
+    metadata: BTreeMap<String, String>,
+}
+
+impl From<CodeownersEntryV4> for CodeownersEntry {
+    fn from(entry: CodeownersEntryV4) -> CodeownersEntry {
+        CodeownersEntry {
+            source_file: entry.source_file,
+            line_number: entry.line_number,
+            pattern: entry.pattern,
+            owners: entry.owners,
+            tags: entry.tags,
+            spans: entry.spans,
+            metadata: entry.metadata,
+            section: None,
         }
-    });
+    }
+}
 
-    Ok(CodeownersCache {
-        hash,
-        entries,
-        files: file_entries,
-        owners_map,
-        tags_map,
-    })
+impl From<FileEntryV1> for FileEntry {
+    fn from(file: FileEntryV1) -> FileEntry {
+        FileEntry {
+            path: file.path,
+            owners: file.owners,
+            tags: file.tags,
+            generated: false,
+            inline: None,
+        }
+    }
+}
+
+/// File entry of layouts 2 to 9, before files recorded their inline declaration
+#[derive(Deserialize)]
+struct FileEntryV9 {
+    path: PathBuf,
+    owners: Vec<Owner>,
+    tags: Vec<Tag>,
+    generated: bool,
+}
+
+impl From<FileEntryV9> for FileEntry {
+    fn from(file: FileEntryV9) -> FileEntry {
+        FileEntry {
+            path: file.path,
+            owners: file.owners,
+            tags: file.tags,
+            generated: file.generated,
+            inline: None,
+        }
+    }
 }
 
-/// Store Cache
-pub fn store_cache(cache: &CodeownersCache, path: &Path, encoding: CacheEncoding) -> Result<()> {
-    let parent = path
-        .parent()
-        .ok_or_else(|| Error::new("Invalid cache path"))?;
-    std::fs::create_dir_all(parent)?;
+/// Build info of layout 8, before it recorded the repository state
+#[derive(Deserialize)]
+struct BuildInfoV8 {
+    tool_version: String,
+    matcher: String,
+    case_insensitive: bool,
+    dialect: Dialect,
+    generated_markers: Vec<String>,
+}
 
-    let file = std::fs::File::create(path)?;
-    let mut writer = std::io::BufWriter::new(file);
+impl From<BuildInfoV8> for BuildInfo {
+    fn from(build: BuildInfoV8) -> BuildInfo {
+        BuildInfo {
+            tool_version: build.tool_version,
+            matcher: build.matcher,
+            case_insensitive: build.case_insensitive,
+            dialect: build.dialect,
+            generated_markers: build.generated_markers,
+            source: None,
+        }
+    }
+}
 
-    match encoding {
-        CacheEncoding::Bincode => {
-            bincode::serde::encode_into_std_write(cache, &mut writer, bincode::config::standard())
-                .map_err(|e| Error::new(&format!("Failed to serialize cache: {}", e)))?;
+/// JSON cache of any layout; fields it predates take their defaults
+#[derive(Deserialize)]
+struct JsonLayout {
+    #[serde(default)]
+    version: u32,
+    #[serde(default)]
+    build: BuildInfo,
+    hash: [u8; 32],
+    entries: Vec<CodeownersEntry>,
+    files: Vec<JsonFileEntry>,
+    #[serde(default)]
+    rule_matches: Vec<Vec<usize>>,
+    #[serde(default)]
+    path_index: PathIndex,
+    #[serde(default)]
+    precedence: PrecedencePolicy,
+}
+
+#[derive(Deserialize)]
+struct JsonFileEntry {
+    path: PathBuf,
+    owners: Vec<Owner>,
+    tags: Vec<Tag>,
+    generated: Option<bool>,
+    #[serde(default)]
+    inline: Option<InlineDeclaration>,
+}
+
+impl<E: Into<CodeownersEntry>, F: Into<FileEntry>> BincodeBody<E, F> {
+    fn into_cache(self) -> Result<CodeownersCache> {
+        CodeownersCache::from_stored(
+            self.hash,
+            self.entries.into_iter().map(Into::into).collect(),
+            self.files.into_iter().map(Into::into).collect(),
+            self.rule_matches,
+            self.path_index,
+            self.precedence,
+        )
+        .map_err(Error::new)
+    }
+}
+
+/// Read a cache file in any layout this version knows, detecting JSON, SQLite, archives or
+/// bincode
+///
+/// Layouts newer than [`CACHE_VERSION`] and caches too old to migrate are errors.
+pub fn read_cache(path: &Path) -> Result<StoredCache> {
+    if is_archive(path) {
+        #[cfg(feature = "archive")]
+        {
+            let archive = ArchiveCache::open(path)?;
+            let version = archive.version;
+            check_version(version)?;
+            return Ok(StoredCache {
+                version,
+                encoding: CacheEncoding::Archive,
+                cache: archive.load()?,
+            });
         }
-        CacheEncoding::Json => {
-            serde_json::to_writer_pretty(&mut writer, cache)
-                .map_err(|e| Error::new(&format!("Failed to serialize cache to JSON: {}", e)))?;
+        #[cfg(not(feature = "archive"))]
+        return Err(Error::new(&format!(
+            "{} is an archived cache, which this build cannot read; rebuild it with `ci codeowners parse`",
+            path.display()
+        )));
+    }
+    if is_sqlite(path) {
+        #[cfg(feature = "sqlite")]
+        {
+            let sqlite = SqliteCache::open(path)?;
+            let version = sqlite.version;
+            check_version(version)?;
+            return Ok(StoredCache {
+                version,
+                encoding: CacheEncoding::Sqlite,
+                cache: sqlite.load()?,
+            });
         }
+        #[cfg(not(feature = "sqlite"))]
+        return Err(Error::new(&format!(
+            "{} is a SQLite cache, which this build cannot read; rebuild it with `ci codeowners parse`",
+            path.display()
+        )));
+    }
+
+    let open = || {
+        std::fs::File::open(path)
+            .map(std::io::BufReader::new)
+            .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))
+    };
+
+    let mut reader = open()?;
+    if reader.fill_buf()?.first() == Some(&b'{') {
+        let layout: JsonLayout = serde_json::from_reader(reader)
+            .map_err(|e| Error::new(&format!("Failed to deserialize JSON cache: {}", e)))?;
+        // Unversioned caches are layout 2 once every file is flagged
+        let version = match layout.version {
+            0 if layout.files.iter().all(|file| file.generated.is_some()) => 2,
+            0 => 1,
+            version => version,
+        };
+        check_version(version)?;
+        let files = layout
+            .files
+            .into_iter()
+            .map(|file| FileEntry {
+                path: file.path,
+                owners: file.owners,
+                tags: file.tags,
+                generated: file.generated.unwrap_or(false),
+                inline: file.inline,
+            })
+            .collect();
+        let cache = CodeownersCache::from_stored(
+            layout.hash,
+            layout.entries,
+            files,
+            layout.rule_matches,
+            layout.path_index,
+            layout.precedence,
+        )
+        .map_err(Error::new)?;
+        return Ok(StoredCache {
+            version,
+            encoding: CacheEncoding::Json,
+            cache: CodeownersCache {
+                build: layout.build,
+                ..cache
+            },
+        });
     }
 
-    writer.flush()?;
+    let config = bincode::config::standard();
+    let mut header = [0u8; CACHE_FORMAT.len() + 1];
+    let headed = reader.read_exact(&mut header).is_ok()
+        && header[0] as usize == CACHE_FORMAT.len()
+        && &header[1..] == CACHE_FORMAT.as_bytes();
+    let (version, cache) = if headed {
+        let version: u32 = bincode::serde::decode_from_std_read(&mut reader, config)
+            .map_err(|e| Error::new(&format!("Failed to deserialize cache: {}", e)))?;
+        check_version(version)?;
+        let build: BuildInfo = if version >= 9 {
+            bincode::serde::decode_from_std_read(&mut reader, config)
+                .map_err(|e| Error::new(&format!("Failed to deserialize cache: {}", e)))?
+        } else if version == 8 {
+            let build: BuildInfoV8 = bincode::serde::decode_from_std_read(&mut reader, config)
+                .map_err(|e| Error::new(&format!("Failed to deserialize cache: {}", e)))?;
+            build.into()
+        } else {
+            BuildInfo::default()
+        };
+        let cache = if version >= 10 {
+            decode_to_end::<BincodeBody<CodeownersEntry, FileEntry>>(&mut reader)?.into_cache()?
+        } else if version >= 5 {
+            decode_to_end::<BincodeBody<CodeownersEntry, FileEntryV9>>(&mut reader)?.into_cache()?
+        } else if version == 4 {
+            decode_to_end::<BincodeBody<CodeownersEntryV4, FileEntryV9>>(&mut reader)?
+                .into_cache()?
+        } else {
+            decode_to_end::<BincodeBody<CodeownersEntryV3, FileEntryV9>>(&mut reader)?
+                .into_cache()?
+        };
+        (version, CodeownersCache { build, ..cache })
+    } else {
+        // No header: layout 2, or layout 1 if its file entries do not fit
+        match decode_to_end::<BincodeBody<CodeownersEntryV3, FileEntryV9>>(&mut open()?) {
+            Ok(body) => (2, body.into_cache()?),
+            Err(_) => {
+                let body: BincodeBody<CodeownersEntryV3, FileEntryV1> = decode_to_end(&mut open()?)
+                    .map_err(|e| Error::new(&format!("Unknown cache format: {}", e)))?;
+                (1, body.into_cache()?)
+            }
+        }
+    };
+
+    Ok(StoredCache {
+        version,
+        encoding: CacheEncoding::Bincode,
+        cache,
+    })
+}
+
+/// Whether the file at `path` is a SQLite database
+pub(crate) fn is_sqlite(path: &Path) -> bool {
+    starts_with(path, SQLITE_HEADER)
+}
+
+/// Whether the file at `path` is an archived cache
+pub(crate) fn is_archive(path: &Path) -> bool {
+    starts_with(path, ARCHIVE_HEADER)
+}
+
+fn starts_with(path: &Path, expected: &[u8; 16]) -> bool {
+    let mut header = [0u8; 16];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header == *expected
+}
 
+fn check_version(version: u32) -> Result<()> {
+    if version > CACHE_VERSION {
+        return Err(Error::new(&format!(
+            "Cache format {} was written by a newer version; this version reads up to {}",
+            version, CACHE_VERSION
+        )));
+    }
     Ok(())
 }
 
-/// Load Cache from file, automatically detecting whether it's JSON or Bincode format
+/// Decode a bincode value that must take up the rest of `reader`
+fn decode_to_end<T: serde::de::DeserializeOwned>(
+    reader: &mut std::io::BufReader<std::fs::File>,
+) -> Result<T> {
+    let value = bincode::serde::decode_from_std_read(reader, bincode::config::standard())
+        .map_err(|e| Error::new(&format!("Failed to deserialize cache: {}", e)))?;
+    if !reader.fill_buf()?.is_empty() {
+        return Err(Error::new("Unexpected data after the end of the cache"));
+    }
+    Ok(value)
+}
+
+/// Load a cache file, automatically detecting whether it's JSON or Bincode format
+///
+/// Caches in an older layout are an error; [`read_cache`] reads them for
+/// [`migrate_cache`].
 pub fn load_cache(path: &Path) -> Result<CodeownersCache> {
-    // Read the first byte to make an educated guess about the format
-    let mut file = std::fs::File::open(path)
-        .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))?;
-
-    let mut first_byte = [0u8; 1];
-    let read_result = file.read_exact(&mut first_byte);
-
-    // Close the file handle and reopen for full reading
-    drop(file);
-
-    if read_result.is_ok() && first_byte[0] == b'{' {
-        // First byte is '{', likely JSON
-        let file = std::fs::File::open(path)
-            .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))?;
-        let reader = std::io::BufReader::new(file);
-
-        return serde_json::from_reader(reader)
-            .map_err(|e| Error::new(&format!("Failed to deserialize JSON cache: {}", e)));
-    }
-
-    // Try bincode first since it's not JSON
-    let file = std::fs::File::open(path)
-        .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))?;
-    let mut reader = std::io::BufReader::new(file);
-
-    match bincode::serde::decode_from_std_read(&mut reader, bincode::config::standard()) {
-        Ok(cache) => Ok(cache),
-        Err(_) => {
-            // If bincode fails and it's not obviously JSON, still try JSON as a fallback
-            let file = std::fs::File::open(path)
-                .map_err(|e| Error::new(&format!("Failed to open cache file: {}", e)))?;
-            let reader = std::io::BufReader::new(file);
-
-            serde_json::from_reader(reader).map_err(|e| {
-                Error::new(&format!(
-                    "Failed to deserialize cache in any supported format: {}",
-                    e
-                ))
-            })
+    let stored = read_cache(path)?;
+    check_current_layout(path, stored.version)?;
+    Ok(stored.cache)
+}
+
+/// Load a cache file like [`load_cache`], or just what `query` selects of it
+///
+/// SQLite and archived caches are read in part, as by [`sync_cache_for`]; the others are
+/// loaded whole. The cache is not checked against the repository.
+pub fn load_cache_for(path: &Path, query: CacheQuery<'_>) -> Result<CodeownersCache> {
+    #[cfg(feature = "archive")]
+    if is_archive(path) {
+        let archive = ArchiveCache::open(path)?;
+        check_version(archive.version)?;
+        check_current_layout(path, archive.version)?;
+        return archive.query(query);
+    }
+    #[cfg(feature = "sqlite")]
+    if is_sqlite(path) {
+        let sqlite = SqliteCache::open(path)?;
+        check_version(sqlite.version)?;
+        check_current_layout(path, sqlite.version)?;
+        return sqlite.query(query);
+    }
+    #[cfg(not(any(feature = "sqlite", feature = "archive")))]
+    let _ = query;
+    load_cache(path)
+}
+
+/// Fail unless a cache in layout `version` can be used without migrating it
+fn check_current_layout(path: &Path, version: u32) -> Result<()> {
+    if version < CACHE_VERSION {
+        return Err(Error::new(&format!(
+            "{} is in cache format {} and needs migrating to {}; run `ci codeowners cache migrate` or rebuild it",
+            path.display(),
+            version,
+            CACHE_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Bring a cache read in an older layout up to [`CACHE_VERSION`], one layout at a time
+///
+/// Each step fills in only what its layout added, far cheaper than resolving every file
+/// again. Files are read from the paths the cache records, as when it was built.
+pub fn migrate_cache(ctx: &Context, stored: StoredCache) -> CodeownersCache {
+    let mut cache = stored.cache;
+    for version in stored.version..CACHE_VERSION {
+        match version {
+            // 1 → 2: flag generated files
+            1 => cache.files.par_iter_mut().for_each(|file| {
+                file.generated = is_generated(&file.path, &ctx.config.generated_markers);
+            }),
+            // 3 → 4: read the annotations of the rules
+            3 => annotate_entries(&mut cache.entries),
+            // 4 → 5: section headers were read as rules, so files with sections need
+            // resolving again; a hash no repository has makes the next load rebuild
+            4 if has_sections(&cache.entries) => cache.hash = [0; 32],
+            // 5 → 6: negations were ignored, so the files they exclude need resolving again
+            5 if cache.entries.iter().any(CodeownersEntry::is_negation) => cache.hash = [0; 32],
+            // 6 → 7: quotes were part of patterns, and split those with spaces
+            6 if cache
+                .entries
+                .iter()
+                .any(|entry| entry.pattern.contains('"')) =>
+            {
+                cache.hash = [0; 32]
+            }
+            // 9 → 10: record inline declarations, now read from front matter too
+            9 => record_inline(&mut cache),
+            // 2 → 3 only added the header, which is written on save; 7 → 8 added the build
+            // info and 8 → 9 its repository state, which are left unrecorded
+            _ => {}
+        }
+    }
+    cache
+}
+
+/// Record the inline declaration of each file owned without a rule
+///
+/// Markdown front matter naming owners overrides the rules and markers the cache was
+/// resolved with, so if any file has it, a hash no repository has makes the next load
+/// rebuild.
+fn record_inline(cache: &mut CodeownersCache) {
+    let mut decided = vec![false; cache.files.len()];
+    for &position in cache.rule_matches.iter().flatten() {
+        decided[position] = true;
+    }
+
+    let mut stale = false;
+    for (file, decided) in cache.files.iter_mut().zip(decided) {
+        let declared = !decided
+            && file
+                .owners
+                .iter()
+                .any(|owner| owner.owner_type != OwnerType::Unowned);
+        if !declared && !reads_front_matter(&file.path) {
+            continue;
+        }
+        match detect_inline_codeowners(&file.path) {
+            Ok(Some(entry)) if entry.source == InlineSource::FrontMatter => stale = true,
+            Ok(Some(entry)) if declared => file.inline = Some(InlineDeclaration::from(&entry)),
+            _ => {}
+        }
+    }
+    if stale {
+        cache.hash = [0; 32];
+    }
+}
+
+/// Copy the annotations of each rule from its CODEOWNERS file
+///
+/// Rules whose file no longer has the same pattern on their line are left without.
+fn annotate_entries(entries: &mut [CodeownersEntry]) {
+    let mut sources: HashMap<PathBuf, Vec<CodeownersEntry>> = HashMap::new();
+    for entry in entries {
+        let parsed = sources
+            .entry(entry.source_file.clone())
+            .or_insert_with(|| parse_codeowners(&entry.source_file).unwrap_or_default());
+        if let Some(source) = parsed.iter().find(|source| {
+            source.line_number == entry.line_number && source.pattern == entry.pattern
+        }) {
+            entry.metadata = source.metadata.clone();
+        }
+    }
+}
+
+/// Whether any CODEOWNERS file of `entries` has GitLab sections
+fn has_sections(entries: &[CodeownersEntry]) -> bool {
+    let mut sources: Vec<&Path> = entries
+        .iter()
+        .map(|entry| entry.source_file.as_path())
+        .collect();
+    sources.sort_unstable();
+    sources.dedup();
+    sources.into_iter().any(|source| {
+        parse_codeowners(source)
+            .is_ok_and(|parsed| parsed.iter().any(|entry| entry.section.is_some()))
+    })
+}
+
+/// Write a whole cache to `path` in `encoding`, replacing it atomically
+pub fn save_cache(cache: &CodeownersCache, path: &Path, encoding: CacheEncoding) -> Result<()> {
+    let mut rules = vec![Vec::new(); cache.files.len()];
+    for (rule, positions) in cache.rule_matches.iter().enumerate() {
+        for &position in positions {
+            if let Some(file_rules) = rules.get_mut(position) {
+                file_rules.push(rule);
+            }
         }
     }
+
+    #[cfg(feature = "sqlite")]
+    if encoding == CacheEncoding::Sqlite {
+        return replace_file_atomically(path, |temp_path| {
+            let mut writer = SqliteWriter::create(
+                temp_path,
+                &cache.build,
+                &cache.hash,
+                &cache.entries,
+                cache.precedence,
+            )?;
+            for (file, rules) in cache.files.iter().zip(&rules) {
+                writer.write_file(file, rules)?;
+            }
+            writer.finish()
+        });
+    }
+
+    #[cfg(feature = "archive")]
+    if encoding == CacheEncoding::Archive {
+        return replace_file_atomically(path, |temp_path| {
+            let mut writer = ArchiveWriter::create(
+                temp_path,
+                &cache.build,
+                &cache.hash,
+                &cache.entries,
+                cache.precedence,
+            )?;
+            for (file, rules) in cache.files.iter().zip(&rules) {
+                writer.write_file(file, rules)?;
+            }
+            writer.finish()
+        });
+    }
+
+    write_file_atomically(path, |writer| {
+        let mut cache_writer = CacheWriter::new(
+            writer,
+            encoding,
+            &cache.build,
+            &cache.hash,
+            &cache.entries,
+            cache.files.len(),
+            cache.precedence,
+        )?;
+        for (file, rules) in cache.files.iter().zip(&rules) {
+            cache_writer.write_file(file, rules)?;
+        }
+        cache_writer.finish()?;
+        Ok(())
+    })
+}
+
+/// Note the file and unowned counts of `cache` for the run summary
+///
+/// Generated files are not counted as unowned.
+pub(crate) fn note_summary(cache: &CodeownersCache) {
+    summary::note("files", cache.files.len() as u64);
+    summary::note("unowned", unowned_count(cache) as u64);
 }
 
+/// Number of files of `cache` without an owner, leaving out generated ones
+pub(crate) fn unowned_count(cache: &CodeownersCache) -> usize {
+    cache
+        .files
+        .iter()
+        .filter(|file| !file.generated)
+        .filter(|file| {
+            file.owners
+                .iter()
+                .all(|o| o.owner_type == OwnerType::Unowned)
+        })
+        .count()
+}
+
+/// Load the cache for `repo`, building or rebuilding it when it is missing or stale
+///
+/// Rules left out by the [`rule_filter`] set for the process are left out of the cache
+/// returned, not of the one on disk.
 pub fn sync_cache(
-    repo: &std::path::Path, cache_file: Option<&std::path::Path>,
+    ctx: &Context, repo: &std::path::Path, cache_file: Option<&std::path::Path>,
 ) -> Result<CodeownersCache> {
-    let config_cache_file = crate::utils::app_config::AppConfig::fetch()?
-        .cache_file
-        .clone();
+    let cache = rule_filter::apply_current(ctx, load_or_rebuild(ctx, repo, cache_file)?)?;
+    note_summary(&cache);
+    Ok(cache)
+}
 
-    let cache_file: &std::path::Path = match cache_file {
-        Some(file) => file.into(),
-        None => std::path::Path::new(&config_cache_file),
+/// Load the cache for `repo` like [`sync_cache`], narrowed to the subtree at `scope`
+///
+/// `scope` is relative to `repo`; rules inherited from CODEOWNERS files above it still
+/// apply, see [`CodeownersCache::scoped`].
+pub fn sync_scoped_cache(
+    ctx: &Context, repo: &std::path::Path, cache_file: Option<&std::path::Path>,
+    scope: Option<&std::path::Path>,
+) -> Result<CodeownersCache> {
+    let Some(scope) = scope else {
+        return sync_cache(ctx, repo, cache_file);
     };
+    let dir = repo.join(scope);
+    if !dir.is_dir() {
+        return Err(Error::new(&format!(
+            "Scope {} is not a directory of {}",
+            scope.display(),
+            repo.display()
+        )));
+    }
+
+    let cache =
+        rule_filter::apply_current(ctx, load_or_rebuild(ctx, repo, cache_file)?)?.scoped(&dir);
+    note_summary(&cache);
+    Ok(cache)
+}
+
+/// What a command reads of the cache, for [`sync_cache_for`]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(any(feature = "sqlite", feature = "archive")), allow(dead_code))]
+pub enum CacheQuery<'a> {
+    /// Files with an owner whose identifier contains any of these, as `list-files
+    /// --owners` matches them
+    Owners(&'a [&'a str]),
+    /// Files at any of these paths, spelled as the cache spells them
+    Paths(&'a [PathBuf]),
+}
+
+/// Load the cache for `repo` like [`sync_cache`], or just what `query` selects of it
+///
+/// A current SQLite cache is read through its indexes for the files `query` selects, and
+/// the rules, as is a current archived cache, in place from the mapped file; repository-wide
+/// views of the result, such as its owners map, are incomplete. Any other cache, a stale
+/// one, or one read with a [`rule_filter`] set is loaded whole, so callers still filter the
+/// files themselves.
+pub fn sync_cache_for(
+    ctx: &Context, repo: &Path, cache_file: Option<&Path>, query: CacheQuery<'_>,
+) -> Result<CodeownersCache> {
+    #[cfg(feature = "sqlite")]
+    if let Some(cache) = query_current_sqlite(ctx, repo, cache_file, query)? {
+        return Ok(cache);
+    }
+    #[cfg(feature = "archive")]
+    if let Some(cache) = query_current_archive(ctx, repo, cache_file, query)? {
+        return Ok(cache);
+    }
+    #[cfg(not(any(feature = "sqlite", feature = "archive")))]
+    let _ = query;
+    sync_cache(ctx, repo, cache_file)
+}
+
+/// What `query` selects of the SQLite cache for `repo`, or `None` unless it is one, is
+/// current and no rule filter is set
+#[cfg(feature = "sqlite")]
+fn query_current_sqlite(
+    ctx: &Context, repo: &Path, cache_file: Option<&Path>, query: CacheQuery<'_>,
+) -> Result<Option<CodeownersCache>> {
+    let cache_file = cache_file_or_default(ctx, cache_file);
+    let path = repo.join(cache_file);
+    if rule_filter::is_set() || !is_sqlite(&path) {
+        return Ok(None);
+    }
+    // Unreadable caches are rebuilt by `sync_cache`
+    let Ok(sqlite) = SqliteCache::open(&path) else {
+        return Ok(None);
+    };
+    if !is_current(
+        ctx,
+        repo,
+        cache_file,
+        sqlite.version,
+        &sqlite.build,
+        sqlite.precedence,
+        &sqlite.entries,
+        &sqlite.hash,
+    )? {
+        return Ok(None);
+    }
+
+    let (files, unowned) = sqlite.counts()?;
+    note_queried(files, unowned);
+    let cache = sqlite.query(query)?;
+    log::debug!(
+        "Read {} of {} files from {}",
+        cache.files.len(),
+        files,
+        cache_file.display()
+    );
+    Ok(Some(cache))
+}
+
+/// What `query` selects of the archived cache for `repo`, or `None` unless it is one, is
+/// current and no rule filter is set
+#[cfg(feature = "archive")]
+fn query_current_archive(
+    ctx: &Context, repo: &Path, cache_file: Option<&Path>, query: CacheQuery<'_>,
+) -> Result<Option<CodeownersCache>> {
+    let cache_file = cache_file_or_default(ctx, cache_file);
+    let path = repo.join(cache_file);
+    if rule_filter::is_set() || !is_archive(&path) {
+        return Ok(None);
+    }
+    // Unreadable caches are rebuilt by `sync_cache`
+    let Ok(archive) = ArchiveCache::open(&path) else {
+        return Ok(None);
+    };
+    if !is_current(
+        ctx,
+        repo,
+        cache_file,
+        archive.version,
+        &archive.build,
+        archive.precedence,
+        &archive.entries,
+        &archive.hash,
+    )? {
+        return Ok(None);
+    }
+
+    let (files, unowned) = archive.counts();
+    note_queried(files, unowned);
+    let cache = archive.query(query)?;
+    log::debug!(
+        "Read {} of {} files in place from {}",
+        cache.files.len(),
+        files,
+        cache_file.display()
+    );
+    Ok(Some(cache))
+}
+
+/// Whether a cache read in part can be used as it is: in the current layout, built with
+/// the current settings, for the current state of `repo`
+#[cfg(any(feature = "sqlite", feature = "archive"))]
+#[allow(clippy::too_many_arguments)]
+fn is_current(
+    ctx: &Context, repo: &Path, cache_file: &Path, version: u32, build: &BuildInfo,
+    precedence: PrecedencePolicy, entries: &[CodeownersEntry], hash: &[u8; 32],
+) -> Result<bool> {
+    if version != CACHE_VERSION || needs_rebuild(ctx, repo, cache_file, build, precedence, entries)?
+    {
+        return Ok(false);
+    }
+    let config = &ctx.config;
+    Ok(*hash == get_cache_hash(repo, config.match_options(), &config.generated_markers)?)
+}
+
+/// Note the counts of a cache read in part for telemetry and the run summary, as
+/// [`note_summary`] does for a whole one
+#[cfg(any(feature = "sqlite", feature = "archive"))]
+fn note_queried(files: usize, unowned: usize) {
+    telemetry::note_repo_size(files);
+    summary::note("files", files as u64);
+    summary::note("unowned", unowned as u64);
+}
+
+fn load_or_rebuild(
+    ctx: &Context, repo: &std::path::Path, cache_file: Option<&std::path::Path>,
+) -> Result<CodeownersCache> {
+    let config = &ctx.config;
+    let cache_file = cache_file_or_default(ctx, cache_file);
+
+    // A base cache with its overlay stands in for the cache file while it can be layered
+    if !config.base_cache.is_empty() {
+        let base_file = Path::new(&config.base_cache);
+        if let Some(cache) = overlay::load_layered(ctx, repo, base_file, cache_file)? {
+            telemetry::note_repo_size(cache.files.len());
+            return Ok(cache);
+        }
+    }
 
     // Verify that the cache file exists
     if !repo.join(cache_file).exists() {
         // parse the codeowners files and build the cache
-        return parse_repo(&repo, &cache_file);
+        return rebuild(ctx, repo, cache_file, CacheEncoding::Bincode);
     }
 
-    // Load the cache from the specified file
-    let cache = load_cache(&repo.join(cache_file)).map_err(|e| {
-        crate::utils::error::Error::new(&format!(
-            "Failed to load cache from {}: {}",
-            cache_file.display(),
-            e
-        ))
-    })?;
+    // Load the cache from the specified file, migrating it if it is in an older layout and
+    // rebuilding it if it is unreadable; it is written back in the encoding it was in
+    let encoding;
+    let cache = match read_cache(&repo.join(cache_file)) {
+        Ok(stored) if stored.version < CACHE_VERSION => {
+            let version = stored.version;
+            encoding = stored.encoding;
+            let cache = migrate_cache(ctx, stored);
+            save_cache(&cache, &repo.join(cache_file), encoding)?;
+            registry::note(repo, &repo.join(cache_file), &cache);
+            log::info!(
+                "Migrated {} from cache format {} to {}",
+                cache_file.display(),
+                version,
+                CACHE_VERSION
+            );
+            cache
+        }
+        Ok(stored) => {
+            encoding = stored.encoding;
+            stored.cache
+        }
+        Err(e) => {
+            warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "Failed to load cache from {}, rebuilding: {}",
+                    cache_file.display(),
+                    e
+                ),
+            );
+            return rebuild(ctx, repo, cache_file, CacheEncoding::Bincode);
+        }
+    };
+
+    telemetry::note_repo_size(cache.files.len());
+
+    if needs_rebuild(
+        ctx,
+        repo,
+        cache_file,
+        &cache.build,
+        cache.precedence,
+        &cache.entries,
+    )? {
+        return rebuild(ctx, repo, cache_file, encoding);
+    }
 
     // verify the hash of the cache matches the current repo hash
-    let current_hash = get_repo_hash(repo)?;
-    let cache_hash = cache.hash;
+    let current_hash = get_cache_hash(repo, config.match_options(), &config.generated_markers)?;
+    if cache.hash == current_hash {
+        return Ok(cache);
+    }
 
-    if cache_hash != current_hash {
-        // parse the codeowners files and build the cache
-        return parse_repo(&repo, &cache_file);
-    } else {
+    // Resolve only the files changed since the cache was built, when they are known
+    match update_cache(ctx, repo, cache)? {
+        Some(cache) => {
+            save_cache(&cache, &repo.join(cache_file), encoding)?;
+            registry::note(repo, &repo.join(cache_file), &cache);
+            Ok(cache)
+        }
+        None => rebuild(ctx, repo, cache_file, encoding),
+    }
+}
+
+/// Download the cache for the current state of `repo` from the remote cache, or else
+/// build it
+fn rebuild(
+    ctx: &Context, repo: &Path, cache_file: &Path, encoding: CacheEncoding,
+) -> Result<CodeownersCache> {
+    if let Some(cache) = remote_cache::pull(ctx, repo, &repo.join(cache_file))? {
+        telemetry::note_repo_size(cache.files.len());
         return Ok(cache);
     }
+    parse_repo(ctx, repo, cache_file, encoding)
+}
+
+/// `cache_file`, or else the cache file of the configuration
+pub(crate) fn cache_file_or_default<'a>(
+    ctx: &'a Context, cache_file: Option<&'a Path>,
+) -> &'a Path {
+    match cache_file {
+        Some(file) => file,
+        None => Path::new(&ctx.config.cache_file),
+    }
+}
+
+/// Whether a cache with `build`, `precedence` and `entries` needs rebuilding, whatever
+/// files changed since, see [`rebuild_reasons`]
+fn needs_rebuild(
+    ctx: &Context, repo: &Path, cache_file: &Path, build: &BuildInfo, precedence: PrecedencePolicy,
+    entries: &[CodeownersEntry],
+) -> Result<bool> {
+    let reasons = rebuild_reasons(ctx, repo, build, precedence, entries)?;
+    if !reasons.is_empty() {
+        log::debug!(
+            "Rebuilding {}, which was built with {}",
+            cache_file.display(),
+            reasons.join(", ")
+        );
+    }
+    Ok(!reasons.is_empty())
+}
+
+/// What a cache with `build`, `precedence` and `entries` was built with that differs from
+/// `ctx`, or nothing if it can be used
+///
+/// A cache built with other settings, as when it is shared between machines, is rebuilt
+/// rather than trusted, as is one resolved with another precedence policy or the rules of
+/// an outdated central repository.
+pub(crate) fn rebuild_reasons(
+    ctx: &Context, repo: &Path, build: &BuildInfo, precedence: PrecedencePolicy,
+    entries: &[CodeownersEntry],
+) -> Result<Vec<String>> {
+    let config = &ctx.config;
+    let mut reasons = build.mismatches(config.match_options(), &config.generated_markers);
+    if precedence != config.precedence {
+        reasons.push(format!("precedence = {}", precedence));
+    }
+    if !central::is_current(ctx, repo, entries)? {
+        reasons.push("outdated central rules".to_string());
+    }
+    Ok(reasons)
+}
+
+/// How a cache file compares with its repository, see [`verify_cache`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CacheStatus {
+    /// Commands use it as it is
+    Current,
+    /// There is no cache file; commands build it
+    Missing,
+    /// The file cannot be read as a cache; commands rebuild it
+    Unreadable { error: String },
+    /// In an older layout; commands migrate it
+    Outdated { version: u32 },
+    /// Built with other settings, see [`rebuild_reasons`]; commands rebuild it
+    Mismatched { reasons: Vec<String> },
+    /// The repository changed since it was built; commands update or rebuild it
+    Stale,
+}
+
+impl CacheStatus {
+    /// What the next command to load the cache does to it, if anything
+    pub fn action(&self) -> Option<&'static str> {
+        match self {
+            CacheStatus::Current => None,
+            CacheStatus::Missing => Some("build"),
+            CacheStatus::Unreadable { .. } | CacheStatus::Mismatched { .. } => Some("rebuild"),
+            CacheStatus::Outdated { .. } => Some("migrate"),
+            CacheStatus::Stale => Some("update"),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheStatus::Current => write!(f, "current"),
+            CacheStatus::Missing => write!(f, "missing"),
+            CacheStatus::Unreadable { error } => write!(f, "unreadable: {}", error),
+            CacheStatus::Outdated { version } => write!(
+                f,
+                "in cache format {}, written by an older version",
+                version
+            ),
+            CacheStatus::Mismatched { reasons } => write!(f, "built with {}", reasons.join(", ")),
+            CacheStatus::Stale => write!(f, "stale; the repository changed since it was built"),
+        }
+    }
+}
+
+/// Check the cache file at `cache_file` against `repo` as commands do before using it,
+/// without migrating, updating or rebuilding it
+pub fn verify_cache(ctx: &Context, repo: &Path, cache_file: &Path) -> Result<CacheStatus> {
+    if !cache_file.exists() {
+        return Ok(CacheStatus::Missing);
+    }
+    let stored = match read_cache(cache_file) {
+        Ok(stored) => stored,
+        Err(e) => {
+            return Ok(CacheStatus::Unreadable {
+                error: e.to_string(),
+            })
+        }
+    };
+    if stored.version < CACHE_VERSION {
+        return Ok(CacheStatus::Outdated {
+            version: stored.version,
+        });
+    }
+
+    let cache = stored.cache;
+    let reasons = rebuild_reasons(ctx, repo, &cache.build, cache.precedence, &cache.entries)?;
+    if !reasons.is_empty() {
+        return Ok(CacheStatus::Mismatched { reasons });
+    }
+    let config = &ctx.config;
+    if cache.hash != get_cache_hash(repo, config.match_options(), &config.generated_markers)? {
+        return Ok(CacheStatus::Stale);
+    }
+    Ok(CacheStatus::Current)
+}
+
+/// `cache` of `repo` updated for the files changed since it was built, or `None` when it
+/// needs rebuilding
+///
+/// Changed files are those that differ between the commit the cache was built from and
+/// HEAD, or that differed from their commit at either time, see [`changed_since`]. It needs
+/// rebuilding when that was not recorded or the commit is gone, when a change touches the
+/// rules (see [`changes_rules`]), or when more than [`MAX_UPDATED_SHARE`] of the files
+/// changed, which a rebuild resolves faster in parallel.
+fn update_cache(
+    ctx: &Context, repo: &Path, cache: CodeownersCache,
+) -> Result<Option<CodeownersCache>> {
+    let Some(source) = &cache.build.source else {
+        return Ok(None);
+    };
+    let Some(changed) = changed_since(repo, source)? else {
+        return Ok(None);
+    };
+    // Spell the paths like the cache does, under `repo`
+    let changed: Vec<PathBuf> = changed.iter().map(|path| repo.join(path)).collect();
+    if changed.len() as f64 > cache.files.len() as f64 * MAX_UPDATED_SHARE
+        || changed
+            .iter()
+            .any(|path| changes_rules(ctx, repo, path, &cache.entries))
+    {
+        return Ok(None);
+    }
+
+    let cache = refresh_paths(ctx, repo, cache, &changed)?;
+    log::debug!(
+        "Updated the cache for {} paths changed since it was built",
+        changed.len()
+    );
+    Ok(Some(cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{EntrySpans, OwnerType};
+
+    fn owner(identifier: &str) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::Team,
+        }
+    }
+
+    fn entry(pattern: &str, identifier: &str, tag: &str) -> CodeownersEntry {
+        CodeownersEntry {
+            source_file: PathBuf::from("/project/CODEOWNERS"),
+            line_number: 0,
+            pattern: pattern.to_string(),
+            owners: vec![owner(identifier)],
+            tags: vec![Tag(tag.to_string())],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        }
+    }
+
+    fn file_entry(path: &str, identifier: &str, tag: &str) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: vec![owner(identifier)],
+            tags: vec![Tag(tag.to_string())],
+            generated: false,
+            inline: None,
+        }
+    }
+
+    fn round_trip(encoding: CacheEncoding) -> CodeownersCache {
+        let entries = vec![
+            entry("*.rs", "@rust", "rust"),
+            entry("*.md", "@docs", "docs"),
+        ];
+        let files: Vec<FileEntry> = (0..10)
+            .map(|i| file_entry(&format!("/project/src/{}.rs", i), "@rust", "rust"))
+            .collect();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache");
+        let build = BuildInfo::new("trie", Default::default(), &[]);
+        let mut writer = CacheWriter::new(
+            std::fs::File::create(&path).unwrap(),
+            encoding,
+            &build,
+            &[7; 32],
+            &entries,
+            files.len(),
+            PrecedencePolicy::Merge,
+        )
+        .unwrap();
+        for file in &files {
+            writer.write_file(file, &[0]).unwrap();
+        }
+        writer.finish().unwrap();
+
+        load_cache(&path).unwrap()
+    }
+
+    #[test]
+    fn test_cache_writer_round_trip() {
+        for encoding in [CacheEncoding::Bincode, CacheEncoding::Json] {
+            let cache = round_trip(encoding);
+
+            assert_eq!(cache.hash, [7; 32]);
+            assert_eq!(cache.entries.len(), 2);
+            assert_eq!(cache.files.len(), 10);
+            assert_eq!(cache.files[9].path, PathBuf::from("/project/src/9.rs"));
+
+            // Derived lookups are rebuilt on load, including rule owners with no files
+            assert_eq!(cache.owners_map[&owner("@rust")].len(), 10);
+            assert!(cache.owners_map[&owner("@docs")].is_empty());
+            assert_eq!(cache.tags_map[&Tag("rust".to_string())].len(), 10);
+
+            // Rule matches are written alongside the files
+            assert_eq!(cache.files_for_rule(0).len(), 10);
+            assert!(cache.files_for_rule(1).is_empty());
+            assert_eq!(cache.precedence, PrecedencePolicy::Merge);
+            assert_eq!(cache.build.matcher, "trie");
+            assert_eq!(cache.build.tool_version, env!("CARGO_PKG_VERSION"));
+        }
+    }
+
+    #[test]
+    fn test_cache_writer_checks_declared_count() {
+        let file = file_entry("/project/a.rs", "@rust", "rust");
+
+        let policy = PrecedencePolicy::Closest;
+        let build = BuildInfo::default();
+        let new = |encoding, count| {
+            CacheWriter::new(Vec::new(), encoding, &build, &[0; 32], &[], count, policy).unwrap()
+        };
+        let mut writer = new(CacheEncoding::Bincode, 2);
+        writer.write_file(&file, &[]).unwrap();
+        assert!(writer.finish().is_err());
+
+        let mut writer = new(CacheEncoding::Json, 0);
+        assert!(writer.write_file(&file, &[]).is_err());
+
+        let mut writer = new(CacheEncoding::Json, 1);
+        assert!(writer.write_file(&file, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_cache_matches_whole_serialization() {
+        let cache = CodeownersCache {
+            build: BuildInfo::new("bucketed", Default::default(), &["@generated".to_string()]),
+            ..CodeownersCache::new(
+                [1; 32],
+                vec![entry("*.rs", "@rust", "rust")],
+                vec![file_entry("/project/a.rs", "@rust", "rust")],
+                vec![vec![0]],
+            )
+        };
+
+        let mut writer = CacheWriter::new(
+            Vec::new(),
+            CacheEncoding::Bincode,
+            &cache.build,
+            &cache.hash,
+            &cache.entries,
+            cache.files.len(),
+            cache.precedence,
+        )
+        .unwrap();
+        writer.write_file(&cache.files[0], &[0]).unwrap();
+        let streamed = writer.finish().unwrap();
+
+        let whole = bincode::serde::encode_to_vec(&cache, bincode::config::standard()).unwrap();
+        assert_eq!(streamed, whole);
+    }
+
+    #[test]
+    fn test_migrate_older_layouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let generated = dir.path().join("schema.rs");
+        let handwritten = dir.path().join("lib.rs");
+        std::fs::write(&generated, "// @generated by protoc\n").unwrap();
+        std::fs::write(&handwritten, "fn main() {}\n").unwrap();
+        let codeowners = dir.path().join("CODEOWNERS");
+        std::fs::write(&codeowners, "*.rs @rust #rust # escalation:P1\n").unwrap();
+        let entries = vec![CodeownersEntry {
+            source_file: codeowners,
+            ..entry("*.rs", "@rust", "rust")
+        }];
+        // Rules of layouts 1 to 3, without annotations
+        let entries_v3: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                let fields = (&e.source_file, e.line_number, &e.pattern, &e.owners);
+                (fields, &e.tags, &e.spans)
+            })
+            .collect();
+        let paths = [generated.clone(), handwritten.clone()];
+        let rule_matches: Vec<Vec<usize>> = vec![vec![0, 1]];
+        let index = PathIndex::from_paths(paths.iter().map(PathBuf::as_path));
+        let ctx = Context::default();
+
+        // Layout 1: bincode, no header, files without the generated flag
+        let files_v1: Vec<(PathBuf, Vec<Owner>, Vec<Tag>)> = paths
+            .iter()
+            .map(|path| (path.clone(), vec![owner("@rust")], Vec::new()))
+            .collect();
+        let v1 = (
+            [3u8; 32],
+            &entries_v3,
+            files_v1,
+            &rule_matches,
+            &index,
+            PrecedencePolicy::Closest,
+        );
+        let path = dir.path().join("v1.cache");
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(&v1, bincode::config::standard()).unwrap(),
+        )
+        .unwrap();
+
+        assert!(load_cache(&path).is_err());
+        let stored = read_cache(&path).unwrap();
+        assert_eq!(stored.version, 1);
+        assert_eq!(stored.encoding, CacheEncoding::Bincode);
+        let cache = migrate_cache(&ctx, stored);
+        assert!(cache.files[0].generated && !cache.files[1].generated);
+
+        save_cache(&cache, &path, CacheEncoding::Bincode).unwrap();
+        let migrated = load_cache(&path).unwrap();
+        assert_eq!(migrated.hash, [3; 32]);
+        assert!(migrated.files[0].generated);
+        assert_eq!(migrated.files_for_rule(0).len(), 2);
+
+        // Layout 2: bincode, no header
+        let files_v9 = |cache: &CodeownersCache| -> Vec<_> {
+            cache
+                .files
+                .iter()
+                .map(|f| {
+                    (
+                        f.path.clone(),
+                        f.owners.clone(),
+                        f.tags.clone(),
+                        f.generated,
+                    )
+                })
+                .collect()
+        };
+        let files_v2 = files_v9(&cache);
+        let v2 = (
+            [3u8; 32],
+            &entries_v3,
+            files_v2,
+            &rule_matches,
+            &index,
+            PrecedencePolicy::Closest,
+        );
+        std::fs::write(
+            &path,
+            bincode::serde::encode_to_vec(&v2, bincode::config::standard()).unwrap(),
+        )
+        .unwrap();
+        let stored = read_cache(&path).unwrap();
+        assert_eq!(stored.version, 2);
+        assert!(stored.cache.files[0].generated);
+
+        // Layout 3: header, rules without annotations
+        let mut v3 = vec![CACHE_FORMAT.len() as u8];
+        v3.extend(CACHE_FORMAT.as_bytes());
+        v3.extend(bincode::serde::encode_to_vec(3u32, bincode::config::standard()).unwrap());
+        v3.extend(bincode::serde::encode_to_vec(&v2, bincode::config::standard()).unwrap());
+        std::fs::write(&path, v3).unwrap();
+        let stored = read_cache(&path).unwrap();
+        assert_eq!(stored.version, 3);
+        assert!(stored.cache.entries[0].metadata.is_empty());
+        let cache = migrate_cache(&ctx, stored);
+        assert_eq!(cache.entries[0].metadata["escalation"], "P1");
+        save_cache(&cache, &path, CacheEncoding::Bincode).unwrap();
+        assert_eq!(load_cache(&path).unwrap().entries[0].metadata.len(), 1);
+
+        // Layout 4: rules without sections; a file that has them must be resolved again
+        let entries_v4: Vec<_> = cache
+            .entries
+            .iter()
+            .map(|e| {
+                let fields = (&e.source_file, e.line_number, &e.pattern, &e.owners);
+                (fields, &e.tags, &e.spans, &e.metadata)
+            })
+            .collect();
+        let v4_body = (
+            [3u8; 32],
+            &entries_v4,
+            files_v9(&cache),
+            &rule_matches,
+            &index,
+            PrecedencePolicy::Closest,
+        );
+        let mut v4 = vec![CACHE_FORMAT.len() as u8];
+        v4.extend(CACHE_FORMAT.as_bytes());
+        v4.extend(bincode::serde::encode_to_vec(4u32, bincode::config::standard()).unwrap());
+        v4.extend(bincode::serde::encode_to_vec(v4_body, bincode::config::standard()).unwrap());
+        std::fs::write(&path, v4).unwrap();
+        let stored = read_cache(&path).unwrap();
+        assert_eq!(stored.version, 4);
+        assert_eq!(migrate_cache(&ctx, stored).hash, [3; 32]);
+        std::fs::write(
+            dir.path().join("CODEOWNERS"),
+            "[Rust] @rust\n*.rs #rust # escalation:P1\n",
+        )
+        .unwrap();
+        let stored = read_cache(&path).unwrap();
+        assert_eq!(migrate_cache(&ctx, stored).hash, [0; 32]);
+
+        // Layouts 5 to 9 have the current rules and files without their inline
+        // declaration; the build info came with 8 and its repository state with 9
+        let mut cache = cache;
+        cache.hash = [3; 32];
+        let save_as = |cache: &CodeownersCache, version: u32| {
+            let config = bincode::config::standard();
+            let mut bytes = vec![CACHE_FORMAT.len() as u8];
+            bytes.extend(CACHE_FORMAT.as_bytes());
+            bytes.extend(bincode::serde::encode_to_vec(version, config).unwrap());
+            if version >= 9 {
+                bytes.extend(bincode::serde::encode_to_vec(&cache.build, config).unwrap());
+            }
+            let body = (
+                cache.hash,
+                &cache.entries,
+                files_v9(cache),
+                &cache.rule_matches,
+                &cache.path_index,
+                cache.precedence,
+            );
+            bytes.extend(bincode::serde::encode_to_vec(body, config).unwrap());
+            std::fs::write(&path, bytes).unwrap();
+            let stored = read_cache(&path).unwrap();
+            assert_eq!(stored.version, version);
+            migrate_cache(&ctx, stored)
+        };
+        assert_eq!(save_as(&cache, 5).hash, [3; 32]);
+        assert_eq!(save_as(&cache, 6).hash, [3; 32]);
+        assert_eq!(save_as(&cache, 7).hash, [3; 32]);
+        assert_eq!(save_as(&cache, 9).hash, [3; 32]);
+
+        // A cache an older codeinput built with the same settings is used once migrated,
+        // not rebuilt
+        let config = &ctx.config;
+        cache.build = BuildInfo {
+            tool_version: "0.0.1".to_string(),
+            ..BuildInfo::new("trie", config.match_options(), &config.generated_markers)
+        };
+        let precedence = std::mem::replace(&mut cache.precedence, config.precedence);
+        let migrated = save_as(&cache, 9);
+        let (build, rules) = (&migrated.build, &migrated.entries);
+        assert!(
+            !needs_rebuild(&ctx, dir.path(), &path, build, migrated.precedence, rules).unwrap()
+        );
+        cache.precedence = precedence;
+
+        // Layout 9 recorded no inline declarations; front matter owners were not read
+        let marked = dir.path().join("marked.py");
+        std::fs::write(&marked, "# !!!CODEOWNERS @python\n").unwrap();
+        let mut declared = cache.files[1].clone();
+        declared.path = marked;
+        cache.files.push(declared);
+        let migrated = save_as(&cache, 9);
+        assert_eq!(migrated.hash, [3; 32]);
+        assert_eq!(
+            migrated.files[2].inline,
+            Some(InlineDeclaration {
+                source: InlineSource::Marker,
+                line: 1,
+            })
+        );
+        assert_eq!(migrated.files[0].inline, None);
+        let guide = dir.path().join("guide.md");
+        std::fs::write(&guide, "---\nowners: [@docs]\n---\n").unwrap();
+        cache.files[2].path = guide;
+        assert_eq!(save_as(&cache, 9).hash, [0; 32]);
+        cache.files.pop();
+
+        // Negations never matched and quotes were kept in patterns; those need resolving
+        cache.entries[0].pattern = "!generated/".to_string();
+        assert_eq!(save_as(&cache, 5).hash, [0; 32]);
+        cache.entries[0].pattern = "\"My Docs/\"".to_string();
+        assert_eq!(save_as(&cache, 6).hash, [0; 32]);
+
+        // Unversioned JSON without the generated flag is layout 1
+        let json = serde_json::json!({
+            "hash": vec![3; 32],
+            "entries": entries,
+            "files": paths.iter().map(|path| serde_json::json!({
+                "path": path,
+                "owners": [owner("@rust")],
+                "tags": [],
+            })).collect::<Vec<_>>(),
+            "rule_matches": rule_matches,
+        });
+        std::fs::write(&path, json.to_string()).unwrap();
+        let stored = read_cache(&path).unwrap();
+        assert_eq!((stored.version, stored.encoding), (1, CacheEncoding::Json));
+        assert!(migrate_cache(&ctx, stored).files[0].generated);
+
+        // A layout from the future is refused
+        let json = serde_json::json!({
+            "format": CACHE_FORMAT,
+            "version": CACHE_VERSION + 1,
+            "hash": vec![3; 32],
+            "entries": [],
+            "files": [],
+        });
+        std::fs::write(&path, json.to_string()).unwrap();
+        assert!(read_cache(&path).is_err());
+    }
 }
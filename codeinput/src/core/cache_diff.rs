@@ -0,0 +1,168 @@
+//! Ownership changes between two caches, reported by `codeowners cache diff`
+//!
+//! Comparing caches built elsewhere, such as the artifacts of a main branch build and a
+//! pull request build, reviews ownership changes without resolving anything locally.
+//! Files are matched by path, so both caches must be built from the same directory
+//! spelling, e.g. `ci codeowners parse` run in each checkout's root.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use super::equivalence::{same_owners, same_tags};
+use super::types::{CodeownersCache, FileEntry, Owner, Tag};
+
+/// How a file differs between the two caches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Only in the second cache
+    Added,
+    /// Only in the first cache
+    Removed,
+    /// In both, with different owners or tags
+    Changed,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// A file whose ownership differs between the caches; the side it is missing from is empty
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileChange {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    pub a_owners: Vec<Owner>,
+    pub a_tags: Vec<Tag>,
+    pub b_owners: Vec<Owner>,
+    pub b_tags: Vec<Tag>,
+}
+
+/// Files whose owners or tags differ from cache `a` to cache `b`, sorted by path
+///
+/// Owners and tags are compared regardless of order.
+pub fn diff(a: &CodeownersCache, b: &CodeownersCache) -> Vec<FileChange> {
+    let a_files = by_path(a);
+    let mut b_files = by_path(b);
+
+    let mut changes = Vec::new();
+    for (path, a_file) in a_files {
+        match b_files.remove(&path) {
+            Some(b_file) => {
+                if !same_owners(&a_file.owners, &b_file.owners)
+                    || !same_tags(&a_file.tags, &b_file.tags)
+                {
+                    changes.push(FileChange {
+                        path,
+                        kind: ChangeKind::Changed,
+                        a_owners: a_file.owners.clone(),
+                        a_tags: a_file.tags.clone(),
+                        b_owners: b_file.owners.clone(),
+                        b_tags: b_file.tags.clone(),
+                    });
+                }
+            }
+            None => changes.push(FileChange {
+                path,
+                kind: ChangeKind::Removed,
+                a_owners: a_file.owners.clone(),
+                a_tags: a_file.tags.clone(),
+                b_owners: Vec::new(),
+                b_tags: Vec::new(),
+            }),
+        }
+    }
+    changes.extend(b_files.into_iter().map(|(path, b_file)| FileChange {
+        path,
+        kind: ChangeKind::Added,
+        a_owners: Vec::new(),
+        a_tags: Vec::new(),
+        b_owners: b_file.owners.clone(),
+        b_tags: b_file.tags.clone(),
+    }));
+    changes.sort_by(|x, y| x.path.cmp(&y.path));
+    changes
+}
+
+fn by_path(cache: &CodeownersCache) -> BTreeMap<PathBuf, &FileEntry> {
+    cache
+        .files
+        .iter()
+        .map(|file| (normalize(&file.path), file))
+        .collect()
+}
+
+/// `./src/lib.rs` and `src/lib.rs` are the same file
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::OwnerType;
+
+    fn file(path: &str, owners: &[&str], tags: &[&str]) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners
+                .iter()
+                .map(|identifier| Owner {
+                    identifier: identifier.to_string(),
+                    owner_type: OwnerType::User,
+                })
+                .collect(),
+            tags: tags.iter().map(|tag| Tag(tag.to_string())).collect(),
+            generated: false,
+            inline: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let a = CodeownersCache::new(
+            [0; 32],
+            Vec::new(),
+            vec![
+                file("./src/lib.rs", &["@a", "@b"], &["rust"]),
+                file("./src/old.rs", &["@a"], &[]),
+                file("./README.md", &["@docs"], &[]),
+            ],
+            Vec::new(),
+        );
+        let b = CodeownersCache::new(
+            [0; 32],
+            Vec::new(),
+            vec![
+                file("src/lib.rs", &["@b", "@a"], &["rust"]),
+                file("src/new.rs", &["@a"], &[]),
+                file("README.md", &["@docs"], &["docs"]),
+            ],
+            Vec::new(),
+        );
+
+        let changes: Vec<_> = diff(&a, &b)
+            .into_iter()
+            .map(|change| (change.path, change.kind))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (PathBuf::from("README.md"), ChangeKind::Changed),
+                (PathBuf::from("src/new.rs"), ChangeKind::Added),
+                (PathBuf::from("src/old.rs"), ChangeKind::Removed),
+            ]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Rules from a central ownership repository, merged with the repository's own
+//!
+//! Platform teams can keep the baseline ownership of many repositories in one place. The
+//! `central_repo` setting names that repository, as a local path or a URL, and
+//! `central_ref` and `central_path` the CODEOWNERS file to read from it. Its patterns are
+//! relative to the repository root, and the `central_precedence` setting decides how its
+//! rules rank against the repository's own CODEOWNERS files:
+//!
+//! - `baseline`: a matching rule of the repository's own files wins, so central rules
+//!   only own what those leave unowned
+//! - `override`: a matching central rule wins over the repository's own
+//!
+//! The rules are recorded as if read from a CODEOWNERS file in a directory at the root
+//! named for the precedence, such as `.codeowners-central/CODEOWNERS`, which is how the
+//! resolver ranks them. A remote repository is fetched into a mirror inside the git
+//! directory; it is fetched again by `codeowners parse`, and the rules fetched last are
+//! used while the remote cannot be reached.
+
+use std::path::{Path, PathBuf};
+
+use git2::Repository;
+use sha2::{Digest, Sha256};
+
+use super::bitbucket;
+use super::context::Context;
+use super::parser::parse_content;
+use super::types::{CodeownersEntry, Dialect};
+use crate::utils::error::{Error, Result};
+use crate::utils::warnings::{self, WarningKind};
+
+pub use super::types::{central_precedence, CentralPrecedence, BASELINE_DIR, OVERRIDE_DIR};
+
+/// Bare repository in the git directory that remote central repositories are fetched into
+const MIRROR_DIR: &str = "codeinput-central";
+
+/// Credential attempts before a fetch gives up, so a rejected key is not retried forever
+const MAX_CREDENTIAL_ATTEMPTS: usize = 3;
+
+/// Rank of the rules of `source_file` when several CODEOWNERS files match, lowest first:
+/// overriding central rules, then the repository's own, then baseline central rules
+pub(crate) fn rank(source_file: &Path) -> u8 {
+    match central_precedence(source_file) {
+        Some(CentralPrecedence::Override) => 0,
+        None => 1,
+        Some(CentralPrecedence::Baseline) => 2,
+    }
+}
+
+/// The central rules configured for `repo` with the file they are recorded as read from,
+/// or `None` when the `central_repo` setting is empty
+///
+/// A remote repository is fetched when `fetch` is set or it was never fetched. When
+/// fetching fails, the rules fetched last are used with a warning.
+pub fn central_codeowners(
+    ctx: &Context, repo: &Path, fetch: bool,
+) -> Result<Option<(PathBuf, Vec<CodeownersEntry>)>> {
+    let config = &ctx.config;
+    if config.central_repo.is_empty() {
+        return Ok(None);
+    }
+
+    // Local paths are relative to the repository, like the other settings naming files
+    let local = repo.join(&config.central_repo);
+    let (source, git_ref) = if local.is_dir() {
+        let source = Repository::open(&local).map_err(|e| {
+            Error::with_source(
+                &format!("Failed to open central repository {}", local.display()),
+                Box::new(e),
+            )
+        })?;
+        (source, config.central_ref.clone())
+    } else {
+        mirror(repo, &config.central_repo, &config.central_ref, fetch)?
+    };
+
+    let spec = format!("{}:{}", git_ref, config.central_path);
+    let blob = source
+        .revparse_single(&spec)
+        .and_then(|object| object.peel_to_blob())
+        .map_err(|e| {
+            Error::new(&format!(
+                "No {} at {} in central repository {}: {}",
+                config.central_path,
+                config.central_ref,
+                config.central_repo,
+                e.message()
+            ))
+        })?;
+
+    let source_file = repo
+        .join(config.central_precedence.dir())
+        .join("CODEOWNERS");
+    let mut entries = parse_content(&String::from_utf8_lossy(blob.content()), &source_file)?;
+    if config.dialect == Dialect::Bitbucket {
+        bitbucket::apply_owner_syntax(&mut entries);
+    }
+    Ok(Some((source_file, entries)))
+}
+
+/// Whether the central rules among `entries`, as recorded in a cache, are those configured
+/// for `repo` now
+///
+/// The repository hash does not cover rules read from elsewhere, so a cache is checked
+/// against the central repository, or the mirror of a remote one, without fetching. Tags
+/// are not compared, as the cache records them remapped.
+pub(crate) fn is_current(ctx: &Context, repo: &Path, entries: &[CodeownersEntry]) -> Result<bool> {
+    let recorded: Vec<&CodeownersEntry> = entries
+        .iter()
+        .filter(|entry| central_precedence(&entry.source_file).is_some())
+        .collect();
+    let current = match central_codeowners(ctx, repo, false)? {
+        Some((_, entries)) => entries,
+        None => Vec::new(),
+    };
+    Ok(recorded.len() == current.len()
+        && recorded.iter().zip(&current).all(|(recorded, current)| {
+            central_precedence(&recorded.source_file) == central_precedence(&current.source_file)
+                && recorded.line_number == current.line_number
+                && recorded.pattern == current.pattern
+                && recorded.owners == current.owners
+        }))
+}
+
+/// The mirror of the remote repository `url` inside the git directory of `repo`, with the
+/// ref `git_ref` was fetched into
+fn mirror(repo: &Path, url: &str, git_ref: &str, fetch: bool) -> Result<(Repository, String)> {
+    let local = Repository::discover(repo)
+        .map_err(|e| Error::with_source("Failed to open git repository", Box::new(e)))?;
+    let dir = local.path().join(MIRROR_DIR);
+    let mirror = Repository::open_bare(&dir).or_else(|_| Repository::init_bare(&dir))?;
+
+    // One ref per source, so changing the settings never reads another source's rules
+    let digest = Sha256::digest(format!("{}\0{}", url, git_ref));
+    let mirror_ref = format!(
+        "refs/central/{}",
+        digest[..8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    );
+    let fetched = mirror.find_reference(&mirror_ref).is_ok();
+    if fetch || !fetched {
+        match fetch_into(&mirror, url, &format!("+{}:{}", git_ref, mirror_ref)) {
+            Ok(()) => {}
+            Err(e) if fetched => warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "Failed to fetch central ownership from {}, using the rules fetched before: {}",
+                    url,
+                    e.message()
+                ),
+            ),
+            Err(e) => {
+                return Err(Error::with_source(
+                    &format!("Failed to fetch central ownership from {}", url),
+                    Box::new(e),
+                ))
+            }
+        }
+    }
+    Ok((mirror, mirror_ref))
+}
+
+/// Fetch `refspec` from `url` into `mirror`, authenticating with the SSH agent or the git
+/// credential helper
+fn fetch_into(
+    mirror: &Repository, url: &str, refspec: &str,
+) -> std::result::Result<(), git2::Error> {
+    let git_config = git2::Config::open_default()?;
+    let mut attempts = 0;
+    let mut callbacks = git2::RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        attempts += 1;
+        if attempts > MAX_CREDENTIAL_ATTEMPTS {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+        if allowed.contains(git2::CredentialType::SSH_KEY) {
+            git2::Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else {
+            git2::Cred::credential_helper(&git_config, url, username)
+        }
+    });
+    let mut options = git2::FetchOptions::new();
+    options.remote_callbacks(callbacks);
+    mirror
+        .remote_anonymous(url)?
+        .fetch(&[refspec], Some(&mut options), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        assert_eq!(
+            central_precedence(Path::new("repo/.codeowners-central/CODEOWNERS")),
+            Some(CentralPrecedence::Baseline)
+        );
+        assert_eq!(
+            central_precedence(Path::new("repo/.codeowners-central-override/CODEOWNERS")),
+            Some(CentralPrecedence::Override)
+        );
+        assert_eq!(central_precedence(Path::new("repo/CODEOWNERS")), None);
+        assert!(
+            rank(Path::new("repo/.codeowners-central-override/CODEOWNERS"))
+                < rank(Path::new("repo/src/CODEOWNERS"))
+        );
+        assert!(
+            rank(Path::new("repo/CODEOWNERS"))
+                < rank(Path::new("repo/.codeowners-central/CODEOWNERS"))
+        );
+    }
+}
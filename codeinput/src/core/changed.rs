@@ -0,0 +1,194 @@
+//! Owners and tags of a set of changed files, for `codeowners changed-files`
+//!
+//! A change needs a review from every owner of the files it touches. Files are resolved
+//! from the cache. Deleted files, and the old paths of renamed ones, are resolved as they
+//! were at the base of the change, so removing a file still needs its owners' approval
+//! even when the same change removes their rule. Without a base, files the cache does not
+//! hold are resolved against its rules.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::context::Context;
+use super::matcher::{Matcher, MatcherStrategy};
+use super::owner_resolver::dedup_owners;
+use super::ref_diff::resolve_at;
+use super::resolver::resolve_file_with_policy;
+use super::tag_resolver::dedup_tags;
+use super::types::{CodeownersCache, FileEntry, Owner, OwnerType, Tag};
+use crate::utils::error::Result;
+
+/// A changed file with its owners and tags
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChangedFile {
+    /// Relative to the repository root
+    pub path: PathBuf,
+    pub owners: Vec<Owner>,
+    pub tags: Vec<Tag>,
+    /// The file is gone from the working tree, deleted or renamed away
+    pub deleted: bool,
+}
+
+impl ChangedFile {
+    pub fn is_owned(&self) -> bool {
+        self.owners
+            .iter()
+            .any(|owner| owner.owner_type != OwnerType::Unowned)
+    }
+}
+
+/// Owners who must review a change, the tags it carries and the files nobody owns
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Reviewers {
+    /// Sorted by identifier, ignoring case, each once
+    pub owners: Vec<Owner>,
+    /// Sorted, each once
+    pub tags: Vec<Tag>,
+    pub unowned: Vec<PathBuf>,
+}
+
+/// Resolve the owners and tags of `changed`, paths relative to `repo`
+///
+/// Owners come from `cache`. Files gone from the working tree are resolved at the `base`
+/// revision, from its tree and CODEOWNERS files; without `base`, or if it does not have
+/// them either, files the cache does not hold are resolved against its rules with the
+/// match options of `ctx`.
+pub fn resolve_changed_files(
+    ctx: &Context, repo: &Path, changed: &[PathBuf], base: Option<&str>, cache: &CodeownersCache,
+) -> Result<Vec<ChangedFile>> {
+    let deleted: Vec<PathBuf> = changed
+        .iter()
+        .filter(|path| !repo.join(path).exists())
+        .cloned()
+        .collect();
+    let at_base: HashMap<PathBuf, FileEntry> = match base {
+        Some(base) if !deleted.is_empty() => resolve_at(
+            repo,
+            base,
+            &deleted,
+            ctx.config.match_options(),
+            cache.precedence,
+        )?
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect(),
+        _ => HashMap::new(),
+    };
+
+    let mut matcher = None;
+    changed
+        .iter()
+        .map(|path| {
+            let full_path = repo.join(path);
+            let (owners, tags) = match (at_base.get(path), cache.file(&full_path)) {
+                (Some(file), _) | (None, Some(file)) => (file.owners.clone(), file.tags.clone()),
+                (None, None) => {
+                    let matcher = matcher.get_or_insert_with(|| {
+                        Matcher::build_with_options(
+                            &cache.entries,
+                            MatcherStrategy::Override,
+                            ctx.config.match_options(),
+                        )
+                    });
+                    let (owners, tags, _) = resolve_file_with_policy(
+                        &full_path,
+                        &cache.entries,
+                        matcher,
+                        cache.precedence,
+                    )?;
+                    (owners, tags)
+                }
+            };
+            Ok(ChangedFile {
+                path: path.clone(),
+                owners,
+                tags,
+                deleted: deleted.contains(path),
+            })
+        })
+        .collect()
+}
+
+/// The union of the owners and tags of `files`
+pub fn reviewers(files: &[ChangedFile]) -> Reviewers {
+    let owners = dedup_owners(
+        files
+            .iter()
+            .flat_map(|file| &file.owners)
+            .filter(|owner| owner.owner_type != OwnerType::Unowned)
+            .cloned()
+            .collect(),
+    );
+    let owners: BTreeMap<String, Owner> = owners
+        .into_iter()
+        .map(|owner| (owner.identifier.to_lowercase(), owner))
+        .collect();
+
+    let mut tags = dedup_tags(files.iter().flat_map(|file| file.tags.clone()).collect());
+    tags.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Reviewers {
+        owners: owners.into_values().collect(),
+        tags,
+        unowned: files
+            .iter()
+            .filter(|file| !file.is_owned())
+            .map(|file| file.path.clone())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    #[test]
+    fn test_reviewers() {
+        let file = |path: &str, owners: Vec<Owner>, tags: &[&str]| ChangedFile {
+            path: PathBuf::from(path),
+            owners,
+            tags: tags.iter().map(|tag| Tag(tag.to_string())).collect(),
+            deleted: false,
+        };
+        let files = vec![
+            file(
+                "src/lib.rs",
+                vec![
+                    owner("@rust", OwnerType::User),
+                    owner("@Alice", OwnerType::User),
+                ],
+                &["rust"],
+            ),
+            file(
+                "src/api.rs",
+                vec![owner("@alice", OwnerType::User)],
+                &["api", "rust"],
+            ),
+            file("README.md", vec![owner("NOOWNER", OwnerType::Unowned)], &[]),
+            file("docs/guide.md", Vec::new(), &["docs"]),
+        ];
+
+        let reviewers = reviewers(&files);
+        let owners: Vec<_> = reviewers
+            .owners
+            .iter()
+            .map(|owner| owner.identifier.as_str())
+            .collect();
+        assert_eq!(owners, ["@Alice", "@rust"]);
+        let tags: Vec<_> = reviewers.tags.iter().map(|tag| tag.0.as_str()).collect();
+        assert_eq!(tags, ["api", "docs", "rust"]);
+        assert_eq!(
+            reviewers.unowned,
+            [PathBuf::from("README.md"), PathBuf::from("docs/guide.md")]
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Markdown review checklist for a change, printed by `codeowners checklist`
+//!
+//! Changed files are grouped under each of their owners, with one checkbox per file and
+//! the review notes of the tags the group's files carry. The layout is read from a TOML
+//! template at the repository root (the `checklist_template` setting):
+//!
+//! ```toml
+//! title = "## Review checklist for {base}"
+//! # {owner} and {files}, the number of changed files the owner reviews
+//! group = "### {owner} ({files})"
+//! item = "- [ ] `{path}`"
+//! # {tag} and {note}
+//! note = "- [ ] {note}"
+//!
+//! [notes]
+//! security = "Run the threat model"
+//! "#migrations" = "Check the migration can be rolled back"
+//! ```
+//!
+//! Every setting is optional; without notes only the files are listed.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::changed::{resolve_changed_files, ChangedFile};
+use super::context::Context;
+use super::tag_remap::tag_name;
+use super::types::{CodeownersCache, OwnerType, Tag};
+use crate::utils::error::{Error, Result};
+
+/// Group of the changed files no rule or inline marker owns
+pub const UNOWNED_GROUP: &str = "Unowned";
+
+/// Layout of a review checklist
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChecklistTemplate {
+    pub title: String,
+    pub group: String,
+    pub item: String,
+    pub note: String,
+    /// Review note for each tag name, with or without the leading `#`
+    pub notes: BTreeMap<String, String>,
+}
+
+impl Default for ChecklistTemplate {
+    fn default() -> Self {
+        ChecklistTemplate {
+            title: "## Review checklist for {base}".to_string(),
+            group: "### {owner} ({files})".to_string(),
+            item: "- [ ] `{path}`".to_string(),
+            note: "- [ ] {note}".to_string(),
+            notes: BTreeMap::new(),
+        }
+    }
+}
+
+/// Changed files one owner reviews
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChecklistGroup {
+    /// Owner identifier, or [`UNOWNED_GROUP`]
+    pub owner: String,
+    pub files: Vec<PathBuf>,
+    /// Review notes of the tags on `files`, in tag order
+    pub notes: Vec<ReviewNote>,
+}
+
+/// Review note a tag on the changed files calls for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReviewNote {
+    /// Tag name, without the leading `#`
+    pub tag: String,
+    pub note: String,
+}
+
+impl ChecklistTemplate {
+    /// Parse a checklist template; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<ChecklistTemplate> {
+        toml::from_str(contents).map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse checklist template {}: {}",
+                source.display(),
+                e
+            ))
+        })
+    }
+
+    /// Load a checklist template file
+    pub fn load(path: &Path) -> Result<ChecklistTemplate> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read checklist template {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        ChecklistTemplate::parse(&contents, path)
+    }
+
+    /// Load the checklist template configured for `repo`, or the default one if there is
+    /// none
+    ///
+    /// The `checklist_template` setting is resolved relative to the repository.
+    pub fn for_repo(ctx: &Context, repo: &Path) -> Result<ChecklistTemplate> {
+        let path = repo.join(&ctx.config.checklist_template);
+        if !path.exists() {
+            return Ok(ChecklistTemplate::default());
+        }
+        ChecklistTemplate::load(&path)
+    }
+
+    /// Review note for `tag`, if the template has one
+    pub fn note_for(&self, tag: &Tag) -> Option<&str> {
+        self.notes
+            .iter()
+            .find(|(name, _)| tag_name(name) == tag.0)
+            .map(|(_, note)| note.as_str())
+    }
+
+    /// Render `groups` as Markdown, for a change against `base`
+    pub fn render(&self, base: &str, groups: &[ChecklistGroup]) -> String {
+        let mut lines = vec![self.title.replace("{base}", base)];
+        for group in groups {
+            lines.push(String::new());
+            lines.push(
+                self.group
+                    .replace("{owner}", &group.owner)
+                    .replace("{files}", &group.files.len().to_string()),
+            );
+            lines.push(String::new());
+            for path in &group.files {
+                lines.push(self.item.replace("{path}", &path.display().to_string()));
+            }
+            for note in &group.notes {
+                lines.push(
+                    self.note
+                        .replace("{tag}", &note.tag)
+                        .replace("{note}", &note.note),
+                );
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// Group the `changed` files of `repo`, relative to its root, by owner
+///
+/// Owners are resolved as [`resolve_changed_files`] does, deleted files at `base`. Groups
+/// are sorted by owner with [`UNOWNED_GROUP`] last, and a file with several owners is
+/// listed in each of their groups.
+pub fn group_by_owner(
+    ctx: &Context, repo: &Path, changed: &[PathBuf], base: Option<&str>, cache: &CodeownersCache,
+    template: &ChecklistTemplate,
+) -> Result<Vec<ChecklistGroup>> {
+    let mut groups: BTreeMap<String, (Vec<PathBuf>, Vec<Tag>)> = BTreeMap::new();
+    let mut unowned = (Vec::new(), Vec::new());
+
+    for ChangedFile {
+        path, owners, tags, ..
+    } in resolve_changed_files(ctx, repo, changed, base, cache)?
+    {
+        let mut owners = owners
+            .into_iter()
+            .filter(|owner| owner.owner_type != OwnerType::Unowned)
+            .peekable();
+        if owners.peek().is_none() {
+            unowned.0.push(path);
+            unowned.1.extend(tags);
+            continue;
+        }
+        for owner in owners {
+            let group = groups.entry(owner.identifier).or_default();
+            group.0.push(path.clone());
+            group.1.extend(tags.iter().cloned());
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    if !unowned.0.is_empty() {
+        groups.push((UNOWNED_GROUP.to_string(), unowned));
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(owner, (mut files, tags))| {
+            files.sort();
+            files.dedup();
+            let mut notes: Vec<ReviewNote> = Vec::new();
+            for tag in tags {
+                if let Some(note) = template.note_for(&tag) {
+                    if !notes.iter().any(|existing| existing.tag == tag.0) {
+                        notes.push(ReviewNote {
+                            tag: tag.0,
+                            note: note.to_string(),
+                        });
+                    }
+                }
+            }
+            ChecklistGroup {
+                owner,
+                files,
+                notes,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{CodeownersEntry, FileEntry, Owner};
+
+    #[test]
+    fn test_group_by_owner() -> Result<()> {
+        let owner = |identifier: &str, owner_type| Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        };
+        let file = |path: &str, owners: Vec<Owner>, tags: &[&str]| FileEntry {
+            path: PathBuf::from(path),
+            owners,
+            tags: tags.iter().map(|tag| Tag(tag.to_string())).collect(),
+            generated: false,
+            inline: None,
+        };
+        let rule = CodeownersEntry {
+            source_file: PathBuf::from("repo/CODEOWNERS"),
+            line_number: 0,
+            pattern: "*.sql".to_string(),
+            owners: vec![owner("@org/db", OwnerType::Team)],
+            tags: vec![Tag("migrations".to_string())],
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let cache = CodeownersCache::new(
+            [0; 32],
+            vec![rule],
+            vec![
+                file(
+                    "repo/auth.rs",
+                    vec![
+                        owner("@org/security", OwnerType::Team),
+                        owner("@alice", OwnerType::User),
+                    ],
+                    &["security"],
+                ),
+                file(
+                    "repo/README.md",
+                    vec![owner("NO_OWNER", OwnerType::Unowned)],
+                    &[],
+                ),
+            ],
+            vec![Vec::new()],
+        );
+        let template = ChecklistTemplate::parse(
+            "[notes]\nsecurity = \"Run the threat model\"\n\"#migrations\" = \"Check rollback\"\n",
+            Path::new("checklist.toml"),
+        )?;
+
+        let changed = [
+            PathBuf::from("auth.rs"),
+            PathBuf::from("README.md"),
+            PathBuf::from("drop.sql"),
+        ];
+        let groups = group_by_owner(
+            &Context::default(),
+            Path::new("repo"),
+            &changed,
+            None,
+            &cache,
+            &template,
+        )?;
+        let owners: Vec<_> = groups.iter().map(|group| group.owner.as_str()).collect();
+        assert_eq!(owners, ["@alice", "@org/db", "@org/security", "Unowned"]);
+        assert_eq!(groups[1].files, [PathBuf::from("drop.sql")]);
+        assert_eq!(
+            groups[1].notes,
+            [ReviewNote {
+                tag: "migrations".to_string(),
+                note: "Check rollback".to_string()
+            }]
+        );
+        assert_eq!(groups[3].files, [PathBuf::from("README.md")]);
+
+        let rendered = template.render("main", &groups[2..3]);
+        assert_eq!(
+            rendered,
+            "## Review checklist for main\n\n### @org/security (1)\n\n- [ ] `auth.rs`\n\
+             - [ ] Run the threat model"
+        );
+        Ok(())
+    }
+}
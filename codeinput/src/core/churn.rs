@@ -0,0 +1,285 @@
+//! How often ownership changed, read from the history of the CODEOWNERS files
+//!
+//! Every commit on the first-parent history that edits a CODEOWNERS file is compared with
+//! its parent: a pattern that gains a rule, loses its rule or gets different owners is an
+//! ownership change of that area. Areas changed repeatedly within the window have
+//! unstable ownership, which usually means nobody really owns them. Tag-only edits are not
+//! ownership changes.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use git2::{Oid, Repository, Sort};
+use serde::Serialize;
+
+use super::equivalence::same_owners;
+use super::parser::parse_content;
+use super::types::Owner;
+use crate::utils::error::{Error, Result};
+use crate::utils::interrupt;
+
+/// How a commit changed the rule for a pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnershipChangeKind {
+    Added,
+    Removed,
+    OwnersChanged,
+}
+
+impl std::fmt::Display for OwnershipChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OwnershipChangeKind::Added => write!(f, "added"),
+            OwnershipChangeKind::Removed => write!(f, "removed"),
+            OwnershipChangeKind::OwnersChanged => write!(f, "owners changed"),
+        }
+    }
+}
+
+/// The rule for one pattern changed by one commit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnershipChange {
+    pub commit: String,
+    pub time: DateTime<Utc>,
+    /// CODEOWNERS file, relative to the repository root
+    pub source_file: PathBuf,
+    pub pattern: String,
+    pub kind: OwnershipChangeKind,
+    pub old_owners: Vec<String>,
+    pub new_owners: Vec<String>,
+}
+
+/// Ownership changes of one pattern of one CODEOWNERS file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AreaChurn {
+    pub source_file: PathBuf,
+    pub pattern: String,
+    pub changes: usize,
+    /// Changes that replaced the owners of an existing rule
+    pub owner_changes: usize,
+    pub last_changed: DateTime<Utc>,
+    /// Changed at least as often as the threshold given to [`summarize`]
+    pub unstable: bool,
+}
+
+/// Ownership changes of the CODEOWNERS files of `repo` committed since `since`, oldest
+/// first
+///
+/// Follows the first parents from HEAD, so a branch merged in counts once, as its merge
+/// commit. An unborn HEAD has no changes.
+pub fn ownership_changes(repo: &Path, since: DateTime<Utc>) -> Result<Vec<OwnershipChange>> {
+    let repository = Repository::open(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    if repository.head().is_err() {
+        return Ok(Vec::new());
+    }
+
+    let mut walk = repository.revwalk()?;
+    walk.push_head()?;
+    walk.simplify_first_parent()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+    let mut changes = Vec::new();
+    for oid in walk {
+        interrupt::check()?;
+        let commit = repository.find_commit(oid?)?;
+        let time = DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_default();
+        if time < since {
+            break;
+        }
+
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+
+        let mut commit_changes = Vec::new();
+        for delta in diff.deltas() {
+            let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+                continue;
+            };
+            if path.file_name().and_then(|name| name.to_str()) != Some("CODEOWNERS") {
+                continue;
+            }
+
+            let old = rules_at(&repository, delta.old_file().id(), path)?;
+            let new = rules_at(&repository, delta.new_file().id(), path)?;
+            for (pattern, kind, old_owners, new_owners) in compare_rules(&old, &new) {
+                commit_changes.push(OwnershipChange {
+                    commit: commit.id().to_string()[..7].to_string(),
+                    time,
+                    source_file: path.to_path_buf(),
+                    pattern,
+                    kind,
+                    old_owners,
+                    new_owners,
+                });
+            }
+        }
+        // The walk is newest first; keep each commit's changes in file order
+        changes.push(commit_changes);
+    }
+
+    Ok(changes.into_iter().rev().flatten().collect())
+}
+
+/// Owners of each pattern of the CODEOWNERS blob `id`, the last rule winning as in matching
+fn rules_at(
+    repository: &Repository, id: Oid, source: &Path,
+) -> Result<BTreeMap<String, Vec<Owner>>> {
+    if id.is_zero() {
+        return Ok(BTreeMap::new());
+    }
+    let blob = repository.find_blob(id)?;
+    let content = String::from_utf8_lossy(blob.content());
+    Ok(parse_content(&content, source)?
+        .into_iter()
+        .map(|entry| (entry.pattern, entry.owners))
+        .collect())
+}
+
+pub(crate) type RuleChange = (String, OwnershipChangeKind, Vec<String>, Vec<String>);
+
+pub(crate) fn compare_rules(
+    old: &BTreeMap<String, Vec<Owner>>, new: &BTreeMap<String, Vec<Owner>>,
+) -> Vec<RuleChange> {
+    let identifiers =
+        |owners: &[Owner]| -> Vec<String> { owners.iter().map(|o| o.identifier.clone()).collect() };
+
+    let mut changes = Vec::new();
+    for (pattern, old_owners) in old {
+        match new.get(pattern) {
+            None => changes.push((
+                pattern.clone(),
+                OwnershipChangeKind::Removed,
+                identifiers(old_owners),
+                Vec::new(),
+            )),
+            Some(new_owners) if !same_owners(old_owners, new_owners) => changes.push((
+                pattern.clone(),
+                OwnershipChangeKind::OwnersChanged,
+                identifiers(old_owners),
+                identifiers(new_owners),
+            )),
+            Some(_) => {}
+        }
+    }
+    for (pattern, new_owners) in new {
+        if !old.contains_key(pattern) {
+            changes.push((
+                pattern.clone(),
+                OwnershipChangeKind::Added,
+                Vec::new(),
+                identifiers(new_owners),
+            ));
+        }
+    }
+    changes
+}
+
+/// Count the `changes` of each area, most changed first
+///
+/// Areas changed `min_changes` times or more are flagged unstable.
+pub fn summarize(changes: &[OwnershipChange], min_changes: usize) -> Vec<AreaChurn> {
+    let mut areas: BTreeMap<(&Path, &str), AreaChurn> = BTreeMap::new();
+    for change in changes {
+        let area = areas
+            .entry((&change.source_file, &change.pattern))
+            .or_insert_with(|| AreaChurn {
+                source_file: change.source_file.clone(),
+                pattern: change.pattern.clone(),
+                changes: 0,
+                owner_changes: 0,
+                last_changed: change.time,
+                unstable: false,
+            });
+        area.changes += 1;
+        if change.kind == OwnershipChangeKind::OwnersChanged {
+            area.owner_changes += 1;
+        }
+        area.last_changed = area.last_changed.max(change.time);
+    }
+
+    let mut areas: Vec<AreaChurn> = areas
+        .into_values()
+        .map(|area| AreaChurn {
+            unstable: area.changes >= min_changes,
+            ..area
+        })
+        .collect();
+    areas.sort_by_key(|area| std::cmp::Reverse(area.changes));
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Signature, Time};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_ownership_changes() -> Result<()> {
+        let dir = TempDir::new()?;
+        let repository = Repository::init(dir.path())?;
+        let day = 86_400;
+        let history: &[(i64, &str)] = &[
+            (day, "*.rs @old\n"),
+            (2 * day, "*.rs @old\ndocs/ @docs\n"),
+            (3 * day, "*.rs @new #rust\ndocs/ @docs\n"),
+            (4 * day, "*.rs @new #core\ndocs/ @docs\n"),
+            (5 * day, "*.rs @old\n"),
+        ];
+        for (seconds, contents) in history {
+            std::fs::write(dir.path().join("CODEOWNERS"), contents)?;
+            let mut index = repository.index()?;
+            index.add_path(Path::new("CODEOWNERS"))?;
+            index.write()?;
+            let tree = repository.find_tree(index.write_tree()?)?;
+            let signature = Signature::new("Dev", "dev@example.com", &Time::new(*seconds, 0))?;
+            let parent = repository
+                .head()
+                .ok()
+                .map(|head| head.peel_to_commit())
+                .transpose()?;
+            let parents: Vec<_> = parent.iter().collect();
+            repository.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Edit",
+                &tree,
+                &parents,
+            )?;
+        }
+
+        let all = ownership_changes(dir.path(), DateTime::UNIX_EPOCH)?;
+        let kinds: Vec<_> = all
+            .iter()
+            .map(|change| (change.pattern.as_str(), change.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("*.rs", OwnershipChangeKind::Added),
+                ("docs/", OwnershipChangeKind::Added),
+                ("*.rs", OwnershipChangeKind::OwnersChanged),
+                ("*.rs", OwnershipChangeKind::OwnersChanged),
+                ("docs/", OwnershipChangeKind::Removed),
+            ]
+        );
+
+        let areas = summarize(&all, 3);
+        assert_eq!(areas[0].pattern, "*.rs");
+        assert_eq!((areas[0].changes, areas[0].owner_changes), (3, 2));
+        assert!(areas[0].unstable);
+        assert!(!areas[1].unstable);
+
+        let recent = ownership_changes(dir.path(), DateTime::from_timestamp(3 * day, 0).unwrap())?;
+        assert_eq!(recent.len(), 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    core::{
+        branches::{default_branch, owned_branches},
+        cache::sync_cache,
+        context::Context,
+        display::truncate_string,
+        team_map::TeamMap,
+        types::OutputFormat,
+    },
+    utils::{
+        error::{Error, Result},
+        output,
+    },
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct BranchDisplay {
+    #[tabled(rename = "Branch")]
+    name: String,
+    #[tabled(rename = "Last Commit")]
+    last_commit: String,
+    #[tabled(rename = "Days Idle")]
+    days_idle: i64,
+    #[tabled(rename = "Author")]
+    author: String,
+    #[tabled(rename = "Ahead")]
+    ahead: usize,
+    #[tabled(rename = "Owned Files")]
+    owned_files: String,
+}
+
+/// List the remote branches changing files `owner` is responsible for, compared with
+/// `base` or else the default branch
+///
+/// Only branches whose last commit is at least `older_than` days old are listed.
+pub fn run(
+    path: &Path, owner: &str, base: Option<&str>, older_than: u32, cache_file: Option<&Path>,
+    format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let base = match base {
+        Some(base) => base.to_string(),
+        None => default_branch(path)?,
+    };
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    // Without a team mapping, only owners named in the rules are responsible for files
+    let team_map = path.join(&ctx.config.team_map);
+    let teams = if team_map.exists() {
+        TeamMap::load(&team_map)?
+    } else {
+        TeamMap::default()
+    };
+    let branches = owned_branches(&ctx, path, &base, owner, older_than, &cache, &teams)?;
+
+    match format {
+        OutputFormat::Text => {
+            if branches.is_empty() {
+                println!(
+                    "No branches change files of {} since leaving {}",
+                    owner, base
+                );
+                return Ok(());
+            }
+
+            let now = output::now();
+            let table_data: Vec<BranchDisplay> = branches
+                .iter()
+                .map(|branch| BranchDisplay {
+                    name: truncate_string(&branch.name, 40),
+                    last_commit: branch.last_commit.format("%Y-%m-%d").to_string(),
+                    days_idle: (now - branch.last_commit).num_days(),
+                    author: truncate_string(&branch.author, 30),
+                    ahead: branch.ahead,
+                    owned_files: format!("{} of {}", branch.owned_files.len(), branch.changed),
+                })
+                .collect();
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+            println!(
+                "{} branches change files of {} since leaving {}",
+                branches.len(),
+                owner,
+                base
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "owner": owner,
+                "base": base,
+                "older_than_days": older_than,
+                "branches": branches,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
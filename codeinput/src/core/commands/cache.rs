@@ -0,0 +1,289 @@
+use crate::{
+    core::{
+        cache::{load_cache, migrate_cache, read_cache, save_cache, verify_cache, CacheStatus},
+        cache_diff::{diff as diff_caches, ChangeKind},
+        commands::equivalent::{owner_list, tag_list},
+        context::Context,
+        display::{truncate_path, truncate_string},
+        overlay::overlay_path,
+        types::{OutputFormat, CACHE_VERSION},
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct ChangeDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "Change")]
+    kind: ChangeKind,
+    #[tabled(rename = "Owners (A)")]
+    a_owners: String,
+    #[tabled(rename = "Owners (B)")]
+    b_owners: String,
+    #[tabled(rename = "Tags (A)")]
+    a_tags: String,
+    #[tabled(rename = "Tags (B)")]
+    b_tags: String,
+}
+
+/// List the files whose owners or tags differ between cache files `a` and `b`
+///
+/// Nothing is resolved; both caches are read as they are. With `exit_code`, any
+/// difference makes the command fail.
+pub fn diff(a: &Path, b: &Path, exit_code: bool, format: &OutputFormat) -> Result<()> {
+    let load = |path: &Path| {
+        load_cache(path).map_err(|e| Error::new(&format!("{}: {}", path.display(), e)))
+    };
+    let a_cache = load(a)?;
+    let b_cache = load(b)?;
+    let changes = diff_caches(&a_cache, &b_cache);
+
+    match format {
+        OutputFormat::Text => {
+            if changes.is_empty() {
+                println!("No ownership changes");
+            } else {
+                let table_data: Vec<ChangeDisplay> = changes
+                    .iter()
+                    .map(|change| ChangeDisplay {
+                        path: truncate_path(&change.path.display().to_string(), 50),
+                        kind: change.kind,
+                        a_owners: truncate_string(&owner_list(&change.a_owners), 30),
+                        b_owners: truncate_string(&owner_list(&change.b_owners), 30),
+                        a_tags: truncate_string(&tag_list(&change.a_tags), 20),
+                        b_tags: truncate_string(&tag_list(&change.b_tags), 20),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+
+                let count = |kind| changes.iter().filter(|c| c.kind == kind).count();
+                println!(
+                    "{} changed, {} added, {} removed",
+                    count(ChangeKind::Changed),
+                    count(ChangeKind::Added),
+                    count(ChangeKind::Removed)
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&changes, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if exit_code && !changes.is_empty() {
+        return Err(Error::new(&format!(
+            "Ownership differs for {} files",
+            changes.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Bring the cache of `repo` up to the current layout without resolving files again
+pub fn migrate(repo: &Path, cache_file: Option<&Path>) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache_file = cache_path(&ctx, repo, cache_file);
+
+    let stored = read_cache(&cache_file)?;
+    if stored.version == CACHE_VERSION {
+        println!(
+            "{} is already in cache format {}",
+            cache_file.display(),
+            CACHE_VERSION
+        );
+        return Ok(());
+    }
+
+    let (version, encoding) = (stored.version, stored.encoding);
+    let cache = migrate_cache(&ctx, stored);
+    save_cache(&cache, &cache_file, encoding)?;
+    println!(
+        "Migrated {} from cache format {} to {}",
+        cache_file.display(),
+        version,
+        CACHE_VERSION
+    );
+
+    Ok(())
+}
+
+/// The cache file of `repo`: `cache_file` under it, or else the configured one
+fn cache_path(ctx: &Context, repo: &Path, cache_file: Option<&Path>) -> PathBuf {
+    match cache_file {
+        Some(file) => repo.join(file),
+        None => repo.join(&ctx.config.cache_file),
+    }
+}
+
+/// Print what the cache of `repo` holds and how it was built, without checking it
+pub fn info(repo: &Path, cache_file: Option<&Path>, format: &OutputFormat) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache_file = cache_path(&ctx, repo, cache_file);
+    if !cache_file.exists() {
+        return Err(Error::new(&format!(
+            "No cache at {}; run `ci codeowners parse` to build it",
+            cache_file.display()
+        )));
+    }
+    let size = std::fs::metadata(&cache_file)?.len();
+    let stored = read_cache(&cache_file)?;
+    let cache = &stored.cache;
+    let hash: String = cache
+        .hash
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    match format {
+        OutputFormat::Text => {
+            println!("Cache: {}", cache_file.display());
+            println!("Encoding: {}", stored.encoding);
+            println!("Format: {}", stored.version);
+            println!("Size: {} bytes", size);
+            println!("Hash: {}", hash);
+            let build = &cache.build;
+            if build.tool_version.is_empty() {
+                println!("Built with: unrecorded");
+            } else {
+                println!(
+                    "Built with: codeinput {}, {} matcher, {} dialect{}",
+                    build.tool_version,
+                    build.matcher,
+                    build.dialect,
+                    if build.case_insensitive {
+                        ", case-insensitive"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            match &build.source {
+                Some(source) if !source.head.is_empty() => println!(
+                    "Built from: {}{}",
+                    source.head,
+                    match source.changed.len() {
+                        0 => String::new(),
+                        1 => " with 1 uncommitted change".to_string(),
+                        changes => format!(" with {} uncommitted changes", changes),
+                    }
+                ),
+                Some(_) => println!("Built from: no commit"),
+                None => println!("Built from: unrecorded"),
+            }
+            println!("Precedence: {}", cache.precedence);
+            println!("Rules: {}", cache.entries.len());
+            println!("Files: {}", cache.files.len());
+            println!("Owners: {}", cache.owners_map.len());
+            println!("Tags: {}", cache.tags_map.len());
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "path": cache_file,
+                "encoding": stored.encoding.to_string(),
+                "version": stored.version,
+                "size": size,
+                "hash": hash,
+                "build": cache.build,
+                "precedence": cache.precedence,
+                "rules": cache.entries.len(),
+                "files": cache.files.len(),
+                "owners": cache.owners_map.len(),
+                "tags": cache.tags_map.len(),
+            });
+            write_report(&report, format)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the cache of `repo` against the repository without changing it, failing unless
+/// commands would use it as it is
+pub fn verify(repo: &Path, cache_file: Option<&Path>, format: &OutputFormat) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache_file = cache_path(&ctx, repo, cache_file);
+    let status = verify_cache(&ctx, repo, &cache_file)?;
+
+    match format {
+        OutputFormat::Text => println!("{}: {}", cache_file.display(), status),
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let mut report = serde_json::to_value(&status)
+                .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+            report["path"] = serde_json::json!(cache_file);
+            write_report(&report, format)?;
+        }
+    }
+
+    match status.action() {
+        Some(action) => Err(Error::new(&format!(
+            "The cache is not current; the next command to load it will {} it",
+            action
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Delete the cache of `repo` unless it is current, or even then with `force`, and its
+/// overlay, see [`overlay_path`]
+pub fn clear(repo: &Path, cache_file: Option<&Path>, force: bool) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache_file = cache_path(&ctx, repo, cache_file);
+    let status = verify_cache(&ctx, repo, &cache_file)?;
+
+    match status {
+        CacheStatus::Missing => println!("No cache at {}", cache_file.display()),
+        CacheStatus::Current if !force => println!(
+            "{} is current; kept it (add --force to delete it anyway)",
+            cache_file.display()
+        ),
+        status => {
+            std::fs::remove_file(&cache_file).map_err(|e| {
+                Error::new(&format!("Failed to delete {}: {}", cache_file.display(), e))
+            })?;
+            println!("Deleted {}, which was {}", cache_file.display(), status);
+        }
+    }
+
+    // An overlay is resolved again from its base cache, so it is always cheap to delete
+    let overlay = overlay_path(&cache_file);
+    if overlay.exists() {
+        std::fs::remove_file(&overlay)
+            .map_err(|e| Error::new(&format!("Failed to delete {}: {}", overlay.display(), e)))?;
+        println!("Deleted {}", overlay.display());
+    }
+
+    Ok(())
+}
+
+fn write_report(report: &serde_json::Value, format: &OutputFormat) -> Result<()> {
+    if *format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(report).unwrap());
+    } else {
+        let encoded = bincode::serde::encode_to_vec(report, bincode::config::standard())
+            .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+        // Write raw binary bytes to stdout
+        io::stdout()
+            .write_all(&encoded)
+            .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use crate::{
+    core::{
+        common::{find_codeowners_files, find_files},
+        matcher::{calibrate, MatcherProfile, MatcherStrategy},
+        parser::parse_codeowners,
+        types::CodeownersEntry,
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct CalibrationDisplay {
+    #[tabled(rename = "Matcher")]
+    strategy: String,
+    #[tabled(rename = "Build (ns/rule)")]
+    build_ns: u64,
+    #[tabled(rename = "Match (ns/file)")]
+    match_ns: u64,
+    #[tabled(rename = "Estimated Total (ms)")]
+    estimate_ms: String,
+}
+
+/// Measure matcher costs on this machine and store them as the matcher profile
+pub fn run(path: &std::path::Path, sample: usize) -> Result<()> {
+    println!("Calibrating matchers at {}", path.display());
+
+    let entries: Vec<CodeownersEntry> = find_codeowners_files(path)?
+        .iter()
+        .filter_map(|file| parse_codeowners(file).ok())
+        .flatten()
+        .collect();
+
+    let files = find_files(path)?;
+    let sample_files: Vec<_> = files.iter().take(sample.max(1)).cloned().collect();
+
+    let profile = calibrate(&entries, &sample_files)?;
+
+    let row = |strategy: MatcherStrategy, build_ns: u64, match_ns: u64| CalibrationDisplay {
+        strategy: strategy.to_string(),
+        build_ns,
+        match_ns,
+        estimate_ms: format!(
+            "{:.2}",
+            profile.estimate(strategy, entries.len(), files.len()) as f64 / 1_000_000.0
+        ),
+    };
+    let MatcherProfile {
+        override_build_ns,
+        override_match_ns,
+        gitignore_build_ns,
+        gitignore_match_ns,
+        regex_set_build_ns,
+        regex_set_match_ns,
+        globset_build_ns,
+        globset_match_ns,
+    } = profile;
+    let table_data = vec![
+        row(
+            MatcherStrategy::Override,
+            override_build_ns,
+            // Per-entry overrides test every rule, so report the cost per file
+            override_match_ns.saturating_mul(entries.len() as u64),
+        ),
+        row(
+            MatcherStrategy::Gitignore,
+            gitignore_build_ns,
+            gitignore_match_ns,
+        ),
+        row(
+            MatcherStrategy::RegexSet,
+            regex_set_build_ns,
+            regex_set_match_ns,
+        ),
+        row(MatcherStrategy::GlobSet, globset_build_ns, globset_match_ns),
+    ];
+
+    let mut table = Table::new(table_data);
+    table.with(tabled::settings::Style::modern());
+    println!("{}", table);
+
+    let selected = MatcherStrategy::Auto.select(entries.len(), files.len(), &profile);
+    println!(
+        "{} rules, {} files ({} sampled): auto selects {}",
+        entries.len(),
+        files.len(),
+        sample_files.len(),
+        selected
+    );
+
+    let value = toml::Value::try_from(&profile)
+        .map_err(|e| Error::new(&format!("Failed to serialize matcher profile: {}", e)))?;
+    let config_path = AppConfig::persist("matcher_profile", value)?;
+    println!("Saved matcher profile to {}", config_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        changed::{resolve_changed_files, reviewers, ChangedFile},
+        commands::{
+            equivalent::{owner_list, tag_list},
+            list_files::read_paths,
+        },
+        common::{changed_files_since, merge_base},
+        context::Context,
+        display::{truncate_path, truncate_string},
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct ChangedFileDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "Owners")]
+    owners: String,
+    #[tabled(rename = "Tags")]
+    tags: String,
+}
+
+impl From<&ChangedFile> for ChangedFileDisplay {
+    fn from(file: &ChangedFile) -> Self {
+        ChangedFileDisplay {
+            path: if file.deleted {
+                format!(
+                    "{} (deleted)",
+                    truncate_path(&file.path.display().to_string(), 40)
+                )
+            } else {
+                truncate_path(&file.path.display().to_string(), 50)
+            },
+            owners: truncate_string(&owner_list(&file.owners), 40),
+            tags: truncate_string(&tag_list(&file.tags), 30),
+        }
+    }
+}
+
+/// Print the owners who must review the files changed since `base`, with the tags the
+/// change carries
+///
+/// Without `base`, the changed paths are read from stdin, one per line, relative to the
+/// repository. Every owner is listed once, however many files they own. With `base`,
+/// deleted files are resolved at its merge base with HEAD, as they were before the change.
+pub fn run(
+    path: &Path, base: Option<&str>, cache_file: Option<&Path>, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    // Read the paths before building the cache, so bad input fails fast
+    let changed = match base {
+        Some(base) => changed_files_since(path, base)?,
+        None => read_paths(Path::new("-"))?,
+    };
+    let at = base.map(|base| merge_base(path, base)).transpose()?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let files = resolve_changed_files(&ctx, path, &changed, at.as_deref(), &cache)?;
+    let reviewers = reviewers(&files);
+
+    match format {
+        OutputFormat::Text => {
+            if files.is_empty() {
+                match base {
+                    Some(base) => println!("No files changed since {}", base),
+                    None => println!("No files given"),
+                }
+                return Ok(());
+            }
+
+            let table_data: Vec<ChangedFileDisplay> =
+                files.iter().map(ChangedFileDisplay::from).collect();
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+
+            println!("Reviewers: {}", owner_list(&reviewers.owners));
+            println!("Tags: {}", tag_list(&reviewers.tags));
+            if !reviewers.unowned.is_empty() {
+                println!(
+                    "{} of {} changed files have no owner",
+                    reviewers.unowned.len(),
+                    files.len()
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "base": base,
+                "files": files,
+                "owners": reviewers.owners,
+                "tags": reviewers.tags,
+                "unowned": reviewers.unowned,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        checklist::{group_by_owner, ChecklistTemplate},
+        common::{changed_files_since, merge_base},
+        context::Context,
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Print a review checklist for the changes on the current branch since `base`
+///
+/// The Markdown layout and the notes added for tags come from the repository's
+/// checklist template, see [`ChecklistTemplate`].
+pub fn run(
+    path: &Path, base: &str, cache_file: Option<&Path>, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let changed = changed_files_since(path, base)?;
+    let at = merge_base(path, base)?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let template = ChecklistTemplate::for_repo(&ctx, path)?;
+    let groups = group_by_owner(&ctx, path, &changed, Some(&at), &cache, &template)?;
+
+    match format {
+        OutputFormat::Text => {
+            if changed.is_empty() {
+                println!("No files changed since {}", base);
+                return Ok(());
+            }
+            println!("{}", template.render(base, &groups));
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "base": base,
+                "groups": groups,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use crate::{
+    core::{
+        churn::{ownership_changes, summarize},
+        types::OutputFormat,
+    },
+    utils::{
+        error::{Error, Result},
+        output,
+    },
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct AreaDisplay {
+    #[tabled(rename = "CODEOWNERS")]
+    source_file: String,
+    #[tabled(rename = "Pattern")]
+    pattern: String,
+    #[tabled(rename = "Changes")]
+    changes: usize,
+    #[tabled(rename = "Owner Changes")]
+    owner_changes: usize,
+    #[tabled(rename = "Last Changed")]
+    last_changed: String,
+    #[tabled(rename = "Stability")]
+    stability: &'static str,
+}
+
+/// Report how often the ownership of each area of `repo` changed in the last `days` days
+///
+/// Areas changed `min_changes` times or more are flagged unstable.
+pub fn run(path: &Path, days: u32, min_changes: usize, format: &OutputFormat) -> Result<()> {
+    let since = output::now() - chrono::Duration::days(days.into());
+    let changes = ownership_changes(path, since)?;
+    let areas = summarize(&changes, min_changes);
+    let unstable = areas.iter().filter(|area| area.unstable).count();
+
+    match format {
+        OutputFormat::Text => {
+            if areas.is_empty() {
+                println!("No ownership changes in the last {} days", days);
+                return Ok(());
+            }
+
+            let table_data: Vec<AreaDisplay> = areas
+                .iter()
+                .map(|area| AreaDisplay {
+                    source_file: area.source_file.display().to_string(),
+                    pattern: area.pattern.clone(),
+                    changes: area.changes,
+                    owner_changes: area.owner_changes,
+                    last_changed: area.last_changed.format("%Y-%m-%d").to_string(),
+                    stability: if area.unstable { "UNSTABLE" } else { "stable" },
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+            println!(
+                "{} ownership changes to {} areas in the last {} days; {} unstable (changed {} or more times)",
+                changes.len(),
+                areas.len(),
+                days,
+                unstable,
+                min_changes
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "days": days,
+                "min_changes": min_changes,
+                "unstable": unstable,
+                "areas": areas,
+                "changes": changes,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
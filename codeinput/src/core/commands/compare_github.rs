@@ -0,0 +1,181 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        github::{github_entries, github_owners, GitHubClient, GitHubRepo},
+        matcher::{Matcher, MatcherStrategy},
+        types::{OutputFormat, OwnerType},
+    },
+    utils::error::{Error, Result},
+};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct RejectedDisplay {
+    #[tabled(rename = "Line")]
+    line: usize,
+    #[tabled(rename = "Kind")]
+    kind: String,
+    #[tabled(rename = "Message")]
+    message: String,
+}
+
+#[derive(Tabled)]
+struct MismatchDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "GitHub")]
+    github: String,
+    #[tabled(rename = "Local")]
+    local: String,
+}
+
+/// A sampled file GitHub and the local cache disagree on
+#[derive(Serialize)]
+struct Mismatch {
+    path: PathBuf,
+    github: Vec<String>,
+    local: Vec<String>,
+}
+
+/// Compare the owners GitHub resolves at `git_ref` with the local cache for a sample of files
+///
+/// `sample` files are picked evenly from the cache, or every file when it is 0. The local
+/// side is the working tree, so run this on a checkout of `git_ref`. With `offline`, GitHub's
+/// answers come from the responses cached by an earlier run.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    path: &Path, git_ref: &str, github_repo: Option<&GitHubRepo>, sample: usize,
+    cache_file: Option<&Path>, offline: bool, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let repo = match github_repo {
+        Some(repo) => repo.clone(),
+        None => GitHubRepo::discover(path)?,
+    };
+    let client = GitHubClient::new(&ctx, offline);
+    let (location, contents) = client.codeowners_file(&repo, git_ref)?.ok_or_else(|| {
+        Error::new(&format!(
+            "No CODEOWNERS file in {} at {} (looked in .github/, the root and docs/)",
+            repo, git_ref
+        ))
+    })?;
+    let rejected = client.codeowners_errors(&repo, git_ref)?;
+    let entries = github_entries(&contents, &location, &rejected)?;
+    let matcher = Matcher::build(&entries, MatcherStrategy::Override);
+
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let mut files: Vec<_> = cache.files.iter().collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    let step = if sample == 0 {
+        1
+    } else {
+        files.len().div_ceil(sample).max(1)
+    };
+    let sampled: Vec<_> = files.into_iter().step_by(step).collect();
+
+    let mut mismatches = Vec::new();
+    for file in &sampled {
+        let relative = file.path.strip_prefix(path).unwrap_or(&file.path);
+        let github = github_owners(&entries, &matcher, relative)?;
+        let mut local: Vec<String> = file
+            .owners
+            .iter()
+            .filter(|owner| owner.owner_type != OwnerType::Unowned)
+            .map(|owner| owner.identifier.to_lowercase())
+            .collect();
+        local.sort();
+        local.dedup();
+        if github != local {
+            mismatches.push(Mismatch {
+                path: relative.to_path_buf(),
+                github,
+                local,
+            });
+        }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            println!("GitHub uses {} of {} at {}", location, repo, git_ref);
+            if !rejected.is_empty() {
+                println!(
+                    "GitHub ignores {} line{}:",
+                    rejected.len(),
+                    if rejected.len() == 1 { "" } else { "s" }
+                );
+                let table_data: Vec<RejectedDisplay> = rejected
+                    .iter()
+                    .map(|error| RejectedDisplay {
+                        line: error.line,
+                        kind: error.kind.clone(),
+                        message: error.message.lines().next().unwrap_or("").to_string(),
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+            if !mismatches.is_empty() {
+                let owners = |owners: &[String]| {
+                    if owners.is_empty() {
+                        "(unowned)".to_string()
+                    } else {
+                        owners.join(", ")
+                    }
+                };
+                let table_data: Vec<MismatchDisplay> = mismatches
+                    .iter()
+                    .map(|mismatch| MismatchDisplay {
+                        path: mismatch.path.display().to_string(),
+                        github: owners(&mismatch.github),
+                        local: owners(&mismatch.local),
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+            println!(
+                "{} of {} sampled files resolve the same as on GitHub",
+                sampled.len() - mismatches.len(),
+                sampled.len()
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "repository": repo.to_string(),
+                "ref": git_ref,
+                "codeowners_file": location,
+                "rejected_lines": rejected,
+                "sampled": sampled.len(),
+                "mismatches": mismatches,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if !mismatches.is_empty() {
+        return Err(Error::new(&format!(
+            "{} of {} sampled files resolve differently on GitHub",
+            mismatches.len(),
+            sampled.len()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use crate::{
+    core::{
+        cache::load_cache,
+        completion::{candidates, completion_target},
+        context::Context,
+        discovery::repo_root,
+    },
+    utils::error::Result,
+};
+use std::path::Path;
+
+/// Print the completions of the word at `position` of `args`, one per line
+///
+/// Owners, tags and paths come from the cache at the root of the git repository holding
+/// the current directory, or of the current directory outside one, named by a
+/// `--cache-file` in `args` or the `cache_file` setting. Paths complete relative to the
+/// current directory, as commands read them. The cache is only read, never built, so
+/// completion stays fast and silent; without it only fixed choices complete.
+/// `positional` tells whether the word is a command argument, see [`completion_target`].
+pub fn run(args: &[String], position: usize, positional: bool) -> Result<()> {
+    let Some(target) = completion_target(args, position, positional) else {
+        return Ok(());
+    };
+
+    let ctx = Context::fetch()?;
+    let cache_file = args
+        .iter()
+        .take(position)
+        .skip_while(|arg| *arg != "--cache-file")
+        .nth(1)
+        .map_or(ctx.config.cache_file.as_str(), String::as_str);
+    let current = std::env::current_dir()?;
+    let root = repo_root(&current).unwrap_or_else(|| current.clone());
+    let dir = current.strip_prefix(&root).unwrap_or(Path::new(""));
+    let cache = load_cache(&root.join(cache_file)).ok();
+
+    for candidate in candidates(cache.as_ref(), &target, dir) {
+        println!("{}", candidate);
+    }
+    Ok(())
+}
@@ -1,4 +1,14 @@
-use crate::utils::{app_config::AppConfig, error::Result};
+use crate::core::{
+    central::CentralPrecedence,
+    matcher::MatcherStrategy,
+    types::{Dialect, PrecedencePolicy},
+    watch::WatchBackendKind,
+};
+use crate::utils::{
+    app_config::AppConfig,
+    error::{Error, Result},
+    telemetry,
+};
 use tabled::{Table, Tabled};
 
 #[derive(Tabled)]
@@ -26,6 +36,215 @@ pub fn run() -> Result<()> {
             key: "Cache File".to_string(),
             value: config.cache_file,
         },
+        ConfigDisplay {
+            key: "Base Cache".to_string(),
+            value: if config.base_cache.is_empty() {
+                "none".to_string()
+            } else {
+                config.base_cache
+            },
+        },
+        ConfigDisplay {
+            key: "Remote Cache".to_string(),
+            value: if config.remote_cache.is_empty() {
+                "none".to_string()
+            } else {
+                config.remote_cache
+            },
+        },
+        ConfigDisplay {
+            key: "Remote Cache Token".to_string(),
+            value: if config.remote_cache_token.is_empty() {
+                "(not set)".to_string()
+            } else {
+                "(set)".to_string()
+            },
+        },
+        ConfigDisplay {
+            key: "S3 Region".to_string(),
+            value: config.s3_region,
+        },
+        ConfigDisplay {
+            key: "S3 Endpoint".to_string(),
+            value: if config.s3_endpoint.is_empty() {
+                "AWS".to_string()
+            } else {
+                config.s3_endpoint
+            },
+        },
+        ConfigDisplay {
+            key: "S3 Credentials".to_string(),
+            value: if config.s3_access_key_id.is_empty() || config.s3_secret_access_key.is_empty()
+            {
+                "(not set)".to_string()
+            } else {
+                "(set)".to_string()
+            },
+        },
+        ConfigDisplay {
+            key: "Matcher".to_string(),
+            value: config.matcher.to_string(),
+        },
+        ConfigDisplay {
+            key: "Case-Insensitive Matching".to_string(),
+            value: config.case_insensitive.to_string(),
+        },
+        ConfigDisplay {
+            key: "Precedence".to_string(),
+            value: config.precedence.to_string(),
+        },
+        ConfigDisplay {
+            key: "Dialect".to_string(),
+            value: config.dialect.to_string(),
+        },
+        ConfigDisplay {
+            key: "Central Ownership".to_string(),
+            value: if config.central_repo.is_empty() {
+                "none".to_string()
+            } else {
+                format!(
+                    "{}:{} at {} ({})",
+                    config.central_repo,
+                    config.central_path,
+                    config.central_ref,
+                    config.central_precedence
+                )
+            },
+        },
+        ConfigDisplay {
+            key: "Team Map".to_string(),
+            value: config.team_map,
+        },
+        ConfigDisplay {
+            key: "Tag Remap".to_string(),
+            value: config.tag_remap,
+        },
+        ConfigDisplay {
+            key: "Owner Aliases".to_string(),
+            value: config.owner_aliases,
+        },
+        ConfigDisplay {
+            key: "Policy File".to_string(),
+            value: config.policy_file,
+        },
+        ConfigDisplay {
+            key: "Checklist Template".to_string(),
+            value: config.checklist_template,
+        },
+        ConfigDisplay {
+            key: "Generated Markers".to_string(),
+            value: if config.generated_markers.is_empty() {
+                "none".to_string()
+            } else {
+                config.generated_markers.join(", ")
+            },
+        },
+        ConfigDisplay {
+            key: "Bot Authors".to_string(),
+            value: if config.bot_authors.is_empty() {
+                "none".to_string()
+            } else {
+                config.bot_authors.join(", ")
+            },
+        },
+        ConfigDisplay {
+            key: "Inference Weights".to_string(),
+            value: format!(
+                "lines {}, commits {}, recency {}",
+                config.infer_weight_lines, config.infer_weight_commits, config.infer_weight_recency
+            ),
+        },
+        ConfigDisplay {
+            key: "Cache Memory Budget".to_string(),
+            value: format!("{} MiB", config.cache_memory_budget),
+        },
+        ConfigDisplay {
+            key: "Jobs".to_string(),
+            value: match config.jobs {
+                0 => "auto".to_string(),
+                jobs => jobs.to_string(),
+            },
+        },
+        ConfigDisplay {
+            key: "Low Priority".to_string(),
+            value: config.low_priority.to_string(),
+        },
+        ConfigDisplay {
+            key: "Global Gitignore".to_string(),
+            value: config.global_gitignore.to_string(),
+        },
+        ConfigDisplay {
+            key: "Git Exclude".to_string(),
+            value: config.git_exclude.to_string(),
+        },
+        ConfigDisplay {
+            key: "Telemetry".to_string(),
+            value: if !telemetry::AVAILABLE {
+                "disabled at build time".to_string()
+            } else {
+                config.telemetry.to_string()
+            },
+        },
+        ConfigDisplay {
+            key: "Repository Registry".to_string(),
+            value: config.registry.to_string(),
+        },
+        ConfigDisplay {
+            key: "Watch Backend".to_string(),
+            value: config.watch_backend.to_string(),
+        },
+        ConfigDisplay {
+            key: "Watch Debounce".to_string(),
+            value: format!(
+                "{} ms quiet, {} ms max, rescan above {} paths",
+                config.watch_debounce_ms, config.watch_max_wait_ms, config.watch_burst_limit
+            ),
+        },
+        ConfigDisplay {
+            key: "Watch Poll Interval".to_string(),
+            value: format!("{} ms", config.watch_poll_interval_ms),
+        },
+        ConfigDisplay {
+            key: "Snapshot Directory".to_string(),
+            value: config.snapshot_dir,
+        },
+        ConfigDisplay {
+            key: "Snapshot Retention".to_string(),
+            value: match config.snapshot_keep_weekly_weeks {
+                0 => format!(
+                    "daily for {} days, then weekly",
+                    config.snapshot_keep_daily_days
+                ),
+                weeks => format!(
+                    "daily for {} days, then weekly for {} weeks",
+                    config.snapshot_keep_daily_days, weeks
+                ),
+            },
+        },
+        ConfigDisplay {
+            key: "GitHub API".to_string(),
+            value: config.github_api_url,
+        },
+        ConfigDisplay {
+            key: "GitHub Token".to_string(),
+            value: if config.github_token.is_empty() {
+                "(not set)".to_string()
+            } else {
+                "(set)".to_string()
+            },
+        },
+        ConfigDisplay {
+            key: "GitLab API".to_string(),
+            value: config.gitlab_api_url,
+        },
+        ConfigDisplay {
+            key: "GitLab Token".to_string(),
+            value: if config.gitlab_token.is_empty() {
+                "(not set)".to_string()
+            } else {
+                "(set)".to_string()
+            },
+        },
     ];
 
     let mut table = Table::new(table_data);
@@ -35,3 +254,132 @@ pub fn run() -> Result<()> {
 
     Ok(())
 }
+
+/// Persist a setting to the user config file
+pub fn set(key: &str, value: &str) -> Result<()> {
+    let parsed = match key {
+        "debug" | "low_priority" | "telemetry" | "registry" | "case_insensitive" | "global_gitignore"
+        | "git_exclude" => {
+            toml::Value::Boolean(parse_bool(key, value)?)
+        }
+        "jobs" | "cache_memory_budget" | "watch_debounce_ms" | "watch_max_wait_ms"
+        | "watch_burst_limit" | "watch_poll_interval_ms" | "snapshot_keep_daily_days"
+        | "snapshot_keep_weekly_weeks" => {
+            let number = value.parse::<u32>().map_err(|_| {
+                Error::new(&format!("Invalid value for {}: expected a number", key))
+            })?;
+            toml::Value::Integer(number.into())
+        }
+        "infer_weight_lines" | "infer_weight_commits" | "infer_weight_recency" => {
+            let weight = value
+                .parse::<f64>()
+                .ok()
+                .filter(|weight| weight.is_finite() && *weight >= 0.0)
+                .ok_or_else(|| {
+                    Error::new(&format!(
+                        "Invalid value for {}: expected a number that is not negative",
+                        key
+                    ))
+                })?;
+            toml::Value::Float(weight)
+        }
+        "log_level" => match value {
+            "debug" | "info" | "warn" | "error" => toml::Value::String(value.to_string()),
+            _ => {
+                return Err(Error::new(&format!(
+                    "Invalid log level: {}. Valid options: debug, info, warn, error",
+                    value
+                )))
+            }
+        },
+        "matcher" => toml::Value::String(value.parse::<MatcherStrategy>()?.to_string()),
+        "watch_backend" => toml::Value::String(value.parse::<WatchBackendKind>()?.to_string()),
+        "precedence" => toml::Value::String(
+            value
+                .parse::<PrecedencePolicy>()
+                .map_err(|e| Error::new(&e))?
+                .to_string(),
+        ),
+"central_precedence" => toml::Value::String(
+            value
+                .parse::<CentralPrecedence>()
+                .map_err(|e| Error::new(&e))?
+                .to_string(),
+        ),
+"dialect" => toml::Value::String(
+            value
+                .parse::<Dialect>()
+                .map_err(|e| Error::new(&e))?
+                .to_string(),
+        ),
+        "cache_file" | "base_cache" | "remote_cache" | "remote_cache_token" | "s3_region"
+        | "s3_endpoint" | "s3_access_key_id" | "s3_secret_access_key" | "s3_session_token"
+        | "central_repo" | "central_ref" | "central_path" | "team_map" | "tag_remap" | "owner_aliases" | "policy_file" | "checklist_template"
+        | "snapshot_dir" | "github_api_url" | "github_token" | "gitlab_api_url"
+        | "gitlab_token" => {
+            toml::Value::String(value.to_string())
+        }
+        // Comma-separated; an empty value turns detection or exclusion off
+        "generated_markers" | "bot_authors" => toml::Value::Array(
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|marker| !marker.is_empty())
+                .map(|marker| toml::Value::String(marker.to_string()))
+                .collect(),
+        ),
+        _ => {
+            return Err(Error::new(&format!(
+                "Unknown setting: {}. Valid settings: debug, log_level, cache_file, base_cache, remote_cache, \
+                 remote_cache_token, s3_region, s3_endpoint, s3_access_key_id, s3_secret_access_key, \
+                 s3_session_token, matcher, case_insensitive, precedence, dialect, central_repo, central_ref, central_path, central_precedence, team_map, tag_remap, owner_aliases, policy_file, checklist_template, generated_markers, bot_authors, infer_weight_lines, infer_weight_commits, infer_weight_recency, cache_memory_budget, jobs, low_priority, global_gitignore, git_exclude, telemetry, registry, watch_backend, \
+                 watch_debounce_ms, watch_max_wait_ms, watch_burst_limit, watch_poll_interval_ms, \
+                 snapshot_dir, snapshot_keep_daily_days, snapshot_keep_weekly_weeks, github_api_url, github_token, gitlab_api_url, gitlab_token",
+                key
+            )))
+        }
+    };
+
+    if key == "telemetry" && parsed.as_bool() == Some(true) && !telemetry::AVAILABLE {
+        return Err(Error::new(
+            "Telemetry was disabled when this binary was built and cannot be enabled",
+        ));
+    }
+
+    let config_path = AppConfig::persist(key, parsed.clone())?;
+    // Keep the token out of terminal scrollback and CI logs
+    if matches!(
+        key,
+        "github_token"
+            | "gitlab_token"
+            | "remote_cache_token"
+            | "s3_secret_access_key"
+            | "s3_session_token"
+    ) {
+        println!("Set {} in {}", key, config_path.display());
+    } else {
+        println!("Set {} = {} in {}", key, parsed, config_path.display());
+    }
+
+    if key == "telemetry" && parsed.as_bool() == Some(true) {
+        if let Some(spool) = telemetry::spool_path() {
+            println!(
+                "Anonymous usage events (command, repository size bucket, duration) are written to {}",
+                spool.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "true" | "on" | "yes" | "1" => Ok(true),
+        "false" | "off" | "no" | "0" => Ok(false),
+        _ => Err(Error::new(&format!(
+            "Invalid value for {}: expected true or false",
+            key
+        ))),
+    }
+}
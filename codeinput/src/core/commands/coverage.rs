@@ -0,0 +1,120 @@
+use crate::{
+    core::{
+        cache::sync_scoped_cache,
+        context::Context,
+        coverage::{coverage_by_directory, DirectoryCoverage},
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct CoverageDisplay {
+    #[tabled(rename = "Directory")]
+    directory: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Owned")]
+    owned: usize,
+    #[tabled(rename = "Unowned")]
+    unowned: usize,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+}
+
+impl From<&DirectoryCoverage> for CoverageDisplay {
+    fn from(stats: &DirectoryCoverage) -> Self {
+        CoverageDisplay {
+            directory: stats.directory.clone(),
+            files: stats.files,
+            owned: stats.owned,
+            unowned: stats.unowned(),
+            coverage: format!("{:.1}%", stats.coverage()),
+        }
+    }
+}
+
+/// Report the share of owned files, overall and per top-level directory, and fail below
+/// `min_coverage` percent
+///
+/// With `scope`, only the subtree at that path is reported, broken down by its own
+/// top-level directories.
+pub fn run(
+    path: &Path, cache_file: Option<&Path>, scope: Option<&Path>, min_coverage: Option<f64>,
+    format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache = sync_scoped_cache(&ctx, path, cache_file, scope)?;
+    let root = match scope {
+        Some(scope) => path.join(scope),
+        None => path.to_path_buf(),
+    };
+    let (total, directories) = coverage_by_directory(&root, &cache.files);
+
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<CoverageDisplay> =
+                directories.iter().map(CoverageDisplay::from).collect();
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+
+            println!("{}", table);
+            println!(
+                "Total: {} of {} files owned ({:.1}%)",
+                total.owned,
+                total.files,
+                total.coverage()
+            );
+            if total.generated > 0 {
+                println!(
+                    "{} generated files are left out of coverage",
+                    total.generated
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let stats = |stats: &DirectoryCoverage| {
+                serde_json::json!({
+                    "directory": stats.directory,
+                    "files": stats.files,
+                    "owned": stats.owned,
+                    "unowned": stats.unowned(),
+                    "generated": stats.generated,
+                    "coverage": stats.coverage(),
+                })
+            };
+            let mut report = serde_json::json!({
+                "total": stats(&total),
+                "directories": directories.iter().map(stats).collect::<Vec<_>>(),
+            });
+            if let Some(min) = min_coverage {
+                report["min_coverage"] = serde_json::json!(min);
+                report["passed"] = serde_json::json!(total.coverage() >= min);
+            }
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    match min_coverage {
+        Some(min) if total.coverage() < min => Err(Error::new(&format!(
+            "coverage {:.1}% is below the minimum of {}%",
+            total.coverage(),
+            min
+        ))),
+        _ => Ok(()),
+    }
+}
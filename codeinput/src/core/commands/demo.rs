@@ -0,0 +1,188 @@
+use crate::utils::{
+    error::{Error, Result},
+    output,
+};
+use git2::{Repository, Signature, Time};
+use std::path::Path;
+
+/// Root rules; `tools/` and `vendor/` are deliberately left unowned
+const ROOT_CODEOWNERS: &str = "\
+# Demo repository generated by `ci demo --generate`
+*.md @acme/docs #docs
+/Cargo.toml @acme/platform #build
+
+# Services
+services/api/ @acme/backend #api
+services/billing/** @acme/billing billing-oncall@acme.example #payments #critical
+";
+
+/// Nested rules, which take precedence over the root file for the web app
+const WEB_CODEOWNERS: &str = "\
+# The frontend team owns the web app
+* @acme/frontend #ui
+*.css @acme/design #styles
+";
+
+/// A commit in the generated history
+struct DemoCommit {
+    author: (&'static str, &'static str),
+    days_ago: i64,
+    message: &'static str,
+    files: &'static [(&'static str, &'static str)],
+}
+
+const ALICE: (&str, &str) = ("Alice Example", "alice@acme.example");
+const BOB: (&str, &str) = ("Bob Example", "bob@acme.example");
+const CAROL: (&str, &str) = ("Carol Example", "carol@acme.example");
+const DAVE: (&str, &str) = ("Dave Example", "dave@acme.example");
+const ERIN: (&str, &str) = ("Erin Example", "erin@acme.example");
+
+const HISTORY: &[DemoCommit] = &[
+    DemoCommit {
+        author: CAROL,
+        days_ago: 120,
+        message: "Add documentation",
+        files: &[
+            (".gitignore", ".codeowners.cache\n"),
+            ("README.md", "# Acme\n\nDemo monorepo for trying codeinput.\n"),
+            ("docs/getting-started.md", "# Getting Started\n\nRun `cargo build`.\n"),
+            ("docs/architecture.md", "# Architecture\n\nServices talk over HTTP.\n"),
+        ],
+    },
+    DemoCommit {
+        author: ALICE,
+        days_ago: 90,
+        message: "Add API service",
+        files: &[
+            ("Cargo.toml", "[workspace]\nmembers = [\"services/*\"]\n"),
+            ("services/api/src/main.rs", "fn main() {\n    println!(\"api\");\n}\n"),
+            ("services/api/src/routes.rs", "pub fn routes() -> Vec<&'static str> {\n    vec![\"/health\"]\n}\n"),
+            (
+                "services/api/src/auth.rs",
+                "// !!!CODEOWNERS @acme/security @alice #security #critical\npub fn verify(token: &str) -> bool {\n    !token.is_empty()\n}\n",
+            ),
+        ],
+    },
+    DemoCommit {
+        author: BOB,
+        days_ago: 60,
+        message: "Add web frontend",
+        files: &[
+            ("services/web/index.html", "<!-- !!!CODEOWNERS @acme/design #templates -->\n<div id=\"app\"></div>\n"),
+            ("services/web/src/app.js", "export const app = () => 'web';\n"),
+            (
+                "services/web/src/components/Button.js",
+                "// !!!CODEOWNERS @acme/design-system #components\nexport const Button = () => 'button';\n",
+            ),
+            ("services/web/styles/main.css", ".app {\n  color: #333;\n}\n"),
+        ],
+    },
+    DemoCommit {
+        author: DAVE,
+        days_ago: 45,
+        message: "Add billing service",
+        files: &[
+            ("services/billing/src/invoice.rs", "pub struct Invoice {\n    pub cents: u64,\n}\n"),
+            ("services/billing/src/tax.rs", "pub fn tax(cents: u64) -> u64 {\n    cents / 5\n}\n"),
+        ],
+    },
+    DemoCommit {
+        author: ALICE,
+        days_ago: 30,
+        message: "Add CODEOWNERS",
+        files: &[
+            ("CODEOWNERS", ROOT_CODEOWNERS),
+            ("services/web/CODEOWNERS", WEB_CODEOWNERS),
+        ],
+    },
+    DemoCommit {
+        author: ERIN,
+        days_ago: 10,
+        message: "Add release tooling and vendored parser",
+        files: &[
+            ("tools/release.sh", "#!/bin/sh\necho release\n"),
+            ("tools/lint.py", "print('lint')\n"),
+            ("vendor/legacy/parser.c", "int parse(void) { return 0; }\n"),
+        ],
+    },
+    DemoCommit {
+        author: BOB,
+        days_ago: 5,
+        message: "Add version route",
+        files: &[(
+            "services/api/src/routes.rs",
+            "pub fn routes() -> Vec<&'static str> {\n    vec![\"/health\", \"/version\"]\n}\n",
+        )],
+    },
+];
+
+/// Generate the demo repository and suggest commands to try on it
+pub fn run(path: &Path) -> Result<()> {
+    generate(path)?;
+
+    println!(
+        "Generated demo repository at {} with {} commits",
+        path.display(),
+        HISTORY.len()
+    );
+    println!("\nTry:");
+    println!("  cd {}", path.display());
+    println!("  ci codeowners list-files");
+    println!("  ci codeowners list-files --unowned");
+    println!("  ci codeowners ls services");
+    println!("  ci codeowners inspect services/api/src/auth.rs --explain");
+    println!("  ci codeowners list-owners");
+    println!("  ci codeowners infer-owners --min-commits 1");
+
+    Ok(())
+}
+
+/// Create a synthetic repository at `path` for demos, bug reports and tests
+///
+/// The repository has root and nested CODEOWNERS files, inline `!!!CODEOWNERS` markers,
+/// unowned directories and a git history from several authors. Its contents are always
+/// the same; commit dates are relative to now, or fixed with `--stable-output`.
+pub fn generate(path: &Path) -> Result<()> {
+    if path.exists() && path.read_dir()?.next().is_some() {
+        return Err(Error::new(&format!(
+            "{} already exists and is not empty",
+            path.display()
+        )));
+    }
+    std::fs::create_dir_all(path)?;
+
+    let repo = Repository::init(path)?;
+    let now = output::now().timestamp();
+
+    for commit in HISTORY {
+        let mut index = repo.index()?;
+        for (file, contents) in commit.files {
+            let file_path = path.join(file);
+            if let Some(parent) = file_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&file_path, contents)?;
+            index.add_path(Path::new(file))?;
+        }
+        index.write()?;
+
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let time = Time::new(now - commit.days_ago * 86400, 0);
+        let signature = Signature::new(commit.author.0, commit.author.1, &time)?;
+        let parent = match repo.head() {
+            Ok(head) => Some(head.peel_to_commit()?),
+            Err(_) => None,
+        };
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            commit.message,
+            &tree,
+            &parents,
+        )?;
+    }
+
+    Ok(())
+}
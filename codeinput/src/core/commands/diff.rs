@@ -0,0 +1,141 @@
+use crate::{
+    core::{
+        cache_diff::ChangeKind,
+        churn::OwnershipChangeKind,
+        commands::equivalent::{owner_list, tag_list},
+        context::Context,
+        display::{truncate_path, truncate_string},
+        ref_diff::diff_refs,
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct RuleDisplay {
+    #[tabled(rename = "CODEOWNERS")]
+    source_file: String,
+    #[tabled(rename = "Pattern")]
+    pattern: String,
+    #[tabled(rename = "Change")]
+    kind: OwnershipChangeKind,
+    #[tabled(rename = "Owners (from)")]
+    old_owners: String,
+    #[tabled(rename = "Owners (to)")]
+    new_owners: String,
+}
+
+#[derive(Tabled)]
+struct FileDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "Change")]
+    kind: ChangeKind,
+    #[tabled(rename = "Owners (from)")]
+    a_owners: String,
+    #[tabled(rename = "Owners (to)")]
+    b_owners: String,
+    #[tabled(rename = "Tags (from)")]
+    a_tags: String,
+    #[tabled(rename = "Tags (to)")]
+    b_tags: String,
+}
+
+/// List the CODEOWNERS rules and the files whose ownership differs between git refs
+/// `from` and `to` of the repository at `path`
+///
+/// Both refs are read from git, so neither has to be checked out. With `exit_code`, any
+/// difference makes the command fail.
+pub fn run(
+    path: &Path, from: &str, to: &str, exit_code: bool, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let changes = diff_refs(
+        path,
+        from,
+        to,
+        ctx.config.match_options(),
+        ctx.config.precedence,
+    )?;
+
+    match format {
+        OutputFormat::Text => {
+            if changes.rules.is_empty() && changes.files.is_empty() {
+                println!("No ownership changes from {} to {}", from, to);
+                return Ok(());
+            }
+            if !changes.rules.is_empty() {
+                let table_data: Vec<RuleDisplay> = changes
+                    .rules
+                    .iter()
+                    .map(|rule| RuleDisplay {
+                        source_file: rule.source_file.display().to_string(),
+                        pattern: rule.pattern.clone(),
+                        kind: rule.kind,
+                        old_owners: truncate_string(&rule.old_owners.join(", "), 30),
+                        new_owners: truncate_string(&rule.new_owners.join(", "), 30),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("Rules:\n{}", table);
+            }
+            if !changes.files.is_empty() {
+                let table_data: Vec<FileDisplay> = changes
+                    .files
+                    .iter()
+                    .map(|change| FileDisplay {
+                        path: truncate_path(&change.path.display().to_string(), 50),
+                        kind: change.kind,
+                        a_owners: truncate_string(&owner_list(&change.a_owners), 30),
+                        b_owners: truncate_string(&owner_list(&change.b_owners), 30),
+                        a_tags: truncate_string(&tag_list(&change.a_tags), 20),
+                        b_tags: truncate_string(&tag_list(&change.b_tags), 20),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("Files:\n{}", table);
+            }
+
+            let count = |kind| changes.files.iter().filter(|c| c.kind == kind).count();
+            println!(
+                "{} rules changed; {} files changed, {} added, {} removed",
+                changes.rules.len(),
+                count(ChangeKind::Changed),
+                count(ChangeKind::Added),
+                count(ChangeKind::Removed)
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&changes, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if exit_code && !(changes.rules.is_empty() && changes.files.is_empty()) {
+        return Err(Error::new(&format!(
+            "Ownership differs from {} to {}: {} rules, {} files",
+            from,
+            to,
+            changes.rules.len(),
+            changes.files.len()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+use std::path::Path;
+
+use git2::Repository;
+use tabled::{Table, Tabled};
+
+use crate::{
+    core::{
+        cache::read_cache,
+        common::{find_codeowners_files, get_cache_hash},
+        parser::parse_codeowners,
+        types::{try_codeowners_entry_to_matcher, MatchOptions, CACHE_VERSION},
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Ok => write!(f, "ok"),
+            Status::Warning => write!(f, "warning"),
+            Status::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct Check {
+    #[tabled(rename = "Check")]
+    name: String,
+    #[tabled(rename = "Status")]
+    status: Status,
+    #[tabled(rename = "Details")]
+    details: String,
+    #[tabled(rename = "Fix")]
+    fix: String,
+}
+
+impl Check {
+    fn ok(name: &str, details: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Ok,
+            details: details.into(),
+            fix: String::new(),
+        }
+    }
+
+    fn warning(name: &str, details: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Warning,
+            details: details.into(),
+            fix: fix.into(),
+        }
+    }
+
+    fn error(name: &str, details: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name: name.to_string(),
+            status: Status::Error,
+            details: details.into(),
+            fix: fix.into(),
+        }
+    }
+}
+
+/// Diagnose the environment, repository, configuration, CODEOWNERS files and cache
+///
+/// Fails when any check reports an error, so it can gate scripts.
+pub fn run(path: &Path, cache_file: Option<&Path>) -> Result<()> {
+    let mut checks = vec![check_git()];
+    let repo_ok = check_repository(path, &mut checks);
+    let config = check_config(&mut checks);
+    check_codeowners(path, &mut checks);
+    if repo_ok {
+        let defaults = AppConfig::default();
+        let config = config.as_ref().unwrap_or(&defaults);
+        let cache_file = path.join(cache_file.unwrap_or(Path::new(&config.cache_file)));
+        checks.push(check_cache(
+            path,
+            &cache_file,
+            config.match_options(),
+            &config.generated_markers,
+        ));
+    }
+
+    let errors = checks.iter().filter(|c| c.status == Status::Error).count();
+    let warnings = checks
+        .iter()
+        .filter(|c| c.status == Status::Warning)
+        .count();
+
+    let mut table = Table::new(&checks);
+    table.with(tabled::settings::Style::modern());
+    println!("{}", table);
+    println!("{} errors, {} warnings", errors, warnings);
+
+    if errors > 0 {
+        return Err(Error::new(&format!("doctor found {} errors", errors)));
+    }
+
+    Ok(())
+}
+
+fn check_git() -> Check {
+    let (major, minor, patch) = git2::Version::get().libgit2_version();
+    let libgit2 = format!("libgit2 {}.{}.{}", major, minor, patch);
+
+    match std::process::Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => Check::ok(
+            "git",
+            format!(
+                "{}; {}",
+                String::from_utf8_lossy(&output.stdout).trim(),
+                libgit2
+            ),
+        ),
+        _ => Check::warning(
+            "git",
+            format!("git not found on PATH (using bundled {})", libgit2),
+            "Install git so repository problems can be inspected with git itself",
+        ),
+    }
+}
+
+/// Returns whether the repository is usable for cache operations
+fn check_repository(path: &Path, checks: &mut Vec<Check>) -> bool {
+    let repo = match Repository::discover(path) {
+        Ok(repo) => repo,
+        Err(e) => {
+            checks.push(Check::error(
+                "Repository",
+                format!(
+                    "{} is not in a git repository: {}",
+                    path.display(),
+                    e.message()
+                ),
+                "Run inside a git checkout or pass its path, or run `git init`",
+            ));
+            return false;
+        }
+    };
+
+    if repo.is_bare() {
+        checks.push(Check::error(
+            "Repository",
+            format!("{} is a bare repository", repo.path().display()),
+            "Run against a checkout, e.g. one created with `git worktree add`",
+        ));
+        return false;
+    }
+
+    let mut usable = true;
+    let workdir = repo.workdir().unwrap_or(repo.path());
+    let same_dir = match (workdir.canonicalize(), path.canonicalize()) {
+        (Ok(workdir), Ok(path)) => workdir == path,
+        _ => false,
+    };
+    if same_dir {
+        let kind = if repo.is_worktree() {
+            "linked worktree"
+        } else {
+            "working tree"
+        };
+        checks.push(Check::ok(
+            "Repository",
+            format!("{} at {}", kind, workdir.display()),
+        ));
+    } else {
+        usable = false;
+        checks.push(Check::warning(
+            "Repository",
+            format!(
+                "{} is inside the repository at {}",
+                path.display(),
+                workdir.display()
+            ),
+            format!("Run from the repository root: {}", workdir.display()),
+        ));
+    }
+
+    if repo.is_shallow() {
+        checks.push(Check::warning(
+            "History",
+            "Shallow clone; infer-owners only sees the fetched commits",
+            "Run `git fetch --unshallow` for complete ownership inference",
+        ));
+    } else if repo.head().is_err() {
+        checks.push(Check::warning(
+            "History",
+            "No commits yet",
+            "Create an initial commit; infer-owners needs history",
+        ));
+    } else {
+        checks.push(Check::ok("History", "Full history available"));
+    }
+
+    usable
+}
+
+fn check_config(checks: &mut Vec<Check>) -> Option<AppConfig> {
+    if let Some(path) = AppConfig::user_config_path().filter(|path| path.exists()) {
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                contents
+                    .parse::<toml::Table>()
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            });
+        if let Err(e) = parsed {
+            checks.push(Check::error(
+                "Config file",
+                format!("{}: {}", path.display(), e),
+                format!(
+                    "Fix the syntax or delete {} to restore defaults",
+                    path.display()
+                ),
+            ));
+        }
+    }
+
+    match AppConfig::fetch() {
+        Ok(config) => {
+            let source = AppConfig::user_config_path()
+                .filter(|path| path.exists())
+                .map(|path| format!("defaults and {}", path.display()))
+                .unwrap_or_else(|| "defaults".to_string());
+            checks.push(Check::ok("Config", format!("Loaded from {}", source)));
+            Some(config)
+        }
+        Err(e) => {
+            let details = match std::error::Error::source(&e) {
+                Some(source) => format!("{}: {}", e, source),
+                None => e.to_string(),
+            };
+            checks.push(Check::error(
+                "Config",
+                details,
+                "Correct the value in the config file or CODEINPUT_* environment variables; `ci config set` validates values",
+            ));
+            None
+        }
+    }
+}
+
+fn check_codeowners(path: &Path, checks: &mut Vec<Check>) {
+    let files = match find_codeowners_files(path) {
+        Ok(files) => files,
+        Err(e) => {
+            checks.push(Check::error(
+                "CODEOWNERS",
+                format!("Failed to search {}: {}", path.display(), e),
+                "Check the directory permissions",
+            ));
+            return;
+        }
+    };
+
+    if files.is_empty() {
+        checks.push(Check::warning(
+            "CODEOWNERS",
+            format!("No CODEOWNERS files under {}", path.display()),
+            "Add a CODEOWNERS file, e.g. `* @your-team`",
+        ));
+        return;
+    }
+
+    let mut rules = 0;
+    let mut invalid = 0;
+    for file in &files {
+        let entries = match parse_codeowners(file) {
+            Ok(entries) => entries,
+            Err(e) => {
+                invalid += 1;
+                checks.push(Check::error(
+                    "CODEOWNERS",
+                    format!("{}: {}", file.display(), e),
+                    "Fix the file; every rule is ignored while it fails to parse",
+                ));
+                continue;
+            }
+        };
+
+        rules += entries.len();
+        for entry in &entries {
+            if let Err(e) = try_codeowners_entry_to_matcher(entry) {
+                invalid += 1;
+                checks.push(Check::error(
+                    "Pattern",
+                    format!("{}:{}: {}", file.display(), entry.line_number + 1, e),
+                    "Correct or remove the pattern on that line",
+                ));
+            }
+        }
+    }
+
+    if invalid == 0 {
+        checks.push(Check::ok(
+            "CODEOWNERS",
+            format!("{} files, {} rules, all patterns valid", files.len(), rules),
+        ));
+    }
+}
+
+fn check_cache(
+    path: &Path, cache_file: &Path, options: MatchOptions, generated_markers: &[String],
+) -> Check {
+    if !cache_file.exists() {
+        return Check::warning(
+            "Cache",
+            format!("No cache at {}", cache_file.display()),
+            "Run `ci codeowners parse` (other commands build it on first use)",
+        );
+    }
+
+    let cache = match read_cache(cache_file) {
+        Ok(stored) if stored.version < CACHE_VERSION => {
+            return Check::warning(
+                "Cache",
+                format!(
+                    "In cache format {}, written by an older version",
+                    stored.version
+                ),
+                "Run `ci codeowners cache migrate` (other commands migrate it automatically)",
+            )
+        }
+        Ok(stored) => stored.cache,
+        Err(e) => {
+            return Check::error(
+                "Cache",
+                format!("{} is unreadable: {}", cache_file.display(), e),
+                "Run `ci codeowners parse` to rebuild it",
+            )
+        }
+    };
+
+    let mismatches = cache.build.mismatches(options, generated_markers);
+    if !mismatches.is_empty() {
+        return Check::warning(
+            "Cache",
+            format!(
+                "Built with {}, unlike this run; it will be rebuilt",
+                mismatches.join(", ")
+            ),
+            "Run `ci codeowners parse`, or give each configuration its own --cache-file",
+        );
+    }
+
+    match get_cache_hash(path, options, generated_markers) {
+        Ok(hash) if hash == cache.hash => Check::ok(
+            "Cache",
+            format!(
+                "Up to date: {} files, {} rules",
+                cache.files.len(),
+                cache.entries.len()
+            ),
+        ),
+        Ok(_) => Check::warning(
+            "Cache",
+            "Stale; the repository or matching options changed since it was built",
+            "Run `ci codeowners parse` (other commands rebuild it automatically)",
+        ),
+        Err(e) => Check::warning(
+            "Cache",
+            format!("Could not compute the repository state: {}", e),
+            "Check that the repository index is readable",
+        ),
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    core::{
+        common::{find_codeowners_files, find_files},
+        display::{truncate_path, truncate_string},
+        equivalence::{compare, Difference},
+        parser::{parse_content, read_codeowners},
+        types::{CodeownersEntry, OutputFormat, Owner, Tag},
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct DifferenceDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "Owners (A)")]
+    a_owners: String,
+    #[tabled(rename = "Owners (B)")]
+    b_owners: String,
+    #[tabled(rename = "Tags (A)")]
+    a_tags: String,
+    #[tabled(rename = "Tags (B)")]
+    b_tags: String,
+}
+
+/// Check that two CODEOWNERS configurations resolve every file under `repo` identically
+///
+/// `a` and `b` are each a CODEOWNERS file or a directory of them laid out like the
+/// repository. Files differing in owners or tags are listed and make the command fail.
+pub fn run(repo: &Path, a: &Path, b: &Path, format: &OutputFormat) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let a_rules = load_rules(repo, a)?;
+    let b_rules = load_rules(repo, b)?;
+    let files = find_files(repo)?;
+
+    let differences = compare(
+        &a_rules,
+        &b_rules,
+        &files,
+        config.match_options(),
+        config.precedence,
+    )?;
+    let relative = |path: &Path| {
+        path.strip_prefix(repo)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    match format {
+        OutputFormat::Text => {
+            if differences.is_empty() {
+                println!(
+                    "Equivalent: all {} files resolve to the same owners and tags",
+                    files.len()
+                );
+            } else {
+                let table_data: Vec<DifferenceDisplay> = differences
+                    .iter()
+                    .map(|difference| DifferenceDisplay {
+                        path: truncate_path(&relative(&difference.path), 50),
+                        a_owners: truncate_string(&owner_list(&difference.a_owners), 30),
+                        b_owners: truncate_string(&owner_list(&difference.b_owners), 30),
+                        a_tags: truncate_string(&tag_list(&difference.a_tags), 20),
+                        b_tags: truncate_string(&tag_list(&difference.b_tags), 20),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+                println!(
+                    "Not equivalent: {} of {} files resolve differently",
+                    differences.len(),
+                    files.len()
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "equivalent": differences.is_empty(),
+                "files": files.len(),
+                "differences": differences.iter().map(|difference| {
+                    let Difference { path, a_owners, a_tags, b_owners, b_tags } = difference;
+                    serde_json::json!({
+                        "path": relative(path),
+                        "a": { "owners": a_owners, "tags": a_tags },
+                        "b": { "owners": b_owners, "tags": b_tags },
+                    })
+                }).collect::<Vec<_>>(),
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if !differences.is_empty() {
+        return Err(Error::new(&format!(
+            "CODEOWNERS configurations are not equivalent: {} files differ",
+            differences.len()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Rules of a configuration, placed in the repository as they would apply there
+///
+/// A directory's CODEOWNERS files keep their position relative to it. A single file
+/// inside the repository applies from its own directory; one outside applies from the
+/// root.
+fn load_rules(repo: &Path, path: &Path) -> Result<Vec<CodeownersEntry>> {
+    let sources: Vec<(PathBuf, PathBuf)> = if path.is_dir() {
+        let files = find_codeowners_files(path)?;
+        if files.is_empty() {
+            return Err(Error::new(&format!(
+                "No CODEOWNERS files found in {}",
+                path.display()
+            )));
+        }
+        files
+            .into_iter()
+            .map(|file| {
+                let placed = repo.join(file.strip_prefix(path).unwrap_or(&file));
+                (file, placed)
+            })
+            .collect()
+    } else if path.is_file() {
+        vec![(path.to_path_buf(), placed_in_repo(repo, path))]
+    } else {
+        return Err(Error::new(&format!("{} does not exist", path.display())));
+    };
+
+    let mut rules = Vec::new();
+    for (file, placed) in sources {
+        rules.extend(parse_content(&read_codeowners(&file)?, &placed)?);
+    }
+    Ok(rules)
+}
+
+/// Where a standalone CODEOWNERS file applies: its own directory within the repository,
+/// or the root
+fn placed_in_repo(repo: &Path, file: &Path) -> PathBuf {
+    let dir = file
+        .canonicalize()
+        .ok()
+        .and_then(|file| file.parent().map(Path::to_path_buf));
+    let relative = match (dir, repo.canonicalize()) {
+        (Some(dir), Ok(root)) => dir.strip_prefix(root).ok().map(Path::to_path_buf),
+        _ => None,
+    };
+    repo.join(relative.unwrap_or_default()).join("CODEOWNERS")
+}
+
+pub(crate) fn owner_list(owners: &[Owner]) -> String {
+    if owners.is_empty() {
+        return "None".to_string();
+    }
+    owners
+        .iter()
+        .map(|owner| owner.identifier.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn tag_list(tags: &[Tag]) -> String {
+    if tags.is_empty() {
+        return "None".to_string();
+    }
+    tags.iter()
+        .map(|tag| format!("#{}", tag.0))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
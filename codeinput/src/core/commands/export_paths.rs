@@ -0,0 +1,63 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        export::{owned_files, write_list, write_tar, ExportFormat},
+    },
+    utils::{
+        error::{Error, Result},
+        summary,
+    },
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Export the files owned by any of `owners` as a list, or a tarball with
+/// [`ExportFormat::Tar`], to `to` or else standard output
+///
+/// Without `format`, it follows the name of `to`: a tarball for `.tar`, else a list. A
+/// tarball is only written to a file.
+pub fn run(
+    path: &Path, owners: &[String], to: Option<&Path>, format: Option<ExportFormat>,
+    cache_file: Option<&Path>,
+) -> Result<()> {
+    let format = format.unwrap_or_else(|| to.map_or(ExportFormat::List, ExportFormat::for_path));
+    if format == ExportFormat::Tar && to.is_none() {
+        return Err(Error::new(
+            "A tarball is only written to a file: add --to <FILE>",
+        ));
+    }
+
+    let ctx = Context::fetch()?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let files = owned_files(path, &cache, owners);
+    summary::note("files", files.len() as u64);
+
+    let Some(to) = to else {
+        let stdout = io::stdout();
+        let mut out = stdout.lock();
+        write_list(&files, &mut out)?;
+        return Ok(());
+    };
+
+    let failed = |e: io::Error| Error::new(&format!("Failed to write {}: {}", to.display(), e));
+    let mut out = BufWriter::new(File::create(to).map_err(failed)?);
+    let exported = match format {
+        ExportFormat::List => {
+            write_list(&files, &mut out).map_err(failed)?;
+            files.len()
+        }
+        ExportFormat::Tar => write_tar(path, &files, &mut out)?,
+    };
+    out.flush().map_err(failed)?;
+
+    println!(
+        "Exported {} {} owned by {} to {}",
+        exported,
+        if exported == 1 { "file" } else { "files" },
+        owners.join(", "),
+        to.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::{
+    core::{
+        cache::sync_scoped_cache,
+        context::Context,
+        delegation::find_delegations,
+        gate::{evaluate, CheckOutcome, GateCheck},
+        policy::Policy,
+        types::OutputFormat,
+    },
+    utils::{
+        error::{Error, Result},
+        output,
+    },
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct OutcomeDisplay {
+    #[tabled(rename = "Check")]
+    check: String,
+    #[tabled(rename = "Result")]
+    result: &'static str,
+    #[tabled(rename = "Details")]
+    details: String,
+}
+
+/// Run several checks over one load of the cache and fail if any of them fails
+///
+/// With `scope`, the checks cover only the subtree at that path, see [`sync_scoped_cache`].
+pub fn run(
+    path: &Path, checks: &[GateCheck], cache_file: Option<&Path>, scope: Option<&Path>,
+    format: &OutputFormat,
+) -> Result<()> {
+    if checks.is_empty() {
+        return Err(Error::new(
+            "No checks given; pass e.g. --checks validate,coverage:90,unowned:0,policy",
+        ));
+    }
+
+    let ctx = Context::fetch()?;
+    let cache = sync_scoped_cache(&ctx, path, cache_file, scope)?;
+    let policy = if checks.contains(&GateCheck::Policy) {
+        Policy::for_repo(&ctx, path)?
+    } else {
+        None
+    };
+    let delegations = if checks.contains(&GateCheck::Validate) {
+        find_delegations(path)?
+    } else {
+        Vec::new()
+    };
+
+    let today = output::now().date_naive();
+    let outcomes: Vec<CheckOutcome> = checks
+        .iter()
+        .map(|check| evaluate(check, &cache, policy.as_ref(), &delegations, today))
+        .collect();
+    let failed = outcomes.iter().filter(|outcome| !outcome.passed).count();
+
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<OutcomeDisplay> = outcomes
+                .iter()
+                .map(|outcome| OutcomeDisplay {
+                    check: outcome.check.clone(),
+                    result: if outcome.passed { "pass" } else { "FAIL" },
+                    details: outcome.details.clone(),
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+            println!("{} passed, {} failed", outcomes.len() - failed, failed);
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "passed": failed == 0,
+                "checks": outcomes,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::new(&format!(
+            "gate failed: {} of {} checks failed",
+            failed,
+            outcomes.len()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        heatmap::{heatmap, tree, HeatmapFile, HeatmapFormat},
+    },
+    utils::{
+        error::{Error, Result},
+        output,
+    },
+};
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Export the size, primary owner and churn of the last `days` days of every file, as a
+/// nested JSON tree or CSV rows, to `output` or else standard output
+pub fn run(
+    path: &Path, days: u32, cache_file: Option<&Path>, format: &HeatmapFormat,
+    output: Option<&Path>,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let since = output::now() - chrono::Duration::days(days.into());
+    let files = heatmap(path, &cache, since)?;
+
+    let rendered = match format {
+        HeatmapFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&tree(&files)).unwrap();
+            json.push('\n');
+            json
+        }
+        HeatmapFormat::Csv => render_csv(&files),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, rendered)
+                .map_err(|e| Error::new(&format!("Failed to write {}: {}", path.display(), e)))?;
+            println!(
+                "Wrote {} heatmap of {} files to {}",
+                format,
+                files.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+fn render_csv(files: &[HeatmapFile]) -> String {
+    let mut out = String::from("path,size,owner,churn\n");
+    for file in files {
+        let _ = writeln!(
+            out,
+            "{},{},{},{}",
+            csv_field(&file.path.to_string_lossy()),
+            file.size,
+            csv_field(file.owner.as_deref().unwrap_or("")),
+            file.churn
+        );
+    }
+    out
+}
+
+/// `field` quoted when it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
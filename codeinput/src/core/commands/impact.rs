@@ -0,0 +1,131 @@
+use crate::{
+    core::{
+        cache::sync_cache, commands::infer_owners::suggest_owners, context::Context,
+        impact::analyze, types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tabled::{Table, Tabled};
+
+/// Replacement candidates suggested per orphaned file
+const MAX_SUGGESTIONS: usize = 3;
+
+#[derive(Tabled)]
+struct RuleDisplay {
+    #[tabled(rename = "Rule")]
+    location: String,
+    #[tabled(rename = "Pattern")]
+    pattern: String,
+    #[tabled(rename = "Left Without Owners")]
+    sole_owner: &'static str,
+}
+
+#[derive(Tabled)]
+struct OrphanDisplay {
+    #[tabled(rename = "File")]
+    path: String,
+    #[tabled(rename = "Suggested Owners")]
+    suggestions: String,
+}
+
+/// Report the files that would lose their only owner if `owner` were removed
+///
+/// Replacements are suggested from the blame history of each orphaned file unless
+/// `no_suggest` is set.
+pub fn run(
+    path: &Path, owner: &str, cache_file: Option<&Path>, no_suggest: bool, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let impact = analyze(&cache, owner);
+    let suggestions: HashMap<PathBuf, Vec<String>> = if no_suggest {
+        HashMap::new()
+    } else {
+        suggest_owners(path, &impact.orphaned, owner, MAX_SUGGESTIONS)
+    };
+
+    match format {
+        OutputFormat::Text => {
+            if impact.rules.is_empty() && impact.orphaned.is_empty() && impact.reduced.is_empty() {
+                println!("{} is not named in any CODEOWNERS rule", owner);
+                return Ok(());
+            }
+
+            if !impact.rules.is_empty() {
+                println!("Rules naming {}:", owner);
+                let table_data: Vec<RuleDisplay> = impact
+                    .rules
+                    .iter()
+                    .map(|rule| RuleDisplay {
+                        location: format!("{}:{}", rule.source_file.display(), rule.line),
+                        pattern: rule.pattern.clone(),
+                        sole_owner: if rule.sole_owner { "yes" } else { "no" },
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+
+            if impact.orphaned.is_empty() {
+                println!("No file would be left unowned");
+            } else {
+                println!(
+                    "Files left unowned without {} ({}):",
+                    owner,
+                    impact.orphaned.len()
+                );
+                let table_data: Vec<OrphanDisplay> = impact
+                    .orphaned
+                    .iter()
+                    .map(|file| OrphanDisplay {
+                        path: file.display().to_string(),
+                        suggestions: match suggestions.get(file) {
+                            Some(candidates) if !candidates.is_empty() => candidates.join(", "),
+                            _ => "-".to_string(),
+                        },
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+            println!(
+                "{} files would be left unowned, {} keep other owners",
+                impact.orphaned.len(),
+                impact.reduced.len()
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            // Sorted so the report is stable across runs
+            let suggestions: BTreeMap<String, Vec<String>> = suggestions
+                .into_iter()
+                .map(|(file, candidates)| (file.display().to_string(), candidates))
+                .collect();
+            let report = serde_json::json!({
+                "owner": impact.owner,
+                "rules": impact.rules,
+                "orphaned": impact.orphaned,
+                "reduced": impact.reduced,
+                "suggestions": suggestions,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use crate::{
+    core::{
+        commands::equivalent::owner_list,
+        common::{find_dialect_codeowners_files, find_files},
+        document::CodeownersDocument,
+        import::{check_import, normalize_prefix, rewrite_entries, ImportedFile},
+        parser::parse_codeowners_as,
+        types::CodeownersEntry,
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Merge the CODEOWNERS rules of the repository at `from` into the root CODEOWNERS file of
+/// `repo`, rewritten to apply below `prefix`
+///
+/// The merged file is printed, or written in place with `write`. Either way, the files of
+/// the other repository are resolved at their new location before and after the merge,
+/// and the files the rules in place give other owners are reported, as are the files
+/// that would not get their imported owners.
+pub fn run(repo: &Path, from: &Path, prefix: &str, write: bool) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let prefix = normalize_prefix(prefix)?;
+    if !from.is_dir() {
+        return Err(Error::new(&format!(
+            "{} is not a directory",
+            from.display()
+        )));
+    }
+
+    let mut imported: Vec<CodeownersEntry> = Vec::new();
+    for file in find_dialect_codeowners_files(from, config.dialect)? {
+        imported.extend(parse_codeowners_as(&file, config.dialect)?);
+    }
+    if imported.is_empty() {
+        return Err(Error::new(&format!(
+            "No CODEOWNERS rules found in {}",
+            from.display()
+        )));
+    }
+    let mut existing: Vec<CodeownersEntry> = Vec::new();
+    for file in find_dialect_codeowners_files(repo, config.dialect)? {
+        existing.extend(parse_codeowners_as(&file, config.dialect)?);
+    }
+
+    let target = repo.join("CODEOWNERS");
+    let mut document = CodeownersDocument::load_or_new(&target)?;
+    if document
+        .lines()
+        .last()
+        .is_some_and(|line| !line.text().is_empty())
+    {
+        document.push_line(String::new())?;
+    }
+    document.push_line(format!(
+        "# Imported from {} under {}/",
+        repo_name(from),
+        prefix
+    ))?;
+    let rewritten = rewrite_entries(&imported, from, &prefix, &target);
+    for entry in &rewritten {
+        document.push_entry(entry)?;
+    }
+
+    // Check the file as it will be written, alongside the other CODEOWNERS files
+    let mut merged: Vec<CodeownersEntry> = existing
+        .iter()
+        .filter(|entry| entry.source_file != target)
+        .cloned()
+        .collect();
+    merged.extend(
+        CodeownersDocument::parse(&document.to_string(), &target)?
+            .entries()
+            .cloned(),
+    );
+    let files: Vec<(PathBuf, PathBuf)> = find_files(from)?
+        .into_iter()
+        .filter_map(|file| {
+            let placed = repo.join(&prefix).join(file.strip_prefix(from).ok()?);
+            Some((file, placed))
+        })
+        .collect();
+    let checked = check_import(
+        &imported,
+        &existing,
+        &merged,
+        &files,
+        config.match_options(),
+        config.precedence,
+    )?;
+
+    let display = target.strip_prefix(repo).unwrap_or(&target).display();
+    let summary = format!(
+        "{}: imported {} rules from {} under {}/, checked against {} files",
+        display,
+        rewritten.len(),
+        repo_name(from),
+        prefix,
+        files.len()
+    );
+    let conflicts: Vec<&ImportedFile> = checked.iter().filter(|file| file.conflicts()).collect();
+    let shadowed: Vec<&ImportedFile> = checked.iter().filter(|file| file.shadowed()).collect();
+    let mut report = Vec::new();
+    if conflicts.is_empty() {
+        report.push("No owner conflicts".to_string());
+    } else {
+        report.push(format!(
+            "Owner conflicts ({} files the rules in place give other owners):",
+            conflicts.len()
+        ));
+        report.extend(grouped(repo, &conflicts, |file| {
+            format!(
+                "{} -> {}",
+                owner_list(&file.existing),
+                owner_list(&file.imported)
+            )
+        }));
+    }
+    if !shadowed.is_empty() {
+        report.push(format!(
+            "Not imported ({} files keep other owners after the merge, e.g. from a CODEOWNERS file closer to them):",
+            shadowed.len()
+        ));
+        report.extend(grouped(repo, &shadowed, |file| {
+            format!(
+                "{} instead of {}",
+                owner_list(&file.merged),
+                owner_list(&file.imported)
+            )
+        }));
+    }
+
+    if write {
+        document.save(&target)?;
+        println!("{}", summary);
+        for line in report {
+            println!("{}", line);
+        }
+    } else {
+        print!("{}", document);
+        eprintln!("{}", summary);
+        for line in report {
+            eprintln!("{}", line);
+        }
+    }
+
+    Ok(())
+}
+
+/// One line per distinct `describe` of `files`, with how many files it covers and an
+/// example
+fn grouped(
+    repo: &Path, files: &[&ImportedFile], describe: impl Fn(&ImportedFile) -> String,
+) -> Vec<String> {
+    let mut groups: BTreeMap<String, (usize, &Path)> = BTreeMap::new();
+    for file in files {
+        let group = groups.entry(describe(file)).or_insert((0, &file.path));
+        group.0 += 1;
+    }
+    groups
+        .into_iter()
+        .map(|(description, (count, example))| {
+            let example = example.strip_prefix(repo).unwrap_or(example);
+            let example = example.strip_prefix("./").unwrap_or(example);
+            format!(
+                "  {}: {} files, e.g. {}",
+                description,
+                count,
+                example.display()
+            )
+        })
+        .collect()
+}
+
+/// Name of the repository at `path` for the heading of the imported rules
+fn repo_name(path: &Path) -> String {
+    path.canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| path.display().to_string())
+}
@@ -1,16 +1,35 @@
 use crate::core::{
+    blame_cache::{blame_key, summarize, BlameAuthor, BlameCache},
     cache::load_cache,
+    commands::tag::{diff, Change},
     common::find_files,
+    context::Context,
+    display::truncate_path,
+    document::CodeownersDocument,
+    owner_alias::OwnerAliases,
+    parser::escape_pattern,
     resolver::find_owners_and_tags_for_file,
-    types::{codeowners_entry_to_matcher, CodeownersCache, Owner, OwnerType},
+    reviews::{ReviewHistory, ReviewSource, ReviewerStats},
+    team_map::TeamMap,
+    types::{
+        try_codeowners_entry_to_matcher_with, CodeownersCache, CodeownersEntryMatcher,
+        MatchOptions, Owner, OwnerType,
+    },
 };
-use crate::utils::error::{Error, Result};
-use git2::{Blame, BlameOptions, Repository, Time};
+use crate::utils::{
+    app_config::AppConfig,
+    error::{Error, Result},
+    interrupt, output,
+    warnings::{self, WarningKind},
+};
+use git2::{BlameOptions, Mailmap, Oid, Repository, Signature, Time};
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use tabled::{Table, Tabled};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -19,11 +38,70 @@ pub enum InferScope {
     Unowned,
 }
 
+/// Level of the rules written for inferred owners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InferAggregation {
+    /// A rule per file
+    File,
+    /// A rule per directory where enough of the files share an owner
+    Directory,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum InferAlgorithm {
     Commits,
     Lines,
     Recent,
+    /// Lines, commits and recency combined by the weights
+    Weighted(InferWeights),
+}
+
+/// Weights of the signals the weighted algorithm combines, each applied to a contributor's
+/// share of that signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InferWeights {
+    /// Share of the file's blamed lines
+    pub lines: f64,
+    /// Share of the commits touching the file
+    pub commits: f64,
+    /// Share of the recency of the last commit touching the file
+    pub recency: f64,
+}
+
+impl Default for InferWeights {
+    fn default() -> Self {
+        InferWeights {
+            lines: 0.5,
+            commits: 0.3,
+            recency: 0.2,
+        }
+    }
+}
+
+impl InferWeights {
+    /// Weights that are finite and not negative, at least one of them above zero
+    pub fn new(lines: f64, commits: f64, recency: f64) -> Result<Self> {
+        let weights = [lines, commits, recency];
+        if weights
+            .iter()
+            .any(|weight| !weight.is_finite() || *weight < 0.0)
+        {
+            return Err(Error::new(&format!(
+                "Invalid inference weights: lines {}, commits {}, recency {}; weights must not be negative",
+                lines, commits, recency
+            )));
+        }
+        if weights.iter().all(|weight| *weight == 0.0) {
+            return Err(Error::new(
+                "Invalid inference weights: at least one must be above zero",
+            ));
+        }
+        Ok(InferWeights {
+            lines,
+            commits,
+            recency,
+        })
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,16 +110,186 @@ pub struct FileOwnershipInference {
     pub inferred_owners: Vec<InferredOwner>,
     pub confidence: f64,
     pub existing_owners: Vec<Owner>,
+    /// Team suggested instead of the individual owners, with `--suggest-teams`
+    #[serde(default)]
+    pub suggested_team: Option<SuggestedTeam>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A team most of a file's inferred owners belong to, suggested in their place
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SuggestedTeam {
+    pub team: String,
+    /// Inferred owners of the file the team mapping lists in the team
+    pub members: Vec<String>,
+    /// Share of the file's inferred owners in the team, from 0 to 1
+    pub share: f64,
+    /// Commits and lines of those members together
+    pub commits: u32,
+    pub lines: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InferredOwner {
     pub email: String,
+    /// Owner the email is aliased to, which is suggested instead of the email
     pub username: Option<String>,
     pub score: f64,
     pub commits: u32,
     pub lines: u32,
     pub last_commit_days_ago: u32,
+    /// Merged pull requests reviewed, with `--reviews`
+    #[serde(default)]
+    pub reviews: u32,
+}
+
+impl InferredOwner {
+    /// Owner to suggest: the aliased owner, or else the email address
+    pub fn owner(&self) -> &str {
+        self.username.as_deref().unwrap_or(&self.email)
+    }
+}
+
+/// Accounts of automation excluded from inference unless the `bot_authors` setting says
+/// otherwise: GitHub Apps such as `dependabot[bot]`, Dependabot and Renovate under any
+/// address, and GitLab project and group access tokens
+pub const DEFAULT_BOT_AUTHORS: [&str; 4] = [
+    "*[bot]@*",
+    "*dependabot*",
+    "*renovate*",
+    "*_bot_*@noreply.*",
+];
+
+/// Authors and paths left out of inference, such as contractors, departed employees,
+/// bots and vendored code
+#[derive(Debug, Clone, Default)]
+pub struct InferExclusions {
+    /// Globs over author email addresses
+    authors: GlobSet,
+    /// Globs over the email addresses of automation accounts
+    bots: GlobSet,
+    /// Globs over paths relative to the repository
+    paths: GlobSet,
+}
+
+impl InferExclusions {
+    /// Exclusions of the authors whose email matches any of the `authors` globs, and of
+    /// the files matching any of the `paths` globs, where `*` also matches `/`
+    ///
+    /// Author globs ignore case, and `[` and `]` match themselves so that `*[bot]@*`
+    /// matches GitHub App accounts.
+    pub fn new(authors: &[String], paths: &[String]) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for path in paths {
+            let glob = Glob::new(path)
+                .map_err(|e| Error::new(&format!("Invalid --exclude-path glob {}: {}", path, e)))?;
+            builder.add(glob);
+        }
+        Ok(InferExclusions {
+            authors: author_globs(authors, "--exclude-author")?,
+            bots: GlobSet::empty(),
+            paths: builder
+                .build()
+                .map_err(|e| Error::new(&format!("Invalid --exclude-path globs: {}", e)))?,
+        })
+    }
+
+    /// Also exclude the automation accounts matching any of the `bots` globs, usually
+    /// the `bot_authors` setting
+    pub fn with_bots(mut self, bots: &[String]) -> Result<Self> {
+        self.bots = author_globs(bots, "bot_authors")?;
+        Ok(self)
+    }
+
+    fn excludes_author(&self, email: &str) -> bool {
+        self.authors.is_match(email) || self.bots.is_match(email)
+    }
+
+    /// Whether `file_path`, under `base_path`, matches an excluded glob
+    fn excludes_path(&self, file_path: &Path, base_path: &Path) -> bool {
+        let relative = file_path.strip_prefix(base_path).unwrap_or(file_path);
+        self.paths.is_match(relative)
+    }
+}
+
+/// Progress line on stderr while files are analyzed, shown only on a terminal and never
+/// in stable output
+struct Progress {
+    total: usize,
+    visible: bool,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Progress {
+            total,
+            visible: !output::is_stable() && std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// Show that the `current`th file, `file_path`, is being analyzed
+    fn report(&self, current: usize, file_path: &Path) {
+        if self.visible {
+            eprint!(
+                "\r\x1b[K🔍 Analyzing [{}/{}] {}",
+                current,
+                self.total,
+                truncate_path(&file_path.display().to_string(), 60)
+            );
+        }
+    }
+
+    /// Clear the progress line
+    fn finish(&self) {
+        if self.visible && self.total > 0 {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+/// Compile email globs that ignore case and take `[` and `]` literally; `source` names
+/// where they came from in error messages
+fn author_globs(patterns: &[String], source: &str) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let escaped: String = pattern
+            .chars()
+            .map(|c| match c {
+                '[' => "[[]".to_string(),
+                ']' => "[]]".to_string(),
+                c => c.to_string(),
+            })
+            .collect();
+        let glob = GlobBuilder::new(&escaped)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| Error::new(&format!("Invalid {} glob {}: {}", source, pattern, e)))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| Error::new(&format!("Invalid {} globs: {}", source, e)))
+}
+
+/// How owners are inferred for each file, as the `infer-owners` options choose
+#[derive(Debug, Clone)]
+pub struct InferOptions {
+    pub scope: InferScope,
+    pub algorithm: InferAlgorithm,
+    /// Days of history read, all of it when 0
+    pub lookback_days: u32,
+    /// Commits, or reviews, an author needs on a file to be suggested
+    pub min_commits: u32,
+    /// Share of a file's total score, in percent, an author needs to be suggested
+    pub min_percentage: u32,
+    pub exclusions: InferExclusions,
+    /// Where the reviews blended in with `--reviews` are read from
+    pub reviews: Option<ReviewSource>,
+    /// Percentage of a file's inferred owners in one team for it to be suggested instead,
+    /// with `--suggest-teams`
+    pub suggest_teams: Option<u32>,
+    /// Percentage of a directory's files sharing an owner for it to get one rule, with
+    /// `--aggregate directory`
+    pub aggregate: Option<u32>,
 }
 
 #[derive(Debug, Tabled)]
@@ -60,9 +308,31 @@ struct InferenceTableRow {
     lines: u32,
 }
 
+/// Suggest owners for files from their git history
+///
+/// Files matching the exclusions of `options` are not analyzed and excluded authors are never suggested;
+/// the shares of the remaining authors are computed without them. Blame summaries are
+/// kept in the [`BlameCache`], so files unchanged since an earlier run are not blamed
+/// again.
+///
+/// Authors are suggested by the owner their email is aliased to in `alias_file`, or else
+/// in the alias file configured for the repository; the addresses of one owner count
+/// together.
+///
+/// With reviews, the reviewers of the merged pull requests touching each file are blended
+/// in, see [`blend_reviews`].
+///
+/// With `suggest_teams`, a file whose inferred owners are at least that percentage members
+/// of one team, by `team_map` or else the team mapping configured for the repository, is
+/// suggested for the team instead.
+///
+/// With `output_file`, the suggestions are merged into that CODEOWNERS file instead of
+/// reported, see [`output_codeowners`]; with `dry_run` the change is printed as a diff.
+/// With `aggregate`, directories where at least that percentage of the files share an
+/// owner get one rule instead of a rule per file, see [`inferred_rules`].
 pub fn run(
-    path: Option<&Path>, scope: &InferScope, algorithm: &InferAlgorithm, lookback_days: u32,
-    min_commits: u32, min_percentage: u32, cache_file: Option<&Path>, output_file: Option<&Path>,
+    path: Option<&Path>, options: &InferOptions, alias_file: Option<&Path>,
+    team_map: Option<&Path>, cache_file: Option<&Path>, output_file: Option<&Path>, dry_run: bool,
 ) -> Result<()> {
     let base_path = path.unwrap_or_else(|| Path::new("."));
     let cache_path = cache_file.unwrap_or_else(|| Path::new(".codeowners.cache"));
@@ -71,69 +341,245 @@ pub fn run(
     let cache = match load_cache(cache_path) {
         Ok(cache) => Some(cache),
         Err(_) => {
-            log::warn!("No cache found, running without CODEOWNERS context");
+            warnings::warn(
+                WarningKind::Other,
+                "No cache found, running without CODEOWNERS context".to_string(),
+            );
             None
         }
     };
 
-    // Open git repository
+    // Compile the cached rules once, with the configured matching options
+    let config = AppConfig::fetch().unwrap_or_default();
+    let matchers = cache
+        .as_ref()
+        .map(|cache| cache_matchers(cache, config.match_options()));
+
+    let ctx = Context::new(config);
+    let aliases = match alias_file {
+        Some(file) => OwnerAliases::load(file)?,
+        None => OwnerAliases::for_repo(&ctx, base_path)?,
+    };
+    // Load the team mapping before the analysis, so a missing one fails fast
+    let teams = match (options.suggest_teams, team_map) {
+        (None, _) => None,
+        (Some(threshold), Some(file)) => Some((TeamMap::load(file)?, threshold)),
+        (Some(threshold), None) => Some((TeamMap::for_repo(&ctx, base_path)?, threshold)),
+    };
+
+    // Fail early outside a git repository; each worker opens its own handle below
     let repo = Repository::open(base_path)
         .map_err(|e| Error::with_source("Failed to open git repository", Box::new(e)))?;
+    let blame_cache = BlameCache::open(&repo);
 
     // Find files to analyze
-    let files = find_files(base_path)?;
-    let files_to_analyze = match scope {
+    let mut files = find_files(base_path)?;
+    // Directories are only rolled up when every file under them was analyzed
+    let found_files = match options.aggregate {
+        Some(_) => files.clone(),
+        None => Vec::new(),
+    };
+    files.retain(|file| !options.exclusions.excludes_path(file, base_path));
+    let files_to_analyze = match options.scope {
         InferScope::All => files,
-        InferScope::Unowned => filter_unowned_files(files, &cache)?,
+        InferScope::Unowned => filter_unowned_files(files, matchers.as_deref())?,
+    };
+
+    // The commits algorithm reads the history once for all files
+    let history = match options.algorithm {
+        InferAlgorithm::Commits | InferAlgorithm::Weighted(_) => commit_history(
+            &repo,
+            repo.mailmap().ok().as_ref(),
+            &files_to_analyze,
+            base_path,
+            options.lookback_days,
+        )?,
+        _ => CommitHistory::new(),
     };
+    let review_history = options
+        .reviews
+        .as_ref()
+        .map(|source| source.history(&ctx, base_path, options.lookback_days))
+        .transpose()?;
+    let reviews = options.reviews.as_ref().zip(review_history.as_ref());
 
     log::info!(
         "Analyzing {} files for ownership inference",
         files_to_analyze.len()
     );
 
-    // Analyze each file
-    let mut inferences = Vec::new();
-    for file_path in files_to_analyze {
-        if let Ok(inference) = analyze_file_ownership(
-            &repo,
-            &file_path,
-            base_path,
-            algorithm,
-            lookback_days,
-            min_commits,
-            min_percentage,
-            &cache,
-        ) {
-            inferences.push(inference);
+    // Analyze each file on the worker pool
+    let skipped = AtomicUsize::new(0);
+    let analyzed = AtomicUsize::new(0);
+    let progress = Progress::new(files_to_analyze.len());
+    let mut inferences: Vec<FileOwnershipInference> = files_to_analyze
+        .par_iter()
+        .map_init(
+            || open_with_mailmap(base_path),
+            |worker, file_path| {
+                // Skip the remaining files once Ctrl-C is pressed or the deadline passes
+                if interrupt::is_interrupted() {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                let (repo, mailmap) = worker.as_ref().ok()?;
+                progress.report(analyzed.fetch_add(1, Ordering::Relaxed) + 1, file_path);
+                analyze_file_ownership(
+                    repo,
+                    mailmap.as_ref(),
+                    file_path,
+                    base_path,
+                    options,
+                    &aliases,
+                    matchers.as_deref(),
+                    &blame_cache,
+                    &history,
+                    reviews,
+                )
+                .ok()
+            },
+        )
+        .flatten()
+        .collect();
+    progress.finish();
+
+    if let Some((teams, threshold)) = &teams {
+        for inference in &mut inferences {
+            inference.suggested_team = suggest_team(&inference.inferred_owners, teams, *threshold);
         }
     }
 
+    let (blamed, reused) = blame_cache.counts();
+    log::info!("Blamed {} files, reused {} blame summaries", blamed, reused);
+    if let Err(e) = blame_cache.save() {
+        warnings::warn(
+            WarningKind::Other,
+            format!("Failed to save the blame cache: {}", e),
+        );
+    }
+
+    // Each inference is complete on its own, so a run stopped early still reports them,
+    // marked as partial, and then fails
+    let skipped = skipped.into_inner();
+    let partial = (skipped > 0).then(|| {
+        format!(
+            "{}; {} of {} files were not analyzed",
+            if interrupt::is_timed_out() {
+                "Timed out"
+            } else {
+                "Interrupted"
+            },
+            skipped,
+            files_to_analyze.len()
+        )
+    });
+    if let Some(partial) = &partial {
+        warnings::warn(WarningKind::Other, partial.to_string());
+    }
+
     // Output results
-    if output_file.is_some() {
-        output_codeowners(&inferences, output_file)?;
+    if let Some(output_file) = output_file {
+        let rules = inferred_rules(base_path, &inferences, &found_files, options.aggregate);
+        output_codeowners(base_path, &rules, output_file, dry_run)?;
     } else {
-        output_text(&inferences);
+        output_text(&inferences, partial.as_deref());
     }
 
+    if partial.is_some() {
+        interrupt::check()?;
+    }
     Ok(())
 }
 
+/// Top contributors to each of `files` by blamed lines, leaving out `exclude`
+///
+/// Used to suggest replacement owners; files without history get no suggestions.
+pub(crate) fn suggest_owners(
+    base_path: &Path, files: &[PathBuf], exclude: &str, limit: usize,
+) -> HashMap<PathBuf, Vec<String>> {
+    let exclude = exclude.to_lowercase();
+    let Ok(repo) = Repository::open(base_path) else {
+        return HashMap::new();
+    };
+    let blame_cache = BlameCache::open(&repo);
+    let options = InferOptions {
+        scope: InferScope::All,
+        algorithm: InferAlgorithm::Lines,
+        lookback_days: 0,
+        min_commits: 1,
+        min_percentage: 0,
+        exclusions: InferExclusions::default(),
+        reviews: None,
+        suggest_teams: None,
+        aggregate: None,
+    };
+    let suggestions = files
+        .par_iter()
+        .map_init(
+            || open_with_mailmap(base_path),
+            |worker, file_path| {
+                let (repo, mailmap) = worker.as_ref().ok()?;
+                let inference = analyze_file_ownership(
+                    repo,
+                    mailmap.as_ref(),
+                    file_path,
+                    base_path,
+                    &options,
+                    &OwnerAliases::default(),
+                    None,
+                    &blame_cache,
+                    &CommitHistory::new(),
+                    None,
+                )
+                .ok()?;
+                let candidates: Vec<String> = inference
+                    .inferred_owners
+                    .into_iter()
+                    .map(|owner| owner.email)
+                    .filter(|email| email.to_lowercase() != exclude)
+                    .take(limit)
+                    .collect();
+                Some((file_path.clone(), candidates))
+            },
+        )
+        .flatten()
+        .collect();
+    if let Err(e) = blame_cache.save() {
+        warnings::warn(
+            WarningKind::Other,
+            format!("Failed to save the blame cache: {}", e),
+        );
+    }
+    suggestions
+}
+
+/// Matchers for the cached rules; invalid patterns are skipped with a warning
+fn cache_matchers(cache: &CodeownersCache, options: MatchOptions) -> Vec<CodeownersEntryMatcher> {
+    cache
+        .entries
+        .iter()
+        .filter_map(
+            |entry| match try_codeowners_entry_to_matcher_with(entry, options) {
+                Ok(matcher) => Some(matcher),
+                Err(e) => {
+                    warnings::warn(WarningKind::SkippedPattern, e.to_string());
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
 fn filter_unowned_files(
-    files: Vec<PathBuf>, cache: &Option<CodeownersCache>,
+    files: Vec<PathBuf>, matchers: Option<&[CodeownersEntryMatcher]>,
 ) -> Result<Vec<PathBuf>> {
-    let Some(cache) = cache else {
+    let Some(matchers) = matchers else {
         return Ok(files);
     };
 
     let mut unowned_files = Vec::new();
-    let matchers: Vec<_> = cache
-        .entries
-        .iter()
-        .map(codeowners_entry_to_matcher)
-        .collect();
     for file in files {
-        let (owners, _tags) = find_owners_and_tags_for_file(&file, &matchers)?;
+        let (owners, _tags) = find_owners_and_tags_for_file(&file, matchers)?;
         if owners.is_empty() || owners.iter().all(|o| o.owner_type == OwnerType::Unowned) {
             unowned_files.push(file);
         }
@@ -142,48 +588,106 @@ fn filter_unowned_files(
     Ok(unowned_files)
 }
 
+/// A repository handle with its mailmap, for one worker
+///
+/// The mailmap is read from `.mailmap` and the `mailmap.file` setting; a repository
+/// without one gets an empty mailmap, and one that fails to load is not applied.
+fn open_with_mailmap(base_path: &Path) -> Result<(Repository, Option<Mailmap>)> {
+    let repo = Repository::open(base_path)?;
+    let mailmap = repo.mailmap().ok();
+    Ok((repo, mailmap))
+}
+
+#[allow(clippy::too_many_arguments)]
 fn analyze_file_ownership(
-    repo: &Repository, file_path: &Path, base_path: &Path, algorithm: &InferAlgorithm,
-    lookback_days: u32, min_commits: u32, min_percentage: u32, cache: &Option<CodeownersCache>,
+    repo: &Repository, mailmap: Option<&Mailmap>, file_path: &Path, base_path: &Path,
+    options: &InferOptions, aliases: &OwnerAliases, matchers: Option<&[CodeownersEntryMatcher]>,
+    blame_cache: &BlameCache, history: &CommitHistory,
+    reviews: Option<(&ReviewSource, &ReviewHistory)>,
 ) -> Result<FileOwnershipInference> {
-    // Get existing owners from cache
-    let existing_owners = match cache {
-        Some(cache) => {
-            let matchers: Vec<_> = cache
-                .entries
-                .iter()
-                .map(codeowners_entry_to_matcher)
-                .collect();
+    let lookback_days = options.lookback_days;
+    let exclusions = &options.exclusions;
+    // Get existing owners from the cached rules
+    let existing_owners = match matchers {
+        Some(matchers) => {
             let (owners, _tags) =
-                find_owners_and_tags_for_file(file_path, &matchers).unwrap_or_default();
+                find_owners_and_tags_for_file(file_path, matchers).unwrap_or_default();
             owners
         }
         None => Vec::new(),
     };
 
-    // Get git blame for the file
-    let blame = get_file_blame(repo, file_path, base_path, lookback_days)?;
+    let relative_path = file_path
+        .strip_prefix(base_path)
+        .map_err(|_| Error::new("File path is not within repository"))?;
+    // Only files committed at HEAD have a history to infer from
+    let blob = repo.head()?.peel_to_tree()?.get_path(relative_path)?.id();
 
     // Analyze ownership based on algorithm
-    let contributors = match algorithm {
-        InferAlgorithm::Lines => analyze_by_lines(&blame, min_commits)?,
-        InferAlgorithm::Commits => {
-            analyze_by_commits(repo, file_path, base_path, lookback_days, min_commits)?
-        }
-        InferAlgorithm::Recent => analyze_by_recent_activity(&blame, min_commits)?,
+    let mut contributors = match &options.algorithm {
+        InferAlgorithm::Lines => analyze_by_lines(&blame_summary(
+            repo,
+            mailmap,
+            relative_path,
+            blob,
+            lookback_days,
+            blame_cache,
+        )?)?,
+        InferAlgorithm::Commits => analyze_by_commits(history, relative_path)?,
+        InferAlgorithm::Recent => analyze_by_recent_activity(&blame_summary(
+            repo,
+            mailmap,
+            relative_path,
+            blob,
+            lookback_days,
+            blame_cache,
+        )?)?,
+        InferAlgorithm::Weighted(weights) => analyze_weighted(
+            &blame_summary(
+                repo,
+                mailmap,
+                relative_path,
+                blob,
+                lookback_days,
+                blame_cache,
+            )?,
+            history,
+            relative_path,
+            weights,
+        )?,
     };
+    contributors.retain(|email, _| !exclusions.excludes_author(email));
+
+    // Count the commits of every address of an owner together before filtering
+    let mut contributors = merge_aliases(contributors, aliases);
+    if let Some((source, history)) = reviews {
+        contributors = blend_reviews(
+            contributors,
+            history.get(relative_path),
+            source.weight,
+            exclusions,
+        );
+    }
+    // Reviews of merged pull requests count like commits
+    contributors
+        .retain(|_, contributor| contributor.commits + contributor.reviews >= options.min_commits);
 
     // Filter by minimum percentage
     let total_score: f64 = contributors.values().map(|c| c.score).sum();
-    let min_score = (min_percentage as f64 / 100.0) * total_score;
+    let min_score = (options.min_percentage as f64 / 100.0) * total_score;
 
     let mut inferred_owners: Vec<InferredOwner> = contributors
         .into_values()
         .filter(|c| c.score >= min_score)
         .collect();
 
-    // Sort by score descending
-    inferred_owners.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    // Sort by score descending, ties by email so runs agree
+    inferred_owners.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap()
+            .then_with(|| a.email.cmp(&b.email))
+    });
 
     // Calculate confidence (higher if fewer candidates, higher scores)
     let confidence = if inferred_owners.is_empty() {
@@ -196,7 +700,7 @@ fn analyze_file_ownership(
             0.0
         };
         let candidate_penalty = 1.0 - (inferred_owners.len().min(5) as f64 * 0.1);
-        (score_ratio * candidate_penalty).min(1.0).max(0.0)
+        (score_ratio * candidate_penalty).clamp(0.0, 1.0)
     };
 
     Ok(FileOwnershipInference {
@@ -204,82 +708,278 @@ fn analyze_file_ownership(
         inferred_owners,
         confidence,
         existing_owners,
+        suggested_team: None,
     })
 }
 
-fn get_file_blame<'a>(
-    repo: &'a Repository, file_path: &Path, base_path: &Path, lookback_days: u32,
-) -> Result<Blame<'a>> {
-    let relative_path = file_path
-        .strip_prefix(base_path)
-        .map_err(|_| Error::new("File path is not within repository"))?;
+/// The team at least `threshold` percent of `owners` are members of, by their aliased
+/// owner or email; of several such teams, the one with the most of them
+fn suggest_team(
+    owners: &[InferredOwner], teams: &TeamMap, threshold: u32,
+) -> Option<SuggestedTeam> {
+    if owners.is_empty() {
+        return None;
+    }
 
-    let mut blame_options = BlameOptions::new();
+    let mut members: BTreeMap<&str, Vec<&InferredOwner>> = BTreeMap::new();
+    for owner in owners {
+        let mut owner_teams = teams.teams_of(owner.owner());
+        owner_teams.extend(teams.teams_of(&owner.email));
+        owner_teams.sort_unstable();
+        owner_teams.dedup();
+        for team in owner_teams {
+            members.entry(team).or_default().push(owner);
+        }
+    }
 
-    // Set lookback period
-    if lookback_days > 0 {
-        let cutoff_time = chrono::Utc::now() - chrono::Duration::days(lookback_days as i64);
-        let _git_time = Time::new(cutoff_time.timestamp(), 0);
-        blame_options.oldest_commit(repo.head()?.peel_to_commit()?.id());
-        // Note: git2 doesn't have direct time filtering, so we'll handle this in analysis
+    // Ties go to the first team by handle, so runs agree
+    let (team, members) = members
+        .into_iter()
+        .max_by(|a, b| a.1.len().cmp(&b.1.len()).then_with(|| b.0.cmp(a.0)))?;
+    let share = members.len() as f64 / owners.len() as f64;
+    if share * 100.0 < threshold as f64 {
+        return None;
     }
+    Some(SuggestedTeam {
+        team: team.to_string(),
+        members: members
+            .iter()
+            .map(|member| member.owner().to_string())
+            .collect(),
+        share,
+        commits: members.iter().map(|member| member.commits).sum(),
+        lines: members.iter().map(|member| member.lines).sum(),
+    })
+}
 
-    repo.blame_file(relative_path, Some(&mut blame_options))
-        .map_err(|e| Error::with_source("Failed to get git blame", Box::new(e)))
+/// Blame summary of the file at `relative_path`, whose content at HEAD is `blob`, from
+/// the blame cache or blamed now, with authors canonicalized by `mailmap`
+///
+/// Summaries are cached with the identities recorded in the commits, so editing the
+/// mailmap takes effect without blaming again.
+fn blame_summary(
+    repo: &Repository, mailmap: Option<&Mailmap>, relative_path: &Path, blob: Oid,
+    lookback_days: u32, blame_cache: &BlameCache,
+) -> Result<Vec<BlameAuthor>> {
+    // Set lookback period
+    // Note: git2 doesn't have direct time filtering, so we'll handle this in analysis
+    let oldest_commit = if lookback_days > 0 {
+        Some(repo.head()?.peel_to_commit()?.id())
+    } else {
+        None
+    };
+
+    blame_cache
+        .summary(&blame_key(blob, oldest_commit), || {
+            let mut blame_options = BlameOptions::new();
+            if let Some(oldest) = oldest_commit {
+                blame_options.oldest_commit(oldest);
+            }
+            let blame = repo
+                .blame_file(relative_path, Some(&mut blame_options))
+                .map_err(|e| Error::with_source("Failed to get git blame", Box::new(e)))?;
+            Ok(summarize(&blame))
+        })
+        .map(|authors| apply_mailmap(authors, mailmap))
 }
 
-fn analyze_by_lines(blame: &Blame, min_commits: u32) -> Result<HashMap<String, InferredOwner>> {
-    let mut contributors: HashMap<String, InferredOwner> = HashMap::new();
+/// Email `mailmap` maps the author `name <email>` to, or `email` itself
+fn canonical_email(mailmap: &Mailmap, name: &str, email: &str) -> String {
+    // A signature needs a name; an author without one matches on the email alone
+    let name = if name.is_empty() { email } else { name };
+    Signature::new(name, email, &Time::new(0, 0))
+        .and_then(|signature| mailmap.resolve_signature(&signature))
+        .ok()
+        .and_then(|signature| signature.email().map(str::to_string))
+        .unwrap_or_else(|| email.to_string())
+}
 
-    for hunk in blame.iter() {
-        let signature = hunk.final_signature();
-        let email = signature.email().unwrap_or("unknown").to_string();
+/// `authors` with their emails canonicalized by `mailmap`, merging the authors who turn
+/// out to be the same person
+fn apply_mailmap(authors: Vec<BlameAuthor>, mailmap: Option<&Mailmap>) -> Vec<BlameAuthor> {
+    let Some(mailmap) = mailmap else {
+        return authors;
+    };
+    let mut canonical: Vec<BlameAuthor> = Vec::new();
+    for author in authors {
+        let email = canonical_email(mailmap, &author.name, &author.email);
+        match canonical
+            .iter_mut()
+            .find(|existing| existing.email == email)
+        {
+            Some(existing) => {
+                existing.lines += author.lines;
+                existing.hunks += author.hunks;
+                existing.last_commit_time = existing.last_commit_time.max(author.last_commit_time);
+            }
+            None => canonical.push(BlameAuthor { email, ..author }),
+        }
+    }
+    canonical
+}
 
-        let entry = contributors
-            .entry(email.clone())
-            .or_insert_with(|| InferredOwner {
-                email: email.clone(),
-                username: None,
-                score: 0.0,
-                commits: 0,
-                lines: 0,
-                last_commit_days_ago: u32::MAX,
-            });
+/// Contributors with the addresses aliased to one owner merged into a single contributor,
+/// keyed by that owner
+///
+/// The merged contributor keeps the first of its addresses, so runs agree.
+fn merge_aliases(
+    contributors: HashMap<String, InferredOwner>, aliases: &OwnerAliases,
+) -> HashMap<String, InferredOwner> {
+    if aliases.is_empty() {
+        return contributors;
+    }
 
-        entry.lines += hunk.lines_in_hunk() as u32;
-        entry.score += hunk.lines_in_hunk() as f64;
-        entry.commits += 1;
+    let mut merged: HashMap<String, InferredOwner> = HashMap::new();
+    for (email, contributor) in contributors {
+        let Some(owner) = aliases.owner(&email) else {
+            merged.insert(email, contributor);
+            continue;
+        };
+        match merged.entry(owner.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let existing = entry.get_mut();
+                existing.score += contributor.score;
+                existing.commits += contributor.commits;
+                existing.lines += contributor.lines;
+                existing.last_commit_days_ago = existing
+                    .last_commit_days_ago
+                    .min(contributor.last_commit_days_ago);
+                if contributor.email < existing.email {
+                    existing.email = contributor.email;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(InferredOwner {
+                    username: Some(owner.to_string()),
+                    ..contributor
+                });
+            }
+        }
+    }
+    merged
+}
 
-        // Update most recent commit
-        let commit_time = hunk.final_signature().when();
-        let days_ago = (chrono::Utc::now().timestamp() - commit_time.seconds()) / 86400;
-        entry.last_commit_days_ago = entry.last_commit_days_ago.min(days_ago as u32);
+/// `contributors` with the `reviewers` of their file blended in: each contributor's share of
+/// the scores of the contributors weighs `1 - weight`, and each reviewer's share of the
+/// review scores `weight`
+///
+/// Reviewers are suggested by `@login`, and one an author is aliased to is credited with
+/// both. Excluded authors are matched by the GitHub noreply address of the login, such as
+/// `dependabot[bot]@users.noreply.github.com`. Files nobody reviewed keep their scores.
+fn blend_reviews(
+    mut contributors: HashMap<String, InferredOwner>,
+    reviewers: Option<&HashMap<String, ReviewerStats>>, weight: f64, exclusions: &InferExclusions,
+) -> HashMap<String, InferredOwner> {
+    let reviewers: Vec<(&ReviewerStats, String)> = reviewers
+        .into_iter()
+        .flat_map(HashMap::values)
+        .map(|reviewer| {
+            let email = format!("{}@users.noreply.github.com", reviewer.login);
+            (reviewer, email)
+        })
+        .filter(|(_, email)| !exclusions.excludes_author(email))
+        .collect();
+    let total_reviews: f64 = reviewers.iter().map(|(reviewer, _)| reviewer.score).sum();
+    if total_reviews <= 0.0 {
+        return contributors;
     }
 
-    // Filter by minimum commits
-    contributors.retain(|_, contributor| contributor.commits >= min_commits);
+    let total_score: f64 = contributors.values().map(|c| c.score).sum();
+    for contributor in contributors.values_mut() {
+        contributor.score = if total_score > 0.0 {
+            (1.0 - weight) * contributor.score / total_score
+        } else {
+            0.0
+        };
+    }
+    for (reviewer, email) in reviewers {
+        let handle = format!("@{}", reviewer.login);
+        let score = weight * reviewer.score / total_reviews;
+        match contributors
+            .values_mut()
+            .find(|contributor| contributor.owner().eq_ignore_ascii_case(&handle))
+        {
+            Some(contributor) => {
+                contributor.score += score;
+                contributor.reviews += reviewer.reviews;
+                contributor.last_commit_days_ago = contributor
+                    .last_commit_days_ago
+                    .min(reviewer.last_review_days_ago);
+            }
+            None => {
+                contributors.insert(
+                    handle.clone(),
+                    InferredOwner {
+                        email,
+                        username: Some(handle),
+                        score,
+                        commits: 0,
+                        lines: 0,
+                        last_commit_days_ago: reviewer.last_review_days_ago,
+                        reviews: reviewer.reviews,
+                    },
+                );
+            }
+        }
+    }
+    contributors
+}
+
+fn analyze_by_lines(blame: &[BlameAuthor]) -> Result<HashMap<String, InferredOwner>> {
+    let mut contributors: HashMap<String, InferredOwner> = HashMap::new();
+
+    for author in blame {
+        // Each blamed hunk counts as a commit
+        let days_ago = (output::now().timestamp() - author.last_commit_time) / 86400;
+        contributors.insert(
+            author.email.clone(),
+            InferredOwner {
+                email: author.email.clone(),
+                username: None,
+                score: author.lines as f64,
+                commits: author.hunks,
+                lines: author.lines,
+                last_commit_days_ago: days_ago as u32,
+                reviews: 0,
+            },
+        );
+    }
 
     Ok(contributors)
 }
 
-fn analyze_by_commits(
-    repo: &Repository, file_path: &Path, base_path: &Path, lookback_days: u32, min_commits: u32,
-) -> Result<HashMap<String, InferredOwner>> {
-    let relative_path = file_path
-        .strip_prefix(base_path)
-        .map_err(|_| Error::new("File path is not within repository"))?;
+/// Authors of the commits touching each file, by path relative to the repository
+type CommitHistory = HashMap<PathBuf, HashMap<String, InferredOwner>>;
+
+/// Authors of the commits within the last `lookback_days` days (all commits when 0) that
+/// touch each of `files`, read in one pass over the history reachable from HEAD
+///
+/// Every commit is diffed against its first parent once, rather than once per file.
+/// Authors are canonicalized by `mailmap`.
+fn commit_history(
+    repo: &Repository, mailmap: Option<&Mailmap>, files: &[PathBuf], base_path: &Path,
+    lookback_days: u32,
+) -> Result<CommitHistory> {
+    let targets: HashSet<&Path> = files
+        .iter()
+        .filter_map(|file| file.strip_prefix(base_path).ok())
+        .collect();
 
-    let mut contributors: HashMap<String, InferredOwner> = HashMap::new();
+    let mut history = CommitHistory::new();
     let mut revwalk = repo.revwalk()?;
     revwalk.push_head()?;
 
     let cutoff_time = if lookback_days > 0 {
-        Some(chrono::Utc::now() - chrono::Duration::days(lookback_days as i64))
+        Some(output::now() - chrono::Duration::days(lookback_days as i64))
     } else {
         None
     };
 
     for oid in revwalk {
+        // Files keep the commits read so far once Ctrl-C is pressed
+        if interrupt::is_interrupted() {
+            break;
+        }
         let oid = oid?;
         let commit = repo.find_commit(oid)?;
 
@@ -291,12 +991,35 @@ fn analyze_by_commits(
             }
         }
 
-        // Check if commit touches our file
-        if commit_touches_file(repo, &commit, relative_path)? {
-            let signature = commit.author();
-            let email = signature.email().unwrap_or("unknown").to_string();
+        let tree = commit.tree()?;
+        let parent_tree = if commit.parent_count() > 0 {
+            Some(commit.parent(0)?.tree()?)
+        } else {
+            None
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+
+        // A file counts once per commit, whether it was added, changed or deleted
+        let touched: HashSet<&Path> = diff
+            .deltas()
+            .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+            .flatten()
+            .filter(|path| targets.contains(path))
+            .collect();
+        if touched.is_empty() {
+            continue;
+        }
 
-            let entry = contributors
+        let signature = match mailmap {
+            Some(mailmap) => commit.author_with_mailmap(mailmap)?,
+            None => commit.author(),
+        };
+        let email = signature.email().unwrap_or("unknown").to_string();
+        let days_ago = (output::now().timestamp() - commit.time().seconds()) / 86400;
+        for path in touched {
+            let entry = history
+                .entry(path.to_path_buf())
+                .or_default()
                 .entry(email.clone())
                 .or_insert_with(|| InferredOwner {
                     email: email.clone(),
@@ -305,79 +1028,104 @@ fn analyze_by_commits(
                     commits: 0,
                     lines: 0,
                     last_commit_days_ago: u32::MAX,
+                    reviews: 0,
                 });
 
             entry.commits += 1;
             entry.score += 1.0;
-
-            let days_ago = (chrono::Utc::now().timestamp() - commit.time().seconds()) / 86400;
             entry.last_commit_days_ago = entry.last_commit_days_ago.min(days_ago as u32);
         }
     }
 
-    contributors.retain(|_, contributor| contributor.commits >= min_commits);
-    Ok(contributors)
+    Ok(history)
 }
 
-fn analyze_by_recent_activity(
-    blame: &Blame, min_commits: u32,
+fn analyze_by_commits(
+    history: &CommitHistory, relative_path: &Path,
 ) -> Result<HashMap<String, InferredOwner>> {
-    let mut contributors = analyze_by_lines(blame, min_commits)?;
+    Ok(history.get(relative_path).cloned().unwrap_or_default())
+}
+
+fn analyze_by_recent_activity(blame: &[BlameAuthor]) -> Result<HashMap<String, InferredOwner>> {
+    let mut contributors = analyze_by_lines(blame)?;
 
     // Weight recent activity higher
-    let _now = chrono::Utc::now().timestamp();
     for contributor in contributors.values_mut() {
-        let days_ago = contributor.last_commit_days_ago as f64;
-        let recency_weight = (1.0 / (1.0 + days_ago / 30.0)).max(0.1); // More weight for recent commits
-        contributor.score *= recency_weight;
+        contributor.score *= recency_weight(contributor.last_commit_days_ago);
     }
 
     Ok(contributors)
 }
 
-fn commit_touches_file(repo: &Repository, commit: &git2::Commit, file_path: &Path) -> Result<bool> {
-    let tree = commit.tree()?;
-    let parent_tree = if commit.parent_count() > 0 {
-        Some(commit.parent(0)?.tree()?)
-    } else {
-        None
-    };
-
-    // repo is passed as parameter now
-    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
-
-    let file_path_str = file_path
-        .to_str()
-        .ok_or_else(|| Error::new("Invalid file path"))?;
-    let mut found = false;
+/// Weight of a contribution last made `days_ago` days ago: 1 today, halving after a month,
+/// never below 0.1
+fn recency_weight(days_ago: u32) -> f64 {
+    (1.0 / (1.0 + days_ago as f64 / 30.0)).max(0.1)
+}
 
-    diff.foreach(
-        &mut |delta, _| {
-            if let Some(path) = delta.new_file().path() {
-                if path.to_str() == Some(file_path_str) {
-                    found = true;
-                    return false; // Stop iteration
-                }
+/// Contributors scored by `weights` over their shares of the blamed lines, of the commits
+/// in the history touching the file, and of the recency of their last change
+///
+/// A large formatting commit earns a share of the lines but only one commit, and the lines
+/// of an author who left long ago count for less. Commits are counted from the history,
+/// not from blamed hunks, so `--min-commits` counts real commits. Scores are divided by the
+/// sum of the weights, so the scores of a file's contributors add up to 1 whatever the
+/// weights.
+fn analyze_weighted(
+    blame: &[BlameAuthor], history: &CommitHistory, relative_path: &Path, weights: &InferWeights,
+) -> Result<HashMap<String, InferredOwner>> {
+    let mut contributors = analyze_by_lines(blame)?;
+    for contributor in contributors.values_mut() {
+        contributor.commits = 0;
+    }
+    for (email, committer) in analyze_by_commits(history, relative_path)? {
+        match contributors.entry(email) {
+            Entry::Occupied(mut entry) => {
+                let contributor = entry.get_mut();
+                contributor.commits = committer.commits;
+                contributor.last_commit_days_ago = contributor
+                    .last_commit_days_ago
+                    .min(committer.last_commit_days_ago);
             }
-            if let Some(path) = delta.old_file().path() {
-                if path.to_str() == Some(file_path_str) {
-                    found = true;
-                    return false; // Stop iteration
-                }
+            // Authors whose lines were all replaced still count for their commits
+            Entry::Vacant(entry) => {
+                entry.insert(InferredOwner {
+                    lines: 0,
+                    ..committer
+                });
             }
-            true // Continue iteration
-        },
-        None,
-        None,
-        None,
-    )?;
+        }
+    }
+
+    let share = |value: f64, total: f64| if total > 0.0 { value / total } else { 0.0 };
+    let total_lines: f64 = contributors.values().map(|c| c.lines as f64).sum();
+    let total_commits: f64 = contributors.values().map(|c| c.commits as f64).sum();
+    let total_recency: f64 = contributors
+        .values()
+        .map(|c| recency_weight(c.last_commit_days_ago))
+        .sum();
+    let total_weight = weights.lines + weights.commits + weights.recency;
+    for contributor in contributors.values_mut() {
+        contributor.score = (weights.lines * share(contributor.lines as f64, total_lines)
+            + weights.commits * share(contributor.commits as f64, total_commits)
+            + weights.recency
+                * share(
+                    recency_weight(contributor.last_commit_days_ago),
+                    total_recency,
+                ))
+            / total_weight;
+    }
 
-    Ok(found)
+    Ok(contributors)
 }
 
-fn output_text(inferences: &[FileOwnershipInference]) {
+/// Print `inferences` as a table; `partial` says why the results are incomplete
+fn output_text(inferences: &[FileOwnershipInference], partial: Option<&str>) {
     if inferences.is_empty() {
         println!("No ownership inferences found.");
+        if let Some(partial) = partial {
+            println!("Partial results: {}", partial);
+        }
         return;
     }
 
@@ -394,17 +1142,24 @@ fn output_text(inferences: &[FileOwnershipInference]) {
                 .join(", ")
         };
 
-        let (inferred_owner, score, commits, lines) =
-            if let Some(top_owner) = inference.inferred_owners.first() {
-                (
-                    top_owner.email.clone(),
-                    format!("{:.1}%", top_owner.score * 100.0),
-                    top_owner.commits,
-                    top_owner.lines,
-                )
-            } else {
-                ("None".to_string(), "0%".to_string(), 0, 0)
-            };
+        let (inferred_owner, score, commits, lines) = if let Some(team) = &inference.suggested_team
+        {
+            (
+                format!("{} ({})", team.team, team.members.join(", ")),
+                format!("{:.1}%", team.share * 100.0),
+                team.commits,
+                team.lines,
+            )
+        } else if let Some(top_owner) = inference.inferred_owners.first() {
+            (
+                top_owner.owner().to_string(),
+                format!("{:.1}%", top_owner.score * 100.0),
+                top_owner.commits,
+                top_owner.lines,
+            )
+        } else {
+            ("None".to_string(), "0%".to_string(), 0, 0)
+        };
 
         rows.push(InferenceTableRow {
             file: inference.file_path.display().to_string(),
@@ -420,6 +1175,9 @@ fn output_text(inferences: &[FileOwnershipInference]) {
     println!("{}", table);
 
     println!("\nSummary:");
+    if let Some(partial) = partial {
+        println!("  Partial results: {}", partial);
+    }
     println!("  Total files analyzed: {}", inferences.len());
     println!(
         "  Files with inferred owners: {}",
@@ -428,51 +1186,297 @@ fn output_text(inferences: &[FileOwnershipInference]) {
             .filter(|i| !i.inferred_owners.is_empty())
             .count()
     );
+    let suggested_teams = inferences
+        .iter()
+        .filter(|i| i.suggested_team.is_some())
+        .count();
+    if suggested_teams > 0 {
+        println!("  Files suggested for a team: {}", suggested_teams);
+    }
     println!(
         "  Average confidence: {:.1}%",
         inferences.iter().map(|i| i.confidence).sum::<f64>() / inferences.len() as f64 * 100.0
     );
 }
 
+/// A CODEOWNERS rule written for inferred owners
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct InferredRule {
+    /// Pattern as written
+    pattern: String,
+    /// File or directory the rule is for, relative to the repository
+    path: String,
+    owner: String,
+    directory: bool,
+}
+
+/// The owner rules are written for: the suggested team, or else the top inferred owner
+fn rule_owner(inference: &FileOwnershipInference) -> Option<&str> {
+    match &inference.suggested_team {
+        Some(team) => Some(team.team.as_str()),
+        None => inference.inferred_owners.first().map(InferredOwner::owner),
+    }
+}
+
+/// A rule per file with an inferred owner or, with `aggregate`, a rule per directory
+/// where at least that percentage of the files share one owner
+///
+/// `files` are all the files found under `base_path`. A directory is only rolled up when
+/// every file under it was analyzed, so it never takes over files owned by other rules
+/// or left out of the analysis, and when it holds at least two files. The shallowest
+/// such directory wins. Its files inferred to another owner keep a rule of their own,
+/// after the directory rules so that they take precedence.
+fn inferred_rules(
+    base_path: &Path, inferences: &[FileOwnershipInference], files: &[PathBuf],
+    aggregate: Option<u32>,
+) -> Vec<InferredRule> {
+    let relative = |path: &Path| {
+        let path = path.strip_prefix(base_path).unwrap_or(path);
+        normalize_pattern(&path.to_string_lossy())
+    };
+    let owned: Vec<(String, Option<&str>, &FileOwnershipInference)> = inferences
+        .iter()
+        .map(|inference| {
+            (
+                relative(&inference.file_path),
+                rule_owner(inference),
+                inference,
+            )
+        })
+        .collect();
+
+    let mut directory_rules: Vec<InferredRule> = Vec::new();
+    if let Some(threshold) = aggregate {
+        /// Files found under a directory, those analyzed and their owners
+        #[derive(Default)]
+        struct DirectoryStats<'a> {
+            files: usize,
+            analyzed: usize,
+            owners: BTreeMap<&'a str, usize>,
+        }
+
+        let ancestors = |path: &str| -> Vec<String> {
+            let mut dirs: Vec<String> = Path::new(path)
+                .ancestors()
+                .skip(1)
+                .map(|dir| dir.to_string_lossy().into_owned())
+                .filter(|dir| !dir.is_empty())
+                .collect();
+            dirs.reverse();
+            dirs
+        };
+        let mut stats: BTreeMap<String, DirectoryStats> = BTreeMap::new();
+        for file in files {
+            for dir in ancestors(&relative(file)) {
+                stats.entry(dir).or_default().files += 1;
+            }
+        }
+        for (path, owner, _) in &owned {
+            for dir in ancestors(path) {
+                let stats = stats.entry(dir).or_default();
+                stats.analyzed += 1;
+                if let Some(owner) = owner {
+                    *stats.owners.entry(owner).or_insert(0) += 1;
+                }
+            }
+        }
+
+        // Shallowest first, so a rolled-up directory covers its subdirectories
+        let mut dirs: Vec<(&String, &DirectoryStats)> = stats.iter().collect();
+        dirs.sort_by_key(|(dir, _)| (Path::new(dir.as_str()).components().count(), *dir));
+        for (dir, stats) in dirs {
+            let inside = |rule: &InferredRule| Path::new(dir.as_str()).starts_with(&rule.path);
+            if stats.files < 2 || stats.analyzed < stats.files || directory_rules.iter().any(inside)
+            {
+                continue;
+            }
+            // The first owner in order wins a tie
+            let Some((owner, count)) =
+                stats
+                    .owners
+                    .iter()
+                    .fold(
+                        None,
+                        |best: Option<(&str, usize)>, (&owner, &count)| match best {
+                            Some((_, most)) if most >= count => best,
+                            _ => Some((owner, count)),
+                        },
+                    )
+            else {
+                continue;
+            };
+            if count * 100 >= threshold as usize * stats.files {
+                directory_rules.push(InferredRule {
+                    pattern: format!("/{}/", escape_pattern(dir)),
+                    path: dir.clone(),
+                    owner: owner.to_string(),
+                    directory: true,
+                });
+            }
+        }
+    }
+
+    let file_rules = owned.iter().filter_map(|(path, owner, inference)| {
+        let owner = (*owner)?;
+        let covered = directory_rules
+            .iter()
+            .any(|rule| Path::new(path).starts_with(&rule.path) && rule.owner == owner);
+        (!covered).then(|| InferredRule {
+            pattern: escape_pattern(&inference.file_path.display().to_string()),
+            path: path.clone(),
+            owner: owner.to_string(),
+            directory: false,
+        })
+    });
+    let mut rules = directory_rules.clone();
+    rules.extend(file_rules);
+    rules
+}
+
+/// Merge `rules` into the CODEOWNERS file `output_file`, or with `dry_run` print the
+/// change as a diff
+///
+/// Rules for a file or directory the document already has a rule for, or whose last
+/// matching rule already names the owner, are skipped; the others are appended without
+/// disturbing the comments and layout of the file.
 fn output_codeowners(
-    inferences: &[FileOwnershipInference], output_file: Option<&Path>,
+    base_path: &Path, rules: &[InferredRule], output_file: &Path, dry_run: bool,
 ) -> Result<()> {
-    let mut output_lines = Vec::new();
+    let mut document = CodeownersDocument::load_or_new(output_file).map_err(|e| {
+        Error::with_source(
+            &format!("Failed to open file: {}", output_file.display()),
+            Box::new(e),
+        )
+    })?;
+    let before = document.to_string();
+    let existing: Vec<(String, Option<CodeownersEntryMatcher>)> = document
+        .entries()
+        .map(|entry| {
+            let matcher = try_codeowners_entry_to_matcher_with(entry, MatchOptions::default());
+            let pattern = normalize_pattern(&entry.pattern);
+            (pattern.trim_end_matches('/').to_string(), matcher.ok())
+        })
+        .collect();
 
-    for inference in inferences {
-        if let Some(top_owner) = inference.inferred_owners.first() {
-            let owner_str = top_owner.email.clone();
-            let pattern = inference.file_path.display().to_string();
-            output_lines.push(format!("{} {}", pattern, owner_str));
+    let mut added = 0;
+    let mut covered = 0;
+    for rule in rules {
+        // Patterns are relative to the repository, wherever the file is written
+        let last_match = existing.iter().rev().find_map(|(_, matcher)| {
+            let matcher = matcher.as_ref()?;
+            matcher
+                .override_matcher
+                .matched(Path::new(&rule.path), rule.directory)
+                .is_whitelist()
+                .then_some(matcher)
+        });
+        let has_rule = existing.iter().any(|(existing, _)| *existing == rule.path);
+        let owned = last_match.is_some_and(|matcher| {
+            matcher
+                .owners
+                .iter()
+                .any(|o| o.identifier.eq_ignore_ascii_case(&rule.owner))
+        });
+        if has_rule || owned {
+            covered += 1;
+            continue;
         }
+
+        document.push_line(format!("{} {}", rule.pattern, rule.owner))?;
+        added += 1;
     }
 
-    if let Some(file_path) = output_file {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)
-            .map_err(|e| {
-                Error::with_source(
-                    &format!("Failed to open file: {}", file_path.display()),
-                    Box::new(e),
-                )
-            })?;
+    let summary = format!(
+        "{} CODEOWNERS rules to {}; {} already covered",
+        added,
+        output_file.display(),
+        covered
+    );
+    if dry_run {
+        let change = Change {
+            path: output_file.to_path_buf(),
+            before,
+            after: document.to_string(),
+        };
+        print!("{}", diff(base_path, &change));
+        println!("Would add {}", summary);
+        println!("Dry run: no files were changed");
+    } else {
+        if added > 0 {
+            document.save(output_file)?;
+        }
+        println!("Added {}", summary);
+    }
 
-        for line in &output_lines {
-            writeln!(file, "{}", line)
-                .map_err(|e| Error::with_source("Failed to write to file", Box::new(e)))?;
+    Ok(())
+}
+
+/// `pattern` without the leading `./` or `/` that anchors it, for comparing rules
+fn normalize_pattern(pattern: &str) -> String {
+    let pattern = pattern.strip_prefix("./").unwrap_or(pattern);
+    pattern.strip_prefix('/').unwrap_or(pattern).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blamed(email: &str, lines: u32) -> BlameAuthor {
+        BlameAuthor {
+            email: email.to_string(),
+            name: email.to_string(),
+            lines,
+            hunks: 1,
+            last_commit_time: output::now().timestamp(),
         }
+    }
 
-        log::info!(
-            "Appended {} CODEOWNERS entries to {}",
-            inferences
-                .iter()
-                .filter(|i| !i.inferred_owners.is_empty())
-                .count(),
-            file_path.display()
-        );
+    fn committed(email: &str, commits: u32) -> (String, InferredOwner) {
+        let owner = InferredOwner {
+            email: email.to_string(),
+            username: None,
+            score: commits as f64,
+            commits,
+            lines: 0,
+            last_commit_days_ago: 0,
+            reviews: 0,
+        };
+        (email.to_string(), owner)
     }
 
-    Ok(())
+    #[test]
+    fn test_analyze_weighted() {
+        let path = Path::new("src/lib.rs");
+        let blame = [blamed("alice@example.com", 3), blamed("bob@example.com", 1)];
+        let history: CommitHistory = HashMap::from([(
+            path.to_path_buf(),
+            HashMap::from([
+                committed("alice@example.com", 1),
+                committed("bob@example.com", 1),
+            ]),
+        )]);
+        let scores = |lines, commits, recency| {
+            let weights = InferWeights::new(lines, commits, recency).unwrap();
+            let contributors = analyze_weighted(&blame, &history, path, &weights).unwrap();
+            let score = |email: &str| contributors[email].score;
+            (score("alice@example.com"), score("bob@example.com"))
+        };
+        let close = |(a, b): (f64, f64), (x, y): (f64, f64)| {
+            assert!((a - x).abs() < 1e-9 && (b - y).abs() < 1e-9, "{:?}", (a, b));
+        };
+
+        // A single signal scores the shares of that signal alone, whatever its weight
+        close(scores(0.0, 1.0, 0.0), (0.5, 0.5));
+        close(scores(0.0, 0.3, 0.0), (0.5, 0.5));
+        close(scores(2.0, 0.0, 0.0), (0.75, 0.25));
+        // The defaults blend lines 0.5, commits 0.3 and recency 0.2
+        close(scores(0.5, 0.3, 0.2), (0.625, 0.375));
+        close(scores(5.0, 3.0, 2.0), (0.625, 0.375));
+
+        // Commits come from the history, not from blamed hunks
+        let weights = InferWeights::default();
+        let contributors = analyze_weighted(&blame, &history, path, &weights).unwrap();
+        assert_eq!(contributors["alice@example.com"].commits, 1);
+        assert_eq!(contributors["alice@example.com"].lines, 3);
+    }
 }
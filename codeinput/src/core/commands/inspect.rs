@@ -1,22 +1,41 @@
 use crate::{
     core::{
-        cache::sync_cache,
-        types::{CodeownersEntry, OutputFormat},
+        cache::{sync_cache_for, CacheQuery},
+        context::Context,
+        matcher::{Matcher, MatcherStrategy},
+        parse::resolve_paths,
+        resolver::find_matching_entries,
+        team_map::TeamMap,
+        types::{normalize_codeowners_pattern, CodeownersEntry, OutputFormat},
     },
     utils::error::{Error, Result},
 };
 use std::io::{self, Write};
 
 /// Inspect ownership and tags for a specific file
+///
+/// With `raw`, every matching rule is listed with its owners and tags exactly as written,
+/// instead of only the rule that applied. With `explain`, every matching rule is listed
+/// along with how it was matched: the case sensitivity in effect, the normalized pattern
+/// and why the applied rule takes precedence. With `expand`, team owners are listed with
+/// their members from the team mapping. With `no_cache`, the file is resolved on the fly
+/// against the CODEOWNERS files above it, without reading or building the cache.
+#[allow(clippy::too_many_arguments)]
 pub fn run(
     file_path: &std::path::Path, repo: Option<&std::path::Path>, format: &OutputFormat,
-    cache_file: Option<&std::path::Path>,
+    cache_file: Option<&std::path::Path>, raw: bool, explain: bool, expand: bool, no_cache: bool,
 ) -> Result<()> {
+    let ctx = Context::fetch()?;
+
     // Repository path
     let repo = repo.unwrap_or_else(|| std::path::Path::new("."));
 
-    // Load the cache
-    let cache = sync_cache(repo, cache_file)?;
+    // Fail before building the cache if members were asked for but cannot be found
+    let team_map = if expand {
+        Some(TeamMap::for_repo(&ctx, repo)?)
+    } else {
+        None
+    };
 
     // Normalize the file path to be relative to the repo
     let normalized_file_path = if file_path.is_absolute() {
@@ -34,11 +53,23 @@ pub fn run(
         file_path.to_path_buf()
     };
 
-    // Find the file in the cache
+    // Load the cache, or resolve just this file when there is none to wait for
+    let cache = if no_cache {
+        resolve_paths(&ctx, repo, std::slice::from_ref(&normalized_file_path))?
+    } else {
+        // A SQLite cache reads just this file
+        let paths = [
+            normalized_file_path.clone(),
+            repo.join(&normalized_file_path),
+        ];
+        sync_cache_for(&ctx, repo, cache_file, CacheQuery::Paths(&paths))?
+    };
+    let options = ctx.config.match_options();
+
+    // Find the file in the cache, as given or relative to the repo
     let file_entry = cache
-        .files
-        .iter()
-        .find(|file| file.path == normalized_file_path)
+        .file(&normalized_file_path)
+        .or_else(|| cache.file(&repo.join(&normalized_file_path)))
         .ok_or_else(|| {
             Error::new(&format!(
                 "File {} not found in cache",
@@ -46,50 +77,84 @@ pub fn run(
             ))
         })?;
 
-    // Find the CODEOWNERS entries that match this file
-    let matching_entries: Vec<&CodeownersEntry> = cache
-        .entries
+    // Find the CODEOWNERS entries that match this file, highest precedence under the
+    // cache's policy first; the override matcher reports every match, including
+    // overridden ones
+    let policy = cache.precedence;
+    let matcher = Matcher::build_with_options(&cache.entries, MatcherStrategy::Override, options);
+    let matching_entries: Vec<&CodeownersEntry> =
+        find_matching_entries(&file_entry.path, &cache.entries, &matcher, policy)?
+            .into_iter()
+            .map(|index| &cache.entries[index])
+            .collect();
+
+    // The rules that decided ownership when the cache was built; several when merging
+    let applied: Vec<&CodeownersEntry> = cache
+        .rule_matches
         .iter()
-        .filter(|entry| {
-            // Simple pattern matching - in a real implementation you'd want proper glob matching
-            let pattern = &entry.pattern;
-            let file_str = normalized_file_path.to_string_lossy();
-
-            if pattern.ends_with("*") {
-                let prefix = &pattern[..pattern.len() - 1];
-                file_str.starts_with(prefix)
-            } else if pattern.starts_with("*") {
-                let suffix = &pattern[1..];
-                file_str.ends_with(suffix)
-            } else if pattern.contains('*') {
-                // Basic wildcard matching - could be improved
-                let parts: Vec<&str> = pattern.split('*').collect();
-                if parts.len() == 2 {
-                    file_str.starts_with(parts[0]) && file_str.ends_with(parts[1])
-                } else {
-                    file_str == *pattern || file_str.starts_with(&format!("{}/", pattern))
-                }
-            } else {
-                file_str == *pattern || file_str.starts_with(&format!("{}/", pattern))
-            }
+        .enumerate()
+        .filter(|(_, files)| {
+            files
+                .iter()
+                .any(|&file| std::ptr::eq(&cache.files[file], file_entry))
         })
+        .map(|(index, _)| &cache.entries[index])
+        .collect();
+    let is_applied = |entry: &CodeownersEntry| applied.iter().any(|a| std::ptr::eq(*a, entry));
+
+    // Without --raw or --explain only the rules that applied are shown
+    let show_all = raw || explain;
+    let shown_entries: Vec<&CodeownersEntry> = matching_entries
+        .into_iter()
+        .filter(|entry| show_all || is_applied(entry))
         .collect();
 
     // Create inspection result
-    let inspection_result = serde_json::json!({
+    let mut inspection_result = serde_json::json!({
         "file_path": normalized_file_path.to_string_lossy(),
         "owners": file_entry.owners,
         "tags": file_entry.tags.iter().map(|t| &t.0).collect::<Vec<_>>(),
-        "matching_rules": matching_entries.iter().map(|entry| {
+        "precedence": policy,
+        "inline": file_entry.inline,
+        "matching_rules": shown_entries.iter().map(|entry| {
             serde_json::json!({
                 "source_file": entry.source_file.to_string_lossy(),
                 "line_number": entry.line_number,
                 "pattern": entry.pattern,
                 "owners": entry.owners,
-                "tags": entry.tags.iter().map(|t| &t.0).collect::<Vec<_>>()
+                "tags": entry.tags.iter().map(|t| &t.0).collect::<Vec<_>>(),
+                "metadata": entry.metadata,
+                "section": entry.section,
+                "applied": is_applied(entry)
             })
         }).collect::<Vec<_>>()
     });
+    if let Some(team_map) = &team_map {
+        inspection_result["members"] = file_entry
+            .owners
+            .iter()
+            .filter_map(|owner| {
+                let members = team_map.members(&owner.identifier)?;
+                Some((owner.identifier.clone(), serde_json::json!(members)))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    if explain {
+        inspection_result["explain"] = serde_json::json!({
+            "case_insensitive": options.case_insensitive,
+            "precedence": policy.description(),
+            "rules": shown_entries.iter().map(|entry| {
+                serde_json::json!({
+                    "source_file": entry.source_file.to_string_lossy(),
+                    "line_number": entry.line_number,
+                    "pattern": entry.pattern,
+                    "normalized_pattern": normalize_codeowners_pattern(&entry.pattern),
+                    "applied": is_applied(entry)
+                })
+            }).collect::<Vec<_>>()
+        });
+    }
 
     // Output the inspection result in the requested format
     match format {
@@ -107,6 +172,10 @@ pub fn run(
             } else {
                 for owner in &file_entry.owners {
                     println!("  - {}", owner.identifier);
+                    let members = team_map.as_ref().and_then(|m| m.members(&owner.identifier));
+                    for member in members.unwrap_or_default() {
+                        println!("      - {}", member);
+                    }
                 }
             }
 
@@ -119,15 +188,32 @@ pub fn run(
                 }
             }
 
-            println!("\nMatching CODEOWNERS Rules:");
-            if matching_entries.is_empty() {
+            println!("\nPrecedence Policy: {}", policy);
+            if let Some(inline) = file_entry.inline {
+                println!(
+                    "Inline Declaration: {} on line {}, overriding every rule",
+                    inline.source, inline.line
+                );
+            }
+
+            if raw {
+                println!("\nMatching CODEOWNERS Rules (highest precedence first, as written):");
+            } else {
+                println!("\nMatching CODEOWNERS Rules:");
+            }
+            if shown_entries.is_empty() {
                 println!("  (no explicit rules)");
             } else {
-                for entry in matching_entries {
+                for entry in &shown_entries {
                     println!(
-                        "\n  From {}:{}",
+                        "\n  From {}:{}{}",
                         entry.source_file.display(),
-                        entry.line_number
+                        entry.line_number,
+                        if show_all && !is_applied(entry) {
+                            " (overridden)"
+                        } else {
+                            ""
+                        }
                     );
                     println!("    Pattern: {}", entry.pattern);
                     let owners_str = entry
@@ -148,6 +234,61 @@ pub fn run(
                                 .join(", ")
                         );
                     }
+                    if let Some(section) = &entry.section {
+                        println!(
+                            "    Section: {}{}",
+                            section,
+                            if section.optional { " (optional)" } else { "" }
+                        );
+                    }
+                    if !entry.metadata.is_empty() {
+                        println!(
+                            "    Annotations: {}",
+                            entry
+                                .metadata
+                                .iter()
+                                .map(|(key, value)| format!("{}:{}", key, value))
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        );
+                    }
+                }
+            }
+
+            if explain {
+                println!("\nExplanation:");
+                if options.case_insensitive {
+                    println!("  Matching:   case-insensitive (case_insensitive = true)");
+                } else {
+                    println!(
+                        "  Matching:   case-sensitive, as on GitHub (set case_insensitive = true to ignore case)"
+                    );
+                }
+                println!("  Precedence: {} ({})", policy, policy.description());
+                for entry in &shown_entries {
+                    let normalized = normalize_codeowners_pattern(&entry.pattern);
+                    // When merging, a rule can only be overridden from its own file
+                    let overriding = applied
+                        .iter()
+                        .find(|a| a.source_file == entry.source_file)
+                        .or(applied.first());
+                    let decision = match overriding {
+                        Some(a) if std::ptr::eq(*a, *entry) => "applied".to_string(),
+                        Some(a) => format!(
+                            "overridden by {}:{}",
+                            a.source_file.display(),
+                            a.line_number
+                        ),
+                        None => "not applied".to_string(),
+                    };
+                    println!(
+                        "  - {}:{} '{}' matched as '{}': {}",
+                        entry.source_file.display(),
+                        entry.line_number,
+                        entry.pattern,
+                        normalized,
+                        decision
+                    );
                 }
             }
             println!();
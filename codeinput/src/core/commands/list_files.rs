@@ -1,14 +1,37 @@
 use crate::{
     core::{
-        cache::sync_cache,
+        cache::{sync_cache, sync_cache_for, CacheQuery},
+        context::Context,
         display::{truncate_path, truncate_string},
-        types::OutputFormat,
+        parse::resolve_paths,
+        team_map::TeamMap,
+        types::{FileEntry, OutputFormat},
+    },
+    utils::{
+        error::{Error, Result},
+        summary,
+        warnings::{self, WarningKind},
     },
-    utils::error::{Error, Result},
 };
+use std::collections::BTreeMap;
 use std::io::{self, Write};
 use tabled::{Table, Tabled};
 
+/// Group of files without an owner when grouping by owner
+const UNOWNED_GROUP: &str = "(unowned)";
+
+/// Group of files without a tag when grouping by tag
+const UNTAGGED_GROUP: &str = "(untagged)";
+
+/// What `list-files` groups files by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Owner,
+    Tag,
+    /// Parent directory, relative to the repository
+    Dir,
+}
+
 #[derive(Tabled)]
 struct FileDisplay {
     #[tabled(rename = "File Path")]
@@ -20,20 +43,97 @@ struct FileDisplay {
 }
 
 /// Find and list files with their owners based on filter criteria
+///
+/// With `expand`, team owners are listed with their members from the team mapping. With
+/// `paths_from`, only the paths listed one per line in that file (`-` for stdin) are
+/// considered, relative to the repository. With `no_cache`, only the files under
+/// `path_prefix` (or the explicit paths) are resolved on the fly, without reading or
+/// building the cache. With `exclude_generated`, files carrying a generated-code marker
+/// are left out. With `group_by`, files are listed under each of their owners, tags or
+/// their directory instead of in one table.
+#[allow(clippy::too_many_arguments)]
 pub fn run(
-    repo: Option<&std::path::Path>, tags: Option<&str>, owners: Option<&str>, unowned: bool,
-    show_all: bool, format: &OutputFormat, cache_file: Option<&std::path::Path>,
+    repo: Option<&std::path::Path>, path_prefix: Option<&std::path::Path>, tags: Option<&str>,
+    owners: Option<&str>, unowned: bool, show_all: bool, format: &OutputFormat,
+    cache_file: Option<&std::path::Path>, expand: bool, paths_from: Option<&std::path::Path>,
+    no_cache: bool, exclude_generated: bool, group_by: Option<GroupBy>,
 ) -> Result<()> {
+    let ctx = Context::fetch()?;
     // Repository path
     let repo = repo.unwrap_or_else(|| std::path::Path::new("."));
 
-    // Load the cache
-    let cache = sync_cache(repo, cache_file)?;
+    // Fail before building the cache if members were asked for but cannot be found
+    let team_map = if expand {
+        Some(TeamMap::for_repo(&ctx, repo)?)
+    } else {
+        None
+    };
+
+    // Read the explicit paths before building the cache, so bad input fails fast
+    let explicit_paths = paths_from.map(read_paths).transpose()?;
+
+    // Load the cache, or resolve just the requested files when there is none to wait for
+    let cache = if no_cache {
+        let scope = match (&explicit_paths, path_prefix) {
+            // Listed paths that do not exist are skipped below, as with the cache
+            (Some(paths), _) => paths
+                .iter()
+                .filter(|path| repo.join(path).is_file())
+                .cloned()
+                .collect(),
+            (None, Some(prefix)) => vec![prefix.to_path_buf()],
+            (None, None) => vec![std::path::PathBuf::from(".")],
+        };
+        resolve_paths(&ctx, repo, &scope)?
+    } else if let Some(owner_filter) = owners {
+        // A SQLite cache reads just the files of these owners
+        let owner_patterns: Vec<&str> = owner_filter.split(',').collect();
+        sync_cache_for(&ctx, repo, cache_file, CacheQuery::Owners(&owner_patterns))?
+    } else {
+        sync_cache(&ctx, repo, cache_file)?
+    };
+
+    // Narrow to a directory through the path index instead of scanning every file
+    let mut candidate_files = match path_prefix {
+        Some(prefix) => cache.files_under(&repo.join(prefix)),
+        None => cache.files.iter().collect(),
+    };
+
+    // Restrict to the explicit paths, in the order given
+    if let Some(paths) = explicit_paths {
+        let in_scope: std::collections::HashSet<*const FileEntry> = candidate_files
+            .iter()
+            .map(|file| *file as *const _)
+            .collect();
+        let mut seen = std::collections::HashSet::new();
+        let mut missing = 0;
+        let mut selected: Vec<&FileEntry> = Vec::new();
+        for path in &paths {
+            let Some(file) = cache.file(path).or_else(|| cache.file(&repo.join(path))) else {
+                missing += 1;
+                continue;
+            };
+            let key = file as *const FileEntry;
+            if in_scope.contains(&key) && seen.insert(key) {
+                selected.push(file);
+            }
+        }
+        if missing > 0 {
+            warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "{} of {} listed paths are not in the cache and were skipped",
+                    missing,
+                    paths.len()
+                ),
+            );
+        }
+        candidate_files = selected;
+    }
 
     // Filter files based on criteria
-    let filtered_files = cache
-        .files
-        .iter()
+    let filtered_files = candidate_files
+        .into_iter()
         .filter(|file| {
             // Check if we should include this file based on filters
             let passes_owner_filter = match owners {
@@ -75,9 +175,23 @@ pub fn run(
                 && passes_tag_filter
                 && passes_unowned_filter
                 && passes_ownership_requirement
+                && !(exclude_generated && file.generated)
         })
         .collect::<Vec<_>>();
 
+    summary::note("listed", filtered_files.len() as u64);
+
+    if let Some(group_by) = group_by {
+        let groups = group_files(repo, &filtered_files, group_by);
+        return output_groups(
+            &groups,
+            filtered_files.len(),
+            group_by,
+            team_map.as_ref(),
+            format,
+        );
+    }
+
     // Output the filtered files in the requested format
     match format {
         OutputFormat::Text => {
@@ -92,7 +206,14 @@ pub fn run(
                     } else {
                         file.owners
                             .iter()
-                            .map(|o| o.identifier.clone())
+                            .map(|o| {
+                                match team_map.as_ref().and_then(|m| m.members(&o.identifier)) {
+                                    Some(members) => {
+                                        format!("{} ({})", o.identifier, members.join(", "))
+                                    }
+                                    None => o.identifier.clone(),
+                                }
+                            })
                             .collect::<Vec<_>>()
                             .join(", ")
                     };
@@ -134,13 +255,22 @@ pub fn run(
             println!("{}", table);
             println!("Total: {} files", filtered_files.len());
         }
-        OutputFormat::Json => {
-            println!("{}", serde_json::to_string_pretty(&filtered_files).unwrap());
-        }
+        OutputFormat::Json => match &team_map {
+            Some(team_map) => {
+                let expanded = expanded_files(&filtered_files, team_map);
+                println!("{}", serde_json::to_string_pretty(&expanded).unwrap());
+            }
+            None => println!("{}", serde_json::to_string_pretty(&filtered_files).unwrap()),
+        },
         OutputFormat::Bincode => {
-            let encoded =
-                bincode::serde::encode_to_vec(&filtered_files, bincode::config::standard())
-                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+            let encoded = match &team_map {
+                Some(team_map) => bincode::serde::encode_to_vec(
+                    expanded_files(&filtered_files, team_map),
+                    bincode::config::standard(),
+                ),
+                None => bincode::serde::encode_to_vec(&filtered_files, bincode::config::standard()),
+            }
+            .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
 
             // Write raw binary bytes to stdout
             io::stdout()
@@ -151,3 +281,149 @@ pub fn run(
 
     Ok(())
 }
+
+/// `files` under each of their owners, each of their tags or their parent directory
+/// relative to `repo`
+fn group_files<'a>(
+    repo: &std::path::Path, files: &[&'a FileEntry], group_by: GroupBy,
+) -> BTreeMap<String, Vec<&'a FileEntry>> {
+    let mut groups: BTreeMap<String, Vec<&FileEntry>> = BTreeMap::new();
+    for &file in files {
+        let keys: Vec<String> = match group_by {
+            GroupBy::Owner if file.owners.is_empty() => vec![UNOWNED_GROUP.to_string()],
+            GroupBy::Owner => file.owners.iter().map(|o| o.identifier.clone()).collect(),
+            GroupBy::Tag if file.tags.is_empty() => vec![UNTAGGED_GROUP.to_string()],
+            GroupBy::Tag => file.tags.iter().map(|t| t.0.clone()).collect(),
+            GroupBy::Dir => {
+                let path = file.path.strip_prefix(repo).unwrap_or(&file.path);
+                let dir = path
+                    .parent()
+                    .map(|dir| dir.to_string_lossy().into_owned())
+                    .filter(|dir| !dir.is_empty())
+                    .unwrap_or_else(|| ".".to_string());
+                vec![dir]
+            }
+        };
+        for key in keys {
+            groups.entry(key).or_default().push(file);
+        }
+    }
+    groups
+}
+
+/// Print `groups` of the `total` files listed, as a text tree or an object keyed by group
+fn output_groups(
+    groups: &BTreeMap<String, Vec<&FileEntry>>, total: usize, group_by: GroupBy,
+    team_map: Option<&TeamMap>, format: &OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for (key, files) in groups {
+                let heading = match team_map.and_then(|m| m.members(key)) {
+                    Some(members) if group_by == GroupBy::Owner => {
+                        format!("{} ({})", key, members.join(", "))
+                    }
+                    _ => key.clone(),
+                };
+                let count = match files.len() {
+                    1 => "1 file".to_string(),
+                    n => format!("{} files", n),
+                };
+                println!("{} ({})", heading, count);
+                for (position, file) in files.iter().enumerate() {
+                    let branch = if position + 1 == files.len() {
+                        "└──"
+                    } else {
+                        "├──"
+                    };
+                    // The owners of a file grouped by owner are in the heading, so show its
+                    // tags instead
+                    let details: Vec<String> = match group_by {
+                        GroupBy::Owner => file.tags.iter().map(|t| format!("#{}", t.0)).collect(),
+                        GroupBy::Tag | GroupBy::Dir => {
+                            file.owners.iter().map(|o| o.identifier.clone()).collect()
+                        }
+                    };
+                    if details.is_empty() {
+                        println!("{} {}", branch, file.path.display());
+                    } else {
+                        println!("{} {}  {}", branch, file.path.display(), details.join(" "));
+                    }
+                }
+            }
+            println!("Total: {} files in {} groups", total, groups.len());
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report: serde_json::Map<String, serde_json::Value> = groups
+                .iter()
+                .map(|(key, files)| {
+                    let files = match team_map {
+                        Some(team_map) => serde_json::json!(expanded_files(files, team_map)),
+                        None => serde_json::json!(files),
+                    };
+                    (key.clone(), files)
+                })
+                .collect();
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// File entries with the members of each team owner, for structured output
+fn expanded_files(files: &[&FileEntry], team_map: &TeamMap) -> Vec<serde_json::Value> {
+    files
+        .iter()
+        .map(|file| {
+            let members: serde_json::Map<String, serde_json::Value> = file
+                .owners
+                .iter()
+                .filter_map(|owner| {
+                    let members = team_map.members(&owner.identifier)?;
+                    Some((owner.identifier.clone(), serde_json::json!(members)))
+                })
+                .collect();
+            serde_json::json!({
+                "path": file.path,
+                "owners": file.owners,
+                "tags": file.tags,
+                "members": members,
+            })
+        })
+        .collect()
+}
+
+/// Read paths one per line from `source`, or from stdin when it is `-`
+pub(crate) fn read_paths(source: &std::path::Path) -> Result<Vec<std::path::PathBuf>> {
+    let contents = if source == std::path::Path::new("-") {
+        io::read_to_string(io::stdin())
+            .map_err(|e| Error::new(&format!("Failed to read paths from stdin: {}", e)))?
+    } else {
+        std::fs::read_to_string(source).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read paths from {}: {}",
+                source.display(),
+                e
+            ))
+        })?
+    };
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(std::path::PathBuf::from)
+        .collect())
+}
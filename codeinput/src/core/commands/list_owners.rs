@@ -1,5 +1,12 @@
 use crate::{
-    core::{cache::sync_cache, display::truncate_string, types::OutputFormat},
+    core::{
+        cache::sync_scoped_cache,
+        context::Context,
+        display::truncate_string,
+        identity::{mixed_identities, type_breakdown, MixedIdentity, TypeStats},
+        team_map::{RolledUpOwner, TeamMap},
+        types::OutputFormat,
+    },
     utils::error::{Error, Result},
 };
 use std::io::{self, Write};
@@ -17,19 +24,72 @@ struct OwnerDisplay {
     sample_files: String,
 }
 
+#[derive(Tabled)]
+struct RollupDisplay {
+    #[tabled(rename = "Owner")]
+    identifier: String,
+    #[tabled(rename = "Type")]
+    owner_type: String,
+    #[tabled(rename = "Files")]
+    file_count: usize,
+    #[tabled(rename = "Rolled Up Members")]
+    members: String,
+}
+
+#[derive(Tabled)]
+struct TypeDisplay {
+    #[tabled(rename = "Type")]
+    owner_type: String,
+    #[tabled(rename = "Owners")]
+    owners: usize,
+    #[tabled(rename = "Files")]
+    files: usize,
+}
+
 /// Display aggregated owner statistics and associations
+///
+/// With `rollup`, individual owners are aggregated into the teams that list them in the
+/// team mapping. With `by_type`, owners are counted per owner type instead, and email
+/// addresses that probably belong to a listed handle are flagged.
+///
+/// With `scope`, only the subtree at that path is counted, see [`sync_scoped_cache`].
 pub fn run(
     repo: Option<&std::path::Path>, format: &OutputFormat, cache_file: Option<&std::path::Path>,
+    rollup: bool, by_type: bool, scope: Option<&std::path::Path>,
 ) -> Result<()> {
+    let ctx = Context::fetch()?;
     // Repository path
     let repo = repo.unwrap_or_else(|| std::path::Path::new("."));
 
+    // Fail before building the cache if teams were asked for but cannot be found
+    let team_map = if rollup {
+        Some(TeamMap::for_repo(&ctx, repo)?)
+    } else {
+        None
+    };
+
     // Load the cache
-    let cache = sync_cache(repo, cache_file)?;
+    let cache = sync_scoped_cache(&ctx, repo, cache_file, scope)?;
+
+    if let Some(team_map) = team_map {
+        return output_rollup(&team_map.rollup(&cache.owners_map), format);
+    }
+    if by_type {
+        return output_by_type(
+            &type_breakdown(&cache.owners_map),
+            &mixed_identities(cache.owners_map.keys()),
+            format,
+        );
+    }
 
     // Sort owners by number of files they own (descending)
     let mut owners_with_counts: Vec<_> = cache.owners_map.iter().collect();
-    owners_with_counts.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    owners_with_counts.sort_by(|(a, a_paths), (b, b_paths)| {
+        b_paths
+            .len()
+            .cmp(&a_paths.len())
+            .then_with(|| a.identifier.cmp(&b.identifier))
+    });
 
     // Process the owners from the cache
     match format {
@@ -117,3 +177,124 @@ pub fn run(
 
     Ok(())
 }
+
+fn output_by_type(
+    types: &[TypeStats], mixed: &[MixedIdentity], format: &OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<TypeDisplay> = types
+                .iter()
+                .map(|stats| TypeDisplay {
+                    owner_type: stats.owner_type.to_string(),
+                    owners: stats.owners,
+                    files: stats.files,
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+
+            println!("{}", table);
+            println!(
+                "Total: {} owners",
+                types.iter().map(|stats| stats.owners).sum::<usize>()
+            );
+
+            if !mixed.is_empty() {
+                println!("\nOwners named by both email and handle:");
+                for identity in mixed {
+                    println!("  {} and {}", identity.email, identity.handle);
+                }
+                println!("\nSuggested aliases:");
+                for identity in mixed {
+                    println!("  {}", identity.alias());
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "types": types.iter().map(|stats| {
+                    serde_json::json!({
+                        "type": stats.owner_type.to_string(),
+                        "owners": stats.owners,
+                        "files": stats.files,
+                    })
+                }).collect::<Vec<_>>(),
+                "mixed_identities": mixed.iter().map(|identity| {
+                    serde_json::json!({
+                        "email": identity.email,
+                        "handle": identity.handle,
+                        "alias": identity.alias(),
+                    })
+                }).collect::<Vec<_>>(),
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn output_rollup(owners: &[RolledUpOwner], format: &OutputFormat) -> Result<()> {
+    let owners_data: Vec<_> = owners
+        .iter()
+        .map(|rolled| {
+            serde_json::json!({
+                "identifier": rolled.owner.identifier,
+                "type": format!("{:?}", rolled.owner.owner_type),
+                "file_count": rolled.files.len(),
+                "rolled_up_members": rolled.members,
+                "files": rolled.files.iter().map(|p| p.to_string_lossy().to_string()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<RollupDisplay> = owners
+                .iter()
+                .map(|rolled| RollupDisplay {
+                    identifier: truncate_string(&rolled.owner.identifier, 35),
+                    owner_type: format!("{:?}", rolled.owner.owner_type),
+                    file_count: rolled.files.len(),
+                    members: if rolled.members.is_empty() {
+                        "-".to_string()
+                    } else {
+                        truncate_string(&rolled.members.join(", "), 45)
+                    },
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+
+            println!("{}", table);
+            println!("Total: {} owners after rollup", owners.len());
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&owners_data).unwrap());
+        }
+        OutputFormat::Bincode => {
+            let encoded = bincode::serde::encode_to_vec(&owners_data, bincode::config::standard())
+                .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+            io::stdout()
+                .write_all(&encoded)
+                .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+        }
+    }
+
+    Ok(())
+}
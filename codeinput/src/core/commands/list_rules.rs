@@ -1,8 +1,17 @@
 use crate::{
-    core::{cache::sync_cache, display::truncate_string, types::OutputFormat},
+    core::{
+        cache::sync_scoped_cache,
+        common::last_commit_touching,
+        context::Context,
+        display::truncate_string,
+        types::{CodeownersCache, CodeownersEntry, OutputFormat},
+    },
     utils::error::{Error, Result},
 };
+use git2::Repository;
+use std::collections::{BTreeMap, HashSet};
 use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
 use tabled::{Table, Tabled};
 
 #[derive(Tabled)]
@@ -13,16 +22,69 @@ struct RuleDisplay {
     source: String,
     #[tabled(rename = "Line")]
     line_number: usize,
+    #[tabled(rename = "Section")]
+    section: String,
     #[tabled(rename = "Owners")]
     owners: String,
     #[tabled(rename = "Tags")]
     tags: String,
+    #[tabled(rename = "Annotations")]
+    annotations: String,
+    #[tabled(rename = "Files")]
+    files: usize,
 }
 
-/// Display CODEOWNERS rules from the cache
-pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Result<()> {
+#[derive(Tabled)]
+struct SourceDisplay {
+    #[tabled(rename = "Source")]
+    source: String,
+    #[tabled(rename = "Rules")]
+    rules: usize,
+    #[tabled(rename = "Matched Files")]
+    matched_files: usize,
+    #[tabled(rename = "Dead Rules")]
+    dead_rules: usize,
+    #[tabled(rename = "Last Modified")]
+    last_modified: String,
+}
+
+/// Rule statistics for one CODEOWNERS file
+struct SourceStats<'a> {
+    source: &'a Path,
+    rules: Vec<usize>,
+    matched_files: usize,
+    /// Rules that decide ownership of no file
+    dead_rules: Vec<usize>,
+    last_commit: Option<LastCommit>,
+}
+
+struct LastCommit {
+    id: String,
+    author: String,
+    /// Commit date as YYYY-MM-DD
+    date: String,
+    summary: String,
+}
+
+/// Display CODEOWNERS rules from the cache of the repository at `repo`
+///
+/// With `with_matches`, each rule also lists the files it decides ownership for, read
+/// from the cache's rule index. With `by_source`, rules are summarized per CODEOWNERS
+/// file instead.
+///
+/// With `scope`, only the rules that can apply in that subtree are shown and matches are
+/// limited to it, see [`sync_scoped_cache`].
+pub fn run(
+    repo: &Path, format: &OutputFormat, cache_file: Option<&Path>, with_matches: bool,
+    by_source: bool, scope: Option<&Path>,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
     // Load the cache
-    let cache = sync_cache(std::path::Path::new("."), cache_file)?;
+    let cache = sync_scoped_cache(&ctx, repo, cache_file, scope)?;
+
+    if by_source {
+        return output_by_source(&cache, &source_stats(repo, &cache), format);
+    }
 
     // Process the rules from the cache
     match format {
@@ -31,7 +93,8 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
             let table_data: Vec<RuleDisplay> = cache
                 .entries
                 .iter()
-                .map(|entry| {
+                .enumerate()
+                .map(|(index, entry)| {
                     // Format owners list
                     let owners_display = if entry.owners.is_empty() {
                         "None".to_string()
@@ -67,8 +130,14 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
                         pattern: truncate_string(&entry.pattern, 40),
                         source: truncate_string(&source_display, 20),
                         line_number: entry.line_number,
+                        section: entry.section.as_ref().map_or_else(
+                            || "None".to_string(),
+                            |s| truncate_string(&s.to_string(), 25),
+                        ),
                         owners: truncate_string(&owners_display, 30),
                         tags: truncate_string(&tags_display, 25),
+                        annotations: truncate_string(&annotations_display(entry), 40),
+                        files: cache.rule_matches.get(index).map_or(0, Vec::len),
                     }
                 })
                 .collect();
@@ -82,6 +151,21 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
                 };
 
             let mut table = Table::new(table_data);
+            if !with_matches {
+                table.with(tabled::settings::Remove::column(
+                    tabled::settings::location::ByColumnName::new("Files"),
+                ));
+            }
+            if cache.entries.iter().all(|entry| entry.section.is_none()) {
+                table.with(tabled::settings::Remove::column(
+                    tabled::settings::location::ByColumnName::new("Section"),
+                ));
+            }
+            if cache.entries.iter().all(|entry| entry.metadata.is_empty()) {
+                table.with(tabled::settings::Remove::column(
+                    tabled::settings::location::ByColumnName::new("Annotations"),
+                ));
+            }
             table
                 .with(tabled::settings::Style::modern())
                 .with(tabled::settings::Width::wrap(
@@ -91,14 +175,19 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
 
             println!("{}", table);
             println!("Total: {} rules", cache.entries.len());
+            if with_matches {
+                let unmatched = cache.rule_matches.iter().filter(|m| m.is_empty()).count();
+                println!("Rules matching no files: {}", unmatched);
+            }
         }
         OutputFormat::Json => {
             // Convert to a more friendly JSON structure
             let rules_data: Vec<_> = cache
                 .entries
                 .iter()
-                .map(|entry| {
-                    serde_json::json!({
+                .enumerate()
+                .map(|(index, entry)| {
+                    let mut rule = serde_json::json!({
                         "pattern": entry.pattern,
                         "source_file": entry.source_file.to_string_lossy().to_string(),
                         "line_number": entry.line_number,
@@ -109,16 +198,46 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
                             })
                         }).collect::<Vec<_>>(),
                         "tags": entry.tags.iter().map(|t| &t.0).collect::<Vec<_>>()
-                    })
+                    });
+                    if !entry.metadata.is_empty() {
+                        rule["metadata"] = serde_json::json!(entry.metadata);
+                    }
+                    if let Some(section) = &entry.section {
+                        rule["section"] = serde_json::json!(section);
+                    }
+                    if with_matches {
+                        rule["matched_files"] = cache
+                            .files_for_rule(index)
+                            .iter()
+                            .map(|file| file.path.to_string_lossy().to_string())
+                            .collect();
+                    }
+                    rule
                 })
                 .collect();
 
             println!("{}", serde_json::to_string_pretty(&rules_data).unwrap());
         }
         OutputFormat::Bincode => {
-            let encoded =
+            let encoded = if with_matches {
+                let rules: Vec<_> = cache
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        let paths: Vec<_> = cache
+                            .files_for_rule(index)
+                            .iter()
+                            .map(|file| &file.path)
+                            .collect();
+                        (entry, paths)
+                    })
+                    .collect();
+                bincode::serde::encode_to_vec(&rules, bincode::config::standard())
+            } else {
                 bincode::serde::encode_to_vec(&cache.entries, bincode::config::standard())
-                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+            }
+            .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
 
             // Write raw binary bytes to stdout
             io::stdout()
@@ -129,3 +248,150 @@ pub fn run(format: &OutputFormat, cache_file: Option<&std::path::Path>) -> Resul
 
     Ok(())
 }
+
+/// Group the cached rules by CODEOWNERS file, sorted by path
+fn source_stats<'a>(repo: &Path, cache: &'a CodeownersCache) -> Vec<SourceStats<'a>> {
+    let mut rules: BTreeMap<&Path, Vec<usize>> = BTreeMap::new();
+    for (index, entry) in cache.entries.iter().enumerate() {
+        rules
+            .entry(entry.source_file.as_path())
+            .or_default()
+            .push(index);
+    }
+
+    // Outside a git repository there is no history to report
+    let repo = Repository::discover(repo).ok();
+
+    rules
+        .into_iter()
+        .map(|(source, rules)| {
+            let matched: HashSet<usize> = rules
+                .iter()
+                .filter_map(|&index| cache.rule_matches.get(index))
+                .flatten()
+                .copied()
+                .collect();
+            let dead_rules = rules
+                .iter()
+                .copied()
+                .filter(|&index| cache.rule_matches.get(index).is_none_or(Vec::is_empty))
+                .collect();
+            let last_commit = repo.as_ref().and_then(|repo| last_commit_for(repo, source));
+
+            SourceStats {
+                source,
+                rules,
+                matched_files: matched.len(),
+                dead_rules,
+                last_commit,
+            }
+        })
+        .collect()
+}
+
+/// The last commit that changed `source`, or `None` if it is untracked or unreadable
+fn last_commit_for(repo: &Repository, source: &Path) -> Option<LastCommit> {
+    let workdir = repo.workdir()?;
+    let absolute = std::fs::canonicalize(source).ok()?;
+    let relative: PathBuf = absolute
+        .strip_prefix(std::fs::canonicalize(workdir).ok()?)
+        .ok()?
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect();
+
+    let commit = last_commit_touching(repo, &relative).ok()??;
+    let date = chrono::DateTime::from_timestamp(commit.time().seconds(), 0)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_default();
+    let author = commit.author().name().unwrap_or("unknown").to_string();
+    Some(LastCommit {
+        id: commit.id().to_string(),
+        author,
+        date,
+        summary: commit.summary().unwrap_or_default().to_string(),
+    })
+}
+
+fn output_by_source(
+    cache: &CodeownersCache, stats: &[SourceStats], format: &OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<SourceDisplay> = stats
+                .iter()
+                .map(|stats| SourceDisplay {
+                    source: truncate_string(&stats.source.display().to_string(), 40),
+                    rules: stats.rules.len(),
+                    matched_files: stats.matched_files,
+                    dead_rules: stats.dead_rules.len(),
+                    last_modified: match &stats.last_commit {
+                        Some(commit) => truncate_string(
+                            &format!("{} {} {}", &commit.id[..7], commit.date, commit.author),
+                            40,
+                        ),
+                        None => "Not committed".to_string(),
+                    },
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+
+            println!("{}", table);
+            println!("Total: {} CODEOWNERS files", stats.len());
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let sources_data: Vec<_> = stats
+                .iter()
+                .map(|stats| {
+                    serde_json::json!({
+                        "source_file": stats.source.to_string_lossy(),
+                        "rules": stats.rules.len(),
+                        "matched_files": stats.matched_files,
+                        "dead_rules": stats.dead_rules.iter().map(|&index| {
+                            let entry = &cache.entries[index];
+                            serde_json::json!({
+                                "pattern": entry.pattern,
+                                "line_number": entry.line_number,
+                            })
+                        }).collect::<Vec<_>>(),
+                        "last_commit": stats.last_commit.as_ref().map(|commit| {
+                            serde_json::json!({
+                                "id": commit.id,
+                                "author": commit.author,
+                                "date": commit.date,
+                                "summary": commit.summary,
+                            })
+                        }),
+                    })
+                })
+                .collect();
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&sources_data).unwrap());
+            } else {
+                let encoded =
+                    bincode::serde::encode_to_vec(&sources_data, bincode::config::standard())
+                        .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Annotations of a rule as `key:value` pairs
+fn annotations_display(entry: &CodeownersEntry) -> String {
+    entry
+        .metadata
+        .iter()
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -1,5 +1,11 @@
 use crate::{
-    core::{cache::sync_cache, display::truncate_string, types::OutputFormat},
+    core::{
+        cache::sync_scoped_cache,
+        context::Context,
+        display::truncate_string,
+        tag_analysis::{co_occurrence, owner_correlation, TagOwner, TagPair},
+        types::OutputFormat,
+    },
     utils::error::{Error, Result},
 };
 use std::io::{self, Write};
@@ -15,19 +21,65 @@ struct TagDisplay {
     sample_files: String,
 }
 
+#[derive(Tabled)]
+struct PairDisplay {
+    #[tabled(rename = "Tags")]
+    tags: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Rules")]
+    rules: usize,
+    #[tabled(rename = "Overlap")]
+    overlap: String,
+}
+
+#[derive(Tabled)]
+struct TagOwnerDisplay {
+    #[tabled(rename = "Tag")]
+    tag: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Top Owner")]
+    owner: String,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+    #[tabled(rename = "Exclusivity")]
+    exclusivity: String,
+}
+
 /// Audit and analyze tag usage across CODEOWNERS files
+///
+/// With `co_occurrence`, report which tags appear together and the owner most associated
+/// with each tag instead.
+///
+/// With `scope`, only the subtree at that path is counted, see [`sync_scoped_cache`].
 pub fn run(
     repo: Option<&std::path::Path>, format: &OutputFormat, cache_file: Option<&std::path::Path>,
+    co_occurrence_report: bool, scope: Option<&std::path::Path>,
 ) -> Result<()> {
+    let ctx = Context::fetch()?;
     // Repository path
     let repo = repo.unwrap_or_else(|| std::path::Path::new("."));
 
     // Load the cache
-    let cache = sync_cache(repo, cache_file)?;
+    let cache = sync_scoped_cache(&ctx, repo, cache_file, scope)?;
+
+    if co_occurrence_report {
+        return output_co_occurrence(
+            &co_occurrence(&cache.files, &cache.entries),
+            &owner_correlation(&cache.files),
+            format,
+        );
+    }
 
     // Sort tags by number of files they're associated with (descending)
     let mut tags_with_counts: Vec<_> = cache.tags_map.iter().collect();
-    tags_with_counts.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+    tags_with_counts.sort_by(|(a, a_paths), (b, b_paths)| {
+        b_paths
+            .len()
+            .cmp(&a_paths.len())
+            .then_with(|| a.0.cmp(&b.0))
+    });
 
     // Process the tags from the cache
     match format {
@@ -112,3 +164,87 @@ pub fn run(
 
     Ok(())
 }
+
+fn output_co_occurrence(
+    pairs: &[TagPair], owners: &[TagOwner], format: &OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            if pairs.is_empty() {
+                println!("No tags appear together on a file or rule");
+            } else {
+                let table_data: Vec<PairDisplay> = pairs
+                    .iter()
+                    .map(|pair| PairDisplay {
+                        tags: truncate_string(&format!("{} + {}", pair.first.0, pair.second.0), 40),
+                        files: pair.files,
+                        rules: pair.rules,
+                        overlap: format!("{:.0}%", pair.jaccard * 100.0),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("Tags appearing together:");
+                println!("{}", table);
+            }
+
+            if !owners.is_empty() {
+                let table_data: Vec<TagOwnerDisplay> = owners
+                    .iter()
+                    .map(|owner| TagOwnerDisplay {
+                        tag: truncate_string(&owner.tag.0, 30),
+                        files: owner.tag_files,
+                        owner: truncate_string(&owner.owner.identifier, 30),
+                        coverage: format!("{:.0}%", owner.coverage * 100.0),
+                        exclusivity: format!("{:.0}%", owner.exclusivity * 100.0),
+                    })
+                    .collect();
+
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("\nOwner most associated with each tag:");
+                println!("{}", table);
+            }
+            println!(
+                "Overlap: files with both tags out of files with either. Coverage: the tag's files the owner owns. Exclusivity: the owner's files carrying the tag."
+            );
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "pairs": pairs.iter().map(|pair| {
+                    serde_json::json!({
+                        "tags": [pair.first.0, pair.second.0],
+                        "files": pair.files,
+                        "rules": pair.rules,
+                        "jaccard": pair.jaccard,
+                    })
+                }).collect::<Vec<_>>(),
+                "owners": owners.iter().map(|owner| {
+                    serde_json::json!({
+                        "tag": owner.tag.0,
+                        "tag_files": owner.tag_files,
+                        "owner": owner.owner.identifier,
+                        "files": owner.files,
+                        "coverage": owner.coverage,
+                        "exclusivity": owner.exclusivity,
+                    })
+                }).collect::<Vec<_>>(),
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
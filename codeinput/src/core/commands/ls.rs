@@ -0,0 +1,327 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        delegation::{find_delegations, Delegation},
+        display::truncate_string,
+        types::{FileEntry, OutputFormat, OwnerType},
+    },
+    utils::error::{Error, Result},
+};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct ChildDisplay {
+    #[tabled(rename = "Name")]
+    name: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+    #[tabled(rename = "Owners")]
+    owners: String,
+    #[tabled(rename = "Tags")]
+    tags: String,
+}
+
+/// An immediate child of the listed directory, with the files at or below it
+struct Child<'a> {
+    name: String,
+    is_dir: bool,
+    files: Vec<&'a FileEntry>,
+    /// Delegations of this directory
+    delegations: Vec<&'a Delegation>,
+}
+
+impl Child<'_> {
+    /// Files counted for coverage: generated files are left out
+    fn counted_files(&self) -> usize {
+        self.files.iter().filter(|file| !file.generated).count()
+    }
+
+    fn owned_files(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| !file.generated && is_owned(file))
+            .count()
+    }
+
+    fn coverage(&self) -> f64 {
+        match self.counted_files() {
+            0 => 0.0,
+            counted => self.owned_files() as f64 * 100.0 / counted as f64,
+        }
+    }
+
+    /// Owners with the number of files they own here, most files first
+    fn owners(&self) -> Vec<(&str, usize)> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for file in &self.files {
+            for owner in &file.owners {
+                if owner.owner_type != OwnerType::Unowned {
+                    *counts.entry(owner.identifier.as_str()).or_default() += 1;
+                }
+            }
+        }
+        let mut owners: Vec<_> = counts.into_iter().collect();
+        owners.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        owners
+    }
+
+    fn tags(&self) -> Vec<&str> {
+        let mut tags: Vec<&str> = self
+            .files
+            .iter()
+            .flat_map(|file| file.tags.iter().map(|tag| tag.0.as_str()))
+            .collect();
+        tags.sort_unstable();
+        tags.dedup();
+        tags
+    }
+}
+
+/// List the immediate children of a directory with their owners, tags and coverage
+///
+/// Directories summarize every file below them: the share of owned files and the owners
+/// covering the most files.
+pub fn run(
+    dir: &Path, repo: Option<&Path>, format: &OutputFormat, cache_file: Option<&Path>,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    // Repository path
+    let repo = repo.unwrap_or_else(|| Path::new("."));
+
+    // Load the cache
+    let cache = sync_cache(&ctx, repo, cache_file)?;
+
+    let dir_path = repo.join(dir);
+    let delegations = find_delegations(repo)?;
+    let mut children = group_children(&dir_path, cache.files_under(&dir_path));
+    for child in children.iter_mut().filter(|child| child.is_dir) {
+        let child_path = dir_path.join(&child.name);
+        child.delegations = delegations
+            .iter()
+            .filter(|delegation| delegation.is_for(&child_path))
+            .collect();
+    }
+    if children.is_empty() {
+        return Err(Error::new(&format!(
+            "No files found under {} in the cache",
+            dir.display()
+        )));
+    }
+
+    match format {
+        OutputFormat::Text => {
+            let table_data: Vec<ChildDisplay> = children
+                .iter()
+                .map(|child| {
+                    let owners = child.owners();
+                    let owners_str = if owners.is_empty() {
+                        "None".to_string()
+                    } else if child.is_dir {
+                        let mut shown: Vec<String> = owners
+                            .iter()
+                            .take(3)
+                            .map(|(owner, count)| format!("{} ({})", owner, count))
+                            .collect();
+                        if owners.len() > 3 {
+                            shown.push(format!("+{}", owners.len() - 3));
+                        }
+                        shown.join(", ")
+                    } else {
+                        owners
+                            .iter()
+                            .map(|(owner, _)| *owner)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    };
+                    let tags = child.tags();
+
+                    ChildDisplay {
+                        name: if child.is_dir {
+                            format!("{}/", child.name)
+                        } else {
+                            child.name.clone()
+                        },
+                        files: child.files.len(),
+                        coverage: format!("{:.0}%", child.coverage()),
+                        owners: truncate_string(&owners_str, 45),
+                        tags: if tags.is_empty() {
+                            "None".to_string()
+                        } else {
+                            truncate_string(&tags.join(", "), 30)
+                        },
+                    }
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+
+            println!("{}", table);
+            let total: usize = children.iter().map(|child| child.files.len()).sum();
+            let counted: usize = children.iter().map(|child| child.counted_files()).sum();
+            let owned: usize = children.iter().map(|child| child.owned_files()).sum();
+            println!(
+                "{}: {} files, {} owned ({:.0}%)",
+                dir.display(),
+                total,
+                owned,
+                owned as f64 * 100.0 / counted.max(1) as f64
+            );
+            if counted < total {
+                println!(
+                    "{} generated files are left out of coverage",
+                    total - counted
+                );
+            }
+            for child in &children {
+                for delegation in &child.delegations {
+                    println!(
+                        "{}/ is delegated to {} ({}:{})",
+                        child.name,
+                        delegation
+                            .delegates
+                            .iter()
+                            .map(|owner| owner.identifier.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        delegation.source_file.display(),
+                        delegation.line_number + 1
+                    );
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let children_data: Vec<_> = children
+                .iter()
+                .map(|child| {
+                    let mut data = serde_json::json!({
+                        "name": child.name,
+                        "type": if child.is_dir { "directory" } else { "file" },
+                        "files": child.files.len(),
+                        "owned_files": child.owned_files(),
+                        "coverage": child.coverage(),
+                        "owners": child.owners().iter().map(|(owner, files)| {
+                            serde_json::json!({ "identifier": owner, "files": files })
+                        }).collect::<Vec<_>>(),
+                        "tags": child.tags(),
+                    });
+                    // Only delegated directories carry the key
+                    if !child.delegations.is_empty() {
+                        data["delegated_to"] = serde_json::json!(child.delegations);
+                    }
+                    data
+                })
+                .collect();
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&children_data).unwrap());
+            } else {
+                let encoded =
+                    bincode::serde::encode_to_vec(&children_data, bincode::config::standard())
+                        .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn is_owned(file: &FileEntry) -> bool {
+    file.owners
+        .iter()
+        .any(|owner| owner.owner_type != OwnerType::Unowned)
+}
+
+/// Group files under `dir` by their first path component below it; directories first
+fn group_children<'a>(dir: &Path, files: Vec<&'a FileEntry>) -> Vec<Child<'a>> {
+    let mut children: BTreeMap<(bool, String), Vec<&'a FileEntry>> = BTreeMap::new();
+    for file in files {
+        let Ok(relative) = file.path.strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(first) = components.next() else {
+            continue;
+        };
+        let is_dir = components.next().is_some();
+        let name = first.as_os_str().to_string_lossy().into_owned();
+        // `false` sorts first, so directories are keyed with it
+        children.entry((!is_dir, name)).or_default().push(file);
+    }
+
+    children
+        .into_iter()
+        .map(|((is_file, name), files)| Child {
+            name,
+            is_dir: !is_file,
+            files,
+            delegations: Vec::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{Owner, Tag};
+    use std::path::PathBuf;
+
+    fn file(path: &str, owners: &[&str], tags: &[&str], generated: bool) -> FileEntry {
+        let owner = |identifier: &str| Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::User,
+        };
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners.iter().map(|identifier| owner(identifier)).collect(),
+            tags: tags.iter().map(|tag| Tag(tag.to_string())).collect(),
+            generated,
+            inline: None,
+        }
+    }
+
+    #[test]
+    fn test_group_children() {
+        let files = [
+            file("./src/lib.rs", &["@bob"], &["rust"], false),
+            file("./README.md", &[], &[], false),
+            file("./src/api/routes.rs", &["@alice", "@bob"], &["api"], false),
+            file("./src/schema.rs", &[], &["rust"], true),
+            file("./build.rs", &["@alice"], &[], false),
+        ];
+        let children = group_children(Path::new("."), files.iter().collect());
+
+        // Directories first, then files, each by name
+        let names: Vec<_> = children
+            .iter()
+            .map(|child| (child.name.as_str(), child.is_dir, child.files.len()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("src", true, 3),
+                ("README.md", false, 1),
+                ("build.rs", false, 1)
+            ]
+        );
+
+        // The generated schema is left out of coverage, but its tags still show
+        let src = &children[0];
+        assert_eq!((src.counted_files(), src.owned_files()), (2, 2));
+        assert_eq!(src.coverage(), 100.0);
+        assert_eq!(src.owners(), [("@bob", 2), ("@alice", 1)]);
+        assert_eq!(src.tags(), ["api", "rust"]);
+        assert_eq!(children[1].coverage(), 0.0);
+    }
+}
@@ -1,8 +1,39 @@
+pub mod branches;
+pub mod cache;
+pub mod calibrate;
+pub mod changed_files;
+pub mod checklist;
+pub mod churn;
+pub mod compare_github;
+pub mod complete;
 pub mod config;
+pub mod coverage;
+pub mod demo;
+pub mod diff;
+pub mod doctor;
+pub mod equivalent;
+pub mod export_paths;
+pub mod gate;
+pub mod heatmap;
+pub mod impact;
+pub mod import;
 pub mod infer_owners;
 pub mod inspect;
 pub mod list_files;
 pub mod list_owners;
 pub mod list_rules;
 pub mod list_tags;
+pub mod ls;
+pub mod optimize;
+pub mod org;
 pub mod parse;
+pub mod repos;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod simulate_membership;
+pub mod snapshot;
+pub mod statusline;
+pub mod tag;
+pub mod validate;
+pub mod verify_owners;
+pub mod watch;
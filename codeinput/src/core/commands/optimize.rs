@@ -0,0 +1,121 @@
+use crate::{
+    core::{
+        common::{find_codeowners_files, find_files},
+        document::CodeownersDocument,
+        equivalence::compare,
+        optimize::{optimize, RuleEdit},
+        types::CodeownersEntry,
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+use std::path::Path;
+
+/// Rewrite the CODEOWNERS files under `repo` with fewer rules and the same ownership
+///
+/// The optimized files are printed, or written in place with `write`. Before either,
+/// every file in the repository is resolved against both rule sets; any difference is an
+/// error and nothing is emitted.
+pub fn run(repo: &Path, write: bool) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let options = config.match_options();
+
+    let mut documents = Vec::new();
+    for path in find_codeowners_files(repo)? {
+        documents.push(CodeownersDocument::load(&path)?);
+    }
+    // Document and line of each rule, in the order of `entries`
+    let mut locations = Vec::new();
+    let mut entries: Vec<CodeownersEntry> = Vec::new();
+    for (doc, document) in documents.iter().enumerate() {
+        for (line, text) in document.lines().iter().enumerate() {
+            if let Some(entry) = text.entry() {
+                locations.push((doc, line));
+                entries.push(entry.clone());
+            }
+        }
+    }
+    let files = find_files(repo)?;
+
+    let edits = optimize(&entries, &files, options, config.precedence)?;
+    if edits.is_empty() {
+        println!(
+            "No smaller equivalent rule set found for {} rules in {} CODEOWNERS files",
+            entries.len(),
+            documents.len()
+        );
+        return Ok(());
+    }
+
+    // Patterns first, then lines from the bottom up so earlier line indices stay valid
+    let mut removals = Vec::new();
+    let mut changed = vec![false; documents.len()];
+    for edit in &edits {
+        match edit {
+            RuleEdit::Replace(rule, pattern) => {
+                let (doc, line) = locations[*rule];
+                documents[doc].set_pattern(line, pattern)?;
+                changed[doc] = true;
+            }
+            RuleEdit::Remove(rule) => {
+                removals.push(locations[*rule]);
+                changed[locations[*rule].0] = true;
+            }
+        }
+    }
+    removals.sort_unstable_by(|a, b| b.cmp(a));
+    for (doc, line) in removals {
+        documents[doc].remove_line(line)?;
+    }
+
+    // Check the files as they will be written, not the optimizer's view of them
+    let mut optimized = Vec::new();
+    for document in &documents {
+        let reparsed = CodeownersDocument::parse(&document.to_string(), document.source_file())?;
+        optimized.extend(reparsed.entries().cloned());
+    }
+    let differing = compare(&entries, &optimized, &files, options, config.precedence)?;
+    if let Some(difference) = differing.first() {
+        return Err(Error::new(&format!(
+            "Optimized rules resolve {} files differently, including {}; nothing was changed",
+            differing.len(),
+            difference.path.display()
+        )));
+    }
+
+    let changed: Vec<&CodeownersDocument> = documents
+        .iter()
+        .zip(changed)
+        .filter_map(|(document, changed)| changed.then_some(document))
+        .collect();
+    for document in &changed {
+        let path = document.source_file();
+        let display = path.strip_prefix(repo).unwrap_or(path).display();
+        let before = entries
+            .iter()
+            .filter(|entry| entry.source_file == path)
+            .count();
+        let summary = format!(
+            "{}: {} rules -> {} rules, verified against {} files",
+            display,
+            before,
+            document.entries().count(),
+            files.len()
+        );
+
+        if write {
+            document.save(path)?;
+            println!("{}", summary);
+        } else {
+            if changed.len() > 1 {
+                println!("# ==> {} <==", display);
+            }
+            print!("{}", document);
+            eprintln!("{}", summary);
+        }
+    }
+
+    Ok(())
+}
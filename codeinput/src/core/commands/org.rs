@@ -0,0 +1,298 @@
+use crate::{
+    core::{
+        cache::load_cache,
+        display::truncate_string,
+        org::{aggregate, OrgOwner, OrgReport, ReportFormat, SkippedRepo, Workspace},
+        registry::Registry,
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+        output,
+    },
+};
+use std::fmt::Write as _;
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct RepoDisplay {
+    #[tabled(rename = "Repository")]
+    name: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Unowned")]
+    unowned_files: usize,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+    #[tabled(rename = "Owners")]
+    owners: usize,
+}
+
+#[derive(Tabled)]
+struct OwnerDisplay {
+    #[tabled(rename = "Owner")]
+    identifier: String,
+    #[tabled(rename = "Repos")]
+    repo_count: usize,
+    #[tabled(rename = "Repositories")]
+    repos: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+}
+
+/// Roll up the ownership caches of the repositories listed in `workspace`, or else
+/// those in the registry
+///
+/// Caches are read as they are, without rebuilding; repositories whose cache is missing
+/// or unreadable are reported as skipped. The report is printed, or written to `output`.
+pub fn report(workspace: Option<&Path>, format: ReportFormat, output: Option<&Path>) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let workspace = match workspace {
+        Some(workspace) => Workspace::load(workspace)?,
+        None => Workspace::from_registry(&Registry::load()?)?,
+    };
+
+    let mut caches = Vec::new();
+    let mut skipped = Vec::new();
+    for repo in &workspace.repos {
+        let cache_file = repo
+            .cache_file
+            .clone()
+            .unwrap_or_else(|| config.cache_file.clone().into());
+        let cache_path = repo.path.join(cache_file);
+        let name = repo.name().to_string();
+        if !cache_path.exists() {
+            skipped.push(SkippedRepo {
+                name,
+                reason: format!(
+                    "no cache at {}; run `ci codeowners parse` in the repository",
+                    cache_path.display()
+                ),
+            });
+            continue;
+        }
+        match load_cache(&cache_path) {
+            Ok(cache) => caches.push((name, cache)),
+            Err(e) => skipped.push(SkippedRepo {
+                name,
+                reason: e.to_string(),
+            }),
+        }
+    }
+    if caches.is_empty() {
+        let reasons: Vec<String> = skipped
+            .iter()
+            .map(|skipped| format!("{}: {}", skipped.name, skipped.reason))
+            .collect();
+        return Err(Error::new(&format!(
+            "No repository caches could be loaded ({})",
+            reasons.join("; ")
+        )));
+    }
+
+    let report = aggregate(&caches, skipped);
+    let rendered = match format {
+        ReportFormat::Text => render_text(&report),
+        ReportFormat::Json => render_json(&report),
+        ReportFormat::Html => render_html(&report),
+    };
+
+    match output {
+        Some(path) => {
+            std::fs::write(path, rendered)
+                .map_err(|e| Error::new(&format!("Failed to write {}: {}", path.display(), e)))?;
+            println!(
+                "Wrote {} report for {} repositories to {}",
+                format,
+                report.repos.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+fn coverage(files: usize, unowned: usize) -> String {
+    if files == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", (files - unowned) as f64 * 100.0 / files as f64)
+}
+
+fn owner_rows<'a>(owners: impl Iterator<Item = &'a OrgOwner>) -> Vec<OwnerDisplay> {
+    owners
+        .map(|owner| OwnerDisplay {
+            identifier: truncate_string(&owner.identifier, 40),
+            repo_count: owner.repos.len(),
+            repos: truncate_string(&owner.repos.join(", "), 50),
+            files: owner.files,
+        })
+        .collect()
+}
+
+fn render_text(report: &OrgReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "Repositories: {}, files: {}, unowned: {} (coverage {})",
+        report.repos.len(),
+        report.total_files,
+        report.unowned_files,
+        coverage(report.total_files, report.unowned_files)
+    );
+
+    let repos: Vec<RepoDisplay> = report
+        .repos
+        .iter()
+        .map(|repo| RepoDisplay {
+            name: repo.name.clone(),
+            files: repo.files,
+            unowned_files: repo.unowned_files,
+            coverage: coverage(repo.files, repo.unowned_files),
+            owners: repo.owners,
+        })
+        .collect();
+    let mut table = Table::new(repos);
+    table.with(tabled::settings::Style::modern());
+    let _ = writeln!(out, "\n{}", table);
+
+    let spanning = owner_rows(report.spanning_owners());
+    let _ = writeln!(out, "\nOwners spanning repositories:");
+    if spanning.is_empty() {
+        let _ = writeln!(out, "  (none)");
+    } else {
+        let mut table = Table::new(spanning);
+        table.with(tabled::settings::Style::modern());
+        let _ = writeln!(out, "{}", table);
+    }
+
+    let teams = owner_rows(report.teams());
+    let _ = writeln!(out, "\nTeams:");
+    if teams.is_empty() {
+        let _ = writeln!(out, "  (none)");
+    } else {
+        let mut table = Table::new(teams);
+        table.with(tabled::settings::Style::modern());
+        let _ = writeln!(out, "{}", table);
+    }
+
+    if !report.skipped.is_empty() {
+        let _ = writeln!(out, "\nSkipped repositories:");
+        for skipped in &report.skipped {
+            let _ = writeln!(out, "  - {}: {}", skipped.name, skipped.reason);
+        }
+    }
+    out
+}
+
+fn render_json(report: &OrgReport) -> String {
+    let mut value = serde_json::to_value(report).unwrap();
+    value["generated_at"] = output::now().to_rfc3339().into();
+    format!("{}\n", serde_json::to_string_pretty(&value).unwrap())
+}
+
+fn render_html(report: &OrgReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Code ownership report</title>\n<style>\n\
+         body {{ font-family: sans-serif; margin: 2em; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 2em; }}\n\
+         th, td {{ border: 1px solid #ccc; padding: 0.3em 0.8em; text-align: left; }}\n\
+         th {{ background: #f4f4f4; }}\n\
+         </style>\n</head>\n<body>\n<h1>Code ownership report</h1>"
+    );
+    let _ = writeln!(
+        out,
+        "<p>{} repositories, {} files, {} unowned (coverage {}). Generated {}.</p>",
+        report.repos.len(),
+        report.total_files,
+        report.unowned_files,
+        coverage(report.total_files, report.unowned_files),
+        output::now().format("%Y-%m-%d %H:%M UTC")
+    );
+
+    let _ = writeln!(out, "<h2>Repositories</h2>");
+    let rows: Vec<Vec<String>> = report
+        .repos
+        .iter()
+        .map(|repo| {
+            vec![
+                repo.name.clone(),
+                repo.files.to_string(),
+                repo.unowned_files.to_string(),
+                coverage(repo.files, repo.unowned_files),
+                repo.owners.to_string(),
+            ]
+        })
+        .collect();
+    html_table(
+        &mut out,
+        &["Repository", "Files", "Unowned", "Coverage", "Owners"],
+        &rows,
+    );
+
+    let owner_cells = |owner: &OrgOwner| {
+        vec![
+            owner.identifier.clone(),
+            owner.repos.len().to_string(),
+            owner.repos.join(", "),
+            owner.files.to_string(),
+        ]
+    };
+    let _ = writeln!(out, "<h2>Owners spanning repositories</h2>");
+    let rows: Vec<Vec<String>> = report.spanning_owners().map(owner_cells).collect();
+    html_table(
+        &mut out,
+        &["Owner", "Repos", "Repositories", "Files"],
+        &rows,
+    );
+
+    let _ = writeln!(out, "<h2>Teams</h2>");
+    let rows: Vec<Vec<String>> = report.teams().map(owner_cells).collect();
+    html_table(&mut out, &["Team", "Repos", "Repositories", "Files"], &rows);
+
+    if !report.skipped.is_empty() {
+        let _ = writeln!(out, "<h2>Skipped repositories</h2>");
+        let rows: Vec<Vec<String>> = report
+            .skipped
+            .iter()
+            .map(|skipped| vec![skipped.name.clone(), skipped.reason.clone()])
+            .collect();
+        html_table(&mut out, &["Repository", "Reason"], &rows);
+    }
+
+    let _ = writeln!(out, "</body>\n</html>");
+    out
+}
+
+fn html_table(out: &mut String, headers: &[&str], rows: &[Vec<String>]) {
+    if rows.is_empty() {
+        let _ = writeln!(out, "<p>None.</p>");
+        return;
+    }
+    let _ = write!(out, "<table>\n<tr>");
+    for header in headers {
+        let _ = write!(out, "<th>{}</th>", escape_html(header));
+    }
+    let _ = writeln!(out, "</tr>");
+    for row in rows {
+        let _ = write!(out, "<tr>");
+        for cell in row {
+            let _ = write!(out, "<td>{}</td>", escape_html(cell));
+        }
+        let _ = writeln!(out, "</tr>");
+    }
+    let _ = writeln!(out, "</table>");
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -1,53 +1,213 @@
 use crate::{
     core::{
-        cache::{build_cache, load_cache, store_cache},
-        common::{find_codeowners_files, find_files, get_repo_hash},
-        parser::parse_codeowners,
-        types::{CacheEncoding, CodeownersEntry},
+        cache::{load_cache, note_summary, write_cache},
+        central::central_codeowners,
+        common::{find_dialect_codeowners_files, find_files, get_cache_hash, repo_state},
+        context::Context,
+        matcher::MatcherStrategy,
+        parse_report::ParseReport,
+        parser::parse_codeowners_as,
+        registry, remote_cache,
+        tag_remap::TagRemap,
+        types::{CacheEncoding, CodeownersEntry, OutputFormat},
+    },
+    utils::{
+        error::{Error, Result},
+        output,
+        warnings::{self, WarningKind},
     },
-    utils::{app_config::AppConfig, error::Result},
 };
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct CodeownersFileDisplay {
+    #[tabled(rename = "CODEOWNERS File")]
+    path: String,
+    #[tabled(rename = "Rules")]
+    rules: usize,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+#[derive(Tabled)]
+struct SkippedDisplay {
+    #[tabled(rename = "Rule")]
+    location: String,
+    #[tabled(rename = "Pattern")]
+    pattern: String,
+    #[tabled(rename = "Reason")]
+    reason: String,
+}
 
 /// Preprocess CODEOWNERS files and build ownership map
+///
+/// With `report`, a summary of what was parsed and skipped is printed in that format
+/// once the cache is written. With `push_cache`, the cache is then uploaded to that
+/// remote cache.
 pub fn run(
     path: &std::path::Path, cache_file: Option<&std::path::Path>, encoding: CacheEncoding,
+    matcher: Option<MatcherStrategy>, report: Option<&OutputFormat>, push_cache: Option<&str>,
 ) -> Result<()> {
+    let started = Instant::now();
     println!("Parsing CODEOWNERS files at {}", path.display());
 
+    let ctx = Context::fetch()?;
+    let config = &ctx.config;
     let cache_file = match cache_file {
         Some(file) => path.join(file),
-        None => {
-            let config = AppConfig::fetch()?;
-            path.join(config.cache_file)
-        }
+        None => path.join(&config.cache_file),
     };
+    let matcher = matcher.unwrap_or(config.matcher);
 
-    // Collect all CODEOWNERS files in the specified path
-    let codeowners_files = find_codeowners_files(path)?;
+    // Collect the CODEOWNERS files the dialect reads in the specified path
+    let codeowners_files = find_dialect_codeowners_files(path, config.dialect)?;
 
-    // Parse each CODEOWNERS file and collect entries
-    let parsed_codeowners: Vec<CodeownersEntry> = codeowners_files
-        .iter()
-        .filter_map(|file| {
-            let parsed = parse_codeowners(file).ok()?;
-            Some(parsed)
-        })
-        .flatten()
-        .collect();
+    // Parse each CODEOWNERS file and collect entries, remembering the ones skipped
+    let mut parsed_codeowners: Vec<CodeownersEntry> = Vec::new();
+    let mut parsed_files = Vec::with_capacity(codeowners_files.len());
+    for file in codeowners_files {
+        match parse_codeowners_as(&file, config.dialect) {
+            Ok(entries) => {
+                parsed_codeowners.extend(entries);
+                parsed_files.push((file, None));
+            }
+            Err(e) => {
+                warnings::warn(
+                    WarningKind::SkippedPattern,
+                    format!("Skipping {}: {}", file.display(), e),
+                );
+                parsed_files.push((file, Some(e.to_string())));
+            }
+        }
+    }
+
+    // Merge the rules of the central ownership repository, fetching it again
+    if let Some((source_file, entries)) = central_codeowners(&ctx, path, true)? {
+        parsed_codeowners.extend(entries);
+        parsed_files.push((source_file, None));
+    }
 
     // Collect all files in the specified path
     let files = find_files(path)?;
 
     // Build the cache from the parsed CODEOWNERS entries and the files
-    let hash = get_repo_hash(path)?;
-
-    let cache = build_cache(parsed_codeowners, files, hash)?;
+    let hash = get_cache_hash(path, config.match_options(), &config.generated_markers)?;
+    let source = repo_state(path)?;
 
-    // Store the cache in the specified file
-    store_cache(&cache, &cache_file, encoding)?;
+    // Resolve every file and stream the cache to disk
+    write_cache(
+        &ctx,
+        &parsed_codeowners,
+        &files,
+        hash,
+        &source,
+        matcher,
+        &TagRemap::for_repo(&ctx, path)?,
+        &cache_file,
+        encoding,
+        config.cache_memory_budget_bytes(),
+    )?;
 
     // Test the cache by loading it back
-    let _cache = load_cache(&cache_file)?;
+    let cache = load_cache(&cache_file)?;
+    note_summary(&cache);
+    registry::note(path, &cache_file, &cache);
+
+    if let Some(location) = push_cache {
+        let url = remote_cache::push(&ctx, location, &cache_file, &hash)?;
+        println!("Pushed the cache to {}", url);
+    }
+
+    if let Some(format) = report {
+        // The duration varies between runs
+        let elapsed = if output::is_stable() {
+            Duration::ZERO
+        } else {
+            started.elapsed()
+        };
+        let report = ParseReport::new(path, parsed_files, &cache, config.match_options(), elapsed);
+        print_report(&report, format)?;
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &ParseReport, format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            if report.codeowners_files.is_empty() {
+                println!("No CODEOWNERS files found");
+            } else {
+                let table_data: Vec<CodeownersFileDisplay> = report
+                    .codeowners_files
+                    .iter()
+                    .map(|file| CodeownersFileDisplay {
+                        path: file.path.display().to_string(),
+                        rules: file.rules,
+                        status: match &file.error {
+                            Some(error) => format!("skipped: {}", error),
+                            None => "ok".to_string(),
+                        },
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+
+            if report.skipped_patterns.is_empty() {
+                println!("No patterns skipped");
+            } else {
+                println!("Skipped patterns ({}):", report.skipped_patterns.len());
+                let table_data: Vec<SkippedDisplay> = report
+                    .skipped_patterns
+                    .iter()
+                    .map(|skipped| SkippedDisplay {
+                        location: format!("{}:{}", skipped.source_file.display(), skipped.line),
+                        pattern: skipped.pattern.clone(),
+                        reason: skipped.reason.clone(),
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+
+            if report.inline_markers.is_empty() {
+                println!("No inline markers found");
+            } else {
+                println!("Inline markers ({}):", report.inline_markers.len());
+                for file in &report.inline_markers {
+                    println!("  {}", file.display());
+                }
+            }
+            if !report.front_matter.is_empty() {
+                println!("Front matter owners ({}):", report.front_matter.len());
+                for file in &report.front_matter {
+                    println!("  {}", file.display());
+                }
+            }
+
+            println!(
+                "Resolved {} files ({} unowned) against {} rules in {} ms",
+                report.files, report.unowned, report.rules, report.duration_ms
+            );
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(report).unwrap());
+        }
+        OutputFormat::Bincode => {
+            let encoded = bincode::serde::encode_to_vec(report, bincode::config::standard())
+                .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+            // Write raw binary bytes to stdout
+            io::stdout()
+                .write_all(&encoded)
+                .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,137 @@
+use crate::{
+    core::{
+        cache::{sync_cache, unowned_count},
+        context::Context,
+        display::truncate_path,
+        registry::{RegisteredRepo, Registry},
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct RepoDisplay {
+    #[tabled(rename = "Repository")]
+    name: String,
+    #[tabled(rename = "Path")]
+    path: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+    #[tabled(rename = "Cached")]
+    cached_at: String,
+}
+
+/// List the repositories in the registry
+pub fn list(format: &OutputFormat) -> Result<()> {
+    let registry = Registry::load()?;
+
+    match format {
+        OutputFormat::Text => {
+            if registry.repos.is_empty() {
+                println!(
+                    "No repositories recorded; they are added when their cache is built, e.g. by `ci codeowners parse`"
+                );
+                return Ok(());
+            }
+            let table_data: Vec<RepoDisplay> = registry
+                .repos
+                .iter()
+                .map(|repo| RepoDisplay {
+                    name: repo.name().to_string(),
+                    path: if repo.path.is_dir() {
+                        truncate_path(&repo.path.display().to_string(), 50)
+                    } else {
+                        format!(
+                            "{} (gone)",
+                            truncate_path(&repo.path.display().to_string(), 43)
+                        )
+                    },
+                    files: repo.files,
+                    coverage: coverage(repo),
+                    cached_at: repo.cached_at.format("%Y-%m-%d %H:%M").to_string(),
+                })
+                .collect();
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&registry.repos).unwrap());
+        }
+        OutputFormat::Bincode => {
+            let encoded =
+                bincode::serde::encode_to_vec(&registry.repos, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+            // Write raw binary bytes to stdout
+            io::stdout()
+                .write_all(&encoded)
+                .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Bring the cache of every repository in the registry up to date, building, updating
+/// or rebuilding it as any command would
+///
+/// Repositories whose directory is gone are skipped, or forgotten with `prune`.
+pub fn refresh(prune: bool) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let mut registry = Registry::load()?;
+    if prune {
+        let gone = registry.prune();
+        if !gone.is_empty() {
+            registry.save()?;
+        }
+        for repo in gone {
+            println!("Forgot {}, which is gone", repo.path.display());
+        }
+    }
+
+    let mut failed = 0;
+    for repo in &registry.repos {
+        if !repo.path.is_dir() {
+            println!(
+                "Skipped {}, which is gone; add --prune to forget it",
+                repo.path.display()
+            );
+            continue;
+        }
+        match sync_cache(&ctx, &repo.path, Some(&repo.cache_file)) {
+            Ok(cache) => println!(
+                "Refreshed {}: {} files, coverage {}",
+                repo.path.display(),
+                cache.files.len(),
+                coverage(&RegisteredRepo {
+                    files: cache.files.len(),
+                    unowned: unowned_count(&cache),
+                    ..repo.clone()
+                })
+            ),
+            Err(e) => {
+                failed += 1;
+                eprintln!("Failed to refresh {}: {}", repo.path.display(), e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(Error::new(&format!(
+            "Failed to refresh {} of {} repositories",
+            failed,
+            registry.repos.len()
+        )));
+    }
+    Ok(())
+}
+
+fn coverage(repo: &RegisteredRepo) -> String {
+    repo.coverage()
+        .map_or("-".to_string(), |coverage| format!("{:.1}%", coverage))
+}
@@ -0,0 +1,56 @@
+use crate::{
+    core::{
+        cache::{cache_file_or_default, sync_cache},
+        context::Context,
+        server::{self, ServedCache},
+    },
+    utils::{
+        error::{Error, Result},
+        interrupt,
+    },
+};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the server checks for Ctrl-C
+const IDLE_CHECK: Duration = Duration::from_millis(250);
+
+/// Answer ownership queries about `repo` over HTTP on `bind` until Ctrl-C or the
+/// `--timeout` deadline
+///
+/// The cache is built or brought up to date once at start; after that it is read again
+/// whenever its file changes, so `ci codeowners watch` can keep it fresh. See [`server`]
+/// for the endpoints. Stopping fails as interrupted or timed out, as `watch` does.
+pub fn run(repo: &Path, bind: &str, cache_file: Option<&Path>) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache = sync_cache(&ctx, repo, cache_file)?;
+    let path = repo.join(cache_file_or_default(&ctx, cache_file));
+    let served = Arc::new(ServedCache::new(ctx, repo, &path, cache));
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| Error::with_source("Failed to start the HTTP server", Box::new(e)))?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(bind)
+            .await
+            .map_err(|e| Error::with_source(&format!("Cannot listen on {}", bind), Box::new(e)))?;
+        println!(
+            "Serving the ownership of {} on http://{}, press Ctrl-C to stop",
+            repo.display(),
+            listener.local_addr()?
+        );
+        axum::serve(listener, server::router(served))
+            .with_graceful_shutdown(interrupted())
+            .await
+            .map_err(|e| Error::with_source("HTTP server failed", Box::new(e)))
+    })?;
+    interrupt::check()
+}
+
+async fn interrupted() {
+    while !interrupt::is_interrupted() {
+        tokio::time::sleep(IDLE_CHECK).await;
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        membership::{simulate, MembershipChanges},
+        team_map::TeamMap,
+        types::OutputFormat,
+    },
+    utils::error::{Error, Result},
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct ImpactDisplay {
+    #[tabled(rename = "Member")]
+    member: String,
+    #[tabled(rename = "Joins")]
+    joins: String,
+    #[tabled(rename = "Leaves")]
+    leaves: String,
+    #[tabled(rename = "Gains")]
+    gained: usize,
+    #[tabled(rename = "Loses")]
+    lost: usize,
+}
+
+/// Report the files each member gains or loses review responsibility over when the team
+/// membership changes in `changes_file` are applied to the repository's team mapping
+pub fn run(
+    path: &Path, changes_file: &Path, cache_file: Option<&Path>, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let teams = TeamMap::for_repo(&ctx, path)?;
+    let changes = MembershipChanges::load(changes_file)?;
+    let cache = sync_cache(&ctx, path, cache_file)?;
+    let impacts = simulate(&cache, &teams, &changes)?;
+
+    match format {
+        OutputFormat::Text => {
+            if impacts.is_empty() {
+                println!("No membership changes in {}", changes_file.display());
+                return Ok(());
+            }
+
+            let list = |teams: &[String]| {
+                if teams.is_empty() {
+                    "-".to_string()
+                } else {
+                    teams.join(", ")
+                }
+            };
+            let table_data: Vec<ImpactDisplay> = impacts
+                .iter()
+                .map(|impact| ImpactDisplay {
+                    member: impact.member.clone(),
+                    joins: list(&impact.joins),
+                    leaves: list(&impact.leaves),
+                    gained: impact.gained.len(),
+                    lost: impact.lost.len(),
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+
+            for impact in &impacts {
+                if impact.gained.is_empty() && impact.lost.is_empty() {
+                    continue;
+                }
+                println!("\n{}:", impact.member);
+                for file in &impact.gained {
+                    println!("  + {}", file.display());
+                }
+                for file in &impact.lost {
+                    println!("  - {}", file.display());
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({ "members": impacts });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
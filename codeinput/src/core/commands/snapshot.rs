@@ -0,0 +1,161 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        snapshot::{self, Snapshot},
+        types::OutputFormat,
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+        output,
+    },
+};
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+#[derive(Tabled)]
+struct SnapshotDisplay {
+    #[tabled(rename = "Taken")]
+    taken_at: String,
+    #[tabled(rename = "Commit")]
+    commit: String,
+    #[tabled(rename = "Files")]
+    files: usize,
+    #[tabled(rename = "Unowned")]
+    unowned_files: usize,
+    #[tabled(rename = "Coverage")]
+    coverage: String,
+    #[tabled(rename = "Owners")]
+    owners: usize,
+}
+
+/// Record the current ownership of `repo` in its snapshot directory
+pub fn save(repo: &Path, cache_file: Option<&Path>) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let config = &ctx.config;
+    let cache = sync_cache(&ctx, repo, cache_file)?;
+    let commit = git2::Repository::discover(repo)
+        .ok()
+        .and_then(|repository| repository.head().ok()?.target())
+        .map(|oid| oid.to_string());
+    let snapshot = Snapshot::from_cache(&cache, output::now(), commit);
+
+    let dir = repo.join(&config.snapshot_dir);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(snapshot.file_name());
+    let contents = serde_json::to_string_pretty(&snapshot)
+        .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+    std::fs::write(&path, contents)
+        .map_err(|e| Error::new(&format!("Failed to write {}: {}", path.display(), e)))?;
+
+    println!(
+        "Saved snapshot {}: {} files, {} unowned, {} owners",
+        path.display(),
+        snapshot.files,
+        snapshot.unowned_files,
+        snapshot.owners.len()
+    );
+    Ok(())
+}
+
+/// List the stored snapshots of `repo`, oldest first
+pub fn list(repo: &Path, format: &OutputFormat) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let mut snapshots = Vec::new();
+    for stored in snapshot::list(&repo.join(&config.snapshot_dir))? {
+        snapshots.push(snapshot::load(&stored.path)?);
+    }
+
+    match format {
+        OutputFormat::Text => {
+            if snapshots.is_empty() {
+                println!(
+                    "No snapshots in {}; record one with `ci codeowners snapshot save`",
+                    config.snapshot_dir
+                );
+                return Ok(());
+            }
+            let table_data: Vec<SnapshotDisplay> = snapshots
+                .iter()
+                .map(|snapshot| SnapshotDisplay {
+                    taken_at: snapshot.taken_at.format("%Y-%m-%d %H:%M").to_string(),
+                    commit: snapshot
+                        .commit
+                        .as_deref()
+                        .map(|commit| commit.chars().take(8).collect())
+                        .unwrap_or_else(|| "-".to_string()),
+                    files: snapshot.files,
+                    unowned_files: snapshot.unowned_files,
+                    coverage: if snapshot.files == 0 {
+                        "-".to_string()
+                    } else {
+                        format!(
+                            "{:.1}%",
+                            (snapshot.files - snapshot.unowned_files) as f64 * 100.0
+                                / snapshot.files as f64
+                        )
+                    },
+                    owners: snapshot.owners.len(),
+                })
+                .collect();
+
+            let mut table = Table::new(table_data);
+            table.with(tabled::settings::Style::modern());
+            println!("{}", table);
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&snapshots).unwrap());
+        }
+        OutputFormat::Bincode => {
+            let encoded = bincode::serde::encode_to_vec(&snapshots, bincode::config::standard())
+                .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+            // Write raw binary bytes to stdout
+            io::stdout()
+                .write_all(&encoded)
+                .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete the snapshots of `repo` that the retention policy no longer keeps
+///
+/// With `dry_run`, the snapshots that would be deleted are listed instead.
+pub fn gc(repo: &Path, dry_run: bool) -> Result<()> {
+    let config = AppConfig::fetch()?;
+    let policy = config.retention_policy();
+    let stored = snapshot::list(&repo.join(&config.snapshot_dir))?;
+    let taken: Vec<_> = stored.iter().map(|snapshot| snapshot.taken_at).collect();
+    let keep = policy.keep(&taken, output::now());
+
+    let expired: Vec<&Path> = stored
+        .iter()
+        .zip(&keep)
+        .filter(|(_, keep)| !**keep)
+        .map(|(snapshot, _)| snapshot.path.as_path())
+        .collect();
+    for path in &expired {
+        if dry_run {
+            println!("Would remove {}", path.display());
+        } else {
+            std::fs::remove_file(path)
+                .map_err(|e| Error::new(&format!("Failed to remove {}: {}", path.display(), e)))?;
+        }
+    }
+
+    println!(
+        "{} {} of {} snapshots, keeping {}",
+        if dry_run { "Would remove" } else { "Removed" },
+        expired.len(),
+        stored.len(),
+        stored.len() - expired.len()
+    );
+    if dry_run {
+        println!("Dry run: no files were changed");
+    }
+    Ok(())
+}
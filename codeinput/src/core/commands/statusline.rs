@@ -0,0 +1,142 @@
+use crate::{
+    core::{
+        cache::{load_cache_for, CacheQuery},
+        context::Context,
+        types::{CodeownersCache, OwnerType},
+    },
+    utils::error::{Error, Result},
+};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Print one compact line with the owners, tags and deciding rule of a file
+///
+/// Meant to be called by editors on every buffer switch, so it only reads the cache: it
+/// is not checked for staleness or rebuilt, and loading it must finish within
+/// `budget_ms`; a SQLite or archived cache is read just for the file. Without `repo`, the
+/// repository is the nearest directory above the file holding the cache.
+pub fn run(
+    file: &Path, repo: Option<&Path>, cache_file: Option<&Path>, budget_ms: u64,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let cache_file = cache_file.unwrap_or_else(|| Path::new(&ctx.config.cache_file));
+
+    let file = file
+        .canonicalize()
+        .map_err(|e| Error::new(&format!("Cannot read {}: {}", file.display(), e)))?;
+    let repo = match repo {
+        Some(repo) => repo.to_path_buf(),
+        None => file
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join(cache_file).is_file())
+            .map(Path::to_path_buf)
+            .ok_or_else(|| {
+                Error::new(&format!(
+                    "No ownership cache found above {}; build it with `ci codeowners parse` \
+                     or keep it fresh with `ci codeowners watch`",
+                    file.display()
+                ))
+            })?,
+    };
+    let root = repo.canonicalize()?;
+    let relative = file.strip_prefix(&root).map_err(|_| {
+        Error::new(&format!(
+            "File {} is not within repository {}",
+            file.display(),
+            repo.display()
+        ))
+    })?;
+
+    let prefixes = [Path::new("."), &repo, &root];
+    let candidates = candidates(relative, &prefixes);
+
+    // Load on a separate thread so a slow disk or a huge cache cannot hold up the editor
+    let (sender, receiver) = mpsc::channel();
+    let cache_path = repo.join(cache_file);
+    let paths = candidates.clone();
+    std::thread::spawn(move || {
+        // Nobody is listening any more once the budget is exceeded
+        let _ = sender.send(load_cache_for(&cache_path, CacheQuery::Paths(&paths)));
+    });
+    let cache = receiver
+        .recv_timeout(Duration::from_millis(budget_ms))
+        .map_err(|_| {
+            Error::new(&format!(
+                "Ownership cache not loaded within {} ms",
+                budget_ms
+            ))
+        })??;
+
+    println!("{}", status_line(&cache, relative, &candidates, &prefixes)?);
+
+    Ok(())
+}
+
+/// The spellings of `relative` a cache may hold
+///
+/// Cached paths start with whatever the repository was called when the cache was built,
+/// so `relative` is tried as it is and below each of `prefixes`.
+fn candidates(relative: &Path, prefixes: &[&Path]) -> Vec<PathBuf> {
+    std::iter::once(relative.to_path_buf())
+        .chain(prefixes.iter().map(|prefix| prefix.join(relative)))
+        .collect()
+}
+
+/// `@owner1,@owner2 · #tag · rule CODEOWNERS:42` for the file at `relative`, cached at
+/// one of `candidates`
+///
+/// The deciding rules are looked up in the rule matches the cache stores, see
+/// [`CodeownersCache::rules_matching`], so no pattern is compiled.
+fn status_line(
+    cache: &CodeownersCache, relative: &Path, candidates: &[PathBuf], prefixes: &[&Path],
+) -> Result<String> {
+    let (path, position) = candidates
+        .iter()
+        .find_map(|path| Some((path, cache.path_index.get(path)?)))
+        .ok_or_else(|| {
+            Error::new(&format!(
+                "File {} is not in the ownership cache",
+                relative.display()
+            ))
+        })?;
+    let entry = &cache.files[position];
+
+    let owners: Vec<&str> = entry
+        .owners
+        .iter()
+        .filter(|owner| owner.owner_type != OwnerType::Unowned)
+        .map(|owner| owner.identifier.as_str())
+        .collect();
+    let mut parts = vec![if owners.is_empty() {
+        "unowned".to_string()
+    } else {
+        owners.join(",")
+    }];
+
+    if !entry.tags.is_empty() {
+        let tags: Vec<String> = entry.tags.iter().map(|tag| format!("#{}", tag.0)).collect();
+        parts.push(tags.join(","));
+    }
+
+    // Line numbers are 1-based here, as editors count them
+    let rules: Vec<String> = cache
+        .rules_matching(path)
+        .map(|(_, rule)| {
+            let source = prefixes
+                .iter()
+                .find_map(|prefix| rule.source_file.strip_prefix(prefix).ok())
+                .unwrap_or(&rule.source_file);
+            format!("{}:{}", source.display(), rule.line_number + 1)
+        })
+        .collect();
+    if !rules.is_empty() {
+        parts.push(format!("rule {}", rules.join(",")));
+    } else if !owners.is_empty() {
+        // Owned without a rule: declared in the file itself
+        parts.push("inline".to_string());
+    }
+
+    Ok(parts.join(" · "))
+}
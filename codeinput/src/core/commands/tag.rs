@@ -0,0 +1,403 @@
+use crate::{
+    core::{
+        common::{find_codeowners_files, find_files},
+        document::CodeownersDocument,
+        inline_parser::{detect_inline_codeowners, rename_inline_tag},
+        matcher::{Matcher, MatcherStrategy},
+        parser::{parse_codeowners, parse_owner},
+        resolver::resolve_file_with_policy,
+        tag_remap::tag_name,
+        tag_resolver::dedup_tags,
+        types::{CodeownersEntry, Owner, Tag},
+    },
+    utils::{
+        app_config::AppConfig,
+        error::{Error, Result},
+    },
+};
+use std::path::{Path, PathBuf};
+
+/// A file rewritten by a tag command, with its contents before and after
+pub(crate) struct Change {
+    pub(crate) path: PathBuf,
+    pub(crate) before: String,
+    pub(crate) after: String,
+}
+
+/// Rename tag `old` to `new` in every CODEOWNERS file and inline marker under `repo`
+///
+/// Rules and markers that already carry `new` lose `old` instead. With `dry_run` the
+/// changes are printed as a diff and nothing is written.
+pub fn rename(repo: &Path, old: &str, new: &str, dry_run: bool) -> Result<()> {
+    let old = tag_name(old);
+    let new = validate_tag(new)?;
+    if old == new {
+        return Err(Error::new(&format!("#{} is already named #{}", old, new)));
+    }
+
+    let mut changes = Vec::new();
+    let mut rules = 0;
+    for path in find_codeowners_files(repo)? {
+        let mut document = CodeownersDocument::load(&path)?;
+        let before = document.to_string();
+
+        for index in 0..document.lines().len() {
+            let Some(entry) = document.lines()[index].entry() else {
+                continue;
+            };
+            if !entry.tags.iter().any(|tag| tag.0 == old) {
+                continue;
+            }
+            let tags = dedup_tags(
+                entry
+                    .tags
+                    .iter()
+                    .map(|tag| Tag(if tag.0 == old { new } else { tag.0.as_str() }.to_string()))
+                    .collect(),
+            );
+            document.set_tags(index, &tags)?;
+            rules += 1;
+        }
+
+        let after = document.to_string();
+        if after != before {
+            changes.push(Change {
+                path,
+                before,
+                after,
+            });
+        }
+    }
+    let codeowners_files = changes.len();
+
+    for path in find_files(repo)? {
+        // Binary and unreadable files carry no markers
+        let Ok(before) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        if let Some(after) = rename_inline_tag(&before, &path, old, new)? {
+            changes.push(Change {
+                path,
+                before,
+                after,
+            });
+        }
+    }
+    let markers = changes.len() - codeowners_files;
+
+    if changes.is_empty() {
+        return Err(Error::new(&format!(
+            "No CODEOWNERS rule or inline marker under {} is tagged #{}",
+            repo.display(),
+            old
+        )));
+    }
+    changes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if dry_run {
+        for change in &changes {
+            print!("{}", diff(repo, change));
+        }
+    } else {
+        for change in &changes {
+            // Written in place to keep the permissions of scripts with inline markers
+            std::fs::write(&change.path, &change.after).map_err(|e| {
+                Error::new(&format!("Failed to write {}: {}", change.path.display(), e))
+            })?;
+        }
+    }
+
+    println!(
+        "{} #{} to #{} in {} rules across {} CODEOWNERS files and {} inline markers",
+        if dry_run { "Would rename" } else { "Renamed" },
+        old,
+        new,
+        rules,
+        codeowners_files,
+        markers
+    );
+    if dry_run {
+        println!("Dry run: no files were changed");
+    }
+
+    Ok(())
+}
+
+/// Add `tag` to the rules for `pattern`, creating a rule if there is none
+///
+/// `pattern` is relative to the repository root. It belongs to the deepest CODEOWNERS
+/// file whose directory contains everything it can match, where it is rewritten relative
+/// to that file. Rules there with the same pattern get the tag; without one, a rule is
+/// appended with `owners`, or else the owners the matching files already have, so that
+/// ownership does not change. With `dry_run` the change is printed as a diff.
+pub fn apply(
+    repo: &Path, tag: &str, pattern: &str, owners: Option<&str>, dry_run: bool,
+) -> Result<()> {
+    let tag = validate_tag(tag)?;
+    let pattern = pattern.trim();
+    if pattern.is_empty() || pattern.chars().any(char::is_whitespace) {
+        return Err(Error::new(&format!("Invalid pattern: {:?}", pattern)));
+    }
+
+    let codeowners_files = find_codeowners_files(repo)?;
+    let (path, relative) = target_file(repo, &codeowners_files, pattern);
+    let mut document = CodeownersDocument::load_or_new(&path)?;
+    let before = document.to_string();
+
+    let mut tagged = 0;
+    let mut matching = 0;
+    for index in 0..document.lines().len() {
+        let Some(entry) = document.lines()[index].entry() else {
+            continue;
+        };
+        if !same_pattern(&entry.pattern, &relative) {
+            continue;
+        }
+        matching += 1;
+        if entry.tags.iter().any(|t| t.0 == tag) {
+            continue;
+        }
+        let mut tags = entry.tags.clone();
+        tags.push(Tag(tag.to_string()));
+        document.set_tags(index, &tags)?;
+        tagged += 1;
+    }
+
+    let display_path = path.strip_prefix(repo).unwrap_or(&path).display();
+    let summary = if matching > 0 && tagged == 0 {
+        println!(
+            "Every rule for {} in {} is already tagged #{}",
+            relative, display_path, tag
+        );
+        return Ok(());
+    } else if matching > 0 {
+        format!(
+            "#{} to {} rules for {} in {}",
+            tag, tagged, relative, display_path
+        )
+    } else {
+        let entry = CodeownersEntry {
+            source_file: path.clone(),
+            line_number: document.lines().len(),
+            pattern: relative.clone(),
+            owners: match owners {
+                Some(owners) => parse_owners(owners)?,
+                None => current_owners(repo, &codeowners_files, &path, &relative)?,
+            },
+            tags: vec![Tag(tag.to_string())],
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        document.push_entry(&entry)?;
+        format!(
+            "a rule for {} tagged #{} to {}",
+            relative, tag, display_path
+        )
+    };
+
+    let change = Change {
+        path: path.clone(),
+        before,
+        after: document.to_string(),
+    };
+    if dry_run {
+        print!("{}", diff(repo, &change));
+        println!("Would add {}", summary);
+        println!("Dry run: no files were changed");
+    } else {
+        document.save(&path)?;
+        println!("Added {}", summary);
+    }
+
+    Ok(())
+}
+
+/// The CODEOWNERS file a repository-relative `pattern` belongs in, and the pattern
+/// rewritten relative to it
+///
+/// Patterns without a directory match at any depth, so they go in the root file.
+/// Otherwise the deepest existing file whose directory holds the pattern's leading
+/// literal directories is used; the rewritten pattern is anchored there with `/`.
+fn target_file(repo: &Path, codeowners_files: &[PathBuf], pattern: &str) -> (PathBuf, String) {
+    let root = (repo.join("CODEOWNERS"), pattern.to_string());
+    let trimmed = pattern.trim_start_matches('/');
+    if !trimmed.trim_end_matches('/').contains('/') {
+        return root;
+    }
+
+    // Directory components before the first glob; the last component may be a file
+    let mut components: Vec<&str> = trimmed.split('/').collect();
+    components.pop();
+    let literal: Vec<&str> = components
+        .into_iter()
+        .take_while(|c| !c.contains(['*', '?', '[']))
+        .collect();
+
+    codeowners_files
+        .iter()
+        .filter_map(|file| {
+            let dir: Vec<String> = file
+                .parent()?
+                .strip_prefix(repo)
+                .ok()?
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let nested = !dir.is_empty()
+                && dir.len() <= literal.len()
+                && dir.iter().zip(&literal).all(|(d, l)| d == l);
+            nested.then_some((dir.len(), file))
+        })
+        .max_by_key(|(depth, _)| *depth)
+        .map(|(depth, file)| {
+            let rest: Vec<&str> = trimmed.split('/').skip(depth).collect();
+            (file.clone(), format!("/{}", rest.join("/")))
+        })
+        .unwrap_or(root)
+}
+
+/// Whether two patterns in the same CODEOWNERS file are the same rule
+///
+/// A leading `/` makes no difference once the pattern has a slash in the middle.
+fn same_pattern(a: &str, b: &str) -> bool {
+    let normalize = |pattern: &str| {
+        let trimmed = pattern.strip_prefix('/').unwrap_or(pattern);
+        if trimmed.trim_end_matches('/').contains('/') {
+            trimmed.to_string()
+        } else {
+            pattern.to_string()
+        }
+    };
+    normalize(a) == normalize(b)
+}
+
+fn parse_owners(owners: &str) -> Result<Vec<Owner>> {
+    owners
+        .split(',')
+        .map(str::trim)
+        .filter(|owner| !owner.is_empty())
+        .map(parse_owner)
+        .collect()
+}
+
+/// Owners that files would keep if a rule for `pattern` were appended to `path`
+///
+/// Every file the rule would decide must have the same owners today; files declaring
+/// inline owners or governed by a closer CODEOWNERS file are left out.
+fn current_owners(
+    repo: &Path, codeowners_files: &[PathBuf], path: &Path, pattern: &str,
+) -> Result<Vec<Owner>> {
+    let config = AppConfig::fetch().unwrap_or_default();
+    let mut entries: Vec<CodeownersEntry> = Vec::new();
+    for file in codeowners_files {
+        entries.extend(parse_codeowners(file)?);
+    }
+    let existing = entries.len();
+    entries.push(CodeownersEntry {
+        source_file: path.to_path_buf(),
+        line_number: usize::MAX,
+        pattern: pattern.to_string(),
+        owners: Vec::new(),
+        tags: Vec::new(),
+        spans: Default::default(),
+        metadata: Default::default(),
+        section: None,
+    });
+
+    let strategy = MatcherStrategy::Override;
+    let with_rule = Matcher::build_with_options(&entries, strategy, config.match_options());
+    let without_rule =
+        Matcher::build_with_options(&entries[..existing], strategy, config.match_options());
+
+    let mut owners: Option<(PathBuf, Vec<Owner>)> = None;
+    for file in find_files(repo)? {
+        if detect_inline_codeowners(&file)?.is_some() {
+            continue;
+        }
+        let (_, _, applied) =
+            resolve_file_with_policy(&file, &entries, &with_rule, config.precedence)?;
+        if !applied.contains(&existing) {
+            continue;
+        }
+        let (current, _, _) = resolve_file_with_policy(
+            &file,
+            &entries[..existing],
+            &without_rule,
+            config.precedence,
+        )?;
+        match &owners {
+            None => owners = Some((file, current)),
+            Some((first, first_owners)) if *first_owners != current => {
+                return Err(Error::new(&format!(
+                    "Files matching {} have different owners ({} is owned by {}, {} by {}); tag their rules or pass --owners",
+                    pattern,
+                    first.strip_prefix(repo).unwrap_or(first).display(),
+                    owner_list(first_owners),
+                    file.strip_prefix(repo).unwrap_or(&file).display(),
+                    owner_list(&current)
+                )));
+            }
+            Some(_) => {}
+        }
+    }
+
+    owners.map(|(_, owners)| owners).ok_or_else(|| {
+        Error::new(&format!(
+            "No file matches {}; pass --owners to create the rule anyway",
+            pattern
+        ))
+    })
+}
+
+fn owner_list(owners: &[Owner]) -> String {
+    if owners.is_empty() {
+        return "nobody".to_string();
+    }
+    owners
+        .iter()
+        .map(|owner| owner.identifier.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Tag name without its leading `#`, rejecting names that cannot be written as a tag
+fn validate_tag(tag: &str) -> Result<&str> {
+    let name = tag_name(tag);
+    if name.is_empty() || name.contains('#') || name.chars().any(char::is_whitespace) {
+        return Err(Error::new(&format!("Invalid tag name: {}", tag)));
+    }
+    Ok(name)
+}
+
+/// Unified diff of a change that rewrites lines in place or appends lines, one hunk per
+/// changed line
+pub(crate) fn diff(repo: &Path, change: &Change) -> String {
+    let path = change.path.strip_prefix(repo).unwrap_or(&change.path);
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path.display(), path.display());
+    let before: Vec<&str> = change.before.lines().collect();
+    let after: Vec<&str> = change.after.lines().collect();
+    for (number, (old, new)) in before.iter().zip(&after).enumerate() {
+        if old != new {
+            out.push_str(&format!(
+                "@@ -{} +{} @@\n-{}\n+{}\n",
+                number + 1,
+                number + 1,
+                old,
+                new
+            ));
+        }
+    }
+    if after.len() > before.len() {
+        out.push_str(&format!(
+            "@@ -{},0 +{},{} @@\n",
+            before.len(),
+            before.len() + 1,
+            after.len() - before.len()
+        ));
+        for line in &after[before.len()..] {
+            out.push_str(&format!("+{}\n", line));
+        }
+    }
+    out
+}
@@ -0,0 +1,78 @@
+use crate::{
+    core::{context::Context, expiry::ExpiryWindow, types::OutputFormat, validate::validate_repo},
+    utils::{
+        error::{Error, Result},
+        output,
+    },
+};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Lint the CODEOWNERS files of `path` and fail if any has a problem
+///
+/// Problems are printed one per line as `file:line: message`, like compiler errors.
+/// Rules expiring within `warning_days` are warnings, which do not fail validation.
+pub fn run(path: &Path, warning_days: u32, format: &OutputFormat) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let expiry = ExpiryWindow {
+        today: output::now().date_naive(),
+        warning_days,
+    };
+    let (files, problems) = validate_repo(path, ctx.config.match_options(), expiry)?;
+    let (warnings, problems): (Vec<_>, Vec<_>) = problems
+        .into_iter()
+        .partition(|problem| problem.is_warning());
+
+    match format {
+        OutputFormat::Text => {
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            for warning in &warnings {
+                println!("{}", warning);
+            }
+            let mut summary = if problems.is_empty() {
+                format!("{} CODEOWNERS files, no problems", files.len())
+            } else {
+                format!(
+                    "{} problems in {} CODEOWNERS files",
+                    problems.len(),
+                    files.len()
+                )
+            };
+            if !warnings.is_empty() {
+                summary.push_str(&format!(", {} warnings", warnings.len()));
+            }
+            println!("{}", summary);
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let report = serde_json::json!({
+                "valid": problems.is_empty(),
+                "files": files,
+                "problems": problems,
+                "warnings": warnings,
+            });
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(Error::new(&format!(
+            "validation failed: {} problems in CODEOWNERS files",
+            problems.len()
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use crate::{
+    core::{
+        cache::sync_cache,
+        context::Context,
+        github::{GitHubClient, GitHubRepo, OwnerStatus},
+        gitlab::{GitLabClient, GitLabProject, ProjectAccess},
+        types::{OutputFormat, Owner, OwnerType},
+    },
+    utils::error::{Error, Result},
+};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use tabled::{Table, Tabled};
+
+/// Code host the owners are verified against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Provider {
+    #[default]
+    GitHub,
+    GitLab,
+}
+
+impl std::fmt::Display for Provider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Provider::GitHub => write!(f, "GitHub"),
+            Provider::GitLab => write!(f, "GitLab"),
+        }
+    }
+}
+
+/// Where the owners are looked up: a client and the repository it checks access to
+enum Host {
+    GitHub(GitHubClient, GitHubRepo),
+    GitLab(GitLabClient, GitLabProject, ProjectAccess),
+}
+
+impl Host {
+    fn provider(&self) -> Provider {
+        match self {
+            Host::GitHub(..) => Provider::GitHub,
+            Host::GitLab(..) => Provider::GitLab,
+        }
+    }
+
+    fn repository(&self) -> String {
+        match self {
+            Host::GitHub(_, repo) => repo.to_string(),
+            Host::GitLab(_, project, _) => project.to_string(),
+        }
+    }
+
+    fn owner_status(&self, owner: &Owner) -> Result<Option<OwnerStatus>> {
+        match self {
+            Host::GitHub(client, repo) => client.owner_status(owner, repo),
+            Host::GitLab(client, _, access) => client.owner_status(owner, access),
+        }
+    }
+
+    fn members(&self, owner: &Owner) -> Result<Option<Vec<String>>> {
+        match self {
+            Host::GitHub(client, _) => client.team_members(owner),
+            Host::GitLab(client, ..) => client.group_members(owner),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct MembersDisplay {
+    #[tabled(rename = "Group")]
+    group: String,
+    #[tabled(rename = "Members")]
+    members: String,
+}
+
+#[derive(Tabled)]
+struct ProblemDisplay {
+    #[tabled(rename = "Owner")]
+    owner: String,
+    #[tabled(rename = "Type")]
+    owner_type: OwnerType,
+    #[tabled(rename = "Status")]
+    status: OwnerStatus,
+    #[tabled(rename = "Files")]
+    files: usize,
+}
+
+/// An owner the code host does not accept
+#[derive(Serialize)]
+struct Problem {
+    owner: String,
+    owner_type: OwnerType,
+    status: OwnerStatus,
+    /// Files the owner owns, which the code host requests no review for
+    files: usize,
+}
+
+/// Ask the code host of `provider` about every user, team and group owning files in the
+/// cache and list those that do not exist, are suspended or, for teams and groups, cannot
+/// access the repository
+///
+/// Email owners cannot be looked up and are skipped. With `expand_groups`, the members of
+/// every valid team or group are listed too. With `offline`, the host's answers come from
+/// the responses cached by an earlier run.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    path: &Path, provider: Provider, github_repo: Option<&GitHubRepo>,
+    gitlab_project: Option<&GitLabProject>, cache_file: Option<&Path>, expand_groups: bool,
+    offline: bool, format: &OutputFormat,
+) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let host = match provider {
+        Provider::GitHub => {
+            let repo = match github_repo {
+                Some(repo) => repo.clone(),
+                None => GitHubRepo::discover(path)?,
+            };
+            Host::GitHub(GitHubClient::new(&ctx, offline), repo)
+        }
+        Provider::GitLab => {
+            let project = match gitlab_project {
+                Some(project) => project.clone(),
+                None => GitLabProject::discover(path)?,
+            };
+            let client = GitLabClient::new(&ctx, offline);
+            let access = client.project_access(&project)?;
+            Host::GitLab(client, project, access)
+        }
+    };
+    let repo = host.repository();
+    let cache = sync_cache(&ctx, path, cache_file)?;
+
+    let mut owners: Vec<_> = cache.owners_map.iter().collect();
+    owners.sort_by(|a, b| a.0.identifier.cmp(&b.0.identifier));
+
+    let mut checked = 0;
+    let mut skipped = Vec::new();
+    let mut problems = Vec::new();
+    let mut groups = std::collections::BTreeMap::new();
+    for (owner, files) in owners {
+        match host.owner_status(owner)? {
+            None => {
+                if owner.owner_type == OwnerType::Email {
+                    skipped.push(owner.identifier.clone());
+                }
+            }
+            Some(status) => {
+                checked += 1;
+                if status == OwnerStatus::Valid && expand_groups {
+                    if let Some(members) = host.members(owner)? {
+                        groups.insert(owner.identifier.clone(), members);
+                    }
+                }
+                if status != OwnerStatus::Valid {
+                    problems.push(Problem {
+                        owner: owner.identifier.clone(),
+                        owner_type: owner.owner_type.clone(),
+                        status,
+                        files: files.len(),
+                    });
+                }
+            }
+        }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            if !problems.is_empty() {
+                let table_data: Vec<ProblemDisplay> = problems
+                    .iter()
+                    .map(|problem| ProblemDisplay {
+                        owner: problem.owner.clone(),
+                        owner_type: problem.owner_type.clone(),
+                        status: problem.status,
+                        files: problem.files,
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+            if !groups.is_empty() {
+                let table_data: Vec<MembersDisplay> = groups
+                    .iter()
+                    .map(|(group, members)| MembersDisplay {
+                        group: group.clone(),
+                        members: if members.is_empty() {
+                            "None".to_string()
+                        } else {
+                            members.join(", ")
+                        },
+                    })
+                    .collect();
+                let mut table = Table::new(table_data);
+                table.with(tabled::settings::Style::modern());
+                println!("{}", table);
+            }
+            println!(
+                "{} of {} owners are valid on {}",
+                checked - problems.len(),
+                checked,
+                repo
+            );
+            if !skipped.is_empty() {
+                println!(
+                    "Skipped {} email owner{}, which {} matches against verified addresses",
+                    skipped.len(),
+                    if skipped.len() == 1 { "" } else { "s" },
+                    host.provider()
+                );
+            }
+        }
+        OutputFormat::Json | OutputFormat::Bincode => {
+            let mut report = serde_json::json!({
+                "repository": repo,
+                "checked": checked,
+                "skipped": skipped,
+                "problems": problems,
+            });
+            if expand_groups {
+                report["groups"] = serde_json::json!(groups);
+            }
+
+            if *format == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            } else {
+                let encoded = bincode::serde::encode_to_vec(&report, bincode::config::standard())
+                    .map_err(|e| Error::new(&format!("Serialization error: {}", e)))?;
+
+                // Write raw binary bytes to stdout
+                io::stdout()
+                    .write_all(&encoded)
+                    .map_err(|e| Error::new(&format!("IO error: {}", e)))?;
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(Error::new(&format!(
+            "{} of {} owners are not valid on {}",
+            problems.len(),
+            checked,
+            repo
+        )));
+    }
+
+    Ok(())
+}
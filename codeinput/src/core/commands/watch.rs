@@ -0,0 +1,108 @@
+use crate::{
+    core::{
+        cache::save_cache,
+        context::Context,
+        parse::{changes_rules, parse_repo, refresh_paths},
+        types::CacheEncoding,
+        watch::{self, Batch, Debouncer, WatchBackendKind},
+    },
+    utils::{error::Result, interrupt},
+};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// How often the watch loop checks for Ctrl-C while nothing changes
+const IDLE_CHECK: Duration = Duration::from_millis(250);
+
+/// Keep the ownership cache of `path` up to date until Ctrl-C or the `--timeout` deadline
+///
+/// The cache is built once, then updated after each batch of changes: only the files
+/// created, modified or removed are resolved again, see [`refresh_paths`]. A change to a
+/// CODEOWNERS file, an ignore file or the tag remapping, or a burst too large to list,
+/// rebuilds it. Changes under `.git` and to the cache itself are ignored. Stopping fails
+/// as interrupted or timed out, so the exit code tells why.
+pub fn run(path: &Path, backend: Option<WatchBackendKind>) -> Result<()> {
+    let ctx = Context::fetch()?;
+    let config = &ctx.config;
+    let cache_file = Path::new(&config.cache_file);
+
+    let mut cache = parse_repo(&ctx, path, cache_file, CacheEncoding::Bincode)?;
+
+    // Backends report paths under the root they watch, so make them readable
+    let root = path.canonicalize()?;
+    let backend = watch::open(
+        &root,
+        backend.unwrap_or(config.watch_backend),
+        config.watch_poll_interval(),
+    )?;
+    let mut debouncer = Debouncer::new(backend, config.debounce_options());
+    println!(
+        "Watching {} with the {} backend, press Ctrl-C to stop",
+        path.display(),
+        debouncer.backend_name()
+    );
+
+    let cache_name = cache_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let keep = |changed: &Path| is_relevant(changed, &cache_name);
+
+    while !interrupt::is_interrupted() {
+        let paths = match debouncer.next_batch(IDLE_CHECK, &keep)? {
+            None => continue,
+            Some(Batch::Paths(paths)) => paths,
+            Some(Batch::Rescan) => {
+                println!("Too many changes to list, rescanning");
+                cache = parse_repo(&ctx, path, cache_file, CacheEncoding::Bincode)?;
+                continue;
+            }
+        };
+        for changed in paths.iter().take(5) {
+            println!(
+                "Changed: {}",
+                changed.strip_prefix(&root).unwrap_or(changed).display()
+            );
+        }
+        if paths.len() > 5 {
+            println!("... and {} more", paths.len() - 5);
+        }
+
+        // Spell the paths like the cache does, under `path`
+        let changed: Vec<PathBuf> = paths
+            .iter()
+            .map(|changed| path.join(changed.strip_prefix(&root).unwrap_or(changed)))
+            .collect();
+        if changed
+            .iter()
+            .any(|changed| changes_rules(&ctx, path, changed, &cache.entries))
+        {
+            cache = parse_repo(&ctx, path, cache_file, CacheEncoding::Bincode)?;
+            continue;
+        }
+        cache = refresh_paths(&ctx, path, cache, &changed)?;
+        save_cache(&cache, &path.join(cache_file), CacheEncoding::Bincode)?;
+        println!(
+            "Updated the cache for {} changed {}",
+            changed.len(),
+            if changed.len() == 1 { "path" } else { "paths" }
+        );
+    }
+
+    interrupt::check()
+}
+
+/// Whether a change can affect ownership: not in `.git`, and not the cache being written
+fn is_relevant(changed: &Path, cache_name: &str) -> bool {
+    if changed
+        .components()
+        .any(|component| component == Component::Normal(".git".as_ref()))
+    {
+        return false;
+    }
+    // The cache is written through a hidden temporary file named after it
+    match changed.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name != cache_name && !name.starts_with(&format!(".{}.", cache_name)),
+        None => true,
+    }
+}
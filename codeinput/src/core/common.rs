@@ -1,11 +1,17 @@
-use crate::utils::error::{Error, Result};
-use git2::{DiffFormat, DiffOptions, Repository};
-use ignore::Walk;
+use super::bitbucket;
+use super::discovery::{self, walker};
+use super::generated::DEFAULT_MARKERS;
+use super::types::{Dialect, MatchOptions, RepoState};
+use crate::utils::{
+    error::{Error, Result},
+    output,
+    warnings::{self, WarningKind},
+};
+use git2::{DiffFormat, DiffOptions, Repository, StatusOptions};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 
-use super::types::{CodeownersEntry, Owner, Tag};
-
 /// Find CODEOWNERS files recursively in the given directory and its subdirectories
 pub fn find_codeowners_files<P: AsRef<Path>>(base_path: P) -> Result<Vec<PathBuf>> {
     let mut result = Vec::new();
@@ -25,46 +31,124 @@ pub fn find_codeowners_files<P: AsRef<Path>>(base_path: P) -> Result<Vec<PathBuf
             }
         }
     }
+    if output::is_stable() {
+        result.sort();
+    }
 
     Ok(result)
 }
 
+/// Find the CODEOWNERS files `dialect` reads in the given directory
+///
+/// GitHub reads every CODEOWNERS file in the tree, Bitbucket only the first of
+/// [`bitbucket::CODEOWNERS_LOCATIONS`].
+pub fn find_dialect_codeowners_files<P: AsRef<Path>>(
+    base_path: P, dialect: Dialect,
+) -> Result<Vec<PathBuf>> {
+    match dialect {
+        Dialect::GitHub => find_codeowners_files(base_path),
+        Dialect::Bitbucket => Ok(bitbucket::find_codeowners_file(base_path.as_ref())
+            .into_iter()
+            .collect()),
+    }
+}
+
 /// Find all files in the given directory and its subdirectories
+///
+/// Directories and files that cannot be read are skipped with a warning.
 pub fn find_files<P: AsRef<Path>>(base_path: P) -> Result<Vec<PathBuf>> {
-    let result = Walk::new(base_path)
-        .filter_map(|entry| entry.ok())
+    let mut result = walker(base_path.as_ref())
+        .build()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warnings::warn(WarningKind::UnreadableFile, format!("Skipping: {}", e));
+                None
+            }
+        })
         .filter(|e| e.path().is_file())
         .filter(|e| e.clone().file_name().to_str().unwrap() != "CODEOWNERS")
         .map(|entry| entry.into_path())
         .collect::<Vec<_>>();
+    if output::is_stable() {
+        result.sort();
+    }
 
     Ok(result)
 }
 
-/// Collect all unique owners from CODEOWNERS entries
-pub fn collect_owners(entries: &[CodeownersEntry]) -> Vec<Owner> {
-    let mut owners = std::collections::HashSet::new();
-
-    for entry in entries {
-        for owner in &entry.owners {
-            owners.insert(owner.clone());
-        }
+/// Whether [`find_files`] would list the file at `path`: it exists, is not ignored and
+/// is not a CODEOWNERS file
+pub fn is_listed_file(path: &Path) -> bool {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return false;
+    };
+    if !path.is_file() || name == "CODEOWNERS" {
+        return false;
     }
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    // A file walked on its own is listed even when ignored, so walk its directory
+    walker(parent)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.depth() == 1 && entry.file_name() == name)
+}
+
+/// Replace `path` with the output of `write`, never leaving a partially written file
+///
+/// Output goes to a temporary file in the same directory, which is renamed over `path`
+/// only once `write` succeeds; on failure (including Ctrl-C) it is removed and any
+/// existing file is left as it was.
+pub fn write_file_atomically<F>(path: &Path, write: F) -> Result<()>
+where
+    F: FnOnce(&mut std::io::BufWriter<std::fs::File>) -> Result<()>,
+{
+    use std::io::Write;
 
-    owners.into_iter().collect()
+    replace_file_atomically(path, |temp_path| {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(temp_path)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        Ok(())
+    })
 }
 
-/// Collect all unique tags from CODEOWNERS entries
-pub fn collect_tags(entries: &[CodeownersEntry]) -> Vec<Tag> {
-    let mut tags = std::collections::HashSet::new();
+/// Replace `path` with the file `create` writes at the temporary path it is given
+///
+/// Like [`write_file_atomically`], for files written by a library that opens them itself.
+pub(crate) fn replace_file_atomically<F>(path: &Path, create: F) -> Result<()>
+where
+    F: FnOnce(&Path) -> Result<()>,
+{
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        Some(_) => Path::new("."),
+        None => return Err(Error::new(&format!("Invalid path: {}", path.display()))),
+    };
+    std::fs::create_dir_all(parent)?;
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::new(&format!("Invalid path: {}", path.display())))?;
+    let temp_path = parent.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
 
-    for entry in entries {
-        for tag in &entry.tags {
-            tags.insert(tag.clone());
+    match create(&temp_path).and_then(|()| Ok(std::fs::rename(&temp_path, path)?)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            Err(e)
         }
     }
-
-    tags.into_iter().collect()
 }
 
 pub fn get_repo_hash(repo_path: &Path) -> Result<[u8; 32]> {
@@ -75,7 +159,7 @@ pub fn get_repo_hash(repo_path: &Path) -> Result<[u8; 32]> {
     let head_oid = repo
         .head()
         .and_then(|r| r.resolve())
-        .and_then(|r| Ok(r.target()))
+        .map(|r| r.target())
         .unwrap_or(None);
 
     // 2. Get index/staging area tree hash
@@ -108,17 +192,235 @@ pub fn get_repo_hash(repo_path: &Path) -> Result<[u8; 32]> {
     let mut hasher = Sha256::new();
     hasher.update(head_oid.unwrap_or(git2::Oid::zero()).as_bytes());
     hasher.update(index_tree.as_bytes());
-    hasher.update(&unstaged_hash);
+    hasher.update(unstaged_hash);
 
     Ok(hasher.finalize().into())
 }
 
+/// The most recent commit reachable from HEAD that changed `path`, relative to the
+/// repository root
+///
+/// Follows first parents only, so a change merged in from a branch is attributed to the
+/// merge commit. Returns `None` for an unborn HEAD or a path no commit touched.
+pub fn last_commit_touching<'r>(
+    repo: &'r Repository, path: &Path,
+) -> Result<Option<git2::Commit<'r>>> {
+    let mut commit = match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => commit,
+        Err(_) => return Ok(None),
+    };
+
+    loop {
+        let blob = commit.tree()?.get_path(path).ok().map(|entry| entry.id());
+        let parent = commit.parent(0).ok();
+        let parent_blob = match &parent {
+            Some(parent) => parent.tree()?.get_path(path).ok().map(|entry| entry.id()),
+            None => None,
+        };
+
+        if blob != parent_blob {
+            return Ok(Some(commit));
+        }
+        match parent {
+            Some(parent) => commit = parent,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// Files changed on the current branch since it left `base`, relative to the repository
+/// root
+///
+/// Compares the merge base of `base` and HEAD with the working tree, like
+/// `git diff base...` with uncommitted changes included. Deleted files are listed under
+/// their old path; untracked files are not listed. The result is sorted.
+pub fn changed_files_since(repo_path: &Path, base: &str) -> Result<Vec<PathBuf>> {
+    let repo = Repository::open(repo_path)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let merge_base = merge_base_of(&repo, base)?;
+    let tree = repo.find_commit(merge_base)?.tree()?;
+
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), None)?;
+    let mut paths: Vec<PathBuf> = diff
+        .deltas()
+        .filter_map(|delta| match delta.status() {
+            git2::Delta::Deleted => delta.old_file().path(),
+            _ => delta.new_file().path(),
+        })
+        .map(Path::to_path_buf)
+        .collect();
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// The commit [`changed_files_since`] compares the working tree with: the merge base of
+/// `base` and HEAD
+pub fn merge_base(repo_path: &Path, base: &str) -> Result<String> {
+    let repo = Repository::open(repo_path)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    Ok(merge_base_of(&repo, base)?.to_string())
+}
+
+fn merge_base_of(repo: &Repository, base: &str) -> Result<git2::Oid> {
+    let base_commit = repo
+        .revparse_single(base)
+        .and_then(|object| object.peel_to_commit())
+        .map_err(|e| Error::new(&format!("Unknown base revision {}: {}", base, e.message())))?;
+    let head = repo.head()?.peel_to_commit()?;
+    Ok(repo.merge_base(base_commit.id(), head.id())?)
+}
+
+/// The HEAD commit of the repository at `repo_path` and the paths that differ from it
+pub fn repo_state(repo_path: &Path) -> Result<RepoState> {
+    let repo = Repository::open(repo_path)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+
+    let mut options = StatusOptions::new();
+    options
+        .include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(false)
+        .renames_head_to_index(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .map_err(|e| Error::with_source("Failed to read the status", Box::new(e)))?;
+    let mut changed: Vec<PathBuf> = statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(PathBuf::from))
+        .collect();
+    changed.sort();
+    changed.dedup();
+
+    Ok(RepoState { head, changed })
+}
+
+/// Paths relative to the root of the repository at `repo_path` that may differ between
+/// `since` and now, or `None` when that cannot be told, as when the commit of `since` is
+/// gone
+pub fn changed_since(repo_path: &Path, since: &RepoState) -> Result<Option<Vec<PathBuf>>> {
+    let repo = Repository::open(repo_path)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let now = repo_state(repo_path)?;
+
+    let tree = |head: &str| -> Result<Option<Option<git2::Tree<'_>>>> {
+        if head.is_empty() {
+            return Ok(Some(None));
+        }
+        let Ok(oid) = git2::Oid::from_str(head) else {
+            return Ok(None);
+        };
+        Ok(repo
+            .find_commit(oid)
+            .ok()
+            .map(|commit| commit.tree())
+            .transpose()?
+            .map(Some))
+    };
+    let (Some(old_tree), Some(new_tree)) = (tree(&since.head)?, tree(&now.head)?) else {
+        return Ok(None);
+    };
+
+    let mut changed: BTreeSet<PathBuf> = since.changed.iter().cloned().collect();
+    changed.extend(now.changed);
+    if since.head != now.head {
+        let diff = repo.diff_tree_to_tree(old_tree.as_ref(), new_tree.as_ref(), None)?;
+        changed.extend(
+            diff.deltas()
+                .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+                .flatten()
+                .map(Path::to_path_buf),
+        );
+    }
+    Ok(Some(changed.into_iter().collect()))
+}
+
+/// Hash identifying a cache built for the current repository state, match options and
+/// generated-code markers
+///
+/// Settings that change what the cache records are mixed in so that changing them, for
+/// example enabling `case_insensitive`, invalidates existing caches. The default settings
+/// leave the repository hash unchanged.
+pub fn get_cache_hash(
+    repo_path: &Path, options: MatchOptions, generated_markers: &[String],
+) -> Result<[u8; 32]> {
+    let repo_hash = get_repo_hash(repo_path)?;
+    let (global_gitignore, git_exclude) = discovery::current();
+    if options == MatchOptions::default()
+        && generated_markers == DEFAULT_MARKERS
+        && global_gitignore
+        && git_exclude
+    {
+        return Ok(repo_hash);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(repo_hash);
+    hasher.update([options.case_insensitive as u8]);
+    if options.dialect != Dialect::GitHub {
+        hasher.update(options.dialect.to_string().as_bytes());
+    }
+    for marker in generated_markers {
+        hasher.update(marker.as_bytes());
+        hasher.update([0]);
+    }
+    // Caches listing files ignored only outside the repository are kept apart
+    if !global_gitignore || !git_exclude {
+        hasher.update(b"discovery");
+        hasher.update([global_gitignore as u8, git_exclude as u8]);
+    }
+    Ok(hasher.finalize().into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::commands::demo;
     use std::fs::{self, File};
     use tempfile::TempDir;
 
+    #[test]
+    fn test_last_commit_touching() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        demo::generate(temp_dir.path())?;
+        let repo = Repository::open(temp_dir.path())?;
+
+        let summary = |path: &str| -> Result<Option<String>> {
+            Ok(last_commit_touching(&repo, Path::new(path))?
+                .map(|commit| commit.summary().unwrap_or_default().to_string()))
+        };
+        assert_eq!(summary("CODEOWNERS")?.as_deref(), Some("Add CODEOWNERS"));
+        assert_eq!(
+            summary("services/api/src/routes.rs")?.as_deref(),
+            Some("Add version route")
+        );
+        assert_eq!(summary("README.md")?.as_deref(), Some("Add documentation"));
+        assert_eq!(summary("missing.txt")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_changed_files_since() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        demo::generate(temp_dir.path())?;
+
+        fs::write(temp_dir.path().join("README.md"), "# Changed\n")?;
+        fs::remove_file(temp_dir.path().join("CODEOWNERS"))?;
+        fs::write(temp_dir.path().join("untracked.txt"), "new\n")?;
+        assert_eq!(
+            changed_files_since(temp_dir.path(), "HEAD")?,
+            [PathBuf::from("CODEOWNERS"), PathBuf::from("README.md")]
+        );
+        assert!(changed_files_since(temp_dir.path(), "no-such-branch").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_find_codeowners_files() -> Result<()> {
         // Create a temporary directory structure
@@ -161,6 +463,27 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_write_file_atomically_keeps_old_file_on_error() -> Result<()> {
+        use std::io::Write;
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("out.txt");
+
+        write_file_atomically(&path, |w| Ok(w.write_all(b"first")?))?;
+        assert_eq!(fs::read_to_string(&path)?, "first");
+
+        let result = write_file_atomically(&path, |w| {
+            w.write_all(b"partial")?;
+            Err(Error::new("failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path)?, "first");
+        assert_eq!(fs::read_dir(temp_dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_find_codeowners_files_nonexistent_dir() -> Result<()> {
         let nonexistent_dir = PathBuf::from("/nonexistent/directory");
@@ -0,0 +1,260 @@
+//! Dynamic completion of option values, behind `codeinput complete`
+//!
+//! The generated completion scripts know the commands and options but not the values
+//! they take. Their hooks ([`BASH_HOOK`], [`ZSH_HOOK`], [`FISH_HOOK`]) pass the command
+//! line and the cursor position to `codeinput complete`, which answers with the owners,
+//! tags or paths from the cache that fit the word being typed, one per line. When it
+//! prints nothing, the hooks fall back to the static completion.
+
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use super::types::CodeownersCache;
+
+/// Bash hook, appended to `completion bash`
+pub const BASH_HOOK: &str = r#"
+_codeinput_dynamic() {
+    local IFS=$'\n'
+    local values
+    values=$(codeinput complete --position "$COMP_CWORD" -- "${COMP_WORDS[@]}" 2>/dev/null)
+    if [[ -n "$values" ]]; then
+        COMPREPLY=($values)
+    else
+        _codeinput "$@"
+    fi
+}
+complete -F _codeinput_dynamic -o nosort -o bashdefault -o default codeinput
+"#;
+
+/// Zsh hook, appended to `completion zsh`
+pub const ZSH_HOOK: &str = r#"
+_codeinput_dynamic() {
+    local -a values
+    values=(${(f)"$(codeinput complete --position $((CURRENT - 1)) -- "${words[@]}" 2>/dev/null)"})
+    if (( ${#values} )); then
+        compadd -Q -S '' -a values
+    else
+        _codeinput "$@"
+    fi
+}
+compdef _codeinput_dynamic codeinput
+"#;
+
+/// Fish hook, appended to `completion fish`
+pub const FISH_HOOK: &str = r#"
+complete -c codeinput -f -a '(codeinput complete --position (count (commandline -opc)) -- (commandline -opc) (commandline -ct) 2>/dev/null)'
+"#;
+
+/// What an option's value is completed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    /// Owners in the cache; comma-separated lists complete their last item
+    Owners,
+    /// Tags in the cache, as lists like owners
+    Tags,
+    /// File and directory paths in the cache, one directory level at a time
+    Paths,
+    /// A fixed set of values
+    Choices(&'static [&'static str]),
+}
+
+/// The value kind of `option`, for options whose values can be completed
+pub fn option_value_kind(option: &str) -> Option<ValueKind> {
+    match option {
+        "--owners" | "--owner" => Some(ValueKind::Owners),
+        "--tags" | "--tag" => Some(ValueKind::Tags),
+        "--path" | "--scope" | "--a" | "--b" => Some(ValueKind::Paths),
+        "--format" => Some(ValueKind::Choices(&["text", "json", "bincode"])),
+        "--dialect" => Some(ValueKind::Choices(&["github", "bitbucket"])),
+        _ => None,
+    }
+}
+
+/// The word being completed: what it completes to, the part kept as typed and the prefix
+/// of the value to complete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub kind: ValueKind,
+    /// Kept in front of every candidate: an `--option=` and earlier list items
+    pub head: String,
+    pub prefix: String,
+}
+
+/// What the word at `position` of `args` completes to, if its value can be completed
+///
+/// `args` is the command line as the shell splits it, program name first; a position past
+/// its end is a new, empty word. Values of options are completed by their kind. With
+/// `positional`, when the word is an argument of a command rather than a subcommand name,
+/// words starting with `@` complete as owners and other words as paths.
+pub fn completion_target(args: &[String], position: usize, positional: bool) -> Option<Target> {
+    let current = args.get(position).map(String::as_str).unwrap_or_default();
+    let word_before = |offset: usize| {
+        position
+            .checked_sub(offset)
+            .and_then(|index| args.get(index))
+            .map(String::as_str)
+    };
+    // Bash splits `--option=value` into three words
+    let previous = match word_before(1) {
+        Some("=") => word_before(2),
+        previous => previous,
+    };
+
+    let (kind, head, value) = if let Some((option, value)) = current
+        .strip_prefix("--")
+        .and_then(|rest| rest.split_once('='))
+    {
+        (
+            option_value_kind(&format!("--{}", option))?,
+            format!("--{}=", option),
+            value,
+        )
+    } else if let Some(kind) = previous.and_then(option_value_kind) {
+        (kind, String::new(), current)
+    } else if !positional || current.starts_with('-') {
+        return None;
+    } else if current.starts_with('@') {
+        (ValueKind::Owners, String::new(), current)
+    } else {
+        (ValueKind::Paths, String::new(), current)
+    };
+
+    let (head, prefix) = match kind {
+        ValueKind::Owners | ValueKind::Tags => match value.rsplit_once(',') {
+            Some((items, last)) => (format!("{}{},", head, items), last),
+            None => (head, value),
+        },
+        _ => (head, value),
+    };
+    Some(Target {
+        kind,
+        head,
+        prefix: prefix.to_string(),
+    })
+}
+
+/// Candidates for `target`, sorted, each with the target's head
+///
+/// Paths are completed relative to `dir`, the directory the command line is typed in,
+/// relative to the root of the repository the cache is of. Without a cache only fixed
+/// choices complete.
+pub fn candidates(cache: Option<&CodeownersCache>, target: &Target, dir: &Path) -> Vec<String> {
+    let values: BTreeSet<String> = match (target.kind, cache) {
+        (ValueKind::Choices(choices), _) => choices.iter().map(|c| c.to_string()).collect(),
+        (ValueKind::Owners, Some(cache)) => cache
+            .owners_map
+            .keys()
+            .map(|owner| owner.identifier.clone())
+            .collect(),
+        (ValueKind::Tags, Some(cache)) => cache.tags_map.keys().map(|tag| tag.0.clone()).collect(),
+        (ValueKind::Paths, Some(cache)) => cache
+            .files
+            .iter()
+            .filter_map(|file| path_candidate(&file.path, dir, &target.prefix))
+            .collect(),
+        (_, None) => BTreeSet::new(),
+    };
+    values
+        .into_iter()
+        .filter(|value| value.starts_with(&target.prefix))
+        .map(|value| format!("{}{}", target.head, value))
+        .collect()
+}
+
+/// `path`, relative to `dir`, completed from `prefix` up to the next directory, or whole
+/// if it is in the directory being typed
+fn path_candidate(path: &Path, dir: &Path, prefix: &str) -> Option<String> {
+    let path: PathBuf = path
+        .components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect();
+    let relative: Vec<String> = path
+        .strip_prefix(dir)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    let relative = relative.join("/");
+    let rest = relative.strip_prefix(prefix)?;
+    Some(match rest.find('/') {
+        Some(slash) => relative[..prefix.len() + slash + 1].to_string(),
+        None => relative,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{FileEntry, Owner, OwnerType, Tag};
+    use std::path::PathBuf;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split(' ').map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_completion() {
+        let file = |path: &str, owner: &str, tag: &str| FileEntry {
+            path: PathBuf::from(path),
+            owners: vec![Owner {
+                identifier: owner.to_string(),
+                owner_type: OwnerType::User,
+            }],
+            tags: vec![Tag(tag.to_string())],
+            generated: false,
+            inline: None,
+        };
+        let cache = CodeownersCache::new(
+            [0; 32],
+            Vec::new(),
+            vec![
+                file("./src/api/lib.rs", "@alice", "api"),
+                file("./src/main.rs", "@alan", "rust"),
+                file("./README.md", "@bob", "docs"),
+            ],
+            Vec::new(),
+        );
+        let complete = |line: &str, position| {
+            completion_target(&args(line), position, true)
+                .map(|target| candidates(Some(&cache), &target, Path::new("")))
+                .unwrap_or_default()
+        };
+
+        let line = "codeinput codeowners list-files --owners @al";
+        assert_eq!(complete(line, 4), ["@alan", "@alice"]);
+        assert_eq!(
+            complete("codeinput codeowners list-files --owners=@bob,@ali", 3),
+            ["--owners=@bob,@alice"]
+        );
+        assert_eq!(
+            complete("codeinput codeowners list-files --tags", 4),
+            ["api", "docs", "rust"]
+        );
+        assert_eq!(
+            complete("codeinput codeowners inspect src/", 3),
+            ["src/api/", "src/main.rs"]
+        );
+        let target = completion_target(&args("codeinput codeowners inspect "), 3, true).unwrap();
+        assert_eq!(
+            candidates(Some(&cache), &target, Path::new("src")),
+            ["api/", "main.rs"]
+        );
+        assert_eq!(
+            complete("codeinput codeowners list-files --format j", 4),
+            ["json"]
+        );
+        assert!(complete("codeinput codeowners list-files --unow", 3).is_empty());
+
+        assert_eq!(
+            complete("codeinput codeowners list-files --tags = docs,r", 5),
+            ["docs,rust"]
+        );
+        assert_eq!(
+            completion_target(&args("codeinput codeowners s"), 2, false),
+            None
+        );
+        let target =
+            completion_target(&args("codeinput codeowners ls --owners @"), 4, false).unwrap();
+        assert!(candidates(None, &target, Path::new("")).is_empty());
+    }
+}
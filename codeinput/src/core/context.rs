@@ -0,0 +1,59 @@
+//! Settings passed explicitly to the core
+//!
+//! Core functions take a [`Context`] instead of reading the process-wide configuration, so
+//! one process can work on several repositories with different settings. Commands build
+//! theirs from the global configuration when they start.
+
+use crate::utils::{app_config::AppConfig, error::Result};
+
+/// Configuration a core operation runs with
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub config: AppConfig,
+}
+
+impl Context {
+    pub fn new(config: AppConfig) -> Context {
+        Context { config }
+    }
+
+    /// Context for the global configuration, as initialized by the binary
+    pub fn fetch() -> Result<Context> {
+        AppConfig::fetch().map(Context::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parse::resolve_paths;
+    use crate::core::types::OwnerType;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_contexts_are_independent() {
+        let repo = TempDir::new().unwrap();
+        std::fs::write(repo.path().join("CODEOWNERS"), "readme.md @docs\n").unwrap();
+        std::fs::write(repo.path().join("README.md"), "# Demo\n").unwrap();
+
+        let owners = |ctx: &Context| {
+            let cache = resolve_paths(ctx, repo.path(), &[PathBuf::from("README.md")]).unwrap();
+            cache.files[0]
+                .owners
+                .iter()
+                .filter(|owner| owner.owner_type != OwnerType::Unowned)
+                .map(|owner| owner.identifier.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Two settings side by side in one process, without touching the global config
+        let sensitive = Context::default();
+        let insensitive = Context::new(AppConfig {
+            case_insensitive: true,
+            ..AppConfig::default()
+        });
+        assert!(owners(&sensitive).is_empty());
+        assert_eq!(owners(&insensitive), vec!["@docs"]);
+    }
+}
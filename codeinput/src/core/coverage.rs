@@ -0,0 +1,136 @@
+//! Ownership coverage per top-level directory, for `codeowners coverage`
+//!
+//! Coverage is the share of files with an owner. Generated files are left out, as in the
+//! coverage check of `codeowners gate`, so checked-in build output does not count as
+//! ownership debt.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::types::{FileEntry, OwnerType};
+
+/// Name of the group of files directly in the analyzed directory
+pub const ROOT_FILES: &str = ".";
+
+/// Owned and unowned files of a directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DirectoryCoverage {
+    pub directory: String,
+    /// Files counted for coverage, generated files excluded
+    pub files: usize,
+    pub owned: usize,
+    /// Generated files, not counted
+    pub generated: usize,
+}
+
+impl DirectoryCoverage {
+    pub fn unowned(&self) -> usize {
+        self.files - self.owned
+    }
+
+    /// Percentage of counted files that are owned; 100 when no file counts
+    pub fn coverage(&self) -> f64 {
+        match self.files {
+            0 => 100.0,
+            files => self.owned as f64 * 100.0 / files as f64,
+        }
+    }
+
+    fn add(&mut self, file: &FileEntry) {
+        if file.generated {
+            self.generated += 1;
+        } else {
+            self.files += 1;
+            if is_owned(file) {
+                self.owned += 1;
+            }
+        }
+    }
+}
+
+/// Coverage of every file under `root`, and of each top-level directory below it in name
+/// order, with files directly in `root` grouped as [`ROOT_FILES`]
+pub fn coverage_by_directory<'a>(
+    root: &Path, files: impl IntoIterator<Item = &'a FileEntry>,
+) -> (DirectoryCoverage, Vec<DirectoryCoverage>) {
+    let mut total = DirectoryCoverage {
+        directory: ROOT_FILES.to_string(),
+        ..Default::default()
+    };
+    let mut directories: BTreeMap<String, DirectoryCoverage> = BTreeMap::new();
+    for file in files {
+        let Ok(relative) = file.path.strip_prefix(root) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(first) = components.next() else {
+            continue;
+        };
+        let directory = match components.next() {
+            Some(_) => first.as_os_str().to_string_lossy().into_owned(),
+            None => ROOT_FILES.to_string(),
+        };
+        total.add(file);
+        directories
+            .entry(directory.clone())
+            .or_insert_with(|| DirectoryCoverage {
+                directory,
+                ..Default::default()
+            })
+            .add(file);
+    }
+    (total, directories.into_values().collect())
+}
+
+fn is_owned(file: &FileEntry) -> bool {
+    file.owners
+        .iter()
+        .any(|owner| owner.owner_type != OwnerType::Unowned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::Owner;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_coverage_by_directory() {
+        let file = |path: &str, owned: bool, generated: bool| FileEntry {
+            path: PathBuf::from(path),
+            owners: if owned {
+                vec![Owner {
+                    identifier: "@team".to_string(),
+                    owner_type: OwnerType::Team,
+                }]
+            } else {
+                Vec::new()
+            },
+            tags: Vec::new(),
+            generated,
+            inline: None,
+        };
+        let files = vec![
+            file("./README.md", false, false),
+            file("./src/lib.rs", true, false),
+            file("./src/api/routes.rs", false, false),
+            file("./src/schema.rs", false, true),
+            file("./docs/guide.md", true, false),
+        ];
+
+        let (total, directories) = coverage_by_directory(Path::new("."), &files);
+        assert_eq!((total.files, total.owned, total.generated), (4, 2, 1));
+        assert_eq!(total.coverage(), 50.0);
+        let summary: Vec<_> = directories
+            .iter()
+            .map(|dir| (dir.directory.as_str(), dir.files, dir.owned, dir.coverage()))
+            .collect();
+        assert_eq!(
+            summary,
+            [(".", 1, 0, 0.0), ("docs", 1, 1, 100.0), ("src", 2, 1, 50.0)]
+        );
+        assert_eq!(directories[2].generated, 1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Delegation of directories to other owners, recorded with `DELEGATE` in CODEOWNERS
+//!
+//! ```text
+//! DELEGATE payments/ @org/payments
+//! ```
+//!
+//! hands the directory, relative to the CODEOWNERS file, to the named owners, who are
+//! expected to maintain its ownership, usually in a CODEOWNERS file of their own. A
+//! delegation is a record only: it is not a rule and matches no files.
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serialize;
+
+use super::common::find_codeowners_files;
+use super::parser::{parse_owner, read_codeowners, tokenize, Token};
+use super::types::{Owner, OwnerType};
+use crate::utils::error::Result;
+
+/// Keyword starting a delegation line
+pub const DELEGATE: &str = "DELEGATE";
+
+/// A directory handed to other owners
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Delegation {
+    pub source_file: PathBuf,
+    /// 0-based, like `CodeownersEntry::line_number`
+    pub line_number: usize,
+    /// Directory as written, relative to the CODEOWNERS file
+    pub directory: String,
+    pub delegates: Vec<Owner>,
+}
+
+impl Delegation {
+    /// The delegated directory, joined to the directory of the CODEOWNERS file
+    pub fn target(&self) -> PathBuf {
+        let base = self.source_file.parent().unwrap_or(Path::new(""));
+        normalize(&base.join(self.directory.trim_matches('/')))
+    }
+
+    /// Whether `dir` is the delegated directory
+    pub fn is_for(&self, dir: &Path) -> bool {
+        self.target() == normalize(dir)
+    }
+
+    /// Why the delegation is invalid: a missing directory or missing or malformed
+    /// delegates
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.directory.is_empty() {
+            problems.push("delegation names no directory".to_string());
+        } else if !self.target().is_dir() {
+            problems.push(format!(
+                "delegated directory {} does not exist",
+                self.directory
+            ));
+        }
+        if self.delegates.is_empty() {
+            problems.push(format!("delegation of {} names no owners", self.directory));
+        }
+        for delegate in &self.delegates {
+            if delegate.owner_type == OwnerType::Unknown {
+                problems.push(format!("malformed delegate {}", delegate.identifier));
+            }
+        }
+        problems
+    }
+}
+
+/// Parse a `DELEGATE <directory> <owner>...` line; other lines are `None`
+pub fn parse_delegation(
+    line: &str, line_number: usize, source_path: &Path,
+) -> Result<Option<Delegation>> {
+    let mut words = tokenize(line).map_while(|(token, _)| match token {
+        Token::Word(word) => Some(word),
+        Token::Hash(_) => None,
+    });
+    if words.next() != Some(DELEGATE) {
+        return Ok(None);
+    }
+
+    let directory = words.next().unwrap_or_default().to_string();
+    let delegates = words.map(parse_owner).collect::<Result<Vec<_>>>()?;
+    Ok(Some(Delegation {
+        source_file: source_path.to_path_buf(),
+        line_number,
+        directory,
+        delegates,
+    }))
+}
+
+/// Delegations in every CODEOWNERS file of `repo`, in file and line order
+///
+/// Files that cannot be read are skipped, as when the cache is built.
+pub fn find_delegations(repo: &Path) -> Result<Vec<Delegation>> {
+    let mut delegations = Vec::new();
+    let mut files = find_codeowners_files(repo)?;
+    files.sort();
+    for file in files {
+        let Ok(content) = read_codeowners(&file) else {
+            continue;
+        };
+        for (line_number, line) in content.lines().enumerate() {
+            delegations.extend(parse_delegation(line, line_number, &file)?);
+        }
+    }
+    Ok(delegations)
+}
+
+/// `path` without `.` components, so targets compare equal to cached paths
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_delegations() -> Result<()> {
+        let dir = TempDir::new()?;
+        let services = dir.path().join("services");
+        std::fs::create_dir_all(services.join("billing"))?;
+        std::fs::write(
+            services.join("CODEOWNERS"),
+            "* @org/platform\n\
+             DELEGATE billing/ @org/billing # since the reorg\n\
+             DELEGATE /search/ @org/search not-an-owner\n\
+             DELEGATE\n",
+        )?;
+
+        let delegations = find_delegations(dir.path())?;
+        assert_eq!(delegations.len(), 3);
+        assert_eq!(delegations[0].line_number, 1);
+        assert_eq!(delegations[0].target(), services.join("billing"));
+        assert!(delegations[0].is_for(&services.join("./billing")));
+        assert_eq!(delegations[0].delegates[0].identifier, "@org/billing");
+        assert!(delegations[0].problems().is_empty());
+        assert_eq!(
+            delegations[1].problems(),
+            [
+                "delegated directory /search/ does not exist",
+                "malformed delegate not-an-owner"
+            ]
+        );
+        assert_eq!(
+            delegations[2].problems(),
+            [
+                "delegation names no directory",
+                "delegation of  names no owners"
+            ]
+        );
+
+        // Delegations are not rules
+        let entries = parse_content(
+            &std::fs::read_to_string(services.join("CODEOWNERS"))?,
+            &services.join("CODEOWNERS"),
+        )?;
+        assert_eq!(entries.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+//! Which ignore files decide the files of a repository
+//!
+//! Files are discovered with the rules of git: the repository's `.gitignore` files always
+//! apply, and by default so do the user's global gitignore (git's `core.excludesFile`) and
+//! the repository's `.git/info/exclude`. Those two are specific to one machine, so a
+//! developer's junk files stay out of local runs; turning them off with [`set`] lists the
+//! same files as a clean CI checkout.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use git2::Repository;
+use ignore::WalkBuilder;
+
+/// Whether the global gitignore applies, set by [`set`]
+static GLOBAL_GITIGNORE: AtomicBool = AtomicBool::new(true);
+
+/// Whether `.git/info/exclude` applies, set by [`set`]
+static GIT_EXCLUDE: AtomicBool = AtomicBool::new(true);
+
+/// Set whether the global gitignore and `.git/info/exclude` apply, for the rest of the
+/// process
+pub fn set(global_gitignore: bool, git_exclude: bool) {
+    GLOBAL_GITIGNORE.store(global_gitignore, Ordering::Relaxed);
+    GIT_EXCLUDE.store(git_exclude, Ordering::Relaxed);
+}
+
+/// Whether the global gitignore and `.git/info/exclude` apply
+pub fn current() -> (bool, bool) {
+    (
+        GLOBAL_GITIGNORE.load(Ordering::Relaxed),
+        GIT_EXCLUDE.load(Ordering::Relaxed),
+    )
+}
+
+/// Working directory of the git repository holding `dir`, `None` outside one or in a
+/// bare repository
+pub fn repo_root(dir: &Path) -> Option<PathBuf> {
+    Repository::discover(dir)
+        .ok()?
+        .workdir()
+        .map(Path::to_path_buf)
+}
+
+/// A walk of `path` honoring the ignore files that apply
+pub fn walker(path: &Path) -> WalkBuilder {
+    let (global_gitignore, git_exclude) = current();
+    let mut builder = WalkBuilder::new(path);
+    builder
+        .git_global(global_gitignore)
+        .git_exclude(git_exclude);
+    builder
+}
@@ -0,0 +1,547 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::common::write_file_atomically;
+use super::parser::{format_line, parse_line, read_codeowners};
+use super::types::{CodeownersEntry, Owner, Tag};
+use crate::utils::error::{Error, Result};
+
+/// Lossless model of a CODEOWNERS file
+///
+/// Every line is kept verbatim together with its line ending, so writing a document back
+/// reproduces the file byte for byte; only lines that are explicitly edited change.
+/// Rewriting tools use this instead of regenerating the file from parsed entries, which
+/// would drop comments, blank lines and alignment.
+#[derive(Debug)]
+pub struct CodeownersDocument {
+    source_file: PathBuf,
+    lines: Vec<DocumentLine>,
+}
+
+/// A single line of a [`CodeownersDocument`]
+#[derive(Debug)]
+pub struct DocumentLine {
+    text: String,
+    ending: &'static str,
+    kind: LineKind,
+}
+
+/// What a document line holds
+#[derive(Debug)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    Rule(Box<CodeownersEntry>),
+}
+
+/// A run of non-blank lines, usually a comment heading followed by related rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Section {
+    /// Index of the first line
+    pub start: usize,
+    /// Index one past the last line
+    pub end: usize,
+}
+
+impl DocumentLine {
+    /// Line text without its line ending
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn kind(&self) -> &LineKind {
+        &self.kind
+    }
+
+    /// The rule on this line, if it is one
+    pub fn entry(&self) -> Option<&CodeownersEntry> {
+        match &self.kind {
+            LineKind::Rule(entry) => Some(entry.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl CodeownersDocument {
+    /// Parse `content`, read from `source_path`
+    pub fn parse(content: &str, source_path: &Path) -> Result<Self> {
+        let mut document = CodeownersDocument {
+            source_file: source_path.to_path_buf(),
+            lines: Vec::new(),
+        };
+
+        for raw in content.split_inclusive('\n') {
+            let (text, ending) = match raw.strip_suffix("\r\n") {
+                Some(text) => (text, "\r\n"),
+                None => match raw.strip_suffix('\n') {
+                    Some(text) => (text, "\n"),
+                    None => (raw, ""),
+                },
+            };
+            let line_number = document.lines.len();
+            let kind = classify(text, line_number, source_path)?;
+            document.lines.push(DocumentLine {
+                text: text.to_string(),
+                ending,
+                kind,
+            });
+        }
+
+        Ok(document)
+    }
+
+    /// Read and parse the CODEOWNERS file at `path`
+    ///
+    /// Invalid UTF-8 is replaced, as in [`parse_codeowners`](super::parser::parse_codeowners).
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = read_codeowners(path)?;
+        Self::parse(&content, path)
+    }
+
+    /// Load `path`, or start an empty document if it does not exist yet
+    pub fn load_or_new(path: &Path) -> Result<Self> {
+        if path.exists() {
+            Self::load(path)
+        } else {
+            Self::parse("", path)
+        }
+    }
+
+    /// Write the document to `path`, replacing it atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_file_atomically(path, |writer| {
+            use std::io::Write;
+            write!(writer, "{}", self)?;
+            Ok(())
+        })
+    }
+
+    pub fn source_file(&self) -> &Path {
+        &self.source_file
+    }
+
+    pub fn lines(&self) -> &[DocumentLine] {
+        &self.lines
+    }
+
+    /// Parsed rules in file order
+    pub fn entries(&self) -> impl Iterator<Item = &CodeownersEntry> {
+        self.lines.iter().filter_map(DocumentLine::entry)
+    }
+
+    /// Groups of consecutive non-blank lines
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections = Vec::new();
+        let mut start = None;
+        for (index, line) in self.lines.iter().enumerate() {
+            match (&line.kind, start) {
+                (LineKind::Blank, Some(first)) => {
+                    sections.push(Section {
+                        start: first,
+                        end: index,
+                    });
+                    start = None;
+                }
+                (LineKind::Blank, None) => {}
+                (_, None) => start = Some(index),
+                (_, Some(_)) => {}
+            }
+        }
+        if let Some(first) = start {
+            sections.push(Section {
+                start: first,
+                end: self.lines.len(),
+            });
+        }
+        sections
+    }
+
+    /// Replace the pattern of the rule on line `index`, keeping the rest of the line
+    pub fn set_pattern(&mut self, index: usize, pattern: &str) -> Result<()> {
+        let line = self.lines.get(index).ok_or_else(|| line_error(index))?;
+        let entry = line
+            .entry()
+            .ok_or_else(|| Error::new(&format!("Line {} is not a CODEOWNERS rule", index + 1)))?;
+
+        let span = entry.spans.pattern;
+        let text = format!(
+            "{}{}{}",
+            &line.text[..span.start],
+            pattern,
+            &line.text[span.end..]
+        );
+        self.replace_line(index, text)
+    }
+
+    /// Replace the owners of the rule on line `index`
+    ///
+    /// Only the owners are rewritten; the pattern, tags, trailing comment and the
+    /// whitespace around them are kept.
+    pub fn set_owners(&mut self, index: usize, owners: &[Owner]) -> Result<()> {
+        let line = self.lines.get(index).ok_or_else(|| line_error(index))?;
+        let entry = line
+            .entry()
+            .ok_or_else(|| Error::new(&format!("Line {} is not a CODEOWNERS rule", index + 1)))?;
+
+        let identifiers = owners
+            .iter()
+            .map(|owner| owner.identifier.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let spans = &entry.spans;
+        let text = match (spans.owners.first(), spans.owners.last()) {
+            // Replace the existing owners, keeping what surrounds them
+            (Some(_), Some(last)) if identifiers.is_empty() => format!(
+                "{}{}",
+                &line.text[..spans.pattern.end],
+                &line.text[last.end..]
+            ),
+            (Some(first), Some(last)) => format!(
+                "{}{}{}",
+                &line.text[..first.start],
+                identifiers,
+                &line.text[last.end..]
+            ),
+            // No owners yet: insert them right after the pattern
+            _ if identifiers.is_empty() => line.text.clone(),
+            _ => format!(
+                "{} {}{}",
+                &line.text[..spans.pattern.end],
+                identifiers,
+                &line.text[spans.pattern.end..]
+            ),
+        };
+
+        self.replace_line(index, text)
+    }
+
+    /// Replace the tags of the rule on line `index`
+    ///
+    /// Only the tags are rewritten; the pattern, owners, trailing comment and the
+    /// whitespace around them are kept.
+    pub fn set_tags(&mut self, index: usize, tags: &[Tag]) -> Result<()> {
+        let line = self.lines.get(index).ok_or_else(|| line_error(index))?;
+        let entry = line
+            .entry()
+            .ok_or_else(|| Error::new(&format!("Line {} is not a CODEOWNERS rule", index + 1)))?;
+
+        let names = tags
+            .iter()
+            .map(|tag| format!("#{}", tag.0))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let spans = &entry.spans;
+        // Tags follow the owners, or the pattern when there are none
+        let before_tags = spans.owners.last().unwrap_or(&spans.pattern).end;
+        let text = match (spans.tags.first(), spans.tags.last()) {
+            (Some(_), Some(last)) if names.is_empty() => {
+                format!("{}{}", &line.text[..before_tags], &line.text[last.end..])
+            }
+            (Some(first), Some(last)) => format!(
+                "{}{}{}",
+                &line.text[..first.start],
+                names,
+                &line.text[last.end..]
+            ),
+            _ if names.is_empty() => line.text.clone(),
+            _ => format!(
+                "{} {}{}",
+                &line.text[..before_tags],
+                names,
+                &line.text[before_tags..]
+            ),
+        };
+
+        self.replace_line(index, text)
+    }
+
+    /// Replace the text of line `index`, keeping its line ending
+    pub fn replace_line(&mut self, index: usize, text: String) -> Result<()> {
+        let kind = classify(&text, index, &self.source_file)?;
+        let line = self.lines.get_mut(index).ok_or_else(|| line_error(index))?;
+        line.text = text;
+        line.kind = kind;
+        Ok(())
+    }
+
+    /// Insert a line of raw text before line `index` (or at the end)
+    pub fn insert_line(&mut self, index: usize, text: String) -> Result<()> {
+        if index > self.lines.len() {
+            return Err(line_error(index));
+        }
+
+        let kind = classify(&text, index, &self.source_file)?;
+        let ending = self.line_ending();
+        if index == self.lines.len() {
+            self.terminate_last_line();
+        }
+        self.lines
+            .insert(index, DocumentLine { text, ending, kind });
+        self.renumber(index);
+        Ok(())
+    }
+
+    /// Append a line of raw text
+    pub fn push_line(&mut self, text: String) -> Result<()> {
+        self.insert_line(self.lines.len(), text)
+    }
+
+    /// Append a rule formatted from `entry`
+    pub fn push_entry(&mut self, entry: &CodeownersEntry) -> Result<()> {
+        self.push_line(format_line(entry))
+    }
+
+    /// Remove line `index`
+    pub fn remove_line(&mut self, index: usize) -> Result<DocumentLine> {
+        if index >= self.lines.len() {
+            return Err(line_error(index));
+        }
+        let line = self.lines.remove(index);
+        self.renumber(index);
+        Ok(line)
+    }
+
+    /// Line ending used for new lines: the one the file already uses, or `\n`
+    fn line_ending(&self) -> &'static str {
+        self.lines
+            .iter()
+            .map(|line| line.ending)
+            .find(|ending| !ending.is_empty())
+            .unwrap_or("\n")
+    }
+
+    /// Give the last line an ending so that lines added after it stay separate
+    fn terminate_last_line(&mut self) {
+        let ending = self.line_ending();
+        if let Some(last) = self.lines.last_mut() {
+            if last.ending.is_empty() {
+                last.ending = ending;
+            }
+        }
+    }
+
+    /// Keep `line_number` of rules at or after `from` in step with their position
+    fn renumber(&mut self, from: usize) {
+        for (index, line) in self.lines.iter_mut().enumerate().skip(from) {
+            if let LineKind::Rule(entry) = &mut line.kind {
+                entry.line_number = index;
+            }
+        }
+    }
+}
+
+impl fmt::Display for CodeownersDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}{}", line.text, line.ending)?;
+        }
+        Ok(())
+    }
+}
+
+fn classify(text: &str, line_number: usize, source_path: &Path) -> Result<LineKind> {
+    if text.contains('\n') {
+        return Err(Error::new("A CODEOWNERS line cannot contain a line break"));
+    }
+
+    if let Some(entry) = parse_line(text, line_number, source_path)? {
+        return Ok(LineKind::Rule(Box::new(entry)));
+    }
+
+    let trimmed = text.trim_matches(|c: char| c.is_whitespace() || c == '\u{feff}');
+    Ok(if trimmed.is_empty() {
+        LineKind::Blank
+    } else {
+        LineKind::Comment
+    })
+}
+
+fn line_error(index: usize) -> Error {
+    Error::new(&format!("Line {} is out of range", index + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_owner;
+
+    const SAMPLE: &str = "\
+# Default owners
+*       @org/everyone
+
+# Backend
+/src/api/   @alice   @bob   #api # reviewed weekly
+*.rs @rust
+";
+
+    fn document(content: &str) -> CodeownersDocument {
+        CodeownersDocument::parse(content, Path::new("/repo/CODEOWNERS")).unwrap()
+    }
+
+    fn owners(identifiers: &[&str]) -> Vec<Owner> {
+        identifiers
+            .iter()
+            .map(|identifier| parse_owner(identifier).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        for content in [
+            SAMPLE,
+            "",
+            "\n\n",
+            "* @a\r\n# comment\r\n\r\n*.md @docs",
+            "\u{feff}* @a\n  \t\n# trailing",
+        ] {
+            assert_eq!(document(content).to_string(), content);
+        }
+    }
+
+    #[test]
+    fn test_classifies_lines() {
+        let doc = document(SAMPLE);
+        let kinds: Vec<_> = doc
+            .lines()
+            .iter()
+            .map(|line| match line.kind() {
+                LineKind::Blank => "blank",
+                LineKind::Comment => "comment",
+                LineKind::Rule(_) => "rule",
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            ["comment", "rule", "blank", "comment", "rule", "rule"]
+        );
+
+        let patterns: Vec<_> = doc.entries().map(|e| e.pattern.as_str()).collect();
+        assert_eq!(patterns, ["*", "/src/api/", "*.rs"]);
+        assert_eq!(doc.entries().nth(1).unwrap().line_number, 4);
+    }
+
+    #[test]
+    fn test_sections() {
+        let doc = document(SAMPLE);
+        assert_eq!(
+            doc.sections(),
+            [Section { start: 0, end: 2 }, Section { start: 3, end: 6 }]
+        );
+    }
+
+    #[test]
+    fn test_set_owners_keeps_layout() -> Result<()> {
+        let mut doc = document(SAMPLE);
+
+        doc.set_owners(4, &owners(&["@org/backend"]))?;
+        assert_eq!(
+            doc.lines()[4].text(),
+            "/src/api/   @org/backend   #api # reviewed weekly"
+        );
+        let entry = doc.lines()[4].entry().unwrap();
+        assert_eq!(entry.owners, owners(&["@org/backend"]));
+        assert_eq!(entry.tags.len(), 1);
+
+        doc.set_owners(4, &[])?;
+        assert_eq!(doc.lines()[4].text(), "/src/api/   #api # reviewed weekly");
+
+        doc.set_owners(4, &owners(&["@carol", "@dave"]))?;
+        assert_eq!(
+            doc.lines()[4].text(),
+            "/src/api/ @carol @dave   #api # reviewed weekly"
+        );
+
+        assert!(doc.set_owners(0, &owners(&["@x"])).is_err());
+        assert!(doc.set_owners(99, &owners(&["@x"])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_pattern_keeps_layout() -> Result<()> {
+        let mut doc = document(SAMPLE);
+        doc.set_pattern(4, "/src/")?;
+        assert_eq!(
+            doc.lines()[4].text(),
+            "/src/   @alice   @bob   #api # reviewed weekly"
+        );
+        assert_eq!(doc.lines()[4].entry().unwrap().pattern, "/src/");
+        assert!(doc.set_pattern(0, "*").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_tags_keeps_layout() -> Result<()> {
+        let mut doc = document(SAMPLE);
+        let tags = |names: &[&str]| -> Vec<Tag> {
+            names.iter().map(|name| Tag(name.to_string())).collect()
+        };
+
+        doc.set_tags(4, &tags(&["backend", "critical"]))?;
+        assert_eq!(
+            doc.lines()[4].text(),
+            "/src/api/   @alice   @bob   #backend #critical # reviewed weekly"
+        );
+        assert_eq!(
+            doc.lines()[4].entry().unwrap().tags,
+            tags(&["backend", "critical"])
+        );
+
+        doc.set_tags(4, &[])?;
+        assert_eq!(
+            doc.lines()[4].text(),
+            "/src/api/   @alice   @bob # reviewed weekly"
+        );
+
+        doc.set_tags(5, &tags(&["rust"]))?;
+        assert_eq!(doc.lines()[5].text(), "*.rs @rust #rust");
+
+        assert!(doc.set_tags(0, &tags(&["x"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_and_remove_renumber_rules() -> Result<()> {
+        let mut doc = document(SAMPLE);
+
+        doc.insert_line(0, "# Header".to_string())?;
+        assert_eq!(doc.entries().next().unwrap().line_number, 2);
+
+        doc.remove_line(0)?;
+        assert_eq!(doc.entries().next().unwrap().line_number, 1);
+        assert_eq!(doc.to_string(), SAMPLE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_terminates_last_line() -> Result<()> {
+        let mut doc = document("* @a\r\n*.md @docs");
+        let entry = parse_line("*.rs @rust #lang", 0, Path::new("CODEOWNERS"))?.unwrap();
+        doc.push_entry(&entry)?;
+
+        assert_eq!(
+            doc.to_string(),
+            "* @a\r\n*.md @docs\r\n*.rs @rust #lang\r\n"
+        );
+        assert_eq!(doc.entries().last().unwrap().line_number, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("CODEOWNERS");
+
+        let mut doc = CodeownersDocument::load_or_new(&path)?;
+        doc.push_line("# Owners".to_string())?;
+        doc.push_line("* @all".to_string())?;
+        doc.save(&path)?;
+
+        assert_eq!(std::fs::read_to_string(&path)?, "# Owners\n* @all\n");
+        assert_eq!(CodeownersDocument::load(&path)?.entries().count(), 1);
+
+        Ok(())
+    }
+}
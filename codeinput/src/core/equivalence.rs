@@ -0,0 +1,106 @@
+//! Comparing how two sets of CODEOWNERS rules resolve over the same files
+//!
+//! Used to check that refactoring CODEOWNERS files, by hand or with `optimize`, keeps
+//! every file's owners and tags. Both sets are resolved with the same matcher and
+//! precedence policy; inline declarations apply to both alike.
+
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+
+use super::matcher::{Matcher, MatcherStrategy};
+use super::resolver::resolve_file_with_policy;
+use super::types::{CodeownersEntry, MatchOptions, Owner, PrecedencePolicy, Tag};
+use crate::utils::error::Result;
+
+/// A file the two rule sets resolve differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    pub path: PathBuf,
+    pub a_owners: Vec<Owner>,
+    pub a_tags: Vec<Tag>,
+    pub b_owners: Vec<Owner>,
+    pub b_tags: Vec<Tag>,
+}
+
+/// Files whose owners or tags differ between rule sets `a` and `b`, in the order of `files`
+pub fn compare(
+    a: &[CodeownersEntry], b: &[CodeownersEntry], files: &[PathBuf], options: MatchOptions,
+    policy: PrecedencePolicy,
+) -> Result<Vec<Difference>> {
+    let a_matcher = Matcher::build_with_options(a, MatcherStrategy::Override, options);
+    let b_matcher = Matcher::build_with_options(b, MatcherStrategy::Override, options);
+    let differences = files
+        .par_iter()
+        .map(|file| {
+            let (a_owners, a_tags, _) = resolve_file_with_policy(file, a, &a_matcher, policy)?;
+            let (b_owners, b_tags, _) = resolve_file_with_policy(file, b, &b_matcher, policy)?;
+            let same = same_owners(&a_owners, &b_owners) && same_tags(&a_tags, &b_tags);
+            Ok((!same).then(|| Difference {
+                path: file.clone(),
+                a_owners,
+                a_tags,
+                b_owners,
+                b_tags,
+            }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(differences.into_iter().flatten().collect())
+}
+
+/// Owners are the same regardless of order, as GitHub requests reviews from all of them
+pub(crate) fn same_owners(a: &[Owner], b: &[Owner]) -> bool {
+    let mut a: Vec<&str> = a.iter().map(|o| o.identifier.as_str()).collect();
+    let mut b: Vec<&str> = b.iter().map(|o| o.identifier.as_str()).collect();
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
+
+pub(crate) fn same_tags(a: &[Tag], b: &[Tag]) -> bool {
+    let mut a: Vec<&str> = a.iter().map(|t| t.0.as_str()).collect();
+    let mut b: Vec<&str> = b.iter().map(|t| t.0.as_str()).collect();
+    a.sort_unstable();
+    b.sort_unstable();
+    a == b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use std::path::Path;
+
+    fn rules(content: &str, source: &str) -> Vec<CodeownersEntry> {
+        parse_content(content, Path::new(source)).unwrap()
+    }
+
+    #[test]
+    fn test_compare_lists_files_that_resolve_differently() {
+        let files: Vec<PathBuf> = ["/repo/a.rs", "/repo/docs/b.md", "/repo/web/c.js"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let a = rules(
+            "*.rs @x @y #rust\n/docs/ @docs\n/web/ @web\n",
+            "/repo/CODEOWNERS",
+        );
+        // Split into a nested file, with owners reordered and one owner changed
+        let mut b = rules("*.rs @y @x #rust\n/docs/ @writers\n", "/repo/CODEOWNERS");
+        b.extend(rules("* @web\n", "/repo/web/CODEOWNERS"));
+
+        let differences = compare(
+            &a,
+            &b,
+            &files,
+            MatchOptions::default(),
+            PrecedencePolicy::Closest,
+        )
+        .unwrap();
+
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].path, PathBuf::from("/repo/docs/b.md"));
+        assert_eq!(differences[0].a_owners[0].identifier, "@docs");
+        assert_eq!(differences[0].b_owners[0].identifier, "@writers");
+    }
+}
@@ -0,0 +1,122 @@
+//! Temporary ownership, marked with an `expires:YYYY-MM-DD` annotation
+//!
+//! During a migration a team often owns code only until it is handed over. The annotation
+//! records when that ownership ends: `codeowners validate` warns as the date approaches
+//! and reports rules past it as problems, which also fails the validate check of
+//! `codeowners gate`.
+
+use chrono::NaiveDate;
+
+use super::types::CodeownersEntry;
+use super::validate::{Problem, ProblemKind};
+
+/// Annotation key holding the last day a rule's ownership holds
+pub const EXPIRES_KEY: &str = "expires";
+
+/// Days before its expiry that a rule is warned about, unless set otherwise
+pub const DEFAULT_WARNING_DAYS: u32 = 14;
+
+/// The day rules are checked on and how many days ahead an expiry is warned about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpiryWindow {
+    pub today: NaiveDate,
+    pub warning_days: u32,
+}
+
+impl ExpiryWindow {
+    /// Problem with the expiry of `entry`: a malformed date, an expiry within the window,
+    /// a warning, or a date already past
+    ///
+    /// A rule still holds on the day it expires.
+    pub fn problem(&self, entry: &CodeownersEntry) -> Option<Problem> {
+        let problem = |kind, message| {
+            Some(Problem {
+                source_file: entry.source_file.clone(),
+                line: Some(entry.line_number + 1),
+                kind,
+                message,
+            })
+        };
+        let date = match expiry_date(entry)? {
+            Ok(date) => date,
+            Err(value) => {
+                return problem(
+                    ProblemKind::Syntax,
+                    format!("invalid expiry date {}; expected YYYY-MM-DD", value),
+                )
+            }
+        };
+
+        let days_left = (date - self.today).num_days();
+        if days_left < 0 {
+            problem(
+                ProblemKind::Expired,
+                format!("ownership of {} expired on {}", entry.pattern, date),
+            )
+        } else if days_left <= self.warning_days.into() {
+            problem(
+                ProblemKind::Expiring,
+                format!(
+                    "ownership of {} expires on {}, in {} day{}",
+                    entry.pattern,
+                    date,
+                    days_left,
+                    if days_left == 1 { "" } else { "s" }
+                ),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+/// The expiry date of `entry`, or the annotation's value when it is not a date
+pub fn expiry_date(entry: &CodeownersEntry) -> Option<Result<NaiveDate, &str>> {
+    let value = entry.metadata.get(EXPIRES_KEY)?;
+    Some(NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| value.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use crate::utils::error::Result;
+    use std::path::Path;
+
+    #[test]
+    fn test_expiry() -> Result<()> {
+        let entries = parse_content(
+            "*.rs @rust # expires:2024-01-31\n\
+             *.md @docs # expires:2024-01-10\n\
+             *.js @web # expires:2023-12-31\n\
+             *.py @py # expires:2024-01-01\n\
+             *.go @go # expires:soon\n\
+             *.sh @ops\n",
+            Path::new("CODEOWNERS"),
+        )?;
+        let window = ExpiryWindow {
+            today: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            warning_days: DEFAULT_WARNING_DAYS,
+        };
+        let problems: Vec<_> = entries
+            .iter()
+            .map(|entry| window.problem(entry).map(|problem| problem.kind))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                None,
+                Some(ProblemKind::Expiring),
+                Some(ProblemKind::Expired),
+                Some(ProblemKind::Expiring),
+                Some(ProblemKind::Syntax),
+                None,
+            ]
+        );
+        assert_eq!(
+            window.problem(&entries[1]).unwrap().message,
+            "ownership of *.md expires on 2024-01-10, in 9 days"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,200 @@
+//! Files of given owners, exported as a list or a tarball
+//!
+//! Audits, handover packages and security reviews are scoped to what some owners are
+//! responsible for. The files any of the owners is listed for, compared without case, are
+//! written one path per line or packed into an uncompressed POSIX (ustar) tarball, with
+//! paths relative to the repository root.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use super::types::CodeownersCache;
+use crate::utils::error::{Error, Result};
+use crate::utils::interrupt;
+use crate::utils::warnings::{self, WarningKind};
+
+/// Size of a tar block; headers take one and file contents are padded to a whole number
+const BLOCK: usize = 512;
+
+/// What the files are exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One path per line
+    List,
+    /// An uncompressed tarball of the files
+    Tar,
+}
+
+impl ExportFormat {
+    /// The format the name of `path` suggests: a tarball for `.tar`, else a list
+    pub fn for_path(path: &Path) -> ExportFormat {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("tar") => ExportFormat::Tar,
+            _ => ExportFormat::List,
+        }
+    }
+}
+
+/// Files of `cache` owned by any of `owners`, relative to `repo` and sorted
+pub fn owned_files(repo: &Path, cache: &CodeownersCache, owners: &[String]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = cache
+        .files
+        .iter()
+        .filter(|file| {
+            file.owners.iter().any(|owner| {
+                owners
+                    .iter()
+                    .any(|wanted| owner.identifier.eq_ignore_ascii_case(wanted))
+            })
+        })
+        .map(|file| {
+            let path = file.path.strip_prefix(repo).unwrap_or(&file.path);
+            path.strip_prefix(".").unwrap_or(path).to_path_buf()
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Write `files` one per line
+pub fn write_list(files: &[PathBuf], out: &mut impl Write) -> io::Result<()> {
+    for file in files {
+        writeln!(out, "{}", file.display())?;
+    }
+    Ok(())
+}
+
+/// Pack `files` of `repo` into a tarball, returning how many were packed
+///
+/// Files missing from the working tree are skipped with a warning.
+pub fn write_tar(repo: &Path, files: &[PathBuf], out: &mut impl Write) -> Result<usize> {
+    let mut packed = 0;
+    for file in files {
+        interrupt::check()?;
+        let source = repo.join(file);
+        let (reader, metadata) = match File::open(&source).and_then(|reader| {
+            let metadata = reader.metadata()?;
+            Ok((reader, metadata))
+        }) {
+            Ok(opened) => opened,
+            Err(e) => {
+                warnings::warn(
+                    WarningKind::UnreadableFile,
+                    format!("Skipping {}: {}", source.display(), e),
+                );
+                continue;
+            }
+        };
+
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_secs());
+        out.write_all(&tar_header(file, metadata.len(), mode(&metadata), mtime)?)?;
+        let copied = io::copy(&mut reader.take(metadata.len()), out)?;
+        if copied != metadata.len() {
+            return Err(Error::new(&format!(
+                "{} changed while it was exported",
+                source.display()
+            )));
+        }
+        out.write_all(&vec![0; padding(copied)])?;
+        packed += 1;
+    }
+    // The archive ends with two empty blocks
+    out.write_all(&[0; 2 * BLOCK])?;
+    Ok(packed)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &std::fs::Metadata) -> u32 {
+    0o644
+}
+
+/// Zero bytes after `size` bytes of contents up to the end of their last block
+fn padding(size: u64) -> usize {
+    (BLOCK - (size % BLOCK as u64) as usize) % BLOCK
+}
+
+/// The ustar header of a regular file at `path`
+///
+/// Paths over 100 bytes are split at a `/` into the 155-byte prefix field; longer paths
+/// are an error.
+fn tar_header(path: &Path, size: u64, mode: u32, mtime: u64) -> Result<[u8; BLOCK]> {
+    let name = path.to_string_lossy().replace('\\', "/");
+    let (prefix, name) = split_name(&name)
+        .ok_or_else(|| Error::new(&format!("Path too long for a tarball: {}", name)))?;
+
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], mode.into());
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], size);
+    octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // The checksum is taken with its own field as spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&byte| u32::from(byte)).sum();
+    octal(&mut header[148..155], checksum.into());
+    Ok(header)
+}
+
+/// `name` as the prefix and name fields of a ustar header, if it fits
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    name.match_indices('/')
+        .map(|(at, _)| (&name[..at], &name[at + 1..]))
+        .find(|(prefix, rest)| prefix.len() <= 155 && rest.len() <= 100 && !rest.is_empty())
+}
+
+/// Write `value` into `field` as zero-padded octal digits ending with a NUL
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    let digits = &digits.as_bytes()[digits.len() - (field.len() - 1)..];
+    field[..digits.len()].copy_from_slice(digits);
+    field[digits.len()] = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tar_header() {
+        let header = tar_header(Path::new("src/lib.rs"), 10, 0o644, 0).unwrap();
+        assert_eq!(&header[..11], b"src/lib.rs\0");
+        assert_eq!(&header[100..108], b"0000644\0");
+        assert_eq!(&header[124..136], b"00000000012\0");
+        assert_eq!(&header[257..263], b"ustar\0");
+
+        let checksum = std::str::from_utf8(&header[148..154]).unwrap();
+        let mut blank = header;
+        blank[148..156].fill(b' ');
+        let sum: u32 = blank.iter().map(|&byte| u32::from(byte)).sum();
+        assert_eq!(u32::from_str_radix(checksum, 8).unwrap(), sum);
+    }
+
+    #[test]
+    fn test_split_name() {
+        let long = format!("{}/{}", "a".repeat(120), "b".repeat(90));
+        assert_eq!(split_name(&long), Some((&long[..120], &long[121..])));
+        assert_eq!(split_name(&"c".repeat(101)), None);
+        assert_eq!(padding(0), 0);
+        assert_eq!(padding(10), 502);
+    }
+}
@@ -0,0 +1,333 @@
+//! Checks combined into one CI gate by `codeowners gate`
+//!
+//! Every check runs against the same cache, so a pipeline gating on several of them
+//! loads it once instead of once per command.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use chrono::NaiveDate;
+
+use super::delegation::Delegation;
+use super::expiry::ExpiryWindow;
+use super::policy::Policy;
+use super::types::{CodeownersCache, MatchOptions, OwnerType};
+use super::validate::rule_problems;
+
+/// Problems listed in a check's details before the rest are only counted
+const MAX_LISTED: usize = 3;
+
+/// A check of the gate, as given to `--checks`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GateCheck {
+    /// Every rule has a valid pattern, at least one well-formed owner and has not expired,
+    /// and every delegation names an existing directory and well-formed owners
+    Validate,
+    /// At least this percentage of files is owned
+    Coverage(f64),
+    /// At most this many files are unowned
+    Unowned(usize),
+    /// No file breaks the repository's [`Policy`]
+    Policy,
+}
+
+impl fmt::Display for GateCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GateCheck::Validate => write!(f, "validate"),
+            GateCheck::Coverage(min) => write!(f, "coverage:{}", min),
+            GateCheck::Unowned(max) => write!(f, "unowned:{}", max),
+            GateCheck::Policy => write!(f, "policy"),
+        }
+    }
+}
+
+impl FromStr for GateCheck {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+        match (name.to_lowercase().as_str(), argument) {
+            ("validate", None) => Ok(GateCheck::Validate),
+            ("policy", None) => Ok(GateCheck::Policy),
+            ("coverage", Some(min)) => match min.trim_end_matches('%').parse::<f64>() {
+                Ok(min) if (0.0..=100.0).contains(&min) => Ok(GateCheck::Coverage(min)),
+                _ => Err(format!(
+                    "Invalid coverage threshold: {} (expected a percentage from 0 to 100)",
+                    min
+                )),
+            },
+            ("unowned", Some(max)) => max
+                .parse::<usize>()
+                .map(GateCheck::Unowned)
+                .map_err(|_| format!("Invalid unowned file limit: {}", max)),
+            ("coverage", None) => {
+                Err("coverage needs a minimum percentage, e.g. coverage:90".to_string())
+            }
+            ("unowned", None) => {
+                Err("unowned needs a maximum file count, e.g. unowned:0".to_string())
+            }
+            _ => Err(format!(
+                "Invalid check: {} (expected validate, coverage:N, unowned:N or policy)",
+                s
+            )),
+        }
+    }
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckOutcome {
+    pub check: String,
+    pub passed: bool,
+    pub details: String,
+}
+
+/// Run `check` against `cache`; the policy check fails when there is no `policy`
+///
+/// `delegations` are only read by the validate check, which fails rules expired before
+/// `today`.
+pub fn evaluate(
+    check: &GateCheck, cache: &CodeownersCache, policy: Option<&Policy>,
+    delegations: &[Delegation], today: NaiveDate,
+) -> CheckOutcome {
+    // Generated files do not count toward coverage
+    let counted: Vec<_> = cache.files.iter().filter(|file| !file.generated).collect();
+    let files = counted.len();
+    let unowned = counted
+        .iter()
+        .filter(|file| {
+            file.owners
+                .iter()
+                .all(|owner| owner.owner_type == OwnerType::Unowned)
+        })
+        .count();
+
+    let (passed, details) = match check {
+        GateCheck::Validate => {
+            let problems = validation_problems(cache, delegations, today);
+            if problems.is_empty() {
+                (true, format!("{} rules valid", cache.entries.len()))
+            } else {
+                (false, summarize(problems.len(), "problem", problems))
+            }
+        }
+        GateCheck::Coverage(min) => {
+            let coverage = if files == 0 {
+                100.0
+            } else {
+                (files - unowned) as f64 * 100.0 / files as f64
+            };
+            (
+                coverage >= *min,
+                format!(
+                    "{:.1}% of files owned, at least {}% required",
+                    coverage, min
+                ),
+            )
+        }
+        GateCheck::Unowned(max) => (
+            unowned <= *max,
+            format!("{} unowned files, at most {} allowed", unowned, max),
+        ),
+        GateCheck::Policy => match policy {
+            None => (
+                false,
+                "no policy file (see the policy_file setting)".to_string(),
+            ),
+            Some(policy) => {
+                let violations: Vec<String> = policy
+                    .violations(cache)
+                    .into_iter()
+                    .map(|v| format!("{}: {}", v.path.display(), v.message))
+                    .collect();
+                if violations.is_empty() {
+                    (true, "no violations".to_string())
+                } else {
+                    (false, summarize(violations.len(), "violation", violations))
+                }
+            }
+        },
+    };
+
+    CheckOutcome {
+        check: check.to_string(),
+        passed,
+        details,
+    }
+}
+
+/// `source:line: problem` for each invalid or expired rule and invalid delegation
+fn validation_problems(
+    cache: &CodeownersCache, delegations: &[Delegation], today: NaiveDate,
+) -> Vec<String> {
+    let expiry = ExpiryWindow {
+        today,
+        warning_days: 0,
+    };
+    let mut problems = Vec::new();
+    for entry in &cache.entries {
+        let rule = rule_problems(entry, MatchOptions::default());
+        problems.extend(
+            rule.iter()
+                .chain(expiry.problem(entry).iter())
+                .filter(|problem| !problem.is_warning())
+                .map(ToString::to_string),
+        );
+    }
+    for delegation in delegations {
+        for problem in delegation.problems() {
+            problems.push(format!(
+                "{}:{}: {}",
+                delegation.source_file.display(),
+                delegation.line_number + 1,
+                problem
+            ));
+        }
+    }
+    problems
+}
+
+/// `N problems: first; second; third; and 2 more`
+fn summarize(count: usize, noun: &str, items: Vec<String>) -> String {
+    let mut details = format!(
+        "{} {}{}: {}",
+        count,
+        noun,
+        if count == 1 { "" } else { "s" },
+        items
+            .iter()
+            .take(MAX_LISTED)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("; ")
+    );
+    if count > MAX_LISTED {
+        details.push_str(&format!("; and {} more", count - MAX_LISTED));
+    }
+    details
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{CodeownersEntry, FileEntry, Owner};
+    use std::path::PathBuf;
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    fn cache() -> CodeownersCache {
+        let entry = |line_number, owners| CodeownersEntry {
+            source_file: PathBuf::from("CODEOWNERS"),
+            line_number,
+            pattern: "*.rs".to_string(),
+            owners,
+            tags: Vec::new(),
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let entries = vec![
+            entry(0, vec![owner("@team", OwnerType::Team)]),
+            entry(1, Vec::new()),
+            entry(2, vec![owner("team", OwnerType::Unknown)]),
+        ];
+        let file = |path: &str, owners| FileEntry {
+            path: PathBuf::from(path),
+            owners,
+            tags: Vec::new(),
+            generated: false,
+            inline: None,
+        };
+        let files = vec![
+            file("a.rs", vec![owner("@team", OwnerType::Team)]),
+            file("b.rs", vec![owner("@team", OwnerType::Team)]),
+            file("c.rs", vec![owner("@team", OwnerType::Team)]),
+            file("d.txt", Vec::new()),
+        ];
+        CodeownersCache::new([0; 32], entries, files, Vec::new())
+    }
+
+    #[test]
+    fn test_parse_checks() {
+        assert_eq!("validate".parse(), Ok(GateCheck::Validate));
+        assert_eq!("coverage:90".parse(), Ok(GateCheck::Coverage(90.0)));
+        assert_eq!("coverage:87.5%".parse(), Ok(GateCheck::Coverage(87.5)));
+        assert_eq!("unowned:0".parse(), Ok(GateCheck::Unowned(0)));
+        assert_eq!("POLICY".parse(), Ok(GateCheck::Policy));
+        assert!("coverage".parse::<GateCheck>().is_err());
+        assert!("coverage:120".parse::<GateCheck>().is_err());
+        assert!("unowned:-1".parse::<GateCheck>().is_err());
+        assert!("lint".parse::<GateCheck>().is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut cache = cache();
+        let today = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+
+        let coverage = evaluate(&GateCheck::Coverage(75.0), &cache, None, &[], today);
+        assert!(coverage.passed);
+        assert_eq!(
+            coverage.details,
+            "75.0% of files owned, at least 75% required"
+        );
+        assert!(!evaluate(&GateCheck::Coverage(80.0), &cache, None, &[], today).passed);
+        assert!(evaluate(&GateCheck::Unowned(1), &cache, None, &[], today).passed);
+        assert!(!evaluate(&GateCheck::Unowned(0), &cache, None, &[], today).passed);
+        assert!(!evaluate(&GateCheck::Policy, &cache, None, &[], today).passed);
+        assert!(
+            evaluate(
+                &GateCheck::Policy,
+                &cache,
+                Some(&Policy::default()),
+                &[],
+                today
+            )
+            .passed
+        );
+
+        let validate = evaluate(&GateCheck::Validate, &cache, None, &[], today);
+        assert!(!validate.passed);
+        assert_eq!(
+            validate.details,
+            "2 problems: CODEOWNERS:2: rule has no owners; CODEOWNERS:3: malformed owner team"
+        );
+
+        let delegation = Delegation {
+            source_file: PathBuf::from("CODEOWNERS"),
+            line_number: 3,
+            directory: "no-such-dir/".to_string(),
+            delegates: vec![owner("@team", OwnerType::Team)],
+        };
+        let validate = evaluate(&GateCheck::Validate, &cache, None, &[delegation], today);
+        assert_eq!(
+            validate.details,
+            "3 problems: CODEOWNERS:2: rule has no owners; CODEOWNERS:3: malformed owner team; \
+             CODEOWNERS:4: delegated directory no-such-dir/ does not exist"
+        );
+
+        // Expired rules fail; rules expiring today still hold
+        let expires = |date: &str| [("expires".to_string(), date.to_string())].into();
+        cache.entries[0].metadata = expires("2024-01-01");
+        let validate = evaluate(&GateCheck::Validate, &cache, None, &[], today);
+        assert!(validate.details.starts_with("2 problems"));
+        cache.entries[0].metadata = expires("2023-12-31");
+        let validate = evaluate(&GateCheck::Validate, &cache, None, &[], today);
+        assert_eq!(
+            validate.details,
+            "3 problems: CODEOWNERS:1: ownership of *.rs expired on 2023-12-31; \
+             CODEOWNERS:2: rule has no owners; CODEOWNERS:3: malformed owner team"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! Detection of generated code
+//!
+//! Generators mark their output with comments such as `// @generated` or
+//! `// Code generated by protoc-gen-go. DO NOT EDIT.` near the top of the file. Files
+//! carrying one of the configured markers are flagged in the cache so they can be left
+//! out of listings and coverage.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes at the start of a file searched for markers
+const SCAN_BYTES: u64 = 4096;
+
+/// Markers used unless the `generated_markers` setting says otherwise
+pub const DEFAULT_MARKERS: [&str; 2] = ["@generated", "DO NOT EDIT"];
+
+/// Whether the start of the file at `path` contains one of `markers`
+///
+/// Unreadable files are not generated.
+pub fn is_generated(path: &Path, markers: &[String]) -> bool {
+    if markers.is_empty() {
+        return false;
+    }
+    let Ok(file) = File::open(path) else {
+        return false;
+    };
+    let mut head = Vec::new();
+    if file.take(SCAN_BYTES).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&head);
+    markers
+        .iter()
+        .any(|marker| !marker.is_empty() && head.contains(marker.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_generated() {
+        let dir = TempDir::new().unwrap();
+        let markers: Vec<String> = DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect();
+        let write = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            path
+        };
+
+        let go = write(
+            "api.pb.go",
+            b"// Code generated by protoc-gen-go. DO NOT EDIT.\n",
+        );
+        let js = write("bundle.js", b"/* \xff\xfe */\n// @generated\nexport {}\n");
+        let late = write(
+            "late.rs",
+            format!("{}// @generated\n", "\n".repeat(SCAN_BYTES as usize)).as_bytes(),
+        );
+        let plain = write("main.rs", b"fn main() {}\n");
+
+        assert!(is_generated(&go, &markers));
+        assert!(is_generated(&js, &markers));
+        assert!(!is_generated(&late, &markers));
+        assert!(!is_generated(&plain, &markers));
+        assert!(!is_generated(&go, &[]));
+        assert!(!is_generated(&dir.path().join("missing"), &markers));
+    }
+}
@@ -0,0 +1,510 @@
+//! GitHub's side of CODEOWNERS resolution, for `codeowners compare-github`
+//!
+//! GitHub's API does not resolve the owners of a path. It does expose the CODEOWNERS file
+//! GitHub uses at a ref, the first of [`CODEOWNERS_LOCATIONS`], and the lines of that file
+//! GitHub rejects and ignores. GitHub's resolution is rebuilt from those: that file alone,
+//! scoped to the repository root wherever it lives, without the rejected lines, nested
+//! CODEOWNERS files or inline markers.
+//!
+//! `codeowners verify-owners` asks the API about each owner instead: whether the user or
+//! team exists, and whether a team can access the repository at all; with
+//! `--expand-groups` it lists the members of each team too.
+//!
+//! `codeowners infer-owners --reviews` reads who reviewed the merged pull requests
+//! touching each file.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::api_client::{ApiClient, ApiResponse, Auth};
+use super::context::Context;
+use super::matcher::Matcher;
+use super::parser::parse_content;
+use super::resolver::find_entry_for_file;
+use super::types::{CodeownersEntry, Owner, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Where GitHub looks for CODEOWNERS, in order; the first file found is the only one used
+pub const CODEOWNERS_LOCATIONS: [&str; 3] = [".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// Items requested per page when listing team members, pull requests, their files or
+/// their reviews
+const PER_PAGE: usize = 100;
+
+/// Root that GitHub's rules are resolved under; it is not read from disk
+const ROOT: &str = "/";
+
+/// A repository on GitHub, as `owner/name`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitHubRepo {
+    pub owner: String,
+    pub name: String,
+}
+
+impl fmt::Display for GitHubRepo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+impl FromStr for GitHubRepo {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((owner, name)) if !owner.is_empty() && !name.is_empty() && !name.contains('/') => {
+                Ok(GitHubRepo {
+                    owner: owner.to_string(),
+                    name: name.trim_end_matches(".git").to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Invalid GitHub repository: {} (expected OWNER/NAME)",
+                s
+            )),
+        }
+    }
+}
+
+impl GitHubRepo {
+    /// The repository a remote URL points at, for SSH and HTTPS remotes on any host
+    pub fn from_remote_url(url: &str) -> Option<GitHubRepo> {
+        let path = if let Some((_, rest)) = url.split_once("://") {
+            // https://host/owner/name.git or ssh://git@host/owner/name.git
+            rest.split_once('/')?.1
+        } else {
+            // git@host:owner/name.git
+            url.split_once(':')?.1
+        };
+        path.trim_end_matches('/').parse().ok()
+    }
+
+    /// The repository behind the `origin` remote of the git repository at `path`
+    pub fn discover(path: &Path) -> Result<GitHubRepo> {
+        let repo = git2::Repository::discover(path)
+            .map_err(|e| Error::with_source("Failed to open git repository", Box::new(e)))?;
+        let remote = repo.find_remote("origin").map_err(|_| {
+            Error::new("No origin remote; pass the repository with --github-repo OWNER/NAME")
+        })?;
+        remote
+            .url()
+            .and_then(GitHubRepo::from_remote_url)
+            .ok_or_else(|| {
+                Error::new(&format!(
+                    "Cannot tell the GitHub repository from origin {}; pass --github-repo OWNER/NAME",
+                    remote.url().unwrap_or("(not UTF-8)")
+                ))
+            })
+    }
+}
+
+/// A CODEOWNERS line GitHub rejects, from the `codeowners/errors` endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeownersError {
+    /// 1-based line
+    pub line: usize,
+    pub kind: String,
+    #[serde(default)]
+    pub message: String,
+    /// CODEOWNERS file the line is in
+    pub path: String,
+}
+
+#[derive(Deserialize)]
+struct CodeownersErrors {
+    errors: Vec<CodeownersError>,
+}
+
+/// What GitHub knows of an owner named in CODEOWNERS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnerStatus {
+    Valid,
+    /// No such user or team, or the token cannot see it
+    Unknown,
+    /// A suspended account, which GitHub Enterprise Server reports, or a GitLab account
+    /// that is blocked or deactivated
+    Suspended,
+    /// An organization named as a user; GitHub only accepts users and teams
+    Organization,
+    /// A team or group without access to the repository, which is ignored
+    NoRepoAccess,
+}
+
+impl fmt::Display for OwnerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnerStatus::Valid => write!(f, "valid"),
+            OwnerStatus::Unknown => write!(f, "unknown"),
+            OwnerStatus::Suspended => write!(f, "suspended"),
+            OwnerStatus::Organization => write!(f, "organization, not a user"),
+            OwnerStatus::NoRepoAccess => write!(f, "no access to repository"),
+        }
+    }
+}
+
+/// An account from the `users` endpoint
+#[derive(Debug, Clone, Deserialize)]
+struct GitHubUser {
+    /// `User` or `Organization`
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    suspended_at: Option<String>,
+}
+
+/// A member from the team `members` endpoint
+#[derive(Debug, Clone, Deserialize)]
+struct TeamMember {
+    login: String,
+}
+
+/// An account as pull requests and reviews name it
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Account {
+    pub login: String,
+}
+
+/// A merged pull request from the `pulls` endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PullRequest {
+    pub number: u64,
+    /// Missing for deleted accounts
+    pub user: Option<Account>,
+    pub merged_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A file a pull request changed, from the pull request `files` endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PullRequestFile {
+    /// Path relative to the repository root
+    pub filename: String,
+    /// Path before a rename
+    #[serde(default)]
+    pub previous_filename: Option<String>,
+}
+
+/// A review of a pull request, from the pull request `reviews` endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Review {
+    /// Missing for deleted accounts
+    pub user: Option<Account>,
+    /// `APPROVED`, `CHANGES_REQUESTED`, `COMMENTED`, `DISMISSED` or `PENDING`
+    pub state: String,
+}
+
+/// Status of a user owner, from the account GitHub returns for the login if any
+fn user_status(user: Option<&GitHubUser>) -> OwnerStatus {
+    match user {
+        None => OwnerStatus::Unknown,
+        Some(user) if user.kind == "Organization" => OwnerStatus::Organization,
+        Some(user) if user.suspended_at.is_some() => OwnerStatus::Suspended,
+        Some(_) => OwnerStatus::Valid,
+    }
+}
+
+/// Client for the GitHub REST API
+pub struct GitHubClient {
+    api: ApiClient,
+}
+
+impl GitHubClient {
+    /// Client for the API URL of `ctx`, authenticated with `GITHUB_TOKEN` or `GH_TOKEN`,
+    /// or else the `github_token` setting
+    ///
+    /// In `offline` mode every answer comes from responses cached by earlier runs.
+    pub fn new(ctx: &Context, offline: bool) -> GitHubClient {
+        let token = ["GITHUB_TOKEN", "GH_TOKEN"]
+            .iter()
+            .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+            .or_else(|| Some(ctx.config.github_token.clone()).filter(|token| !token.is_empty()));
+        let api = ApiClient::new(&ctx.config.github_api_url)
+            .header(
+                "User-Agent",
+                concat!("codeinput/", env!("CARGO_PKG_VERSION")),
+            )
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .auth(token.map(Auth::Bearer))
+            .offline(offline);
+        GitHubClient { api }
+    }
+
+    /// Every item of the paged list at `path`; `None` when GitHub answers 404
+    fn all_pages<T: DeserializeOwned>(&self, path: &str) -> Result<Option<Vec<T>>> {
+        let per_page = PER_PAGE.to_string();
+        let mut items = Vec::new();
+        for page in 1.. {
+            let page = page.to_string();
+            let query = [("per_page", per_page.as_str()), ("page", page.as_str())];
+            let Some(response) = self.api.get(path, &query, "application/vnd.github+json")? else {
+                return Ok(None);
+            };
+            let batch: Vec<T> = response.json()?;
+            let last = batch.len() < PER_PAGE;
+            items.extend(batch);
+            if last {
+                break;
+            }
+        }
+        Ok(Some(items))
+    }
+
+    /// GET `path` at `git_ref`; `None` when GitHub answers 404
+    fn get(&self, path: &str, git_ref: &str, accept: &str) -> Result<Option<ApiResponse>> {
+        self.api.get(path, &[("ref", git_ref)], accept)
+    }
+
+    /// The CODEOWNERS file GitHub uses for `repo` at `git_ref`, as its location and contents
+    pub fn codeowners_file(
+        &self, repo: &GitHubRepo, git_ref: &str,
+    ) -> Result<Option<(String, String)>> {
+        for location in CODEOWNERS_LOCATIONS {
+            let path = format!("/repos/{}/contents/{}", repo, location);
+            if let Some(response) = self.get(&path, git_ref, "application/vnd.github.raw")? {
+                return Ok(Some((location.to_string(), response.body)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The lines GitHub rejects in the CODEOWNERS file of `repo` at `git_ref`
+    pub fn codeowners_errors(
+        &self, repo: &GitHubRepo, git_ref: &str,
+    ) -> Result<Vec<CodeownersError>> {
+        let path = format!("/repos/{}/codeowners/errors", repo);
+        let Some(response) = self.get(&path, git_ref, "application/vnd.github+json")? else {
+            return Ok(Vec::new());
+        };
+        Ok(response.json::<CodeownersErrors>()?.errors)
+    }
+
+    /// Whether `owner` exists on GitHub and, for a team, whether it can access `repo`
+    ///
+    /// `None` for owners GitHub cannot be asked about: emails, which it matches against
+    /// verified addresses, and `NOOWNER`. Teams are only visible to a token of their
+    /// organization with the `read:org` scope.
+    pub fn owner_status(&self, owner: &Owner, repo: &GitHubRepo) -> Result<Option<OwnerStatus>> {
+        let name = owner.identifier.trim_start_matches('@');
+        let accept = "application/vnd.github+json";
+        match owner.owner_type {
+            OwnerType::User => {
+                let user = self
+                    .api
+                    .get(&format!("/users/{}", name), &[], accept)?
+                    .map(|response| response.json::<GitHubUser>())
+                    .transpose()?;
+                Ok(Some(user_status(user.as_ref())))
+            }
+            OwnerType::Team => {
+                let Some((org, slug)) = name.split_once('/') else {
+                    return Ok(Some(OwnerStatus::Unknown));
+                };
+                let team = format!("/orgs/{}/teams/{}", org, slug);
+                if self.api.get(&team, &[], accept)?.is_none() {
+                    return Ok(Some(OwnerStatus::Unknown));
+                }
+                // 204 when the team has access, 404 when it does not
+                let access = format!("{}/repos/{}", team, repo);
+                Ok(Some(match self.api.get(&access, &[], accept)? {
+                    Some(_) => OwnerStatus::Valid,
+                    None => OwnerStatus::NoRepoAccess,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Logins of the members of the team `owner` names, including those of its child
+    /// teams, sorted
+    ///
+    /// `None` when the owner is not a team GitHub shows the token.
+    pub fn team_members(&self, owner: &Owner) -> Result<Option<Vec<String>>> {
+        let name = owner.identifier.trim_start_matches('@');
+        let Some((org, slug)) = name
+            .split_once('/')
+            .filter(|_| owner.owner_type == OwnerType::Team)
+        else {
+            return Ok(None);
+        };
+        let path = format!("/orgs/{}/teams/{}/members", org, slug);
+        let Some(members) = self.all_pages::<TeamMember>(&path)? else {
+            return Ok(None);
+        };
+        let mut members: Vec<String> = members
+            .into_iter()
+            .map(|member| format!("@{}", member.login))
+            .collect();
+        members.sort();
+        members.dedup();
+        Ok(Some(members))
+    }
+
+    /// Pull requests of `repo` merged since `since`, or ever with `None`, the most recently
+    /// updated first, at most `limit` of them
+    pub fn merged_pulls(
+        &self, repo: &GitHubRepo, since: Option<DateTime<Utc>>, limit: usize,
+    ) -> Result<Vec<PullRequest>> {
+        let path = format!("/repos/{}/pulls", repo);
+        let per_page = PER_PAGE.to_string();
+        let mut pulls = Vec::new();
+        for page in 1.. {
+            let page = page.to_string();
+            let query = [
+                ("state", "closed"),
+                ("sort", "updated"),
+                ("direction", "desc"),
+                ("per_page", per_page.as_str()),
+                ("page", page.as_str()),
+            ];
+            let Some(response) = self.api.get(&path, &query, "application/vnd.github+json")? else {
+                return Err(Error::new(&format!(
+                    "No pull requests for {}; check the repository and that the token can read it",
+                    repo
+                )));
+            };
+            let batch: Vec<PullRequest> = response.json()?;
+            let last = batch.len() < PER_PAGE;
+            for pull in batch {
+                // A pull request is merged before it was last updated, so older ones follow
+                if since.is_some_and(|since| pull.updated_at < since) {
+                    return Ok(pulls);
+                }
+                if pull
+                    .merged_at
+                    .is_some_and(|merged| since.is_none_or(|since| merged >= since))
+                {
+                    pulls.push(pull);
+                    if pulls.len() >= limit {
+                        return Ok(pulls);
+                    }
+                }
+            }
+            if last {
+                break;
+            }
+        }
+        Ok(pulls)
+    }
+
+    /// Files the pull request `number` of `repo` changed
+    pub fn pull_files(&self, repo: &GitHubRepo, number: u64) -> Result<Vec<PullRequestFile>> {
+        let path = format!("/repos/{}/pulls/{}/files", repo, number);
+        Ok(self.all_pages(&path)?.unwrap_or_default())
+    }
+
+    /// Reviews of the pull request `number` of `repo`, oldest first
+    pub fn pull_reviews(&self, repo: &GitHubRepo, number: u64) -> Result<Vec<Review>> {
+        let path = format!("/repos/{}/pulls/{}/reviews", repo, number);
+        Ok(self.all_pages(&path)?.unwrap_or_default())
+    }
+}
+
+/// The rules GitHub applies from `contents`, the CODEOWNERS file at `location`
+///
+/// Lines GitHub reports in `errors` are dropped. The rules are scoped to the repository
+/// root even when the file lives in `.github/` or `docs/`.
+pub fn github_entries(
+    contents: &str, location: &str, errors: &[CodeownersError],
+) -> Result<Vec<CodeownersEntry>> {
+    let mut entries = parse_content(contents, &Path::new(ROOT).join("CODEOWNERS"))?;
+    entries.retain(|entry| {
+        !errors
+            .iter()
+            .any(|error| error.path == location && error.line == entry.line_number + 1)
+    });
+    Ok(entries)
+}
+
+/// Owners GitHub resolves for `path`, relative to the repository root, lowercased and sorted
+pub fn github_owners(
+    entries: &[CodeownersEntry], matcher: &Matcher, path: &Path,
+) -> Result<Vec<String>> {
+    let file = Path::new(ROOT).join(path);
+    let mut owners: Vec<String> = match find_entry_for_file(&file, entries, matcher)? {
+        Some(index) => entries[index]
+            .owners
+            .iter()
+            .map(|owner| owner.identifier.to_lowercase())
+            .collect(),
+        None => Vec::new(),
+    };
+    owners.sort();
+    owners.dedup();
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::matcher::MatcherStrategy;
+
+    #[test]
+    fn test_repo_from_remote_url() {
+        let expected = Some(GitHubRepo {
+            owner: "code-input".to_string(),
+            name: "cli".to_string(),
+        });
+        assert_eq!(
+            GitHubRepo::from_remote_url("git@github.com:code-input/cli.git"),
+            expected
+        );
+        assert_eq!(
+            GitHubRepo::from_remote_url("https://github.com/code-input/cli"),
+            expected
+        );
+        assert_eq!(
+            GitHubRepo::from_remote_url("ssh://git@github.example.com/code-input/cli.git"),
+            expected
+        );
+        assert_eq!(GitHubRepo::from_remote_url("/srv/git/cli.git"), None);
+        assert!("code-input".parse::<GitHubRepo>().is_err());
+    }
+
+    #[test]
+    fn test_github_entries() {
+        let contents = "* @global\n/src/ @Src-Team\nsrc/legacy/ @ghost\n";
+        let errors = vec![CodeownersError {
+            line: 3,
+            kind: "Unknown owner".to_string(),
+            message: String::new(),
+            path: ".github/CODEOWNERS".to_string(),
+        }];
+        let entries = github_entries(contents, ".github/CODEOWNERS", &errors).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // Rules in .github/ still apply from the repository root
+        let matcher = Matcher::build(&entries, MatcherStrategy::Override);
+        let owners = |path: &str| github_owners(&entries, &matcher, Path::new(path)).unwrap();
+        assert_eq!(owners("README.md"), vec!["@global"]);
+        assert_eq!(owners("src/legacy/old.rs"), vec!["@src-team"]);
+
+        // Errors in another file do not apply
+        let entries = github_entries(contents, "CODEOWNERS", &errors).unwrap();
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn test_user_status() {
+        let user = |json: &str| serde_json::from_str::<GitHubUser>(json).unwrap();
+        assert_eq!(user_status(None), OwnerStatus::Unknown);
+        assert_eq!(
+            user_status(Some(&user(r#"{"login":"octocat","type":"User"}"#))),
+            OwnerStatus::Valid
+        );
+        assert_eq!(
+            user_status(Some(&user(r#"{"type":"Organization"}"#))),
+            OwnerStatus::Organization
+        );
+        assert_eq!(
+            user_status(Some(&user(
+                r#"{"type":"User","suspended_at":"2024-01-01T00:00:00Z"}"#
+            ))),
+            OwnerStatus::Suspended
+        );
+    }
+}
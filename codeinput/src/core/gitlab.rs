@@ -0,0 +1,318 @@
+//! GitLab's side of owner verification, for `codeowners verify-owners --provider gitlab`
+//!
+//! GitLab requests reviews from users, from groups and subgroups with access to the
+//! project, and from users matched by email. A top-level group is written like a user
+//! (`@group`), so a user owner GitLab knows no user for is looked up as a group too. A
+//! group has access when the project lives below it or the project is shared with it.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use super::api_client::{ApiClient, Auth};
+use super::context::Context;
+use super::github::OwnerStatus;
+use super::types::{Owner, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Members requested per page when listing the members of a group
+const MEMBERS_PER_PAGE: usize = 100;
+
+/// A project on GitLab, as its full path below any number of groups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitLabProject {
+    pub path: String,
+}
+
+impl fmt::Display for GitLabProject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+impl FromStr for GitLabProject {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let path = s.trim_matches('/').trim_end_matches(".git");
+        if path.contains('/') && path.split('/').all(|part| !part.is_empty()) {
+            Ok(GitLabProject {
+                path: path.to_string(),
+            })
+        } else {
+            Err(format!(
+                "Invalid GitLab project: {} (expected GROUP/PROJECT)",
+                s
+            ))
+        }
+    }
+}
+
+impl GitLabProject {
+    /// The project a remote URL points at, for SSH and HTTPS remotes on any host
+    pub fn from_remote_url(url: &str) -> Option<GitLabProject> {
+        let path = if let Some((_, rest)) = url.split_once("://") {
+            // https://host/group/sub/project.git or ssh://git@host/group/project.git
+            rest.split_once('/')?.1
+        } else {
+            // git@host:group/sub/project.git
+            url.split_once(':')?.1
+        };
+        path.parse().ok()
+    }
+
+    /// The project behind the `origin` remote of the git repository at `path`
+    pub fn discover(path: &Path) -> Result<GitLabProject> {
+        let repo = git2::Repository::discover(path)
+            .map_err(|e| Error::with_source("Failed to open git repository", Box::new(e)))?;
+        let remote = repo.find_remote("origin").map_err(|_| {
+            Error::new("No origin remote; pass the project with --gitlab-project GROUP/PROJECT")
+        })?;
+        remote
+            .url()
+            .and_then(GitLabProject::from_remote_url)
+            .ok_or_else(|| {
+                Error::new(&format!(
+                    "Cannot tell the GitLab project from origin {}; pass --gitlab-project GROUP/PROJECT",
+                    remote.url().unwrap_or("(not UTF-8)")
+                ))
+            })
+    }
+}
+
+/// A project from the `projects` endpoint, with the groups that can access it
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectAccess {
+    namespace: Namespace,
+    #[serde(default)]
+    shared_with_groups: Vec<SharedGroup>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Namespace {
+    full_path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SharedGroup {
+    group_full_path: String,
+}
+
+impl ProjectAccess {
+    /// Whether members of the group at `path` can review the project: it is the group
+    /// holding the project, one above it, or a group the project is shared with
+    fn grants(&self, path: &str) -> bool {
+        let path = path.to_lowercase();
+        let namespace = self.namespace.full_path.to_lowercase();
+        namespace == path
+            || namespace.starts_with(&format!("{}/", path))
+            || self
+                .shared_with_groups
+                .iter()
+                .any(|group| group.group_full_path.to_lowercase() == path)
+    }
+}
+
+/// An account from the `users` endpoint
+#[derive(Debug, Clone, Deserialize)]
+struct GitLabUser {
+    username: String,
+    /// `active`, or `blocked`, `deactivated` and the like for accounts that cannot review
+    #[serde(default)]
+    state: String,
+}
+
+/// Status of a user owner, from the account GitLab returns for the username if any
+fn user_status(user: Option<&GitLabUser>) -> OwnerStatus {
+    match user {
+        None => OwnerStatus::Unknown,
+        Some(user) if user.state == "active" => OwnerStatus::Valid,
+        Some(_) => OwnerStatus::Suspended,
+    }
+}
+
+/// Path of a group or project in an API URL, where `/` is escaped
+fn encode_path(path: &str) -> String {
+    path.replace('/', "%2F")
+}
+
+/// Client for the GitLab REST API
+pub struct GitLabClient {
+    api: ApiClient,
+}
+
+impl GitLabClient {
+    /// Client for the API URL of `ctx`, authenticated with `GITLAB_TOKEN`, or else the
+    /// `gitlab_token` setting
+    ///
+    /// In `offline` mode every answer comes from responses cached by earlier runs.
+    pub fn new(ctx: &Context, offline: bool) -> GitLabClient {
+        let token = std::env::var("GITLAB_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .or_else(|| Some(ctx.config.gitlab_token.clone()).filter(|token| !token.is_empty()));
+        let api = ApiClient::new(&ctx.config.gitlab_api_url)
+            .header(
+                "User-Agent",
+                concat!("codeinput/", env!("CARGO_PKG_VERSION")),
+            )
+            .auth(token.map(|value| Auth::Header {
+                name: "PRIVATE-TOKEN".to_string(),
+                value,
+            }))
+            .offline(offline);
+        GitLabClient { api }
+    }
+
+    /// The groups that can access `project`
+    pub fn project_access(&self, project: &GitLabProject) -> Result<ProjectAccess> {
+        let path = format!("/projects/{}", encode_path(&project.path));
+        match self.api.get(&path, &[], "application/json")? {
+            Some(response) => response.json(),
+            None => Err(Error::new(&format!(
+                "GitLab project {} not found, or the token cannot see it",
+                project
+            ))),
+        }
+    }
+
+    /// Whether a group exists at `path`
+    fn group_exists(&self, path: &str) -> Result<bool> {
+        let path = format!("/groups/{}", encode_path(path));
+        Ok(self.api.get(&path, &[], "application/json")?.is_some())
+    }
+
+    /// Whether `owner` exists on GitLab and, for a group, whether it can access the
+    /// project of `access`
+    ///
+    /// `None` for owners GitLab cannot be asked about: emails, which it matches against
+    /// users' addresses, and `NOOWNER`. Private groups are only visible to a token of
+    /// one of their members.
+    pub fn owner_status(
+        &self, owner: &Owner, access: &ProjectAccess,
+    ) -> Result<Option<OwnerStatus>> {
+        let name = owner.identifier.trim_start_matches('@');
+        let group_status = |path: &str| -> Result<OwnerStatus> {
+            Ok(if !self.group_exists(path)? {
+                OwnerStatus::Unknown
+            } else if access.grants(path) {
+                OwnerStatus::Valid
+            } else {
+                OwnerStatus::NoRepoAccess
+            })
+        };
+        match owner.owner_type {
+            OwnerType::User => {
+                let users = self
+                    .api
+                    .get("/users", &[("username", name)], "application/json")?
+                    .map(|response| response.json::<Vec<GitLabUser>>())
+                    .transpose()?
+                    .unwrap_or_default();
+                let user = users
+                    .iter()
+                    .find(|user| user.username.eq_ignore_ascii_case(name));
+                match user_status(user) {
+                    // `@name` may be a top-level group
+                    OwnerStatus::Unknown => group_status(name).map(Some),
+                    status => Ok(Some(status)),
+                }
+            }
+            OwnerType::Team => group_status(name).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Usernames of the active members of the group `owner` names, including those
+    /// inherited from groups above it, sorted
+    ///
+    /// `None` when the owner is not a group GitLab knows.
+    pub fn group_members(&self, owner: &Owner) -> Result<Option<Vec<String>>> {
+        if !matches!(owner.owner_type, OwnerType::User | OwnerType::Team) {
+            return Ok(None);
+        }
+        let name = owner.identifier.trim_start_matches('@');
+        let path = format!("/groups/{}/members/all", encode_path(name));
+        let per_page = MEMBERS_PER_PAGE.to_string();
+
+        let mut members = Vec::new();
+        for page in 1.. {
+            let page = page.to_string();
+            let query = [("per_page", per_page.as_str()), ("page", page.as_str())];
+            let Some(response) = self.api.get(&path, &query, "application/json")? else {
+                return Ok(None);
+            };
+            let batch: Vec<GitLabUser> = response.json()?;
+            let last = batch.len() < MEMBERS_PER_PAGE;
+            members.extend(
+                batch
+                    .into_iter()
+                    .filter(|member| member.state.is_empty() || member.state == "active")
+                    .map(|member| format!("@{}", member.username)),
+            );
+            if last {
+                break;
+            }
+        }
+        members.sort();
+        members.dedup();
+        Ok(Some(members))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_from_remote_url() {
+        let expected = Some(GitLabProject {
+            path: "acme/platform/api".to_string(),
+        });
+        assert_eq!(
+            GitLabProject::from_remote_url("git@gitlab.com:acme/platform/api.git"),
+            expected
+        );
+        assert_eq!(
+            GitLabProject::from_remote_url("https://gitlab.example.com/acme/platform/api"),
+            expected
+        );
+        assert_eq!(GitLabProject::from_remote_url("/srv/git/api.git"), None);
+        assert!("api".parse::<GitLabProject>().is_err());
+        assert!("acme//api".parse::<GitLabProject>().is_err());
+    }
+
+    #[test]
+    fn test_project_access_grants() {
+        let access: ProjectAccess = serde_json::from_str(
+            r#"{
+                "namespace": {"full_path": "acme/platform"},
+                "shared_with_groups": [{"group_full_path": "partners/reviewers"}]
+            }"#,
+        )
+        .unwrap();
+        assert!(access.grants("acme/platform"));
+        assert!(access.grants("Acme"));
+        assert!(access.grants("partners/reviewers"));
+        // Subgroups of the project's group are not granted access
+        assert!(!access.grants("acme/platform/web"));
+        assert!(!access.grants("acme/plat"));
+        assert!(!access.grants("partners"));
+    }
+
+    #[test]
+    fn test_user_status() {
+        let user = |json: &str| serde_json::from_str::<GitLabUser>(json).unwrap();
+        assert_eq!(user_status(None), OwnerStatus::Unknown);
+        assert_eq!(
+            user_status(Some(&user(r#"{"username":"alice","state":"active"}"#))),
+            OwnerStatus::Valid
+        );
+        assert_eq!(
+            user_status(Some(&user(r#"{"username":"bob","state":"blocked"}"#))),
+            OwnerStatus::Suspended
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Size and activity of the files of each owner, for treemaps
+//!
+//! Every file is listed with its size in bytes, its primary owner and its churn, the
+//! number of commits that changed it in the time window. The CSV output is one row per
+//! file, ready for `d3.stratify` or a spreadsheet; the JSON output nests the files in
+//! their directories, the shape `d3.hierarchy` and Observable's treemap expect, with each
+//! directory summing up its size and churn and naming the owner of most of its bytes.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use git2::{Repository, Sort};
+use serde::Serialize;
+
+use super::types::CodeownersCache;
+use crate::utils::error::{Error, Result};
+use crate::utils::interrupt;
+
+/// Output format of the heatmap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapFormat {
+    Json,
+    Csv,
+}
+
+impl std::fmt::Display for HeatmapFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HeatmapFormat::Json => write!(f, "json"),
+            HeatmapFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+/// One file of the heatmap
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeatmapFile {
+    /// Path relative to the repository root
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// First owner of the file; `None` when unowned
+    pub owner: Option<String>,
+    /// Commits that changed the file in the time window
+    pub churn: usize,
+}
+
+/// A directory or file of the heatmap tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeatmapNode {
+    pub name: String,
+    /// Path relative to the repository root; empty for the root
+    pub path: PathBuf,
+    /// Size in bytes, summed over the files below a directory
+    pub size: u64,
+    /// Owner of the file, or of most bytes below a directory
+    pub owner: Option<String>,
+    /// Commits that changed the file, summed over the files below a directory
+    pub churn: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<HeatmapNode>,
+}
+
+/// The files of `cache` with their size, primary owner and the commits on the
+/// first-parent history of `repo` that changed them since `since`, sorted by path
+///
+/// Generated files and files missing from the working tree are left out.
+pub fn heatmap(
+    repo: &Path, cache: &CodeownersCache, since: DateTime<Utc>,
+) -> Result<Vec<HeatmapFile>> {
+    let churn = file_churn(repo, since)?;
+
+    let mut files = Vec::new();
+    for file in cache.files.iter().filter(|file| !file.generated) {
+        let Ok(metadata) = std::fs::metadata(&file.path) else {
+            continue;
+        };
+        let path = file.path.strip_prefix(repo).unwrap_or(&file.path);
+        let path = path.strip_prefix(".").unwrap_or(path).to_path_buf();
+        files.push(HeatmapFile {
+            churn: churn.get(&path).copied().unwrap_or(0),
+            path,
+            size: metadata.len(),
+            owner: file.owners.first().map(|owner| owner.identifier.clone()),
+        });
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+/// Commits on the first-parent history of `repo` since `since` that changed each path,
+/// relative to the repository root
+///
+/// A renamed file counts under both names. An unborn HEAD has no churn.
+pub fn file_churn(repo: &Path, since: DateTime<Utc>) -> Result<HashMap<PathBuf, usize>> {
+    let repository = Repository::discover(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let mut churn = HashMap::new();
+    if repository.head().is_err() {
+        return Ok(churn);
+    }
+
+    let mut walk = repository.revwalk()?;
+    walk.push_head()?;
+    walk.simplify_first_parent()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+
+    for oid in walk {
+        interrupt::check()?;
+        let commit = repository.find_commit(oid?)?;
+        if commit.time().seconds() < since.timestamp() {
+            break;
+        }
+
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        let touched: HashSet<&Path> = diff
+            .deltas()
+            .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+            .flatten()
+            .collect();
+        for path in touched {
+            *churn.entry(path.to_path_buf()).or_insert(0) += 1;
+        }
+    }
+
+    Ok(churn)
+}
+
+/// `files` nested in their directories under a root node
+pub fn tree(files: &[HeatmapFile]) -> HeatmapNode {
+    #[derive(Default)]
+    struct Directory<'a> {
+        directories: BTreeMap<String, Directory<'a>>,
+        files: Vec<&'a HeatmapFile>,
+    }
+
+    let mut root = Directory::default();
+    for file in files {
+        let mut directory = &mut root;
+        if let Some(parent) = file.path.parent() {
+            for component in parent.components() {
+                let name = component.as_os_str().to_string_lossy().into_owned();
+                directory = directory.directories.entry(name).or_default();
+            }
+        }
+        directory.files.push(file);
+    }
+
+    /// The node of `directory` at `path`, with the bytes of each owner below it
+    fn build(
+        name: String, path: PathBuf, directory: Directory<'_>,
+    ) -> (HeatmapNode, BTreeMap<Option<String>, u64>) {
+        let mut bytes: BTreeMap<Option<String>, u64> = BTreeMap::new();
+        let mut children = Vec::new();
+        for (name, subdirectory) in directory.directories {
+            let (child, child_bytes) = build(name.clone(), path.join(&name), subdirectory);
+            for (owner, size) in child_bytes {
+                *bytes.entry(owner).or_insert(0) += size;
+            }
+            children.push(child);
+        }
+        for file in directory.files {
+            *bytes.entry(file.owner.clone()).or_insert(0) += file.size;
+            children.push(HeatmapNode {
+                name: file
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path: file.path.clone(),
+                size: file.size,
+                owner: file.owner.clone(),
+                churn: file.churn,
+                children: Vec::new(),
+            });
+        }
+
+        // The first owner in order wins a tie
+        let mut owner: Option<(&Option<String>, u64)> = None;
+        for (candidate, &size) in &bytes {
+            if owner.is_none_or(|(_, most)| size > most) {
+                owner = Some((candidate, size));
+            }
+        }
+        let node = HeatmapNode {
+            name,
+            path,
+            size: children.iter().map(|child| child.size).sum(),
+            owner: owner.and_then(|(owner, _)| owner.clone()),
+            churn: children.iter().map(|child| child.churn).sum(),
+            children,
+        };
+        (node, bytes)
+    }
+
+    build(String::new(), PathBuf::new(), root).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64, owner: Option<&str>, churn: usize) -> HeatmapFile {
+        HeatmapFile {
+            path: PathBuf::from(path),
+            size,
+            owner: owner.map(str::to_string),
+            churn,
+        }
+    }
+
+    #[test]
+    fn test_tree_sums_directories() {
+        let files = [
+            file("README.md", 10, None, 1),
+            file("src/lib.rs", 100, Some("@core"), 4),
+            file("src/ui/app.rs", 60, Some("@web"), 2),
+            file("src/ui/style.css", 50, Some("@web"), 0),
+        ];
+        let root = tree(&files);
+
+        assert_eq!(root.size, 220);
+        assert_eq!(root.churn, 7);
+        assert_eq!(root.owner.as_deref(), Some("@web"));
+        assert_eq!(root.children.len(), 2);
+
+        let src = &root.children[0];
+        assert_eq!(src.path, PathBuf::from("src"));
+        assert_eq!(src.size, 210);
+        assert_eq!(src.owner.as_deref(), Some("@web"));
+        let ui = &src.children[0];
+        assert_eq!(ui.name, "ui");
+        assert_eq!(ui.path, PathBuf::from("src/ui"));
+        assert_eq!(ui.children.len(), 2);
+        assert_eq!(src.children[1].name, "lib.rs");
+        assert!(src.children[1].children.is_empty());
+
+        assert_eq!(root.children[1].name, "README.md");
+        assert_eq!(root.children[1].owner, None);
+    }
+}
@@ -0,0 +1,159 @@
+//! Owner identifier styles, for `codeowners list-owners --by-type`
+//!
+//! CODEOWNERS can name a person by handle or by email address. Files owned by
+//! `alice@corp.com` and by `@alice` then look like they have different owners, so
+//! ownership reports split. Pairs of an email address and a handle that probably name the
+//! same person are flagged, with an alias mapping the address to the handle.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::types::{Owner, OwnerType};
+
+/// Owner types in report order
+pub const OWNER_TYPES: [OwnerType; 5] = [
+    OwnerType::User,
+    OwnerType::Team,
+    OwnerType::Email,
+    OwnerType::Unowned,
+    OwnerType::Unknown,
+];
+
+/// Owners and owned files of one owner type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TypeStats {
+    pub owner_type: OwnerType,
+    pub owners: usize,
+    /// Files with at least one owner of the type
+    pub files: usize,
+}
+
+/// An email address and a handle that probably name the same person
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MixedIdentity {
+    pub email: String,
+    pub handle: String,
+}
+
+impl MixedIdentity {
+    /// Alias mapping the address to the handle, as a TOML line
+    pub fn alias(&self) -> String {
+        format!(
+            "{} = {}",
+            toml::Value::String(self.email.clone()),
+            toml::Value::String(self.handle.clone())
+        )
+    }
+}
+
+/// Owners and files per owner type, for the types in use, in [`OWNER_TYPES`] order
+pub fn type_breakdown(owners_map: &HashMap<Owner, Vec<PathBuf>>) -> Vec<TypeStats> {
+    OWNER_TYPES
+        .iter()
+        .filter_map(|owner_type| {
+            let owners: Vec<&Vec<PathBuf>> = owners_map
+                .iter()
+                .filter(|(owner, _)| owner.owner_type == *owner_type)
+                .map(|(_, files)| files)
+                .collect();
+            let files: HashSet<&PathBuf> = owners.iter().copied().flatten().collect();
+            (!owners.is_empty()).then(|| TypeStats {
+                owner_type: owner_type.clone(),
+                owners: owners.len(),
+                files: files.len(),
+            })
+        })
+        .collect()
+}
+
+/// Email owners whose local part matches a user handle, sorted by address
+///
+/// Case, `.`, `-` and `_` are ignored and a `+suffix` is dropped, so
+/// `Alice.Smith+ci@corp.com` pairs with `@alice-smith`.
+pub fn mixed_identities<'a>(owners: impl IntoIterator<Item = &'a Owner>) -> Vec<MixedIdentity> {
+    let owners: Vec<&Owner> = owners.into_iter().collect();
+    let handles: HashMap<String, &str> = owners
+        .iter()
+        .filter(|owner| owner.owner_type == OwnerType::User)
+        .map(|owner| {
+            let name = owner.identifier.trim_start_matches('@');
+            (normalize(name), owner.identifier.as_str())
+        })
+        .collect();
+
+    let mut mixed: Vec<MixedIdentity> = owners
+        .iter()
+        .filter(|owner| owner.owner_type == OwnerType::Email)
+        .filter_map(|owner| {
+            let local = owner.identifier.split('@').next()?;
+            let local = local.split('+').next().unwrap_or(local);
+            let handle = handles.get(&normalize(local))?;
+            Some(MixedIdentity {
+                email: owner.identifier.clone(),
+                handle: handle.to_string(),
+            })
+        })
+        .collect();
+    mixed.sort_by(|a, b| a.email.cmp(&b.email).then_with(|| a.handle.cmp(&b.handle)));
+    mixed.dedup();
+    mixed
+}
+
+/// `name` lowercased without separators
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, '.' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    #[test]
+    fn test_identities() {
+        let owners_map: HashMap<Owner, Vec<PathBuf>> = [
+            (owner("@alice-smith", OwnerType::User), vec!["a.rs", "b.rs"]),
+            (
+                owner("Alice.Smith+ci@corp.com", OwnerType::Email),
+                vec!["b.rs", "c.rs"],
+            ),
+            (owner("bob@corp.com", OwnerType::Email), vec!["d.rs"]),
+            (owner("@org/web", OwnerType::Team), vec!["e.rs"]),
+        ]
+        .into_iter()
+        .map(|(owner, files)| (owner, files.into_iter().map(PathBuf::from).collect()))
+        .collect();
+
+        let breakdown = type_breakdown(&owners_map);
+        assert_eq!(
+            breakdown
+                .iter()
+                .map(|stats| (stats.owner_type.clone(), stats.owners, stats.files))
+                .collect::<Vec<_>>(),
+            [
+                (OwnerType::User, 1, 2),
+                (OwnerType::Team, 1, 1),
+                (OwnerType::Email, 2, 3),
+            ]
+        );
+
+        let mixed = mixed_identities(owners_map.keys());
+        assert_eq!(mixed.len(), 1);
+        assert_eq!(mixed[0].handle, "@alice-smith");
+        assert_eq!(
+            mixed[0].alias(),
+            r#""Alice.Smith+ci@corp.com" = "@alice-smith""#
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! What removing an owner would leave behind, reported by `codeowners impact`
+//!
+//! Run before offboarding someone: files naming them as their only owner become unowned
+//! once they are removed, while files they share keep their other owners.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::types::{CodeownersCache, OwnerType};
+
+/// A rule naming the removed owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NamingRule {
+    pub source_file: PathBuf,
+    /// 1-based line of the rule
+    pub line: usize,
+    pub pattern: String,
+    /// Whether the rule is left without owners once the owner is removed
+    pub sole_owner: bool,
+}
+
+/// Files and rules affected by removing one owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OwnerImpact {
+    pub owner: String,
+    /// Files whose only owner is the removed one
+    pub orphaned: Vec<PathBuf>,
+    /// Files that keep at least one other owner
+    pub reduced: Vec<PathBuf>,
+    pub rules: Vec<NamingRule>,
+}
+
+/// Files and rules of `cache` affected by removing `owner`, compared case-insensitively
+pub fn analyze(cache: &CodeownersCache, owner: &str) -> OwnerImpact {
+    let target = owner.to_lowercase();
+    let is_target = |identifier: &str| identifier.to_lowercase() == target;
+
+    let mut orphaned = Vec::new();
+    let mut reduced = Vec::new();
+    for file in &cache.files {
+        let owners: Vec<_> = file
+            .owners
+            .iter()
+            .filter(|o| o.owner_type != OwnerType::Unowned)
+            .collect();
+        if !owners.iter().any(|o| is_target(&o.identifier)) {
+            continue;
+        }
+        if owners.iter().all(|o| is_target(&o.identifier)) {
+            orphaned.push(file.path.clone());
+        } else {
+            reduced.push(file.path.clone());
+        }
+    }
+
+    let rules = cache
+        .entries
+        .iter()
+        .filter(|entry| entry.owners.iter().any(|o| is_target(&o.identifier)))
+        .map(|entry| NamingRule {
+            source_file: entry.source_file.clone(),
+            line: entry.line_number + 1,
+            pattern: entry.pattern.clone(),
+            sole_owner: entry.owners.iter().all(|o| is_target(&o.identifier)),
+        })
+        .collect();
+
+    OwnerImpact {
+        owner: owner.to_string(),
+        orphaned,
+        reduced,
+        rules,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{CodeownersEntry, FileEntry, Owner};
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let entry = |line_number, pattern: &str, owners| CodeownersEntry {
+            source_file: PathBuf::from("CODEOWNERS"),
+            line_number,
+            pattern: pattern.to_string(),
+            owners,
+            tags: Vec::new(),
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let entries = vec![
+            entry(0, "*.rs", vec![owner("@Alice", OwnerType::User)]),
+            entry(
+                1,
+                "api/**",
+                vec![
+                    owner("@alice", OwnerType::User),
+                    owner("@org/api", OwnerType::Team),
+                ],
+            ),
+        ];
+        let file = |path: &str, owners| FileEntry {
+            path: PathBuf::from(path),
+            owners,
+            tags: Vec::new(),
+            generated: false,
+            inline: None,
+        };
+        let files = vec![
+            file("lib.rs", vec![owner("@Alice", OwnerType::User)]),
+            file(
+                "api/mod.rs",
+                vec![
+                    owner("@alice", OwnerType::User),
+                    owner("@org/api", OwnerType::Team),
+                ],
+            ),
+            file("README.md", vec![owner("NOBODY", OwnerType::Unowned)]),
+        ];
+        let cache = CodeownersCache::new([0; 32], entries, files, Vec::new());
+
+        let impact = analyze(&cache, "@alice");
+        assert_eq!(impact.orphaned, vec![PathBuf::from("lib.rs")]);
+        assert_eq!(impact.reduced, vec![PathBuf::from("api/mod.rs")]);
+        assert_eq!(
+            impact
+                .rules
+                .iter()
+                .map(|rule| (rule.line, rule.sole_owner))
+                .collect::<Vec<_>>(),
+            vec![(1, true), (2, false)]
+        );
+
+        let impact = analyze(&cache, "@bob");
+        assert!(impact.orphaned.is_empty() && impact.reduced.is_empty());
+        assert!(impact.rules.is_empty());
+    }
+}
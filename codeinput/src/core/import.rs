@@ -0,0 +1,259 @@
+//! Importing the CODEOWNERS rules of another repository under a directory prefix
+//!
+//! When repositories are consolidated into a monorepo, each one's rules have to keep
+//! owning its files at their new location. [`rewrite_entries`] anchors every rule of the
+//! other repository below the prefix, flattening nested CODEOWNERS files into one list
+//! ordered so that the last matching rule still wins, and [`check_import`] resolves the
+//! files of the other repository before and after the merge to find where the rules
+//! already in place disagree with the imported ones.
+
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use super::equivalence::same_owners;
+use super::matcher::{Matcher, MatcherStrategy};
+use super::parser::escape_pattern;
+use super::resolver::find_applied_entries;
+use super::types::{CodeownersEntry, MatchOptions, Owner, PrecedencePolicy};
+use crate::utils::error::{Error, Result};
+
+/// How one imported file is owned before and after the merge
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedFile {
+    /// Path of the file at its new location
+    pub path: PathBuf,
+    /// Owners in the other repository
+    pub imported: Vec<Owner>,
+    /// Owners the rules already in place give the new location
+    pub existing: Vec<Owner>,
+    /// Owners the new location has once the rules are merged
+    pub merged: Vec<Owner>,
+}
+
+impl ImportedFile {
+    /// Whether the rules in place already give the file other owners
+    pub fn conflicts(&self) -> bool {
+        !self.existing.is_empty() && !same_owners(&self.existing, &self.imported)
+    }
+
+    /// Whether the merged rules fail to give the file its imported owners, such as when a
+    /// CODEOWNERS file closer to it takes precedence
+    pub fn shadowed(&self) -> bool {
+        !same_owners(&self.merged, &self.imported)
+    }
+}
+
+/// `prefix` as a directory relative to the repository root, without leading or trailing
+/// `/`
+pub fn normalize_prefix(prefix: &str) -> Result<String> {
+    let trimmed = prefix.trim_matches('/');
+    let trimmed = trimmed.strip_prefix("./").unwrap_or(trimmed);
+    if trimmed.is_empty() || trimmed == "." {
+        return Err(Error::new(
+            "The prefix must name a directory below the repository root",
+        ));
+    }
+    if trimmed
+        .split('/')
+        .any(|component| component.is_empty() || component == "." || component == "..")
+    {
+        return Err(Error::new(&format!(
+            "Invalid prefix {}: expected a relative directory such as services/api/",
+            prefix
+        )));
+    }
+    Ok(trimmed.to_string())
+}
+
+/// `pattern`, written relative to a CODEOWNERS file's directory, anchored below `prefix`
+/// instead
+///
+/// `prefix` is the directory part of a pattern, escaped like a path. Patterns
+/// matching at any depth, with no `/` but a trailing one, match at any depth below the
+/// prefix.
+pub fn prefix_pattern(pattern: &str, prefix: &str) -> String {
+    if let Some(negated) = pattern.strip_prefix('!') {
+        return format!("!{}", prefix_pattern(negated, prefix));
+    }
+    if pattern == "*" || pattern == "**" {
+        return format!("/{}/", prefix);
+    }
+    if let Some(anchored) = pattern.strip_prefix('/') {
+        return format!("/{}/{}", prefix, anchored);
+    }
+    if pattern.trim_end_matches('/').contains('/') {
+        return format!("/{}/{}", prefix, pattern);
+    }
+    format!("/{}/**/{}", prefix, pattern)
+}
+
+/// The rules of another repository, checked out at `other_root`, rewritten for
+/// `target`, a CODEOWNERS file whose directory holds `prefix`, as from [`normalize_prefix`]
+///
+/// Rules of nested CODEOWNERS files are anchored at the prefix and their directory.
+/// Files closer to the root come first, keeping their line order, so a rule of a nested
+/// file still overrides the rules above it as the last match in `target`.
+pub fn rewrite_entries(
+    entries: &[CodeownersEntry], other_root: &Path, prefix: &str, target: &Path,
+) -> Vec<CodeownersEntry> {
+    let mut ordered: Vec<(usize, &CodeownersEntry)> = entries
+        .iter()
+        .map(|entry| {
+            let depth = relative_dir(entry, other_root).map_or(0, |dir| dir.components().count());
+            (depth, entry)
+        })
+        .collect();
+    // Stable, so lines keep their order within each file
+    ordered.sort_by(|(a_depth, a), (b_depth, b)| {
+        a_depth
+            .cmp(b_depth)
+            .then_with(|| a.source_file.cmp(&b.source_file))
+    });
+
+    ordered
+        .into_iter()
+        .map(|(_, entry)| {
+            let mut prefix = escape_pattern(prefix);
+            if let Some(dir) = relative_dir(entry, other_root) {
+                for component in dir.components() {
+                    prefix.push('/');
+                    prefix.push_str(&escape_pattern(&component.as_os_str().to_string_lossy()));
+                }
+            }
+            CodeownersEntry {
+                source_file: target.to_path_buf(),
+                pattern: prefix_pattern(&entry.pattern, &prefix),
+                ..entry.clone()
+            }
+        })
+        .collect()
+}
+
+/// Directory of `entry`'s CODEOWNERS file relative to `root`, `None` at the root itself
+fn relative_dir(entry: &CodeownersEntry, root: &Path) -> Option<PathBuf> {
+    let dir = entry.base_dir()?;
+    let relative = dir.strip_prefix(root).ok()?;
+    (!relative.as_os_str().is_empty()).then(|| relative.to_path_buf())
+}
+
+/// Resolve each of `files`, `(path in the other repository, new location)`, against the
+/// other repository's rules, the rules in place and the merged rules
+///
+/// Inline declarations are left out, as the new locations need not exist yet.
+pub fn check_import(
+    imported: &[CodeownersEntry], existing: &[CodeownersEntry], merged: &[CodeownersEntry],
+    files: &[(PathBuf, PathBuf)], options: MatchOptions, policy: PrecedencePolicy,
+) -> Result<Vec<ImportedFile>> {
+    let matchers = [imported, existing, merged]
+        .map(|entries| Matcher::build_with_options(entries, MatcherStrategy::Override, options));
+    let owners = |path: &Path, entries: &[CodeownersEntry], matcher: &Matcher| {
+        let applied = find_applied_entries(path, entries, matcher, policy)?;
+        let mut owners: Vec<Owner> = applied
+            .iter()
+            .flat_map(|&index| entries[index].owners.iter().cloned())
+            .collect();
+        let mut seen = std::collections::HashSet::new();
+        owners.retain(|owner| seen.insert(owner.identifier.clone()));
+        Ok::<_, Error>(owners)
+    };
+
+    files
+        .par_iter()
+        .map(|(original, placed)| {
+            Ok(ImportedFile {
+                path: placed.clone(),
+                imported: owners(original, imported, &matchers[0])?,
+                existing: owners(placed, existing, &matchers[1])?,
+                merged: owners(placed, merged, &matchers[2])?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::{format_line, parse_content};
+
+    #[test]
+    fn test_prefix_pattern() {
+        let prefix = normalize_prefix("/services/api/").unwrap();
+        assert_eq!(prefix, "services/api");
+        let cases = [
+            ("*", "/services/api/"),
+            ("*.rs", "/services/api/**/*.rs"),
+            ("build/", "/services/api/**/build/"),
+            ("/docs/", "/services/api/docs/"),
+            ("src/*.rs", "/services/api/src/*.rs"),
+            ("**/fixtures/**", "/services/api/**/fixtures/**"),
+            ("!/generated/", "!/services/api/generated/"),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(prefix_pattern(pattern, &prefix), expected, "{}", pattern);
+        }
+
+        assert!(normalize_prefix("/").is_err());
+        assert!(normalize_prefix("../elsewhere").is_err());
+        assert_eq!(
+            prefix_pattern("*.rs", &escape_pattern("my app")),
+            "/my\\ app/**/*.rs"
+        );
+    }
+
+    #[test]
+    fn test_import_keeps_ownership() {
+        let other = Path::new("/other");
+        let mut imported =
+            parse_content("* @api\n*.md @docs\n", &other.join("CODEOWNERS")).unwrap();
+        imported.extend(parse_content("* @db\n", &other.join("db/CODEOWNERS")).unwrap());
+        let target = Path::new("/mono/CODEOWNERS");
+        let root = "* @platform\n/services/ @services\n";
+        let existing = parse_content(root, target).unwrap();
+        let nested =
+            parse_content("* @legacy\n", Path::new("/mono/services/api/db/CODEOWNERS")).unwrap();
+
+        let rewritten = rewrite_entries(&imported, other, "services/api", target);
+        let patterns: Vec<&str> = rewritten.iter().map(|e| e.pattern.as_str()).collect();
+        assert_eq!(
+            patterns,
+            [
+                "/services/api/",
+                "/services/api/**/*.md",
+                "/services/api/db/"
+            ]
+        );
+
+        // As written to the root file, after its own rules
+        let lines: Vec<String> = rewritten.iter().map(format_line).collect();
+        let mut merged = parse_content(&format!("{}{}\n", root, lines.join("\n")), target).unwrap();
+        merged.extend(nested.clone());
+        let mut in_place = existing;
+        in_place.extend(nested);
+        let files: Vec<(PathBuf, PathBuf)> = ["main.rs", "README.md", "db/schema.sql"]
+            .iter()
+            .map(|file| (other.join(file), Path::new("/mono/services/api").join(file)))
+            .collect();
+        let checked = check_import(
+            &imported,
+            &in_place,
+            &merged,
+            &files,
+            MatchOptions::default(),
+            PrecedencePolicy::Closest,
+        )
+        .unwrap();
+
+        let owners = |owners: &[Owner]| -> Vec<String> {
+            owners.iter().map(|o| o.identifier.clone()).collect()
+        };
+        assert_eq!(owners(&checked[0].imported), ["@api"]);
+        assert_eq!(owners(&checked[0].existing), ["@services"]);
+        assert!(checked[0].conflicts() && !checked[0].shadowed());
+        assert_eq!(owners(&checked[1].merged), ["@docs"]);
+        // The nested file in the monorepo still wins
+        assert_eq!(owners(&checked[2].imported), ["@db"]);
+        assert_eq!(owners(&checked[2].merged), ["@legacy"]);
+        assert!(checked[2].conflicts() && checked[2].shadowed());
+    }
+}
@@ -1,28 +1,55 @@
 use crate::utils::error::Result;
+use crate::utils::warnings::{self, WarningKind};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
 
 use super::parser::parse_owner;
-use super::types::{InlineCodeownersEntry, Owner, Tag};
+use super::types::{InlineCodeownersEntry, InlineSource, Owner, Tag};
+
+/// Extensions of the files whose YAML front matter can declare owners
+const FRONT_MATTER_EXTENSIONS: [&str; 3] = ["md", "markdown", "mdx"];
 
 /// Detects inline CODEOWNERS declaration in the first 50 lines of a file
+///
+/// Markdown files can also declare their owners in YAML front matter, which is read
+/// before looking for a `!!!CODEOWNERS` marker, see [`reads_front_matter`].
 pub fn detect_inline_codeowners(file_path: &Path) -> Result<Option<InlineCodeownersEntry>> {
     let file = match File::open(file_path) {
         Ok(f) => f,
-        Err(_) => return Ok(None), // File doesn't exist or can't be read
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            warnings::warn(
+                WarningKind::UnreadableFile,
+                format!("Failed to read {}: {}", file_path.display(), e),
+            );
+            return Ok(None);
+        }
     };
 
-    let reader = BufReader::new(file);
-    let lines = reader.lines().take(50);
+    detect_inline_codeowners_in(BufReader::new(file), file_path)
+}
 
-    for (line_num, line_result) in lines.enumerate() {
-        let line = match line_result {
-            Ok(l) => l,
-            Err(_) => continue, // Skip lines that can't be read
-        };
+/// Detects inline CODEOWNERS declaration in the first 50 lines read from `reader`, the
+/// content of `file_path`
+pub fn detect_inline_codeowners_in(
+    reader: impl BufRead, file_path: &Path,
+) -> Result<Option<InlineCodeownersEntry>> {
+    // Lines that can't be read are left empty
+    let lines: Vec<String> = reader
+        .lines()
+        .take(50)
+        .map(|line| line.unwrap_or_default())
+        .collect();
+
+    if reads_front_matter(file_path) {
+        if let Some(entry) = parse_front_matter(&lines, file_path)? {
+            return Ok(Some(entry));
+        }
+    }
 
-        if let Some(entry) = parse_inline_codeowners_line(&line, line_num + 1, file_path)? {
+    for (line_num, line) in lines.iter().enumerate() {
+        if let Some(entry) = parse_inline_codeowners_line(line, line_num + 1, file_path)? {
             return Ok(Some(entry));
         }
     }
@@ -30,6 +57,133 @@ pub fn detect_inline_codeowners(file_path: &Path) -> Result<Option<InlineCodeown
     Ok(None)
 }
 
+/// Whether the front matter of `file_path` is read for owners: Markdown files only, as
+/// other files starting with `---`, such as YAML documents, are not front matter
+pub(crate) fn reads_front_matter(file_path: &Path) -> bool {
+    file_path.extension().is_some_and(|extension| {
+        FRONT_MATTER_EXTENSIONS
+            .iter()
+            .any(|known| extension.eq_ignore_ascii_case(known))
+    })
+}
+
+/// Owners and tags declared by the YAML front matter opening `lines`
+///
+/// ```text
+/// ---
+/// title: Deploying
+/// owners: [@docs-team, ops@example.com]
+/// tags:
+///   - docs
+/// ---
+/// ```
+///
+/// Only the top-level `owners` and `tags` keys are read, each a flow list, a block list
+/// or whitespace-separated values; front matter without `owners` declares nothing.
+/// Front matter must close within the 50 lines read.
+fn parse_front_matter(lines: &[String], file_path: &Path) -> Result<Option<InlineCodeownersEntry>> {
+    if lines.first().map(|line| line.trim_end()) != Some("---") {
+        return Ok(None);
+    }
+    let Some(end) = lines
+        .iter()
+        .skip(1)
+        .position(|line| matches!(line.trim_end(), "---" | "..."))
+    else {
+        return Ok(None);
+    };
+    let body = &lines[1..end + 1];
+
+    let mut owners: Option<(usize, Vec<String>)> = None;
+    let mut tags = Vec::new();
+    for (index, line) in body.iter().enumerate() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if key.starts_with(char::is_whitespace) || !matches!(key, "owners" | "tags") {
+            continue;
+        }
+        let values = front_matter_values(value, &body[index + 1..]);
+        if key == "owners" {
+            // Line numbers count the opening `---`
+            owners = Some((index + 2, values));
+        } else {
+            tags = values;
+        }
+    }
+
+    let Some((line_number, owners)) = owners else {
+        return Ok(None);
+    };
+    if owners.is_empty() {
+        warnings::warn(
+            WarningKind::UnresolvedInlineMarker,
+            format!(
+                "Ignoring front matter naming no owners at {}:{}",
+                file_path.display(),
+                line_number
+            ),
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(InlineCodeownersEntry {
+        file_path: file_path.to_path_buf(),
+        line_number,
+        owners: owners
+            .iter()
+            .map(|owner| parse_owner(owner))
+            .collect::<Result<_>>()?,
+        tags: tags
+            .iter()
+            .map(|tag| tag.trim_start_matches('#'))
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| Tag(tag.to_string()))
+            .collect(),
+        source: InlineSource::FrontMatter,
+    }))
+}
+
+/// Values of a front matter key: the flow list or values after its colon, or else the
+/// block list of `- item` lines indented below it
+fn front_matter_values(value: &str, following: &[String]) -> Vec<String> {
+    let value = value.trim();
+    if let Some(list) = value.strip_prefix('[') {
+        let list = list.split_once(']').map_or(list, |(list, _)| list);
+        return list.split(',').filter_map(front_matter_scalar).collect();
+    }
+    if !value.is_empty() {
+        // A ` #` starts a YAML comment; a leading `#` is a tag
+        let value = value.split_once(" #").map_or(value, |(value, _)| value);
+        return value
+            .split_whitespace()
+            .filter_map(front_matter_scalar)
+            .collect();
+    }
+    following
+        .iter()
+        .take_while(|line| {
+            line.trim().is_empty() || line.starts_with(char::is_whitespace) || line.starts_with('-')
+        })
+        .filter_map(|line| line.trim().strip_prefix('-'))
+        .filter_map(front_matter_scalar)
+        .collect()
+}
+
+/// A scalar without its surrounding whitespace and quotes, if it is not empty
+fn front_matter_scalar(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value);
+    (!value.is_empty()).then(|| value.to_string())
+}
+
 /// Parse a single line for inline CODEOWNERS declaration
 fn parse_inline_codeowners_line(
     line: &str, line_number: usize, file_path: &Path,
@@ -42,10 +196,6 @@ fn parse_inline_codeowners_line(
         // Split by whitespace to get tokens
         let tokens: Vec<&str> = after_marker.split_whitespace().collect();
 
-        if tokens.is_empty() {
-            return Ok(None);
-        }
-
         let mut owners: Vec<Owner> = Vec::new();
         let mut tags: Vec<Tag> = Vec::new();
         let mut i = 0;
@@ -59,13 +209,12 @@ fn parse_inline_codeowners_line(
         // Collect tags
         while i < tokens.len() {
             let token = tokens[i];
-            if token.starts_with('#') {
+            if let Some(tag_part) = token.strip_prefix('#') {
                 if token == "#" {
                     // Standalone # means comment starts, break
                     break;
                 } else {
-                    // Extract tag name, but check if this might be a comment
-                    let tag_part = &token[1..];
+                    // Tag name extracted above, but check if this might be a comment
 
                     // If the tag part is empty, it's probably a comment marker
                     if tag_part.is_empty() {
@@ -127,12 +276,22 @@ fn parse_inline_codeowners_line(
         }
 
         // Only return an entry if we have at least one owner
-        if !owners.is_empty() {
+        if owners.is_empty() {
+            warnings::warn(
+                WarningKind::UnresolvedInlineMarker,
+                format!(
+                    "Ignoring the !!!CODEOWNERS marker without owners at {}:{}",
+                    file_path.display(),
+                    line_number
+                ),
+            );
+        } else {
             return Ok(Some(InlineCodeownersEntry {
                 file_path: file_path.to_path_buf(),
                 line_number,
                 owners,
                 tags,
+                source: InlineSource::Marker,
             }));
         }
     }
@@ -140,6 +299,85 @@ fn parse_inline_codeowners_line(
     Ok(None)
 }
 
+/// Rename tag `old` to `new` in the inline declaration of a file's `content`
+///
+/// Only the tags of the declaration that applies, the first within 50 lines, are
+/// rewritten; the rest of the file is kept byte for byte. If the file already carries
+/// `new`, `old` is removed instead. Returns `None` when the declaration has no `old` tag.
+pub(crate) fn rename_inline_tag(
+    content: &str, file_path: &Path, old: &str, new: &str,
+) -> Result<Option<String>> {
+    let mut offset = 0;
+    for (line_num, line) in content.split_inclusive('\n').take(50).enumerate() {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let Some(entry) = parse_inline_codeowners_line(text, line_num + 1, file_path)? else {
+            offset += line.len();
+            continue;
+        };
+        if !entry.tags.iter().any(|tag| tag.0 == old) {
+            return Ok(None);
+        }
+        let has_new = entry.tags.iter().any(|tag| tag.0 == new);
+
+        // Tags are the tokens right after the owners
+        let marker_end = text.find("!!!CODEOWNERS").unwrap_or(0) + "!!!CODEOWNERS".len();
+        let tokens = token_spans(&text[marker_end..]);
+        let tag_tokens = tokens
+            .iter()
+            .skip(entry.owners.len())
+            .take(entry.tags.len());
+
+        let mut rewritten = String::with_capacity(text.len());
+        let mut last = 0;
+        for &(start, end) in tag_tokens {
+            let (start, end) = (marker_end + start, marker_end + end);
+            if text[start..end].strip_prefix('#') != Some(old) {
+                continue;
+            }
+            if has_new {
+                // Drop the token together with the whitespace before it
+                let trimmed = text[..start].trim_end().len().max(last);
+                rewritten.push_str(&text[last..trimmed]);
+            } else {
+                rewritten.push_str(&text[last..start]);
+                rewritten.push('#');
+                rewritten.push_str(new);
+            }
+            last = end;
+        }
+        rewritten.push_str(&line[last..]);
+
+        return Ok(Some(format!(
+            "{}{}{}",
+            &content[..offset],
+            rewritten,
+            &content[offset + line.len()..]
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Byte ranges of the whitespace-separated tokens in `text`
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -362,6 +600,95 @@ fn main() {}
         Ok(())
     }
 
+    #[test]
+    fn test_rename_inline_tag_rewrites_only_the_declaration() -> Result<()> {
+        let path = Path::new("test.rs");
+        let content =
+            "// intro #api\r\n// !!!CODEOWNERS @user1  #api\t#core # keep #api\nfn api() {}\n";
+
+        let renamed = rename_inline_tag(content, path, "api", "service")?.unwrap();
+        assert_eq!(
+            renamed,
+            "// intro #api\r\n// !!!CODEOWNERS @user1  #service\t#core # keep #api\nfn api() {}\n"
+        );
+
+        // Renaming onto a tag the file already has removes the old one
+        let merged = rename_inline_tag(content, path, "api", "core")?.unwrap();
+        assert_eq!(
+            merged,
+            "// intro #api\r\n// !!!CODEOWNERS @user1\t#core # keep #api\nfn api() {}\n"
+        );
+
+        assert!(rename_inline_tag(content, path, "keep", "x")?.is_none());
+        assert!(rename_inline_tag("fn main() {}\n", path, "api", "x")?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_front_matter_owners() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("guide.md");
+
+        let content = r#"---
+title: "Deploying: a guide"
+owners: [@docs-team, "ops@example.com"] # reviewed quarterly
+tags:
+  - docs
+  - '#ops'
+---
+# Deploying
+
+<!-- !!!CODEOWNERS @ignored -->
+"#;
+        fs::write(&file_path, content).unwrap();
+
+        let entry = detect_inline_codeowners(&file_path)?.unwrap();
+        assert_eq!(entry.source, InlineSource::FrontMatter);
+        assert_eq!(entry.line_number, 3);
+        let owners: Vec<_> = entry.owners.iter().map(|o| o.identifier.as_str()).collect();
+        assert_eq!(owners, vec!["@docs-team", "ops@example.com"]);
+        let tags: Vec<_> = entry.tags.iter().map(|t| t.0.as_str()).collect();
+        assert_eq!(tags, vec!["docs", "ops"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_front_matter_only_in_markdown_with_owners() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+
+        // Front matter naming no owners leaves the marker to declare them
+        let post = temp_dir.path().join("post.md");
+        fs::write(
+            &post,
+            "---\ntags: [release]\n---\n<!-- !!!CODEOWNERS @blog #news -->\n",
+        )
+        .unwrap();
+        let entry = detect_inline_codeowners(&post)?.unwrap();
+        assert_eq!(entry.source, InlineSource::Marker);
+        assert_eq!(entry.line_number, 4);
+        assert_eq!(entry.tags[0].0, "news");
+
+        // A YAML document is not front matter
+        let config = temp_dir.path().join("config.yml");
+        fs::write(&config, "---\nowners:\n- \"@ops\"\n---\n").unwrap();
+        assert!(detect_inline_codeowners(&config)?.is_none());
+        let notes = temp_dir.path().join("notes.MD");
+        fs::write(&notes, "---\nowners:\n- \"@ops\"\n---\n").unwrap();
+        assert_eq!(
+            detect_inline_codeowners(&notes)?.unwrap().owners[0].identifier,
+            "@ops"
+        );
+
+        // Unclosed front matter declares nothing
+        let draft = temp_dir.path().join("draft.md");
+        fs::write(&draft, "---\nowners: @docs\n").unwrap();
+        assert!(detect_inline_codeowners(&draft)?.is_none());
+
+        Ok(())
+    }
+
     #[test]
     fn test_detect_inline_codeowners_nonexistent_file() -> Result<()> {
         let temp_dir = TempDir::new().unwrap();
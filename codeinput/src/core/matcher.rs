@@ -0,0 +1,799 @@
+//! Rule matching strategies.
+//!
+//! Matching files against CODEOWNERS rules can be done in several ways, each with a
+//! different build/match trade-off. [`Matcher`] wraps the available strategies behind a
+//! single interface, and [`MatcherStrategy::Auto`] picks one from a cost model
+//! ([`MatcherProfile`]) that `codeowners calibrate` measures on the current machine.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Instant;
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::bytes::RegexSet;
+use serde::{Deserialize, Serialize};
+
+use super::types::{
+    normalize_codeowners_pattern, try_codeowners_entry_to_matcher_with, CodeownersEntry,
+    CodeownersEntryMatcher, MatchOptions,
+};
+use crate::utils::error::{Error, Result};
+use crate::utils::warnings::{self, WarningKind};
+
+/// Strategy used to match files against CODEOWNERS rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MatcherStrategy {
+    /// Pick a strategy from the rule/file counts and the configured [`MatcherProfile`]
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// One `Override` matcher per rule; cheap to build, matching is linear in rules
+    #[serde(rename = "override")]
+    Override,
+    /// One combined gitignore matcher per CODEOWNERS file
+    #[serde(rename = "gitignore")]
+    Gitignore,
+    /// One compiled regex set per CODEOWNERS file; expensive to build, fastest to match
+    #[serde(rename = "regex-set")]
+    RegexSet,
+    /// One glob set per CODEOWNERS file, matching a file against every rule in one pass
+    #[serde(rename = "globset")]
+    GlobSet,
+}
+
+impl MatcherStrategy {
+    /// Concrete strategies, in the order they are calibrated and reported
+    pub const CONCRETE: [MatcherStrategy; 4] = [
+        MatcherStrategy::Override,
+        MatcherStrategy::Gitignore,
+        MatcherStrategy::RegexSet,
+        MatcherStrategy::GlobSet,
+    ];
+
+    /// Resolve `Auto` to the concrete strategy with the lowest estimated cost
+    pub fn select(self, rules: usize, files: usize, profile: &MatcherProfile) -> MatcherStrategy {
+        if self != MatcherStrategy::Auto {
+            return self;
+        }
+
+        Self::CONCRETE
+            .iter()
+            .copied()
+            .min_by_key(|strategy| profile.estimate(*strategy, rules, files))
+            .unwrap_or(MatcherStrategy::Override)
+    }
+}
+
+impl std::fmt::Display for MatcherStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatcherStrategy::Auto => write!(f, "auto"),
+            MatcherStrategy::Override => write!(f, "override"),
+            MatcherStrategy::Gitignore => write!(f, "gitignore"),
+            MatcherStrategy::RegexSet => write!(f, "regex-set"),
+            MatcherStrategy::GlobSet => write!(f, "globset"),
+        }
+    }
+}
+
+impl FromStr for MatcherStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(MatcherStrategy::Auto),
+            "override" => Ok(MatcherStrategy::Override),
+            "gitignore" => Ok(MatcherStrategy::Gitignore),
+            "regex-set" | "regexset" | "regex" => Ok(MatcherStrategy::RegexSet),
+            "globset" | "glob-set" => Ok(MatcherStrategy::GlobSet),
+            _ => Err(Error::new(&format!(
+                "Invalid matcher: {}. Valid options: auto, override, gitignore, regex-set, globset",
+                s
+            ))),
+        }
+    }
+}
+
+/// Measured matcher costs, in nanoseconds, used to resolve [`MatcherStrategy::Auto`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatcherProfile {
+    /// Cost of compiling one rule into its own override matcher
+    pub override_build_ns: u64,
+    /// Cost of testing one file against one rule's override matcher
+    pub override_match_ns: u64,
+    /// Cost of adding one rule to a combined gitignore matcher
+    pub gitignore_build_ns: u64,
+    /// Cost of testing one file against the combined gitignore matchers
+    pub gitignore_match_ns: u64,
+    /// Cost of compiling one rule into a regex set
+    pub regex_set_build_ns: u64,
+    /// Cost of testing one file against the compiled regex sets
+    pub regex_set_match_ns: u64,
+    /// Cost of compiling one rule into a glob set
+    pub globset_build_ns: u64,
+    /// Cost of testing one file against the compiled glob sets
+    pub globset_match_ns: u64,
+}
+
+impl Default for MatcherProfile {
+    fn default() -> Self {
+        MatcherProfile {
+            override_build_ns: 10_000,
+            override_match_ns: 150,
+            gitignore_build_ns: 12_000,
+            gitignore_match_ns: 1_500,
+            regex_set_build_ns: 40_000,
+            regex_set_match_ns: 600,
+            globset_build_ns: 12_000,
+            globset_match_ns: 300,
+        }
+    }
+}
+
+impl MatcherProfile {
+    /// Estimated total cost of building a matcher for `rules` rules and matching `files` files
+    pub fn estimate(&self, strategy: MatcherStrategy, rules: usize, files: usize) -> u128 {
+        let rules = rules as u128;
+        let files = files as u128;
+
+        match strategy {
+            MatcherStrategy::Override => {
+                self.override_build_ns as u128 * rules
+                    + self.override_match_ns as u128 * rules * files
+            }
+            MatcherStrategy::Gitignore => {
+                self.gitignore_build_ns as u128 * rules + self.gitignore_match_ns as u128 * files
+            }
+            MatcherStrategy::RegexSet => {
+                self.regex_set_build_ns as u128 * rules + self.regex_set_match_ns as u128 * files
+            }
+            MatcherStrategy::GlobSet => {
+                self.globset_build_ns as u128 * rules + self.globset_match_ns as u128 * files
+            }
+            MatcherStrategy::Auto => MatcherStrategy::CONCRETE
+                .iter()
+                .map(|s| self.estimate(*s, rules as usize, files as usize))
+                .min()
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Rules from one CODEOWNERS file compiled into a single gitignore matcher
+#[derive(Debug)]
+pub struct GitignoreRoot {
+    root: PathBuf,
+    gitignore: Gitignore,
+    /// Normalized pattern to the index of the last entry using it
+    entries: HashMap<String, usize>,
+}
+
+/// Rules from one CODEOWNERS file compiled into a single regex set
+#[derive(Debug)]
+pub struct RegexSetRoot {
+    root: PathBuf,
+    set: RegexSet,
+    /// Regex index to entry index
+    entries: Vec<usize>,
+}
+
+/// Rules from one CODEOWNERS file compiled into a single glob set
+#[derive(Debug)]
+pub struct GlobSetRoot {
+    root: PathBuf,
+    set: GlobSet,
+    /// Glob index to entry index
+    entries: Vec<usize>,
+}
+
+/// Compiled CODEOWNERS rules, ready to match files
+#[derive(Debug)]
+pub enum Matcher {
+    /// Indexed like the entries it was built from; `None` for invalid patterns
+    Override(Vec<Option<CodeownersEntryMatcher>>),
+    Gitignore(Vec<GitignoreRoot>),
+    RegexSet(Vec<RegexSetRoot>),
+    GlobSet(Vec<GlobSetRoot>),
+}
+
+impl Matcher {
+    /// Compile `entries` with the given strategy
+    ///
+    /// `Auto` should be resolved with [`MatcherStrategy::select`] first; if it is not,
+    /// per-entry overrides are used. Invalid patterns are skipped with a warning and
+    /// never match.
+    pub fn build(entries: &[CodeownersEntry], strategy: MatcherStrategy) -> Matcher {
+        Matcher::build_with_options(entries, strategy, MatchOptions::default())
+    }
+
+    /// Compile `entries` with the given strategy and match options
+    pub fn build_with_options(
+        entries: &[CodeownersEntry], strategy: MatcherStrategy, options: MatchOptions,
+    ) -> Matcher {
+        match strategy {
+            MatcherStrategy::Auto | MatcherStrategy::Override => Matcher::Override(
+                entries
+                    .iter()
+                    .map(
+                        |entry| match try_codeowners_entry_to_matcher_with(entry, options) {
+                            Ok(matcher) => Some(matcher),
+                            Err(e) => {
+                                warnings::warn(WarningKind::SkippedPattern, e.to_string());
+                                None
+                            }
+                        },
+                    )
+                    .collect(),
+            ),
+            MatcherStrategy::Gitignore => {
+                Matcher::Gitignore(build_gitignore_roots(entries, options))
+            }
+            MatcherStrategy::RegexSet => Matcher::RegexSet(build_regex_set_roots(entries, options)),
+            MatcherStrategy::GlobSet => Matcher::GlobSet(build_globset_roots(entries, options)),
+        }
+    }
+
+    /// The strategy this matcher was built with
+    pub fn strategy(&self) -> MatcherStrategy {
+        match self {
+            Matcher::Override(_) => MatcherStrategy::Override,
+            Matcher::Gitignore(_) => MatcherStrategy::Gitignore,
+            Matcher::RegexSet(_) => MatcherStrategy::RegexSet,
+            Matcher::GlobSet(_) => MatcherStrategy::GlobSet,
+        }
+    }
+
+    /// Indices of the entries whose pattern matches `file_path`
+    ///
+    /// Negations are included when the pattern they negate matches. The result is
+    /// unordered and is not filtered by CODEOWNERS location; the resolver handles
+    /// precedence and negations. The gitignore strategy only reports the last matching rule of
+    /// each CODEOWNERS file, since earlier matches in the same file can never win.
+    pub fn candidates(&self, file_path: &Path) -> Vec<usize> {
+        match self {
+            Matcher::Override(matchers) => matchers
+                .iter()
+                .enumerate()
+                .filter_map(|(index, matcher)| {
+                    let matcher = matcher.as_ref()?;
+                    matcher
+                        .override_matcher
+                        .matched(file_path, false)
+                        .is_whitelist()
+                        .then_some(index)
+                })
+                .collect(),
+            Matcher::Gitignore(roots) => roots
+                .iter()
+                .filter_map(|root| {
+                    let relative = relative_to_root(&root.root, file_path)?;
+                    match root.gitignore.matched(relative, false) {
+                        ignore::Match::Ignore(glob) | ignore::Match::Whitelist(glob) => {
+                            root.entries.get(glob.original()).copied()
+                        }
+                        ignore::Match::None => None,
+                    }
+                })
+                .collect(),
+            Matcher::RegexSet(roots) => roots
+                .iter()
+                .flat_map(|root| {
+                    let Some(relative) = relative_to_root(&root.root, file_path) else {
+                        return Vec::new();
+                    };
+                    root.set
+                        .matches(candidate_bytes(relative).as_bytes())
+                        .into_iter()
+                        .map(|index| root.entries[index])
+                        .collect()
+                })
+                .collect(),
+            Matcher::GlobSet(roots) => roots
+                .iter()
+                .flat_map(|root| {
+                    let Some(relative) = relative_to_root(&root.root, file_path) else {
+                        return Vec::new();
+                    };
+                    root.set
+                        .matches(candidate_bytes(relative))
+                        .into_iter()
+                        .map(|index| root.entries[index])
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Group entry indices by the directory of the CODEOWNERS file they come from
+fn group_by_root(entries: &[CodeownersEntry]) -> Vec<(PathBuf, Vec<usize>)> {
+    let mut roots: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let Some(root) = entry.base_dir() else {
+            warnings::warn(
+                WarningKind::SkippedPattern,
+                format!(
+                    "CODEOWNERS entry has no parent directory: {}",
+                    entry.source_file.display()
+                ),
+            );
+            continue;
+        };
+        match roots.iter_mut().find(|(r, _)| r == root) {
+            Some((_, indices)) => indices.push(index),
+            None => roots.push((root.to_path_buf(), vec![index])),
+        }
+    }
+    roots
+}
+
+fn build_gitignore_roots(entries: &[CodeownersEntry], options: MatchOptions) -> Vec<GitignoreRoot> {
+    group_by_root(entries)
+        .into_iter()
+        .filter_map(|(root, indices)| {
+            let mut builder = GitignoreBuilder::new(&root);
+            // Must be set before lines are added, which compiles them
+            if let Err(e) = builder.case_insensitive(options.case_insensitive) {
+                warnings::warn(
+                    WarningKind::SkippedPattern,
+                    format!("Failed to configure matcher for {}: {}", root.display(), e),
+                );
+                return None;
+            }
+            let mut patterns = HashMap::new();
+            for index in indices {
+                let entry = &entries[index];
+                // A negation that matches last is reported as a whitelist match
+                let pattern = normalize_codeowners_pattern(&entry.pattern);
+                if let Err(e) = builder.add_line(None, &pattern) {
+                    warnings::warn(
+                        WarningKind::SkippedPattern,
+                        format!(
+                            "Invalid pattern '{}' in {}: {}",
+                            entry.pattern,
+                            entry.source_file.display(),
+                            e
+                        ),
+                    );
+                    continue;
+                }
+                patterns.insert(pattern, index);
+            }
+
+            match builder.build() {
+                Ok(gitignore) => Some(GitignoreRoot {
+                    root,
+                    gitignore,
+                    entries: patterns,
+                }),
+                Err(e) => {
+                    warnings::warn(
+                        WarningKind::SkippedPattern,
+                        format!("Failed to build matcher for {}: {}", root.display(), e),
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn build_regex_set_roots(entries: &[CodeownersEntry], options: MatchOptions) -> Vec<RegexSetRoot> {
+    group_by_root(entries)
+        .into_iter()
+        .filter_map(|(root, indices)| {
+            let (globs, mapped) = compile_globs(entries, indices, options);
+            let regexes: Vec<&str> = globs.iter().map(Glob::regex).collect();
+
+            match RegexSet::new(&regexes) {
+                Ok(set) => Some(RegexSetRoot {
+                    root,
+                    set,
+                    entries: mapped,
+                }),
+                Err(e) => {
+                    warnings::warn(
+                        WarningKind::SkippedPattern,
+                        format!("Failed to build matcher for {}: {}", root.display(), e),
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn build_globset_roots(entries: &[CodeownersEntry], options: MatchOptions) -> Vec<GlobSetRoot> {
+    group_by_root(entries)
+        .into_iter()
+        .filter_map(|(root, indices)| {
+            let (globs, mapped) = compile_globs(entries, indices, options);
+            let mut builder = GlobSetBuilder::new();
+            for glob in globs {
+                builder.add(glob);
+            }
+
+            match builder.build() {
+                Ok(set) => Some(GlobSetRoot {
+                    root,
+                    set,
+                    entries: mapped,
+                }),
+                Err(e) => {
+                    warnings::warn(
+                        WarningKind::SkippedPattern,
+                        format!("Failed to build matcher for {}: {}", root.display(), e),
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Compile the patterns of the entries at `indices` into globs, paired with the entry
+/// index of each glob
+///
+/// Negations and directory-only patterns never match a file and are left out; invalid
+/// patterns are skipped with a warning.
+fn compile_globs(
+    entries: &[CodeownersEntry], indices: Vec<usize>, options: MatchOptions,
+) -> (Vec<Glob>, Vec<usize>) {
+    let mut globs = Vec::new();
+    let mut mapped = Vec::new();
+    for index in indices {
+        let entry = &entries[index];
+        let pattern = normalize_codeowners_pattern(entry.positive_pattern());
+        let Some(glob) = gitignore_glob(&pattern) else {
+            continue;
+        };
+        match GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .backslash_escape(true)
+            .case_insensitive(options.case_insensitive)
+            .build()
+        {
+            Ok(glob) => {
+                globs.push(glob);
+                mapped.push(index);
+            }
+            Err(e) => warnings::warn(
+                WarningKind::SkippedPattern,
+                format!(
+                    "Invalid pattern '{}' in {}: {}",
+                    entry.pattern,
+                    entry.source_file.display(),
+                    e
+                ),
+            ),
+        }
+    }
+    (globs, mapped)
+}
+
+/// Translate a normalized pattern into the glob `ignore` compiles for a gitignore line
+///
+/// Returns `None` for patterns that can never match a file: negations and
+/// directory-only patterns.
+fn gitignore_glob(pattern: &str) -> Option<String> {
+    let mut line = pattern;
+    let mut is_absolute = false;
+
+    if line.starts_with("\\!") || line.starts_with("\\#") {
+        line = &line[1..];
+        is_absolute = line.starts_with('/');
+    } else {
+        if line.starts_with('!') {
+            return None;
+        }
+        if let Some(rest) = line.strip_prefix('/') {
+            line = rest;
+            is_absolute = true;
+        }
+    }
+
+    if line.ends_with('/') {
+        return None;
+    }
+
+    let mut glob = line.to_string();
+    if !(is_absolute || line.contains('/') || glob.starts_with("**/") || glob == "**") {
+        glob = format!("**/{}", glob);
+    }
+    if glob.ends_with("/**") {
+        glob = format!("{}/*", glob);
+    }
+
+    Some(glob)
+}
+
+/// Path of `file_path` relative to a CODEOWNERS root, or `None` if it lies outside it
+fn relative_to_root<'a>(root: &Path, file_path: &'a Path) -> Option<&'a Path> {
+    let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+    if root == Path::new(".") || root.as_os_str().is_empty() {
+        return Some(file_path);
+    }
+    let root = root.strip_prefix("./").unwrap_or(root);
+    file_path.strip_prefix(root).ok()
+}
+
+/// Render a relative path the way globs expect it, with `/` separators
+fn candidate_bytes(path: &Path) -> String {
+    let candidate = path.to_string_lossy();
+    #[cfg(windows)]
+    let candidate = std::borrow::Cow::<str>::Owned(candidate.replace('\\', "/"));
+    candidate.into_owned()
+}
+
+/// Measure each concrete strategy against `entries` and a sample of `files`
+pub fn calibrate(entries: &[CodeownersEntry], files: &[PathBuf]) -> Result<MatcherProfile> {
+    if entries.is_empty() {
+        return Err(Error::new(
+            "No CODEOWNERS rules found; calibration needs at least one rule",
+        ));
+    }
+    if files.is_empty() {
+        return Err(Error::new(
+            "No files found; calibration needs at least one file",
+        ));
+    }
+
+    let rules = entries.len() as u128;
+    let sample = files.len() as u128;
+    let mut profile = MatcherProfile::default();
+
+    for strategy in MatcherStrategy::CONCRETE {
+        let started = Instant::now();
+        let matcher = Matcher::build(entries, strategy);
+        let build_ns = (started.elapsed().as_nanos() / rules).max(1) as u64;
+
+        let started = Instant::now();
+        for file in files {
+            std::hint::black_box(matcher.candidates(file));
+        }
+        let match_total = started.elapsed().as_nanos();
+
+        match strategy {
+            MatcherStrategy::Override => {
+                profile.override_build_ns = build_ns;
+                profile.override_match_ns = (match_total / (sample * rules)).max(1) as u64;
+            }
+            MatcherStrategy::Gitignore => {
+                profile.gitignore_build_ns = build_ns;
+                profile.gitignore_match_ns = (match_total / sample).max(1) as u64;
+            }
+            MatcherStrategy::RegexSet => {
+                profile.regex_set_build_ns = build_ns;
+                profile.regex_set_match_ns = (match_total / sample).max(1) as u64;
+            }
+            MatcherStrategy::GlobSet => {
+                profile.globset_build_ns = build_ns;
+                profile.globset_match_ns = (match_total / sample).max(1) as u64;
+            }
+            MatcherStrategy::Auto => {}
+        }
+    }
+
+    Ok(profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{EntrySpans, Owner, OwnerType};
+
+    fn entry(source_file: &str, line_number: usize, pattern: &str) -> CodeownersEntry {
+        CodeownersEntry {
+            source_file: PathBuf::from(source_file),
+            line_number,
+            pattern: pattern.to_string(),
+            owners: vec![Owner {
+                identifier: format!("@owner{}", line_number),
+                owner_type: OwnerType::User,
+            }],
+            tags: vec![],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        }
+    }
+
+    fn sorted_candidates(matcher: &Matcher, path: &str) -> Vec<usize> {
+        let mut candidates = matcher.candidates(Path::new(path));
+        candidates.sort_unstable();
+        candidates
+    }
+
+    #[test]
+    fn test_strategy_round_trip() {
+        for strategy in [
+            MatcherStrategy::Auto,
+            MatcherStrategy::Override,
+            MatcherStrategy::Gitignore,
+            MatcherStrategy::RegexSet,
+            MatcherStrategy::GlobSet,
+        ] {
+            assert_eq!(
+                strategy.to_string().parse::<MatcherStrategy>().unwrap(),
+                strategy
+            );
+        }
+        assert!("fastest".parse::<MatcherStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_select_keeps_concrete_strategy() {
+        let profile = MatcherProfile::default();
+        assert_eq!(
+            MatcherStrategy::Gitignore.select(10_000, 10_000, &profile),
+            MatcherStrategy::Gitignore
+        );
+    }
+
+    #[test]
+    fn test_select_auto_follows_profile() {
+        let profile = MatcherProfile::default();
+
+        // A handful of rules over a handful of files: building is what matters
+        assert_eq!(
+            MatcherStrategy::Auto.select(2, 10, &profile),
+            MatcherStrategy::Override
+        );
+        // Thousands of rules over many files: per-rule matching dominates
+        assert_eq!(
+            MatcherStrategy::Auto.select(2_000, 100_000, &profile),
+            MatcherStrategy::GlobSet
+        );
+
+        // A profile where regex sets are cheap to build always prefers them
+        let profile = MatcherProfile {
+            regex_set_build_ns: 1,
+            regex_set_match_ns: 1,
+            ..MatcherProfile::default()
+        };
+        assert_eq!(
+            MatcherStrategy::Auto.select(2, 10, &profile),
+            MatcherStrategy::RegexSet
+        );
+    }
+
+    #[test]
+    fn test_gitignore_glob_translation() {
+        assert_eq!(gitignore_glob("*.rs").as_deref(), Some("**/*.rs"));
+        assert_eq!(gitignore_glob("/src/*.rs").as_deref(), Some("src/*.rs"));
+        assert_eq!(gitignore_glob("docs/**").as_deref(), Some("docs/**/*"));
+        assert_eq!(gitignore_glob("**/build").as_deref(), Some("**/build"));
+        assert_eq!(gitignore_glob("src/*/"), None);
+        assert_eq!(gitignore_glob("!generated/**"), None);
+    }
+
+    #[test]
+    fn test_strategies_agree() {
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "*"),
+            entry("/project/CODEOWNERS", 1, "*.rs"),
+            entry("/project/CODEOWNERS", 2, "/docs/"),
+            entry("/project/CODEOWNERS", 3, "src/api/**"),
+            entry("/project/CODEOWNERS", 4, "*.rs"),
+            entry("/project/src/CODEOWNERS", 0, "*.js"),
+            entry("/project/src/CODEOWNERS", 1, "/lib/"),
+        ];
+        let paths = [
+            "/project/README.md",
+            "/project/src/main.rs",
+            "/project/docs/guide/intro.md",
+            "/project/src/api/v1/handler.rs",
+            "/project/src/app.js",
+            "/project/src/lib/util.js",
+            "/project/lib/util.js",
+            "/elsewhere/main.rs",
+        ];
+
+        let overrides = Matcher::build(&entries, MatcherStrategy::Override);
+        let gitignore = Matcher::build(&entries, MatcherStrategy::Gitignore);
+        let regex_set = Matcher::build(&entries, MatcherStrategy::RegexSet);
+        let globset = Matcher::build(&entries, MatcherStrategy::GlobSet);
+
+        for path in paths {
+            // Overrides match regardless of location; the other strategies skip foreign roots
+            let expected: Vec<usize> = sorted_candidates(&overrides, path)
+                .into_iter()
+                .filter(|i| Path::new(path).starts_with(entries[*i].base_dir().unwrap()))
+                .collect();
+            assert_eq!(
+                sorted_candidates(&regex_set, path),
+                expected,
+                "regex-set disagrees on {}",
+                path
+            );
+            assert_eq!(
+                sorted_candidates(&globset, path),
+                expected,
+                "globset disagrees on {}",
+                path
+            );
+
+            // The gitignore strategy keeps only the last match of each CODEOWNERS file
+            let mut last_per_root: Vec<usize> = Vec::new();
+            for index in expected {
+                let root = entries[index].base_dir();
+                last_per_root.retain(|i| entries[*i].base_dir() != root);
+                last_per_root.push(index);
+            }
+            last_per_root.sort_unstable();
+            assert_eq!(
+                sorted_candidates(&gitignore, path),
+                last_per_root,
+                "gitignore disagrees on {}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_pattern_never_matches() {
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "src/{unclosed"),
+            entry("/project/CODEOWNERS", 1, "*.rs"),
+        ];
+
+        for strategy in MatcherStrategy::CONCRETE {
+            let matcher = Matcher::build(&entries, strategy);
+            assert_eq!(
+                sorted_candidates(&matcher, "/project/src/{unclosed"),
+                Vec::<usize>::new()
+            );
+            assert_eq!(sorted_candidates(&matcher, "/project/src/main.rs"), vec![1]);
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_option() {
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "/Docs/"),
+            entry("/project/CODEOWNERS", 1, "*.RS"),
+        ];
+        let insensitive = MatchOptions {
+            case_insensitive: true,
+            ..MatchOptions::default()
+        };
+
+        for strategy in MatcherStrategy::CONCRETE {
+            let matcher = Matcher::build(&entries, strategy);
+            assert_eq!(
+                sorted_candidates(&matcher, "/project/docs/guide.md"),
+                Vec::<usize>::new(),
+                "{} should be case-sensitive by default",
+                strategy
+            );
+            assert_eq!(
+                sorted_candidates(&matcher, "/project/Docs/guide.md"),
+                vec![0]
+            );
+
+            let matcher = Matcher::build_with_options(&entries, strategy, insensitive);
+            assert_eq!(
+                sorted_candidates(&matcher, "/project/docs/guide.md"),
+                vec![0],
+                "{} ignored case_insensitive",
+                strategy
+            );
+            assert_eq!(sorted_candidates(&matcher, "/project/src/main.rs"), vec![1]);
+        }
+    }
+
+    #[test]
+    fn test_calibrate_requires_rules_and_files() {
+        let files = vec![PathBuf::from("/project/src/main.rs")];
+        assert!(calibrate(&[], &files).is_err());
+        assert!(calibrate(&[entry("/project/CODEOWNERS", 0, "*")], &[]).is_err());
+
+        let profile = calibrate(&[entry("/project/CODEOWNERS", 0, "*")], &files).unwrap();
+        assert!(profile.override_build_ns > 0);
+        assert!(profile.regex_set_match_ns > 0);
+        assert!(profile.globset_match_ns > 0);
+    }
+}
@@ -0,0 +1,217 @@
+//! What-if analysis of team membership changes, run by `codeowners simulate-membership`
+//!
+//! A change file lists members joining and leaving teams, in the layout of the team
+//! mapping:
+//!
+//! ```toml
+//! [add]
+//! "@org/backend" = ["@dave"]
+//!
+//! [remove]
+//! "@org/docs" = ["@alice"]
+//! ```
+//!
+//! Applied to the repository's [`TeamMap`], it shows which files each affected member
+//! gains or loses review responsibility over. A member is responsible for a file owned by
+//! them directly or by any team listing them.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::team_map::TeamMap;
+use super::types::{CodeownersCache, FileEntry, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Members joining and leaving teams, keyed by team handle
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MembershipChanges {
+    pub add: BTreeMap<String, Vec<String>>,
+    pub remove: BTreeMap<String, Vec<String>>,
+}
+
+/// Review responsibility one member gains and loses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemberImpact {
+    pub member: String,
+    pub joins: Vec<String>,
+    pub leaves: Vec<String>,
+    pub gained: Vec<PathBuf>,
+    pub lost: Vec<PathBuf>,
+}
+
+impl MembershipChanges {
+    /// Parse a change file; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<MembershipChanges> {
+        toml::from_str(contents).map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse membership changes {}: {}",
+                source.display(),
+                e
+            ))
+        })
+    }
+
+    /// Load a change file
+    pub fn load(path: &Path) -> Result<MembershipChanges> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read membership changes {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        MembershipChanges::parse(&contents, path)
+    }
+
+    /// `teams` after the changes
+    ///
+    /// Fails when a member to add is already in the team or a member to remove is not,
+    /// since the change file was then written against a different membership.
+    pub fn apply(&self, teams: &TeamMap) -> Result<TeamMap> {
+        let mut changed = teams.clone();
+        for (team, members) in &self.remove {
+            for member in members {
+                if !changed.remove_member(team, member) {
+                    return Err(Error::new(&format!(
+                        "Cannot remove {} from {}: not a member",
+                        member, team
+                    )));
+                }
+            }
+        }
+        for (team, members) in &self.add {
+            for member in members {
+                if !changed.add_member(team, member) {
+                    return Err(Error::new(&format!(
+                        "Cannot add {} to {}: already a member",
+                        member, team
+                    )));
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Members named in the changes, with the teams they join and leave
+    fn members(&self) -> BTreeMap<String, (Vec<String>, Vec<String>)> {
+        let mut members: BTreeMap<String, (Vec<String>, Vec<String>)> = BTreeMap::new();
+        for (team, added) in &self.add {
+            for member in added {
+                members
+                    .entry(member.clone())
+                    .or_default()
+                    .0
+                    .push(team.clone());
+            }
+        }
+        for (team, removed) in &self.remove {
+            for member in removed {
+                members
+                    .entry(member.clone())
+                    .or_default()
+                    .1
+                    .push(team.clone());
+            }
+        }
+        members
+    }
+}
+
+/// The files each member named in `changes` gains and loses when they are applied to
+/// `teams`, in member order
+pub fn simulate(
+    cache: &CodeownersCache, teams: &TeamMap, changes: &MembershipChanges,
+) -> Result<Vec<MemberImpact>> {
+    let after = changes.apply(teams)?;
+    Ok(changes
+        .members()
+        .into_iter()
+        .map(|(member, (joins, leaves))| {
+            let before = responsibilities(cache, teams, &member);
+            let now = responsibilities(cache, &after, &member);
+            MemberImpact {
+                gained: now.difference(&before).cloned().collect(),
+                lost: before.difference(&now).cloned().collect(),
+                member,
+                joins,
+                leaves,
+            }
+        })
+        .collect())
+}
+
+/// Files `member` reviews, directly or through a team
+fn responsibilities(cache: &CodeownersCache, teams: &TeamMap, member: &str) -> BTreeSet<PathBuf> {
+    cache
+        .files
+        .iter()
+        .filter(|file| is_responsible(file, teams, member))
+        .map(|file| file.path.clone())
+        .collect()
+}
+
+fn is_responsible(file: &FileEntry, teams: &TeamMap, member: &str) -> bool {
+    file.owners.iter().any(|owner| match owner.owner_type {
+        OwnerType::Team => teams
+            .members(&owner.identifier)
+            .is_some_and(|members| members.iter().any(|m| m.eq_ignore_ascii_case(member))),
+        _ => owner.identifier.eq_ignore_ascii_case(member),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::Owner;
+
+    #[test]
+    fn test_simulate() -> Result<()> {
+        let teams = TeamMap::parse(
+            r#"
+"@org/backend" = ["@alice", "@bob"]
+"@org/docs" = ["@alice"]
+"#,
+            Path::new("teams.toml"),
+        )?;
+        let file = |path: &str, owner: &str, owner_type| FileEntry {
+            path: PathBuf::from(path),
+            owners: vec![Owner {
+                identifier: owner.to_string(),
+                owner_type,
+            }],
+            tags: Vec::new(),
+            generated: false,
+            inline: None,
+        };
+        let files = vec![
+            file("api.rs", "@org/backend", OwnerType::Team),
+            file("README.md", "@org/docs", OwnerType::Team),
+            file("guide.md", "@alice", OwnerType::User),
+        ];
+        let cache = CodeownersCache::new([0; 32], Vec::new(), files, Vec::new());
+
+        let changes = MembershipChanges::parse(
+            "[add]\n\"@org/docs\" = [\"@bob\"]\n[remove]\n\"@org/docs\" = [\"@Alice\"]\n",
+            Path::new("changes.toml"),
+        )?;
+        let impacts = simulate(&cache, &teams, &changes)?;
+        assert_eq!(impacts.len(), 2);
+        assert_eq!(impacts[0].member, "@Alice");
+        assert_eq!(impacts[0].leaves, ["@org/docs"]);
+        assert_eq!(impacts[0].lost, [PathBuf::from("README.md")]);
+        assert!(impacts[0].gained.is_empty());
+        assert_eq!(impacts[1].member, "@bob");
+        assert_eq!(impacts[1].gained, [PathBuf::from("README.md")]);
+
+        let invalid = MembershipChanges::parse(
+            "[remove]\n\"@org/docs\" = [\"@bob\"]\n",
+            Path::new("changes.toml"),
+        )?;
+        assert!(simulate(&cache, &teams, &invalid).is_err());
+        assert!(MembershipChanges::parse("[rename]\n", Path::new("changes.toml")).is_err());
+        Ok(())
+    }
+}
@@ -1,15 +1,67 @@
+pub mod api_client;
+#[cfg(feature = "archive")]
+pub mod archive_cache;
+pub mod bitbucket;
+pub mod blame_cache;
+pub mod branches;
 pub(crate) mod cache;
+pub mod cache_diff;
+pub mod central;
+pub mod changed;
+pub mod checklist;
+pub mod churn;
 pub mod commands;
 pub(crate) mod common;
+pub mod completion;
+pub mod context;
+pub mod coverage;
+pub mod delegation;
+pub mod discovery;
 pub(crate) mod display;
+pub mod document;
+pub mod equivalence;
+pub mod expiry;
+pub mod export;
+pub mod gate;
+pub mod generated;
+pub mod github;
+pub mod gitlab;
+pub mod heatmap;
+pub mod identity;
+pub mod impact;
+pub mod import;
 pub(crate) mod inline_parser;
+pub mod matcher;
+pub mod membership;
+pub mod optimize;
+pub mod org;
+pub(crate) mod overlay;
+pub mod owner_alias;
 pub mod owner_resolver;
 pub(crate) mod parse;
+pub mod parse_report;
 pub mod parser;
+pub mod path_index;
+pub mod policy;
+pub mod ref_diff;
+pub mod registry;
+pub mod remote_cache;
 pub mod resolver;
+pub mod reviews;
+pub mod rule_filter;
+#[cfg(feature = "serve")]
+pub mod server;
 pub(crate) mod smart_iter;
+pub mod snapshot;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_cache;
+pub mod tag_analysis;
+pub mod tag_remap;
 pub mod tag_resolver;
+pub mod team_map;
 pub mod types;
+pub mod validate;
+pub mod watch;
 
 use crate::utils::error::Result;
 
@@ -0,0 +1,409 @@
+//! Shrinking CODEOWNERS rule sets without changing who owns what
+//!
+//! The optimizer removes rules that decide nothing (dead or fully overridden) and merges
+//! rules with the same owners and tags into one directory rule when every file they
+//! decide lies in that directory: sibling directories with identical owners, or a
+//! directory covered by several rules for one owner. Every step is checked by resolving
+//! the affected files again, so the result is equivalent for the files in the repository
+//! today; files added later may resolve differently.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use super::inline_parser::detect_inline_codeowners;
+use super::matcher::{Matcher, MatcherStrategy};
+use super::parser::escape_pattern;
+use super::resolver::resolve_file_with_policy;
+use super::types::{CodeownersEntry, MatchOptions, Owner, PrecedencePolicy, Tag};
+use crate::utils::error::Result;
+
+/// A change to one of the rules passed to [`optimize`], by index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleEdit {
+    /// Delete the rule
+    Remove(usize),
+    /// Give the rule a new pattern, keeping its owners and tags
+    Replace(usize, String),
+}
+
+/// Owners and tags a file resolves to
+type Resolution = (Vec<Owner>, Vec<Tag>);
+
+/// Rules that could merge: same CODEOWNERS file, owner identifiers and tag names
+type GroupKey<'a> = (&'a Path, Vec<&'a str>, Vec<&'a str>);
+
+struct Optimizer<'a> {
+    entries: &'a [CodeownersEntry],
+    /// Files without inline declarations, which no rule can change
+    files: Vec<PathBuf>,
+    options: MatchOptions,
+    policy: PrecedencePolicy,
+    baseline: Vec<Resolution>,
+    /// Current pattern of each rule; `None` once removed
+    patterns: Vec<Option<String>>,
+    /// Files matched by each rule's current pattern, wherever its CODEOWNERS file is
+    matches: Vec<BTreeSet<usize>>,
+}
+
+/// Edits that make `entries` smaller while every file in `files` keeps its owners and tags
+///
+/// Edits are sorted by rule index. A merged group keeps one of its rules, with the
+/// directory pattern, and removes the others.
+pub fn optimize(
+    entries: &[CodeownersEntry], files: &[PathBuf], options: MatchOptions, policy: PrecedencePolicy,
+) -> Result<Vec<RuleEdit>> {
+    let mut files_without_inline = Vec::new();
+    for file in files {
+        if detect_inline_codeowners(file)?.is_none() {
+            files_without_inline.push(file.clone());
+        }
+    }
+
+    let matcher = Matcher::build_with_options(entries, MatcherStrategy::Override, options);
+    let baseline = files_without_inline
+        .par_iter()
+        .map(|file| {
+            resolve_file_with_policy(file, entries, &matcher, policy)
+                .map(|(owners, tags, _)| (owners, tags))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut matches = vec![BTreeSet::new(); entries.len()];
+    for (index, file) in files_without_inline.iter().enumerate() {
+        for rule in matcher.candidates(file) {
+            matches[rule].insert(index);
+        }
+    }
+
+    let mut optimizer = Optimizer {
+        entries,
+        files: files_without_inline,
+        options,
+        policy,
+        baseline,
+        patterns: entries.iter().map(|e| Some(e.pattern.clone())).collect(),
+        matches,
+    };
+    while optimizer.remove_redundant()? || optimizer.merge_one()? {}
+
+    Ok(entries
+        .iter()
+        .zip(&optimizer.patterns)
+        .enumerate()
+        .filter_map(|(index, (entry, pattern))| match pattern {
+            None => Some(RuleEdit::Remove(index)),
+            Some(pattern) if *pattern != entry.pattern => {
+                Some(RuleEdit::Replace(index, pattern.clone()))
+            }
+            Some(_) => None,
+        })
+        .collect())
+}
+
+impl Optimizer<'_> {
+    /// Remove every rule whose removal changes nothing; true if any was removed
+    fn remove_redundant(&mut self) -> Result<bool> {
+        let mut removed = false;
+        for rule in 0..self.patterns.len() {
+            if self.patterns[rule].is_none() {
+                continue;
+            }
+            let mut patterns = self.patterns.clone();
+            patterns[rule] = None;
+            let affected = self.matches[rule].clone();
+            if self.equivalent(&patterns, &affected)? {
+                self.patterns = patterns;
+                self.matches[rule].clear();
+                removed = true;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Merge the first group of rules that can share a directory pattern; true if merged
+    ///
+    /// Shallow directories are tried first, as they merge the most rules.
+    fn merge_one(&mut self) -> Result<bool> {
+        let decided = self.decided()?;
+
+        // Rules with the same file, owners and tags, with the directory of their files
+        let mut groups: BTreeMap<GroupKey, Vec<(usize, PathBuf)>> = BTreeMap::new();
+        for (rule, files) in decided.iter().enumerate() {
+            let Some(scope) = common_dir(files.iter().map(|&file| &self.files[file])) else {
+                continue;
+            };
+            let entry = &self.entries[rule];
+            let key = (
+                entry.source_file.as_path(),
+                entry.owners.iter().map(|o| o.identifier.as_str()).collect(),
+                entry.tags.iter().map(|t| t.0.as_str()).collect(),
+            );
+            groups.entry(key).or_default().push((rule, scope));
+        }
+
+        for ((source_file, _, _), rules) in groups {
+            let Some(source_dir) = source_file.parent() else {
+                continue;
+            };
+            let mut dirs: BTreeSet<(usize, PathBuf)> = BTreeSet::new();
+            for (_, scope) in &rules {
+                for dir in scope.ancestors().filter(|dir| dir.starts_with(source_dir)) {
+                    dirs.insert((dir.components().count(), dir.to_path_buf()));
+                }
+            }
+
+            for (_, dir) in dirs {
+                let members: Vec<usize> = rules
+                    .iter()
+                    .filter(|(_, scope)| scope.starts_with(&dir))
+                    .map(|(rule, _)| *rule)
+                    .collect();
+                if members.len() < 2 {
+                    continue;
+                }
+                if self.try_merge(&members, &dir_pattern(&dir, source_dir))? {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Replace `members` with one rule for `pattern`, at the position of the last or first
+    /// member, if that changes nothing
+    fn try_merge(&mut self, members: &[usize], pattern: &str) -> Result<bool> {
+        let first = members[0];
+        let keeper = members[members.len() - 1];
+        let new_matches = self.matched_by(keeper, pattern);
+
+        for position in [keeper, first] {
+            let mut patterns = self.patterns.clone();
+            for &member in members {
+                patterns[member] = None;
+            }
+            patterns[position] = Some(pattern.to_string());
+
+            let mut affected = new_matches.clone();
+            for &member in members {
+                affected.extend(&self.matches[member]);
+            }
+            if self.equivalent(&patterns, &affected)? {
+                self.patterns = patterns;
+                for &member in members {
+                    self.matches[member].clear();
+                }
+                self.matches[position] = new_matches;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Rules with their current patterns, and the index of the rule each came from
+    fn active(&self, patterns: &[Option<String>]) -> (Vec<CodeownersEntry>, Vec<usize>) {
+        patterns
+            .iter()
+            .enumerate()
+            .filter_map(|(rule, pattern)| {
+                let pattern = pattern.as_ref()?;
+                let entry = CodeownersEntry {
+                    pattern: pattern.clone(),
+                    ..self.entries[rule].clone()
+                };
+                Some((entry, rule))
+            })
+            .unzip()
+    }
+
+    /// Whether the `affected` files still resolve as they did originally with `patterns`
+    fn equivalent(&self, patterns: &[Option<String>], affected: &BTreeSet<usize>) -> Result<bool> {
+        let (entries, _) = self.active(patterns);
+        let matcher =
+            Matcher::build_with_options(&entries, MatcherStrategy::Override, self.options);
+        let affected: Vec<usize> = affected.iter().copied().collect();
+        let unchanged = affected
+            .par_iter()
+            .map(|&file| {
+                let (owners, tags, _) =
+                    resolve_file_with_policy(&self.files[file], &entries, &matcher, self.policy)?;
+                Ok(self.baseline[file] == (owners, tags))
+            })
+            .collect::<Result<Vec<bool>>>()?;
+        Ok(unchanged.into_iter().all(|same| same))
+    }
+
+    /// Files each rule currently decides, by original rule index
+    fn decided(&self) -> Result<Vec<Vec<usize>>> {
+        let (entries, rules) = self.active(&self.patterns);
+        let matcher =
+            Matcher::build_with_options(&entries, MatcherStrategy::Override, self.options);
+        let applied = self
+            .files
+            .par_iter()
+            .map(|file| {
+                resolve_file_with_policy(file, &entries, &matcher, self.policy)
+                    .map(|(_, _, applied)| applied)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut decided = vec![Vec::new(); self.entries.len()];
+        for (file, applied) in applied.into_iter().enumerate() {
+            for index in applied {
+                decided[rules[index]].push(file);
+            }
+        }
+        Ok(decided)
+    }
+
+    /// Files `pattern` would match in the CODEOWNERS file of `rule`
+    fn matched_by(&self, rule: usize, pattern: &str) -> BTreeSet<usize> {
+        let entry = CodeownersEntry {
+            pattern: pattern.to_string(),
+            ..self.entries[rule].clone()
+        };
+        let matcher = Matcher::build_with_options(
+            std::slice::from_ref(&entry),
+            MatcherStrategy::Override,
+            self.options,
+        );
+        self.files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| !matcher.candidates(file).is_empty())
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+/// Deepest directory containing every file, or `None` without files
+fn common_dir<'a>(mut files: impl Iterator<Item = &'a PathBuf>) -> Option<PathBuf> {
+    let mut common = files.next()?.parent()?.to_path_buf();
+    for file in files {
+        while !file.starts_with(&common) {
+            common = common.parent()?.to_path_buf();
+        }
+    }
+    Some(common)
+}
+
+/// Pattern matching everything in `dir`, relative to the CODEOWNERS file in `source_dir`
+fn dir_pattern(dir: &Path, source_dir: &Path) -> String {
+    let relative = dir.strip_prefix(source_dir).unwrap_or(dir);
+    if relative.as_os_str().is_empty() {
+        return "*".to_string();
+    }
+    let relative: Vec<String> = relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    format!("/{}/", escape_pattern(&relative.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{equivalence::compare, parser::parse_content};
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Write `files` under a temporary directory and parse `codeowners` at its root
+    fn setup(codeowners: &str, files: &[&str]) -> (TempDir, Vec<CodeownersEntry>, Vec<PathBuf>) {
+        let dir = TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = files
+            .iter()
+            .map(|file| {
+                let path = dir.path().join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, "content\n").unwrap();
+                path
+            })
+            .collect();
+        let entries = parse_content(codeowners, &dir.path().join("CODEOWNERS")).unwrap();
+        (dir, entries, paths)
+    }
+
+    fn run(entries: &[CodeownersEntry], files: &[PathBuf]) -> Vec<RuleEdit> {
+        optimize(
+            entries,
+            files,
+            MatchOptions::default(),
+            PrecedencePolicy::Closest,
+        )
+        .unwrap()
+    }
+
+    /// Apply `edits` and check that nothing resolves differently
+    fn check(entries: &[CodeownersEntry], files: &[PathBuf], edits: &[RuleEdit]) {
+        let mut optimized: Vec<Option<CodeownersEntry>> =
+            entries.iter().cloned().map(Some).collect();
+        for edit in edits {
+            match edit {
+                RuleEdit::Remove(rule) => optimized[*rule] = None,
+                RuleEdit::Replace(rule, pattern) => {
+                    optimized[*rule].as_mut().unwrap().pattern = pattern.clone()
+                }
+            }
+        }
+        let optimized: Vec<CodeownersEntry> = optimized.into_iter().flatten().collect();
+        let differing = compare(
+            entries,
+            &optimized,
+            files,
+            MatchOptions::default(),
+            PrecedencePolicy::Closest,
+        )
+        .unwrap();
+        assert!(differing.is_empty(), "{:?}", differing);
+    }
+
+    #[test]
+    fn test_removes_dead_and_overridden_rules() {
+        let (_dir, entries, files) = setup(
+            "* @org/all\n/gone/ @x\n/src/*.rs @y\n/src/ @z\n/docs/ @org/all\n",
+            &["src/lib.rs", "docs/a.md", "README.md"],
+        );
+
+        let edits = run(&entries, &files);
+        assert_eq!(
+            edits,
+            [
+                RuleEdit::Remove(1),
+                RuleEdit::Remove(2),
+                RuleEdit::Remove(4)
+            ]
+        );
+        check(&entries, &files, &edits);
+    }
+
+    #[test]
+    fn test_merges_sibling_directories_with_the_same_owners() {
+        let (_dir, entries, files) = setup(
+            "* @org/all\n/src/a/ @team #core\n/src/b/*.rs @team #core\n/src/c/ @other\n",
+            &["README.md", "src/a/x.rs", "src/b/y.rs", "src/c/z.rs"],
+        );
+
+        let edits = run(&entries, &files);
+        assert_eq!(
+            edits,
+            [
+                RuleEdit::Remove(1),
+                RuleEdit::Replace(2, "/src/".to_string())
+            ]
+        );
+        check(&entries, &files, &edits);
+    }
+
+    #[test]
+    fn test_keeps_rules_that_cannot_be_merged() {
+        // Merging a and b would take over c's files, since c comes first
+        let (_dir, entries, files) = setup(
+            "/src/c/ @other\n/src/a/ @team\n/src/b/ @team\n",
+            &["src/a/x.rs", "src/b/y.rs", "src/c/z.rs", "src/d.rs"],
+        );
+
+        let edits = run(&entries, &files);
+        assert!(edits.is_empty(), "{:?}", edits);
+    }
+}
@@ -0,0 +1,376 @@
+//! Ownership rolled up across the repositories of an organization
+//!
+//! A workspace file lists repositories whose caches are already built:
+//!
+//! ```toml
+//! [[repo]]
+//! path = "../api"
+//!
+//! [[repo]]
+//! name = "web"
+//! path = "/src/web-frontend"
+//! cache_file = ".cache/codeowners"
+//! ```
+//!
+//! Paths are relative to the workspace file. The name defaults to the last component
+//! of the path, and the cache file to the `cache_file` setting. Without a workspace file,
+//! the repositories of the machine-wide [`registry`](super::registry) are rolled up.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::registry::Registry;
+use super::types::{CodeownersCache, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Output format of the organization report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
+impl std::fmt::Display for ReportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportFormat::Text => write!(f, "text"),
+            ReportFormat::Json => write!(f, "json"),
+            ReportFormat::Html => write!(f, "html"),
+        }
+    }
+}
+
+/// Repositories to aggregate
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Workspace {
+    #[serde(rename = "repo", default)]
+    pub repos: Vec<WorkspaceRepo>,
+}
+
+/// A repository entry of a workspace file
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WorkspaceRepo {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub cache_file: Option<PathBuf>,
+}
+
+impl Workspace {
+    /// Parse a workspace file, resolving repository paths against `base`
+    pub fn parse(contents: &str, source: &Path, base: &Path) -> Result<Workspace> {
+        let mut workspace: Workspace = toml::from_str(contents).map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse workspace {}: {}",
+                source.display(),
+                e
+            ))
+        })?;
+        if workspace.repos.is_empty() {
+            return Err(Error::new(&format!(
+                "Workspace {} lists no repositories; add [[repo]] entries with a path",
+                source.display()
+            )));
+        }
+
+        let mut names = BTreeSet::new();
+        for repo in &mut workspace.repos {
+            repo.path = base.join(&repo.path);
+            let name = repo.name().to_string();
+            if !names.insert(name.clone()) {
+                return Err(Error::new(&format!(
+                    "Workspace {} lists repository {} more than once; give them distinct names",
+                    source.display(),
+                    name
+                )));
+            }
+        }
+        Ok(workspace)
+    }
+
+    /// Load a workspace file
+    pub fn load(path: &Path) -> Result<Workspace> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read workspace {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        let base = path.parent().unwrap_or(Path::new("."));
+        Workspace::parse(&contents, path, base)
+    }
+
+    /// The repositories recorded in `registry`, see [`registry`](super::registry)
+    ///
+    /// Each is named after the last component of its path, or the whole path when
+    /// another repository has the same name.
+    pub fn from_registry(registry: &Registry) -> Result<Workspace> {
+        if registry.repos.is_empty() {
+            return Err(Error::new(
+                "No repositories recorded in the registry; build their caches with `ci codeowners parse`, or pass --workspace",
+            ));
+        }
+
+        let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+        for repo in &registry.repos {
+            *names.entry(repo.name()).or_default() += 1;
+        }
+        let repos = registry
+            .repos
+            .iter()
+            .map(|repo| WorkspaceRepo {
+                name: Some(if names[repo.name()] > 1 {
+                    repo.path.display().to_string()
+                } else {
+                    repo.name().to_string()
+                }),
+                path: repo.path.clone(),
+                cache_file: Some(repo.cache_file.clone()),
+            })
+            .collect();
+        Ok(Workspace { repos })
+    }
+}
+
+impl WorkspaceRepo {
+    /// Name in reports: as configured, or the last component of the path
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_else(|| {
+            self.path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or("repository")
+        })
+    }
+}
+
+/// Organization-level rollup of the ownership of several repositories
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrgReport {
+    pub repos: Vec<RepoSummary>,
+    pub total_files: usize,
+    pub unowned_files: usize,
+    /// Every owner, those in the most repositories first
+    pub owners: Vec<OrgOwner>,
+    /// Repositories left out, with the reason
+    pub skipped: Vec<SkippedRepo>,
+}
+
+/// Ownership of one repository
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RepoSummary {
+    pub name: String,
+    pub files: usize,
+    pub unowned_files: usize,
+    pub owners: usize,
+}
+
+/// An owner with the repositories it owns files in
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OrgOwner {
+    pub identifier: String,
+    pub owner_type: OwnerType,
+    /// Names of the repositories, sorted
+    pub repos: Vec<String>,
+    pub files: usize,
+}
+
+/// A repository whose cache could not be loaded
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedRepo {
+    pub name: String,
+    pub reason: String,
+}
+
+impl OrgReport {
+    /// Owners of files in more than one repository
+    pub fn spanning_owners(&self) -> impl Iterator<Item = &OrgOwner> {
+        self.owners.iter().filter(|owner| owner.repos.len() > 1)
+    }
+
+    /// Team owners, those in the most repositories first
+    pub fn teams(&self) -> impl Iterator<Item = &OrgOwner> {
+        self.owners
+            .iter()
+            .filter(|owner| owner.owner_type == OwnerType::Team)
+    }
+}
+
+/// Roll up the caches of named repositories
+///
+/// Generated files are left out. Owners are the same across repositories when their handles match ignoring case, as
+/// on GitHub; the first spelling seen is reported.
+pub fn aggregate(repos: &[(String, CodeownersCache)], skipped: Vec<SkippedRepo>) -> OrgReport {
+    // Owner handle, lowercased -> (identifier, type, repositories, files)
+    let mut owners: BTreeMap<String, (String, OwnerType, BTreeSet<String>, usize)> =
+        BTreeMap::new();
+    let mut summaries = Vec::new();
+
+    for (name, cache) in repos {
+        let mut unowned = 0;
+        let mut repo_owners = BTreeSet::new();
+        let files: Vec<_> = cache.files.iter().filter(|file| !file.generated).collect();
+        for file in &files {
+            let mut owned = false;
+            for owner in &file.owners {
+                if owner.owner_type == OwnerType::Unowned {
+                    continue;
+                }
+                owned = true;
+                let key = owner.identifier.to_lowercase();
+                let entry = owners.entry(key.clone()).or_insert_with(|| {
+                    (
+                        owner.identifier.clone(),
+                        owner.owner_type.clone(),
+                        BTreeSet::new(),
+                        0,
+                    )
+                });
+                entry.2.insert(name.clone());
+                entry.3 += 1;
+                repo_owners.insert(key);
+            }
+            if !owned {
+                unowned += 1;
+            }
+        }
+        summaries.push(RepoSummary {
+            name: name.clone(),
+            files: files.len(),
+            unowned_files: unowned,
+            owners: repo_owners.len(),
+        });
+    }
+
+    let mut owners: Vec<OrgOwner> = owners
+        .into_values()
+        .map(|(identifier, owner_type, repos, files)| OrgOwner {
+            identifier,
+            owner_type,
+            repos: repos.into_iter().collect(),
+            files,
+        })
+        .collect();
+    owners.sort_by(|a, b| {
+        b.repos
+            .len()
+            .cmp(&a.repos.len())
+            .then(b.files.cmp(&a.files))
+            .then_with(|| a.identifier.cmp(&b.identifier))
+    });
+
+    OrgReport {
+        total_files: summaries.iter().map(|repo| repo.files).sum(),
+        unowned_files: summaries.iter().map(|repo| repo.unowned_files).sum(),
+        repos: summaries,
+        owners,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{FileEntry, Owner};
+
+    fn cache(files: &[(&str, &[(&str, OwnerType)])]) -> CodeownersCache {
+        let files = files
+            .iter()
+            .map(|(path, owners)| FileEntry {
+                path: PathBuf::from(path),
+                owners: owners
+                    .iter()
+                    .map(|(identifier, owner_type)| Owner {
+                        identifier: identifier.to_string(),
+                        owner_type: owner_type.clone(),
+                    })
+                    .collect(),
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            })
+            .collect();
+        CodeownersCache::new([0; 32], Vec::new(), files, Vec::new())
+    }
+
+    #[test]
+    fn test_aggregate_rolls_up_owners_across_repos() {
+        let api = cache(&[
+            ("src/a.rs", &[("@org/platform", OwnerType::Team)]),
+            ("src/b.rs", &[("@alice", OwnerType::User)]),
+            ("tmp.txt", &[("NOBODY", OwnerType::Unowned)]),
+        ]);
+        let web = cache(&[
+            ("app.js", &[("@Org/Platform", OwnerType::Team)]),
+            ("README.md", &[]),
+        ]);
+
+        let report = aggregate(
+            &[("api".to_string(), api), ("web".to_string(), web)],
+            Vec::new(),
+        );
+
+        assert_eq!(report.total_files, 5);
+        assert_eq!(report.unowned_files, 2);
+        assert_eq!(report.repos[0].owners, 2);
+        assert_eq!(report.owners[0].identifier, "@org/platform");
+        assert_eq!(report.owners[0].repos, vec!["api", "web"]);
+        assert_eq!(report.owners[0].files, 2);
+        assert_eq!(report.spanning_owners().count(), 1);
+        assert_eq!(report.teams().count(), 1);
+    }
+
+    #[test]
+    fn test_workspace_resolves_paths_and_rejects_duplicates() {
+        let workspace = Workspace::parse(
+            "[[repo]]\npath = \"api\"\n\n[[repo]]\nname = \"frontend\"\npath = \"/src/web\"\n",
+            Path::new("ws.toml"),
+            Path::new("/work"),
+        )
+        .unwrap();
+        assert_eq!(workspace.repos[0].path, PathBuf::from("/work/api"));
+        assert_eq!(workspace.repos[0].name(), "api");
+        assert_eq!(workspace.repos[1].path, PathBuf::from("/src/web"));
+        assert_eq!(workspace.repos[1].name(), "frontend");
+
+        let duplicate = Workspace::parse(
+            "[[repo]]\npath = \"a/api\"\n\n[[repo]]\npath = \"b/api\"\n",
+            Path::new("ws.toml"),
+            Path::new("/work"),
+        );
+        assert!(duplicate.is_err());
+    }
+
+    #[test]
+    fn test_workspace_from_registry_names_repos_apart() {
+        use crate::core::registry::RegisteredRepo;
+
+        let registered = |path: &str| RegisteredRepo {
+            path: PathBuf::from(path),
+            cache_file: Path::new(path).join(".codeowners.cache"),
+            cached_at: Default::default(),
+            files: 0,
+            unowned: 0,
+        };
+        let registry = Registry {
+            repos: vec![
+                registered("/a/api"),
+                registered("/b/api"),
+                registered("/src/web"),
+            ],
+        };
+        let workspace = Workspace::from_registry(&registry).unwrap();
+        let names: Vec<&str> = workspace.repos.iter().map(WorkspaceRepo::name).collect();
+        assert_eq!(names, ["/a/api", "/b/api", "web"]);
+        assert_eq!(
+            workspace.repos[2].cache_file,
+            Some(PathBuf::from("/src/web/.codeowners.cache"))
+        );
+        assert!(Workspace::from_registry(&Registry::default()).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+//! Layered caches: a shared base cache with a local overlay
+//!
+//! A base cache, such as one built on the default branch and downloaded as a CI
+//! artifact, is read but never written. The files changed since the commit it was built
+//! from are resolved into a small overlay cache next to the local cache file, and the two
+//! are merged when the cache is loaded. The overlay is reused while neither the repository
+//! nor the base changes, so only the first command after an edit resolves any files.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::cache::{read_cache, rebuild_reasons, save_cache};
+use super::common::{changed_since, get_cache_hash};
+use super::context::Context;
+use super::parse::{changes_rules, refresh_paths};
+use super::types::{
+    BuildInfo, CacheEncoding, CodeownersCache, CodeownersEntry, FileEntry, PrecedencePolicy,
+    CACHE_VERSION,
+};
+use crate::utils::error::Result;
+use crate::utils::output;
+use crate::utils::warnings::{self, WarningKind};
+
+/// Where the overlay of the cache file at `cache_file` is kept: next to it, with
+/// `.overlay` appended to its name
+pub fn overlay_path(cache_file: &Path) -> PathBuf {
+    let mut name = cache_file.as_os_str().to_owned();
+    name.push(".overlay");
+    PathBuf::from(name)
+}
+
+/// The base cache at `base_file` merged with the overlay for the files of `repo` changed
+/// since it was built, or `None` when the base cannot be layered
+///
+/// `base_file` is relative to `repo`, and the overlay is kept next to `cache_file`, see
+/// [`overlay_path`]. The base cannot be layered when it is unreadable, in an older cache
+/// format or built with other settings, when the commit it was built from is unknown, or
+/// when a change since touches the rules (see [`changes_rules`]); all but the last are
+/// warned about. The caller then falls back to the local cache.
+pub(crate) fn load_layered(
+    ctx: &Context, repo: &Path, base_file: &Path, cache_file: &Path,
+) -> Result<Option<CodeownersCache>> {
+    let unusable = |reason: &str| {
+        warnings::warn(
+            WarningKind::Other,
+            format!(
+                "Not using base cache {}, {}; using {}",
+                base_file.display(),
+                reason,
+                cache_file.display()
+            ),
+        );
+        Ok(None)
+    };
+
+    let base = match read_cache(&repo.join(base_file)) {
+        Ok(stored) if stored.version == CACHE_VERSION => stored.cache,
+        Ok(stored) => {
+            return unusable(&format!("which is in cache format {}", stored.version));
+        }
+        Err(e) => return unusable(&e.to_string()),
+    };
+    let reasons = rebuild_reasons(ctx, repo, &base.build, base.precedence, &base.entries)?;
+    if !reasons.is_empty() {
+        return unusable(&format!("which was built with {}", reasons.join(", ")));
+    }
+
+    let config = &ctx.config;
+    let hash = get_cache_hash(repo, config.match_options(), &config.generated_markers)?;
+    if base.hash == hash {
+        return Ok(Some(base));
+    }
+
+    let Some(source) = &base.build.source else {
+        return unusable("which does not record the commit it was built from");
+    };
+    let Some(changed) = changed_since(repo, source)? else {
+        return unusable("whose commit is not in this repository");
+    };
+    // Spell the paths like the cache does, under `repo`
+    let changed: Vec<PathBuf> = changed.iter().map(|path| repo.join(path)).collect();
+    if changed
+        .iter()
+        .any(|path| changes_rules(ctx, repo, path, &base.entries))
+    {
+        log::debug!(
+            "Not layering {}: the rules changed since it was built",
+            base_file.display()
+        );
+        return Ok(None);
+    }
+
+    let key = overlay_hash(&hash, &base.hash);
+    let overlay_file = overlay_path(&repo.join(cache_file));
+    if let Some(overlay) = read_overlay(&overlay_file, &key, &base) {
+        log::debug!(
+            "Layered {} over {}",
+            overlay_file.display(),
+            base_file.display()
+        );
+        return Ok(Some(merge(base, overlay, &changed, hash)));
+    }
+
+    let merged = refresh_paths(ctx, repo, base, &changed)?;
+    save_cache(
+        &overlay_of(&merged, &changed, key),
+        &overlay_file,
+        CacheEncoding::Bincode,
+    )?;
+    log::debug!(
+        "Resolved {} paths changed since {} was built into {}",
+        changed.len(),
+        base_file.display(),
+        overlay_file.display()
+    );
+    Ok(Some(merged))
+}
+
+/// Hash identifying the overlay for the repository state hashed as `repo_hash` over the
+/// base hashed as `base_hash`
+fn overlay_hash(repo_hash: &[u8; 32], base_hash: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(b"overlay");
+    hasher.update(repo_hash);
+    hasher.update(base_hash);
+    hasher.finalize().into()
+}
+
+/// The overlay at `path`, if there is one for `key` that was resolved with the rules of
+/// `base`
+fn read_overlay(path: &Path, key: &[u8; 32], base: &CodeownersCache) -> Option<CodeownersCache> {
+    let stored = read_cache(path).ok()?;
+    let overlay = stored.cache;
+    (stored.version == CACHE_VERSION
+        && overlay.hash == *key
+        && overlay.precedence == base.precedence
+        && overlay.entries.len() == base.entries.len())
+    .then_some(overlay)
+}
+
+/// Each of `files` with the rules of `rule_matches` that decided its ownership
+fn files_with_rules(
+    files: Vec<FileEntry>, rule_matches: &[Vec<usize>],
+) -> Vec<(FileEntry, Vec<usize>)> {
+    let mut rules = vec![Vec::new(); files.len()];
+    for (rule, positions) in rule_matches.iter().enumerate() {
+        for &position in positions {
+            if let Some(file_rules) = rules.get_mut(position) {
+                file_rules.push(rule);
+            }
+        }
+    }
+    files.into_iter().zip(rules).collect()
+}
+
+/// A cache of `files` resolved with `entries` and `precedence`
+fn assemble(
+    hash: [u8; 32], entries: Vec<CodeownersEntry>, files: Vec<(FileEntry, Vec<usize>)>,
+    precedence: PrecedencePolicy, build: BuildInfo,
+) -> CodeownersCache {
+    let mut rule_matches = vec![Vec::new(); entries.len()];
+    let mut file_entries = Vec::with_capacity(files.len());
+    for (position, (file, file_rules)) in files.into_iter().enumerate() {
+        for rule in file_rules {
+            rule_matches[rule].push(position);
+        }
+        file_entries.push(file);
+    }
+
+    let mut cache = CodeownersCache::new(hash, entries, file_entries, rule_matches);
+    cache.precedence = precedence;
+    cache.build = build;
+    cache
+}
+
+/// The files of `merged` at or under `changed`, stored under `key`
+fn overlay_of(merged: &CodeownersCache, changed: &[PathBuf], key: [u8; 32]) -> CodeownersCache {
+    let files = files_with_rules(merged.files.clone(), &merged.rule_matches)
+        .into_iter()
+        .filter(|(file, _)| changed.iter().any(|path| file.path.starts_with(path)))
+        .collect();
+    assemble(
+        key,
+        merged.entries.clone(),
+        files,
+        merged.precedence,
+        merged.build.clone(),
+    )
+}
+
+/// `base` with its files at or under `changed` replaced by those of `overlay`, hashed as
+/// the repository state `hash`
+fn merge(
+    base: CodeownersCache, overlay: CodeownersCache, changed: &[PathBuf], hash: [u8; 32],
+) -> CodeownersCache {
+    // The overlay records the repository state it was resolved at
+    let build = overlay.build.clone();
+    let mut files: Vec<(FileEntry, Vec<usize>)> = files_with_rules(base.files, &base.rule_matches)
+        .into_iter()
+        .filter(|(file, _)| !changed.iter().any(|path| file.path.starts_with(path)))
+        .collect();
+    files.extend(files_with_rules(overlay.files, &overlay.rule_matches));
+    // In the order a rebuild lists them
+    if output::is_stable() {
+        files.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+    }
+    assemble(hash, base.entries, files, base.precedence, build)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parse::parse_repo;
+    use crate::utils::app_config::AppConfig;
+    use git2::{Repository, Signature};
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn commit_all(repo: &Repository) {
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "commit",
+            &tree,
+            &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_load_layered() -> Result<()> {
+        let dir = TempDir::new()?;
+        let repo = dir.path();
+        let git = Repository::init(repo)?;
+        fs::write(repo.join(".gitignore"), "*.cache*\n")?;
+        fs::write(repo.join("CODEOWNERS"), "*.rs @rust\n*.md @docs\n")?;
+        fs::write(repo.join("lib.rs"), "")?;
+        fs::write(repo.join("old.rs"), "")?;
+        commit_all(&git);
+
+        let ctx = Context::new(AppConfig::default());
+        let base_file = Path::new("base.cache");
+        parse_repo(&ctx, repo, base_file, CacheEncoding::Bincode)?;
+        let cache_file = Path::new("local.cache");
+
+        fs::remove_file(repo.join("old.rs"))?;
+        fs::write(repo.join("README.md"), "")?;
+        let owners = |cache: &CodeownersCache| -> Vec<(String, String)> {
+            let mut owners: Vec<(String, String)> = cache
+                .files
+                .iter()
+                .map(|file| {
+                    let path = file.path.strip_prefix(repo).unwrap();
+                    (
+                        path.display().to_string(),
+                        file.owners[0].identifier.clone(),
+                    )
+                })
+                .collect();
+            owners.sort();
+            owners
+        };
+        let expected = [("README.md", "@docs"), ("lib.rs", "@rust")]
+            .map(|(path, owner)| (path.to_string(), owner.to_string()));
+
+        let layered = load_layered(&ctx, repo, base_file, cache_file)?.unwrap();
+        assert_eq!(owners(&layered), expected);
+        let overlay_file = overlay_path(&repo.join(cache_file));
+        assert_eq!(read_cache(&overlay_file)?.cache.files.len(), 1);
+        assert!(!repo.join(cache_file).exists());
+
+        // The stored overlay is merged the same way
+        let reloaded = load_layered(&ctx, repo, base_file, cache_file)?.unwrap();
+        assert_eq!(owners(&reloaded), expected);
+        let readme = repo.join("README.md");
+        assert_eq!(reloaded.rules_matching(&readme).next().unwrap().0, 1);
+
+        // A change to the rules cannot be layered
+        fs::write(repo.join("CODEOWNERS"), "* @everyone\n")?;
+        assert!(load_layered(&ctx, repo, base_file, cache_file)?.is_none());
+        Ok(())
+    }
+}
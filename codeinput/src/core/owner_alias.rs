@@ -0,0 +1,119 @@
+//! Owner aliases translating git author emails into CODEOWNERS handles
+//!
+//! Ownership inference works from git history, which knows authors by email address.
+//! CODEOWNERS entries are more useful with the handle or team GitHub knows them by, so an
+//! alias file maps addresses to owners. It is a TOML table of email to `@handle` or
+//! `@org/team`:
+//!
+//! ```toml
+//! "alice@corp.com" = "@alice"
+//! "alice@old-corp.com" = "@alice"
+//! "build-bot@corp.com" = "@org/infra"
+//! ```
+//!
+//! Addresses are case-insensitive. `codeowners list-owners --by-type` suggests aliases in
+//! this format.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::context::Context;
+use crate::utils::error::{Error, Result};
+
+/// Owner for each aliased email address, keyed by lowercased address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OwnerAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl OwnerAliases {
+    /// Parse an alias file; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<OwnerAliases> {
+        let table = contents.parse::<toml::Table>().map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse owner aliases {}: {}",
+                source.display(),
+                e
+            ))
+        })?;
+
+        let mut aliases = HashMap::new();
+        for (email, value) in table {
+            let owner = value
+                .as_str()
+                .filter(|owner| owner.len() > 1 && owner.starts_with('@'))
+                .ok_or_else(|| {
+                    Error::new(&format!(
+                        "Invalid owner aliases {}: {} must map to an @handle or @org/team",
+                        source.display(),
+                        email
+                    ))
+                })?;
+            aliases.insert(email.to_lowercase(), owner.to_string());
+        }
+
+        Ok(OwnerAliases { aliases })
+    }
+
+    /// Load an alias file
+    pub fn load(path: &Path) -> Result<OwnerAliases> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read owner aliases {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        OwnerAliases::parse(&contents, path)
+    }
+
+    /// Load the aliases configured for `repo`, or none if there is no alias file
+    ///
+    /// The `owner_aliases` setting is resolved relative to the repository.
+    pub fn for_repo(ctx: &Context, repo: &Path) -> Result<OwnerAliases> {
+        let path = repo.join(&ctx.config.owner_aliases);
+        if !path.exists() {
+            return Ok(OwnerAliases::default());
+        }
+        OwnerAliases::load(&path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Owner aliased to `email`, if any
+    pub fn owner(&self, email: &str) -> Option<&str> {
+        self.aliases.get(&email.to_lowercase()).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_is_case_insensitive() {
+        let aliases = OwnerAliases::parse(
+            r#"
+"Alice@Corp.com" = "@alice"
+"bot@corp.com" = "@org/infra"
+"#,
+            Path::new("aliases.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(aliases.owner("alice@corp.com"), Some("@alice"));
+        assert_eq!(aliases.owner("BOT@corp.com"), Some("@org/infra"));
+        assert_eq!(aliases.owner("carol@corp.com"), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_owners() {
+        let parse = |contents: &str| OwnerAliases::parse(contents, Path::new("aliases.toml"));
+        assert!(parse(r#""alice@corp.com" = "alice""#).is_err());
+        assert!(parse(r#""alice@corp.com" = "@""#).is_err());
+        assert!(parse(r#""alice@corp.com" = ["@alice"]"#).is_err());
+        assert!(parse("not toml = ").is_err());
+    }
+}
@@ -20,6 +20,18 @@ pub fn find_files_for_owner(files: &[FileEntry], owner: &Owner) -> Vec<PathBuf>
         .collect()
 }
 
+/// Remove repeated owners, keeping the first occurrence of each
+///
+/// GitHub handles and emails are case-insensitive, so `@Alice` and `@alice` are the same
+/// owner; the spelling that appears first is kept.
+pub fn dedup_owners(owners: Vec<Owner>) -> Vec<Owner> {
+    let mut seen = std::collections::HashSet::new();
+    owners
+        .into_iter()
+        .filter(|owner| seen.insert(owner.identifier.to_lowercase()))
+        .collect()
+}
+
 /// Find owners for a specific file based on all parsed CODEOWNERS entries
 pub fn find_owners_for_file<'a>(
     file_path: &'a Path, entries: &'a [CodeownersEntry],
@@ -33,7 +45,7 @@ pub fn find_owners_for_file<'a>(
     let mut candidates: Vec<_> = entries
         .iter()
         .filter_map(|entry| {
-            let codeowners_dir = match entry.source_file.parent() {
+            let codeowners_dir = match entry.base_dir() {
                 Some(dir) => dir,
                 None => {
                     eprintln!(
@@ -109,14 +121,14 @@ pub fn find_owners_for_file<'a>(
     // Extract the owners from the highest priority entry, if any
     Ok(candidates
         .first()
-        .map(|(entry, _)| entry.owners.clone())
+        .map(|(entry, _)| dedup_owners(entry.owners.clone()))
         .unwrap_or_default())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::types::{Owner, OwnerType};
+    use crate::core::types::{EntrySpans, Owner, OwnerType};
     use std::path::PathBuf;
 
     fn create_test_owner(identifier: &str, owner_type: OwnerType) -> Owner {
@@ -131,6 +143,8 @@ mod tests {
             path: PathBuf::from(path),
             owners,
             tags: vec![],
+            generated: false,
+            inline: None,
         }
     }
 
@@ -143,9 +157,27 @@ mod tests {
             pattern: pattern.to_string(),
             owners,
             tags: vec![],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
         }
     }
 
+    #[test]
+    fn test_dedup_owners_keeps_first_spelling() {
+        let owners = vec![
+            create_test_owner("@org/Team", OwnerType::Team),
+            create_test_owner("dev@example.com", OwnerType::Email),
+            create_test_owner("@org/team", OwnerType::Team),
+            create_test_owner("DEV@example.com", OwnerType::Email),
+        ];
+        let identifiers: Vec<_> = dedup_owners(owners)
+            .into_iter()
+            .map(|owner| owner.identifier)
+            .collect();
+        assert_eq!(identifiers, ["@org/Team", "dev@example.com"]);
+    }
+
     #[test]
     fn test_find_files_for_owner_empty_files() {
         let files: Vec<FileEntry> = vec![];
@@ -1,41 +1,351 @@
-use crate::utils::error::Result;
+use std::collections::BTreeSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::utils::error::{Error, Result};
+use crate::utils::output;
 
 use super::{
-    cache::{build_cache, store_cache},
-    common::{find_codeowners_files, find_files, get_repo_hash},
-    parser::parse_codeowners,
-    types::{CacheEncoding, CodeownersCache, CodeownersEntry},
+    bitbucket,
+    cache::{load_cache, write_cache},
+    central::central_codeowners,
+    common::{
+        find_codeowners_files, find_dialect_codeowners_files, find_files, get_cache_hash,
+        is_listed_file, repo_state,
+    },
+    context::Context,
+    generated::is_generated,
+    matcher::Matcher,
+    parser::parse_codeowners_as,
+    registry,
+    resolver::resolve_file_with_source,
+    tag_remap::TagRemap,
+    types::{BuildInfo, CacheEncoding, CodeownersCache, CodeownersEntry, Dialect, FileEntry},
 };
 
-pub fn parse_repo(repo: &std::path::Path, cache_file: &std::path::Path) -> Result<CodeownersCache> {
+/// Parse the CODEOWNERS files of `repo` and build its cache at `cache_file` in `encoding`
+pub fn parse_repo(
+    ctx: &Context, repo: &std::path::Path, cache_file: &std::path::Path, encoding: CacheEncoding,
+) -> Result<CodeownersCache> {
     println!("Parsing CODEOWNERS files at {}", repo.display());
+    let config = &ctx.config;
 
-    // Collect all CODEOWNERS files in the specified path
-    let codeowners_files = find_codeowners_files(repo)?;
+    // Collect the CODEOWNERS files the dialect reads in the specified path
+    let codeowners_files = find_dialect_codeowners_files(repo, config.dialect)?;
 
     // Parse each CODEOWNERS file and collect entries
-    let parsed_codeowners: Vec<CodeownersEntry> = codeowners_files
+    let mut parsed_codeowners: Vec<CodeownersEntry> = codeowners_files
         .iter()
         .filter_map(|file| {
-            let parsed = parse_codeowners(file).ok()?;
+            let parsed = parse_codeowners_as(file, config.dialect).ok()?;
             Some(parsed)
         })
         .flatten()
         .collect();
 
+    // Merge the rules of the central ownership repository, fetched by `parse` only
+    if let Some((_, central)) = central_codeowners(ctx, repo, false)? {
+        parsed_codeowners.extend(central);
+    }
+
     // Collect all files in the specified path
     let files = find_files(repo)?;
 
     // Get the hash of the repository
-    let hash = get_repo_hash(repo)?;
+    let hash = get_cache_hash(repo, config.match_options(), &config.generated_markers)?;
+    let source = repo_state(repo)?;
 
-    // Build the cache from the parsed CODEOWNERS entries and the files
-    let cache = build_cache(parsed_codeowners, files, hash)?;
+    // Build the cache from the parsed CODEOWNERS entries and the files, streaming it to disk
+    let cache_path = repo.join(cache_file);
+    write_cache(
+        ctx,
+        &parsed_codeowners,
+        &files,
+        hash,
+        &source,
+        config.matcher,
+        &TagRemap::for_repo(ctx, repo)?,
+        &cache_path,
+        encoding,
+        config.cache_memory_budget_bytes(),
+    )?;
 
-    // Store the cache in the specified file
-    store_cache(&cache, &repo.join(cache_file), CacheEncoding::Bincode)?;
+    let cache = load_cache(&cache_path)?;
+    registry::note(repo, &cache_path, &cache);
 
     println!("CODEOWNERS parsing completed successfully");
 
     Ok(cache)
 }
+
+/// Resolve only `paths`, relative to `repo`, without reading or writing the cache
+///
+/// Directories are walked. Since a CODEOWNERS file applies only below its own directory,
+/// just those in the directories above each file are parsed. The result holds only the
+/// requested files, so repository-wide views of it are incomplete.
+pub fn resolve_paths(ctx: &Context, repo: &Path, paths: &[PathBuf]) -> Result<CodeownersCache> {
+    let mut files = Vec::new();
+    let mut seen = BTreeSet::new();
+    for path in paths {
+        // Drop `.` components so paths come out as the cache spells them
+        let relative: PathBuf = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        let full = if relative.as_os_str().is_empty() {
+            repo.to_path_buf()
+        } else {
+            repo.join(relative)
+        };
+        let found = if full.is_dir() {
+            find_files(&full)?
+        } else if full.is_file() {
+            vec![full]
+        } else {
+            return Err(Error::new(&format!("{} does not exist", full.display())));
+        };
+        for file in found {
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+
+    let config = &ctx.config;
+
+    // The CODEOWNERS files of every directory from each file up to the repository root;
+    // Bitbucket reads only one
+    let mut codeowners_files = BTreeSet::new();
+    match config.dialect {
+        Dialect::GitHub => {
+            for file in &files {
+                for dir in file
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|dir| dir.starts_with(repo))
+                {
+                    let codeowners = dir.join("CODEOWNERS");
+                    if codeowners.is_file() {
+                        codeowners_files.insert(codeowners);
+                    }
+                }
+            }
+        }
+        Dialect::Bitbucket => codeowners_files.extend(bitbucket::find_codeowners_file(repo)),
+    }
+    let mut entries: Vec<CodeownersEntry> = codeowners_files
+        .iter()
+        .filter_map(|file| parse_codeowners_as(file, config.dialect).ok())
+        .flatten()
+        .collect();
+    if let Some((_, central)) = central_codeowners(ctx, repo, false)? {
+        entries.extend(central);
+    }
+
+    let remap = TagRemap::for_repo(ctx, repo)?;
+    let entries = remap.apply_to_entries(&entries);
+    let strategy = config
+        .matcher
+        .select(entries.len(), files.len(), &config.matcher_profile);
+    let matcher = Matcher::build_with_options(&entries, strategy, config.match_options());
+
+    let mut file_entries = Vec::with_capacity(files.len());
+    let mut rule_matches = vec![Vec::new(); entries.len()];
+    for (position, path) in files.into_iter().enumerate() {
+        let (owners, tags, rules, inline) =
+            resolve_file_with_source(&path, &entries, &matcher, config.precedence)?;
+        // Rule tags are already remapped; only inline tags are left
+        let tags = if rules.is_empty() {
+            remap.apply(tags)
+        } else {
+            tags
+        };
+        for rule in rules {
+            rule_matches[rule].push(position);
+        }
+        let generated = is_generated(&path, &config.generated_markers);
+        file_entries.push(FileEntry {
+            path,
+            owners,
+            tags,
+            generated,
+            inline,
+        });
+    }
+
+    let mut cache = CodeownersCache::new([0; 32], entries, file_entries, rule_matches);
+    cache.precedence = config.precedence;
+    Ok(cache)
+}
+
+/// Update `cache` of `repo` for the files at or below `changed`, without rebuilding it
+///
+/// `changed` are spelled like the cache's paths, under `repo`. Cached files there are
+/// dropped, and those that still exist, or were created and are not ignored, are
+/// resolved again against the rules the cache holds. A change to a CODEOWNERS file or
+/// anything else the rules come from is not picked up; that takes [`parse_repo`].
+pub fn refresh_paths(
+    ctx: &Context, repo: &Path, cache: CodeownersCache, changed: &[PathBuf],
+) -> Result<CodeownersCache> {
+    let config = &ctx.config;
+    let CodeownersCache {
+        entries,
+        files,
+        rule_matches,
+        precedence,
+        build,
+        ..
+    } = cache;
+
+    // Rules of each file, to carry over those left alone
+    let mut rules = vec![Vec::new(); files.len()];
+    for (rule, positions) in rule_matches.iter().enumerate() {
+        for &position in positions {
+            if let Some(file_rules) = rules.get_mut(position) {
+                file_rules.push(rule);
+            }
+        }
+    }
+    let mut kept: Vec<(FileEntry, Vec<usize>)> = files
+        .into_iter()
+        .zip(rules)
+        .filter(|(file, _)| !changed.iter().any(|path| file.path.starts_with(path)))
+        .collect();
+
+    let mut found = BTreeSet::new();
+    for path in changed {
+        if path.is_dir() {
+            found.extend(find_files(path)?);
+        } else if is_listed_file(path) {
+            found.insert(path.clone());
+        }
+    }
+
+    let remap = TagRemap::for_repo(ctx, repo)?;
+    let strategy = config.matcher.select(
+        entries.len(),
+        kept.len() + found.len(),
+        &config.matcher_profile,
+    );
+    let matcher = Matcher::build_with_options(&entries, strategy, config.match_options());
+    for path in found {
+        let (owners, tags, file_rules, inline) =
+            resolve_file_with_source(&path, &entries, &matcher, precedence)?;
+        // Rule tags are already remapped; only inline tags are left
+        let tags = if file_rules.is_empty() {
+            remap.apply(tags)
+        } else {
+            tags
+        };
+        let generated = is_generated(&path, &config.generated_markers);
+        kept.push((
+            FileEntry {
+                path,
+                owners,
+                tags,
+                generated,
+                inline,
+            },
+            file_rules,
+        ));
+    }
+
+    // In the order a rebuild lists them
+    if output::is_stable() {
+        kept.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+    }
+    let mut rule_matches = vec![Vec::new(); entries.len()];
+    let mut file_entries = Vec::with_capacity(kept.len());
+    for (position, (file, file_rules)) in kept.into_iter().enumerate() {
+        for rule in file_rules {
+            rule_matches[rule].push(position);
+        }
+        file_entries.push(file);
+    }
+
+    let hash = get_cache_hash(repo, config.match_options(), &config.generated_markers)?;
+    let mut cache = CodeownersCache::new(hash, entries, file_entries, rule_matches);
+    cache.precedence = precedence;
+    cache.build = BuildInfo {
+        source: Some(repo_state(repo)?),
+        ..build
+    };
+    Ok(cache)
+}
+
+/// Whether a change at `changed`, spelled like the cache's paths under `repo`, can change
+/// the rules or which files there are, so that [`refresh_paths`] cannot account for it
+///
+/// That is a CODEOWNERS file, an ignore file or the tag remapping, or a directory holding
+/// CODEOWNERS files.
+pub fn changes_rules(
+    ctx: &Context, repo: &Path, changed: &Path, entries: &[CodeownersEntry],
+) -> bool {
+    let name = changed.file_name().and_then(|name| name.to_str());
+    if matches!(name, Some("CODEOWNERS" | ".gitignore" | ".ignore"))
+        || changed == repo.join(&ctx.config.tag_remap)
+    {
+        return true;
+    }
+    entries
+        .iter()
+        .any(|entry| entry.source_file.starts_with(changed))
+        || (changed.is_dir()
+            && !find_codeowners_files(changed)
+                .unwrap_or_default()
+                .is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::app_config::AppConfig;
+    use git2::Repository;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_refresh_paths() -> Result<()> {
+        let dir = TempDir::new()?;
+        let repo = dir.path();
+        Repository::init(repo)?;
+        fs::write(repo.join("CODEOWNERS"), "*.rs @rust\n/docs/ @docs\n")?;
+        fs::write(repo.join(".gitignore"), "*.log\n")?;
+        fs::create_dir_all(repo.join("src"))?;
+        fs::write(repo.join("src/lib.rs"), "")?;
+        fs::write(repo.join("src/old.rs"), "")?;
+
+        let ctx = Context::new(AppConfig::default());
+        let cache = resolve_paths(&ctx, repo, &[PathBuf::from(".")])?;
+        assert!(cache.file(&repo.join("src/old.rs")).is_some());
+
+        fs::remove_file(repo.join("src/old.rs"))?;
+        fs::write(repo.join("src/new.rs"), "")?;
+        fs::write(repo.join("src/debug.log"), "")?;
+        fs::create_dir_all(repo.join("docs"))?;
+        fs::write(repo.join("docs/guide.md"), "")?;
+        let changed =
+            ["src/old.rs", "src/new.rs", "src/debug.log", "docs"].map(|path| repo.join(path));
+        let cache = refresh_paths(&ctx, repo, cache, &changed)?;
+
+        let owners = |path: &str| {
+            cache.file(&repo.join(path)).map(|file| {
+                file.owners
+                    .iter()
+                    .map(|owner| owner.identifier.clone())
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(owners("src/old.rs"), None);
+        assert_eq!(owners("src/debug.log"), None);
+        assert_eq!(owners("src/lib.rs"), Some(vec!["@rust".to_string()]));
+        assert_eq!(owners("src/new.rs"), Some(vec!["@rust".to_string()]));
+        assert_eq!(owners("docs/guide.md"), Some(vec!["@docs".to_string()]));
+        // The rules know the files they match, new ones included
+        let docs_rule = cache.rules_matching(&repo.join("docs/guide.md")).next();
+        assert_eq!(
+            docs_rule.map(|(_, entry)| entry.pattern.as_str()),
+            Some("/docs/")
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+//! Summary of a cache build, printed by `codeowners parse --report`
+//!
+//! Everything a build skips is otherwise only logged as a warning: CODEOWNERS files that
+//! cannot be read, and patterns no matcher can compile. The report lists them next to the
+//! rule counts and the files that declare their owners inline, by marker or front matter.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::types::{
+    normalize_codeowners_pattern, try_codeowners_entry_to_matcher_with, CodeownersCache,
+    InlineSource, MatchOptions, OwnerType,
+};
+
+/// A CODEOWNERS file found while parsing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodeownersFileReport {
+    pub path: PathBuf,
+    /// Rules read from the file; 0 when it could not be read
+    pub rules: usize,
+    /// Why the file was skipped, if it was
+    pub error: Option<String>,
+}
+
+/// A rule left out of matching because its pattern does not compile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedPattern {
+    pub source_file: PathBuf,
+    /// 1-based line of the rule
+    pub line: usize,
+    pub pattern: String,
+    pub reason: String,
+}
+
+/// What a cache build found and skipped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParseReport {
+    pub codeowners_files: Vec<CodeownersFileReport>,
+    pub rules: usize,
+    pub skipped_patterns: Vec<SkippedPattern>,
+    /// Files owned through an inline marker instead of a rule
+    pub inline_markers: Vec<PathBuf>,
+    /// Files owned through the owners of their front matter instead of a rule
+    pub front_matter: Vec<PathBuf>,
+    pub files: usize,
+    pub unowned: usize,
+    /// Wall time of the whole build, in milliseconds
+    pub duration_ms: u128,
+}
+
+impl ParseReport {
+    /// Report on `cache` of `repo`, built from the CODEOWNERS files in `parsed` in `elapsed`
+    ///
+    /// `parsed` pairs every CODEOWNERS file found with the error it was skipped for.
+    /// Patterns are compiled with `options`, as the build matched them. Paths are reported
+    /// relative to `repo`.
+    pub fn new(
+        repo: &Path, parsed: Vec<(PathBuf, Option<String>)>, cache: &CodeownersCache,
+        options: MatchOptions, elapsed: Duration,
+    ) -> ParseReport {
+        let relative = |path: &Path| path.strip_prefix(repo).unwrap_or(path).to_path_buf();
+        let codeowners_files = parsed
+            .into_iter()
+            .map(|(path, error)| CodeownersFileReport {
+                rules: cache
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.source_file == path)
+                    .count(),
+                path: relative(&path),
+                error,
+            })
+            .collect();
+
+        let skipped_patterns = cache
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let e = try_codeowners_entry_to_matcher_with(entry, options).err()?;
+                // The glob error alone, without the location the report already shows
+                let reason = globset::Glob::new(&normalize_codeowners_pattern(&entry.pattern))
+                    .err()
+                    .map_or_else(|| e.to_string(), |glob| glob.kind().to_string());
+                Some(SkippedPattern {
+                    source_file: relative(&entry.source_file),
+                    line: entry.line_number + 1,
+                    pattern: entry.pattern.clone(),
+                    reason,
+                })
+            })
+            .collect();
+
+        // Inline declarations take precedence and apply no rule
+        let declared = |source: InlineSource| -> Vec<PathBuf> {
+            cache
+                .files
+                .iter()
+                .filter(|file| file.inline.is_some_and(|inline| inline.source == source))
+                .map(|file| relative(&file.path))
+                .collect()
+        };
+        let inline_markers = declared(InlineSource::Marker);
+        let front_matter = declared(InlineSource::FrontMatter);
+
+        let unowned = cache
+            .files
+            .iter()
+            .filter(|file| {
+                file.owners
+                    .iter()
+                    .all(|owner| owner.owner_type == OwnerType::Unowned)
+            })
+            .count();
+
+        ParseReport {
+            codeowners_files,
+            rules: cache.entries.len(),
+            skipped_patterns,
+            inline_markers,
+            front_matter,
+            files: cache.files.len(),
+            unowned,
+            duration_ms: elapsed.as_millis(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{CodeownersEntry, FileEntry, InlineDeclaration, Owner};
+
+    #[test]
+    fn test_report() {
+        let entry = |line_number, pattern: &str| CodeownersEntry {
+            source_file: PathBuf::from("CODEOWNERS"),
+            line_number,
+            pattern: pattern.to_string(),
+            owners: vec![Owner {
+                identifier: "@team".to_string(),
+                owner_type: OwnerType::User,
+            }],
+            tags: Vec::new(),
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let file = |path: &str, owner_type, source: Option<InlineSource>| FileEntry {
+            path: PathBuf::from(path),
+            owners: vec![Owner {
+                identifier: "@team".to_string(),
+                owner_type,
+            }],
+            tags: Vec::new(),
+            generated: false,
+            inline: source.map(|source| InlineDeclaration { source, line: 1 }),
+        };
+        let cache = CodeownersCache::new(
+            [0; 32],
+            vec![entry(0, "*.rs"), entry(2, "src/[z-a]")],
+            vec![
+                file("lib.rs", OwnerType::User, None),
+                file("marked.py", OwnerType::User, Some(InlineSource::Marker)),
+                file("guide.md", OwnerType::User, Some(InlineSource::FrontMatter)),
+                file("README.md", OwnerType::Unowned, None),
+            ],
+            vec![vec![0], Vec::new()],
+        );
+
+        let report = ParseReport::new(
+            Path::new("."),
+            vec![
+                (PathBuf::from("CODEOWNERS"), None),
+                (
+                    PathBuf::from("docs/CODEOWNERS"),
+                    Some("permission denied".to_string()),
+                ),
+            ],
+            &cache,
+            MatchOptions::default(),
+            Duration::from_millis(12),
+        );
+        assert_eq!(report.rules, 2);
+        assert_eq!(report.codeowners_files[0].rules, 2);
+        assert_eq!(report.codeowners_files[1].rules, 0);
+        assert_eq!(report.skipped_patterns.len(), 1);
+        assert_eq!(report.skipped_patterns[0].line, 3);
+        assert_eq!(
+            report.skipped_patterns[0].reason,
+            "invalid range; 'z' > 'a'"
+        );
+        assert_eq!(report.inline_markers, vec![PathBuf::from("marked.py")]);
+        assert_eq!(report.front_matter, vec![PathBuf::from("guide.md")]);
+        assert_eq!((report.files, report.unowned), (4, 1));
+        assert_eq!(report.duration_ms, 12);
+    }
+}
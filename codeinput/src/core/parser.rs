@@ -1,89 +1,394 @@
 use crate::utils::error::Result;
+use crate::utils::warnings::{self, WarningKind};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::path::Path;
 
-use super::types::{CodeownersEntry, Owner, OwnerType, Tag};
+use super::bitbucket;
+use super::delegation::DELEGATE;
+use super::types::{CodeownersEntry, Dialect, EntrySpans, Owner, OwnerType, Section, Span, Tag};
 
 /// Parse CODEOWNERS
+///
+/// Invalid UTF-8 is replaced rather than rejected, so one bad byte does not discard
+/// every rule in the file.
 pub fn parse_codeowners(source_path: &Path) -> Result<Vec<CodeownersEntry>> {
-    let content = std::fs::read_to_string(source_path)?;
+    let content = read_codeowners(source_path)?;
+    parse_content(&content, source_path)
+}
 
-    content
-        .lines()
-        .enumerate()
-        .filter_map(|(line_num, line)| parse_line(line, line_num, source_path).transpose())
-        .collect()
+/// Parse a CODEOWNERS file with the owner syntax of `dialect`
+pub fn parse_codeowners_as(source_path: &Path, dialect: Dialect) -> Result<Vec<CodeownersEntry>> {
+    let mut entries = parse_codeowners(source_path)?;
+    if dialect == Dialect::Bitbucket {
+        bitbucket::apply_owner_syntax(&mut entries);
+    }
+    Ok(entries)
 }
 
-/// Parse a line of CODEOWNERS
-pub fn parse_line(
-    line: &str, line_num: usize, source_path: &Path,
-) -> Result<Option<CodeownersEntry>> {
-    // Trim the line and check for empty or comment lines
-    let trimmed = line.trim();
-    if trimmed.is_empty() || trimmed.starts_with('#') {
+/// Read a CODEOWNERS file, replacing invalid UTF-8
+pub(crate) fn read_codeowners(source_path: &Path) -> Result<String> {
+    let bytes = std::fs::read(source_path)?;
+    match String::from_utf8(bytes) {
+        Ok(content) => Ok(content),
+        Err(e) => {
+            warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "{} is not valid UTF-8; invalid bytes are replaced",
+                    source_path.display()
+                ),
+            );
+            Ok(String::from_utf8_lossy(e.as_bytes()).into_owned())
+        }
+    }
+}
+
+/// Parse the contents of a CODEOWNERS file read from `source_path`
+///
+/// Annotations in the comment lines directly above a rule apply to it along with those
+/// in its own comment, which take precedence. Rules after a GitLab section header are in
+/// that section, and those naming no owners get the section's default owners. Negations,
+/// `!pattern`, are kept as rules; see [`CodeownersEntry::is_negation`].
+pub fn parse_content(content: &str, source_path: &Path) -> Result<Vec<CodeownersEntry>> {
+    let mut entries = Vec::new();
+    let mut annotations = BTreeMap::new();
+    let mut section: Option<Section> = None;
+    for (line_num, line) in content.lines().enumerate() {
+        if let Some(header) = parse_section(line)? {
+            section = Some(header);
+            annotations.clear();
+            continue;
+        }
+        match parse_line(line, line_num, source_path)? {
+            Some(mut entry) => {
+                let own = std::mem::replace(&mut entry.metadata, std::mem::take(&mut annotations));
+                entry.metadata.extend(own);
+                if let Some(section) = &section {
+                    if entry.owners.is_empty() && !entry.is_negation() {
+                        entry.owners = section.default_owners.clone();
+                    }
+                    entry.section = Some(section.clone());
+                }
+                entries.push(entry);
+            }
+            None => match tokenize(line).next() {
+                Some((Token::Hash(_), span)) => annotations
+                    .extend(parse_annotations(&line[span.start + 1..]).unwrap_or_default()),
+                // A blank line ends the comment block
+                _ => annotations.clear(),
+            },
+        }
+    }
+    Ok(entries)
+}
+
+/// Annotations of a comment, given without its leading `#`
+///
+/// A comment made up only of `key:value` words, such as `slack:#team-chan escalation:P1`,
+/// is an annotation; keys are letters, digits, `-`, `_` and `.`, and values are not
+/// empty. Any other comment is plain text and yields `None`.
+pub fn parse_annotations(comment: &str) -> Option<BTreeMap<String, String>> {
+    let mut annotations = BTreeMap::new();
+    for word in comment.split(is_separator).filter(|word| !word.is_empty()) {
+        let (key, value) = word.split_once(':')?;
+        let valid_key = !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_key || value.is_empty() {
+            return None;
+        }
+        annotations.insert(key.to_string(), value.to_string());
+    }
+    (!annotations.is_empty()).then_some(annotations)
+}
+
+/// Parse a GitLab section header such as `^[Docs][2] @docs-team`; other lines are `None`
+///
+/// A header is `[name]`, preceded by `^` if the section is optional and followed by `[N]`
+/// if it needs N approvals, then the default owners and an optional comment. `[ab].rs` is
+/// a pattern, not a header, since more than whitespace follows its `]`.
+pub fn parse_section(line: &str) -> Result<Option<Section>> {
+    let line = line.trim_start_matches(is_separator);
+    let (optional, rest) = match line.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let Some((name, mut rest)) = rest.strip_prefix('[').and_then(|rest| rest.split_once(']'))
+    else {
+        return Ok(None);
+    };
+    if name.trim().is_empty() {
         return Ok(None);
     }
 
-    // Split the line by whitespace into a series of tokens
-    let tokens: Vec<&str> = trimmed.split_whitespace().collect();
-    if tokens.is_empty() {
+    let mut approvals = None;
+    if let Some(count) = rest.strip_prefix('[') {
+        let Some((Ok(count), after)) = count
+            .split_once(']')
+            .map(|(count, after)| (count.parse::<u32>(), after))
+        else {
+            return Ok(None);
+        };
+        approvals = Some(count);
+        rest = after;
+    }
+    if rest.chars().next().is_some_and(|c| !is_separator(c)) {
         return Ok(None);
     }
 
-    // The first token is the pattern
-    let pattern = tokens[0].to_string();
+    let default_owners = tokenize(rest)
+        .map_while(|(token, _)| match token {
+            Token::Word(word) => Some(word),
+            Token::Hash(_) => None,
+        })
+        .map(parse_owner)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(Section {
+        name: name.trim().to_string(),
+        optional,
+        approvals,
+        default_owners,
+    }))
+}
 
-    let mut owners: Vec<Owner> = Vec::new();
-    let mut tags: Vec<Tag> = Vec::new();
+/// A whitespace-separated token of a CODEOWNERS line
+///
+/// The grammar of a line is:
+///
+/// ```text
+/// line    = [ rule ] [ comment ]
+/// rule    = pattern { owner } { tag }
+/// pattern = word
+/// owner   = word
+/// tag     = "#" word, followed by another tag or the end of the line
+/// comment = "#" and everything after it
+/// word    = characters up to unescaped, unquoted whitespace, not starting with "#"
+/// ```
+///
+/// A backslash escapes the next character, so `docs/My\ File.md` is a single word, and
+/// whitespace between double quotes does not end a word, so neither does
+/// `"docs/My File.md"`. A quote with no closing quote after it is a plain character. A
+/// byte order mark counts as whitespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    /// A word that does not start with `#`
+    Word(&'a str),
+    /// A word starting with `#`, without the `#`; a tag or the start of a comment
+    Hash(&'a str),
+}
 
-    let mut i = 1; // Start after the pattern
+/// Iterator over the tokens of a line and their spans, see [`tokenize`]
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    rest: &'a str,
+    offset: usize,
+}
 
-    // Collect owners until a token starts with '#'
-    while i < tokens.len() && !tokens[i].starts_with('#') {
-        owners.push(parse_owner(tokens[i])?);
-        i += 1;
-    }
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (Token<'a>, Span);
 
-    // Collect tags with lookahead to check for comments
-    while i < tokens.len() {
-        let token = tokens[i];
-        if token.starts_with('#') {
-            if token == "#" {
-                // Comment starts, break
-                break;
-            } else {
-                // Check if the next token is not a tag (doesn't start with '#')
-                let next_is_non_tag = i + 1 < tokens.len() && !tokens[i + 1].starts_with('#');
-                if next_is_non_tag {
-                    // This token is part of the comment, break
-                    break;
+    fn next(&mut self) -> Option<(Token<'a>, Span)> {
+        let start = self.rest.trim_start_matches(is_separator);
+        self.offset += self.rest.len() - start.len();
+        if start.is_empty() {
+            self.rest = start;
+            return None;
+        }
+
+        let mut escaped = false;
+        let mut quoted = false;
+        let end = start
+            .char_indices()
+            .find(|&(index, c)| {
+                if escaped {
+                    escaped = false;
+                    false
+                } else if c == '\\' {
+                    escaped = true;
+                    false
+                } else if c == '"' {
+                    quoted = !quoted && closing_quote(&start[index + 1..]).is_some();
+                    false
+                } else {
+                    !quoted && is_separator(c)
                 }
-                tags.push(Tag(token[1..].to_string()));
-                i += 1;
+            })
+            .map_or(start.len(), |(index, _)| index);
+
+        let (word, rest) = start.split_at(end);
+        let span = Span::new(self.offset, self.offset + end);
+        self.rest = rest;
+        self.offset += end;
+
+        let token = match word.strip_prefix('#') {
+            Some(name) => Token::Hash(name),
+            None => Token::Word(word),
+        };
+        Some((token, span))
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || c == '\u{feff}'
+}
+
+/// Byte index of the first unescaped `"` in `text`
+fn closing_quote(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    text.char_indices()
+        .find(|&(_, c)| {
+            let found = !escaped && c == '"';
+            escaped = !escaped && c == '\\';
+            found
+        })
+        .map(|(index, _)| index)
+}
+
+/// A pattern word with its quotes replaced by escapes, so `"My Docs/"` reads `My\ Docs/`
+///
+/// Patterns are kept in their escaped form, which the matchers read and
+/// [`format_line`] writes back.
+pub fn unquote_pattern(word: &str) -> Cow<'_, str> {
+    if closing_quote(word).is_none() {
+        return Cow::Borrowed(word);
+    }
+    let mut pattern = String::with_capacity(word.len() + 4);
+    let mut escaped = false;
+    let mut quoted = false;
+    for (index, c) in word.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            if quoted || closing_quote(&word[index + 1..]).is_some() {
+                quoted = !quoted;
+                continue;
             }
-        } else {
-            // Non-tag, part of comment
+            pattern.push('\\');
+        } else if quoted && (is_separator(c) || (c == '#' && pattern.is_empty())) {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    Cow::Owned(pattern)
+}
+
+/// Split a CODEOWNERS line into tokens, each with its byte span in `line`
+pub fn tokenize(line: &str) -> Tokens<'_> {
+    Tokens {
+        rest: line,
+        offset: 0,
+    }
+}
+
+/// Parse a line of CODEOWNERS
+///
+/// `DELEGATE` lines are not rules, see [`super::delegation`], and neither are GitLab
+/// section headers, see [`parse_section`].
+pub fn parse_line(
+    line: &str, line_num: usize, source_path: &Path,
+) -> Result<Option<CodeownersEntry>> {
+    if parse_section(line)?.is_some() {
+        return Ok(None);
+    }
+    let tokens: Vec<(Token, Span)> = tokenize(line).collect();
+
+    // Empty and comment lines have no pattern
+    let (pattern, pattern_span, rest) = match tokens.split_first() {
+        Some(((Token::Word(pattern), span), rest)) if *pattern != DELEGATE => {
+            (*pattern, *span, rest)
+        }
+        _ => return Ok(None),
+    };
+
+    // Owners run until the first `#` token
+    let mut owners = Vec::new();
+    let mut owner_spans = Vec::new();
+    let mut remaining = rest;
+    while let [(Token::Word(owner), span), next @ ..] = remaining {
+        owners.push(parse_owner(owner)?);
+        owner_spans.push(*span);
+        remaining = next;
+    }
+
+    // `#name` is a tag when followed by another `#` token or the end of the line;
+    // a bare `#` or `#word` followed by a plain word starts a comment
+    let mut tags = Vec::new();
+    let mut tag_spans = Vec::new();
+    while let [(Token::Hash(name), span), next @ ..] = remaining {
+        if name.is_empty() || matches!(next.first(), Some((Token::Word(_), _))) {
             break;
         }
+        tags.push(Tag(name.to_string()));
+        tag_spans.push(*span);
+        remaining = next;
     }
 
+    // Anything left is the comment
+    let metadata = match remaining.first() {
+        Some((_, span)) => parse_annotations(&line[span.start + 1..]).unwrap_or_default(),
+        None => BTreeMap::new(),
+    };
+
     Ok(Some(CodeownersEntry {
         source_file: source_path.to_path_buf(),
         line_number: line_num,
-        pattern,
+        pattern: unquote_pattern(pattern).into_owned(),
         owners,
         tags,
+        spans: EntrySpans {
+            pattern: pattern_span,
+            owners: owner_spans,
+            tags: tag_spans,
+        },
+        metadata,
+        section: None,
     }))
 }
 
+/// Escape a literal path for use as a pattern, so whitespace, quotes and a leading `#`
+/// survive tokenizing
+pub fn escape_pattern(path: &str) -> String {
+    let mut pattern = String::with_capacity(path.len());
+    for (index, c) in path.chars().enumerate() {
+        if is_separator(c) || matches!(c, '\\' | '"') || (index == 0 && c == '#') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
+
+/// Format an entry as a CODEOWNERS line that parses back to the same rule
+pub fn format_line(entry: &CodeownersEntry) -> String {
+    let mut line = entry.pattern.clone();
+    for owner in &entry.owners {
+        line.push(' ');
+        line.push_str(&owner.identifier);
+    }
+    for tag in &entry.tags {
+        line.push_str(" #");
+        line.push_str(&tag.0);
+    }
+    if !entry.metadata.is_empty() {
+        line.push_str(" #");
+        for (key, value) in &entry.metadata {
+            line.push_str(&format!(" {}:{}", key, value));
+        }
+    }
+    line
+}
+
 /// Parse an owner string into an Owner struct
 pub fn parse_owner(owner_str: &str) -> Result<Owner> {
     let identifier = owner_str.to_string();
     let owner_type = if identifier.eq_ignore_ascii_case("NOOWNER") {
         OwnerType::Unowned
-    } else if owner_str.starts_with('@') {
-        let parts: Vec<&str> = owner_str[1..].split('/').collect();
-        if parts.len() == 2 {
+    } else if let Some(handle) = owner_str.strip_prefix('@') {
+        if handle.split('/').count() == 2 {
             OwnerType::Team
         } else {
             OwnerType::User
@@ -363,4 +668,292 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens: Vec<Token> = tokenize("  src/My\\ File.md\t@a #tag # note ")
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("src/My\\ File.md"),
+                Token::Word("@a"),
+                Token::Hash("tag"),
+                Token::Hash(""),
+                Token::Word("note"),
+            ]
+        );
+        assert_eq!(tokenize(" \t ").count(), 0);
+        assert_eq!(
+            tokenize("\\#literal").next(),
+            Some((Token::Word("\\#literal"), Span::new(0, 9)))
+        );
+    }
+
+    #[test]
+    fn test_parse_line_escaped_whitespace_in_pattern() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+        let entry = parse_line("docs/My\\ File.md @docs", 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern, "docs/My\\ File.md");
+        assert_eq!(entry.owners.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_line_quoted_pattern() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+        let entry = parse_line("\"docs/release notes/\" @docs #docs", 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern, "docs/release\\ notes/");
+        assert_eq!(entry.spans.pattern, Span::new(0, 21));
+        assert_eq!(entry.owners[0].identifier, "@docs");
+        assert_eq!(entry.tags, [Tag("docs".to_string())]);
+
+        assert_eq!(unquote_pattern("src/\"My #1\"/*.md"), "src/My\\ #1/*.md");
+        assert_eq!(unquote_pattern("\"#notes\""), "\\#notes");
+        assert_eq!(unquote_pattern("say\\\"hi\\\""), "say\\\"hi\\\"");
+        // An unmatched quote is a plain character
+        let entry = parse_line("a\"b @x", 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern, "a\\\"b");
+        assert_eq!(entry.owners[0].identifier, "@x");
+
+        let path = "docs/My \"draft\".md";
+        let entry = parse_line(&format!("{} @x", escape_pattern(path)), 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern, escape_pattern(path));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_content_strips_bom() -> Result<()> {
+        let entries = parse_content("\u{feff}* @all\r\n*.rs @rust\r\n", Path::new("CODEOWNERS"))?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].pattern, "*");
+        assert_eq!(entries[1].owners[0].identifier, "@rust");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_codeowners_invalid_utf8() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("CODEOWNERS");
+        std::fs::write(&path, b"*.rs @rust\n\xff\xfe.md @docs\n")?;
+
+        let entries = parse_codeowners(&path)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].pattern, "*.rs");
+        assert_eq!(entries[1].pattern, "\u{fffd}\u{fffd}.md");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_line_very_long_line() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+
+        let pattern = "a".repeat(10 * 1024 * 1024);
+        let entry = parse_line(&format!("{} @owner #tag", pattern), 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern.len(), pattern.len());
+        assert_eq!(entry.tags.len(), 1);
+
+        let owners = "@o ".repeat(3 * 1024 * 1024);
+        let entry = parse_line(&format!("* {}", owners), 0, source_path)?.unwrap();
+        assert_eq!(entry.owners.len(), 3 * 1024 * 1024);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_line_spans() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+        let line = "\u{feff}/src/é/  @org/team\tdev@example.com #core #api # note";
+        let entry = parse_line(line, 0, source_path)?.unwrap();
+
+        assert_eq!(entry.spans.pattern.slice(line), Some("/src/é/"));
+        let owners: Vec<_> = entry.spans.owners.iter().map(|s| s.slice(line)).collect();
+        assert_eq!(owners, [Some("@org/team"), Some("dev@example.com")]);
+        let tags: Vec<_> = entry.spans.tags.iter().map(|s| s.slice(line)).collect();
+        assert_eq!(tags, [Some("#core"), Some("#api")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_escape_pattern() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+        let pattern = escape_pattern("#docs/My File.md");
+        assert_eq!(pattern, "\\#docs/My\\ File.md");
+
+        let entry = parse_line(&format!("{} @docs", pattern), 0, source_path)?.unwrap();
+        assert_eq!(entry.pattern, pattern);
+        assert_eq!(entry.owners.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_line_round_trip() -> Result<()> {
+        let source_path = Path::new("/test/CODEOWNERS");
+        let entry = parse_line(
+            "  /src/**   @org/team  a@b.c #core #api # comment",
+            3,
+            source_path,
+        )?
+        .unwrap();
+        assert_eq!(format_line(&entry), "/src/** @org/team a@b.c #core #api");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_negation() -> Result<()> {
+        let content = "[Generated] @org/build\n!generated/**\n\\!important.txt @alice\n";
+        let entries = parse_content(content, Path::new("CODEOWNERS"))?;
+        assert!(entries[0].is_negation());
+        assert_eq!(entries[0].positive_pattern(), "generated/**");
+        // Negations take no default owners
+        assert!(entries[0].owners.is_empty());
+        // An escaped `!` is part of the pattern
+        assert!(!entries[1].is_negation());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_sections() -> Result<()> {
+        let content = "\
+*.rs @rust
+[Documentation] @org/docs @alice
+*.md
+README.md @bob
+^[Frontend Team][2]
+*.js @web
+[ab].rs @brackets
+[]
+";
+        let entries = parse_content(content, Path::new("CODEOWNERS"))?;
+        let patterns: Vec<&str> = entries.iter().map(|e| e.pattern.as_str()).collect();
+        assert_eq!(
+            patterns,
+            ["*.rs", "*.md", "README.md", "*.js", "[ab].rs", "[]"]
+        );
+
+        assert!(entries[0].section.is_none());
+        let docs = entries[1].section.as_ref().unwrap();
+        assert_eq!(docs.name, "Documentation");
+        assert!(!docs.optional);
+        assert_eq!(docs.approvals, None);
+        // Rules without owners get the section's default owners
+        let owners = |index: usize| -> Vec<&str> {
+            entries[index]
+                .owners
+                .iter()
+                .map(|o| o.identifier.as_str())
+                .collect()
+        };
+        assert_eq!(owners(1), ["@org/docs", "@alice"]);
+        assert_eq!(owners(2), ["@bob"]);
+
+        let frontend = entries[3].section.as_ref().unwrap();
+        assert_eq!(frontend.name, "Frontend Team");
+        assert!(frontend.optional);
+        assert_eq!(frontend.approvals, Some(2));
+        assert!(frontend.default_owners.is_empty());
+        assert_eq!(entries[4].section, entries[3].section);
+
+        assert!(parse_section("[Docs][two] @a")?.is_none());
+        assert!(parse_line("[Docs] @a", 0, Path::new("CODEOWNERS"))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_annotations() -> Result<()> {
+        let content = "\
+# slack:#payments-chan escalation:P1
+# Payments team, ask in the channel first
+/billing/ @org/payments # escalation:P0 runbook:https://wiki/pay
+
+# plain:comment followed by text
+/docs/ @docs # see http://example.com
+*.rs @rust #lang # oncall:rust-rota
+";
+        let entries = parse_content(content, Path::new("CODEOWNERS"))?;
+        let metadata = |index: usize| -> Vec<(&str, &str)> {
+            entries[index]
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect()
+        };
+
+        assert_eq!(
+            metadata(0),
+            [
+                ("escalation", "P0"),
+                ("runbook", "https://wiki/pay"),
+                ("slack", "#payments-chan")
+            ]
+        );
+        assert!(metadata(1).is_empty());
+        assert_eq!(metadata(2), [("oncall", "rust-rota")]);
+        assert_eq!(entries[2].tags, [Tag("lang".to_string())]);
+        assert_eq!(
+            format_line(&entries[2]),
+            "*.rs @rust #lang # oncall:rust-rota"
+        );
+
+        assert_eq!(parse_annotations(" key: "), None);
+        assert_eq!(parse_annotations("a b:c"), None);
+        Ok(())
+    }
+
+    mod properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        fn token() -> impl Strategy<Value = String> {
+            prop_oneof![
+                "[a-z*/.]{1,8}",
+                "@[a-z]{1,5}(/[a-z]{1,5})?",
+                "#[a-z]{0,5}",
+                "[a-z]{1,3}\\\\ [a-z]{1,3}",
+                "\\PC{1,4}",
+            ]
+        }
+
+        fn codeowners_line() -> impl Strategy<Value = String> {
+            (
+                prop::collection::vec(token(), 0..8),
+                prop::sample::select(vec![" ", "  ", "\t"]),
+            )
+                .prop_map(|(tokens, separator)| tokens.join(separator))
+        }
+
+        proptest! {
+            #[test]
+            fn arbitrary_lines_never_panic(line in any::<String>()) {
+                let _ = parse_line(&line, 0, Path::new("CODEOWNERS"));
+            }
+
+            #[test]
+            fn arbitrary_bytes_never_panic(bytes in prop::collection::vec(any::<u8>(), 0..512)) {
+                let content = String::from_utf8_lossy(&bytes);
+                let _ = parse_content(&content, Path::new("CODEOWNERS"));
+            }
+
+            #[test]
+            fn formatted_entries_reparse_equal(line in prop_oneof![codeowners_line(), any::<String>()]) {
+                let source_path = Path::new("CODEOWNERS");
+                if let Some(entry) = parse_line(&line, 0, source_path).unwrap() {
+                    let reparsed = parse_line(&format_line(&entry), 0, source_path)
+                        .unwrap()
+                        .expect("formatted entry parses");
+                    prop_assert_eq!(&reparsed.pattern, &entry.pattern);
+                    prop_assert_eq!(&reparsed.owners, &entry.owners);
+                    prop_assert_eq!(&reparsed.tags, &entry.tags);
+                    prop_assert_eq!(&reparsed.metadata, &entry.metadata);
+                }
+            }
+        }
+    }
 }
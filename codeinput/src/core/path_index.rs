@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "utoipa")]
+use utoipa::ToSchema;
+
+/// Prefix index over cached file paths
+///
+/// A trie keyed by path component that maps each path to its position in
+/// `CodeownersCache::files`, so exact lookups and directory listings cost O(prefix)
+/// rather than a scan of every file. `.` components are ignored, so `./src/main.rs`
+/// and `src/main.rs` are the same key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct PathIndex {
+    nodes: Vec<PathIndexNode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+struct PathIndexNode {
+    children: BTreeMap<String, usize>,
+    file: Option<usize>,
+}
+
+impl Default for PathIndex {
+    fn default() -> Self {
+        PathIndex {
+            nodes: vec![PathIndexNode::default()],
+        }
+    }
+}
+
+impl PathIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build an index where each path maps to its position in `paths`
+    pub fn from_paths<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Self {
+        let mut index = PathIndex::new();
+        for (position, path) in paths.into_iter().enumerate() {
+            index.insert(path, position);
+        }
+        index
+    }
+
+    /// Record `path` as the file at `file_index`
+    pub fn insert(&mut self, path: &Path, file_index: usize) {
+        if self.nodes.is_empty() {
+            self.nodes.push(PathIndexNode::default());
+        }
+
+        let mut node = 0;
+        for component in components(path) {
+            node = match self.nodes[node].children.get(&component) {
+                Some(child) => *child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(PathIndexNode::default());
+                    self.nodes[node].children.insert(component, child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].file = Some(file_index);
+    }
+
+    /// Position of the file at exactly `path`
+    pub fn get(&self, path: &Path) -> Option<usize> {
+        self.nodes[self.find(path)?].file
+    }
+
+    /// Positions of all files at or below `prefix`, in ascending order
+    pub fn prefixed(&self, prefix: &Path) -> Vec<usize> {
+        let Some(start) = self.find(prefix) else {
+            return Vec::new();
+        };
+
+        let mut files = Vec::new();
+        let mut stack = vec![start];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            files.extend(node.file);
+            stack.extend(node.children.values());
+        }
+        files.sort_unstable();
+        files
+    }
+
+    /// Whether the index holds no paths
+    pub fn is_empty(&self) -> bool {
+        self.nodes.iter().all(|node| node.file.is_none())
+    }
+
+    fn find(&self, path: &Path) -> Option<usize> {
+        let mut node = 0;
+        self.nodes.first()?;
+        for component in components(path) {
+            node = *self.nodes[node].children.get(&component)?;
+        }
+        Some(node)
+    }
+}
+
+fn components(path: &Path) -> impl Iterator<Item = String> + '_ {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn sample_index() -> PathIndex {
+        let paths = [
+            "./src/main.rs",
+            "./src/api/handler.rs",
+            "./src/api/v1/routes.rs",
+            "./src/apis.rs",
+            "./README.md",
+        ]
+        .map(PathBuf::from);
+        PathIndex::from_paths(paths.iter().map(PathBuf::as_path))
+    }
+
+    #[test]
+    fn test_get_exact_path() {
+        let index = sample_index();
+        assert_eq!(index.get(Path::new("./src/api/handler.rs")), Some(1));
+        assert_eq!(index.get(Path::new("src/api/handler.rs")), Some(1));
+        assert_eq!(index.get(Path::new("README.md")), Some(4));
+        assert_eq!(index.get(Path::new("src/api")), None);
+        assert_eq!(index.get(Path::new("src/missing.rs")), None);
+    }
+
+    #[test]
+    fn test_prefixed_matches_whole_components() {
+        let index = sample_index();
+        assert_eq!(index.prefixed(Path::new("src/api/")), vec![1, 2]);
+        assert_eq!(index.prefixed(Path::new("./src")), vec![0, 1, 2, 3]);
+        assert_eq!(index.prefixed(Path::new("src/main.rs")), vec![0]);
+        assert!(index.prefixed(Path::new("src/ap")).is_empty());
+        assert_eq!(index.prefixed(Path::new(".")), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_absolute_paths() {
+        let mut index = PathIndex::new();
+        assert!(index.is_empty());
+        index.insert(Path::new("/repo/src/lib.rs"), 0);
+        assert!(!index.is_empty());
+        assert_eq!(index.get(Path::new("/repo/src/lib.rs")), Some(0));
+        assert!(index.prefixed(Path::new("repo")).is_empty());
+        assert_eq!(index.prefixed(Path::new("/repo")), vec![0]);
+    }
+}
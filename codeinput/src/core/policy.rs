@@ -0,0 +1,217 @@
+//! Ownership rules a repository holds itself to, checked by `codeowners gate`
+//!
+//! The policy file is TOML at the repository root (the `policy_file` setting):
+//!
+//! ```toml
+//! # Every owned file needs a team among its owners
+//! require_team = true
+//! # No file may have more owners than this
+//! max_owners = 3
+//! # Owners that may no longer be named, e.g. people who left
+//! forbidden_owners = ["@alice", "former@example.com"]
+//! # Annotations every rule must carry, e.g. `# slack:#payments escalation:P1`
+//! required_annotations = ["slack", "escalation"]
+//! ```
+//!
+//! Every setting is optional. Unowned files are left to the coverage checks.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::context::Context;
+use super::types::{CodeownersCache, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Ownership requirements for the files of a repository
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Policy {
+    pub require_team: bool,
+    pub max_owners: Option<usize>,
+    pub forbidden_owners: Vec<String>,
+    pub required_annotations: Vec<String>,
+}
+
+/// A file breaking the policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Policy {
+    /// Parse a policy; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<Policy> {
+        toml::from_str(contents).map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse policy {}: {}",
+                source.display(),
+                e
+            ))
+        })
+    }
+
+    /// Load a policy file
+    pub fn load(path: &Path) -> Result<Policy> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::new(&format!("Failed to read policy {}: {}", path.display(), e)))?;
+        Policy::parse(&contents, path)
+    }
+
+    /// Load the policy configured for `repo`, if it has one
+    ///
+    /// The `policy_file` setting is resolved relative to the repository.
+    pub fn for_repo(ctx: &Context, repo: &Path) -> Result<Option<Policy>> {
+        let path = repo.join(&ctx.config.policy_file);
+        if !path.exists() {
+            return Ok(None);
+        }
+        Policy::load(&path).map(Some)
+    }
+
+    /// Files of `cache` breaking the policy, in cache order, then rules missing required
+    /// annotations, reported against their CODEOWNERS file
+    pub fn violations(&self, cache: &CodeownersCache) -> Vec<Violation> {
+        let forbidden: Vec<String> = self
+            .forbidden_owners
+            .iter()
+            .map(|owner| owner.to_lowercase())
+            .collect();
+
+        let mut violations = Vec::new();
+        for file in &cache.files {
+            let owners: Vec<_> = file
+                .owners
+                .iter()
+                .filter(|owner| owner.owner_type != OwnerType::Unowned)
+                .collect();
+            if owners.is_empty() {
+                continue;
+            }
+            let mut violation = |message: String| {
+                violations.push(Violation {
+                    path: file.path.clone(),
+                    message,
+                })
+            };
+
+            if self.require_team && !owners.iter().any(|o| o.owner_type == OwnerType::Team) {
+                violation("no team owner".to_string());
+            }
+            if let Some(max) = self.max_owners {
+                if owners.len() > max {
+                    violation(format!("{} owners, at most {} allowed", owners.len(), max));
+                }
+            }
+            for owner in &owners {
+                if forbidden.contains(&owner.identifier.to_lowercase()) {
+                    violation(format!("owned by forbidden owner {}", owner.identifier));
+                }
+            }
+        }
+
+        for entry in &cache.entries {
+            for key in &self.required_annotations {
+                if !entry.metadata.contains_key(key) {
+                    violations.push(Violation {
+                        path: entry.source_file.clone(),
+                        message: format!(
+                            "rule {} on line {} has no {} annotation",
+                            entry.pattern,
+                            entry.line_number + 1,
+                            key
+                        ),
+                    });
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use crate::core::types::{FileEntry, Owner};
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    #[test]
+    fn test_violations() {
+        let files = vec![
+            FileEntry {
+                path: PathBuf::from("a.rs"),
+                owners: vec![owner("@org/core", OwnerType::Team)],
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            },
+            FileEntry {
+                path: PathBuf::from("b.rs"),
+                owners: vec![
+                    owner("@Alice", OwnerType::User),
+                    owner("@bob", OwnerType::User),
+                ],
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            },
+            FileEntry {
+                path: PathBuf::from("c.txt"),
+                owners: vec![owner("NOBODY", OwnerType::Unowned)],
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            },
+        ];
+        let rules = parse_content(
+            "# slack:#core\n*.rs @org/core\n*.txt @bob # slack:#bob escalation:P1\n",
+            Path::new("CODEOWNERS"),
+        )
+        .unwrap();
+        let cache = CodeownersCache::new([0; 32], rules, files, vec![Vec::new(); 2]);
+        let policy = Policy::parse(
+            "require_team = true\nmax_owners = 1\nforbidden_owners = [\"@alice\"]\n\
+             required_annotations = [\"slack\", \"escalation\"]\n",
+            Path::new("policy.toml"),
+        )
+        .unwrap();
+
+        let messages: Vec<(String, String)> = policy
+            .violations(&cache)
+            .into_iter()
+            .map(|v| (v.path.display().to_string(), v.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                ("b.rs".to_string(), "no team owner".to_string()),
+                (
+                    "b.rs".to_string(),
+                    "2 owners, at most 1 allowed".to_string()
+                ),
+                (
+                    "b.rs".to_string(),
+                    "owned by forbidden owner @Alice".to_string()
+                ),
+                (
+                    "CODEOWNERS".to_string(),
+                    "rule *.rs on line 2 has no escalation annotation".to_string()
+                ),
+            ]
+        );
+        assert!(Policy::default().violations(&cache).is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_settings() {
+        assert!(Policy::parse("require_teams = true", Path::new("policy.toml")).is_err());
+    }
+}
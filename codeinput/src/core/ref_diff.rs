@@ -0,0 +1,343 @@
+//! Ownership at two git refs, compared by `codeowners diff`
+//!
+//! Files and CODEOWNERS files are read from the commits' trees instead of the working
+//! directory, so a branch can be compared with another without checking either out.
+//! Inline declarations are read from the committed files too. Generated files are not
+//! flagged, as that needs the files on disk.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use super::bitbucket;
+use super::cache_diff::{diff, FileChange};
+use super::churn::{compare_rules, OwnershipChangeKind};
+use super::inline_parser::detect_inline_codeowners_in;
+use super::matcher::{Matcher, MatcherStrategy};
+use super::owner_resolver::dedup_owners;
+use super::parser::parse_content;
+use super::resolver::find_applied_entries;
+use super::tag_resolver::dedup_tags;
+use super::types::{
+    CodeownersCache, CodeownersEntry, Dialect, FileEntry, InlineDeclaration, MatchOptions, Owner,
+    PrecedencePolicy,
+};
+use crate::utils::error::{Error, Result};
+
+/// A rule of a CODEOWNERS file added, removed or given other owners between the refs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleChange {
+    /// CODEOWNERS file, relative to the repository root
+    pub source_file: PathBuf,
+    pub pattern: String,
+    pub kind: OwnershipChangeKind,
+    pub old_owners: Vec<String>,
+    pub new_owners: Vec<String>,
+}
+
+/// How ownership changed from one ref to another
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RefDiff {
+    /// By CODEOWNERS file, then pattern
+    pub rules: Vec<RuleChange>,
+    /// Files whose owners or tags differ, or that exist at one ref only, sorted by path
+    pub files: Vec<FileChange>,
+}
+
+/// The files and CODEOWNERS rules of a commit
+struct Snapshot {
+    /// Paths relative to the repository root with the content of each file
+    files: Vec<(PathBuf, git2::Oid)>,
+    /// Rules by CODEOWNERS file, relative to the repository root
+    rules: BTreeMap<PathBuf, Vec<CodeownersEntry>>,
+}
+
+/// Compare the ownership of every file of `repo` at `from` with that at `to`
+///
+/// Both refs are resolved with the matching options and precedence policy of the
+/// working directory's configuration. Paths are reported relative to the repository
+/// root.
+pub fn diff_refs(
+    repo: &Path, from: &str, to: &str, options: MatchOptions, policy: PrecedencePolicy,
+) -> Result<RefDiff> {
+    let repository = Repository::open(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let old = snapshot(&repository, from, options.dialect)?;
+    let new = snapshot(&repository, to, options.dialect)?;
+
+    let mut rules = Vec::new();
+    let sources: BTreeSet<&PathBuf> = old.rules.keys().chain(new.rules.keys()).collect();
+    for source in sources {
+        let patterns = |snapshot: &Snapshot| -> BTreeMap<String, Vec<Owner>> {
+            snapshot
+                .rules
+                .get(source)
+                .into_iter()
+                .flatten()
+                .map(|entry| (entry.pattern.clone(), entry.owners.clone()))
+                .collect()
+        };
+        for (pattern, kind, old_owners, new_owners) in
+            compare_rules(&patterns(&old), &patterns(&new))
+        {
+            rules.push(RuleChange {
+                source_file: source.clone(),
+                pattern,
+                kind,
+                old_owners,
+                new_owners,
+            });
+        }
+    }
+
+    let old = resolve(&repository, &old, options, policy)?;
+    let new = resolve(&repository, &new, options, policy)?;
+    Ok(RefDiff {
+        rules,
+        files: diff(&old, &new),
+    })
+}
+
+/// Owners and tags of `paths`, relative to the repository root, as they were at
+/// `reference`
+///
+/// The files are resolved against the CODEOWNERS files of the commit's tree, as
+/// [`diff_refs`] resolves them, so files deleted since keep the owners they had. Paths
+/// the commit does not have are left out.
+pub fn resolve_at(
+    repo: &Path, reference: &str, paths: &[PathBuf], options: MatchOptions,
+    policy: PrecedencePolicy,
+) -> Result<Vec<FileEntry>> {
+    let repository = Repository::open(repo)
+        .map_err(|e| Error::with_source("Failed to open repo", Box::new(e)))?;
+    let mut snapshot = snapshot(&repository, reference, options.dialect)?;
+    let wanted: BTreeSet<&PathBuf> = paths.iter().collect();
+    snapshot.files.retain(|(path, _)| wanted.contains(path));
+    Ok(resolve(&repository, &snapshot, options, policy)?.files)
+}
+
+/// Read the files and CODEOWNERS rules of the commit `reference` points to
+fn snapshot(repository: &Repository, reference: &str, dialect: Dialect) -> Result<Snapshot> {
+    let tree = repository
+        .revparse_single(reference)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| Error::new(&format!("Unknown revision {}: {}", reference, e.message())))?;
+    let blobs = tree_blobs(&tree)?;
+
+    let is_codeowners = |path: &Path| path.file_name().is_some_and(|name| name == "CODEOWNERS");
+    let sources: Vec<&PathBuf> = match dialect {
+        Dialect::GitHub => blobs
+            .iter()
+            .map(|(path, _)| path)
+            .filter(|path| is_codeowners(path))
+            .collect(),
+        Dialect::Bitbucket => bitbucket::CODEOWNERS_LOCATIONS
+            .iter()
+            .find_map(|location| blobs.iter().find(|(path, _)| path == Path::new(location)))
+            .map(|(path, _)| path)
+            .into_iter()
+            .collect(),
+    };
+
+    let mut rules = BTreeMap::new();
+    for source in sources {
+        let (_, id) = blobs.iter().find(|(path, _)| path == source).unwrap();
+        let blob = repository.find_blob(*id)?;
+        let content = String::from_utf8_lossy(blob.content());
+        let mut entries = parse_content(&content, source)?;
+        if dialect == Dialect::Bitbucket {
+            bitbucket::apply_owner_syntax(&mut entries);
+        }
+        rules.insert(source.clone(), entries);
+    }
+
+    let files = blobs
+        .into_iter()
+        .filter(|(path, _)| !is_codeowners(path))
+        .collect();
+    Ok(Snapshot { files, rules })
+}
+
+/// Every blob of `tree` with its path, in tree order; submodules are skipped
+fn tree_blobs(tree: &Tree) -> Result<Vec<(PathBuf, git2::Oid)>> {
+    let mut blobs = Vec::new();
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if entry.kind() == Some(ObjectType::Blob) {
+            if let Some(name) = entry.name() {
+                blobs.push((Path::new(root).join(name), entry.id()));
+            }
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(blobs)
+}
+
+/// Resolve every file of `snapshot` into a cache, inline declarations first
+fn resolve(
+    repository: &Repository, snapshot: &Snapshot, options: MatchOptions, policy: PrecedencePolicy,
+) -> Result<CodeownersCache> {
+    let entries: Vec<CodeownersEntry> = snapshot.rules.values().flatten().cloned().collect();
+    let matcher = Matcher::build_with_options(&entries, MatcherStrategy::Override, options);
+
+    // Blobs are read here, as git2 handles cannot be shared between workers
+    let inline: Vec<_> = snapshot
+        .files
+        .iter()
+        .map(|(path, id)| {
+            let blob = repository.find_blob(*id)?;
+            detect_inline_codeowners_in(BufReader::new(blob.content()), path)
+        })
+        .collect::<Result<_>>()?;
+
+    let files = snapshot
+        .files
+        .par_iter()
+        .zip(inline)
+        .map(|((path, _), inline)| {
+            let declaration = inline.as_ref().map(InlineDeclaration::from);
+            let (owners, tags) = match inline {
+                Some(inline) => (inline.owners, inline.tags),
+                None => {
+                    let applied = find_applied_entries(path, &entries, &matcher, policy)?;
+                    let owners = applied
+                        .iter()
+                        .flat_map(|&index| entries[index].owners.iter().cloned())
+                        .collect();
+                    let tags = applied
+                        .iter()
+                        .flat_map(|&index| entries[index].tags.iter().cloned())
+                        .collect();
+                    (owners, tags)
+                }
+            };
+            Ok(FileEntry {
+                path: path.clone(),
+                owners: dedup_owners(owners),
+                tags: dedup_tags(tags),
+                generated: false,
+                inline: declaration,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(CodeownersCache::new([0; 32], entries, files, Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Signature;
+    use tempfile::TempDir;
+
+    fn commit(repository: &Repository, files: &[(&str, &str)], message: &str) {
+        let root = repository.workdir().unwrap();
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let mut index = repository.index().unwrap();
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("Test", "test@example.com").unwrap();
+        let parent = repository
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_diff_refs() -> Result<()> {
+        let dir = TempDir::new()?;
+        let repository = Repository::init(dir.path())?;
+        commit(
+            &repository,
+            &[
+                ("CODEOWNERS", "*.rs @rust\n/docs/ @docs\n"),
+                ("src/lib.rs", "fn main() {}\n"),
+                ("docs/guide.md", "# Guide\n"),
+                ("web/app.js", "// !!!CODEOWNERS @web\n"),
+            ],
+            "Initial",
+        );
+        repository.tag_lightweight("v1", &repository.head()?.peel(ObjectType::Commit)?, false)?;
+        commit(
+            &repository,
+            &[
+                ("CODEOWNERS", "*.rs @rust @reviewers\n*.md @writers\n"),
+                ("src/new.rs", "\n"),
+            ],
+            "Reorganize",
+        );
+
+        let changes = diff_refs(
+            dir.path(),
+            "v1",
+            "HEAD",
+            MatchOptions::default(),
+            PrecedencePolicy::Closest,
+        )?;
+        let rules: Vec<_> = changes
+            .rules
+            .iter()
+            .map(|rule| (rule.pattern.as_str(), rule.kind))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("*.rs", OwnershipChangeKind::OwnersChanged),
+                ("/docs/", OwnershipChangeKind::Removed),
+                ("*.md", OwnershipChangeKind::Added),
+            ]
+        );
+
+        let files: Vec<_> = changes
+            .files
+            .iter()
+            .map(|file| {
+                let owners: Vec<_> = file
+                    .b_owners
+                    .iter()
+                    .map(|o| o.identifier.as_str())
+                    .collect();
+                (file.path.to_str().unwrap(), owners)
+            })
+            .collect();
+        // The inline declaration of web/app.js is unchanged
+        assert_eq!(
+            files,
+            [
+                ("docs/guide.md", vec!["@writers"]),
+                ("src/lib.rs", vec!["@rust", "@reviewers"]),
+                ("src/new.rs", vec!["@rust", "@reviewers"]),
+            ]
+        );
+        assert!(diff_refs(
+            dir.path(),
+            "v1",
+            "missing",
+            MatchOptions::default(),
+            PrecedencePolicy::Closest
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+//! Machine-wide registry of the repositories whose cache was built
+//!
+//! Whenever the CLI builds or updates the cache of a repository, it records the
+//! repository, its cache file, when and how many of its files are owned in a JSON file in
+//! the user's data directory. `repos` lists and refreshes them, and `org report` rolls
+//! them up when no workspace file is given. Recording is off for library use; the CLI
+//! turns it on unless `registry = false` is set.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::cache::unowned_count;
+use super::common::write_file_atomically;
+use super::types::CodeownersCache;
+use crate::utils::error::{Error, Result};
+use crate::utils::output;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable recording in the registry for the rest of the process
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// The repositories recorded, sorted by path
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default)]
+    pub repos: Vec<RegisteredRepo>,
+}
+
+/// A repository as it was when its cache was last built or updated
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredRepo {
+    /// Absolute path of the repository
+    pub path: PathBuf,
+    /// Absolute path of its cache file
+    pub cache_file: PathBuf,
+    pub cached_at: DateTime<Utc>,
+    pub files: usize,
+    /// Files without an owner, leaving out generated ones
+    pub unowned: usize,
+}
+
+impl RegisteredRepo {
+    /// Name in listings: the last component of the path
+    pub fn name(&self) -> &str {
+        self.path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("repository")
+    }
+
+    /// Share of the files with an owner, as a percentage, or `None` without files
+    pub fn coverage(&self) -> Option<f64> {
+        (self.files > 0).then(|| (self.files - self.unowned) as f64 * 100.0 / self.files as f64)
+    }
+}
+
+impl Registry {
+    /// Path of the registry file in the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_local_dir().map(|dir| dir.join("codeinput").join("repos.json"))
+    }
+
+    /// Read the registry at `path`; a missing file is an empty registry
+    pub fn load_from(path: &Path) -> Result<Registry> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Registry::default()),
+            Err(e) => {
+                return Err(Error::new(&format!(
+                    "Failed to read {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        serde_json::from_str(&contents)
+            .map_err(|e| Error::new(&format!("Failed to parse {}: {}", path.display(), e)))
+    }
+
+    /// Read the registry in the user's data directory
+    pub fn load() -> Result<Registry> {
+        Registry::load_from(&registry_path()?)
+    }
+
+    /// Write the registry to `path`, creating its directory
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write_file_atomically(path, |writer| {
+            serde_json::to_writer_pretty(writer, self)
+                .map_err(|e| Error::new(&format!("Failed to serialize the registry: {}", e)))
+        })
+    }
+
+    /// Write the registry to the user's data directory
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&registry_path()?)
+    }
+
+    /// Add `repo`, replacing what was recorded for its path
+    pub fn record(&mut self, repo: RegisteredRepo) {
+        match self
+            .repos
+            .binary_search_by(|recorded| recorded.path.cmp(&repo.path))
+        {
+            Ok(position) => self.repos[position] = repo,
+            Err(position) => self.repos.insert(position, repo),
+        }
+    }
+
+    /// Drop the repositories whose directory is gone, returning them
+    pub fn prune(&mut self) -> Vec<RegisteredRepo> {
+        let (kept, gone) = std::mem::take(&mut self.repos)
+            .into_iter()
+            .partition(|repo| repo.path.is_dir());
+        self.repos = kept;
+        gone
+    }
+}
+
+fn registry_path() -> Result<PathBuf> {
+    Registry::default_path()
+        .ok_or_else(|| Error::new("Could not determine the user data directory"))
+}
+
+/// Record `cache` of `repo`, written to `cache_file`, if recording is on
+///
+/// The registry must never affect the command itself, so failures are only logged.
+pub fn note(repo: &Path, cache_file: &Path, cache: &CodeownersCache) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let recorded = (|| -> Result<()> {
+        let repo = RegisteredRepo {
+            path: repo.canonicalize()?,
+            cache_file: cache_file.canonicalize()?,
+            cached_at: output::now(),
+            files: cache.files.len(),
+            unowned: unowned_count(cache),
+        };
+        let mut registry = Registry::load()?;
+        registry.record(repo);
+        registry.save()
+    })();
+    if let Err(e) = recorded {
+        log::debug!("Failed to record {} in the registry: {}", repo.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo(path: &str, files: usize, unowned: usize) -> RegisteredRepo {
+        RegisteredRepo {
+            path: PathBuf::from(path),
+            cache_file: Path::new(path).join(".codeowners.cache"),
+            cached_at: DateTime::from_timestamp(output::STABLE_NOW, 0).unwrap(),
+            files,
+            unowned,
+        }
+    }
+
+    #[test]
+    fn test_record_and_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("codeinput").join("repos.json");
+        assert_eq!(Registry::load_from(&path).unwrap(), Registry::default());
+
+        let mut registry = Registry::default();
+        registry.record(repo("/src/web", 10, 5));
+        registry.record(repo("/src/api", 4, 1));
+        registry.record(repo("/src/web", 8, 0));
+        let names: Vec<&str> = registry.repos.iter().map(RegisteredRepo::name).collect();
+        assert_eq!(names, ["api", "web"]);
+        assert_eq!(registry.repos[0].coverage(), Some(75.0));
+        assert_eq!(registry.repos[1].coverage(), Some(100.0));
+        assert_eq!(repo("/src/empty", 0, 0).coverage(), None);
+
+        registry.save_to(&path).unwrap();
+        assert_eq!(Registry::load_from(&path).unwrap(), registry);
+
+        let gone = registry.prune();
+        assert_eq!(gone.len(), 2);
+        assert!(registry.repos.is_empty());
+    }
+}
@@ -1,20 +1,33 @@
 use super::{
+    central,
     inline_parser::detect_inline_codeowners,
-    types::{CodeownersEntryMatcher, Tag},
+    matcher::Matcher,
+    owner_resolver::dedup_owners,
+    tag_resolver::dedup_tags,
+    types::{
+        is_negation, CodeownersEntry, CodeownersEntryMatcher, InlineDeclaration, PrecedencePolicy,
+        Tag,
+    },
 };
 use crate::utils::error::{Error, Result};
 
+use std::cmp::Ordering;
 use std::path::Path;
 
 use super::types::Owner;
 
 /// Find both owners and tags for a specific file based on all parsed CODEOWNERS entries
+///
+/// Owners and tags are deduplicated, see [`dedup_owners`] and [`dedup_tags`].
 pub fn find_owners_and_tags_for_file(
     file_path: &Path, entries: &[CodeownersEntryMatcher],
 ) -> Result<(Vec<Owner>, Vec<Tag>)> {
     // First, check for inline CODEOWNERS declaration (highest priority)
     if let Some(inline_entry) = detect_inline_codeowners(file_path)? {
-        return Ok((inline_entry.owners, inline_entry.tags));
+        return Ok((
+            dedup_owners(inline_entry.owners),
+            dedup_tags(inline_entry.tags),
+        ));
     }
 
     // Early return if no entries
@@ -29,7 +42,7 @@ pub fn find_owners_and_tags_for_file(
     let mut candidates: Vec<_> = entries
         .iter()
         .filter_map(|entry| {
-            let codeowners_dir = match entry.source_file.parent() {
+            let codeowners_dir = match entry.base_dir() {
                 Some(dir) => dir,
                 None => {
                     eprintln!(
@@ -76,26 +89,238 @@ pub fn find_owners_and_tags_for_file(
         let b_entry = b.0;
         let b_depth = b.1;
 
-        // Primary sort by depth (ascending)
-        a_depth
-            .cmp(&b_depth)
+        // Central rules rank before or after the repository's own, then by depth (ascending)
+        central::rank(&a_entry.source_file)
+            .cmp(&central::rank(&b_entry.source_file))
+            .then_with(|| a_depth.cmp(&b_depth))
             // Then by source file (to group entries from the same CODEOWNERS file)
             .then_with(|| a_entry.source_file.cmp(&b_entry.source_file))
             // Then by line number (descending) to prioritize later entries in the same file
             .then_with(|| b_entry.line_number.cmp(&a_entry.line_number))
     });
+    let candidates = without_negated(
+        candidates,
+        |(entry, _)| entry.source_file.as_path(),
+        |(entry, _)| is_negation(&entry.pattern),
+    );
 
     // Extract both owners and tags from the highest priority entry, if any
     Ok(candidates
         .first()
-        .map(|(entry, _)| (entry.owners.clone(), entry.tags.clone()))
+        .map(|(entry, _)| {
+            (
+                dedup_owners(entry.owners.clone()),
+                dedup_tags(entry.tags.clone()),
+            )
+        })
         .unwrap_or_default())
 }
 
+/// Find both owners and tags for a file using a prebuilt [`Matcher`] over `entries`
+pub fn find_owners_and_tags_with_matcher(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher,
+) -> Result<(Vec<Owner>, Vec<Tag>)> {
+    let (owners, tags, _) = resolve_file_with_matcher(file_path, entries, matcher)?;
+    Ok((owners, tags))
+}
+
+/// Find owners and tags for a file like `find_owners_and_tags_with_matcher`, also returning
+/// the index of the entry that applied
+///
+/// The index is `None` when an inline declaration applies or no entry matches. Owners and
+/// tags are deduplicated.
+pub fn resolve_file_with_matcher(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher,
+) -> Result<(Vec<Owner>, Vec<Tag>, Option<usize>)> {
+    let (owners, tags, applied) =
+        resolve_file_with_policy(file_path, entries, matcher, PrecedencePolicy::Closest)?;
+    Ok((owners, tags, applied.first().copied()))
+}
+
+/// Find owners and tags for a file under a precedence policy, also returning the indices
+/// of the entries that applied
+///
+/// With [`PrecedencePolicy::Merge`] the owners and tags of every applied entry are
+/// combined, closest CODEOWNERS file first. Inline declarations take precedence and apply
+/// no entry. Owners and tags are deduplicated.
+pub fn resolve_file_with_policy(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher, policy: PrecedencePolicy,
+) -> Result<(Vec<Owner>, Vec<Tag>, Vec<usize>)> {
+    let (owners, tags, applied, _) = resolve_file_with_source(file_path, entries, matcher, policy)?;
+    Ok((owners, tags, applied))
+}
+
+/// Owners, tags, indices of the applied entries and the inline declaration of a file
+pub type SourcedResolution = (Vec<Owner>, Vec<Tag>, Vec<usize>, Option<InlineDeclaration>);
+
+/// Find owners and tags for a file like [`resolve_file_with_policy`], also returning the
+/// inline declaration that applied instead of any entry
+pub fn resolve_file_with_source(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher, policy: PrecedencePolicy,
+) -> Result<SourcedResolution> {
+    // Inline declarations take precedence, as in `find_owners_and_tags_for_file`
+    if let Some(inline_entry) = detect_inline_codeowners(file_path)? {
+        let declaration = InlineDeclaration::from(&inline_entry);
+        return Ok((
+            dedup_owners(inline_entry.owners),
+            dedup_tags(inline_entry.tags),
+            Vec::new(),
+            Some(declaration),
+        ));
+    }
+
+    let applied = find_applied_entries(file_path, entries, matcher, policy)?;
+    let owners = applied
+        .iter()
+        .flat_map(|&index| entries[index].owners.iter().cloned())
+        .collect();
+    let tags = applied
+        .iter()
+        .flat_map(|&index| entries[index].tags.iter().cloned())
+        .collect();
+
+    Ok((dedup_owners(owners), dedup_tags(tags), applied, None))
+}
+
+/// Indices of the CODEOWNERS entries that decide ownership of a file under `policy`,
+/// ignoring inline declarations
+///
+/// This is a single entry, except with [`PrecedencePolicy::Merge`], where the last
+/// matching line of each CODEOWNERS file applies, closest file first.
+pub fn find_applied_entries(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher, policy: PrecedencePolicy,
+) -> Result<Vec<usize>> {
+    let matching = find_matching_entries(file_path, entries, matcher, policy)?;
+    if policy != PrecedencePolicy::Merge {
+        return Ok(matching.into_iter().take(1).collect());
+    }
+
+    // Sorted by file, then last line first, so the first entry of each file applies
+    let mut applied: Vec<usize> = Vec::new();
+    for index in matching {
+        let source_file = &entries[index].source_file;
+        if !applied
+            .iter()
+            .any(|&a| &entries[a].source_file == source_file)
+        {
+            applied.push(index);
+        }
+    }
+    Ok(applied)
+}
+
+/// Find the index of the CODEOWNERS entry that applies to a file, ignoring inline declarations
+///
+/// Precedence is the same as `find_owners_and_tags_for_file`: the closest CODEOWNERS file
+/// wins, then the last matching line within it.
+pub fn find_entry_for_file(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher,
+) -> Result<Option<usize>> {
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(
+        find_matching_entries(file_path, entries, matcher, PrecedencePolicy::Closest)?
+            .first()
+            .copied(),
+    )
+}
+
+/// Indices of every CODEOWNERS entry matching a file, highest precedence under `policy`
+/// first
+///
+/// With the closest policy, the first index is the one [`find_entry_for_file`] returns;
+/// the rest are rules that also match but are overridden. Inline declarations are not
+/// included, nor are negations and the rules of CODEOWNERS files that exclude the file
+/// with one. The gitignore matcher only reports the last match per CODEOWNERS file, so
+/// overridden rules from the same file are missing with it.
+pub fn find_matching_entries(
+    file_path: &Path, entries: &[CodeownersEntry], matcher: &Matcher, policy: PrecedencePolicy,
+) -> Result<Vec<usize>> {
+    if entries.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut candidates: Vec<_> = matching_candidates(file_path, entries, matcher)?.collect();
+    candidates.sort_unstable_by(|a, b| precedence(entries, policy, a, b));
+    let candidates = without_negated(
+        candidates,
+        |(index, _)| entries[*index].source_file.as_path(),
+        |(index, _)| entries[*index].is_negation(),
+    );
+    Ok(candidates.into_iter().map(|(index, _)| index).collect())
+}
+
+/// Drop negations from `matches`, which are grouped by CODEOWNERS file with the last line
+/// first, along with every match of a file whose last matching line is a negation
+///
+/// Such a file excludes the path: none of its rules apply.
+fn without_negated<'a, T>(
+    matches: Vec<T>, source_file: impl Fn(&T) -> &'a Path, negation: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    let mut seen: Vec<&Path> = Vec::new();
+    let mut excluded: Vec<&Path> = Vec::new();
+    matches
+        .into_iter()
+        .filter(|item| {
+            let source = source_file(item);
+            if !seen.contains(&source) {
+                seen.push(source);
+                if negation(item) {
+                    excluded.push(source);
+                }
+            }
+            !negation(item) && !excluded.contains(&source)
+        })
+        .collect()
+}
+
+/// Matching entries that apply to the file's directory, with their depth below it
+fn matching_candidates<'a>(
+    file_path: &'a Path, entries: &'a [CodeownersEntry], matcher: &Matcher,
+) -> Result<impl Iterator<Item = (usize, usize)> + 'a> {
+    let target_dir = file_path
+        .parent()
+        .ok_or_else(|| Error::new("file path has no parent directory"))?;
+
+    Ok(matcher
+        .candidates(file_path)
+        .into_iter()
+        .filter_map(move |index| {
+            let codeowners_dir = entries[index].base_dir()?;
+            let depth = target_dir
+                .strip_prefix(codeowners_dir)
+                .ok()?
+                .components()
+                .count();
+            Some((index, depth))
+        }))
+}
+
+/// Order CODEOWNERS files by `policy`, then the last matching line within a file first
+///
+/// Merging lists the closest file first, like the closest policy. Central rules rank
+/// before or after the repository's own files whatever the policy, see [`central::rank`].
+fn precedence(
+    entries: &[CodeownersEntry], policy: PrecedencePolicy, (a, a_depth): &(usize, usize),
+    (b, b_depth): &(usize, usize),
+) -> Ordering {
+    let by_depth = match policy {
+        PrecedencePolicy::Closest | PrecedencePolicy::Merge => a_depth.cmp(b_depth),
+        PrecedencePolicy::Root => b_depth.cmp(a_depth),
+    };
+    central::rank(&entries[*a].source_file)
+        .cmp(&central::rank(&entries[*b].source_file))
+        .then(by_depth)
+        .then_with(|| entries[*a].source_file.cmp(&entries[*b].source_file))
+        .then_with(|| entries[*b].line_number.cmp(&entries[*a].line_number))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::types::{Owner, OwnerType, Tag};
+    use crate::core::types::{EntrySpans, Owner, OwnerType, Tag};
     use ignore::overrides::OverrideBuilder;
     use std::path::PathBuf;
 
@@ -244,4 +469,203 @@ mod tests {
         assert_eq!(result.1.len(), 1);
         assert_eq!(result.1[0].0, "tag2");
     }
+
+    #[test]
+    fn test_find_entry_for_file_agrees_across_strategies() {
+        use crate::core::matcher::MatcherStrategy;
+
+        let entry = |source_file: &str, line_number: usize, pattern: &str| CodeownersEntry {
+            source_file: PathBuf::from(source_file),
+            line_number,
+            pattern: pattern.to_string(),
+            owners: vec![create_test_owner("@team", OwnerType::Team)],
+            tags: vec![],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "*"),
+            entry("/project/CODEOWNERS", 1, "*.rs"),
+            entry("/project/src/CODEOWNERS", 0, "*.js"),
+        ];
+
+        for strategy in MatcherStrategy::CONCRETE {
+            let matcher = Matcher::build(&entries, strategy);
+            let find = |path: &str| find_entry_for_file(Path::new(path), &entries, &matcher);
+
+            assert_eq!(find("/project/README.md").unwrap(), Some(0));
+            assert_eq!(find("/project/src/main.rs").unwrap(), Some(1));
+            assert_eq!(find("/project/src/app.js").unwrap(), Some(2));
+            assert_eq!(find("/other/main.rs").unwrap(), None);
+
+            let all = |path: &str| {
+                find_matching_entries(
+                    Path::new(path),
+                    &entries,
+                    &matcher,
+                    PrecedencePolicy::Closest,
+                )
+            };
+            if strategy != MatcherStrategy::Gitignore {
+                assert_eq!(all("/project/src/main.rs").unwrap(), vec![1, 0]);
+            }
+            assert_eq!(all("/project/src/app.js").unwrap(), vec![2, 0]);
+            assert!(all("/other/main.rs").unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_precedence_policies() {
+        let entry =
+            |source_file: &str, line_number: usize, pattern: &str, owner: &str| CodeownersEntry {
+                source_file: PathBuf::from(source_file),
+                line_number,
+                pattern: pattern.to_string(),
+                owners: vec![create_test_owner(owner, OwnerType::User)],
+                tags: vec![create_test_tag(owner.trim_start_matches('@'))],
+                spans: EntrySpans::default(),
+                metadata: Default::default(),
+                section: None,
+            };
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "*", "@root"),
+            entry("/project/CODEOWNERS", 1, "*.rs", "@rust"),
+            entry("/project/src/CODEOWNERS", 0, "*.rs", "@src"),
+        ];
+        let matcher = Matcher::build(&entries, crate::core::matcher::MatcherStrategy::Override);
+        let resolve = |policy| {
+            let (owners, tags, applied) = resolve_file_with_policy(
+                Path::new("/project/src/main.rs"),
+                &entries,
+                &matcher,
+                policy,
+            )
+            .unwrap();
+            let owners: Vec<_> = owners.into_iter().map(|o| o.identifier).collect();
+            (owners, tags.len(), applied)
+        };
+
+        assert_eq!(
+            resolve(PrecedencePolicy::Closest),
+            (vec!["@src".to_string()], 1, vec![2])
+        );
+        assert_eq!(
+            resolve(PrecedencePolicy::Root),
+            (vec!["@rust".to_string()], 1, vec![1])
+        );
+        assert_eq!(
+            resolve(PrecedencePolicy::Merge),
+            (vec!["@src".to_string(), "@rust".to_string()], 2, vec![2, 1])
+        );
+
+        // A file only one CODEOWNERS file applies to is unaffected by the policy
+        for policy in [PrecedencePolicy::Root, PrecedencePolicy::Merge] {
+            let (_, _, applied) =
+                resolve_file_with_policy(Path::new("/project/a.md"), &entries, &matcher, policy)
+                    .unwrap();
+            assert_eq!(applied, vec![0]);
+        }
+    }
+
+    #[test]
+    fn test_negation() {
+        let entry = |source_file: &str, line_number: usize, pattern: &str, owner: Option<&str>| {
+            CodeownersEntry {
+                source_file: PathBuf::from(source_file),
+                line_number,
+                pattern: pattern.to_string(),
+                owners: owner
+                    .map(|owner| create_test_owner(owner, OwnerType::User))
+                    .into_iter()
+                    .collect(),
+                tags: Vec::new(),
+                spans: EntrySpans::default(),
+                metadata: Default::default(),
+                section: None,
+            }
+        };
+        let entries = vec![
+            entry("/project/CODEOWNERS", 0, "*", Some("@root")),
+            entry("/project/src/CODEOWNERS", 0, "*", Some("@src")),
+            entry("/project/src/CODEOWNERS", 1, "!generated/**", None),
+            entry(
+                "/project/src/CODEOWNERS",
+                2,
+                "generated/keep.rs",
+                Some("@keep"),
+            ),
+        ];
+
+        for strategy in crate::core::matcher::MatcherStrategy::CONCRETE {
+            let matcher = Matcher::build(&entries, strategy);
+            let resolve = |path: &str, policy| {
+                find_applied_entries(Path::new(path), &entries, &matcher, policy).unwrap()
+            };
+
+            assert_eq!(
+                resolve("/project/src/main.rs", PrecedencePolicy::Closest),
+                [1]
+            );
+            // Excluded from src/CODEOWNERS, so the root file decides
+            let generated = "/project/src/generated/out.rs";
+            assert_eq!(
+                resolve(generated, PrecedencePolicy::Closest),
+                [0],
+                "{}",
+                strategy
+            );
+            assert_eq!(
+                resolve(generated, PrecedencePolicy::Merge),
+                [0],
+                "{}",
+                strategy
+            );
+            // A later positive rule takes precedence over the negation
+            let keep = "/project/src/generated/keep.rs";
+            assert_eq!(
+                resolve(keep, PrecedencePolicy::Closest),
+                [3],
+                "{}",
+                strategy
+            );
+            assert_eq!(resolve(keep, PrecedencePolicy::Root), [0], "{}", strategy);
+        }
+
+        // Without a file above, excluded files are unowned
+        let nested = &entries[1..3];
+        let matcher = Matcher::build(nested, crate::core::matcher::MatcherStrategy::Override);
+        let generated = Path::new("/project/src/generated/out.rs");
+        assert_eq!(
+            find_entry_for_file(generated, nested, &matcher).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_file_with_matcher_dedups_owners_and_tags() {
+        let entries = vec![CodeownersEntry {
+            source_file: PathBuf::from("/project/CODEOWNERS"),
+            line_number: 0,
+            pattern: "*".to_string(),
+            owners: vec![
+                create_test_owner("@Alice", OwnerType::User),
+                create_test_owner("@org/team", OwnerType::Team),
+                create_test_owner("@alice", OwnerType::User),
+            ],
+            tags: vec![create_test_tag("core"), create_test_tag("core")],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        }];
+        let matcher = Matcher::build(&entries, crate::core::matcher::MatcherStrategy::Override);
+
+        let (owners, tags, index) =
+            resolve_file_with_matcher(Path::new("/project/missing.rs"), &entries, &matcher)
+                .unwrap();
+        let identifiers: Vec<_> = owners.iter().map(|o| o.identifier.as_str()).collect();
+        assert_eq!(identifiers, ["@Alice", "@org/team"]);
+        assert_eq!(tags, vec![create_test_tag("core")]);
+        assert_eq!(index, Some(0));
+    }
 }
@@ -0,0 +1,188 @@
+//! Pull request reviews as an ownership signal, for `infer-owners --reviews`
+//!
+//! Who reviews the changes to a file often knows it better than who typed them. The
+//! merged pull requests of the repository on GitHub are read with their files and reviews,
+//! and each reviewer is credited for every file of the pull requests they reviewed: an
+//! approval counts fully and any other review half. Reviewers count once per pull request,
+//! whatever number of reviews they left; the author's own comments and dismissed or pending
+//! reviews do not count. Responses are cached like those of `compare-github`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::context::Context;
+use super::github::{GitHubClient, GitHubRepo, PullRequest, PullRequestFile, Review};
+use crate::utils::error::{Error, Result};
+use crate::utils::{interrupt, output};
+
+/// Credit of an approval
+const APPROVAL: f64 = 1.0;
+
+/// Credit of a review requesting changes or only commenting
+const COMMENT: f64 = 0.5;
+
+/// What one reviewer did for one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewerStats {
+    pub login: String,
+    /// Credit summed over the pull requests reviewed
+    pub score: f64,
+    /// Pull requests reviewed
+    pub reviews: u32,
+    /// Days since the last of those was merged
+    pub last_review_days_ago: u32,
+}
+
+/// Reviewers of each file by path relative to the repository root, keyed by lowercased
+/// login
+pub type ReviewHistory = HashMap<PathBuf, HashMap<String, ReviewerStats>>;
+
+/// Where reviews are read from and how much they count
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewSource {
+    /// Repository on GitHub; `None` reads it from the origin remote
+    pub repo: Option<GitHubRepo>,
+    /// Weight of the review signal, from 0 to 1; the blame or commit signal gets the rest
+    pub weight: f64,
+    /// Most merged pull requests read
+    pub max_pulls: usize,
+    /// Answer from responses cached by earlier runs, without network access
+    pub offline: bool,
+}
+
+impl ReviewSource {
+    /// A source whose weight is between 0 and 1
+    pub fn new(
+        repo: Option<GitHubRepo>, weight: f64, max_pulls: usize, offline: bool,
+    ) -> Result<Self> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(Error::new(&format!(
+                "Invalid review weight {}: expected a number from 0 to 1",
+                weight
+            )));
+        }
+        Ok(ReviewSource {
+            repo,
+            weight,
+            max_pulls,
+            offline,
+        })
+    }
+
+    /// Reviewers of the files of the git repository at `path`, from the pull requests
+    /// merged in the last `lookback_days` days (0 reads them regardless of age)
+    pub fn history(&self, ctx: &Context, path: &Path, lookback_days: u32) -> Result<ReviewHistory> {
+        let repo = match &self.repo {
+            Some(repo) => repo.clone(),
+            None => GitHubRepo::discover(path)?,
+        };
+        let client = GitHubClient::new(ctx, self.offline);
+        let since = (lookback_days > 0)
+            .then(|| output::now() - chrono::Duration::days(lookback_days.into()));
+
+        let pulls = client.merged_pulls(&repo, since, self.max_pulls)?;
+        let mut history = ReviewHistory::new();
+        for pull in &pulls {
+            interrupt::check()?;
+            let files = client.pull_files(&repo, pull.number)?;
+            let reviews = client.pull_reviews(&repo, pull.number)?;
+            credit(&mut history, pull, &files, &reviews);
+        }
+        log::info!(
+            "Read the reviews of {} merged pull requests of {}",
+            pulls.len(),
+            repo
+        );
+        Ok(history)
+    }
+}
+
+/// Credit the reviewers of `pull` for each of its `files`, under both names of a renamed
+/// file
+fn credit(
+    history: &mut ReviewHistory, pull: &PullRequest, files: &[PullRequestFile], reviews: &[Review],
+) {
+    let author = pull.user.as_ref().map(|user| user.login.to_lowercase());
+    let mut credits: HashMap<String, (String, f64)> = HashMap::new();
+    for review in reviews {
+        let Some(user) = &review.user else {
+            continue;
+        };
+        let key = user.login.to_lowercase();
+        if author.as_ref() == Some(&key) {
+            continue;
+        }
+        let credit = match review.state.as_str() {
+            "APPROVED" => APPROVAL,
+            "CHANGES_REQUESTED" | "COMMENTED" => COMMENT,
+            _ => continue,
+        };
+        let entry = credits.entry(key).or_insert((user.login.clone(), 0.0));
+        entry.1 = entry.1.max(credit);
+    }
+    if credits.is_empty() {
+        return;
+    }
+
+    let days_ago = pull
+        .merged_at
+        .map(|merged| (output::now() - merged).num_days().max(0) as u32)
+        .unwrap_or(u32::MAX);
+    for file in files {
+        let names = std::iter::once(&file.filename).chain(&file.previous_filename);
+        for name in names {
+            let reviewers = history.entry(PathBuf::from(name)).or_default();
+            for (key, (login, credit)) in &credits {
+                let stats = reviewers.entry(key.clone()).or_insert(ReviewerStats {
+                    login: login.clone(),
+                    score: 0.0,
+                    reviews: 0,
+                    last_review_days_ago: u32::MAX,
+                });
+                stats.score += credit;
+                stats.reviews += 1;
+                stats.last_review_days_ago = stats.last_review_days_ago.min(days_ago);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credit() {
+        let pull: PullRequest = serde_json::from_str(
+            r#"{"number":7,"user":{"login":"author"},"merged_at":"2023-12-30T00:00:00Z","updated_at":"2023-12-30T00:00:00Z"}"#,
+        )
+        .unwrap();
+        let files: Vec<PullRequestFile> = serde_json::from_str(
+            r#"[{"filename":"src/new.rs","previous_filename":"src/old.rs"},{"filename":"README.md"}]"#,
+        )
+        .unwrap();
+        let reviews: Vec<Review> = serde_json::from_str(
+            r#"[
+                {"user":{"login":"Alice"},"state":"COMMENTED"},
+                {"user":{"login":"alice"},"state":"APPROVED"},
+                {"user":{"login":"bob"},"state":"COMMENTED"},
+                {"user":{"login":"carol"},"state":"DISMISSED"},
+                {"user":{"login":"author"},"state":"COMMENTED"},
+                {"user":null,"state":"APPROVED"}
+            ]"#,
+        )
+        .unwrap();
+
+        let mut history = ReviewHistory::new();
+        credit(&mut history, &pull, &files, &reviews);
+        credit(&mut history, &pull, &files, &reviews);
+
+        assert_eq!(history.len(), 3);
+        let reviewers = &history[Path::new("src/old.rs")];
+        assert_eq!(reviewers.len(), 2);
+        // Approving after commenting counts once, as an approval
+        assert_eq!(reviewers["alice"].score, 2.0);
+        assert_eq!(reviewers["alice"].reviews, 2);
+        assert_eq!(reviewers["bob"].score, 1.0);
+    }
+}
@@ -0,0 +1,217 @@
+//! Rules left out by their tags at query time
+//!
+//! `--ignore-rules-tagged` and `--only-rules-tagged` show ownership as it would be without
+//! some rules, such as those tagged `#deprecated`, without editing any CODEOWNERS file.
+//! The cache on disk keeps every rule; commands reading it through
+//! [`sync_cache`](super::cache::sync_cache) get a view with the files resolved again against
+//! the rules kept.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
+
+use super::context::Context;
+use super::matcher::Matcher;
+use super::resolver::resolve_file_with_policy;
+use super::types::{CodeownersCache, CodeownersEntry, FileEntry, Tag};
+use crate::utils::error::Result;
+
+/// Filter set for the rest of the process by [`set`]
+static FILTER: OnceLock<RuleFilter> = OnceLock::new();
+
+/// Tags deciding which rules apply
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuleFilter {
+    /// Rules with any of these tags are left out
+    ignore: Vec<Tag>,
+    /// When not empty, only rules with one of these tags apply
+    only: Vec<Tag>,
+}
+
+impl RuleFilter {
+    /// A filter leaving out the rules tagged with any of `ignore` and, unless `only` is
+    /// empty, the rules tagged with none of `only`
+    ///
+    /// Tags are given with or without their `#`.
+    pub fn new(ignore: &[String], only: &[String]) -> Self {
+        let tags = |tags: &[String]| -> Vec<Tag> {
+            tags.iter()
+                .map(|tag| Tag(tag.trim_start_matches('#').to_string()))
+                .collect()
+        };
+        RuleFilter {
+            ignore: tags(ignore),
+            only: tags(only),
+        }
+    }
+
+    /// Whether the filter keeps every rule
+    pub fn is_empty(&self) -> bool {
+        self.ignore.is_empty() && self.only.is_empty()
+    }
+
+    /// Whether `entry` applies under the filter
+    pub fn keeps(&self, entry: &CodeownersEntry) -> bool {
+        !entry.tags.iter().any(|tag| self.ignore.contains(tag))
+            && (self.only.is_empty() || entry.tags.iter().any(|tag| self.only.contains(tag)))
+    }
+
+    /// `cache` with only the rules the filter keeps, and every file resolved against them
+    ///
+    /// Files are resolved with the cache's precedence policy and the matcher and match
+    /// options of `ctx`. Files resolved by an inline declaration, or by no rule, before
+    /// and after keep their cached entry, as leaving out rules cannot change them.
+    pub fn apply(&self, ctx: &Context, cache: CodeownersCache) -> Result<CodeownersCache> {
+        if self.is_empty() {
+            return Ok(cache);
+        }
+
+        let entries: Vec<CodeownersEntry> = cache
+            .entries
+            .iter()
+            .filter(|entry| self.keeps(entry))
+            .cloned()
+            .collect();
+        log::debug!(
+            "Resolving files against {} of {} rules, filtered by tag",
+            entries.len(),
+            cache.entries.len()
+        );
+
+        let config = &ctx.config;
+        let strategy =
+            config
+                .matcher
+                .select(entries.len(), cache.files.len(), &config.matcher_profile);
+        let matcher = Matcher::build_with_options(&entries, strategy, config.match_options());
+        let decided: HashSet<usize> = cache.rule_matches.iter().flatten().copied().collect();
+
+        let resolved: Vec<(FileEntry, Vec<usize>)> = cache
+            .files
+            .par_iter()
+            .enumerate()
+            .map(|(position, file)| {
+                let (owners, tags, rules) =
+                    resolve_file_with_policy(&file.path, &entries, &matcher, cache.precedence)?;
+                // Inline tags were remapped when the cache was built, so keep them
+                if rules.is_empty() && !decided.contains(&position) {
+                    return Ok((file.clone(), rules));
+                }
+                Ok((
+                    FileEntry {
+                        path: file.path.clone(),
+                        owners,
+                        tags,
+                        generated: file.generated,
+                        inline: None,
+                    },
+                    rules,
+                ))
+            })
+            .collect::<Result<_>>()?;
+
+        let mut rule_matches = vec![Vec::new(); entries.len()];
+        for (position, (_, rules)) in resolved.iter().enumerate() {
+            for &rule in rules {
+                rule_matches[rule].push(position);
+            }
+        }
+        let files = resolved.into_iter().map(|(file, _)| file).collect();
+
+        Ok(CodeownersCache {
+            precedence: cache.precedence,
+            build: cache.build,
+            ..CodeownersCache::new(cache.hash, entries, files, rule_matches)
+        })
+    }
+}
+
+/// Apply `filter` to the caches commands read for the rest of the process
+///
+/// Only the first filter set applies.
+pub fn set(filter: RuleFilter) {
+    let _ = FILTER.set(filter);
+}
+
+/// Whether a filter that leaves out rules was set with [`set`]
+#[cfg(any(feature = "sqlite", feature = "archive"))]
+pub(crate) fn is_set() -> bool {
+    FILTER.get().is_some_and(|filter| !filter.is_empty())
+}
+
+/// `cache` filtered by the filter set with [`set`], if any
+pub(crate) fn apply_current(ctx: &Context, cache: CodeownersCache) -> Result<CodeownersCache> {
+    match FILTER.get() {
+        Some(filter) => filter.apply(ctx, cache),
+        None => Ok(cache),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::parser::parse_content;
+    use crate::utils::app_config::AppConfig;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_filtered_rules_no_longer_own_files() -> Result<()> {
+        let entries = parse_content(
+            "* @everyone\n*.rs @rust #prod\n/legacy/ @old #deprecated\n",
+            Path::new("/repo/CODEOWNERS"),
+        )?;
+        let files = [
+            PathBuf::from("/repo/src/main.rs"),
+            PathBuf::from("/repo/legacy/run.sh"),
+            PathBuf::from("/repo/README.md"),
+        ];
+        let ctx = Context::new(AppConfig::default());
+        let cache = |filter: RuleFilter| -> Result<CodeownersCache> {
+            let matcher = Matcher::build_with_options(
+                &entries,
+                ctx.config.matcher,
+                ctx.config.match_options(),
+            );
+            let mut rule_matches = vec![Vec::new(); entries.len()];
+            let mut file_entries = Vec::new();
+            for (position, path) in files.iter().enumerate() {
+                let (owners, tags, rules) =
+                    resolve_file_with_policy(path, &entries, &matcher, Default::default())?;
+                for rule in rules {
+                    rule_matches[rule].push(position);
+                }
+                file_entries.push(FileEntry {
+                    path: path.clone(),
+                    owners,
+                    tags,
+                    generated: false,
+                    inline: None,
+                });
+            }
+            let cache = CodeownersCache::new([0; 32], entries.clone(), file_entries, rule_matches);
+            filter.apply(&ctx, cache)
+        };
+        let owner = |cache: &CodeownersCache, path: &str| {
+            cache.file(Path::new(path)).unwrap().owners[0]
+                .identifier
+                .clone()
+        };
+
+        let ignored = cache(RuleFilter::new(&["#deprecated".to_string()], &[]))?;
+        assert_eq!(ignored.entries.len(), 2);
+        assert_eq!(owner(&ignored, "/repo/legacy/run.sh"), "@everyone");
+        assert_eq!(owner(&ignored, "/repo/src/main.rs"), "@rust");
+        assert_eq!(ignored.files_for_rule(0).len(), 2);
+
+        let only = cache(RuleFilter::new(&[], &["prod".to_string()]))?;
+        assert_eq!(only.entries.len(), 1);
+        assert_eq!(owner(&only, "/repo/src/main.rs"), "@rust");
+        assert!(only
+            .file(Path::new("/repo/README.md"))
+            .unwrap()
+            .owners
+            .is_empty());
+        Ok(())
+    }
+}
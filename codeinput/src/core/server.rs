@@ -0,0 +1,500 @@
+//! HTTP API over the ownership cache, for `codeowners serve`
+//!
+//! Other services ask who owns a file, list the owners and tags, or get the reviewers of
+//! a change, all as JSON:
+//!
+//! - `GET /files/{path}/owners`: the owners and tags of one file
+//! - `GET /owners`: every owner with the files they own, most files first
+//! - `GET /tags`: every tag with its files, most files first
+//! - `POST /changed-files`: the owners, tags and unowned files of `{"files": [...]}`
+//!
+//! The cache is read again whenever its file changes, so a `watch` or `parse` running
+//! alongside keeps the answers current without restarting the server.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::SystemTime;
+
+use axum::extract::{Path as UrlPath, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::cache::load_cache;
+use super::changed::{resolve_changed_files, reviewers};
+use super::context::Context;
+use super::rule_filter;
+use super::types::CodeownersCache;
+use crate::utils::error::{Error, Result};
+
+/// The cache a server answers from, read again when its file changes
+pub struct ServedCache {
+    ctx: Context,
+    repo: PathBuf,
+    path: PathBuf,
+    current: RwLock<Loaded>,
+}
+
+#[derive(Clone)]
+struct Loaded {
+    /// Modification time and length of the cache file when it was read
+    stamp: Option<(SystemTime, u64)>,
+    cache: Arc<CodeownersCache>,
+}
+
+impl ServedCache {
+    /// Serve `cache` of the repository at `repo`, as read from the file at `path`
+    pub fn new(ctx: Context, repo: &Path, path: &Path, cache: CodeownersCache) -> Self {
+        ServedCache {
+            ctx,
+            repo: repo.to_path_buf(),
+            path: path.to_path_buf(),
+            current: RwLock::new(Loaded {
+                stamp: stamp(path),
+                cache: Arc::new(cache),
+            }),
+        }
+    }
+
+    /// The cache, read again first if its file has changed
+    ///
+    /// While the file is missing or cannot be read, the cache read last is served. The
+    /// file is read without holding the lock, so requests keep being answered from the
+    /// previous cache meanwhile; this blocks, see [`current`] for async handlers.
+    pub fn get(&self) -> Arc<CodeownersCache> {
+        let stamp = stamp(&self.path);
+        // A request panicking while it swapped the cache leaves it whole, so a poisoned
+        // lock is still used
+        let loaded = self
+            .current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        if stamp.is_none() || loaded.stamp == stamp {
+            return loaded.cache;
+        }
+
+        match load_cache(&self.path).and_then(|cache| rule_filter::apply_current(&self.ctx, cache))
+        {
+            Ok(cache) => {
+                log::info!("Reloaded the cache from {}", self.path.display());
+                let cache = Arc::new(cache);
+                *self.current.write().unwrap_or_else(PoisonError::into_inner) = Loaded {
+                    stamp,
+                    cache: cache.clone(),
+                };
+                cache
+            }
+            Err(e) => {
+                log::warn!(
+                    "Serving the previous cache, {} cannot be read: {}",
+                    self.path.display(),
+                    e
+                );
+                loaded.cache
+            }
+        }
+    }
+}
+
+/// [`ServedCache::get`] on a blocking thread, keeping a reload off the async workers
+async fn current(served: &Arc<ServedCache>) -> std::result::Result<Arc<CodeownersCache>, ApiError> {
+    let served = served.clone();
+    tokio::task::spawn_blocking(move || served.get())
+        .await
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// The routes of the API, answering from `served`
+pub fn router(served: Arc<ServedCache>) -> Router {
+    Router::new()
+        // `/files/{path}/owners`, see `owners_route`
+        .route("/files/{*path}", get(file_owners))
+        .route("/owners", get(owners))
+        .route("/tags", get(tags))
+        .route("/changed-files", post(changed_files))
+        .with_state(served)
+}
+
+/// An error answered as `{"error": message}`
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        ApiError(StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+struct ChangedFiles {
+    /// Relative to the repository root
+    files: Vec<PathBuf>,
+}
+
+async fn file_owners(
+    State(served): State<Arc<ServedCache>>, UrlPath(path): UrlPath<String>,
+) -> std::result::Result<Json<Value>, ApiError> {
+    let not_found = |message: String| ApiError(StatusCode::NOT_FOUND, message);
+    let Some(path) = owners_route(&path) else {
+        return Err(not_found(format!("No route for /files/{}", path)));
+    };
+    let cache = current(&served).await?;
+    file_report(&cache, &served.repo, Path::new(path))
+        .map(Json)
+        .ok_or_else(|| not_found(format!("File {} is not in the ownership cache", path)))
+}
+
+/// The file of a `/files/{path}/owners` route, given what follows `/files/`
+///
+/// A wildcard must end an axum route, so the `/owners` suffix is matched here.
+fn owners_route(route: &str) -> Option<&str> {
+    route
+        .strip_suffix("/owners")
+        .filter(|path| !path.is_empty())
+}
+
+async fn owners(
+    State(served): State<Arc<ServedCache>>,
+) -> std::result::Result<Json<Value>, ApiError> {
+    let cache = current(&served).await?;
+    Ok(Json(owner_report(&cache)))
+}
+
+async fn tags(
+    State(served): State<Arc<ServedCache>>,
+) -> std::result::Result<Json<Value>, ApiError> {
+    let cache = current(&served).await?;
+    Ok(Json(tag_report(&cache)))
+}
+
+async fn changed_files(
+    State(served): State<Arc<ServedCache>>, Json(body): Json<ChangedFiles>,
+) -> std::result::Result<Json<Value>, ApiError> {
+    if let Some(path) = body
+        .files
+        .iter()
+        .find(|path| !within_repo(&served.repo, path))
+    {
+        return Err(ApiError(
+            StatusCode::BAD_REQUEST,
+            format!("Path {} is not within the repository", path.display()),
+        ));
+    }
+    let cache = current(&served).await?;
+    Ok(Json(changed_report(
+        &served.ctx,
+        &served.repo,
+        &cache,
+        &body.files,
+    )?))
+}
+
+/// Whether `path` names a file within `repo`
+///
+/// Clients name files relative to the repository, which are then checked for and read, so
+/// absolute paths, `..` and symbolic links leading out of it are refused. A file that does
+/// not exist is checked through the closest directory above it that does.
+fn within_repo(repo: &Path, path: &Path) -> bool {
+    let relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    let Ok(root) = repo.canonicalize() else {
+        return false;
+    };
+    relative
+        && repo
+            .join(path)
+            .ancestors()
+            .find_map(|dir| dir.canonicalize().ok())
+            .is_some_and(|real| real.starts_with(&root))
+}
+
+/// The owners and tags of the file at `path`, relative to `repo`, if it is cached
+fn file_report(cache: &CodeownersCache, repo: &Path, path: &Path) -> Option<Value> {
+    let file = cache.file(path).or_else(|| cache.file(&repo.join(path)))?;
+    Some(json!({
+        "path": path.to_string_lossy(),
+        "owners": file.owners,
+        "tags": file.tags.iter().map(|tag| &tag.0).collect::<Vec<_>>(),
+    }))
+}
+
+/// Every owner with their files, as `list-owners --format json` prints them
+fn owner_report(cache: &CodeownersCache) -> Value {
+    let mut owners: Vec<_> = cache.owners_map.iter().collect();
+    owners.sort_by(|(a, a_paths), (b, b_paths)| {
+        b_paths
+            .len()
+            .cmp(&a_paths.len())
+            .then_with(|| a.identifier.cmp(&b.identifier))
+    });
+    owners
+        .iter()
+        .map(|(owner, paths)| {
+            json!({
+                "identifier": owner.identifier,
+                "type": format!("{:?}", owner.owner_type),
+                "file_count": paths.len(),
+                "files": paths.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// Every tag with its files, as `list-tags --format json` prints them
+fn tag_report(cache: &CodeownersCache) -> Value {
+    let mut tags: Vec<_> = cache.tags_map.iter().collect();
+    tags.sort_by(|(a, a_paths), (b, b_paths)| {
+        b_paths
+            .len()
+            .cmp(&a_paths.len())
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    tags.iter()
+        .map(|(tag, paths)| {
+            json!({
+                "name": tag.0,
+                "file_count": paths.len(),
+                "files": paths.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// The reviewers of `files`, as `changed-files --format json` prints them without a base
+fn changed_report(
+    ctx: &Context, repo: &Path, cache: &CodeownersCache, files: &[PathBuf],
+) -> Result<Value> {
+    let files = resolve_changed_files(ctx, repo, files, None, cache)?;
+    let reviewers = reviewers(&files);
+    Ok(json!({
+        "files": files,
+        "owners": reviewers.owners,
+        "tags": reviewers.tags,
+        "unowned": reviewers.unowned,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cache::save_cache;
+    use crate::core::types::{CacheEncoding, FileEntry, Owner, OwnerType, Tag};
+    use crate::utils::app_config::AppConfig;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    fn file(path: &str, owners: &[&str], tags: &[&str]) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners
+                .iter()
+                .map(|identifier| Owner {
+                    identifier: identifier.to_string(),
+                    owner_type: OwnerType::User,
+                })
+                .collect(),
+            tags: tags.iter().map(|tag| Tag(tag.to_string())).collect(),
+            generated: false,
+            inline: None,
+        }
+    }
+
+    fn cache(files: Vec<FileEntry>) -> CodeownersCache {
+        CodeownersCache::new([0; 32], Vec::new(), files, Vec::new())
+    }
+
+    /// A repository holding `src/lib.rs` and `README.md`, served from a cache of the first
+    fn served_repo() -> (TempDir, Arc<ServedCache>) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        std::fs::write(dir.path().join("README.md"), "").unwrap();
+        let lib = dir.path().join("src/lib.rs");
+        let cached = cache(vec![file(lib.to_str().unwrap(), &["@alice"], &["rust"])]);
+        let path = dir.path().join(".codeowners.cache");
+        save_cache(&cached, &path, CacheEncoding::Bincode).unwrap();
+
+        let ctx = Context::new(AppConfig::default());
+        let served = ServedCache::new(ctx, dir.path(), &path, cached);
+        (dir, Arc::new(served))
+    }
+
+    /// Answer `request` with the routes over `served`, as its status and JSON body
+    fn call(served: &Arc<ServedCache>, request: Request<Body>) -> (StatusCode, Value) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let response = router(served.clone()).oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+        })
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri).body(Body::empty()).unwrap()
+    }
+
+    fn changed(files: Value) -> Request<Body> {
+        Request::post("/changed-files")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "files": files }).to_string()))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_owners_route() {
+        assert_eq!(owners_route("src/lib.rs/owners"), Some("src/lib.rs"));
+        assert_eq!(owners_route("src/owners/owners"), Some("src/owners"));
+        assert_eq!(owners_route("src/lib.rs"), None);
+        assert_eq!(owners_route("/owners"), None);
+    }
+
+    #[test]
+    fn test_routes() {
+        let (_dir, served) = served_repo();
+
+        let (status, body) = call(&served, get("/files/src/lib.rs/owners"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owners"][0]["identifier"], "@alice");
+        assert_eq!(body["tags"], json!(["rust"]));
+        let (status, body) = call(&served, get("/files/src/gone.rs/owners"));
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(body["error"].as_str().unwrap().contains("src/gone.rs"));
+        assert_eq!(
+            call(&served, get("/files/src/lib.rs")).0,
+            StatusCode::NOT_FOUND
+        );
+
+        let (status, body) = call(&served, get("/owners"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["identifier"], "@alice");
+        let (status, body) = call(&served, get("/tags"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["name"], "rust");
+
+        let (status, body) = call(&served, changed(json!(["src/lib.rs", "README.md"])));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owners"][0]["identifier"], "@alice");
+        assert_eq!(body["unowned"], json!(["README.md"]));
+    }
+
+    #[test]
+    fn test_changed_files_outside_the_repository() {
+        let (dir, served) = served_repo();
+
+        for outside in ["/dev/zero", "../README.md", "src/../../README.md"] {
+            let (status, body) = call(&served, changed(json!(["src/lib.rs", outside])));
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", outside);
+            assert!(body["error"].as_str().unwrap().contains(outside));
+        }
+
+        // Nor through a symbolic link
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/dev", dir.path().join("devices")).unwrap();
+            let (status, _) = call(&served, changed(json!(["devices/zero"])));
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        #[cfg(not(unix))]
+        let _ = dir;
+    }
+
+    #[test]
+    fn test_reports() {
+        let cache = cache(vec![
+            file("./src/lib.rs", &["@alice", "@bob"], &["rust"]),
+            file("./src/main.rs", &["@bob"], &["rust"]),
+            file("./README.md", &[], &[]),
+        ]);
+
+        let report = file_report(&cache, Path::new("."), Path::new("src/lib.rs")).unwrap();
+        assert_eq!(report["path"], "src/lib.rs");
+        assert_eq!(report["owners"][1]["identifier"], "@bob");
+        assert_eq!(report["tags"], json!(["rust"]));
+        assert!(file_report(&cache, Path::new("."), Path::new("src/gone.rs")).is_none());
+
+        let owners = owner_report(&cache);
+        let identifiers: Vec<_> = owners
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|owner| {
+                (
+                    owner["identifier"].as_str().unwrap(),
+                    owner["file_count"].as_u64().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(identifiers, [("@bob", 2), ("@alice", 1)]);
+
+        let tags = tag_report(&cache);
+        assert_eq!(tags[0]["name"], "rust");
+        assert_eq!(tags[0]["file_count"], 2);
+    }
+
+    #[test]
+    fn test_reloads_when_the_file_changes() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(".codeowners.cache");
+        let first = cache(vec![file("./a.rs", &["@alice"], &[])]);
+        save_cache(&first, &path, CacheEncoding::Bincode).unwrap();
+
+        let ctx = Context::new(AppConfig::default());
+        let served = ServedCache::new(ctx, Path::new("."), &path, first);
+        assert!(served.get().file(Path::new("./a.rs")).is_some());
+
+        let second = cache(vec![
+            file("./a.rs", &["@alice"], &[]),
+            file("./b.rs", &["@bob"], &[]),
+        ]);
+        save_cache(&second, &path, CacheEncoding::Bincode).unwrap();
+        assert!(served.get().file(Path::new("./b.rs")).is_some());
+
+        // A missing file keeps the cache read last
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(served.get().len(), 2);
+    }
+
+    #[test]
+    fn test_routes_answer_from_the_reloaded_cache() {
+        let (dir, served) = served_repo();
+        assert_eq!(call(&served, get("/owners")).1.as_array().unwrap().len(), 1);
+
+        let lib = dir.path().join("src/lib.rs");
+        let readme = dir.path().join("README.md");
+        let rewritten = cache(vec![
+            file(lib.to_str().unwrap(), &["@alice"], &["rust"]),
+            file(readme.to_str().unwrap(), &["@bob"], &["docs"]),
+        ]);
+        save_cache(&rewritten, &served.path, CacheEncoding::Bincode).unwrap();
+
+        let (status, body) = call(&served, get("/files/README.md/owners"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owners"][0]["identifier"], "@bob");
+        assert_eq!(call(&served, get("/tags")).1.as_array().unwrap().len(), 2);
+        let (_, body) = call(&served, changed(json!(["README.md"])));
+        assert_eq!(body["owners"][0]["identifier"], "@bob");
+    }
+}
@@ -0,0 +1,265 @@
+//! Point-in-time ownership summaries for tracking trends
+//!
+//! `codeowners snapshot save` records the file counts of every owner and tag, one JSON
+//! file per snapshot in the snapshot directory. Taken regularly (from CI, say), they show
+//! how coverage develops. A [`RetentionPolicy`] thins them out as they age so the store
+//! stays small: every day's latest snapshot is kept for a while, then only every
+//! week's.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::types::{CodeownersCache, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Format of snapshot file names, which sort by time
+const FILE_NAME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Ownership of a repository at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub taken_at: DateTime<Utc>,
+    /// HEAD commit when the snapshot was taken, if any
+    pub commit: Option<String>,
+    pub files: usize,
+    pub unowned_files: usize,
+    /// Files owned by each owner
+    pub owners: BTreeMap<String, usize>,
+    /// Files carrying each tag
+    pub tags: BTreeMap<String, usize>,
+}
+
+impl Snapshot {
+    /// Summarize a cache, leaving out generated files
+    pub fn from_cache(
+        cache: &CodeownersCache, taken_at: DateTime<Utc>, commit: Option<String>,
+    ) -> Snapshot {
+        let mut owners = BTreeMap::new();
+        let mut tags = BTreeMap::new();
+        let mut files = 0;
+        let mut unowned_files = 0;
+        for file in cache.files.iter().filter(|file| !file.generated) {
+            files += 1;
+            let mut owned = false;
+            for owner in &file.owners {
+                if owner.owner_type != OwnerType::Unowned {
+                    owned = true;
+                    *owners.entry(owner.identifier.clone()).or_insert(0) += 1;
+                }
+            }
+            if !owned {
+                unowned_files += 1;
+            }
+            for tag in &file.tags {
+                *tags.entry(tag.0.clone()).or_insert(0) += 1;
+            }
+        }
+
+        Snapshot {
+            taken_at,
+            commit,
+            files,
+            unowned_files,
+            owners,
+            tags,
+        }
+    }
+
+    /// File name the snapshot is stored under
+    pub fn file_name(&self) -> String {
+        format!("{}.json", self.taken_at.format(FILE_NAME_FORMAT))
+    }
+}
+
+/// A snapshot file in the store
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSnapshot {
+    pub path: PathBuf,
+    pub taken_at: DateTime<Utc>,
+}
+
+/// The snapshot files in `dir`, oldest first; a missing directory holds none
+///
+/// Only the file names are read. Files not named like snapshots are left alone.
+pub fn list(dir: &Path) -> Result<Vec<StoredSnapshot>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let taken_at = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".json"))
+            .and_then(|stem| NaiveDateTime::parse_from_str(stem, FILE_NAME_FORMAT).ok());
+        if let Some(taken_at) = taken_at {
+            snapshots.push(StoredSnapshot {
+                path,
+                taken_at: taken_at.and_utc(),
+            });
+        }
+    }
+    snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+    Ok(snapshots)
+}
+
+/// Read a stored snapshot
+pub fn load(path: &Path) -> Result<Snapshot> {
+    let contents = std::fs::read_to_string(path)?;
+    serde_json::from_str(&contents).map_err(|e| {
+        Error::new(&format!(
+            "Failed to parse snapshot {}: {}",
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// How long snapshots are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Snapshots younger than this many days keep the latest of each day
+    pub daily_days: u32,
+    /// Older snapshots keep the latest of each ISO week for this many more weeks; 0 keeps
+    /// weekly snapshots forever
+    pub weekly_weeks: u32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            daily_days: 90,
+            weekly_weeks: 0,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Which of `taken` (times of snapshots, in any order) to keep at `now`
+    ///
+    /// The result is indexed like `taken`. Within a day, or a week once past the daily
+    /// window, the latest snapshot is kept. The newest snapshot is always kept.
+    pub fn keep(&self, taken: &[DateTime<Utc>], now: DateTime<Utc>) -> Vec<bool> {
+        let daily_cutoff = now - Duration::days(self.daily_days.into());
+        let weekly_cutoff = (self.weekly_weeks > 0)
+            .then(|| daily_cutoff - Duration::weeks(self.weekly_weeks.into()));
+
+        // Newest first, so the first snapshot seen in each period is the one kept
+        let mut order: Vec<usize> = (0..taken.len()).collect();
+        order.sort_by(|a, b| taken[*b].cmp(&taken[*a]));
+
+        let mut keep = vec![false; taken.len()];
+        let mut periods = HashSet::new();
+        for (rank, index) in order.into_iter().enumerate() {
+            let time = taken[index];
+            let period = if time >= daily_cutoff {
+                Some((time.year(), time.ordinal(), false))
+            } else if weekly_cutoff.is_none_or(|cutoff| time >= cutoff) {
+                let week = time.iso_week();
+                Some((week.year(), week.week(), true))
+            } else {
+                None
+            };
+            let first_in_period = period.is_some_and(|period| periods.insert(period));
+            keep[index] = rank == 0 || first_in_period;
+        }
+        keep
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{FileEntry, Owner, Tag};
+
+    fn at(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_snapshot_counts_owners_tags_and_unowned_files() {
+        let owner = |identifier: &str, owner_type| Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        };
+        let files = vec![
+            FileEntry {
+                path: PathBuf::from("a.rs"),
+                owners: vec![
+                    owner("@team", OwnerType::Team),
+                    owner("@bob", OwnerType::User),
+                ],
+                tags: vec![Tag("core".to_string())],
+                generated: false,
+                inline: None,
+            },
+            FileEntry {
+                path: PathBuf::from("b.rs"),
+                owners: vec![owner("@team", OwnerType::Team)],
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            },
+            FileEntry {
+                path: PathBuf::from("c.txt"),
+                owners: vec![owner("NOBODY", OwnerType::Unowned)],
+                tags: Vec::new(),
+                generated: false,
+                inline: None,
+            },
+        ];
+        let cache = CodeownersCache::new([0; 32], Vec::new(), files, Vec::new());
+        let snapshot = Snapshot::from_cache(&cache, at("2024-03-05T10:20:30Z"), None);
+
+        assert_eq!(snapshot.files, 3);
+        assert_eq!(snapshot.unowned_files, 1);
+        assert_eq!(snapshot.owners["@team"], 2);
+        assert_eq!(snapshot.owners["@bob"], 1);
+        assert_eq!(snapshot.tags["core"], 1);
+        assert_eq!(snapshot.file_name(), "20240305T102030Z.json");
+    }
+
+    #[test]
+    fn test_retention_keeps_daily_then_weekly() {
+        let now = at("2024-06-30T12:00:00Z");
+        let policy = RetentionPolicy {
+            daily_days: 10,
+            weekly_weeks: 4,
+        };
+        let taken = [
+            at("2024-06-29T08:00:00Z"), // two on one recent day: the later one is kept
+            at("2024-06-29T18:00:00Z"),
+            at("2024-06-28T08:00:00Z"),
+            at("2024-06-11T08:00:00Z"), // two in one older ISO week: the later one is kept
+            at("2024-06-13T08:00:00Z"),
+            at("2024-06-03T08:00:00Z"),
+            at("2024-04-01T08:00:00Z"), // beyond the weekly window
+        ];
+
+        assert_eq!(
+            policy.keep(&taken, now),
+            vec![false, true, true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_retention_keeps_weekly_forever_and_the_newest_snapshot() {
+        let now = at("2024-06-30T12:00:00Z");
+        let policy = RetentionPolicy {
+            daily_days: 1,
+            weekly_weeks: 0,
+        };
+        let taken = [at("2020-01-01T00:00:00Z"), at("2020-01-02T00:00:00Z")];
+        assert_eq!(policy.keep(&taken, now), vec![false, true]);
+
+        let policy = RetentionPolicy {
+            daily_days: 1,
+            weekly_weeks: 1,
+        };
+        assert_eq!(policy.keep(&taken, now), vec![false, true]);
+    }
+}
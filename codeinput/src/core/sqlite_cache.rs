@@ -0,0 +1,556 @@
+//! Cache stored in a SQLite database, read in part without loading it
+//!
+//! A cache written with [`CacheEncoding::Sqlite`](super::types::CacheEncoding) holds what
+//! the other encodings do, in tables: `meta` for the header and precedence policy,
+//! `entries` for the rules and `files` for the file entries, in order. The owners and tags
+//! maps are the `owners` and `tags` tables with `file_owners` and `file_tags`, indexed by
+//! owner and tag, and `rule_matches` records the rules that decided each file. Commands
+//! that need only the files of some owners, or a single file, read them with
+//! [`SqliteCache::query`] instead of deserializing every file of a large repository.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::cache::CacheQuery;
+use super::path_index::PathIndex;
+use super::types::{
+    BuildInfo, CodeownersCache, CodeownersEntry, FileEntry, Owner, OwnerType, PrecedencePolicy,
+    Tag, CACHE_FORMAT, CACHE_VERSION,
+};
+use crate::utils::error::{Error, Result};
+
+const SCHEMA: &str = "
+CREATE TABLE meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+CREATE TABLE entries (id INTEGER PRIMARY KEY, entry TEXT NOT NULL);
+CREATE TABLE files (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    owners TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    generated INTEGER NOT NULL,
+    inline TEXT
+);
+CREATE TABLE owners (
+    id INTEGER PRIMARY KEY,
+    identifier TEXT NOT NULL,
+    owner_type TEXT NOT NULL
+);
+CREATE TABLE tags (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE);
+CREATE TABLE file_owners (
+    owner INTEGER NOT NULL,
+    file INTEGER NOT NULL,
+    PRIMARY KEY (owner, file)
+) WITHOUT ROWID;
+CREATE TABLE file_tags (
+    tag INTEGER NOT NULL,
+    file INTEGER NOT NULL,
+    PRIMARY KEY (tag, file)
+) WITHOUT ROWID;
+CREATE TABLE rule_matches (
+    file INTEGER NOT NULL,
+    rule INTEGER NOT NULL,
+    PRIMARY KEY (file, rule)
+) WITHOUT ROWID;
+";
+
+fn failed(e: rusqlite::Error) -> Error {
+    Error::with_source("SQLite cache error", Box::new(e))
+}
+
+fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|e| Error::new(&format!("Failed to serialize cache: {}", e)))
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T> {
+    serde_json::from_str(json)
+        .map_err(|e| Error::new(&format!("Failed to deserialize SQLite cache: {}", e)))
+}
+
+fn path_text(path: &Path) -> Result<&str> {
+    path.to_str().ok_or_else(|| {
+        Error::new(&format!(
+            "{} is not valid UTF-8 and cannot be stored in a SQLite cache",
+            path.display()
+        ))
+    })
+}
+
+/// Writer of a SQLite cache, a file entry at a time, in one transaction
+pub struct SqliteWriter {
+    conn: Connection,
+    path: PathBuf,
+    written: usize,
+    rules: usize,
+    owners: HashMap<Owner, i64>,
+    tags: HashMap<Tag, i64>,
+}
+
+impl SqliteWriter {
+    /// Create the database at `path`, which must not exist yet, with the cache header
+    pub fn create(
+        path: &Path, build: &BuildInfo, hash: &[u8; 32], entries: &[CodeownersEntry],
+        precedence: PrecedencePolicy,
+    ) -> Result<Self> {
+        let conn = Connection::open(path).map_err(failed)?;
+        // The file is renamed into place once complete, so it needs no journal
+        conn.execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF; BEGIN;")
+            .map_err(failed)?;
+        conn.execute_batch(SCHEMA).map_err(failed)?;
+
+        {
+            let mut meta = conn
+                .prepare("INSERT INTO meta (key, value) VALUES (?1, ?2)")
+                .map_err(failed)?;
+            for (key, value) in [
+                ("format", to_json(CACHE_FORMAT)?),
+                ("version", to_json(&CACHE_VERSION)?),
+                ("build", to_json(build)?),
+                ("hash", to_json(hash)?),
+                ("precedence", to_json(&precedence)?),
+            ] {
+                meta.execute(params![key, value]).map_err(failed)?;
+            }
+
+            let mut insert = conn
+                .prepare("INSERT INTO entries (id, entry) VALUES (?1, ?2)")
+                .map_err(failed)?;
+            for (id, entry) in entries.iter().enumerate() {
+                insert
+                    .execute(params![id as i64, to_json(entry)?])
+                    .map_err(failed)?;
+            }
+        }
+
+        Ok(SqliteWriter {
+            conn,
+            path: path.to_path_buf(),
+            written: 0,
+            rules: entries.len(),
+            owners: HashMap::new(),
+            tags: HashMap::new(),
+        })
+    }
+
+    /// Append a file entry, along with the indices of the rules that decided its ownership
+    pub fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        let id = self.written as i64;
+        let row = params![
+            id,
+            path_text(&file.path)?,
+            to_json(&file.owners)?,
+            to_json(&file.tags)?,
+            file.generated,
+            file.inline.as_ref().map(to_json).transpose()?,
+        ];
+        self.conn
+            .prepare_cached(
+                "INSERT INTO files (id, path, owners, tags, generated, inline)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )
+            .and_then(|mut insert| insert.execute(row))
+            .map_err(failed)?;
+
+        for owner in &file.owners {
+            let owner_id = match self.owners.get(owner) {
+                Some(&owner_id) => owner_id,
+                None => {
+                    let owner_id = self.owners.len() as i64;
+                    self.conn
+                        .execute(
+                            "INSERT INTO owners (id, identifier, owner_type) VALUES (?1, ?2, ?3)",
+                            params![owner_id, owner.identifier, owner.owner_type.to_string()],
+                        )
+                        .map_err(failed)?;
+                    self.owners.insert(owner.clone(), owner_id);
+                    owner_id
+                }
+            };
+            self.conn
+                .prepare_cached("INSERT OR IGNORE INTO file_owners (owner, file) VALUES (?1, ?2)")
+                .and_then(|mut insert| insert.execute(params![owner_id, id]))
+                .map_err(failed)?;
+        }
+
+        for tag in &file.tags {
+            let tag_id = match self.tags.get(tag) {
+                Some(&tag_id) => tag_id,
+                None => {
+                    let tag_id = self.tags.len() as i64;
+                    self.conn
+                        .execute(
+                            "INSERT INTO tags (id, name) VALUES (?1, ?2)",
+                            params![tag_id, tag.0],
+                        )
+                        .map_err(failed)?;
+                    self.tags.insert(tag.clone(), tag_id);
+                    tag_id
+                }
+            };
+            self.conn
+                .prepare_cached("INSERT OR IGNORE INTO file_tags (tag, file) VALUES (?1, ?2)")
+                .and_then(|mut insert| insert.execute(params![tag_id, id]))
+                .map_err(failed)?;
+        }
+
+        for &rule in rules {
+            if rule >= self.rules {
+                return Err(Error::new(&format!(
+                    "Rule {} is not in the cache header",
+                    rule
+                )));
+            }
+            self.conn
+                .prepare_cached("INSERT OR IGNORE INTO rule_matches (file, rule) VALUES (?1, ?2)")
+                .and_then(|mut insert| insert.execute(params![id, rule as i64]))
+                .map_err(failed)?;
+        }
+        self.written += 1;
+
+        Ok(())
+    }
+
+    /// Commit the cache and flush it to disk
+    pub fn finish(self) -> Result<()> {
+        self.conn.execute_batch("COMMIT;").map_err(failed)?;
+        self.conn.close().map_err(|(_, e)| failed(e))?;
+        std::fs::File::open(&self.path)?.sync_all()?;
+        Ok(())
+    }
+}
+
+impl super::cache::FileSink for SqliteWriter {
+    fn write_file(&mut self, file: &FileEntry, rules: &[usize]) -> Result<()> {
+        SqliteWriter::write_file(self, file, rules)
+    }
+}
+
+/// A SQLite cache opened for reading, with its header and rules read
+pub struct SqliteCache {
+    conn: Connection,
+    /// Layout of the cache, see [`CACHE_VERSION`]
+    pub version: u32,
+    pub build: BuildInfo,
+    pub hash: [u8; 32],
+    pub precedence: PrecedencePolicy,
+    pub entries: Vec<CodeownersEntry>,
+}
+
+impl SqliteCache {
+    /// Open the cache at `path`, reading only its header and rules
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn =
+            Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(failed)?;
+        let meta = |key: &str| -> Result<String> {
+            conn.query_row("SELECT value FROM meta WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(failed)?
+            .ok_or_else(|| Error::new(&format!("SQLite cache has no {}", key)))
+        };
+        if from_json::<String>(&meta("format")?)? != CACHE_FORMAT {
+            return Err(Error::new("Not a codeowners cache"));
+        }
+        let version = from_json(&meta("version")?)?;
+        let build = from_json(&meta("build")?)?;
+        let hash = from_json(&meta("hash")?)?;
+        let precedence = from_json(&meta("precedence")?)?;
+
+        let entries = {
+            let mut select = conn
+                .prepare("SELECT entry FROM entries ORDER BY id")
+                .map_err(failed)?;
+            let rows = select
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(failed)?;
+            rows.map(|entry| from_json(&entry.map_err(failed)?))
+                .collect::<Result<Vec<CodeownersEntry>>>()?
+        };
+
+        Ok(SqliteCache {
+            conn,
+            version,
+            build,
+            hash,
+            precedence,
+            entries,
+        })
+    }
+
+    /// Number of files, and of those neither generated nor owned, as the run summary
+    /// counts them
+    pub fn counts(&self) -> Result<(usize, usize)> {
+        let files: i64 = self
+            .conn
+            .query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))
+            .map_err(failed)?;
+        let unowned: i64 = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM files WHERE NOT generated AND NOT EXISTS (
+                     SELECT 1 FROM file_owners JOIN owners ON owners.id = file_owners.owner
+                     WHERE file_owners.file = files.id AND owners.owner_type != ?1
+                 )",
+                [OwnerType::Unowned.to_string()],
+                |row| row.get(0),
+            )
+            .map_err(failed)?;
+        Ok((files as usize, unowned as usize))
+    }
+
+    /// The whole cache
+    pub fn load(self) -> Result<CodeownersCache> {
+        let files = {
+            let mut select = self
+                .conn
+                .prepare("SELECT path, owners, tags, generated, inline FROM files ORDER BY id")
+                .map_err(failed)?;
+            let rows = select.query_map([], file_columns).map_err(failed)?;
+            rows.map(|row| file_entry(row.map_err(failed)?))
+                .collect::<Result<Vec<FileEntry>>>()?
+        };
+
+        let mut rule_matches = vec![Vec::new(); self.entries.len()];
+        {
+            let mut select = self
+                .conn
+                .prepare("SELECT rule, file FROM rule_matches ORDER BY rule, file")
+                .map_err(failed)?;
+            let rows = select
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+                .map_err(failed)?;
+            for row in rows {
+                let (rule, file) = row.map_err(failed)?;
+                rule_matches
+                    .get_mut(rule as usize)
+                    .ok_or_else(|| Error::new("SQLite cache matches a rule it does not have"))?
+                    .push(file as usize);
+            }
+        }
+
+        self.into_cache(files, rule_matches)
+    }
+
+    /// The files `query` selects, in cache order, with the rules and the rule matches
+    /// between them
+    pub fn query(self, query: CacheQuery<'_>) -> Result<CodeownersCache> {
+        let mut ids = BTreeSet::new();
+        match query {
+            CacheQuery::Owners(patterns) => {
+                let mut owners = self
+                    .conn
+                    .prepare("SELECT id, identifier FROM owners")
+                    .map_err(failed)?;
+                let rows = owners
+                    .query_map([], |row| {
+                        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                    })
+                    .map_err(failed)?;
+                let mut files = self
+                    .conn
+                    .prepare("SELECT file FROM file_owners WHERE owner = ?1")
+                    .map_err(failed)?;
+                for row in rows {
+                    let (owner, identifier) = row.map_err(failed)?;
+                    if !patterns.iter().any(|pattern| identifier.contains(pattern)) {
+                        continue;
+                    }
+                    for file in files
+                        .query_map([owner], |row| row.get::<_, i64>(0))
+                        .map_err(failed)?
+                    {
+                        ids.insert(file.map_err(failed)?);
+                    }
+                }
+            }
+            CacheQuery::Paths(paths) => {
+                let mut select = self
+                    .conn
+                    .prepare("SELECT id FROM files WHERE path = ?1")
+                    .map_err(failed)?;
+                for path in paths {
+                    let Some(path) = path.to_str() else {
+                        continue;
+                    };
+                    if let Some(id) = select
+                        .query_row([path], |row| row.get::<_, i64>(0))
+                        .optional()
+                        .map_err(failed)?
+                    {
+                        ids.insert(id);
+                    }
+                }
+            }
+        }
+
+        let mut files = Vec::with_capacity(ids.len());
+        let mut rule_matches = vec![Vec::new(); self.entries.len()];
+        {
+            let mut select = self
+                .conn
+                .prepare("SELECT path, owners, tags, generated, inline FROM files WHERE id = ?1")
+                .map_err(failed)?;
+            let mut rules = self
+                .conn
+                .prepare("SELECT rule FROM rule_matches WHERE file = ?1")
+                .map_err(failed)?;
+            for id in ids {
+                let columns = select.query_row([id], file_columns).map_err(failed)?;
+                for rule in rules
+                    .query_map([id], |row| row.get::<_, i64>(0))
+                    .map_err(failed)?
+                {
+                    rule_matches
+                        .get_mut(rule.map_err(failed)? as usize)
+                        .ok_or_else(|| Error::new("SQLite cache matches a rule it does not have"))?
+                        .push(files.len());
+                }
+                files.push(file_entry(columns)?);
+            }
+        }
+
+        self.into_cache(files, rule_matches)
+    }
+
+    fn into_cache(
+        self, files: Vec<FileEntry>, rule_matches: Vec<Vec<usize>>,
+    ) -> Result<CodeownersCache> {
+        let cache = CodeownersCache::from_stored(
+            self.hash,
+            self.entries,
+            files,
+            rule_matches,
+            PathIndex::default(),
+            self.precedence,
+        )
+        .map_err(Error::new)?;
+        Ok(CodeownersCache {
+            build: self.build,
+            ..cache
+        })
+    }
+}
+
+/// Columns of a `files` row: path, owners, tags, generated and inline declaration
+type FileColumns = (String, String, String, bool, Option<String>);
+
+fn file_columns(row: &Row<'_>) -> rusqlite::Result<FileColumns> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn file_entry((path, owners, tags, generated, inline): FileColumns) -> Result<FileEntry> {
+    Ok(FileEntry {
+        path: PathBuf::from(path),
+        owners: from_json(&owners)?,
+        tags: from_json(&tags)?,
+        generated,
+        inline: inline.map(|inline| from_json(&inline)).transpose()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::types::{EntrySpans, OwnerType};
+    use tempfile::TempDir;
+
+    fn owner(identifier: &str) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::Team,
+        }
+    }
+
+    fn file(path: &str, owners: &[&str]) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners.iter().map(|identifier| owner(identifier)).collect(),
+            tags: vec![Tag("core".to_string())],
+            generated: false,
+            inline: None,
+        }
+    }
+
+    fn json<T: Serialize + ?Sized>(value: &T) -> String {
+        to_json(value).unwrap()
+    }
+
+    #[test]
+    fn test_query() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("cache.db");
+        let entries = vec![CodeownersEntry {
+            source_file: PathBuf::from("./CODEOWNERS"),
+            line_number: 1,
+            pattern: "*.rs".to_string(),
+            owners: vec![owner("@org/core")],
+            tags: vec![Tag("core".to_string())],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
+        }];
+        let files = [
+            (file("./src/lib.rs", &["@org/core"]), vec![0]),
+            (file("./README.md", &["@org/docs"]), vec![]),
+            (file("./src/main.rs", &["@org/core", "@alice"]), vec![0]),
+            (file("./LICENSE", &[]), vec![]),
+        ];
+
+        let mut writer = SqliteWriter::create(
+            &path,
+            &BuildInfo::default(),
+            &[7; 32],
+            &entries,
+            PrecedencePolicy::default(),
+        )
+        .unwrap();
+        for (file, rules) in &files {
+            writer.write_file(file, rules).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let full = SqliteCache::open(&path).unwrap().load().unwrap();
+        assert_eq!(full.hash, [7; 32]);
+        assert_eq!(full.entries[0].pattern, "*.rs");
+        assert_eq!(full.files.len(), 4);
+        assert_eq!(json(&full.files[2]), json(&files[2].0));
+        assert_eq!(full.rule_matches, vec![vec![0, 2]]);
+
+        let sqlite = SqliteCache::open(&path).unwrap();
+        assert_eq!(sqlite.version, CACHE_VERSION);
+        assert_eq!(sqlite.counts().unwrap(), (4, 1));
+        let core = sqlite
+            .query(CacheQuery::Owners(&["core", "alice"]))
+            .unwrap();
+        let paths: Vec<_> = core.files.iter().map(|file| file.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("./src/lib.rs"),
+                PathBuf::from("./src/main.rs")
+            ]
+        );
+        assert_eq!(core.rule_matches, vec![vec![0, 1]]);
+
+        let one = SqliteCache::open(&path)
+            .unwrap()
+            .query(CacheQuery::Paths(&[
+                PathBuf::from("README.md"),
+                PathBuf::from("./README.md"),
+            ]))
+            .unwrap();
+        assert_eq!(json(&one.files), json(&[&files[1].0]));
+        assert_eq!(one.rule_matches, vec![Vec::<usize>::new()]);
+    }
+}
@@ -0,0 +1,242 @@
+//! Tag co-occurrence and owner correlation, for cleaning up a tag taxonomy
+//!
+//! Tags that nearly always appear together are candidates for merging; a tag whose files
+//! are mostly owned by one owner may duplicate that owner.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::types::{CodeownersEntry, FileEntry, Owner, OwnerType, Tag};
+
+/// Two tags and how often they appear together
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagPair {
+    pub first: Tag,
+    pub second: Tag,
+    /// Files carrying both tags
+    pub files: usize,
+    /// Rules carrying both tags
+    pub rules: usize,
+    /// Files carrying both tags, as a share of files carrying either (0.0 to 1.0)
+    pub jaccard: f64,
+}
+
+/// The owner most strongly associated with a tag
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagOwner {
+    pub tag: Tag,
+    /// Files carrying the tag
+    pub tag_files: usize,
+    pub owner: Owner,
+    /// Files carrying the tag that the owner owns
+    pub files: usize,
+    /// `files` as a share of the files carrying the tag (0.0 to 1.0)
+    pub coverage: f64,
+    /// `files` as a share of the files the owner owns (0.0 to 1.0)
+    pub exclusivity: f64,
+}
+
+/// Pairs of tags that appear together on at least one file or rule
+///
+/// Sorted by shared files, then shared rules, most first.
+pub fn co_occurrence(files: &[FileEntry], entries: &[CodeownersEntry]) -> Vec<TagPair> {
+    let mut tag_files: HashMap<&str, usize> = HashMap::new();
+    let mut pairs: BTreeMap<(&str, &str), (usize, usize)> = BTreeMap::new();
+
+    for file in files {
+        let tags = tag_names(&file.tags);
+        for tag in &tags {
+            *tag_files.entry(tag).or_default() += 1;
+        }
+        for_each_pair(&tags, |pair| pairs.entry(pair).or_default().0 += 1);
+    }
+    for entry in entries {
+        let tags = tag_names(&entry.tags);
+        for_each_pair(&tags, |pair| pairs.entry(pair).or_default().1 += 1);
+    }
+
+    let mut result: Vec<TagPair> = pairs
+        .into_iter()
+        .map(|((first, second), (files, rules))| {
+            let either = tag_files.get(first).copied().unwrap_or(0)
+                + tag_files.get(second).copied().unwrap_or(0)
+                - files;
+            TagPair {
+                first: Tag(first.to_string()),
+                second: Tag(second.to_string()),
+                files,
+                rules,
+                jaccard: if either == 0 {
+                    0.0
+                } else {
+                    files as f64 / either as f64
+                },
+            }
+        })
+        .collect();
+    result.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| b.rules.cmp(&a.rules)));
+    result
+}
+
+/// For each tag, the owner of the largest share of its files
+///
+/// Ties go to the owner with the higher exclusivity, then the lower identifier. Tags on
+/// unowned files only are left out. Sorted by tag.
+pub fn owner_correlation(files: &[FileEntry]) -> Vec<TagOwner> {
+    let mut tag_files: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut owner_files: HashMap<&str, usize> = HashMap::new();
+    let mut shared: HashMap<(&str, &str), (usize, &Owner)> = HashMap::new();
+
+    for file in files {
+        let tags = tag_names(&file.tags);
+        let owners: BTreeMap<&str, &Owner> = file
+            .owners
+            .iter()
+            .filter(|owner| owner.owner_type != OwnerType::Unowned)
+            .map(|owner| (owner.identifier.as_str(), owner))
+            .collect();
+
+        for identifier in owners.keys() {
+            *owner_files.entry(identifier).or_default() += 1;
+        }
+        for tag in tags {
+            *tag_files.entry(tag).or_default() += 1;
+            for (identifier, owner) in &owners {
+                shared.entry((tag, identifier)).or_insert((0, owner)).0 += 1;
+            }
+        }
+    }
+
+    tag_files
+        .into_iter()
+        .filter_map(|(tag, tag_count)| {
+            shared
+                .iter()
+                .filter(|((t, _), _)| *t == tag)
+                .map(|((_, identifier), (count, owner))| TagOwner {
+                    tag: Tag(tag.to_string()),
+                    tag_files: tag_count,
+                    owner: (*owner).clone(),
+                    files: *count,
+                    coverage: *count as f64 / tag_count as f64,
+                    exclusivity: *count as f64 / owner_files[identifier] as f64,
+                })
+                .max_by(|a, b| {
+                    a.files
+                        .cmp(&b.files)
+                        .then_with(|| a.exclusivity.total_cmp(&b.exclusivity))
+                        .then_with(|| b.owner.identifier.cmp(&a.owner.identifier))
+                })
+        })
+        .collect()
+}
+
+/// Distinct tag names, sorted
+fn tag_names(tags: &[Tag]) -> BTreeSet<&str> {
+    tags.iter().map(|tag| tag.0.as_str()).collect()
+}
+
+/// Call `f` with every unordered pair of distinct tags, in sorted order
+fn for_each_pair<'a>(tags: &BTreeSet<&'a str>, mut f: impl FnMut((&'a str, &'a str))) {
+    let tags: Vec<&str> = tags.iter().copied().collect();
+    for (i, first) in tags.iter().enumerate() {
+        for second in &tags[i + 1..] {
+            f((first, second));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn owner(identifier: &str) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::User,
+        }
+    }
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|name| Tag(name.to_string())).collect()
+    }
+
+    fn file(path: &str, owners: &[&str], tag_names: &[&str]) -> FileEntry {
+        FileEntry {
+            path: PathBuf::from(path),
+            owners: owners.iter().map(|o| owner(o)).collect(),
+            tags: tags(tag_names),
+            generated: false,
+            inline: None,
+        }
+    }
+
+    fn entry(tag_names: &[&str]) -> CodeownersEntry {
+        CodeownersEntry {
+            source_file: PathBuf::from("CODEOWNERS"),
+            line_number: 0,
+            pattern: "*".to_string(),
+            owners: vec![owner("@a")],
+            tags: tags(tag_names),
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        }
+    }
+
+    #[test]
+    fn test_co_occurrence_counts_files_and_rules() {
+        let files = [
+            file("a.rs", &["@a"], &["api", "critical"]),
+            file("b.rs", &["@a"], &["critical", "api"]),
+            file("c.rs", &["@b"], &["api"]),
+            file("d.rs", &["@b"], &["docs"]),
+        ];
+        let entries = [entry(&["api", "critical"]), entry(&["docs", "api"])];
+
+        let pairs = co_occurrence(&files, &entries);
+        assert_eq!(pairs.len(), 2);
+
+        assert_eq!(pairs[0].first.0, "api");
+        assert_eq!(pairs[0].second.0, "critical");
+        assert_eq!(pairs[0].files, 2);
+        assert_eq!(pairs[0].rules, 1);
+        // 2 files with both out of 3 with either
+        assert!((pairs[0].jaccard - 2.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(pairs[1].first.0, "api");
+        assert_eq!(pairs[1].second.0, "docs");
+        assert_eq!(pairs[1].files, 0);
+        assert_eq!(pairs[1].rules, 1);
+        assert_eq!(pairs[1].jaccard, 0.0);
+    }
+
+    #[test]
+    fn test_owner_correlation_picks_the_largest_share() {
+        let files = [
+            file("a.rs", &["@a"], &["api"]),
+            file("b.rs", &["@a", "@b"], &["api"]),
+            file("c.rs", &["@b"], &["api", "docs"]),
+            file("d.md", &["@b"], &["docs"]),
+            file("e.md", &["@c"], &["docs"]),
+            file("f.md", &[], &["orphan"]),
+        ];
+
+        let correlation = owner_correlation(&files);
+        assert_eq!(correlation.len(), 2);
+
+        // @a and @b both own 2 of 3 api files; @a owns nothing else, so it wins
+        let api = &correlation[0];
+        assert_eq!(api.tag.0, "api");
+        assert_eq!(api.owner.identifier, "@a");
+        assert_eq!(api.files, 2);
+        assert!((api.coverage - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(api.exclusivity, 1.0);
+
+        let docs = &correlation[1];
+        assert_eq!(docs.tag.0, "docs");
+        assert_eq!(docs.owner.identifier, "@b");
+        assert_eq!(docs.tag_files, 3);
+        assert!((docs.exclusivity - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
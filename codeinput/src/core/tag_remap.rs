@@ -0,0 +1,151 @@
+//! Tag remapping applied while building the cache, for gradual tag migrations
+//!
+//! While CODEOWNERS files and inline markers are being moved to new tag names, a remap
+//! file makes the old names resolve as the new ones. It is a TOML table of old tag to
+//! new tag, with or without the leading `#`:
+//!
+//! ```toml
+//! "backend" = "api"
+//! "#frontend" = "#web"
+//! ```
+//!
+//! Each tag is remapped once; a tag that is itself renamed is not followed further.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::context::Context;
+use super::tag_resolver::dedup_tags;
+use super::types::{CodeownersEntry, Tag};
+use crate::utils::error::{Error, Result};
+
+/// New tag name for each old tag name, without the leading `#`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagRemap {
+    tags: HashMap<String, String>,
+}
+
+impl TagRemap {
+    /// Parse a tag remapping; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<TagRemap> {
+        let table = contents.parse::<toml::Table>().map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse tag remapping {}: {}",
+                source.display(),
+                e
+            ))
+        })?;
+
+        let mut tags = HashMap::new();
+        for (old, value) in table {
+            let new = value
+                .as_str()
+                .map(tag_name)
+                .filter(|new| !new.is_empty())
+                .ok_or_else(|| {
+                    Error::new(&format!(
+                        "Invalid tag remapping {}: {} must map to a tag name",
+                        source.display(),
+                        old
+                    ))
+                })?;
+            tags.insert(tag_name(&old).to_string(), new.to_string());
+        }
+
+        Ok(TagRemap { tags })
+    }
+
+    /// Load a tag remapping file
+    pub fn load(path: &Path) -> Result<TagRemap> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read tag remapping {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        TagRemap::parse(&contents, path)
+    }
+
+    /// Load the tag remapping configured for `repo`, or an empty one if there is none
+    ///
+    /// The `tag_remap` setting is resolved relative to the repository.
+    pub fn for_repo(ctx: &Context, repo: &Path) -> Result<TagRemap> {
+        let path = repo.join(&ctx.config.tag_remap);
+        if !path.exists() {
+            return Ok(TagRemap::default());
+        }
+        TagRemap::load(&path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    /// Remap `tags`, dropping duplicates created by the remapping
+    pub fn apply(&self, tags: Vec<Tag>) -> Vec<Tag> {
+        if self.is_empty() {
+            return tags;
+        }
+        dedup_tags(
+            tags.into_iter()
+                .map(|tag| match self.tags.get(&tag.0) {
+                    Some(new) => Tag(new.clone()),
+                    None => tag,
+                })
+                .collect(),
+        )
+    }
+
+    /// Copies of `entries` with their tags remapped
+    pub fn apply_to_entries(&self, entries: &[CodeownersEntry]) -> Vec<CodeownersEntry> {
+        entries
+            .iter()
+            .map(|entry| CodeownersEntry {
+                tags: self.apply(entry.tags.clone()),
+                ..entry.clone()
+            })
+            .collect()
+    }
+}
+
+/// Tag name without its leading `#`
+pub(crate) fn tag_name(tag: &str) -> &str {
+    tag.strip_prefix('#').unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<Tag> {
+        names.iter().map(|name| Tag(name.to_string())).collect()
+    }
+
+    #[test]
+    fn test_apply_remaps_once_and_dedups() {
+        let remap = TagRemap::parse(
+            r##"
+"backend" = "api"
+"#api" = "#service"
+"frontend" = "web"
+"##,
+            Path::new("remap.toml"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            remap.apply(tags(&["backend", "docs", "api"])),
+            tags(&["api", "docs", "service"])
+        );
+        assert_eq!(remap.apply(tags(&["frontend", "web"])), tags(&["web"]));
+        assert_eq!(TagRemap::default().apply(tags(&["x"])), tags(&["x"]));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_targets() {
+        assert!(TagRemap::parse(r#""backend" = ["api"]"#, Path::new("remap.toml")).is_err());
+        assert!(TagRemap::parse(r##""backend" = "#""##, Path::new("remap.toml")).is_err());
+        assert!(TagRemap::parse("not toml = ", Path::new("remap.toml")).is_err());
+    }
+}
@@ -19,6 +19,14 @@ pub fn find_files_for_tag(files: &[FileEntry], tag: &Tag) -> Vec<PathBuf> {
         .collect()
 }
 
+/// Remove repeated tags, keeping the first occurrence of each
+pub fn dedup_tags(tags: Vec<Tag>) -> Vec<Tag> {
+    let mut seen = std::collections::HashSet::new();
+    tags.into_iter()
+        .filter(|tag| seen.insert(tag.0.clone()))
+        .collect()
+}
+
 /// Find tags for a specific file based on all parsed CODEOWNERS entries
 pub fn find_tags_for_file(file_path: &Path, entries: &[CodeownersEntry]) -> Result<Vec<Tag>> {
     let target_dir = file_path.parent().ok_or_else(|| {
@@ -31,7 +39,7 @@ pub fn find_tags_for_file(file_path: &Path, entries: &[CodeownersEntry]) -> Resu
     let mut candidates: Vec<_> = entries
         .iter()
         .filter_map(|entry| {
-            let codeowners_dir = match entry.source_file.parent() {
+            let codeowners_dir = match entry.base_dir() {
                 Some(dir) => dir,
                 None => {
                     eprintln!(
@@ -106,14 +114,14 @@ pub fn find_tags_for_file(file_path: &Path, entries: &[CodeownersEntry]) -> Resu
     // Extract the tags from the highest priority entry, if any
     Ok(candidates
         .first()
-        .map(|(entry, _)| entry.tags.clone())
+        .map(|(entry, _)| dedup_tags(entry.tags.clone()))
         .unwrap_or_default())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::types::Tag;
+    use crate::core::types::{EntrySpans, Tag};
     use std::path::PathBuf;
 
     fn create_test_tag(name: &str) -> Tag {
@@ -125,6 +133,8 @@ mod tests {
             path: PathBuf::from(path),
             owners: vec![],
             tags,
+            generated: false,
+            inline: None,
         }
     }
 
@@ -137,6 +147,9 @@ mod tests {
             pattern: pattern.to_string(),
             owners: vec![],
             tags,
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
         }
     }
 
@@ -0,0 +1,297 @@
+//! Team membership for expanding team owners into their members
+//!
+//! CODEOWNERS only names teams; who is in them lives on the code host. A team mapping
+//! file supplies that membership locally, as a TOML table of team handle to members:
+//!
+//! ```toml
+//! "@org/backend" = ["@alice", "@bob"]
+//! "@org/docs" = ["@carol", "dave@example.com"]
+//! ```
+//!
+//! Handles are case-insensitive, as on GitHub.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use super::context::Context;
+use super::types::{Owner, OwnerType};
+use crate::utils::error::{Error, Result};
+
+/// Members of each team, keyed by lowercased team handle
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TeamMap {
+    teams: HashMap<String, Team>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Team {
+    /// Handle as spelled in the mapping file
+    name: String,
+    members: Vec<String>,
+}
+
+/// An owner with the files it owns, after rolling members up into their teams
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RolledUpOwner {
+    pub owner: Owner,
+    /// Files owned by the owner itself or any rolled-up member, sorted and deduplicated
+    pub files: Vec<PathBuf>,
+    /// Members whose files were added to this team
+    pub members: Vec<String>,
+}
+
+impl TeamMap {
+    /// Parse a team mapping; `source` is only used in error messages
+    pub fn parse(contents: &str, source: &Path) -> Result<TeamMap> {
+        let table = contents.parse::<toml::Table>().map_err(|e| {
+            Error::new(&format!(
+                "Failed to parse team mapping {}: {}",
+                source.display(),
+                e
+            ))
+        })?;
+
+        let mut teams = HashMap::new();
+        for (name, value) in table {
+            let members = value
+                .as_array()
+                .and_then(|members| {
+                    members
+                        .iter()
+                        .map(|member| member.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or_else(|| {
+                    Error::new(&format!(
+                        "Invalid team mapping {}: members of {} must be a list of handles or emails",
+                        source.display(),
+                        name
+                    ))
+                })?;
+            teams.insert(name.to_lowercase(), Team { name, members });
+        }
+
+        Ok(TeamMap { teams })
+    }
+
+    /// Load a team mapping file
+    pub fn load(path: &Path) -> Result<TeamMap> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            Error::new(&format!(
+                "Failed to read team mapping {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        TeamMap::parse(&contents, path)
+    }
+
+    /// Load the team mapping configured for `repo`, failing if there is none
+    ///
+    /// The `team_map` setting is resolved relative to the repository.
+    pub fn for_repo(ctx: &Context, repo: &Path) -> Result<TeamMap> {
+        let path = repo.join(&ctx.config.team_map);
+        if !path.exists() {
+            return Err(Error::new(&format!(
+                "No team mapping found at {}; create it or point `team_map` at one with `ci config set team_map <FILE>`",
+                path.display()
+            )));
+        }
+        TeamMap::load(&path)
+    }
+
+    /// Members of `team`, or `None` if it is not in the mapping
+    pub fn members(&self, team: &str) -> Option<&[String]> {
+        self.teams
+            .get(&team.to_lowercase())
+            .map(|team| team.members.as_slice())
+    }
+
+    /// Add `member` to `team`, creating the team if the mapping lacks it
+    ///
+    /// Returns `false` if the team already lists the member.
+    pub fn add_member(&mut self, team: &str, member: &str) -> bool {
+        let team = self
+            .teams
+            .entry(team.to_lowercase())
+            .or_insert_with(|| Team {
+                name: team.to_string(),
+                members: Vec::new(),
+            });
+        if team.members.iter().any(|m| m.eq_ignore_ascii_case(member)) {
+            return false;
+        }
+        team.members.push(member.to_string());
+        true
+    }
+
+    /// Remove `member` from `team`; returns `false` if the team does not list the member
+    pub fn remove_member(&mut self, team: &str, member: &str) -> bool {
+        let Some(team) = self.teams.get_mut(&team.to_lowercase()) else {
+            return false;
+        };
+        let before = team.members.len();
+        team.members.retain(|m| !m.eq_ignore_ascii_case(member));
+        team.members.len() < before
+    }
+
+    /// Teams that list `member`, sorted by handle
+    pub fn teams_of(&self, member: &str) -> Vec<&str> {
+        let member = member.to_lowercase();
+        let mut teams: Vec<&str> = self
+            .teams
+            .values()
+            .filter(|team| team.members.iter().any(|m| m.to_lowercase() == member))
+            .map(|team| team.name.as_str())
+            .collect();
+        teams.sort_unstable();
+        teams
+    }
+
+    /// Aggregate owners that belong to a team into that team
+    ///
+    /// Each member's files count toward every team that lists them; owners in no team are
+    /// kept as they are. Results are sorted by file count, largest first.
+    pub fn rollup<'a>(
+        &self, owners: impl IntoIterator<Item = (&'a Owner, &'a Vec<PathBuf>)>,
+    ) -> Vec<RolledUpOwner> {
+        let mut rolled: HashMap<String, (Owner, BTreeSet<PathBuf>, BTreeSet<String>)> =
+            HashMap::new();
+
+        for (owner, files) in owners {
+            let teams = match owner.owner_type {
+                OwnerType::User | OwnerType::Email => self.teams_of(&owner.identifier),
+                _ => Vec::new(),
+            };
+
+            if teams.is_empty() {
+                let (_, owned, _) = rolled
+                    .entry(owner.identifier.to_lowercase())
+                    .or_insert_with(|| (owner.clone(), BTreeSet::new(), BTreeSet::new()));
+                owned.extend(files.iter().cloned());
+                continue;
+            }
+
+            for team in teams {
+                let (_, owned, members) = rolled.entry(team.to_lowercase()).or_insert_with(|| {
+                    let owner = Owner {
+                        identifier: team.to_string(),
+                        owner_type: OwnerType::Team,
+                    };
+                    (owner, BTreeSet::new(), BTreeSet::new())
+                });
+                owned.extend(files.iter().cloned());
+                members.insert(owner.identifier.clone());
+            }
+        }
+
+        let mut result: Vec<RolledUpOwner> = rolled
+            .into_values()
+            .map(|(owner, files, members)| RolledUpOwner {
+                owner,
+                files: files.into_iter().collect(),
+                members: members.into_iter().collect(),
+            })
+            .collect();
+        result.sort_by(|a, b| {
+            b.files
+                .len()
+                .cmp(&a.files.len())
+                .then_with(|| a.owner.identifier.cmp(&b.owner.identifier))
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map() -> TeamMap {
+        TeamMap::parse(
+            r#"
+"@org/Backend" = ["@alice", "@bob"]
+"@org/docs" = ["@Alice", "carol@example.com"]
+"#,
+            Path::new("teams.toml"),
+        )
+        .unwrap()
+    }
+
+    fn owner(identifier: &str, owner_type: OwnerType) -> Owner {
+        Owner {
+            identifier: identifier.to_string(),
+            owner_type,
+        }
+    }
+
+    #[test]
+    fn test_members_are_looked_up_case_insensitively() {
+        let map = map();
+        assert_eq!(
+            map.members("@org/backend").unwrap(),
+            ["@alice".to_string(), "@bob".to_string()]
+        );
+        assert!(map.members("@org/frontend").is_none());
+        assert_eq!(map.teams_of("@ALICE"), ["@org/Backend", "@org/docs"]);
+        assert!(map.teams_of("@dave").is_empty());
+    }
+
+    #[test]
+    fn test_add_and_remove_members() {
+        let mut map = map();
+        assert!(map.add_member("@org/BACKEND", "@dave"));
+        assert!(!map.add_member("@org/backend", "@Dave"));
+        assert!(map.add_member("@org/new", "@erin"));
+        assert_eq!(map.teams_of("@dave"), ["@org/Backend"]);
+        assert_eq!(map.members("@org/new").unwrap(), ["@erin".to_string()]);
+
+        assert!(map.remove_member("@org/docs", "@ALICE"));
+        assert!(!map.remove_member("@org/docs", "@alice"));
+        assert!(!map.remove_member("@org/frontend", "@alice"));
+        assert_eq!(map.teams_of("@alice"), ["@org/Backend"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_non_list_members() {
+        let result = TeamMap::parse(r#""@org/backend" = "@alice""#, Path::new("teams.toml"));
+        assert!(result.is_err());
+        assert!(TeamMap::parse("not toml = ", Path::new("teams.toml")).is_err());
+    }
+
+    #[test]
+    fn test_rollup_aggregates_members_into_teams() {
+        let map = map();
+        let alice = owner("@alice", OwnerType::User);
+        let bob = owner("@bob", OwnerType::User);
+        let backend = owner("@org/backend", OwnerType::Team);
+        let dave = owner("@dave", OwnerType::User);
+        let owners = [
+            (alice, vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")]),
+            (bob, vec![PathBuf::from("b.rs"), PathBuf::from("c.rs")]),
+            (backend, vec![PathBuf::from("d.rs")]),
+            (dave, vec![PathBuf::from("e.md")]),
+        ];
+
+        let rolled = map.rollup(owners.iter().map(|(owner, files)| (owner, files)));
+
+        let backend = &rolled[0];
+        assert_eq!(backend.owner.identifier, "@org/Backend");
+        assert_eq!(backend.owner.owner_type, OwnerType::Team);
+        assert_eq!(backend.files.len(), 4);
+        assert_eq!(backend.members, ["@alice", "@bob"]);
+
+        let docs = rolled
+            .iter()
+            .find(|o| o.owner.identifier == "@org/docs")
+            .unwrap();
+        assert_eq!(docs.files, [PathBuf::from("a.rs"), PathBuf::from("b.rs")]);
+
+        let dave = rolled
+            .iter()
+            .find(|o| o.owner.identifier == "@dave")
+            .unwrap();
+        assert!(dave.members.is_empty());
+        assert_eq!(rolled.len(), 3);
+    }
+}
@@ -1,9 +1,12 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "ignore")]
 use ignore::overrides::Override;
 use serde::{Deserialize, Serialize};
 
+use super::path_index::PathIndex;
+
 #[cfg(feature = "utoipa")]
 use utoipa::ToSchema;
 
@@ -15,7 +18,7 @@ use utoipa::ToSchema;
 /// - `/path/to/dir/**` matches everything recursively (kept as-is)
 /// - Other patterns are kept as-is
 #[cfg(any(feature = "ignore", test))]
-fn normalize_codeowners_pattern(pattern: &str) -> String {
+pub(crate) fn normalize_codeowners_pattern(pattern: &str) -> String {
     // If pattern ends with `/` but not `*/` or `**/`, convert to `/**`
     if pattern.ends_with('/') && !pattern.ends_with("*/") && !pattern.ends_with("**/") {
         format!("{}**", pattern)
@@ -25,7 +28,7 @@ fn normalize_codeowners_pattern(pattern: &str) -> String {
 }
 
 /// CODEOWNERS entry with source tracking
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
 pub struct CodeownersEntry {
     pub source_file: PathBuf,
@@ -33,6 +36,190 @@ pub struct CodeownersEntry {
     pub pattern: String,
     pub owners: Vec<Owner>,
     pub tags: Vec<Tag>,
+    /// Where each part sits on its source line, for in-place edits
+    #[serde(default)]
+    pub spans: EntrySpans,
+    /// `key:value` annotations from the rule's comments, e.g. `escalation:P1`; see
+    /// [`parse_annotations`](super::parser::parse_annotations)
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// GitLab section the rule is in, if any
+    #[serde(default)]
+    pub section: Option<Section>,
+}
+
+impl CodeownersEntry {
+    /// Directory the entry's patterns are relative to, see [`base_dir`]
+    pub fn base_dir(&self) -> Option<&Path> {
+        base_dir(&self.source_file)
+    }
+
+    /// Whether the rule is a negation, `!pattern`
+    ///
+    /// A negation excludes the files it matches from its CODEOWNERS file when it is the
+    /// last matching line there, so none of the file's rules own them. It never applies
+    /// itself.
+    pub fn is_negation(&self) -> bool {
+        is_negation(&self.pattern)
+    }
+
+    /// The pattern without the `!` of a negation
+    pub fn positive_pattern(&self) -> &str {
+        self.pattern.strip_prefix('!').unwrap_or(&self.pattern)
+    }
+}
+
+/// Whether `pattern` is a negation; an escaped `\!` is a literal `!`
+pub fn is_negation(pattern: &str) -> bool {
+    pattern.starts_with('!')
+}
+
+/// Directory central rules ranked below the repository's own are recorded under
+pub const BASELINE_DIR: &str = ".codeowners-central";
+
+/// Directory central rules ranked above the repository's own are recorded under
+pub const OVERRIDE_DIR: &str = ".codeowners-central-override";
+
+/// How central rules rank against the rules of the repository's own CODEOWNERS files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CentralPrecedence {
+    /// The repository's own matching rules win
+    #[default]
+    #[serde(rename = "baseline")]
+    Baseline,
+    /// Matching central rules win
+    #[serde(rename = "override")]
+    Override,
+}
+
+impl CentralPrecedence {
+    /// Directory at the repository root the rules are recorded under
+    pub fn dir(&self) -> &'static str {
+        match self {
+            CentralPrecedence::Baseline => BASELINE_DIR,
+            CentralPrecedence::Override => OVERRIDE_DIR,
+        }
+    }
+}
+
+impl std::fmt::Display for CentralPrecedence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CentralPrecedence::Baseline => write!(f, "baseline"),
+            CentralPrecedence::Override => write!(f, "override"),
+        }
+    }
+}
+
+impl std::str::FromStr for CentralPrecedence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "baseline" => Ok(CentralPrecedence::Baseline),
+            "override" => Ok(CentralPrecedence::Override),
+            _ => Err(format!(
+                "Invalid central precedence: {}. Valid options: baseline, override",
+                s
+            )),
+        }
+    }
+}
+
+/// How the rules of the CODEOWNERS file `source_file` rank against the repository's own,
+/// if they are central rules
+pub fn central_precedence(source_file: &Path) -> Option<CentralPrecedence> {
+    let dir = source_file.parent()?.file_name()?;
+    if dir == BASELINE_DIR {
+        Some(CentralPrecedence::Baseline)
+    } else if dir == OVERRIDE_DIR {
+        Some(CentralPrecedence::Override)
+    } else {
+        None
+    }
+}
+
+/// Directory whose CODEOWNERS file Bitbucket scopes to the repository root
+pub const BITBUCKET_CONFIG_DIR: &str = ".bitbucket";
+
+/// Directory the patterns of the CODEOWNERS file `source_file` are relative to
+///
+/// That is the directory of the file, except for `.bitbucket/CODEOWNERS`, which Bitbucket
+/// reads as if it were at the repository root, and the rules of a central ownership
+/// repository, which are recorded below the root (see [`central_precedence`]).
+pub fn base_dir(source_file: &Path) -> Option<&Path> {
+    let dir = source_file.parent()?;
+    match dir.file_name() {
+        Some(name) if name == BITBUCKET_CONFIG_DIR => dir.parent(),
+        _ if central_precedence(source_file).is_some() => dir.parent(),
+        _ => Some(dir),
+    }
+}
+
+/// A GitLab CODEOWNERS section, started by a `[Name]` line
+///
+/// ```text
+/// ^[Documentation][2] @docs-team
+/// ```
+///
+/// starts an optional section (`^`) needing two approvals, whose rules without owners
+/// are owned by `@docs-team`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Section {
+    pub name: String,
+    /// Approval from the section's owners is not required
+    pub optional: bool,
+    /// Approvals required, when the header sets a number
+    pub approvals: Option<u32>,
+    /// Owners of the section's rules that name none
+    pub default_owners: Vec<Owner>,
+}
+
+impl std::fmt::Display for Section {
+    /// The header without its default owners, e.g. `^[Documentation][2]`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.optional {
+            write!(f, "^")?;
+        }
+        write!(f, "[{}]", self.name)?;
+        if let Some(approvals) = self.approvals {
+            write!(f, "[{}]", approvals)?;
+        }
+        Ok(())
+    }
+}
+
+/// Byte range within a single line, `start..end`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Text covered by the span, if it lies within `line`
+    pub fn slice<'a>(&self, line: &'a str) -> Option<&'a str> {
+        line.get(self.start..self.end)
+    }
+}
+
+/// Positions of a rule's parts on its line (`CodeownersEntry::line_number`)
+///
+/// Entries built by hand rather than parsed carry empty spans.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct EntrySpans {
+    pub pattern: Span,
+    /// One span per owner, in the same order as `CodeownersEntry::owners`
+    pub owners: Vec<Span>,
+    /// One span per tag, including the leading `#`
+    pub tags: Vec<Span>,
 }
 
 /// Inline CODEOWNERS entry for file-specific ownership
@@ -42,6 +229,45 @@ pub struct InlineCodeownersEntry {
     pub line_number: usize,
     pub owners: Vec<Owner>,
     pub tags: Vec<Tag>,
+    pub source: InlineSource,
+}
+
+/// How a file declares its own owners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum InlineSource {
+    /// A `!!!CODEOWNERS @owner #tag` marker in the first 50 lines
+    Marker,
+    /// `owners:` and `tags:` in the YAML front matter of a Markdown file
+    FrontMatter,
+}
+
+impl std::fmt::Display for InlineSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InlineSource::Marker => write!(f, "!!!CODEOWNERS marker"),
+            InlineSource::FrontMatter => write!(f, "front matter"),
+        }
+    }
+}
+
+/// Where a cached file declares its own owners, taking precedence over every rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct InlineDeclaration {
+    pub source: InlineSource,
+    /// 1-based line of the marker or of the `owners:` key
+    pub line: usize,
+}
+
+impl From<&InlineCodeownersEntry> for InlineDeclaration {
+    fn from(entry: &InlineCodeownersEntry) -> InlineDeclaration {
+        InlineDeclaration {
+            source: entry.source,
+            line: entry.line_number,
+        }
+    }
 }
 
 /// CODEOWNERS entry with Override matcher
@@ -56,53 +282,136 @@ pub struct CodeownersEntryMatcher {
     pub override_matcher: Override,
 }
 
+#[cfg(feature = "ignore")]
+impl CodeownersEntryMatcher {
+    /// Directory the entry's patterns are relative to, see [`base_dir`]
+    pub fn base_dir(&self) -> Option<&Path> {
+        base_dir(&self.source_file)
+    }
+}
+
 #[cfg(feature = "ignore")]
 pub fn codeowners_entry_to_matcher(entry: &CodeownersEntry) -> CodeownersEntryMatcher {
-    let codeowners_dir = match entry.source_file.parent() {
-        Some(dir) => dir,
-        None => {
-            eprintln!(
-                "CODEOWNERS entry has no parent directory: {}",
-                entry.source_file.display()
-            );
-            panic!("Invalid CODEOWNERS entry without parent directory");
+    match build_matcher(entry, MatchOptions::default()) {
+        Ok(matcher) => matcher,
+        Err(e) => {
+            eprintln!("{}", e);
+            panic!("Invalid CODEOWNERS entry pattern");
+        }
+    }
+}
+
+/// Options that change how CODEOWNERS patterns match paths
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MatchOptions {
+    /// Ignore case when matching, as macOS and Windows filesystems do; GitHub itself
+    /// matches CODEOWNERS patterns case-sensitively
+    pub case_insensitive: bool,
+    /// Hosting platform whose CODEOWNERS files and owner syntax are followed
+    #[serde(default)]
+    pub dialect: Dialect,
+}
+
+/// Hosting platform whose reading of CODEOWNERS is followed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub enum Dialect {
+    /// Every CODEOWNERS file in the tree, each scoped to its directory
+    #[default]
+    #[serde(rename = "github")]
+    GitHub,
+    /// A single CODEOWNERS file scoped to the repository root, with `@@group` owners;
+    /// see [`bitbucket`](super::bitbucket)
+    #[serde(rename = "bitbucket")]
+    Bitbucket,
+}
+
+impl std::fmt::Display for Dialect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Dialect::GitHub => write!(f, "github"),
+            Dialect::Bitbucket => write!(f, "bitbucket"),
         }
-    };
+    }
+}
+
+impl std::str::FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "github" => Ok(Dialect::GitHub),
+            "bitbucket" => Ok(Dialect::Bitbucket),
+            _ => Err(format!(
+                "Invalid dialect: {}. Valid options: github, bitbucket",
+                s
+            )),
+        }
+    }
+}
+
+/// Build the Override matcher for an entry, failing on invalid patterns instead of panicking
+#[cfg(all(feature = "ignore", not(feature = "types")))]
+pub fn try_codeowners_entry_to_matcher(
+    entry: &CodeownersEntry,
+) -> crate::utils::error::Result<CodeownersEntryMatcher> {
+    try_codeowners_entry_to_matcher_with(entry, MatchOptions::default())
+}
+
+/// Build the Override matcher for an entry with the given match options
+#[cfg(all(feature = "ignore", not(feature = "types")))]
+pub fn try_codeowners_entry_to_matcher_with(
+    entry: &CodeownersEntry, options: MatchOptions,
+) -> crate::utils::error::Result<CodeownersEntryMatcher> {
+    build_matcher(entry, options).map_err(|msg| crate::utils::error::Error::new(&msg))
+}
+
+/// [`try_codeowners_entry_to_matcher_with`], failing with just a message, so builds of
+/// the `types` feature alone have it without the library's error type
+#[cfg(feature = "ignore")]
+fn build_matcher(
+    entry: &CodeownersEntry, options: MatchOptions,
+) -> Result<CodeownersEntryMatcher, String> {
+    let codeowners_dir = entry.base_dir().ok_or_else(|| {
+        format!(
+            "CODEOWNERS entry has no parent directory: {}",
+            entry.source_file.display()
+        )
+    })?;
 
     let mut builder = ignore::overrides::OverrideBuilder::new(codeowners_dir);
+    builder
+        .case_insensitive(options.case_insensitive)
+        .map_err(|e| format!("Failed to configure matcher: {}", e))?;
 
-    // Transform directory patterns to match GitHub CODEOWNERS behavior
-    let pattern = normalize_codeowners_pattern(&entry.pattern);
+    // Transform directory patterns to match GitHub CODEOWNERS behavior; negations match
+    // the files they exclude
+    let pattern = normalize_codeowners_pattern(entry.positive_pattern());
 
     if let Err(e) = builder.add(&pattern) {
-        eprintln!(
+        return Err(format!(
             "Invalid pattern '{}' (normalized from '{}') in {}: {}",
             pattern,
             entry.pattern,
             entry.source_file.display(),
             e
-        );
-        panic!("Invalid CODEOWNERS entry pattern");
+        ));
     }
-    let override_matcher: Override = match builder.build() {
-        Ok(o) => o,
-        Err(e) => {
-            eprintln!(
-                "Failed to build override for pattern '{}': {}",
-                entry.pattern, e
-            );
-            panic!("Failed to build CODEOWNERS entry matcher");
-        }
-    };
+    let override_matcher: Override = builder.build().map_err(|e| {
+        format!(
+            "Failed to build override for pattern '{}': {}",
+            entry.pattern, e
+        )
+    })?;
 
-    CodeownersEntryMatcher {
+    Ok(CodeownersEntryMatcher {
         source_file: entry.source_file.clone(),
         line_number: entry.line_number,
         pattern: entry.pattern.clone(),
         owners: entry.owners.clone(),
         tags: entry.tags.clone(),
         override_matcher,
-    }
+    })
 }
 
 /// Detailed owner representation
@@ -158,6 +467,66 @@ impl std::fmt::Display for OutputFormat {
     }
 }
 
+/// How rules in nested CODEOWNERS files combine with rules in the files above them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub enum PrecedencePolicy {
+    /// The closest CODEOWNERS file with a matching rule decides, as on GitHub
+    #[default]
+    #[serde(rename = "closest")]
+    Closest,
+    /// The CODEOWNERS file closest to the repository root with a matching rule decides
+    #[serde(rename = "root")]
+    Root,
+    /// Every CODEOWNERS file with a matching rule contributes its owners and tags,
+    /// closest first
+    #[serde(rename = "merge")]
+    Merge,
+}
+
+impl PrecedencePolicy {
+    /// How the applied rule is chosen, for explanations
+    pub fn description(&self) -> &'static str {
+        match self {
+            PrecedencePolicy::Closest => {
+                "rules in the closest CODEOWNERS file win; within a file, the last matching line wins"
+            }
+            PrecedencePolicy::Root => {
+                "rules in the CODEOWNERS file closest to the root win; within a file, the last matching line wins"
+            }
+            PrecedencePolicy::Merge => {
+                "the last matching line of every CODEOWNERS file applies, closest file first"
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PrecedencePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrecedencePolicy::Closest => write!(f, "closest"),
+            PrecedencePolicy::Root => write!(f, "root"),
+            PrecedencePolicy::Merge => write!(f, "merge"),
+        }
+    }
+}
+
+impl std::str::FromStr for PrecedencePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "closest" | "closest-wins" => Ok(PrecedencePolicy::Closest),
+            "root" | "root-wins" => Ok(PrecedencePolicy::Root),
+            "merge" => Ok(PrecedencePolicy::Merge),
+            _ => Err(format!(
+                "Invalid precedence policy: {}. Valid options: closest, root, merge",
+                s
+            )),
+        }
+    }
+}
+
 // Cache related types
 /// File entry in the ownership cache
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -166,20 +535,400 @@ pub struct FileEntry {
     pub path: PathBuf,
     pub owners: Vec<Owner>,
     pub tags: Vec<Tag>,
+    /// The file carries a generated-code marker (see the `generated_markers` setting)
+    #[serde(default)]
+    pub generated: bool,
+    /// The inline declaration the file is owned by, if it declares its own owners
+    #[serde(default)]
+    pub inline: Option<InlineDeclaration>,
+}
+
+/// Marks a cache file, ahead of its [`CACHE_VERSION`]
+pub const CACHE_FORMAT: &str = "codeinput-cache";
+
+/// Layout of the cache file written by this version
+///
+/// 1. Files are not flagged generated; no header
+/// 2. Files are flagged generated; no header
+/// 3. Starts with [`CACHE_FORMAT`] and the version
+/// 4. Rules carry the annotations of their comments
+/// 5. Rules carry their GitLab section
+/// 6. Rules starting with `!` exclude files instead of never matching
+/// 7. Quoted patterns are read as the path between the quotes
+/// 8. The header records the [`BuildInfo`] of the cache
+/// 9. The build info records the [`RepoState`] the cache was built from
+/// 10. Files record their [`InlineDeclaration`]; Markdown front matter declares owners
+///
+/// Older layouts are brought up to date by `cache::migrate_cache` instead of rebuilding.
+pub const CACHE_VERSION: u32 = 10;
+
+/// Version of codeinput and settings a cache was built with, recorded in its header
+///
+/// The repository hash covers the settings too, but naming them tells why a cache shared
+/// between machines does not fit. The version is only recorded for diagnostics: changes
+/// in how files resolve come with a new [`CACHE_VERSION`], and caches in older layouts
+/// are migrated. Caches migrated from layouts before 8 record nothing and leave every
+/// field empty.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct BuildInfo {
+    /// Version of codeinput that built the cache, shown by `cache info`
+    pub tool_version: String,
+    /// Matcher the files were resolved with, once `auto` was settled
+    pub matcher: String,
+    pub case_insensitive: bool,
+    pub dialect: Dialect,
+    /// Markers files were flagged generated by
+    pub generated_markers: Vec<String>,
+    /// Commit and uncommitted changes the files were resolved from; `None` when not
+    /// recorded, as in caches migrated from layouts before 9
+    #[serde(default)]
+    pub source: Option<RepoState>,
+}
+
+/// Commit and uncommitted changes of a repository, for telling which files changed since
+///
+/// A file differs between two states only if it differs between their commits or is
+/// among the changes of either.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "utoipa", derive(ToSchema))]
+pub struct RepoState {
+    /// Id of the HEAD commit; empty when HEAD is unborn
+    pub head: String,
+    /// Paths relative to the repository root that differ from HEAD: modified, staged,
+    /// deleted or untracked
+    pub changed: Vec<PathBuf>,
+}
+
+impl BuildInfo {
+    /// Build info of a cache this version of codeinput resolves with `matcher`
+    pub fn new(matcher: &str, options: MatchOptions, generated_markers: &[String]) -> Self {
+        BuildInfo {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            matcher: matcher.to_string(),
+            case_insensitive: options.case_insensitive,
+            dialect: options.dialect,
+            generated_markers: generated_markers.to_vec(),
+            source: None,
+        }
+    }
+
+    /// Whether the cache recorded how it was built
+    pub fn is_recorded(&self) -> bool {
+        !self.tool_version.is_empty()
+    }
+
+    /// What the cache was built with that this version of codeinput, with `options` and
+    /// `generated_markers`, would resolve differently, such as `case_insensitive = true`
+    ///
+    /// Neither the matcher nor the version of codeinput is compared, as neither changes
+    /// the owners resolved within a cache layout. Empty when nothing was recorded.
+    pub fn mismatches(&self, options: MatchOptions, generated_markers: &[String]) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if !self.is_recorded() {
+            return mismatches;
+        }
+        if self.case_insensitive != options.case_insensitive {
+            mismatches.push(format!("case_insensitive = {}", self.case_insensitive));
+        }
+        if self.dialect != options.dialect {
+            mismatches.push(format!("dialect = {}", self.dialect));
+        }
+        if self.generated_markers != generated_markers {
+            mismatches.push(format!(
+                "generated_markers = [{}]",
+                self.generated_markers.join(", ")
+            ));
+        }
+        mismatches
+    }
 }
 
 /// Cache for storing parsed CODEOWNERS information
+///
+/// Library code should read the cache through its methods, such as [`iter_files`],
+/// [`files_for_owner`] and [`rules_matching`], or index it by path; the fields may change
+/// representation.
+///
+/// [`iter_files`]: CodeownersCache::iter_files
+/// [`files_for_owner`]: CodeownersCache::files_for_owner
+/// [`rules_matching`]: CodeownersCache::rules_matching
 #[derive(Debug)]
 #[cfg_attr(feature = "utoipa", derive(ToSchema))]
 pub struct CodeownersCache {
     pub hash: [u8; 32],
     pub entries: Vec<CodeownersEntry>,
     pub files: Vec<FileEntry>,
+    /// Positions in `files` of the files each rule decides ownership for, indexed like `entries`
+    pub rule_matches: Vec<Vec<usize>>,
+    /// Policy the files were resolved with
+    pub precedence: PrecedencePolicy,
+    /// Version and settings the cache was built with
+    pub build: BuildInfo,
     // Derived data for lookups
     pub owners_map: std::collections::HashMap<Owner, Vec<PathBuf>>,
     pub tags_map: std::collections::HashMap<Tag, Vec<PathBuf>>,
+    pub path_index: PathIndex,
 }
 
+impl CodeownersCache {
+    /// Assemble a cache, deriving the lookups from the file entries
+    pub fn new(
+        hash: [u8; 32], entries: Vec<CodeownersEntry>, files: Vec<FileEntry>,
+        rule_matches: Vec<Vec<usize>>,
+    ) -> Self {
+        let path_index = PathIndex::from_paths(files.iter().map(|file| file.path.as_path()));
+        Self::from_parts(
+            hash,
+            entries,
+            files,
+            rule_matches,
+            path_index,
+            PrecedencePolicy::default(),
+        )
+    }
+
+    /// Number of cached files
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether no file is cached
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Cached entries of all files, in cache order
+    ///
+    /// Creating the iterator is O(1); each step is O(1).
+    pub fn iter_files(&self) -> std::slice::Iter<'_, FileEntry> {
+        self.files.iter()
+    }
+
+    /// The CODEOWNERS rules, in the order their indices refer to
+    ///
+    /// Creating the iterator is O(1); each step is O(1).
+    pub fn iter_rules(&self) -> std::slice::Iter<'_, CodeownersEntry> {
+        self.entries.iter()
+    }
+
+    /// Cached entries of the files `owner` owns, in cache order
+    ///
+    /// Finding the owner is O(1); each file is then looked up by path, O(d) for a path
+    /// d components deep. Owners are compared exactly, type included.
+    pub fn files_for_owner<'a>(&'a self, owner: &Owner) -> impl Iterator<Item = &'a FileEntry> {
+        self.owners_map
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .filter_map(|path| self.file(path))
+    }
+
+    /// Cached entries of the files carrying `tag`, in cache order
+    ///
+    /// Same complexity as [`files_for_owner`](CodeownersCache::files_for_owner).
+    pub fn files_for_tag<'a>(&'a self, tag: &Tag) -> impl Iterator<Item = &'a FileEntry> {
+        self.tags_map
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .filter_map(|path| self.file(path))
+    }
+
+    /// The rules that decided ownership of the cached file at `path`, with their indices,
+    /// in rule order
+    ///
+    /// That is a single rule, or one per CODEOWNERS file under
+    /// [`PrecedencePolicy::Merge`], and none for unowned files and inline declarations.
+    /// The path lookup is O(d) for a path d components deep, then each rule's matches are
+    /// binary searched: O(r log m) for r rules matching at most m files.
+    pub fn rules_matching<'a>(
+        &'a self, path: &Path,
+    ) -> impl Iterator<Item = (usize, &'a CodeownersEntry)> {
+        let position = self.path_index.get(path);
+        self.entries
+            .iter()
+            .zip(&self.rule_matches)
+            .enumerate()
+            .filter(move |(_, (_, matches))| {
+                position.is_some_and(|position| matches.binary_search(&position).is_ok())
+            })
+            .map(|(index, (entry, _))| (index, entry))
+    }
+
+    /// Look up the cached entry for a file by path
+    ///
+    /// O(d) for a path d components deep. Indexing the cache by path does the same,
+    /// panicking when the file is not cached.
+    pub fn file(&self, path: &Path) -> Option<&FileEntry> {
+        self.path_index
+            .get(path)
+            .and_then(|index| self.files.get(index))
+    }
+
+    /// Cached entries for all files at or below a directory
+    pub fn files_under(&self, prefix: &Path) -> Vec<&FileEntry> {
+        self.path_index
+            .prefixed(prefix)
+            .into_iter()
+            .filter_map(|index| self.files.get(index))
+            .collect()
+    }
+
+    /// Cached entries for the files a rule decides ownership for
+    pub fn files_for_rule(&self, rule: usize) -> Vec<&FileEntry> {
+        self.rule_matches
+            .get(rule)
+            .map(|positions| {
+                positions
+                    .iter()
+                    .filter_map(|index| self.files.get(*index))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The part of the cache for the subtree at `scope`
+    ///
+    /// Keeps the files under `scope`, with the ownership resolved for the whole
+    /// repository, and the rules of the CODEOWNERS files in the subtree and its ancestors,
+    /// which are the rules that can apply there. Rule matches are limited to the files
+    /// kept. `scope` is compared with the cached paths as given, so it is joined to the
+    /// repository path like them.
+    pub fn scoped(self, scope: &Path) -> CodeownersCache {
+        let kept = self.path_index.prefixed(scope);
+        let mut positions = vec![None; self.files.len()];
+        for (new, &old) in kept.iter().enumerate() {
+            positions[old] = Some(new);
+        }
+
+        let mut files: Vec<Option<FileEntry>> = self.files.into_iter().map(Some).collect();
+        let files: Vec<FileEntry> = kept
+            .iter()
+            .filter_map(|&index| files.get_mut(index).and_then(Option::take))
+            .collect();
+
+        let (entries, rule_matches) = self
+            .entries
+            .into_iter()
+            .zip(self.rule_matches)
+            .filter(|(entry, _)| {
+                let dir = entry.base_dir().unwrap_or(Path::new(""));
+                scope.starts_with(dir) || dir.starts_with(scope)
+            })
+            .map(|(entry, matches)| {
+                let matches = matches
+                    .into_iter()
+                    .filter_map(|index| positions.get(index).copied().flatten())
+                    .collect();
+                (entry, matches)
+            })
+            .unzip();
+
+        let path_index = PathIndex::from_paths(files.iter().map(|file| file.path.as_path()));
+        CodeownersCache {
+            build: self.build,
+            ..Self::from_parts(
+                self.hash,
+                entries,
+                files,
+                rule_matches,
+                path_index,
+                self.precedence,
+            )
+        }
+    }
+
+    /// Assemble a cache read from disk, rebuilding the path index if it was not stored
+    pub(crate) fn from_stored(
+        hash: [u8; 32], entries: Vec<CodeownersEntry>, files: Vec<FileEntry>,
+        rule_matches: Vec<Vec<usize>>, path_index: PathIndex, precedence: PrecedencePolicy,
+    ) -> Result<Self, &'static str> {
+        // Rule matches can only be computed by resolving every file again
+        if rule_matches.len() != entries.len() {
+            return Err("cache has no rule match index; it was written by an older version");
+        }
+
+        // The path index is cheap to rebuild if it is missing
+        let path_index = if path_index.is_empty() && !files.is_empty() {
+            PathIndex::from_paths(files.iter().map(|file| file.path.as_path()))
+        } else {
+            path_index
+        };
+
+        Ok(Self::from_parts(
+            hash,
+            entries,
+            files,
+            rule_matches,
+            path_index,
+            precedence,
+        ))
+    }
+
+    fn from_parts(
+        hash: [u8; 32], entries: Vec<CodeownersEntry>, files: Vec<FileEntry>,
+        rule_matches: Vec<Vec<usize>>, path_index: PathIndex, precedence: PrecedencePolicy,
+    ) -> Self {
+        let mut owners_map: std::collections::HashMap<Owner, Vec<PathBuf>> =
+            std::collections::HashMap::new();
+        let mut tags_map: std::collections::HashMap<Tag, Vec<PathBuf>> =
+            std::collections::HashMap::new();
+
+        // Owners and tags named by a rule are listed even when they match no files
+        for entry in &entries {
+            for owner in &entry.owners {
+                owners_map.entry(owner.clone()).or_default();
+            }
+            for tag in &entry.tags {
+                tags_map.entry(tag.clone()).or_default();
+            }
+        }
+
+        for file in &files {
+            for owner in &file.owners {
+                owners_map
+                    .entry(owner.clone())
+                    .or_default()
+                    .push(file.path.clone());
+            }
+            for tag in &file.tags {
+                tags_map
+                    .entry(tag.clone())
+                    .or_default()
+                    .push(file.path.clone());
+            }
+        }
+
+        CodeownersCache {
+            hash,
+            entries,
+            files,
+            rule_matches,
+            precedence,
+            build: BuildInfo::default(),
+            owners_map,
+            tags_map,
+            path_index,
+        }
+    }
+}
+
+impl std::ops::Index<&Path> for CodeownersCache {
+    type Output = FileEntry;
+
+    fn index(&self, path: &Path) -> &FileEntry {
+        self.file(path)
+            .unwrap_or_else(|| panic!("{} is not in the cache", path.display()))
+    }
+}
+
+// The owner and tag maps are derived data; they are rebuilt on load rather than stored,
+// which lets the cache be written one file entry at a time. The rule matches and path
+// index follow the files so they can be built as the files are written; the precedence
+// policy comes last so caches without it still load as JSON. The format marker and version
+// lead so the layout is known before the rest is read, followed by the build info so it can
+// be checked before the files are.
 impl Serialize for CodeownersCache {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -187,20 +936,16 @@ impl Serialize for CodeownersCache {
     {
         use serde::ser::SerializeStruct;
 
-        let mut state = serializer.serialize_struct("CodeownersCache", 4)?;
+        let mut state = serializer.serialize_struct("CodeownersCache", 9)?;
+        state.serialize_field("format", CACHE_FORMAT)?;
+        state.serialize_field("version", &CACHE_VERSION)?;
+        state.serialize_field("build", &self.build)?;
         state.serialize_field("hash", &self.hash)?;
         state.serialize_field("entries", &self.entries)?;
         state.serialize_field("files", &self.files)?;
-
-        // Convert owners_map to a serializable format
-        let owners_map_serializable: Vec<(&Owner, &Vec<PathBuf>)> =
-            self.owners_map.iter().collect();
-        state.serialize_field("owners_map", &owners_map_serializable)?;
-
-        // Convert tags_map to a serializable format
-        let tags_map_serializable: Vec<(&Tag, &Vec<PathBuf>)> = self.tags_map.iter().collect();
-        state.serialize_field("tags_map", &tags_map_serializable)?;
-
+        state.serialize_field("rule_matches", &self.rule_matches)?;
+        state.serialize_field("path_index", &self.path_index)?;
+        state.serialize_field("precedence", &self.precedence)?;
         state.end()
     }
 }
@@ -212,32 +957,47 @@ impl<'de> Deserialize<'de> for CodeownersCache {
     {
         #[derive(Deserialize)]
         struct CodeownersCacheHelper {
+            #[serde(default)]
+            format: String,
+            #[serde(default)]
+            version: u32,
+            #[serde(default)]
+            build: BuildInfo,
             hash: [u8; 32],
             entries: Vec<CodeownersEntry>,
             files: Vec<FileEntry>,
-            owners_map: Vec<(Owner, Vec<PathBuf>)>,
-            tags_map: Vec<(Tag, Vec<PathBuf>)>,
+            #[serde(default)]
+            rule_matches: Vec<Vec<usize>>,
+            #[serde(default)]
+            path_index: PathIndex,
+            #[serde(default)]
+            precedence: PrecedencePolicy,
         }
 
         let helper = CodeownersCacheHelper::deserialize(deserializer)?;
 
-        // Convert back to HashMap
-        let mut owners_map = std::collections::HashMap::new();
-        for (owner, paths) in helper.owners_map {
-            owners_map.insert(owner, paths);
+        if !helper.format.is_empty() && helper.format != CACHE_FORMAT {
+            return Err(serde::de::Error::custom("not a codeinput cache"));
         }
-
-        let mut tags_map = std::collections::HashMap::new();
-        for (tag, paths) in helper.tags_map {
-            tags_map.insert(tag, paths);
+        if helper.version > CACHE_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "cache format {} was written by a newer version",
+                helper.version
+            )));
         }
 
+        let cache = CodeownersCache::from_stored(
+            helper.hash,
+            helper.entries,
+            helper.files,
+            helper.rule_matches,
+            helper.path_index,
+            helper.precedence,
+        )
+        .map_err(serde::de::Error::custom)?;
         Ok(CodeownersCache {
-            hash: helper.hash,
-            entries: helper.entries,
-            files: helper.files,
-            owners_map,
-            tags_map,
+            build: helper.build,
+            ..cache
         })
     }
 }
@@ -246,6 +1006,25 @@ impl<'de> Deserialize<'de> for CodeownersCache {
 pub enum CacheEncoding {
     Bincode,
     Json,
+    /// A SQLite database, read in part through its indexes
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    /// An rkyv archive, memory-mapped and read in place
+    #[cfg(feature = "archive")]
+    Archive,
+}
+
+impl std::fmt::Display for CacheEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CacheEncoding::Bincode => write!(f, "bincode"),
+            CacheEncoding::Json => write!(f, "json"),
+            #[cfg(feature = "sqlite")]
+            CacheEncoding::Sqlite => write!(f, "sqlite"),
+            #[cfg(feature = "archive")]
+            CacheEncoding::Archive => write!(f, "archive"),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -363,6 +1142,9 @@ mod tests {
                 owner_type: OwnerType::Team,
             }],
             tags: vec![],
+            spans: EntrySpans::default(),
+            metadata: Default::default(),
+            section: None,
         };
 
         let matcher = codeowners_entry_to_matcher(&entry);
@@ -417,4 +1199,157 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_scoped_cache() {
+        let owner = |identifier: &str| Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::Team,
+        };
+        let entry = |source: &str, pattern: &str, owners: &str| CodeownersEntry {
+            source_file: PathBuf::from(source),
+            line_number: 0,
+            pattern: pattern.to_string(),
+            owners: vec![owner(owners)],
+            tags: Vec::new(),
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let file = |path: &str, owners: &str| FileEntry {
+            path: PathBuf::from(path),
+            owners: vec![owner(owners)],
+            tags: Vec::new(),
+            generated: false,
+            inline: None,
+        };
+        let entries = vec![
+            entry("./CODEOWNERS", "*.md", "@org/docs"),
+            entry("./src/CODEOWNERS", "*", "@org/src"),
+            entry("./src/web/CODEOWNERS", "*.js", "@org/web"),
+            entry("./tools/CODEOWNERS", "*", "@org/tools"),
+        ];
+        let files = vec![
+            file("./README.md", "@org/docs"),
+            file("./src/api/lib.rs", "@org/src"),
+            file("./src/web/app.js", "@org/web"),
+            file("./tools/run.sh", "@org/tools"),
+        ];
+        let cache = CodeownersCache::new(
+            [0; 32],
+            entries,
+            files,
+            vec![vec![0], vec![1], vec![2], vec![3]],
+        );
+
+        let scoped = cache.scoped(Path::new("./src/web"));
+        let paths: Vec<_> = scoped.files.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, [PathBuf::from("./src/web/app.js")]);
+        let sources: Vec<_> = scoped
+            .entries
+            .iter()
+            .map(|e| e.source_file.clone())
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                PathBuf::from("./CODEOWNERS"),
+                PathBuf::from("./src/CODEOWNERS"),
+                PathBuf::from("./src/web/CODEOWNERS")
+            ]
+        );
+        assert_eq!(scoped.rule_matches, [vec![], vec![], vec![0]]);
+        assert!(scoped.file(Path::new("./src/web/app.js")).is_some());
+        assert!(!scoped.owners_map.contains_key(&owner("@org/tools")));
+        assert!(scoped.owners_map[&owner("@org/docs")].is_empty());
+    }
+
+    #[test]
+    fn test_cache_accessors() {
+        let owner = |identifier: &str| Owner {
+            identifier: identifier.to_string(),
+            owner_type: OwnerType::Team,
+        };
+        let entry = |pattern: &str, owners: &str| CodeownersEntry {
+            source_file: PathBuf::from("./CODEOWNERS"),
+            line_number: 0,
+            pattern: pattern.to_string(),
+            owners: vec![owner(owners)],
+            tags: vec![Tag("core".to_string())],
+            spans: Default::default(),
+            metadata: Default::default(),
+            section: None,
+        };
+        let file = |path: &str, owners: &[&str], tags: &[&str]| FileEntry {
+            path: PathBuf::from(path),
+            owners: owners.iter().map(|o| owner(o)).collect(),
+            tags: tags.iter().map(|t| Tag(t.to_string())).collect(),
+            generated: false,
+            inline: None,
+        };
+        let cache = CodeownersCache::new(
+            [0; 32],
+            vec![entry("*.md", "@org/docs"), entry("/src/", "@org/src")],
+            vec![
+                file("./README.md", &["@org/docs"], &["core"]),
+                file("./src/lib.rs", &["@org/src"], &["core"]),
+                file("./src/main.rs", &["@org/src"], &[]),
+                file("./LICENSE", &[], &[]),
+            ],
+            vec![vec![0], vec![1]],
+        );
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.iter_files().count(), 4);
+        assert_eq!(cache.iter_rules().count(), 2);
+        let paths: Vec<_> = cache
+            .files_for_owner(&owner("@org/src"))
+            .map(|file| file.path.to_str().unwrap())
+            .collect();
+        assert_eq!(paths, ["./src/lib.rs", "./src/main.rs"]);
+        assert_eq!(cache.files_for_owner(&owner("@nobody")).count(), 0);
+        assert_eq!(cache.files_for_tag(&Tag("core".to_string())).count(), 2);
+
+        let rules: Vec<_> = cache
+            .rules_matching(Path::new("./src/lib.rs"))
+            .map(|(index, entry)| (index, entry.pattern.as_str()))
+            .collect();
+        assert_eq!(rules, [(1, "/src/")]);
+        assert_eq!(cache.rules_matching(Path::new("./src/main.rs")).count(), 0);
+        assert_eq!(cache.rules_matching(Path::new("./missing")).count(), 0);
+        assert_eq!(cache[Path::new("./LICENSE")].owners, []);
+    }
+
+    #[test]
+    fn test_build_info_mismatches() {
+        let markers = vec!["@generated".to_string()];
+        let build = BuildInfo::new("trie", MatchOptions::default(), &markers);
+        assert!(build
+            .mismatches(MatchOptions::default(), &markers)
+            .is_empty());
+
+        let options = MatchOptions {
+            case_insensitive: true,
+            dialect: Dialect::Bitbucket,
+        };
+        assert_eq!(
+            build.mismatches(options, &[]),
+            [
+                "case_insensitive = false",
+                "dialect = github",
+                "generated_markers = [@generated]"
+            ]
+        );
+
+        let older = BuildInfo {
+            tool_version: "0.0.1".to_string(),
+            ..build
+        };
+        assert!(older
+            .mismatches(MatchOptions::default(), &markers)
+            .is_empty());
+
+        // Caches migrated from older layouts recorded nothing to compare
+        assert!(BuildInfo::default().mismatches(options, &[]).is_empty());
+    }
 }
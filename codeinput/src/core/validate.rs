@@ -0,0 +1,317 @@
+//! Lint of CODEOWNERS files, run by `codeowners validate`
+//!
+//! Building the cache skips what it cannot use with a warning: files it cannot read and
+//! patterns no matcher compiles. Validation reads the files itself and reports those
+//! along with mistakes that still parse, such as rules without owners, each at its line.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use super::bitbucket;
+use super::common::find_dialect_codeowners_files;
+use super::delegation::parse_delegation;
+use super::expiry::ExpiryWindow;
+use super::parser::{parse_content, parse_owner, tokenize, Token};
+use super::types::{
+    try_codeowners_entry_to_matcher_with, CodeownersEntry, Dialect, MatchOptions, OwnerType,
+};
+use crate::utils::error::Result;
+
+/// What is wrong with a line or file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The file could not be read or is not valid UTF-8
+    Unreadable,
+    /// The line does not say what it looks like it says
+    Syntax,
+    /// The pattern does not compile
+    InvalidPattern,
+    NoOwners,
+    MalformedOwner,
+    /// A `DELEGATE` line names a missing directory or malformed owners
+    Delegation,
+    /// The rule's `expires` date has passed
+    Expired,
+    /// The rule's `expires` date is near; a warning, see [`Problem::is_warning`]
+    Expiring,
+}
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProblemKind::Unreadable => write!(f, "unreadable"),
+            ProblemKind::Syntax => write!(f, "syntax"),
+            ProblemKind::InvalidPattern => write!(f, "invalid pattern"),
+            ProblemKind::NoOwners => write!(f, "no owners"),
+            ProblemKind::MalformedOwner => write!(f, "malformed owner"),
+            ProblemKind::Delegation => write!(f, "delegation"),
+            ProblemKind::Expired => write!(f, "expired"),
+            ProblemKind::Expiring => write!(f, "expiring"),
+        }
+    }
+}
+
+/// A problem found in a CODEOWNERS file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Problem {
+    pub source_file: PathBuf,
+    /// 1-based line, `None` for problems with the whole file
+    pub line: Option<usize>,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+impl Problem {
+    /// Whether the problem is only worth knowing about and does not fail validation
+    pub fn is_warning(&self) -> bool {
+        self.kind == ProblemKind::Expiring
+    }
+}
+
+impl fmt::Display for Problem {
+    /// `source:line: message`, as compilers report errors, with `warning: ` before the
+    /// message of a warning
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.source_file.display())?;
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if self.is_warning() {
+            write!(f, " warning:")?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+/// Problems of a parsed rule: an invalid pattern, no owners or malformed owners
+///
+/// The pattern is compiled with `options`, as matching would.
+pub fn rule_problems(entry: &CodeownersEntry, options: MatchOptions) -> Vec<Problem> {
+    let problem = |kind, message: String| Problem {
+        source_file: entry.source_file.clone(),
+        line: Some(entry.line_number + 1),
+        kind,
+        message,
+    };
+
+    let mut problems = Vec::new();
+    if let Err(e) = try_codeowners_entry_to_matcher_with(entry, options) {
+        problems.push(problem(ProblemKind::InvalidPattern, e.to_string()));
+    }
+    if entry.is_negation() {
+        // Negations exclude files; owners on them have no effect
+        if !entry.owners.is_empty() {
+            problems.push(problem(
+                ProblemKind::Syntax,
+                format!("negation {} ignores its owners", entry.pattern),
+            ));
+        }
+        return problems;
+    }
+    if entry.owners.is_empty() {
+        problems.push(problem(
+            ProblemKind::NoOwners,
+            "rule has no owners".to_string(),
+        ));
+    }
+    for owner in &entry.owners {
+        if owner.owner_type == OwnerType::Unknown {
+            problems.push(problem(
+                ProblemKind::MalformedOwner,
+                format!("malformed owner {}", owner.identifier),
+            ));
+        }
+    }
+    problems
+}
+
+/// Every problem of the CODEOWNERS file at `path`, in line order
+///
+/// Rule expiry dates are checked against `expiry`.
+pub fn validate_file(path: &Path, options: MatchOptions, expiry: ExpiryWindow) -> Vec<Problem> {
+    let problem = |line, kind, message: String| Problem {
+        source_file: path.to_path_buf(),
+        line,
+        kind,
+        message,
+    };
+
+    let content = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return vec![problem(
+                None,
+                ProblemKind::Unreadable,
+                format!("cannot read file: {}", e),
+            )]
+        }
+    };
+    let mut problems = Vec::new();
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(e) => {
+            let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+            let line = valid.iter().filter(|&&byte| byte == b'\n').count() + 1;
+            problems.push(problem(
+                Some(line),
+                ProblemKind::Unreadable,
+                "invalid UTF-8; the bytes are replaced when parsing".to_string(),
+            ));
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        }
+    };
+
+    for (line_number, line) in content.lines().enumerate() {
+        let line_problem = |kind, message| problem(Some(line_number + 1), kind, message);
+        if let Some(message) = syntax_problem(line) {
+            problems.push(line_problem(ProblemKind::Syntax, message));
+        }
+        match parse_delegation(line, line_number, path) {
+            Ok(Some(delegation)) => problems.extend(
+                delegation
+                    .problems()
+                    .into_iter()
+                    .map(|message| line_problem(ProblemKind::Delegation, message)),
+            ),
+            Ok(None) => {}
+            Err(e) => problems.push(line_problem(ProblemKind::Syntax, e.to_string())),
+        }
+    }
+
+    match parse_content(&content, path) {
+        Ok(mut entries) => {
+            if options.dialect == Dialect::Bitbucket {
+                bitbucket::apply_owner_syntax(&mut entries);
+            }
+            for entry in &entries {
+                problems.extend(rule_problems(entry, options));
+                problems.extend(expiry.problem(entry));
+            }
+        }
+        Err(e) => problems.push(problem(None, ProblemKind::Syntax, e.to_string())),
+    }
+
+    problems.sort_by_key(|problem| problem.line);
+    problems
+}
+
+/// The CODEOWNERS files of `repo` read by the dialect of `options` and the problems found
+/// in them, file by file
+pub fn validate_repo(
+    repo: &Path, options: MatchOptions, expiry: ExpiryWindow,
+) -> Result<(Vec<PathBuf>, Vec<Problem>)> {
+    let mut files = find_dialect_codeowners_files(repo, options.dialect)?;
+    files.sort();
+    let problems = files
+        .iter()
+        .flat_map(|file| validate_file(file, options, expiry))
+        .collect();
+    Ok((files, problems))
+}
+
+/// Mistakes that still parse, but not as intended
+fn syntax_problem(line: &str) -> Option<String> {
+    // `#tag @owner` starts a comment, silently dropping the owner
+    let tokens: Vec<Token> = tokenize(line).map(|(token, _)| token).collect();
+    if !matches!(tokens.first(), Some(Token::Word(_))) {
+        return None;
+    }
+    let comment = tokens.windows(2).find(|pair| {
+        matches!(
+            pair,
+            [Token::Hash(""), _] | [Token::Hash(_), Token::Word(_)]
+        )
+    })?;
+    match comment {
+        [Token::Hash(tag), Token::Word(word)] if !tag.is_empty() => {
+            let owner = parse_owner(word).ok()?;
+            (owner.owner_type != OwnerType::Unknown).then(|| {
+                format!(
+                    "owner {} after #{} is read as a comment; owners must come before tags",
+                    word, tag
+                )
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::expiry::DEFAULT_WARNING_DAYS;
+    use chrono::NaiveDate;
+    use tempfile::TempDir;
+
+    fn window() -> ExpiryWindow {
+        ExpiryWindow {
+            today: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            warning_days: DEFAULT_WARNING_DAYS,
+        }
+    }
+
+    #[test]
+    fn test_validate_file() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::create_dir(dir.path().join("docs"))?;
+        let path = dir.path().join("CODEOWNERS");
+        std::fs::write(
+            &path,
+            "# Rules\n\
+             *.rs @org/rust\n\
+             /docs/\n\
+             *.md docs-team\n\
+             src/[ @a\n\
+             *.js #frontend #web @web\n\
+             trailing\\\n\
+             DELEGATE docs/ @org/docs\n\
+             DELEGATE missing/ @org/docs\n\
+             !generated/\n\
+             !vendor/ @org/vendor\n\
+             *.py @py # expires:2024-01-05\n",
+        )?;
+
+        let problems: Vec<_> = validate_file(&path, MatchOptions::default(), window())
+            .into_iter()
+            .map(|problem| (problem.line.unwrap(), problem.kind))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                (3, ProblemKind::NoOwners),
+                (4, ProblemKind::MalformedOwner),
+                (5, ProblemKind::InvalidPattern),
+                (6, ProblemKind::Syntax),
+                (6, ProblemKind::NoOwners),
+                (7, ProblemKind::InvalidPattern),
+                (7, ProblemKind::NoOwners),
+                (9, ProblemKind::Delegation),
+                (11, ProblemKind::Syntax),
+                (12, ProblemKind::Expiring),
+            ]
+        );
+        let problems = validate_file(&path, MatchOptions::default(), window());
+        assert_eq!(
+            problems.last().unwrap().to_string(),
+            format!(
+                "{}:12: warning: ownership of *.py expires on 2024-01-05, in 4 days",
+                path.display()
+            )
+        );
+
+        let problem = &validate_file(&path, MatchOptions::default(), window())[0];
+        assert_eq!(
+            problem.to_string(),
+            format!("{}:3: rule has no owners", path.display())
+        );
+
+        std::fs::write(&path, b"*.rs @a\n*.md @b \xff\n")?;
+        let problems = validate_file(&path, MatchOptions::default(), window());
+        assert_eq!(problems[0].line, Some(2));
+        assert_eq!(problems[0].kind, ProblemKind::Unreadable);
+        Ok(())
+    }
+}
@@ -0,0 +1,514 @@
+//! Noticing changes to a repository's files
+//!
+//! A [`WatchBackend`] reports changed paths one at a time. [`NativeBackend`] uses the
+//! operating system's notifications (inotify on Linux, FSEvents on macOS,
+//! ReadDirectoryChangesW on Windows); [`PollingBackend`] rescans modification times on
+//! an interval, which also works on network filesystems that never deliver
+//! notifications and on trees too large for the notification limits.
+//!
+//! Raw changes arrive in bursts (an editor saving, a branch checkout), so a
+//! [`Debouncer`] gathers them into one [`Batch`] once the repository has been quiet for
+//! a while, and gives up on listing paths when a burst is too large to be worth it.
+
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use super::discovery;
+use crate::utils::error::{Error, Result};
+use crate::utils::warnings::{self, WarningKind};
+
+/// How changes to the repository are noticed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WatchBackendKind {
+    /// Native notifications, or polling on network filesystems or when they fail to start
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    /// Operating system notifications: inotify, FSEvents or ReadDirectoryChangesW
+    #[serde(rename = "native")]
+    Native,
+    /// Rescan modification times on an interval
+    #[serde(rename = "poll")]
+    Poll,
+}
+
+impl std::fmt::Display for WatchBackendKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchBackendKind::Auto => write!(f, "auto"),
+            WatchBackendKind::Native => write!(f, "native"),
+            WatchBackendKind::Poll => write!(f, "poll"),
+        }
+    }
+}
+
+impl FromStr for WatchBackendKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(WatchBackendKind::Auto),
+            "native" => Ok(WatchBackendKind::Native),
+            "poll" | "polling" => Ok(WatchBackendKind::Poll),
+            _ => Err(Error::new(&format!(
+                "Invalid watch backend: {}. Valid options: auto, native, poll",
+                s
+            ))),
+        }
+    }
+}
+
+/// A change reported by a [`WatchBackend`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A file or directory was created, modified, renamed or removed
+    Path(PathBuf),
+    /// Changes were lost, for example when the notification queue overflowed; anything
+    /// may have changed
+    Rescan,
+}
+
+/// Source of changes under a watched directory
+pub trait WatchBackend {
+    /// Short name for status output
+    fn name(&self) -> &'static str;
+
+    /// Wait up to `timeout` for the next change
+    fn wait(&mut self, timeout: Duration) -> Result<Option<Change>>;
+}
+
+/// Open a backend of the given kind watching `root` recursively
+///
+/// `Auto` polls on network filesystems and falls back to polling when native
+/// notifications cannot be set up, such as when inotify's watch limit is exhausted.
+pub fn open(
+    root: &Path, kind: WatchBackendKind, poll_interval: Duration,
+) -> Result<Box<dyn WatchBackend>> {
+    match kind {
+        WatchBackendKind::Native => Ok(Box::new(NativeBackend::new(root)?)),
+        WatchBackendKind::Poll => Ok(Box::new(PollingBackend::new(root, poll_interval)?)),
+        WatchBackendKind::Auto => {
+            if is_network_filesystem(root) {
+                log::info!(
+                    "{} is on a network filesystem, polling for changes",
+                    root.display()
+                );
+                return Ok(Box::new(PollingBackend::new(root, poll_interval)?));
+            }
+            match NativeBackend::new(root) {
+                Ok(backend) => Ok(Box::new(backend)),
+                Err(e) => {
+                    warnings::warn(
+                        WarningKind::Other,
+                        format!("Native file watching unavailable, polling instead: {}", e),
+                    );
+                    Ok(Box::new(PollingBackend::new(root, poll_interval)?))
+                }
+            }
+        }
+    }
+}
+
+/// Operating system notifications through the `notify` crate
+pub struct NativeBackend {
+    // Dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    pending: VecDeque<Change>,
+}
+
+impl NativeBackend {
+    pub fn new(root: &Path) -> Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .map_err(|e| Error::with_source("Failed to start the file watcher", Box::new(e)))?;
+        watcher.watch(root, RecursiveMode::Recursive).map_err(|e| {
+            Error::with_source(&format!("Failed to watch {}", root.display()), Box::new(e))
+        })?;
+
+        Ok(NativeBackend {
+            _watcher: watcher,
+            events,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl WatchBackend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<Option<Change>> {
+        if let Some(change) = self.pending.pop_front() {
+            return Ok(Some(change));
+        }
+
+        let event = match self.events.recv_timeout(timeout) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => return Ok(None),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(Error::new("The file watcher stopped unexpectedly"))
+            }
+        };
+        match event {
+            Ok(event) if event.need_rescan() => return Ok(Some(Change::Rescan)),
+            // Reads do not change ownership
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(event) => self
+                .pending
+                .extend(event.paths.into_iter().map(Change::Path)),
+            Err(e) => {
+                warnings::warn(
+                    WarningKind::Other,
+                    format!("File watcher error, rescanning: {}", e),
+                );
+                return Ok(Some(Change::Rescan));
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Size and modification time of a file, compared between scans
+type Stamp = (u64, Option<SystemTime>);
+
+/// Periodic rescans of the files under a directory
+///
+/// Ignored files (per `.gitignore`) and `.git` are not scanned, which keeps rescans of
+/// large trees with build output affordable.
+pub struct PollingBackend {
+    root: PathBuf,
+    interval: Duration,
+    snapshot: HashMap<PathBuf, Stamp>,
+    next_scan: Instant,
+    pending: VecDeque<Change>,
+}
+
+impl PollingBackend {
+    pub fn new(root: &Path, interval: Duration) -> Result<Self> {
+        Ok(PollingBackend {
+            root: root.to_path_buf(),
+            interval,
+            snapshot: scan(root)?,
+            next_scan: Instant::now() + interval,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl WatchBackend for PollingBackend {
+    fn name(&self) -> &'static str {
+        "poll"
+    }
+
+    fn wait(&mut self, timeout: Duration) -> Result<Option<Change>> {
+        if let Some(change) = self.pending.pop_front() {
+            return Ok(Some(change));
+        }
+
+        let now = Instant::now();
+        if now < self.next_scan {
+            let remaining = self.next_scan - now;
+            std::thread::sleep(remaining.min(timeout));
+            if remaining > timeout {
+                return Ok(None);
+            }
+        }
+
+        let current = scan(&self.root)?;
+        let mut changed: Vec<&PathBuf> = current
+            .iter()
+            .filter(|(path, stamp)| self.snapshot.get(*path) != Some(stamp))
+            .map(|(path, _)| path)
+            .chain(
+                self.snapshot
+                    .keys()
+                    .filter(|path| !current.contains_key(*path)),
+            )
+            .collect();
+        changed.sort();
+        self.pending
+            .extend(changed.into_iter().cloned().map(Change::Path));
+        self.snapshot = current;
+        self.next_scan = Instant::now() + self.interval;
+
+        Ok(self.pending.pop_front())
+    }
+}
+
+fn scan(root: &Path) -> Result<HashMap<PathBuf, Stamp>> {
+    let mut snapshot = HashMap::new();
+    // Hidden files are kept: .github/CODEOWNERS matters
+    let walker = discovery::walker(root)
+        .hidden(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+    for entry in walker.filter_map(|entry| entry.ok()) {
+        if let Ok(metadata) = entry.metadata() {
+            if metadata.is_file() {
+                snapshot.insert(
+                    entry.into_path(),
+                    (metadata.len(), metadata.modified().ok()),
+                );
+            }
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Whether `path` is on a filesystem whose changes may not raise local notifications
+#[cfg(target_os = "linux")]
+pub fn is_network_filesystem(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    const NFS: u32 = 0x6969;
+    const SMB: u32 = 0x517b;
+    const CIFS: u32 = 0xff53_4d42;
+    const SMB2: u32 = 0xfe53_4d42;
+    const CODA: u32 = 0x7375_7245;
+    const AFS: u32 = 0x5346_414f;
+    // Shared folders of VMs and WSL
+    const V9FS: u32 = 0x0102_1997;
+
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    matches!(
+        stat.f_type as u32,
+        NFS | SMB | CIFS | SMB2 | CODA | AFS | V9FS
+    )
+}
+
+/// Whether `path` is on a filesystem whose changes may not raise local notifications
+#[cfg(not(target_os = "linux"))]
+pub fn is_network_filesystem(_path: &Path) -> bool {
+    false
+}
+
+/// How a [`Debouncer`] gathers changes into batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebounceOptions {
+    /// A batch is complete once no change has arrived for this long
+    pub quiet: Duration,
+    /// A batch is complete this long after its first change, even if changes continue
+    pub max_wait: Duration,
+    /// Batches of more distinct paths than this become a [`Batch::Rescan`]
+    pub burst_limit: usize,
+}
+
+impl Default for DebounceOptions {
+    fn default() -> Self {
+        DebounceOptions {
+            quiet: Duration::from_millis(200),
+            max_wait: Duration::from_secs(2),
+            burst_limit: 1000,
+        }
+    }
+}
+
+/// Changes gathered by a [`Debouncer`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Batch {
+    /// Distinct changed paths, sorted
+    Paths(Vec<PathBuf>),
+    /// Too much changed to list, or changes were lost; treat everything as changed
+    Rescan,
+}
+
+/// Gathers the changes of a [`WatchBackend`] into batches
+pub struct Debouncer {
+    backend: Box<dyn WatchBackend>,
+    options: DebounceOptions,
+}
+
+impl Debouncer {
+    pub fn new(backend: Box<dyn WatchBackend>, options: DebounceOptions) -> Self {
+        Debouncer { backend, options }
+    }
+
+    /// Name of the backend changes come from
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    /// Wait up to `idle` for a change, then gather it and those following into a batch
+    ///
+    /// Paths for which `keep` returns false are dropped and do not start or extend a
+    /// batch. Returns `None` when nothing changed within `idle`.
+    pub fn next_batch(
+        &mut self, idle: Duration, keep: &dyn Fn(&Path) -> bool,
+    ) -> Result<Option<Batch>> {
+        let idle_until = Instant::now() + idle;
+        let mut paths = BTreeSet::new();
+        let mut rescan = false;
+        // Times of the first and latest change in the batch
+        let mut window: Option<(Instant, Instant)> = None;
+
+        loop {
+            let now = Instant::now();
+            let deadline = match window {
+                None => idle_until,
+                Some((first, last)) => {
+                    (last + self.options.quiet).min(first + self.options.max_wait)
+                }
+            };
+            if now >= deadline {
+                break;
+            }
+
+            let counted = match self.backend.wait(deadline - now)? {
+                Some(Change::Path(path)) if keep(&path) => {
+                    // Keep gathering past the limit only to learn when the burst ends
+                    if paths.len() <= self.options.burst_limit {
+                        paths.insert(path);
+                    }
+                    true
+                }
+                Some(Change::Rescan) => {
+                    rescan = true;
+                    true
+                }
+                Some(Change::Path(_)) | None => false,
+            };
+            if counted {
+                let now = Instant::now();
+                window = Some(window.map_or((now, now), |(first, _)| (first, now)));
+            }
+        }
+
+        Ok(match window {
+            None => None,
+            Some(_) if rescan || paths.len() > self.options.burst_limit => Some(Batch::Rescan),
+            Some(_) => Some(Batch::Paths(paths.into_iter().collect())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// Reports scripted changes immediately, then nothing
+    struct Scripted(VecDeque<Change>);
+
+    impl WatchBackend for Scripted {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn wait(&mut self, timeout: Duration) -> Result<Option<Change>> {
+            match self.0.pop_front() {
+                Some(change) => Ok(Some(change)),
+                None => {
+                    std::thread::sleep(timeout);
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn scripted(changes: Vec<Change>, burst_limit: usize) -> Debouncer {
+        Debouncer::new(
+            Box::new(Scripted(changes.into())),
+            DebounceOptions {
+                quiet: Duration::from_millis(20),
+                max_wait: Duration::from_secs(1),
+                burst_limit,
+            },
+        )
+    }
+
+    fn path(name: &str) -> Change {
+        Change::Path(PathBuf::from(name))
+    }
+
+    #[test]
+    fn test_debouncer_coalesces_repeated_changes() {
+        let mut debouncer = scripted(
+            vec![
+                path("/repo/b.rs"),
+                path("/repo/a.rs"),
+                path("/repo/.git/index"),
+                path("/repo/b.rs"),
+            ],
+            100,
+        );
+        let keep = |path: &Path| !path.starts_with("/repo/.git");
+
+        let batch = debouncer
+            .next_batch(Duration::from_millis(50), &keep)
+            .unwrap();
+
+        assert_eq!(
+            batch,
+            Some(Batch::Paths(vec![
+                PathBuf::from("/repo/a.rs"),
+                PathBuf::from("/repo/b.rs")
+            ]))
+        );
+        assert_eq!(
+            debouncer
+                .next_batch(Duration::from_millis(50), &keep)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_debouncer_turns_bursts_and_lost_changes_into_rescans() {
+        let burst = (0..5).map(|i| path(&format!("/repo/{}.rs", i))).collect();
+        let mut debouncer = scripted(burst, 3);
+        let batch = debouncer
+            .next_batch(Duration::from_millis(50), &|_| true)
+            .unwrap();
+        assert_eq!(batch, Some(Batch::Rescan));
+
+        let mut debouncer = scripted(vec![path("/repo/a.rs"), Change::Rescan], 100);
+        let batch = debouncer
+            .next_batch(Duration::from_millis(50), &|_| true)
+            .unwrap();
+        assert_eq!(batch, Some(Batch::Rescan));
+    }
+
+    #[test]
+    fn test_polling_backend_reports_created_modified_and_removed_files() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join("kept.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("removed.rs"), "").unwrap();
+        std::fs::create_dir(root.join(".github")).unwrap();
+        std::fs::write(root.join(".github/CODEOWNERS"), "* @a\n").unwrap();
+
+        let mut backend = PollingBackend::new(root, Duration::ZERO).unwrap();
+        std::fs::write(root.join("created.rs"), "").unwrap();
+        std::fs::remove_file(root.join("removed.rs")).unwrap();
+        std::fs::write(root.join(".github/CODEOWNERS"), "* @a @b\n").unwrap();
+
+        let mut changes = Vec::new();
+        while let Some(change) = backend.wait(Duration::ZERO).unwrap() {
+            changes.push(change);
+        }
+        changes.sort_by_key(|change| format!("{:?}", change));
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Path(root.join(".github/CODEOWNERS")),
+                Change::Path(root.join("created.rs")),
+                Change::Path(root.join("removed.rs")),
+            ]
+        );
+    }
+}
@@ -1,11 +1,14 @@
 #[cfg(feature = "types")]
 mod core {
+    pub mod path_index;
     pub mod types;
 }
 
 #[cfg(feature = "types")]
 pub use core::types::*;
 
+#[cfg(not(feature = "types"))]
+pub mod ci;
 #[cfg(not(feature = "types"))]
 pub mod core;
 #[cfg(not(feature = "types"))]
@@ -2,11 +2,19 @@ use config::builder::DefaultState;
 use config::{Config, ConfigBuilder, Environment};
 use lazy_static::{__Deref, lazy_static};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::RwLock;
+use std::time::Duration;
 
-use super::error::Result;
+use super::error::{Error, Result};
 use super::types::LogLevel;
+use crate::core::central::CentralPrecedence;
+use crate::core::commands::infer_owners::{InferWeights, DEFAULT_BOT_AUTHORS};
+use crate::core::generated::DEFAULT_MARKERS;
+use crate::core::matcher::{MatcherProfile, MatcherStrategy};
+use crate::core::snapshot::RetentionPolicy;
+use crate::core::types::{Dialect, MatchOptions, PrecedencePolicy};
+use crate::core::watch::{DebounceOptions, WatchBackendKind};
 
 // CONFIG static variable. It's actually an AppConfig
 // inside an RwLock.
@@ -14,11 +22,163 @@ lazy_static! {
     pub static ref BUILDER: RwLock<ConfigBuilder<DefaultState>> = RwLock::new(Config::builder());
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub debug: bool,
     pub log_level: LogLevel,
     pub cache_file: String,
+    /// Read-only cache, such as one built on the default branch, that files changed since
+    /// are layered over, relative to the repository; empty reads none
+    pub base_cache: String,
+    /// HTTP(S) URL or `s3://bucket/prefix` the cache for the current state is downloaded
+    /// from before building it; empty downloads none
+    pub remote_cache: String,
+    /// Bearer token for an HTTP(S) remote cache
+    pub remote_cache_token: String,
+    /// Region of an S3 remote cache, used when `AWS_REGION` and `AWS_DEFAULT_REGION` are unset
+    pub s3_region: String,
+    /// Base URL of an S3-compatible service, such as MinIO; empty is AWS
+    pub s3_endpoint: String,
+    /// S3 access key, used when `AWS_ACCESS_KEY_ID` is unset
+    pub s3_access_key_id: String,
+    /// S3 secret key, used when `AWS_SECRET_ACCESS_KEY` is unset
+    pub s3_secret_access_key: String,
+    /// S3 session token, used when `AWS_SESSION_TOKEN` is unset
+    pub s3_session_token: String,
+    pub matcher: MatcherStrategy,
+    pub matcher_profile: MatcherProfile,
+    /// Match paths ignoring case, like macOS and Windows filesystems; GitHub is case-sensitive
+    pub case_insensitive: bool,
+    /// How rules in nested CODEOWNERS files combine with the files above them
+    pub precedence: PrecedencePolicy,
+    /// Hosting platform whose CODEOWNERS files and owner syntax are followed
+    pub dialect: Dialect,
+    /// Central ownership repository whose rules are merged with the repository's own, as a
+    /// path relative to the repository or a URL; empty reads none
+    pub central_repo: String,
+    /// Ref of the central repository the rules are read at
+    pub central_ref: String,
+    /// CODEOWNERS file in the central repository, relative to its root
+    pub central_path: String,
+    /// Whether central rules rank below (`baseline`) or above (`override`) the repository's
+    pub central_precedence: CentralPrecedence,
+    /// Team membership file used to expand teams into members, relative to the repository
+    pub team_map: String,
+    /// Tag remapping file applied while building the cache, relative to the repository
+    pub tag_remap: String,
+    /// Owner aliases mapping author emails to handles in `infer-owners`, relative to the
+    /// repository
+    pub owner_aliases: String,
+    /// Ownership policy checked by `gate`, relative to the repository
+    pub policy_file: String,
+    /// Layout of the review checklist printed by `checklist`, relative to the repository
+    pub checklist_template: String,
+    /// Text marking a file as generated when found near its start; empty disables detection
+    pub generated_markers: Vec<String>,
+    /// Email globs of automation accounts `infer-owners` never suggests; empty keeps them
+    pub bot_authors: Vec<String>,
+    /// Weight of the share of blamed lines in `infer-owners --algorithm weighted`
+    pub infer_weight_lines: f64,
+    /// Weight of the share of commits in `infer-owners --algorithm weighted`
+    pub infer_weight_commits: f64,
+    /// Weight of the recency of the last change in `infer-owners --algorithm weighted`
+    pub infer_weight_recency: f64,
+    /// Memory budget for pending file entries while building the cache, in MiB
+    pub cache_memory_budget: usize,
+    /// Leave out files ignored by the user's global gitignore (git's `core.excludesFile`)
+    pub global_gitignore: bool,
+    /// Leave out files ignored by the repository's `.git/info/exclude`
+    pub git_exclude: bool,
+    /// Worker threads for cache building and inference; 0 uses every CPU
+    pub jobs: usize,
+    /// Run at the lowest CPU and IO priority
+    pub low_priority: bool,
+    /// Record anonymous usage and performance events; off unless explicitly enabled
+    pub telemetry: bool,
+    /// Record repositories whose cache is built in the machine-wide registry read by
+    /// `repos` and `org report`
+    pub registry: bool,
+    /// How `watch` notices changes: auto, native or poll
+    pub watch_backend: WatchBackendKind,
+    /// Quiet period after the last change before `watch` rebuilds, in milliseconds
+    pub watch_debounce_ms: u64,
+    /// Longest `watch` delays a rebuild while changes keep arriving, in milliseconds
+    pub watch_max_wait_ms: u64,
+    /// Changed paths above which a batch is treated as a full rescan
+    pub watch_burst_limit: usize,
+    /// Interval between rescans of the polling watch backend, in milliseconds
+    pub watch_poll_interval_ms: u64,
+    /// Directory ownership snapshots are stored in, relative to the repository
+    pub snapshot_dir: String,
+    /// Days for which `snapshot gc` keeps each day's latest snapshot
+    pub snapshot_keep_daily_days: u32,
+    /// Weeks after the daily period for which `snapshot gc` keeps each week's latest
+    /// snapshot; 0 keeps them forever
+    pub snapshot_keep_weekly_weeks: u32,
+    /// Base URL of the GitHub REST API; change it for GitHub Enterprise Server
+    pub github_api_url: String,
+    /// Token for the GitHub API, used when `GITHUB_TOKEN` and `GH_TOKEN` are unset
+    pub github_token: String,
+    /// Base URL of the GitLab REST API; change it for a self-managed instance
+    pub gitlab_api_url: String,
+    /// Token for the GitLab API, used when `GITLAB_TOKEN` is unset
+    pub gitlab_token: String,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            debug: false,
+            log_level: LogLevel::Info,
+            cache_file: ".codeowners.cache".to_string(),
+            base_cache: String::new(),
+            remote_cache: String::new(),
+            remote_cache_token: String::new(),
+            s3_region: "us-east-1".to_string(),
+            s3_endpoint: String::new(),
+            s3_access_key_id: String::new(),
+            s3_secret_access_key: String::new(),
+            s3_session_token: String::new(),
+            matcher: MatcherStrategy::Auto,
+            matcher_profile: MatcherProfile::default(),
+            case_insensitive: false,
+            precedence: PrecedencePolicy::Closest,
+            dialect: Dialect::GitHub,
+            central_repo: String::new(),
+            central_ref: "HEAD".to_string(),
+            central_path: "CODEOWNERS".to_string(),
+            central_precedence: CentralPrecedence::Baseline,
+            team_map: ".codeowners-teams.toml".to_string(),
+            tag_remap: ".codeowners-tag-remap.toml".to_string(),
+            owner_aliases: ".codeowners-aliases.toml".to_string(),
+            policy_file: ".codeowners-policy.toml".to_string(),
+            checklist_template: ".codeowners-checklist.toml".to_string(),
+            generated_markers: DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect(),
+            bot_authors: DEFAULT_BOT_AUTHORS.iter().map(|b| b.to_string()).collect(),
+            infer_weight_lines: InferWeights::default().lines,
+            infer_weight_commits: InferWeights::default().commits,
+            infer_weight_recency: InferWeights::default().recency,
+            cache_memory_budget: 64,
+            jobs: 0,
+            low_priority: false,
+            global_gitignore: true,
+            git_exclude: true,
+            telemetry: false,
+            registry: true,
+            watch_backend: WatchBackendKind::Auto,
+            watch_debounce_ms: 200,
+            watch_max_wait_ms: 2000,
+            watch_burst_limit: 1000,
+            watch_poll_interval_ms: 1000,
+            snapshot_dir: ".codeowners-snapshots".to_string(),
+            snapshot_keep_daily_days: 90,
+            snapshot_keep_weekly_weeks: 0,
+            github_api_url: "https://api.github.com".to_string(),
+            github_token: String::new(),
+            gitlab_api_url: "https://gitlab.com/api/v4".to_string(),
+            gitlab_token: String::new(),
+        }
+    }
 }
 
 impl AppConfig {
@@ -37,6 +197,11 @@ impl AppConfig {
             ));
         }
 
+        // Merge settings persisted by commands such as `codeowners calibrate`
+        if let Some(user_config) = AppConfig::user_config_path() {
+            builder = builder.add_source(config::File::from(user_config).required(false));
+        }
+
         // Merge settings with env variables
         builder = builder.add_source(Environment::with_prefix("CODEINPUT")); // TODO: Merge settings with Clap Settings Arguments
 
@@ -61,6 +226,31 @@ impl AppConfig {
             AppConfig::set("log_level", &value.to_string())?;
         }
 
+        if args.contains_id("dialect") {
+            let value: &Dialect = args.get_one("dialect").unwrap_or(&Dialect::GitHub);
+            AppConfig::set("dialect", &value.to_string())?;
+        }
+
+        if let Some(value) = args.get_one::<PathBuf>("base_cache") {
+            AppConfig::set("base_cache", &value.to_string_lossy())?;
+        }
+
+        if args.contains_id("jobs") {
+            let value: &usize = args.get_one("jobs").unwrap_or(&0);
+            AppConfig::set("jobs", &value.to_string())?;
+        }
+
+        // A flag is always present in the matches, so only an explicit `true` overrides config
+        if args.get_flag("low_priority") {
+            AppConfig::set("low_priority", "true")?;
+        }
+        if args.get_flag("no_global_gitignore") {
+            AppConfig::set("global_gitignore", "false")?;
+        }
+        if args.get_flag("no_git_exclude") {
+            AppConfig::set("git_exclude", "false")?;
+        }
+
         Ok(())
     }
 
@@ -87,6 +277,73 @@ impl AppConfig {
         Ok(())
     }
 
+    /// Options for matching CODEOWNERS patterns against paths
+    pub fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            case_insensitive: self.case_insensitive,
+            dialect: self.dialect,
+        }
+    }
+
+    /// Cache build memory budget in bytes
+    pub fn cache_memory_budget_bytes(&self) -> usize {
+        self.cache_memory_budget.saturating_mul(1024 * 1024)
+    }
+
+    /// How `watch` gathers changes into rebuilds
+    pub fn debounce_options(&self) -> DebounceOptions {
+        DebounceOptions {
+            quiet: Duration::from_millis(self.watch_debounce_ms),
+            max_wait: Duration::from_millis(self.watch_max_wait_ms),
+            burst_limit: self.watch_burst_limit,
+        }
+    }
+
+    /// Interval between rescans of the polling watch backend
+    pub fn watch_poll_interval(&self) -> Duration {
+        Duration::from_millis(self.watch_poll_interval_ms)
+    }
+
+    /// How long `snapshot gc` keeps ownership snapshots
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            daily_days: self.snapshot_keep_daily_days,
+            weekly_weeks: self.snapshot_keep_weekly_weeks,
+        }
+    }
+
+    /// Path of the per-user config file, merged after the defaults and before env variables
+    pub fn user_config_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("codeinput").join("config.toml"))
+    }
+
+    /// Write a single key to the per-user config file, keeping its other settings
+    ///
+    /// The running configuration is not updated; the value applies from the next `init`.
+    pub fn persist(key: &str, value: toml::Value) -> Result<PathBuf> {
+        let path = AppConfig::user_config_path()
+            .ok_or_else(|| Error::new("Could not determine the user config directory"))?;
+
+        let mut table = if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            contents
+                .parse::<toml::Table>()
+                .map_err(|e| Error::new(&format!("Failed to parse {}: {}", path.display(), e)))?
+        } else {
+            toml::Table::new()
+        };
+        table.insert(key.to_string(), value);
+
+        let contents = toml::to_string(&table)
+            .map_err(|e| Error::new(&format!("Failed to serialize config: {}", e)))?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, contents)?;
+
+        Ok(path)
+    }
+
     // Get a single value
     pub fn get<'de, T>(key: &'de str) -> Result<T>
     where
@@ -121,6 +378,108 @@ impl TryFrom<Config> for AppConfig {
             debug: config.get_bool("debug")?,
             log_level: config.get::<LogLevel>("log_level")?,
             cache_file: config.get::<String>("cache_file")?,
+            base_cache: get_or(&config, "base_cache", String::new())?,
+            remote_cache: get_or(&config, "remote_cache", String::new())?,
+            remote_cache_token: get_or(&config, "remote_cache_token", String::new())?,
+            s3_region: get_or(&config, "s3_region", "us-east-1".to_string())?,
+            s3_endpoint: get_or(&config, "s3_endpoint", String::new())?,
+            s3_access_key_id: get_or(&config, "s3_access_key_id", String::new())?,
+            s3_secret_access_key: get_or(&config, "s3_secret_access_key", String::new())?,
+            s3_session_token: get_or(&config, "s3_session_token", String::new())?,
+            matcher: get_or(&config, "matcher", MatcherStrategy::Auto)?,
+            matcher_profile: get_or(&config, "matcher_profile", MatcherProfile::default())?,
+            case_insensitive: get_or(&config, "case_insensitive", false)?,
+            precedence: get_or(&config, "precedence", PrecedencePolicy::Closest)?,
+            dialect: get_or(&config, "dialect", Dialect::GitHub)?,
+            central_repo: get_or(&config, "central_repo", String::new())?,
+            central_ref: get_or(&config, "central_ref", "HEAD".to_string())?,
+            central_path: get_or(&config, "central_path", "CODEOWNERS".to_string())?,
+            central_precedence: get_or(&config, "central_precedence", CentralPrecedence::Baseline)?,
+            team_map: get_or(&config, "team_map", ".codeowners-teams.toml".to_string())?,
+            tag_remap: get_or(
+                &config,
+                "tag_remap",
+                ".codeowners-tag-remap.toml".to_string(),
+            )?,
+            owner_aliases: get_or(
+                &config,
+                "owner_aliases",
+                ".codeowners-aliases.toml".to_string(),
+            )?,
+            policy_file: get_or(
+                &config,
+                "policy_file",
+                ".codeowners-policy.toml".to_string(),
+            )?,
+            checklist_template: get_or(
+                &config,
+                "checklist_template",
+                ".codeowners-checklist.toml".to_string(),
+            )?,
+            generated_markers: get_or(
+                &config,
+                "generated_markers",
+                DEFAULT_MARKERS.iter().map(|m| m.to_string()).collect(),
+            )?,
+            bot_authors: get_or(
+                &config,
+                "bot_authors",
+                DEFAULT_BOT_AUTHORS.iter().map(|b| b.to_string()).collect(),
+            )?,
+            infer_weight_lines: get_or(
+                &config,
+                "infer_weight_lines",
+                InferWeights::default().lines,
+            )?,
+            infer_weight_commits: get_or(
+                &config,
+                "infer_weight_commits",
+                InferWeights::default().commits,
+            )?,
+            infer_weight_recency: get_or(
+                &config,
+                "infer_weight_recency",
+                InferWeights::default().recency,
+            )?,
+            cache_memory_budget: get_or(&config, "cache_memory_budget", 64)?,
+            jobs: get_or(&config, "jobs", 0)?,
+            low_priority: get_or(&config, "low_priority", false)?,
+            global_gitignore: get_or(&config, "global_gitignore", true)?,
+            git_exclude: get_or(&config, "git_exclude", true)?,
+            telemetry: get_or(&config, "telemetry", false)?,
+            registry: get_or(&config, "registry", true)?,
+            watch_backend: get_or(&config, "watch_backend", WatchBackendKind::Auto)?,
+            watch_debounce_ms: get_or(&config, "watch_debounce_ms", 200)?,
+            watch_max_wait_ms: get_or(&config, "watch_max_wait_ms", 2000)?,
+            watch_burst_limit: get_or(&config, "watch_burst_limit", 1000)?,
+            watch_poll_interval_ms: get_or(&config, "watch_poll_interval_ms", 1000)?,
+            snapshot_dir: get_or(&config, "snapshot_dir", ".codeowners-snapshots".to_string())?,
+            snapshot_keep_daily_days: get_or(&config, "snapshot_keep_daily_days", 90)?,
+            snapshot_keep_weekly_weeks: get_or(&config, "snapshot_keep_weekly_weeks", 0)?,
+            github_api_url: get_or(
+                &config,
+                "github_api_url",
+                "https://api.github.com".to_string(),
+            )?,
+            github_token: get_or(&config, "github_token", String::new())?,
+            gitlab_api_url: get_or(
+                &config,
+                "gitlab_api_url",
+                "https://gitlab.com/api/v4".to_string(),
+            )?,
+            gitlab_token: get_or(&config, "gitlab_token", String::new())?,
         })
     }
 }
+
+// Optional keys fall back to their defaults, but invalid values are still reported
+fn get_or<T>(config: &Config, key: &str, default: T) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    match config.get::<T>(key) {
+        Ok(value) => Ok(value),
+        Err(config::ConfigError::NotFound(_)) => Ok(default),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -51,6 +51,33 @@ impl Error {
             source: Some(source),
         }
     }
+
+    /// Create the error returned when work stops because of Ctrl-C.
+    pub fn interrupted() -> Self {
+        Error::with_source("Interrupted", Box::new(super::interrupt::Interrupted))
+    }
+
+    /// Whether this error means the user interrupted the run.
+    pub fn is_interrupted(&self) -> bool {
+        self.source
+            .as_ref()
+            .is_some_and(|source| source.is::<super::interrupt::Interrupted>())
+    }
+
+    /// Create the error returned when work stops because the run took longer than `timeout`.
+    pub fn timed_out(timeout: std::time::Duration) -> Self {
+        Error::with_source(
+            &format!("Timed out after {} s", timeout.as_secs()),
+            Box::new(super::interrupt::TimedOut(timeout)),
+        )
+    }
+
+    /// Whether this error means the run took longer than `--timeout`.
+    pub fn is_timed_out(&self) -> bool {
+        self.source
+            .as_ref()
+            .is_some_and(|source| source.is::<super::interrupt::TimedOut>())
+    }
 }
 
 impl From<config::ConfigError> for Error {
@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use super::error::{Error, Result};
+
+/// Exit code for a run stopped by Ctrl-C (128 + SIGINT)
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// Exit code for a run stopped by `--timeout`, as `timeout(1)` uses
+pub const EXIT_TIMED_OUT: i32 = 124;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// When the run was started and how long it may take
+static DEADLINE: OnceLock<(Instant, Duration)> = OnceLock::new();
+
+/// Marker source for errors raised because the user pressed Ctrl-C
+#[derive(Debug, thiserror::Error)]
+#[error("interrupted by Ctrl-C")]
+pub struct Interrupted;
+
+/// Marker source for errors raised because the run took longer than `--timeout`
+#[derive(Debug, thiserror::Error)]
+#[error("timed out after {} s", .0.as_secs())]
+pub struct TimedOut(pub Duration);
+
+/// Stop long-running work at its next safe point once `timeout` has passed from now,
+/// as Ctrl-C does
+///
+/// Only the first deadline set applies.
+pub fn set_deadline(timeout: Duration) {
+    let _ = DEADLINE.set((Instant::now(), timeout));
+}
+
+/// Whether the deadline set with [`set_deadline`] has passed
+pub fn is_timed_out() -> bool {
+    DEADLINE.get().is_some_and(has_passed)
+}
+
+/// Whether `timeout` has passed since `started`
+fn has_passed((started, timeout): &(Instant, Duration)) -> bool {
+    started.elapsed() >= *timeout
+}
+
+/// Install the Ctrl-C handler
+///
+/// The first Ctrl-C asks long-running work to stop at the next safe point, so it can
+/// keep or write whatever results are complete. A second Ctrl-C exits immediately.
+pub fn install_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        eprintln!("\nInterrupted, stopping (press Ctrl-C again to exit immediately)");
+    })
+    .map_err(|e| Error::with_source("Failed to install Ctrl-C handler", Box::new(e)))
+}
+
+/// Whether work should stop: Ctrl-C has been pressed or the deadline has passed
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst) || is_timed_out()
+}
+
+/// Fail with an interrupted error if Ctrl-C has been pressed, or a timed-out error if the
+/// deadline has passed
+pub fn check() -> Result<()> {
+    stopped(INTERRUPTED.load(Ordering::SeqCst), DEADLINE.get())
+}
+
+/// The error of work stopped by Ctrl-C, if `interrupted`, or by `deadline` once it passed
+fn stopped(interrupted: bool, deadline: Option<&(Instant, Duration)>) -> Result<()> {
+    if interrupted {
+        Err(Error::interrupted())
+    } else if let Some((_, timeout)) = deadline.filter(|deadline| has_passed(deadline)) {
+        Err(Error::timed_out(*timeout))
+    } else {
+        Ok(())
+    }
+}
+
+/// Exit code of a run that failed with `error`, if it was stopped by Ctrl-C or the deadline
+pub fn exit_code(error: &Error) -> Option<i32> {
+    if error.is_interrupted() {
+        Some(EXIT_INTERRUPTED)
+    } else if error.is_timed_out() {
+        Some(EXIT_TIMED_OUT)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopped() {
+        let now = Instant::now();
+        let passed = (now - Duration::from_secs(5), Duration::from_secs(2));
+        let pending = (now, Duration::from_secs(3600));
+
+        assert!(stopped(false, None).is_ok());
+        assert!(stopped(false, Some(&pending)).is_ok());
+        let error = stopped(false, Some(&passed)).unwrap_err();
+        assert!(error.is_timed_out());
+        assert_eq!(error.to_string(), "Timed out after 2 s");
+        // Ctrl-C wins over the deadline
+        assert!(stopped(true, Some(&passed)).unwrap_err().is_interrupted());
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let timeout = Duration::from_secs(10);
+        assert_eq!(exit_code(&Error::interrupted()), Some(EXIT_INTERRUPTED));
+        assert_eq!(exit_code(&Error::timed_out(timeout)), Some(EXIT_TIMED_OUT));
+        assert_eq!(exit_code(&Error::new("Failed")), None);
+    }
+
+    #[test]
+    fn test_set_deadline() {
+        // The deadline is shared by the whole process, so this one is never reached
+        set_deadline(Duration::from_secs(24 * 3600));
+        // Only the first deadline applies
+        set_deadline(Duration::ZERO);
+        assert!(!is_timed_out());
+        assert!(check().is_ok());
+    }
+}
@@ -1,3 +1,13 @@
+//! Logging setup for the `ci` binary and for applications embedding codeinput
+//!
+//! The library only logs through the `log` facade and never installs a logger by itself.
+//! An application that already has a `log` logger sees codeinput's records there and
+//! needs nothing from this module. One that logs with slog hands its logger to
+//! [`install_logger`], and the binary calls [`setup_logging`] for the configured drains.
+//! Both can be called again, which swaps the logger instead of failing.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use slog::o;
 use slog::Drain;
 #[cfg(all(target_os = "linux", feature = "journald"))]
@@ -9,37 +19,47 @@ use super::app_config::AppConfig;
 use super::error::Result;
 use super::types::LogLevel;
 
+/// Whether `log` records are already routed to the slog logger, or to a `log` logger the
+/// host application installed first
+static LOG_BRIDGED: AtomicBool = AtomicBool::new(false);
+
+/// Log to the drains of the global configuration, as the binary does
 pub fn setup_logging() -> Result<slog_scope::GlobalLoggerGuard> {
-    // Setup Logging
-    let guard = slog_scope::set_global_logger(default_root_logger()?);
-    slog_stdlog::init()?;
+    let config = AppConfig::fetch().unwrap_or_default();
+    Ok(install_logger(default_root_logger()?, &config.log_level))
+}
 
-    // Set log level for the log crate (used by ignore and other crates)
-    let config = AppConfig::fetch().unwrap_or(AppConfig {
-        debug: false,
-        log_level: LogLevel::Info,
-        cache_file: ".codeowners.cache".to_string(),
-    });
+/// Route codeinput's `log` records at `level` and above to `logger`, until the returned
+/// guard is dropped
+///
+/// If the host application installed a `log` logger of its own, records keep going there
+/// and only `logger` is made the slog global logger.
+pub fn install_logger(logger: slog::Logger, level: &LogLevel) -> slog_scope::GlobalLoggerGuard {
+    let guard = slog_scope::set_global_logger(logger);
+
+    // The `log` logger can only be set once per process; the bridge forwards to whichever
+    // slog global logger is current, so later calls only swap that
+    if !LOG_BRIDGED.swap(true, Ordering::SeqCst) && slog_stdlog::init().is_err() {
+        log::debug!("A log logger is already installed; codeinput logs through it");
+    }
 
-    let log_level = match config.log_level {
+    // Set log level for the log crate (used by ignore and other crates)
+    log::set_max_level(match level {
         LogLevel::Debug => log::LevelFilter::Debug,
         LogLevel::Info => log::LevelFilter::Info,
         LogLevel::Warn => log::LevelFilter::Warn,
         LogLevel::Error => log::LevelFilter::Error,
-    };
-
-    log::set_max_level(log_level);
+    });
 
-    Ok(guard)
+    guard
 }
 
+/// Logger writing to the drains enabled by features, at the configured level
+///
+/// Nothing is installed; pass it to [`install_logger`], or use it directly.
 pub fn default_root_logger() -> Result<slog::Logger> {
     // Get configured log level
-    let config = AppConfig::fetch().unwrap_or(AppConfig {
-        debug: false,
-        log_level: LogLevel::Info,
-        cache_file: ".codeowners.cache".to_string(),
-    });
+    let config = AppConfig::fetch().unwrap_or_default();
 
     let slog_level = match config.log_level {
         LogLevel::Debug => slog::Level::Debug,
@@ -125,3 +145,19 @@ fn default_journald_drain() -> Result<slog_async::Async> {
 
     Ok(drain)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_logger_is_reentrant() {
+        let discard = || slog::Logger::root(slog::Discard, o!());
+        let first = install_logger(discard(), &LogLevel::Warn);
+        let second = install_logger(discard(), &LogLevel::Debug);
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
+        log::debug!("routed to the second logger");
+        drop(second);
+        drop(first);
+    }
+}
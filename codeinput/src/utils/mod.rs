@@ -2,5 +2,11 @@
 
 pub mod app_config;
 pub mod error;
+pub mod interrupt;
 pub mod logger;
+pub mod output;
+pub mod parallelism;
+pub mod summary;
+pub mod telemetry;
 pub mod types;
+pub mod warnings;
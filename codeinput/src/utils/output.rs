@@ -0,0 +1,32 @@
+//! Stable output for golden tests
+//!
+//! With `--stable-output` renderers avoid anything that varies between runs: progress
+//! lines and terminal escapes are suppressed, ties are broken by name, and the current
+//! time is pinned to [`STABLE_NOW`] so "days ago" style values do not drift.
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Unix timestamp used as "now" in stable output: 2024-01-01T00:00:00Z
+pub const STABLE_NOW: i64 = 1_704_067_200;
+
+static STABLE: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable stable output for the rest of the process
+pub fn set_stable(stable: bool) {
+    STABLE.store(stable, Ordering::Relaxed);
+}
+
+/// Whether output must be reproducible across runs
+pub fn is_stable() -> bool {
+    STABLE.load(Ordering::Relaxed)
+}
+
+/// The current time, pinned to [`STABLE_NOW`] in stable output
+pub fn now() -> DateTime<Utc> {
+    if is_stable() {
+        DateTime::from_timestamp(STABLE_NOW, 0).unwrap_or_default()
+    } else {
+        Utc::now()
+    }
+}
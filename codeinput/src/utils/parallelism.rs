@@ -0,0 +1,70 @@
+use super::error::{Error, Result};
+use super::warnings::{self, WarningKind};
+
+/// Configure the global thread pool used for cache building and inference
+///
+/// `jobs` of 0 uses one thread per logical CPU. With `low_priority`, the process drops
+/// to the lowest CPU priority (and the idle IO class on Linux) first, so the pool's
+/// threads inherit it. Call once, before any parallel work.
+pub fn configure(jobs: usize, low_priority: bool) -> Result<()> {
+    if low_priority {
+        lower_priority();
+    }
+
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if jobs > 0 {
+        builder = builder.num_threads(jobs);
+    }
+
+    builder
+        .build_global()
+        .map_err(|e| Error::new(&format!("Failed to configure thread pool: {}", e)))
+}
+
+#[cfg(unix)]
+fn lower_priority() {
+    // SAFETY: setpriority only reads its integer arguments
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, 19) } != 0 {
+        warnings::warn(
+            WarningKind::Other,
+            format!(
+                "Failed to lower CPU priority: {}",
+                std::io::Error::last_os_error()
+            ),
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+        const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+        const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+        // SAFETY: ioprio_set only reads its integer arguments
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if result != 0 {
+            warnings::warn(
+                WarningKind::Other,
+                format!(
+                    "Failed to lower IO priority: {}",
+                    std::io::Error::last_os_error()
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_priority() {
+    warnings::warn(
+        WarningKind::Other,
+        "Low-priority mode is not supported on this platform".to_string(),
+    );
+}
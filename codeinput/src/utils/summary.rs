@@ -0,0 +1,76 @@
+//! One-line run summaries for CI logs
+//!
+//! Commands note counts as they run; with `--summary` the CLI prints them after the
+//! command as a single line of `key=value` pairs on stderr, for example:
+//!
+//! ```text
+//! summary command="codeowners list-files" status=ok files=1234 unowned=56 listed=12 duration_ms=789
+//! ```
+//!
+//! Values containing spaces are quoted, as in logfmt.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Counts noted by the current command, in the order they were first noted
+static STATS: Mutex<Vec<(&'static str, u64)>> = Mutex::new(Vec::new());
+
+/// Note a count for the summary, replacing any earlier value for `key`
+pub fn note(key: &'static str, value: u64) {
+    let mut stats = STATS.lock().unwrap_or_else(|e| e.into_inner());
+    match stats.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = value,
+        None => stats.push((key, value)),
+    }
+}
+
+/// Counts noted so far
+pub fn stats() -> Vec<(&'static str, u64)> {
+    STATS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Render the summary line for a finished command
+pub fn format_line(
+    command: &str, success: bool, duration: Duration, stats: &[(&'static str, u64)],
+) -> String {
+    let mut line = format!(
+        "summary command={} status={}",
+        format_value(command),
+        if success { "ok" } else { "error" }
+    );
+    for (key, value) in stats {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    line.push_str(&format!(" duration_ms={}", duration.as_millis()));
+    line
+}
+
+fn format_value(value: &str) -> String {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_line() {
+        let line = format_line(
+            "codeowners list-files",
+            true,
+            Duration::from_millis(789),
+            &[("files", 1234), ("unowned", 56)],
+        );
+        assert_eq!(
+            line,
+            "summary command=\"codeowners list-files\" status=ok files=1234 unowned=56 duration_ms=789"
+        );
+
+        let line = format_line("doctor", false, Duration::from_millis(5), &[]);
+        assert_eq!(line, "summary command=doctor status=error duration_ms=5");
+    }
+}
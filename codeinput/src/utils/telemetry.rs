@@ -0,0 +1,188 @@
+//! Opt-in, anonymous usage and performance telemetry
+//!
+//! Nothing is recorded unless `telemetry = true` is set (for example with
+//! `ci config set telemetry true`). An event holds only the command name, a coarse
+//! repository size bucket, the duration and the CLI version/OS; no paths, owners or
+//! repository identifiers. Events are appended to a local spool file so they can be
+//! inspected before being shared.
+//!
+//! Building with the `disable-telemetry` feature compiles recording out entirely and
+//! refuses to enable it.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::error::Result;
+
+/// Whether this build can record telemetry at all
+pub const AVAILABLE: bool = !cfg!(feature = "disable-telemetry");
+
+/// File count of the repository the current command worked on, if known
+static REPO_FILES: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Coarse repository size, so events cannot identify a repository by its file count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SizeBucket {
+    Unknown,
+    /// Fewer than 1,000 files
+    Small,
+    /// Fewer than 10,000 files
+    Medium,
+    /// Fewer than 100,000 files
+    Large,
+    /// 100,000 files or more
+    Huge,
+}
+
+impl SizeBucket {
+    pub fn from_file_count(files: usize) -> Self {
+        match files {
+            0..=999 => SizeBucket::Small,
+            1_000..=9_999 => SizeBucket::Medium,
+            10_000..=99_999 => SizeBucket::Large,
+            _ => SizeBucket::Huge,
+        }
+    }
+}
+
+impl fmt::Display for SizeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SizeBucket::Unknown => write!(f, "unknown"),
+            SizeBucket::Small => write!(f, "small"),
+            SizeBucket::Medium => write!(f, "medium"),
+            SizeBucket::Large => write!(f, "large"),
+            SizeBucket::Huge => write!(f, "huge"),
+        }
+    }
+}
+
+/// A single recorded command run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub command: String,
+    pub repo_size: SizeBucket,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub version: String,
+    pub os: String,
+}
+
+impl Event {
+    pub fn new(command: &str, repo_size: SizeBucket, duration: Duration, success: bool) -> Self {
+        Event {
+            command: command.to_string(),
+            repo_size,
+            duration_ms: duration.as_millis().try_into().unwrap_or(u64::MAX),
+            success,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+        }
+    }
+}
+
+/// Note the number of files in the repository being processed
+///
+/// Called where the file list is known (cache build and load); the latest value is used
+/// for the size bucket of the event recorded at the end of the command.
+pub fn note_repo_size(files: usize) {
+    REPO_FILES.store(files, Ordering::Relaxed);
+}
+
+/// Size bucket of the repository noted by the current command
+pub fn repo_size() -> SizeBucket {
+    match REPO_FILES.load(Ordering::Relaxed) {
+        usize::MAX => SizeBucket::Unknown,
+        files => SizeBucket::from_file_count(files),
+    }
+}
+
+/// Path of the local event spool
+pub fn spool_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|dir| dir.join("codeinput").join("telemetry.jsonl"))
+}
+
+/// Record `command` if telemetry is enabled
+///
+/// Telemetry must never affect the command itself, so failures are only logged.
+pub fn record(enabled: bool, command: &str, duration: Duration, success: bool) {
+    if !AVAILABLE || !enabled {
+        return;
+    }
+
+    let event = Event::new(command, repo_size(), duration, success);
+    if let Err(e) = append(&event) {
+        log::debug!("Failed to record telemetry: {}", e);
+    }
+}
+
+#[cfg(not(feature = "disable-telemetry"))]
+fn append(event: &Event) -> Result<()> {
+    use std::io::Write;
+
+    let path = spool_path()
+        .ok_or_else(|| super::error::Error::new("Could not determine the user data directory"))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    let line = serde_json::to_string(event)
+        .map_err(|e| super::error::Error::new(&format!("Failed to serialize event: {}", e)))?;
+    writeln!(file, "{}", line)?;
+
+    Ok(())
+}
+
+#[cfg(feature = "disable-telemetry")]
+fn append(_event: &Event) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_bucket_boundaries() {
+        assert_eq!(SizeBucket::from_file_count(0), SizeBucket::Small);
+        assert_eq!(SizeBucket::from_file_count(999), SizeBucket::Small);
+        assert_eq!(SizeBucket::from_file_count(1_000), SizeBucket::Medium);
+        assert_eq!(SizeBucket::from_file_count(99_999), SizeBucket::Large);
+        assert_eq!(SizeBucket::from_file_count(100_000), SizeBucket::Huge);
+    }
+
+    #[test]
+    fn test_event_holds_only_anonymous_fields() {
+        let event = Event::new(
+            "codeowners parse",
+            SizeBucket::Medium,
+            Duration::from_millis(1500),
+            true,
+        );
+        let json = serde_json::to_value(&event).unwrap();
+
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "command",
+                "duration_ms",
+                "os",
+                "repo_size",
+                "success",
+                "version"
+            ]
+        );
+        assert_eq!(json["repo_size"], "medium");
+        assert_eq!(json["duration_ms"], 1500);
+    }
+}
@@ -0,0 +1,109 @@
+//! Non-fatal warnings of the current command, counted for a final summary
+//!
+//! Problems a command works around, such as a pattern that cannot be compiled or a file
+//! that cannot be read, are logged when they happen and recorded here by kind. The CLI
+//! prints how many of each kind there were after the command and, with `--max-warnings`,
+//! fails a command that recorded too many. The same warning recorded twice, as when two
+//! steps of a command read the same file, counts once.
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+/// What a warning is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WarningKind {
+    /// A CODEOWNERS pattern or file left out of matching
+    SkippedPattern,
+    /// A file or directory that could not be read
+    UnreadableFile,
+    /// An inline `!!!CODEOWNERS` marker naming no owner
+    UnresolvedInlineMarker,
+    /// Anything else worked around, such as a cache rebuilt or a request retried
+    Other,
+}
+
+impl WarningKind {
+    /// Name of `count` warnings of this kind, e.g. "2 skipped patterns"
+    fn describe(&self, count: usize) -> String {
+        let (one, many) = match self {
+            WarningKind::SkippedPattern => ("skipped pattern", "skipped patterns"),
+            WarningKind::UnreadableFile => ("unreadable file", "unreadable files"),
+            WarningKind::UnresolvedInlineMarker => {
+                ("unresolved inline marker", "unresolved inline markers")
+            }
+            WarningKind::Other => ("other warning", "other warnings"),
+        };
+        format!("{} {}", count, if count == 1 { one } else { many })
+    }
+}
+
+/// Warnings recorded by the current command
+static WARNINGS: Mutex<BTreeSet<(WarningKind, String)>> = Mutex::new(BTreeSet::new());
+
+/// Log `message` as a warning and record it under `kind`
+pub fn warn(kind: WarningKind, message: String) {
+    log::warn!("{}", message);
+    WARNINGS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert((kind, message));
+}
+
+/// Number of warnings recorded so far
+pub fn count() -> usize {
+    WARNINGS.lock().unwrap_or_else(|e| e.into_inner()).len()
+}
+
+/// Warnings recorded so far, by kind in the order of [`WarningKind`]
+pub fn counts() -> Vec<(WarningKind, usize)> {
+    let warnings = WARNINGS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut counts: Vec<(WarningKind, usize)> = Vec::new();
+    for (kind, _) in warnings.iter() {
+        match counts.last_mut() {
+            Some((last, count)) if last == kind => *count += 1,
+            _ => counts.push((*kind, 1)),
+        }
+    }
+    counts
+}
+
+/// Render the summary of `counts`, e.g. "3 warnings: 2 skipped patterns, 1 unreadable
+/// file", or `None` without warnings
+pub fn format_summary(counts: &[(WarningKind, usize)]) -> Option<String> {
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    if total == 0 {
+        return None;
+    }
+    let kinds: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| kind.describe(*count))
+        .collect();
+    Some(format!(
+        "{} {}: {}",
+        total,
+        if total == 1 { "warning" } else { "warnings" },
+        kinds.join(", ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_summary() {
+        assert_eq!(format_summary(&[]), None);
+        assert_eq!(
+            format_summary(&[
+                (WarningKind::SkippedPattern, 2),
+                (WarningKind::UnreadableFile, 1)
+            ])
+            .as_deref(),
+            Some("3 warnings: 2 skipped patterns, 1 unreadable file")
+        );
+        assert_eq!(
+            format_summary(&[(WarningKind::UnresolvedInlineMarker, 1)]).as_deref(),
+            Some("1 warning: 1 unresolved inline marker")
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! Parity with GitHub's CODEOWNERS resolution
+//!
+//! Each fixture in `tests/fixtures/github` is a CODEOWNERS file with the owners GitHub
+//! resolves for a set of paths. Every path is resolved with every matcher strategy and
+//! must agree with GitHub, except where a fixture records a known divergence; those pin
+//! the current behavior, so a fix shows up as a failure to move the path to `expected`.
+//!
+//! The property tests check GitHub's "last matching line wins" rule over generated paths
+//! and that the matcher strategies agree with each other.
+
+use codeinput::core::{
+    matcher::{Matcher, MatcherStrategy},
+    parser::parse_content,
+    resolver::{find_applied_entries, resolve_file_with_policy},
+    types::{CodeownersEntry, PrecedencePolicy},
+};
+use proptest::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Repository root for fixture paths; it does not exist, so no inline markers are read
+const REPO: &str = "/github-parity";
+
+#[derive(Debug, Deserialize)]
+struct Fixture {
+    description: String,
+    codeowners: String,
+    /// Owners GitHub resolves for each path; empty when the path is unowned
+    expected: BTreeMap<String, Vec<String>>,
+    #[serde(default, rename = "divergence")]
+    divergences: Vec<Divergence>,
+}
+
+/// A path where codeinput knowingly resolves differently from GitHub
+#[derive(Debug, Deserialize)]
+struct Divergence {
+    path: String,
+    github: Vec<String>,
+    codeinput: Vec<String>,
+    reason: String,
+}
+
+fn fixtures() -> Vec<(PathBuf, Fixture)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/github");
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path).unwrap();
+            let fixture = toml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("invalid fixture {}: {}", path.display(), e));
+            (path, fixture)
+        })
+        .collect()
+}
+
+fn parse(codeowners: &str) -> Vec<CodeownersEntry> {
+    parse_content(codeowners, &Path::new(REPO).join("CODEOWNERS")).unwrap()
+}
+
+/// Owners codeinput resolves for `path`, sorted
+fn resolve(entries: &[CodeownersEntry], matcher: &Matcher, path: &str) -> Vec<String> {
+    let file = Path::new(REPO).join(path);
+    let (owners, _, _) =
+        resolve_file_with_policy(&file, entries, matcher, PrecedencePolicy::Closest).unwrap();
+    let mut owners: Vec<String> = owners.into_iter().map(|owner| owner.identifier).collect();
+    owners.sort();
+    owners
+}
+
+fn sorted(owners: &[String]) -> Vec<String> {
+    let mut owners = owners.to_vec();
+    owners.sort();
+    owners
+}
+
+#[test]
+fn test_fixtures_match_github() {
+    let mut failures = Vec::new();
+
+    for (path, fixture) in fixtures() {
+        let entries = parse(&fixture.codeowners);
+        for strategy in MatcherStrategy::CONCRETE {
+            let matcher = Matcher::build(&entries, strategy);
+
+            for (file, expected) in &fixture.expected {
+                let actual = resolve(&entries, &matcher, file);
+                if actual != sorted(expected) {
+                    failures.push(format!(
+                        "{} ({}, {:?}): {} resolved to {:?}, GitHub resolves {:?}",
+                        path.display(),
+                        fixture.description,
+                        strategy,
+                        file,
+                        actual,
+                        expected
+                    ));
+                }
+            }
+
+            for divergence in &fixture.divergences {
+                assert!(
+                    !fixture.expected.contains_key(&divergence.path),
+                    "{}: {} is listed as both expected and divergent",
+                    path.display(),
+                    divergence.path
+                );
+                assert_ne!(
+                    sorted(&divergence.github),
+                    sorted(&divergence.codeinput),
+                    "{}: divergence for {} does not diverge",
+                    path.display(),
+                    divergence.path
+                );
+                let actual = resolve(&entries, &matcher, &divergence.path);
+                if actual != sorted(&divergence.codeinput) {
+                    failures.push(format!(
+                        "{} ({:?}): known divergence for {} changed: resolved to {:?}, recorded {:?} (GitHub: {:?}; {})",
+                        path.display(),
+                        strategy,
+                        divergence.path,
+                        actual,
+                        divergence.codeinput,
+                        divergence.github,
+                        divergence.reason
+                    ));
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+/// Relative paths built from components the fixtures' patterns refer to
+fn fixture_path() -> impl Strategy<Value = String> {
+    let component = prop::sample::select(vec![
+        "apps", "build", "docs", "github", "logs", "scripts", "src", "tests", "vendor", "keep",
+        "a", "Docs",
+    ]);
+    let file = prop::sample::select(vec![
+        "README.md",
+        "NOTES.MD",
+        "app.js",
+        "main.go",
+        "mod.rs",
+        "lib.rs",
+        "notes.txt",
+        "nested.txt",
+        "today.log",
+        "Cargo.lock",
+        "main.c",
+    ]);
+    (prop::collection::vec(component, 0..4), file).prop_map(|(dirs, file)| {
+        let mut parts = dirs;
+        parts.push(file);
+        parts.join("/")
+    })
+}
+
+/// Codeowners contents of the fixtures without known divergences
+fn parity_corpus() -> Vec<String> {
+    fixtures()
+        .into_iter()
+        .filter(|(_, fixture)| fixture.divergences.is_empty())
+        .map(|(_, fixture)| fixture.codeowners)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn trailing_catch_all_rule_wins(index in any::<prop::sample::Index>(), path in fixture_path()) {
+        let corpus = parity_corpus();
+        let codeowners: &String = index.get(&corpus);
+        let codeowners = format!("{}\n* @last\n", codeowners);
+        let entries = parse(&codeowners);
+        let matcher = Matcher::build(&entries, MatcherStrategy::Override);
+        prop_assert_eq!(resolve(&entries, &matcher, &path), vec!["@last".to_string()]);
+    }
+
+    #[test]
+    fn leading_catch_all_rule_is_a_fallback(index in any::<prop::sample::Index>(), path in fixture_path()) {
+        let corpus = parity_corpus();
+        let codeowners: &String = index.get(&corpus);
+        let entries = parse(codeowners);
+        let matcher = Matcher::build(&entries, MatcherStrategy::Override);
+        let without = resolve(&entries, &matcher, &path);
+        // Any matching rule overrides the fallback, including one without owners
+        let matched = !find_applied_entries(
+            &Path::new(REPO).join(&path),
+            &entries,
+            &matcher,
+            PrecedencePolicy::Closest,
+        )
+        .unwrap()
+        .is_empty();
+
+        let with_entries = parse(&format!("* @fallback\n{}", codeowners));
+        let with_matcher = Matcher::build(&with_entries, MatcherStrategy::Override);
+        let with = resolve(&with_entries, &with_matcher, &path);
+
+        if matched {
+            prop_assert_eq!(with, without);
+        } else {
+            prop_assert_eq!(with, vec!["@fallback".to_string()]);
+        }
+    }
+
+    #[test]
+    fn matcher_strategies_agree(index in any::<prop::sample::Index>(), path in fixture_path()) {
+        let corpus: Vec<String> = fixtures().into_iter().map(|(_, fixture)| fixture.codeowners).collect();
+        let codeowners: &String = index.get(&corpus);
+        let entries = parse(codeowners);
+        let results: Vec<_> = MatcherStrategy::CONCRETE
+            .iter()
+            .map(|&strategy| resolve(&entries, &Matcher::build(&entries, strategy), &path))
+            .collect();
+        prop_assert!(
+            results.windows(2).all(|pair| pair[0] == pair[1]),
+            "strategies disagree on {}: {:?}",
+            path,
+            results
+        );
+    }
+}
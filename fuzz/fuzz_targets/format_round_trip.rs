@@ -0,0 +1,19 @@
+#![no_main]
+
+use std::path::Path;
+
+use codeinput::core::parser::{format_line, parse_line};
+use libfuzzer_sys::fuzz_target;
+
+// A formatted entry must parse back to the same pattern, owners and tags
+fuzz_target!(|line: &str| {
+    let source_path = Path::new("CODEOWNERS");
+    if let Ok(Some(entry)) = parse_line(line, 0, source_path) {
+        let reparsed = parse_line(&format_line(&entry), 0, source_path)
+            .expect("formatted entry parses")
+            .expect("formatted entry is a rule");
+        assert_eq!(reparsed.pattern, entry.pattern);
+        assert_eq!(reparsed.owners, entry.owners);
+        assert_eq!(reparsed.tags, entry.tags);
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::path::Path;
+
+use codeinput::core::parser::parse_content;
+use libfuzzer_sys::fuzz_target;
+
+// Any input, including invalid UTF-8 and very long lines, must parse without panicking
+fuzz_target!(|data: &[u8]| {
+    let content = String::from_utf8_lossy(data);
+    let _ = parse_content(&content, Path::new("CODEOWNERS"));
+});